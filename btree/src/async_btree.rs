@@ -0,0 +1,203 @@
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use common::error::DbError;
+use row::{Col, Row, RowType};
+use tokio::task;
+
+use crate::{BTree, Durability, Report, Stats};
+
+#[derive(Clone)]
+pub struct AsyncBTree {
+    inner: Arc<RwLock<BTree>>,
+}
+
+impl AsyncBTree {
+    pub async fn new(path: &Path) -> Result<Self, DbError> {
+        let path = PathBuf::from(path);
+        let btree = blocking(move || BTree::new(&path)).await?;
+        Ok(Self::from(btree))
+    }
+
+    pub async fn with_page_size(path: &Path, page_size: usize) -> Result<Self, DbError> {
+        let path = PathBuf::from(path);
+        let btree = blocking(move || BTree::with_page_size(&path, page_size)).await?;
+        Ok(Self::from(btree))
+    }
+
+    pub fn new_in_memory() -> Result<Self, DbError> {
+        Ok(Self::from(BTree::new_in_memory()?))
+    }
+
+    pub async fn set_durability(&self, durability: Durability) -> Result<(), DbError> {
+        self.write(move |btree| {
+            btree.set_durability(durability);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn sync(&self) -> Result<(), DbError> {
+        self.write(|btree| btree.sync()).await
+    }
+
+    pub async fn set_structure(&self, row_type: RowType) -> Result<(), DbError> {
+        self.write(move |btree| btree.set_structure(row_type)).await
+    }
+
+    pub async fn get_structure(&self) -> Result<RowType, DbError> {
+        self.read(|btree| btree.get_structure()).await
+    }
+
+    pub async fn compact(&self) -> Result<(), DbError> {
+        self.write(|btree| btree.compact()).await
+    }
+
+    pub async fn stats(&self) -> Result<Stats, DbError> {
+        self.read(|btree| btree.stats()).await
+    }
+
+    pub async fn verify(&self) -> Result<Report, DbError> {
+        self.read(|btree| btree.verify()).await
+    }
+
+    pub async fn insert(&self, key: Col, value: Row) -> Result<(), DbError> {
+        self.write(move |btree| btree.insert(key, value)).await
+    }
+
+    pub async fn insert_many(&self, entries: Vec<(Col, Row)>) -> Result<usize, DbError> {
+        self.write(move |btree| btree.insert_many(entries)).await
+    }
+
+    pub async fn bulk_load(&self, entries: Vec<(Col, Row)>) -> Result<usize, DbError> {
+        self.write(move |btree| btree.bulk_load(entries)).await
+    }
+
+    pub async fn search(&self, key: Col) -> Result<Option<Row>, DbError> {
+        self.read(move |btree| btree.search(key)).await
+    }
+
+    pub async fn scan(&self, from: Bound<Col>, to: Bound<Col>) -> Result<Vec<(Col, Row)>, DbError> {
+        self.read(move |btree| btree.scan(from, to)?.collect())
+            .await
+    }
+
+    pub async fn scan_rev(
+        &self,
+        from: Bound<Col>,
+        to: Bound<Col>,
+    ) -> Result<Vec<(Col, Row)>, DbError> {
+        self.read(move |btree| btree.scan_rev(from, to)?.collect())
+            .await
+    }
+
+    pub async fn select_all(&self) -> Result<Vec<Row>, DbError> {
+        self.read(|btree| btree.select_all()).await
+    }
+
+    pub async fn delete(&self, key: Col) -> Result<Option<Row>, DbError> {
+        self.write(move |btree| btree.delete(key)).await
+    }
+
+    pub async fn delete_all(&self) -> Result<i32, DbError> {
+        self.write(|btree| btree.delete_all()).await
+    }
+
+    async fn read<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&BTree) -> Result<T, DbError> + Send + 'static,
+    ) -> Result<T, DbError> {
+        let inner = self.inner.clone();
+        blocking(move || operation(&*read(&inner)?)).await
+    }
+
+    async fn write<T: Send + 'static>(
+        &self,
+        operation: impl FnOnce(&mut BTree) -> Result<T, DbError> + Send + 'static,
+    ) -> Result<T, DbError> {
+        let inner = self.inner.clone();
+        blocking(move || operation(&mut *write(&inner)?)).await
+    }
+}
+
+impl From<BTree> for AsyncBTree {
+    fn from(btree: BTree) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(btree)),
+        }
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> Result<T, DbError> + Send + 'static,
+) -> Result<T, DbError> {
+    task::spawn_blocking(operation)
+        .await
+        .map_err(|err| DbError::Unexpected(err.to_string()))?
+}
+
+fn read(inner: &RwLock<BTree>) -> Result<RwLockReadGuard<'_, BTree>, DbError> {
+    inner
+        .read()
+        .map_err(|_| DbError::unexpected("btree lock is poisoned"))
+}
+
+fn write(inner: &RwLock<BTree>) -> Result<RwLockWriteGuard<'_, BTree>, DbError> {
+    inner
+        .write()
+        .map_err(|_| DbError::unexpected("btree lock is poisoned"))
+}
+
+#[cfg(test)]
+mod tests {
+    use row::row;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_search() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let btree = AsyncBTree::new(tempfile.path()).await.unwrap();
+        btree.set_durability(Durability::OnCommit).await.unwrap();
+        let entries: Vec<_> = (0..500).map(|i| (Col::int(i), row![Col::int(i)])).collect();
+        assert_eq!(500, btree.insert_many(entries).await.unwrap());
+        assert_eq!(
+            Some(row![Col::int(42)]),
+            btree.search(Col::int(42)).await.unwrap()
+        );
+        let range = btree
+            .scan(Bound::Included(Col::int(10)), Bound::Excluded(Col::int(20)))
+            .await
+            .unwrap();
+        assert_eq!(10, range.len());
+        assert_eq!(
+            Some(row![Col::int(0)]),
+            btree.delete(Col::int(0)).await.unwrap()
+        );
+        btree.sync().await.unwrap();
+        drop(btree);
+
+        let btree = AsyncBTree::new(tempfile.path()).await.unwrap();
+        assert_eq!(499, btree.select_all().await.unwrap().len());
+        assert!(btree.verify().await.unwrap().is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_readers() {
+        let btree = AsyncBTree::new_in_memory().unwrap();
+        for i in 0..100 {
+            btree.insert(Col::int(i), row![Col::int(i)]).await.unwrap();
+        }
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let btree = btree.clone();
+                tokio::spawn(async move { btree.select_all().await.unwrap().len() })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(100, reader.await.unwrap());
+        }
+    }
+}
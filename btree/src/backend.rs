@@ -0,0 +1,202 @@
+use std::{
+    fs::File,
+    io::{Error, ErrorKind},
+    os::{fd::AsRawFd, unix::fs::FileExt},
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use common::error::DbError;
+
+use crate::segments::Segments;
+#[cfg(feature = "uring")]
+use crate::uring::Uring;
+
+pub(crate) enum Backend {
+    File(File),
+    Memory(Arc<RwLock<Vec<u8>>>),
+    Segments(Segments),
+    #[cfg(feature = "uring")]
+    Uring(Uring),
+}
+
+impl Backend {
+    #[cfg(not(feature = "uring"))]
+    pub(crate) fn file(fd: File) -> Self {
+        Self::File(fd)
+    }
+
+    #[cfg(feature = "uring")]
+    pub(crate) fn file(fd: File) -> Self {
+        match Uring::new(fd) {
+            Ok(uring) => Self::Uring(uring),
+            Err((fd, _)) => Self::File(fd),
+        }
+    }
+
+    pub(crate) fn memory() -> Self {
+        Self::Memory(Arc::new(RwLock::new(Vec::new())))
+    }
+
+    pub(crate) fn segment_size(&self) -> Option<u64> {
+        match self {
+            Self::Segments(segments) => Some(segments.segment_size()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn lock(&self, path: &Path, shared: bool) -> Result<(), DbError> {
+        let first;
+        let fd = match self {
+            Self::File(fd) => fd,
+            Self::Segments(segments) => {
+                first = segments.first()?;
+                &first
+            }
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.file(),
+            Self::Memory(_) => return Ok(()),
+        };
+        let operation = if shared { libc::LOCK_SH } else { libc::LOCK_EX };
+        if unsafe { libc::flock(fd.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+            return Ok(());
+        }
+        let err = Error::last_os_error();
+        if err.kind() == ErrorKind::WouldBlock {
+            return Err(DbError::Locked(path.display().to_string()));
+        }
+        Err(err.into())
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self, DbError> {
+        match self {
+            Self::File(fd) => Ok(Self::File(fd.try_clone()?)),
+            Self::Memory(data) => Ok(Self::Memory(data.clone())),
+            Self::Segments(segments) => Ok(Self::Segments(segments.try_clone()?)),
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => Ok(Self::Uring(uring.try_clone()?)),
+        }
+    }
+
+    pub(crate) fn len(&self) -> Result<u64, DbError> {
+        match self {
+            Self::File(fd) => Ok(fd.metadata()?.len()),
+            Self::Memory(data) => Ok(read(data).len() as u64),
+            Self::Segments(segments) => segments.len(),
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => Ok(uring.file().metadata()?.len()),
+        }
+    }
+
+    pub(crate) fn set_len(&self, len: u64) -> Result<(), DbError> {
+        match self {
+            Self::File(fd) => fd.set_len(len)?,
+            Self::Memory(data) => write(data).resize(len as usize, 0),
+            Self::Segments(segments) => segments.set_len(len)?,
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.file().set_len(len)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn grow(&self, len: u64, fallocate: bool) -> Result<(), DbError> {
+        let fd = match self {
+            Self::File(fd) => fd,
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.file(),
+            Self::Segments(segments) => {
+                return segments.resize(len, |fd, len| grow_file(fd, len, fallocate));
+            }
+            Self::Memory(_) => return self.set_len(len),
+        };
+        grow_file(fd, len, fallocate)
+    }
+
+    pub(crate) fn read_exact_at(&self, buffer: &mut [u8], offset: u64) -> Result<(), DbError> {
+        match self {
+            Self::File(fd) => fd.read_exact_at(buffer, offset)?,
+            Self::Memory(data) => {
+                let data = read(data);
+                let start = offset as usize;
+                let Some(bytes) = data.get(start..start + buffer.len()) else {
+                    return Err(Error::from(ErrorKind::UnexpectedEof).into());
+                };
+                buffer.copy_from_slice(bytes);
+            }
+            Self::Segments(segments) => segments.read_exact_at(buffer, offset)?,
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.read_exact_at(buffer, offset)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_all_at(&self, buffer: &[u8], offset: u64) -> Result<(), DbError> {
+        match self {
+            Self::File(fd) => fd.write_all_at(buffer, offset)?,
+            Self::Memory(data) => {
+                let mut data = write(data);
+                let start = offset as usize;
+                if data.len() < start + buffer.len() {
+                    data.resize(start + buffer.len(), 0);
+                }
+                data[start..start + buffer.len()].copy_from_slice(buffer);
+            }
+            Self::Segments(segments) => segments.write_all_at(buffer, offset)?,
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.file().write_all_at(buffer, offset)?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_batch(&self, records: &[(u64, Vec<u8>)]) -> Result<(), DbError> {
+        #[cfg(feature = "uring")]
+        if let Self::Uring(uring) = self {
+            return uring.write_batch(records);
+        }
+        for (offset, data) in records {
+            self.write_all_at(data, *offset)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sync_data(&self) -> Result<(), DbError> {
+        match self {
+            Self::File(fd) => fd.sync_data()?,
+            Self::Segments(segments) => segments.sync_data()?,
+            #[cfg(feature = "uring")]
+            Self::Uring(uring) => uring.file().sync_data()?,
+            Self::Memory(_) => {}
+        }
+        Ok(())
+    }
+}
+
+fn grow_file(fd: &File, len: u64, fallocate: bool) -> Result<(), DbError> {
+    if !fallocate {
+        fd.set_len(len)?;
+        return Ok(());
+    }
+    let current = fd.metadata()?.len();
+    if len <= current {
+        return Ok(());
+    }
+    let result = unsafe {
+        libc::posix_fallocate(
+            fd.as_raw_fd(),
+            current as libc::off_t,
+            (len - current) as libc::off_t,
+        )
+    };
+    if result != 0 {
+        return Err(Error::from_raw_os_error(result).into());
+    }
+    Ok(())
+}
+
+fn read(data: &RwLock<Vec<u8>>) -> std::sync::RwLockReadGuard<'_, Vec<u8>> {
+    data.read().unwrap_or_else(|err| err.into_inner())
+}
+
+fn write(data: &RwLock<Vec<u8>>) -> std::sync::RwLockWriteGuard<'_, Vec<u8>> {
+    data.write().unwrap_or_else(|err| err.into_inner())
+}
@@ -0,0 +1,12 @@
+use std::{env, io, path::PathBuf, process::exit};
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: btree-dump <table file>");
+        exit(2);
+    };
+    if let Err(err) = btree::dump(&PathBuf::from(path), &mut io::stdout().lock()) {
+        eprintln!("ERR: {}", err);
+        exit(1);
+    }
+}
@@ -1,51 +1,467 @@
-use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::Write;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use common::Pageable;
 use common::error::DbError;
 use row::{Col, Row, RowType};
 
 use crate::page::{
-    MAX_KEY_VALUE_SIZE, PAGE_SIZE, get_index, insert_key_value, split_leaf, split_node,
+    PAGE_SIZE, PTR_SIZE, balance, cell_size, get_index, insert_key_value, max_key_value_size,
+    min_page_size, pack, split_leaf, split_node, split_tail,
 };
 
-use crate::pager::HEADER_SIZE;
+use crate::dump;
+use crate::io::IoCounters;
+use crate::key::{Key, Value};
+use crate::scan::{Keys, Scan};
+use crate::snapshot::Snapshot;
+use crate::stats::Stats;
+use crate::verify::{self, Report};
 use crate::{
     page::{Offset, Page},
-    pager::Pager,
+    pager::{Durability, Growth, Pager},
 };
 
-pub struct BTree {
-    pager: Pager,
+pub struct BTree<K: Key = Col, V: Value = Row> {
+    path: Option<PathBuf>,
+    pager: Pager<K, V>,
+    tail: Option<Offset>,
 }
 
-impl BTree {
+impl<K: Key, V: Value> BTree<K, V> {
     pub fn new(path: &Path) -> Result<Self, DbError> {
-        let mut pager = Pager::new(path)?;
+        Self::with_page_size(path, PAGE_SIZE)
+    }
+
+    pub fn with_page_size(path: &Path, page_size: usize) -> Result<Self, DbError> {
+        let pager = Pager::with_page_size(path, page_size)?;
+        Self::open(Some(PathBuf::from(path)), pager)
+    }
+
+    pub fn segmented(path: &Path, segment_size: u64) -> Result<Self, DbError> {
+        let pager = Pager::segmented(path, PAGE_SIZE, segment_size)?;
+        Self::open(Some(PathBuf::from(path)), pager)
+    }
+
+    pub fn read_only(path: &Path) -> Result<Self, DbError> {
+        let pager = Pager::read_only(path)?;
+        if pager.get_root()? == 0 {
+            return Err(DbError::InvalidInput(format!(
+                "'{}' has no root page",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            path: Some(PathBuf::from(path)),
+            pager,
+            tail: None,
+        })
+    }
+
+    pub fn new_in_memory() -> Result<Self, DbError> {
+        Self::in_memory_with_page_size(PAGE_SIZE)
+    }
+
+    pub fn in_memory_with_page_size(page_size: usize) -> Result<Self, DbError> {
+        Self::open(None, Pager::in_memory(page_size)?)
+    }
+
+    fn open(path: Option<PathBuf>, mut pager: Pager<K, V>) -> Result<Self, DbError> {
         let mut root_offset = pager.get_root()?;
         if root_offset == 0 {
             let page = Page::Leaf {
                 parent: 0,
+                prev: 0,
+                next: 0,
                 values: vec![],
             };
+            pager.begin();
             root_offset = pager.write_page(page)?;
             pager.set_root(root_offset)?;
+            pager.set_stats(&Stats::root_leaf(pager.page_size()))?;
+            pager.commit()?;
         }
-        Ok(Self { pager })
+        Ok(Self {
+            path,
+            pager,
+            tail: None,
+        })
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.pager.set_durability(durability);
+    }
+
+    pub fn set_growth(&mut self, growth: Growth) {
+        self.pager.set_growth(growth);
+    }
+
+    pub fn set_io(&mut self, io: Arc<IoCounters>) {
+        self.pager.set_io(io);
+    }
+
+    pub fn sync(&mut self) -> Result<(), DbError> {
+        self.pager.sync()
     }
 
     pub fn set_structure(&mut self, row_type: RowType) -> Result<(), DbError> {
-        self.pager.set_structure(row_type)?;
-        Ok(())
+        self.atomic(|btree| btree.pager.set_structure(row_type))
     }
 
-    pub fn get_structure(&mut self) -> Result<RowType, DbError> {
+    pub fn get_structure(&self) -> Result<RowType, DbError> {
         self.pager.get_structure()
     }
 
-    pub fn insert(&mut self, key: Col, value: Row) -> Result<(), DbError> {
-        let mut offset = self.pager.get_root()?;
-        let mut page = self.pager.get_page(offset)?;
-        let mut new_key_offset = None::<(Col, Offset)>;
+    pub fn compact(&mut self) -> Result<(), DbError> {
+        self.pager.check_writable()?;
+        self.pager.sync()?;
+        let structure = self.pager.get_structure()?;
+        let entries = self
+            .scan(Bound::Unbounded, Bound::Unbounded)?
+            .collect::<Result<Vec<_>, DbError>>()?;
+        let Some(path) = self.path.clone() else {
+            let mut compacted = Self::in_memory_with_page_size(self.pager.page_size())?;
+            compacted.set_durability(self.pager.durability());
+            compacted.set_growth(self.pager.growth());
+            compacted.set_io(self.pager.io());
+            compacted.set_structure(structure)?;
+            compacted.bulk_load(entries)?;
+            self.pager = compacted.pager;
+            return Ok(());
+        };
+        let mut compact_path = path.as_os_str().to_owned();
+        compact_path.push("-compact");
+        let compact_path = PathBuf::from(compact_path);
+        let segment_size = self.pager.segment_size();
+        match segment_size {
+            Some(_) => fs::remove_dir_all(&compact_path),
+            None => fs::remove_file(&compact_path),
+        }
+        .or_else(|err| match err.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(err),
+        })?;
+        {
+            let pager = match segment_size {
+                Some(segment_size) => {
+                    Pager::segmented(&compact_path, self.pager.page_size(), segment_size)?
+                }
+                None => Pager::with_page_size(&compact_path, self.pager.page_size())?,
+            };
+            let mut compacted = Self::open(Some(compact_path.clone()), pager)?;
+            compacted.set_durability(Durability::OnCommit);
+            compacted.set_growth(self.pager.growth());
+            compacted.set_io(self.pager.io());
+            compacted.set_structure(structure)?;
+            compacted.bulk_load(entries)?;
+            compacted.sync()?;
+        }
+        match segment_size {
+            Some(_) => {
+                let mut old_path = path.as_os_str().to_owned();
+                old_path.push("-old");
+                fs::rename(&path, &old_path)?;
+                fs::rename(&compact_path, &path)?;
+                fs::remove_dir_all(&old_path)?;
+            }
+            None => fs::rename(&compact_path, &path)?,
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+        let (durability, growth, io) = (
+            self.pager.durability(),
+            self.pager.growth(),
+            self.pager.io(),
+        );
+        self.pager = Pager::new(&path)?;
+        self.pager.set_durability(durability);
+        self.pager.set_growth(growth);
+        self.pager.set_io(io);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot<K, V>, DbError> {
+        Ok(Snapshot::new(self.pager.snapshot()?))
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.pager.page_size()
+    }
+
+    pub fn allocated_size(&self) -> u64 {
+        self.pager.allocated_pages() as u64 * self.pager.page_size() as u64
+    }
+
+    pub fn stats(&self) -> Result<Stats, DbError> {
+        self.pager.get_stats()
+    }
+
+    pub fn verify(&self) -> Result<Report, DbError> {
+        verify::verify(&self.pager)
+    }
+
+    pub fn recount(&mut self) -> Result<u64, DbError> {
+        let entries = self
+            .keys()?
+            .try_fold(0u64, |entries, key| key.map(|_| entries + 1))?;
+        self.atomic(|btree| btree.update_stats(|stats| stats.entries = entries))?;
+        Ok(entries)
+    }
+
+    fn update_stats(&mut self, update: impl FnOnce(&mut Stats)) -> Result<(), DbError> {
+        let mut stats = self.pager.get_stats()?;
+        update(&mut stats);
+        self.pager.set_stats(&stats)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<(), DbError> {
+        self.atomic(|btree| btree.insert_entry(key, value))
+    }
+
+    pub fn insert_many(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<usize, DbError> {
+        self.atomic(|btree| btree.insert_entries(entries))
+    }
+
+    fn insert_entries(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<usize, DbError> {
+        let capacity = self.pager.capacity();
+        let mut queue: VecDeque<(K, V)> = VecDeque::new();
+        for (key, value) in entries {
+            let kv_size = key.size() + value.size();
+            if kv_size > max_key_value_size(capacity) {
+                return Err(DbError::MaxSize(kv_size, max_key_value_size(capacity)));
+            }
+            if let Some((last, _)) = queue.back()
+                && *last >= key
+            {
+                return Err(DbError::invalid_input(
+                    "insert keys must be sorted and unique",
+                ));
+            }
+            queue.push_back((key, value));
+        }
+        let count = queue.len();
+        while let Some((key, _)) = queue.front() {
+            let mut offset = self.pager.get_root()?;
+            let mut upper = None;
+            let (parent, prev, next, mut values) = loop {
+                match self.pager.get_page(offset)? {
+                    Page::Node { children, .. } => {
+                        let idx = get_index(&children, key);
+                        if let Some((separator, _)) = children.get(idx + 1) {
+                            upper = Some(separator.clone());
+                        }
+                        offset = children[idx].1;
+                    }
+                    Page::Leaf {
+                        parent,
+                        prev,
+                        next,
+                        values,
+                    } => break (parent, prev, next, values),
+                }
+            };
+            let mut leaf_size = Page::leaf_size(&values);
+            let (mut inserted, mut entries, mut size) = (false, 0u64, 0i64);
+            let mut overflow = None;
+            while let Some((key, value)) = queue.pop_front() {
+                if upper.as_ref().is_some_and(|upper| key >= *upper) {
+                    queue.push_front((key, value));
+                    break;
+                }
+                let kv_size = key.size() + value.size();
+                let replaced = match values.binary_search_by(|kv| kv.0.cmp(&key)) {
+                    Ok(idx) => Some(values[idx].0.size() + values[idx].1.size()),
+                    Err(_) => None,
+                };
+                let (added, removed) = match replaced {
+                    Some(replaced) => (kv_size, replaced),
+                    None => (cell_size(&key, &value), 0),
+                };
+                if leaf_size + added - removed > capacity {
+                    overflow = Some((key, value));
+                    break;
+                }
+                leaf_size = leaf_size + added - removed;
+                inserted = true;
+                entries += replaced.is_none() as u64;
+                size += kv_size as i64 - replaced.unwrap_or(0) as i64;
+                insert_key_value(&mut values, (key, value));
+            }
+            if inserted {
+                let page = Page::Leaf {
+                    parent,
+                    prev,
+                    next,
+                    values,
+                };
+                self.pager.write_page_at_offset(page, offset)?;
+                self.update_stats(|stats| {
+                    stats.entries += entries;
+                    stats.data_size = (stats.data_size as i64 + size).max(0) as u64;
+                })?;
+            }
+            if let Some((key, value)) = overflow {
+                self.insert_entry(key, value)?;
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn bulk_load(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<usize, DbError> {
+        self.atomic(|btree| btree.load_entries(entries))
+    }
+
+    fn load_entries(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<usize, DbError> {
+        let capacity = self.pager.capacity();
+        let mut values: Vec<(K, V)> = Vec::new();
+        for (key, value) in entries {
+            let kv_size = key.size() + value.size();
+            if kv_size > max_key_value_size(capacity) {
+                return Err(DbError::MaxSize(kv_size, max_key_value_size(capacity)));
+            }
+            if let Some((last, _)) = values.last()
+                && *last >= key
+            {
+                return Err(DbError::invalid_input(
+                    "bulk load keys must be sorted and unique",
+                ));
+            }
+            values.push((key, value));
+        }
+        let count = values.len();
+        let data_size: usize = values.iter().map(|(k, v)| k.size() + v.size()).sum();
+        let root = self.pager.get_root()?;
+        let empty =
+            matches!(self.pager.get_page(root)?, Page::Leaf { values, .. } if values.is_empty());
+        if !empty {
+            for (key, value) in values {
+                self.insert_entry(key, value)?;
+            }
+            return Ok(count);
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let leaves = pack(
+            values,
+            Page::<K, V>::leaf_size(&vec![]),
+            capacity,
+            |(k, v)| cell_size(k, v),
+        );
+        let mut offsets = vec![root];
+        for _ in 1..leaves.len() {
+            offsets.push(self.pager.allocate()?);
+        }
+        let mut level = Vec::with_capacity(leaves.len());
+        for (idx, leaf) in leaves.iter().enumerate() {
+            let key = match idx {
+                0 => leaf[0].0.clone(),
+                _ => K::separator(&leaves[idx - 1][leaves[idx - 1].len() - 1].0, &leaf[0].0),
+            };
+            level.push((key, offsets[idx]));
+        }
+
+        let mut parents = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut depth = 1;
+        while level.len() > 1 {
+            depth += 1;
+            let groups = pack(
+                level,
+                Page::<K, V>::node_size(&vec![]),
+                capacity,
+                |(k, _)| k.compact_size() + PTR_SIZE,
+            );
+            level = Vec::with_capacity(groups.len());
+            for children in groups {
+                let offset = self.pager.allocate()?;
+                for (_, child) in children.iter() {
+                    parents.insert(*child, offset);
+                }
+                level.push((children[0].0.clone(), offset));
+                nodes.push((offset, children));
+            }
+        }
+        self.pager.set_root(level[0].1)?;
+        let (leaf_pages, node_pages) = (leaves.len() as u32, nodes.len() as u32);
+        self.update_stats(|stats| {
+            stats.entries = count as u64;
+            stats.depth = depth;
+            stats.leaf_pages = leaf_pages;
+            stats.node_pages = node_pages;
+            stats.data_size = data_size as u64;
+        })?;
+
+        for (idx, values) in leaves.into_iter().enumerate() {
+            let offset = offsets[idx];
+            let page = Page::Leaf {
+                parent: parents.get(&offset).copied().unwrap_or(0),
+                prev: if idx == 0 { 0 } else { offsets[idx - 1] },
+                next: offsets.get(idx + 1).copied().unwrap_or(0),
+                values,
+            };
+            self.pager.write_page_at_offset(page, offset)?;
+        }
+        for (offset, children) in nodes {
+            let page = Page::Node {
+                parent: parents.get(&offset).copied().unwrap_or(0),
+                children,
+            };
+            self.pager.write_page_at_offset(page, offset)?;
+        }
+        Ok(count)
+    }
+
+    fn tail_leaf(&self, key: &K) -> Result<Option<Page<K, V>>, DbError> {
+        let Some(offset) = self
+            .tail
+            .filter(|offset| self.pager.is_page_offset(*offset))
+        else {
+            return Ok(None);
+        };
+        let page = match self.pager.get_page(offset) {
+            Ok(page) => page,
+            Err(DbError::Encoding | DbError::Checksum) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let Page::Leaf {
+            next: 0, values, ..
+        } = &page
+        else {
+            return Ok(None);
+        };
+        let appended = values.last().is_some_and(|(last, _)| last < key);
+        Ok(appended.then_some(page))
+    }
+
+    fn insert_entry(&mut self, key: K, value: V) -> Result<(), DbError> {
+        let capacity = self.pager.capacity();
+        let (mut offset, mut page) = match (self.tail, self.tail_leaf(&key)?) {
+            (Some(offset), Some(page)) => (offset, page),
+            _ => {
+                let root = self.pager.get_root()?;
+                (root, self.pager.get_page(root)?)
+            }
+        };
+        let mut split = None::<((K, Offset), (K, Offset))>;
+        let mut tail_split = false;
 
         loop {
             match page {
@@ -53,19 +469,28 @@ impl BTree {
                     parent,
                     mut children,
                 } => {
-                    if let Some((key, child_offset)) = new_key_offset.take() {
-                        insert_key_value(&mut children, (key, child_offset));
-                        if Page::node_size(&children) <= PAGE_SIZE {
+                    if let Some((left, right)) = split.take() {
+                        if let Some(child) = children.iter_mut().find(|(_, o)| *o == left.1) {
+                            child.0 = left.0;
+                        }
+                        insert_key_value(&mut children, right);
+                        if Page::<K, V>::node_size(&children) <= capacity {
                             let page = Page::Node { parent, children };
                             self.pager.write_page_at_offset(page, offset)?;
                             break;
                         }
-                        let (children, right_children) = split_node(children);
+                        let header_size = Page::<K, V>::node_size(&vec![]);
+                        let entry_size = |(k, _): &(K, Offset)| k.compact_size() + PTR_SIZE;
+                        let (children, right_children) = match tail_split {
+                            true => split_tail(children, header_size, capacity, entry_size)
+                                .unwrap_or_else(|children| split_node(children, capacity)),
+                            false => split_node(children, capacity),
+                        };
                         let left_key = children[0].0.clone();
                         let right_key = right_children[0].0.clone();
                         if parent == 0 {
-                            let parent = self.pager.get_offset();
-                            let right_offset = self.pager.get_next_offset();
+                            let parent = self.pager.allocate()?;
+                            let right_offset = self.pager.allocate()?;
                             let left = Page::Node { parent, children };
                             self.rewrite_parent(right_offset, &right_children)?;
                             let right = Page::Node {
@@ -74,23 +499,30 @@ impl BTree {
                             };
                             let page = Page::Node {
                                 parent: 0,
-                                children: vec![(left_key, child_offset), (right_key, right_offset)],
+                                children: vec![(left_key, offset), (right_key, right_offset)],
                             };
                             self.pager.set_root(parent)?;
                             self.pager.write_page_at_offset(left, offset)?;
-                            self.pager.write_page(page)?;
-                            self.pager.write_page(right)?;
+                            self.pager.write_page_at_offset(page, parent)?;
+                            self.pager.write_page_at_offset(right, right_offset)?;
+                            self.update_stats(|stats| {
+                                stats.node_pages += 2;
+                                stats.depth += 1;
+                            })?;
                             break;
                         }
-                        offset = parent;
-                        page = self.pager.get_page(parent)?;
+                        self.update_stats(|stats| stats.node_pages += 1)?;
+                        let left = Page::Node { parent, children };
+                        self.pager.write_page_at_offset(left, offset)?;
                         let right = Page::Node {
                             parent,
                             children: right_children.clone(),
                         };
                         let right_offset = self.pager.write_page(right)?;
                         self.rewrite_parent(right_offset, &right_children)?;
-                        new_key_offset = Some((right_key, right_offset));
+                        split = Some(((left_key, offset), (right_key, right_offset)));
+                        offset = parent;
+                        page = self.pager.get_page(parent)?;
                     } else {
                         let idx = get_index(&children, &key);
                         let (_, child_offset) = children[idx];
@@ -98,27 +530,84 @@ impl BTree {
                         offset = child_offset;
                     }
                 }
-                Page::Leaf { parent, mut values } => {
+                Page::Leaf {
+                    parent,
+                    prev,
+                    next,
+                    mut values,
+                } => {
                     let kv_size = key.size() + value.size();
-                    if kv_size > MAX_KEY_VALUE_SIZE {
-                        return Err(DbError::MaxSize(kv_size, MAX_KEY_VALUE_SIZE));
+                    if kv_size > max_key_value_size(capacity) {
+                        return Err(DbError::MaxSize(kv_size, max_key_value_size(capacity)));
+                    }
+                    if next == 0 {
+                        self.tail = Some(offset);
+                    }
+                    let position = values.binary_search_by(|kv| kv.0.cmp(&key));
+                    let replaced = position
+                        .ok()
+                        .map(|idx| values[idx].0.size() + values[idx].1.size());
+                    self.update_stats(|stats| {
+                        if replaced.is_none() {
+                            stats.entries += 1;
+                        }
+                        stats.data_size = (stats.data_size + kv_size as u64)
+                            .saturating_sub(replaced.unwrap_or(0) as u64);
+                    })?;
+                    let in_place = match position {
+                        Ok(idx) => self
+                            .pager
+                            .update_leaf(offset, |leaf| leaf.update::<K, V>(idx, &value))?,
+                        Err(idx)
+                            if Page::leaf_size(&values) + cell_size(&key, &value) <= capacity =>
+                        {
+                            self.pager
+                                .update_leaf(offset, |leaf| leaf.insert(idx, &key, &value))?
+                        }
+                        Err(_) => false,
+                    };
+                    if in_place {
+                        break;
                     }
                     let key_value = (key.clone(), value.clone());
                     insert_key_value(&mut values, key_value);
-                    if Page::leaf_size(&values) <= PAGE_SIZE {
-                        let page = Page::Leaf { parent, values };
+                    if Page::leaf_size(&values) <= capacity {
+                        let page = Page::Leaf {
+                            parent,
+                            prev,
+                            next,
+                            values,
+                        };
                         self.pager.write_page_at_offset(page, offset)?;
                         break;
                     }
-                    let (values, right_values) = split_leaf(values);
+                    tail_split = next == 0 && position == Err(values.len() - 1);
+                    let (values, right_values) = match tail_split {
+                        true => {
+                            let header_size = Page::<K, V>::leaf_size(&vec![]);
+                            split_tail(values, header_size, capacity, |(k, v)| cell_size(k, v))
+                                .unwrap_or_else(|values| split_leaf(values, capacity))
+                        }
+                        false => split_leaf(values, capacity),
+                    };
+                    if next == 0 {
+                        self.tail = None;
+                    }
                     let left_key = values[0].0.clone();
-                    let right_key = right_values[0].0.clone();
+                    let right_key = K::separator(&values[values.len() - 1].0, &right_values[0].0);
                     if parent == 0 {
-                        let parent = self.pager.get_offset();
-                        let right_offset = self.pager.get_next_offset();
-                        let left = Page::Leaf { parent, values };
+                        let parent = self.pager.allocate()?;
+                        let right_offset = self.pager.allocate()?;
+                        let left = Page::Leaf {
+                            parent,
+                            prev,
+                            next: right_offset,
+                            values,
+                        };
                         let right = Page::Leaf {
                             parent,
+                            prev: offset,
+                            next,
                             values: right_values,
                         };
                         let page = Page::Node {
@@ -127,20 +616,35 @@ impl BTree {
                         };
                         self.pager.set_root(parent)?;
                         self.pager.write_page_at_offset(left, offset)?;
-                        self.pager.write_page(page)?;
-                        self.pager.write_page(right)?;
+                        self.pager.write_page_at_offset(page, parent)?;
+                        self.pager.write_page_at_offset(right, right_offset)?;
+                        self.update_stats(|stats| {
+                            stats.leaf_pages += 1;
+                            stats.node_pages += 1;
+                            stats.depth += 1;
+                        })?;
                         break;
                     } else {
-                        let left = Page::Leaf { parent, values };
+                        let right_offset = self.pager.allocate()?;
+                        let left = Page::Leaf {
+                            parent,
+                            prev,
+                            next: right_offset,
+                            values,
+                        };
                         self.pager.write_page_at_offset(left, offset)?;
-                        offset = parent;
-                        page = self.pager.get_page(parent)?;
                         let right = Page::Leaf {
                             parent,
+                            prev: offset,
+                            next,
                             values: right_values,
                         };
-                        let right_offset = self.pager.write_page(right)?;
-                        new_key_offset = Some((right_key, right_offset));
+                        self.pager.write_page_at_offset(right, right_offset)?;
+                        self.rewrite_prev(next, right_offset)?;
+                        self.update_stats(|stats| stats.leaf_pages += 1)?;
+                        split = Some(((left_key, offset), (right_key, right_offset)));
+                        offset = parent;
+                        page = self.pager.get_page(parent)?;
                     }
                 }
             }
@@ -148,7 +652,7 @@ impl BTree {
         Ok(())
     }
 
-    pub fn search(&mut self, key: Col) -> Result<Option<Row>, DbError> {
+    pub fn search(&self, key: K) -> Result<Option<V>, DbError> {
         let offset: Offset = self.pager.get_root()?;
         let mut page = self.pager.get_page(offset)?;
         loop {
@@ -168,42 +672,97 @@ impl BTree {
         }
     }
 
-    pub fn select_all(&mut self) -> Result<Vec<Row>, DbError> {
-        let mut offset = HEADER_SIZE as u32;
-        let latest_offset = self.pager.get_offset();
+    pub fn scan(&self, from: Bound<K>, to: Bound<K>) -> Result<Scan<'_, K, V>, DbError> {
+        Scan::new(&self.pager, from, to, false)
+    }
 
-        let mut rows = Vec::new();
-        while offset < latest_offset {
-            match self.pager.get_page(offset)? {
-                Page::Node { .. } => {}
-                Page::Leaf { values, .. } => {
-                    for (_, row) in values {
-                        rows.push(row);
-                    }
-                }
-            }
-            offset += PAGE_SIZE as u32;
-        }
-        Ok(rows)
+    pub fn scan_rev(&self, from: Bound<K>, to: Bound<K>) -> Result<Scan<'_, K, V>, DbError> {
+        Scan::new(&self.pager, from, to, true)
+    }
+
+    pub fn keys(&self) -> Result<Keys<'_, K, V>, DbError> {
+        self.keys_range(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub fn keys_range(&self, from: Bound<K>, to: Bound<K>) -> Result<Keys<'_, K, V>, DbError> {
+        Keys::new(&self.pager, from, to, false)
+    }
+
+    pub fn last(&self) -> Result<Option<(K, V)>, DbError> {
+        self.scan_rev(Bound::Unbounded, Bound::Unbounded)?
+            .next()
+            .transpose()
+    }
+
+    pub fn select_all(&self) -> Result<Vec<V>, DbError> {
+        self.scan(Bound::Unbounded, Bound::Unbounded)?
+            .map(|kv| kv.map(|(_, row)| row))
+            .collect()
     }
 
     pub fn delete_all(&mut self) -> Result<i32, DbError> {
-        let count = self.select_all()?;
-        self.pager.clear()?;
-        Ok(count.len() as i32)
+        let count = self
+            .keys()?
+            .try_fold(0, |count, key| key.map(|_| count + 1))?;
+        self.atomic(|btree| btree.pager.clear())?;
+        self.pager.truncate()?;
+        Ok(count)
+    }
+
+    pub fn delete(&mut self, key: K) -> Result<Option<V>, DbError> {
+        self.atomic(|btree| btree.delete_entry(key))
     }
 
-    pub fn delete(&mut self, key: Col) -> Result<Option<Row>, DbError> {
+    fn atomic<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        self.pager.begin();
+        match operation(self) {
+            Ok(result) => {
+                self.pager.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                self.pager.rollback();
+                Err(err)
+            }
+        }
+    }
+
+    fn delete_entry(&mut self, key: K) -> Result<Option<V>, DbError> {
         let mut offset = self.pager.get_root()?;
         let mut page = self.pager.get_page(offset)?;
         loop {
             match page {
-                Page::Leaf { parent, mut values } => {
+                Page::Leaf {
+                    parent,
+                    prev,
+                    next,
+                    mut values,
+                } => {
                     return match values.binary_search_by(|kv| kv.0.cmp(&key)) {
                         Ok(idx) => {
                             let value = values.remove(idx);
-                            let page = Page::Leaf { parent, values };
-                            self.pager.write_page_at_offset(page, offset)?;
+                            let kv_size = value.0.size() + value.1.size();
+                            self.update_stats(|stats| {
+                                stats.entries = stats.entries.saturating_sub(1);
+                                stats.data_size = stats.data_size.saturating_sub(kv_size as u64);
+                                stats.dead_size += kv_size as u64;
+                            })?;
+                            if !self
+                                .pager
+                                .update_leaf(offset, |leaf| leaf.remove(idx).map(|_| true))?
+                            {
+                                let page = Page::Leaf {
+                                    parent,
+                                    prev,
+                                    next,
+                                    values,
+                                };
+                                self.pager.write_page_at_offset(page, offset)?;
+                            }
+                            self.rebalance(offset)?;
                             Ok(Some(value.1))
                         }
                         Err(_) => Ok(None),
@@ -218,19 +777,181 @@ impl BTree {
         }
     }
 
-    fn rewrite_parent(
+    fn rebalance(&mut self, mut offset: Offset) -> Result<(), DbError> {
+        loop {
+            let page = self.pager.get_page(offset)?;
+            let parent = page.parent();
+            if parent == 0 {
+                if let Page::Node { children, .. } = page
+                    && children.len() == 1
+                {
+                    self.rewrite_parent(0, &children)?;
+                    self.pager.set_root(children[0].1)?;
+                    self.pager.free_page(offset)?;
+                    self.update_stats(|stats| {
+                        stats.node_pages = stats.node_pages.saturating_sub(1);
+                        stats.depth = stats.depth.saturating_sub(1);
+                    })?;
+                }
+                return Ok(());
+            }
+            if page.size() >= min_page_size(self.pager.capacity()) {
+                return Ok(());
+            }
+            let Page::Node {
+                parent: grandparent,
+                mut children,
+            } = self.pager.get_page(parent)?
+            else {
+                return Err(DbError::Encoding);
+            };
+            let Some(idx) = children.iter().position(|(_, child)| *child == offset) else {
+                return Err(DbError::Encoding);
+            };
+            if children.len() < 2 {
+                return Ok(());
+            }
+            let right_idx = idx.max(1);
+            let left_offset = children[right_idx - 1].1;
+            let (right_key, right_offset) = children[right_idx].clone();
+            match self.merge_or_borrow(left_offset, right_offset, right_key)? {
+                Some(right_key) => children[right_idx].0 = right_key,
+                None => {
+                    children.remove(right_idx);
+                }
+            }
+            let page = Page::Node {
+                parent: grandparent,
+                children,
+            };
+            self.pager.write_page_at_offset(page, parent)?;
+            offset = parent;
+        }
+    }
+
+    fn merge_or_borrow(
         &mut self,
-        right_offset: u32,
-        right_children: &[(Col, Offset)],
-    ) -> Result<(), DbError> {
-        for (_, child_offset) in right_children.iter() {
-            let updated_page = match self.pager.get_page(*child_offset)? {
-                Page::Node { children, .. } => Page::Node {
-                    parent: right_offset,
-                    children,
+        left_offset: Offset,
+        right_offset: Offset,
+        right_key: K,
+    ) -> Result<Option<K>, DbError> {
+        let capacity = self.pager.capacity();
+        let left = self.pager.get_page(left_offset)?;
+        let right = self.pager.get_page(right_offset)?;
+        match (left, right) {
+            (
+                Page::Leaf {
+                    parent,
+                    prev,
+                    mut values,
+                    ..
+                },
+                Page::Leaf {
+                    next,
+                    values: mut right_values,
+                    ..
                 },
-                Page::Leaf { values, .. } => Page::Leaf {
-                    parent: right_offset,
+            ) => {
+                values.append(&mut right_values);
+                if Page::leaf_size(&values) <= capacity {
+                    let page = Page::Leaf {
+                        parent,
+                        prev,
+                        next,
+                        values,
+                    };
+                    self.pager.write_page_at_offset(page, left_offset)?;
+                    self.rewrite_prev(next, left_offset)?;
+                    self.pager.free_page(right_offset)?;
+                    self.update_stats(|stats| {
+                        stats.leaf_pages = stats.leaf_pages.saturating_sub(1)
+                    })?;
+                    return Ok(None);
+                }
+                let (values, right_values) = balance(values, |(k, v)| cell_size(k, v));
+                let right_key = K::separator(&values[values.len() - 1].0, &right_values[0].0);
+                if Page::leaf_size(&values) > capacity || Page::leaf_size(&right_values) > capacity
+                {
+                    return Ok(Some(right_key));
+                }
+                let left = Page::Leaf {
+                    parent,
+                    prev,
+                    next: right_offset,
+                    values,
+                };
+                let right = Page::Leaf {
+                    parent,
+                    prev: left_offset,
+                    next,
+                    values: right_values,
+                };
+                self.pager.write_page_at_offset(left, left_offset)?;
+                self.pager.write_page_at_offset(right, right_offset)?;
+                Ok(Some(right_key))
+            }
+            (
+                Page::Node {
+                    parent,
+                    mut children,
+                },
+                Page::Node {
+                    children: mut right_children,
+                    ..
+                },
+            ) => {
+                right_children[0].0 = right_key;
+                let left_len = children.len();
+                children.append(&mut right_children);
+                if Page::<K, V>::node_size(&children) <= capacity {
+                    self.rewrite_parent(left_offset, &children[left_len..])?;
+                    let page = Page::Node { parent, children };
+                    self.pager.write_page_at_offset(page, left_offset)?;
+                    self.pager.free_page(right_offset)?;
+                    self.update_stats(|stats| {
+                        stats.node_pages = stats.node_pages.saturating_sub(1)
+                    })?;
+                    return Ok(None);
+                }
+                let (children, right_children) =
+                    balance(children, |(k, _)| k.compact_size() + PTR_SIZE);
+                let right_key = right_children[0].0.clone();
+                if Page::<K, V>::node_size(&children) > capacity
+                    || Page::<K, V>::node_size(&right_children) > capacity
+                {
+                    return Ok(Some(right_key));
+                }
+                if children.len() > left_len {
+                    self.rewrite_parent(left_offset, &children[left_len..])?;
+                } else {
+                    self.rewrite_parent(
+                        right_offset,
+                        &right_children[..left_len - children.len()],
+                    )?;
+                }
+                let left = Page::Node { parent, children };
+                let right = Page::Node {
+                    parent,
+                    children: right_children,
+                };
+                self.pager.write_page_at_offset(left, left_offset)?;
+                self.pager.write_page_at_offset(right, right_offset)?;
+                Ok(Some(right_key))
+            }
+            _ => Err(DbError::Encoding),
+        }
+    }
+
+    fn rewrite_parent(&mut self, parent: Offset, children: &[(K, Offset)]) -> Result<(), DbError> {
+        for (_, child_offset) in children.iter() {
+            let updated_page = match self.pager.get_page(*child_offset)? {
+                Page::Node { children, .. } => Page::Node { parent, children },
+                Page::Leaf {
+                    prev, next, values, ..
+                } => Page::Leaf {
+                    parent,
+                    prev,
+                    next,
                     values,
                 },
             };
@@ -239,13 +960,43 @@ impl BTree {
         }
         Ok(())
     }
+
+    fn rewrite_prev(&mut self, offset: Offset, prev: Offset) -> Result<(), DbError> {
+        if offset == 0 {
+            return Ok(());
+        }
+        if let Page::Leaf {
+            parent,
+            next,
+            values,
+            ..
+        } = self.pager.get_page(offset)?
+        {
+            let page = Page::Leaf {
+                parent,
+                prev,
+                next,
+                values,
+            };
+            self.pager.write_page_at_offset(page, offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl BTree {
+    pub fn dump(&self, out: &mut impl Write) -> Result<(), DbError> {
+        dump::dump_pager(&self.pager, out)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use row::{ColType, row};
+    use common::Pageable;
+    use row::{ColType, row, row_type};
     use tempfile::NamedTempFile;
 
+    use crate::page::CHECKSUM_SIZE;
     use crate::pager::HEADER_SIZE;
 
     use super::*;
@@ -259,14 +1010,14 @@ mod tests {
             let value = row![Col::varchar(&i.to_string(), 2048)];
             btree.insert(key, value).unwrap();
         }
-        let mut pager = Pager::new(tempfile.path()).unwrap();
+        let pager = &btree.pager;
         let left_leaf = pager.get_page(HEADER_SIZE as u32).unwrap();
         let root_node = pager.get_page((HEADER_SIZE + PAGE_SIZE) as u32).unwrap();
         let right_leaf = pager
             .get_page((HEADER_SIZE + PAGE_SIZE + PAGE_SIZE) as u32)
             .unwrap();
         match left_leaf {
-            Page::Leaf { parent, values } => {
+            Page::Leaf { parent, values, .. } => {
                 assert_eq!(parent, (HEADER_SIZE + PAGE_SIZE) as u32);
                 assert_eq!(1, values.len());
             }
@@ -282,7 +1033,7 @@ mod tests {
             _ => panic!("Unexpected leaf page"),
         }
         match right_leaf {
-            Page::Leaf { parent, values } => {
+            Page::Leaf { parent, values, .. } => {
                 assert_eq!(parent, (HEADER_SIZE + PAGE_SIZE) as u32);
                 assert_eq!(1, values.len());
             }
@@ -294,16 +1045,25 @@ mod tests {
     fn node_split() {
         let tempfile = NamedTempFile::new().unwrap();
         let mut btree = BTree::new(tempfile.path()).unwrap();
-        for i in 0..4 {
-            let key = Col::varchar(&i.to_string(), 2000);
+        btree.set_durability(Durability::OnCommit);
+        for i in 0..800 {
+            let key = Col::varchar(&format!("{:04}", i), 2000);
             let value = row![Col::varchar(&i.to_string(), 2000)];
             btree.insert(key, value).unwrap();
         }
-        let mut pager = Pager::new(tempfile.path()).unwrap();
-        let mut offset = HEADER_SIZE as u32;
-        for _ in 0..10 {
-            pager.get_page(offset).unwrap();
-            offset += PAGE_SIZE as u32;
+        btree.sync().unwrap();
+        let pager = &btree.pager;
+        let root = pager.get_root().unwrap();
+        let Page::Node { children, .. } = pager.get_page(root).unwrap() else {
+            panic!("Unexpected leaf page");
+        };
+        let Page::Node { .. } = pager.get_page(children[0].1).unwrap() else {
+            panic!("Node hasn't been split");
+        };
+        for i in 0..800 {
+            let key = Col::varchar(&format!("{:04}", i), 2000);
+            let value = btree.search(key).unwrap();
+            assert_eq!(Some(row![Col::varchar(&i.to_string(), 2000)]), value);
         }
     }
 
@@ -337,6 +1097,82 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bulk_load() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        let entries = (0..5000).map(|i| {
+            let key = Col::varchar(&format!("{:05}", i * 2), 100);
+            (key, row![Col::int(i)])
+        });
+        assert_eq!(5000, btree.bulk_load(entries).unwrap());
+        for i in 0..5000 {
+            let key = Col::varchar(&format!("{:05}", i * 2), 100);
+            assert_eq!(Some(row![Col::int(i)]), btree.search(key).unwrap());
+        }
+        for i in 0..5000 {
+            let key = Col::varchar(&format!("{:05}", i * 2 + 1), 100);
+            btree.insert(key, row![Col::int(-i)]).unwrap();
+        }
+        let keys: Vec<Col> = btree
+            .scan(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .map(|kv| kv.unwrap().0)
+            .collect();
+        assert_eq!(10000, keys.len());
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        for i in 0..10000 {
+            let key = Col::varchar(&format!("{:05}", i), 100);
+            assert!(btree.delete(key).unwrap().is_some());
+        }
+        assert!(btree.select_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn insert_many() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        let entries = (0..3000).map(|i| (Col::int(i * 2), row![Col::varchar("", 255)]));
+        assert_eq!(3000, btree.insert_many(entries).unwrap());
+        let entries = (0..3000).map(|i| (Col::int(i), row![Col::varchar(&i.to_string(), 255)]));
+        assert_eq!(3000, btree.insert_many(entries).unwrap());
+        let keys: Vec<Col> = btree
+            .scan(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .map(|kv| kv.unwrap().0)
+            .collect();
+        assert_eq!(4500, keys.len());
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        for i in 0..3000 {
+            let row = btree.search(Col::int(i)).unwrap();
+            assert_eq!(Some(row![Col::varchar(&i.to_string(), 255)]), row);
+        }
+        assert_eq!(walk_stats(&mut btree), btree.stats().unwrap());
+        let entries = vec![
+            (Col::int(1), row![Col::varchar("", 255)]),
+            (Col::int(1), row![Col::varchar("", 255)]),
+        ];
+        assert!(btree.insert_many(entries).is_err());
+    }
+
+    #[test]
+    fn bulk_load_unsorted() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        let entries = vec![
+            (Col::int(2), row![Col::int(2)]),
+            (Col::int(1), row![Col::int(1)]),
+        ];
+        assert!(btree.bulk_load(entries).is_err());
+        assert!(btree.select_all().unwrap().is_empty());
+        btree.insert(Col::int(0), row![Col::int(0)]).unwrap();
+        let entries = (1..100).map(|i| (Col::int(i), row![Col::int(i)]));
+        assert_eq!(99, btree.bulk_load(entries).unwrap());
+        assert_eq!(100, btree.select_all().unwrap().len());
+    }
+
     #[test]
     fn insert_delete_key() {
         let tmpfile = NamedTempFile::new().unwrap();
@@ -364,9 +1200,53 @@ mod tests {
     }
 
     #[test]
-    fn delete_not_existed() {
+    fn delete_rebalance() {
         let tmpfile = NamedTempFile::new().unwrap();
         let mut btree = BTree::new(tmpfile.path()).unwrap();
+        for i in (0..3000).rev() {
+            btree
+                .insert(Col::varchar(&format!("{:04}", i), 64), row![Col::int(i)])
+                .unwrap();
+        }
+        for i in 0..3000 {
+            if i % 10 != 0 {
+                let key = Col::varchar(&format!("{:04}", i), 64);
+                assert_eq!(Some(row![Col::int(i)]), btree.delete(key).unwrap());
+            }
+        }
+        for i in 0..3000 {
+            let value = btree
+                .search(Col::varchar(&format!("{:04}", i), 64))
+                .unwrap();
+            assert_eq!(i % 10 == 0, value.is_some());
+        }
+        let expected: Vec<Row> = (0..300).map(|i| row![Col::int(i * 10)]).collect();
+        assert_eq!(expected, btree.select_all().unwrap());
+
+        let file_len = tmpfile.as_file().metadata().unwrap().len();
+        for i in 0..3000 {
+            btree
+                .insert(Col::varchar(&format!("{:04}", i), 64), row![Col::int(i)])
+                .unwrap();
+        }
+        assert_eq!(file_len, tmpfile.as_file().metadata().unwrap().len());
+
+        for i in 0..3000 {
+            btree
+                .delete(Col::varchar(&format!("{:04}", i), 64))
+                .unwrap();
+        }
+        let root = btree.pager.get_root().unwrap();
+        let Page::Leaf { values, .. } = btree.pager.get_page(root).unwrap() else {
+            panic!("root hasn't been collapsed");
+        };
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn delete_not_existed() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut btree: BTree = BTree::new(tmpfile.path()).unwrap();
         let response = btree.delete(Col::varchar(&0.to_string(), 4)).unwrap();
         assert_eq!(response, None);
     }
@@ -382,15 +1262,97 @@ mod tests {
             panic!("size hasn't been validated")
         };
         assert_eq!(received, 4111);
-        assert_eq!(limit, MAX_KEY_VALUE_SIZE);
+        assert_eq!(limit, max_key_value_size(PAGE_SIZE - CHECKSUM_SIZE));
+    }
+
+    #[test]
+    fn custom_page_size() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let page_size = 16 * 1024;
+        let mut btree = BTree::with_page_size(tempfile.path(), page_size).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        for i in 0..100 {
+            let key = Col::int(i);
+            let value = row![Col::varchar(&i.to_string(), 8000)];
+            btree.insert(key, value).unwrap();
+        }
+        assert!(btree.stats().unwrap().depth > 1);
+        drop(btree);
+        let btree: BTree = BTree::new(tempfile.path()).unwrap();
+        assert_eq!(page_size, btree.page_size());
+        assert_eq!(100, btree.select_all().unwrap().len());
+        let len = fs::metadata(tempfile.path()).unwrap().len() as usize;
+        assert_eq!(0, (len - HEADER_SIZE) % page_size);
+        let err = BTree::<Col, Row>::with_page_size(tempfile.path(), 5000);
+        assert!(matches!(err, Err(DbError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        let entries = (0..2000).map(|i| (Col::int(i), row![Col::int(i * 2)]));
+        btree.bulk_load(entries).unwrap();
+        let btree = &btree;
+        std::thread::scope(|scope| {
+            for t in 0..4 {
+                scope.spawn(move || {
+                    for i in (t..2000).step_by(4) {
+                        let row = btree.search(Col::int(i)).unwrap();
+                        assert_eq!(Some(row![Col::int(i * 2)]), row);
+                    }
+                    assert_eq!(2000, btree.select_all().unwrap().len());
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn snapshot() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        for i in 0..500 {
+            let value = row![Col::varchar(&i.to_string(), 255)];
+            btree.insert(Col::int(i), value).unwrap();
+        }
+        let snapshot = btree.snapshot().unwrap();
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                for _ in 0..3 {
+                    let rows = snapshot.select_all().unwrap();
+                    assert_eq!(500, rows.len());
+                    assert_eq!(row![Col::varchar("0", 255)], rows[0]);
+                }
+            });
+            for i in 0..250 {
+                btree.delete(Col::int(i)).unwrap();
+            }
+            for i in 500..1000 {
+                let value = row![Col::varchar(&i.to_string(), 255)];
+                btree.insert(Col::int(i), value).unwrap();
+            }
+            btree.sync().unwrap();
+            reader.join().unwrap();
+        });
+        assert_eq!(500, snapshot.select_all().unwrap().len());
+        assert!(snapshot.search(Col::int(0)).unwrap().is_some());
+        assert!(snapshot.search(Col::int(750)).unwrap().is_none());
+        assert_eq!(750, btree.select_all().unwrap().len());
+
+        btree.delete_all().unwrap();
+        assert_eq!(500, snapshot.select_all().unwrap().len());
+        drop(snapshot);
+        assert!(btree.select_all().unwrap().is_empty());
     }
 
     #[test]
     fn set_get_structure() {
         let tmpfile = NamedTempFile::new().unwrap();
-        let mut btree = BTree::new(tmpfile.path()).unwrap();
+        let mut btree: BTree = BTree::new(tmpfile.path()).unwrap();
         let row_type = RowType {
             columns: vec![ColType::int("id"), ColType::varchar("name", 16)],
+            constraints: vec![],
         };
         btree.set_structure(row_type.clone()).unwrap();
         let saved = btree.get_structure().unwrap();
@@ -411,15 +1373,490 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scan_range() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tmpfile.path()).unwrap();
+        for i in (0..2000).rev() {
+            btree
+                .insert(Col::varchar(&format!("{:04}", i), 100), row![Col::int(i)])
+                .unwrap();
+        }
+        let from = Bound::Included(Col::varchar("0100", 100));
+        let to = Bound::Excluded(Col::varchar("1900", 100));
+        let rows: Vec<(Col, Row)> = btree
+            .scan(from, to)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(1800, rows.len());
+        for (i, (key, row)) in rows.into_iter().enumerate() {
+            let i = i as i32 + 100;
+            assert_eq!(Col::varchar(&format!("{:04}", i), 100), key);
+            assert_eq!(row![Col::int(i)], row);
+        }
+    }
+
+    #[test]
+    fn scan_unbounded() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tmpfile.path()).unwrap();
+        for i in 0..100 {
+            btree.insert(Col::int(99 - i), row![Col::int(i)]).unwrap();
+        }
+        let keys: Vec<Col> = btree
+            .scan(Bound::Excluded(Col::int(89)), Bound::Unbounded)
+            .unwrap()
+            .map(|kv| kv.unwrap().0)
+            .collect();
+        assert_eq!((90..100).map(Col::int).collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    fn scan_rev() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        assert_eq!(None, btree.last().unwrap());
+        for i in 0..200 {
+            let key = Col::int(i);
+            let value = row![Col::varchar(&i.to_string(), 255)];
+            btree.insert(key, value).unwrap();
+        }
+        let keys: Vec<Col> = btree
+            .scan_rev(
+                Bound::Excluded(Col::int(20)),
+                Bound::Included(Col::int(150)),
+            )
+            .unwrap()
+            .map(|kv| kv.unwrap().0)
+            .collect();
+        assert_eq!((21..=150).rev().map(Col::int).collect::<Vec<_>>(), keys);
+        let count = btree
+            .scan_rev(Bound::Unbounded, Bound::Unbounded)
+            .unwrap()
+            .count();
+        assert_eq!(200, count);
+        let (key, value) = btree.last().unwrap().unwrap();
+        assert_eq!(Col::int(199), key);
+        assert_eq!(row![Col::varchar("199", 255)], value);
+    }
+
+    #[test]
+    fn keys() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        for i in (0..1000).rev() {
+            let value = row![Col::varchar(&i.to_string(), 255)];
+            btree.insert(Col::int(i), value).unwrap();
+        }
+        drop(btree);
+
+        let btree: BTree = BTree::new(tempfile.path()).unwrap();
+        let keys: Vec<Col> = btree.keys().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!((0..1000).map(Col::int).collect::<Vec<_>>(), keys);
+        let keys: Vec<Col> = btree
+            .keys_range(
+                Bound::Excluded(Col::int(10)),
+                Bound::Included(Col::int(600)),
+            )
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!((11..=600).map(Col::int).collect::<Vec<_>>(), keys);
+        assert_eq!(1000, btree.snapshot().unwrap().keys().unwrap().count());
+    }
+
+    #[test]
+    fn read_only() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        for i in 0..100 {
+            btree.insert(Col::int(i), row![Col::int(i)]).unwrap();
+        }
+        drop(btree);
+
+        let mut btree = BTree::read_only(tempfile.path()).unwrap();
+        let other = BTree::read_only(tempfile.path()).unwrap();
+        assert_eq!(Some(row![Col::int(7)]), other.search(Col::int(7)).unwrap());
+        assert_eq!(100, btree.select_all().unwrap().len());
+        let read_only = DbError::ReadOnly(tempfile.path().display().to_string());
+        assert_eq!(
+            Err(read_only),
+            btree.insert(Col::int(100), row![Col::int(100)])
+        );
+        assert!(btree.compact().is_err());
+        assert!(BTree::<Col, Row>::new(tempfile.path()).is_err());
+        let empty = NamedTempFile::new().unwrap();
+        assert!(BTree::<Col, Row>::read_only(empty.path()).is_err());
+    }
+
+    #[test]
+    fn segmented() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table");
+        let mut btree = BTree::segmented(&path, 16 * 1024).unwrap();
+        for i in 0..2000 {
+            btree.insert(Col::int(i), row![Col::int(i)]).unwrap();
+        }
+        drop(btree);
+        let segments = fs::read_dir(&path).unwrap().count();
+        assert!(segments > 2);
+
+        let mut btree = BTree::new(&path).unwrap();
+        assert_eq!(Some(16 * 1024), btree.pager.segment_size());
+        assert_eq!(
+            Some(row![Col::int(1234)]),
+            btree.search(Col::int(1234)).unwrap()
+        );
+        for i in 0..1900 {
+            btree.delete(Col::int(i)).unwrap();
+        }
+        btree.compact().unwrap();
+        assert_eq!(100, btree.select_all().unwrap().len());
+        assert!(btree.verify().unwrap().is_ok());
+        drop(btree);
+
+        assert!(fs::read_dir(&path).unwrap().count() < segments);
+        let btree = BTree::read_only(&path).unwrap();
+        assert_eq!(
+            Some(row![Col::int(1999)]),
+            btree.search(Col::int(1999)).unwrap()
+        );
+    }
+
+    #[test]
+    fn typed_keys() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::<String, u64>::new(tempfile.path()).unwrap();
+        for i in 0..2000u64 {
+            btree.insert(format!("table_{:05}", i), i).unwrap();
+        }
+        drop(btree);
+
+        let mut btree = BTree::<String, u64>::new(tempfile.path()).unwrap();
+        assert_eq!(Some(42), btree.search("table_00042".to_string()).unwrap());
+        let range: Vec<u64> = btree
+            .scan(
+                Bound::Included("table_00100".to_string()),
+                Bound::Excluded("table_00110".to_string()),
+            )
+            .unwrap()
+            .map(|kv| kv.unwrap().1)
+            .collect();
+        assert_eq!((100..110).collect::<Vec<_>>(), range);
+        assert_eq!(Some(7), btree.delete("table_00007".to_string()).unwrap());
+        assert_eq!(1999, btree.keys().unwrap().count());
+        assert!(btree.stats().unwrap().depth > 1);
+        assert!(btree.verify().unwrap().is_ok());
+    }
+
+    fn walk_stats(btree: &mut BTree) -> Stats {
+        let mut stats = Stats {
+            page_size: btree.page_size(),
+            ..Stats::default()
+        };
+        let mut level = vec![btree.pager.get_root().unwrap()];
+        while !level.is_empty() {
+            stats.depth += 1;
+            let mut next = Vec::new();
+            for offset in level {
+                match btree.pager.get_page(offset).unwrap() {
+                    Page::Node { children, .. } => {
+                        stats.node_pages += 1;
+                        next.extend(children.iter().map(|(_, o)| *o));
+                    }
+                    Page::Leaf { values, .. } => {
+                        stats.leaf_pages += 1;
+                        stats.entries += values.len() as u64;
+                        let size: usize = values.iter().map(|(k, v)| k.size() + v.size()).sum();
+                        stats.data_size += size as u64;
+                    }
+                }
+            }
+            level = next;
+        }
+        stats.free_pages = btree.stats().unwrap().free_pages;
+        stats.dead_size = btree.stats().unwrap().dead_size;
+        stats
+    }
+
+    #[test]
+    fn stats() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        assert_eq!(Stats::root_leaf(PAGE_SIZE), btree.stats().unwrap());
+        for i in 0..3000 {
+            let key = Col::int((i * 7919) % 3000);
+            let value = row![Col::varchar(&i.to_string(), 255)];
+            btree.insert(key, value).unwrap();
+        }
+        btree
+            .insert(Col::int(0), row![Col::varchar("", 255)])
+            .unwrap();
+        let stats = btree.stats().unwrap();
+        assert_eq!(walk_stats(&mut btree), stats);
+        assert_eq!(3000, stats.entries);
+        assert!(stats.depth >= 2);
+        assert!(stats.fill_factor() > 0.5 && stats.fill_factor() <= 1.0);
+        for i in 0..2500 {
+            btree.delete(Col::int((i * 7919) % 3000)).unwrap();
+        }
+        let stats = btree.stats().unwrap();
+        assert_eq!(walk_stats(&mut btree), stats);
+        assert_eq!(500, stats.entries);
+        assert!(stats.free_pages > 0);
+        assert!(stats.dead_size > 0);
+        assert!(stats.reclaimable_size() >= stats.free_size());
+        btree.delete_all().unwrap();
+        assert_eq!(Stats::root_leaf(PAGE_SIZE), btree.stats().unwrap());
+        let entries = (0..3000).map(|i| (Col::int(i), row![Col::varchar("", 255)]));
+        btree.bulk_load(entries).unwrap();
+        assert_eq!(walk_stats(&mut btree), btree.stats().unwrap());
+    }
+
+    #[test]
+    fn recount() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        for i in 0..100 {
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        let snapshot = btree.snapshot().unwrap();
+        btree.delete(Col::int(0)).unwrap();
+        assert_eq!(100, snapshot.stats().unwrap().entries);
+        assert_eq!(99, btree.stats().unwrap().entries);
+
+        btree.update_stats(|stats| stats.entries = 7).unwrap();
+        assert!(!btree.verify().unwrap().is_ok());
+        assert_eq!(99, btree.recount().unwrap());
+        assert_eq!(99, btree.stats().unwrap().entries);
+        assert!(btree.verify().unwrap().is_ok());
+        drop(snapshot);
+        drop(btree);
+
+        let btree: BTree = BTree::new(tempfile.path()).unwrap();
+        assert_eq!(99, btree.stats().unwrap().entries);
+    }
+
+    #[test]
+    fn append_only() {
+        let mut btree = BTree::new_in_memory().unwrap();
+        for i in 0..5000 {
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        let stats = btree.stats().unwrap();
+        assert_eq!(walk_stats(&mut btree), stats);
+        assert!(stats.fill_factor() > 0.8, "{}", stats.fill_factor());
+        assert!(btree.tail.is_some());
+        for i in (0..5000).step_by(3) {
+            btree.delete(Col::int(i)).unwrap();
+        }
+        for i in 5000..6000 {
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        btree
+            .insert(Col::int(0), row![Col::varchar("", 255)])
+            .unwrap();
+        let report = btree.verify().unwrap();
+        assert!(report.is_ok(), "{:?}", report.errors);
+        assert_eq!(walk_stats(&mut btree), btree.stats().unwrap());
+        assert_eq!(
+            Some(Col::int(5999)),
+            btree.last().unwrap().map(|(key, _)| key)
+        );
+        assert_eq!(4334, btree.keys().unwrap().count());
+    }
+
+    #[test]
+    fn verify() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        assert!(btree.verify().unwrap().is_ok());
+        btree
+            .set_structure(row_type![ColType::varchar("name", 255)])
+            .unwrap();
+        for i in 0..3000 {
+            let key = Col::int((i * 7919) % 3000);
+            btree.insert(key, row![Col::varchar("", 255)]).unwrap();
+        }
+        for i in 0..2000 {
+            btree.delete(Col::int((i * 7919) % 3000)).unwrap();
+        }
+        let report = btree.verify().unwrap();
+        assert!(report.is_ok(), "{:?}", report.errors);
+        let stats = btree.stats().unwrap();
+        assert_eq!(1, report.schema_pages);
+        assert_eq!(1000, report.entries);
+        assert_eq!(stats.depth, report.depth);
+        assert!(report.free_pages > 0);
+        assert_eq!(
+            report.allocated_pages,
+            report.leaf_pages + report.node_pages + report.free_pages + report.schema_pages
+        );
+        btree.delete_all().unwrap();
+        let entries = (0..3000).map(|i| (Col::int(i), row![Col::varchar("", 255)]));
+        btree.bulk_load(entries).unwrap();
+        assert!(btree.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn verify_corrupted() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        for i in 0..500 {
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        let mut offset = btree.pager.get_root().unwrap();
+        while let Page::Node { children, .. } = btree.pager.get_page(offset).unwrap() {
+            offset = children[0].1;
+        }
+        let Page::Leaf {
+            parent,
+            prev,
+            mut values,
+            ..
+        } = btree.pager.get_page(offset).unwrap()
+        else {
+            panic!("Unexpected node page");
+        };
+        values.swap(0, 1);
+        let page = Page::Leaf {
+            parent,
+            prev,
+            next: 0,
+            values,
+        };
+        btree.pager.write_page_at_offset(page, offset).unwrap();
+        let leaked = btree.pager.allocate().unwrap();
+        let report = btree.verify().unwrap();
+        assert!(!report.is_ok());
+        let errors: Vec<Offset> = report.errors.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(2, errors.iter().filter(|o| **o == offset).count());
+        assert!(errors.contains(&leaked));
+    }
+
+    #[test]
+    fn compact() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        btree.set_durability(Durability::OnCommit);
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 255)],
+            constraints: vec![],
+        };
+        btree.set_structure(row_type.clone()).unwrap();
+        for i in 0..2000 {
+            let value = Row {
+                columns: vec![Col::int(i), Col::varchar(&i.to_string(), 255)],
+            };
+            btree.insert(Col::int(i), value).unwrap();
+        }
+        for i in (0..2000).filter(|i| i % 3 != 0) {
+            btree.delete(Col::int(i)).unwrap();
+        }
+        btree.sync().unwrap();
+        let size = fs::metadata(tempfile.path()).unwrap().len();
+        btree.compact().unwrap();
+        assert!(fs::metadata(tempfile.path()).unwrap().len() < size);
+        assert_eq!(row_type, btree.get_structure().unwrap());
+        let stats = btree.stats().unwrap();
+        assert_eq!(667, stats.entries);
+        assert_eq!(0, stats.free_pages);
+        assert_eq!(0, stats.dead_size);
+        for i in 0..2000 {
+            let row = btree.search(Col::int(i)).unwrap();
+            assert_eq!(i % 3 == 0, row.is_some());
+        }
+        btree
+            .insert(Col::int(1), row![Col::varchar("1", 255)])
+            .unwrap();
+        drop(btree);
+        let btree: BTree = BTree::new(tempfile.path()).unwrap();
+        assert_eq!(668, btree.select_all().unwrap().len());
+    }
+
+    #[test]
+    fn in_memory() {
+        let mut btree = BTree::new_in_memory().unwrap();
+        let row_type = row_type![ColType::int("id")];
+        btree.set_structure(row_type.clone()).unwrap();
+        for i in 0..1000 {
+            btree.insert(Col::int(i), row![Col::int(i)]).unwrap();
+        }
+        for i in (0..1000).filter(|i| i % 2 == 0) {
+            btree.delete(Col::int(i)).unwrap();
+        }
+        let snapshot = btree.snapshot().unwrap();
+        btree.compact().unwrap();
+        btree.insert(Col::int(0), row![Col::int(0)]).unwrap();
+        assert_eq!(row_type, btree.get_structure().unwrap());
+        assert_eq!(501, btree.select_all().unwrap().len());
+        assert_eq!(0, btree.stats().unwrap().free_pages);
+        assert!(btree.verify().unwrap().is_ok());
+        assert_eq!(500, snapshot.select_all().unwrap().len());
+    }
+
+    #[test]
+    fn leaf_chain() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tmpfile.path()).unwrap();
+        for i in (0..1000).rev() {
+            btree
+                .insert(Col::varchar(&format!("{:04}", i), 64), row![Col::int(i)])
+                .unwrap();
+        }
+        let rows = btree.select_all().unwrap();
+        let expected: Vec<Row> = (0..1000).map(|i| row![Col::int(i)]).collect();
+        assert_eq!(expected, rows);
+
+        let pager = &btree.pager;
+        let mut offset = pager.get_root().unwrap();
+        while let Page::Node { children, .. } = pager.get_page(offset).unwrap() {
+            offset = children[0].1;
+        }
+        let mut prev_offset = 0;
+        while offset != 0 {
+            let Page::Leaf { prev, next, .. } = pager.get_page(offset).unwrap() else {
+                panic!("Unexpected node page");
+            };
+            assert_eq!(prev_offset, prev);
+            prev_offset = offset;
+            offset = next;
+        }
+    }
+
     #[test]
     fn delete_all() {
         let tmpfile = NamedTempFile::new().unwrap();
         let mut btree = BTree::new(tmpfile.path()).unwrap();
+        let row_type = row_type![ColType::int("id")];
+        btree.set_structure(row_type.clone()).unwrap();
         for i in 0..100 {
-            btree.insert(Col::int(i), row![Col::int(20)]).unwrap();
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        for i in 0..50 {
+            btree.delete(Col::int(i)).unwrap();
         }
+        assert!(btree.stats().unwrap().free_pages > 0);
         let rows = btree.delete_all().unwrap();
-        assert_eq!(rows, 100);
+        assert_eq!(rows, 50);
+        assert_eq!(row_type, btree.get_structure().unwrap());
+        assert_eq!(Stats::root_leaf(PAGE_SIZE), btree.stats().unwrap());
+        let len = fs::metadata(tmpfile.path()).unwrap().len() as usize;
+        assert_eq!(HEADER_SIZE + 2 * PAGE_SIZE, len);
 
         for i in 0..100 {
             btree.insert(Col::int(i), row![Col::int(20)]).unwrap();
@@ -0,0 +1,159 @@
+use std::io::Write;
+use std::path::Path;
+
+use common::error::DbError;
+use row::Col;
+
+use crate::BTree;
+use crate::page::{Offset, Page};
+use crate::pager::Pager;
+
+pub fn dump(path: &Path, out: &mut impl Write) -> Result<(), DbError> {
+    if !path.exists() {
+        return Err(DbError::IO(format!("{} doesn't exist", path.display())));
+    }
+    BTree::read_only(path)?.dump(out)
+}
+
+pub(crate) fn dump_pager(pager: &Pager, out: &mut impl Write) -> Result<(), DbError> {
+    let root = pager.get_root()?;
+    writeln!(out, "header")?;
+    writeln!(out, "  page size: {}", pager.page_size())?;
+    writeln!(out, "  allocated pages: {}", pager.allocated_pages())?;
+    writeln!(out, "  root: {}", root)?;
+    match pager.freelist() {
+        Ok(freelist) => writeln!(out, "  freelist: {:?}", freelist)?,
+        Err(err) => writeln!(out, "  freelist: !! {}", err)?,
+    }
+    match pager.schema_pages() {
+        Ok(pages) => writeln!(out, "  schema pages: {:?}", pages)?,
+        Err(err) => writeln!(out, "  schema pages: !! {}", err)?,
+    }
+    match pager.get_structure() {
+        Ok(row_type) => {
+            let columns: Vec<String> = row_type.columns.iter().map(|c| c.to_string()).collect();
+            writeln!(out, "  structure: ({})", columns.join(", "))?;
+        }
+        Err(err) => writeln!(out, "  structure: !! {}", err)?,
+    }
+    let stats = pager.get_stats()?;
+    writeln!(
+        out,
+        "  stats: entries={} depth={} leaves={} nodes={} free={} dead={}",
+        stats.entries,
+        stats.depth,
+        stats.leaf_pages,
+        stats.node_pages,
+        stats.free_pages,
+        stats.dead_size
+    )?;
+    writeln!(out, "tree")?;
+    if root != 0 {
+        dump_page(pager, out, root, 1)?;
+    }
+    Ok(())
+}
+
+fn dump_page(
+    pager: &Pager,
+    out: &mut impl Write,
+    offset: Offset,
+    depth: usize,
+) -> Result<(), DbError> {
+    let indent = "  ".repeat(depth);
+    if !pager.is_page_offset(offset) {
+        writeln!(out, "{}!! @{}: offset out of range", indent, offset)?;
+        return Ok(());
+    }
+    match pager.get_page(offset) {
+        Ok(Page::Node { parent, children }) => {
+            writeln!(
+                out,
+                "{}node @{} parent={} children={}",
+                indent,
+                offset,
+                parent,
+                children.len()
+            )?;
+            for (idx, (key, child)) in children.iter().enumerate() {
+                let key = match idx {
+                    0 => "-inf".to_string(),
+                    _ => format_key(key),
+                };
+                writeln!(out, "{}  [{}] -> @{}", indent, key, child)?;
+                if depth > pager.allocated_pages() as usize {
+                    writeln!(out, "{}  !! tree is deeper than the file", indent)?;
+                    return Ok(());
+                }
+                dump_page(pager, out, *child, depth + 2)?;
+            }
+        }
+        Ok(Page::Leaf {
+            parent,
+            prev,
+            next,
+            values,
+        }) => {
+            let keys = match (values.first(), values.last()) {
+                (Some((first, _)), Some((last, _))) => {
+                    format!("{}..{}", format_key(first), format_key(last))
+                }
+                _ => "empty".to_string(),
+            };
+            writeln!(
+                out,
+                "{}leaf @{} parent={} prev={} next={} entries={} keys={}",
+                indent,
+                offset,
+                parent,
+                prev,
+                next,
+                values.len(),
+                keys
+            )?;
+        }
+        Err(err) => writeln!(out, "{}!! @{}: {}", indent, offset, err)?,
+    }
+    Ok(())
+}
+
+fn format_key(key: &Col) -> String {
+    match key {
+        Col::Int(value) => value.to_string(),
+        Col::BigInt(value) => value.to_string(),
+        Col::Varchar(value, _) => format!("{:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use row::{ColType, Row, RowType, row};
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn dump_tree() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut btree = BTree::new(tempfile.path()).unwrap();
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 255)],
+            constraints: vec![],
+        };
+        btree.set_structure(row_type).unwrap();
+        for i in 0..100 {
+            btree
+                .insert(Col::int(i), row![Col::varchar("", 255)])
+                .unwrap();
+        }
+        drop(btree);
+        let mut out = Vec::new();
+        dump(tempfile.path(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("structure: (id INT, name VARCHAR(255))"));
+        assert!(out.contains("entries=100 depth=2"));
+        assert!(out.contains("[-inf] -> @"));
+        assert!(out.contains("keys=0.."));
+        assert!(out.contains("..99\n"));
+    }
+}
@@ -0,0 +1,379 @@
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+use common::error::DbError;
+use row::{Col, ColType, Row, RowType};
+
+use crate::{BTree, Durability, Growth, IoCounters, Report, Snapshot, Stats};
+
+pub struct Index {
+    btree: BTree,
+}
+
+pub struct IndexSnapshot {
+    snapshot: Snapshot,
+}
+
+impl Index {
+    pub fn new(path: &Path) -> Result<Self, DbError> {
+        Ok(Self {
+            btree: BTree::new(path)?,
+        })
+    }
+
+    pub fn read_only(path: &Path) -> Result<Self, DbError> {
+        Ok(Self {
+            btree: BTree::read_only(path)?,
+        })
+    }
+
+    pub fn new_in_memory() -> Result<Self, DbError> {
+        Ok(Self {
+            btree: BTree::new_in_memory()?,
+        })
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.btree.set_durability(durability);
+    }
+
+    pub fn set_growth(&mut self, growth: Growth) {
+        self.btree.set_growth(growth);
+    }
+
+    pub fn set_io(&mut self, io: Arc<IoCounters>) {
+        self.btree.set_io(io);
+    }
+
+    pub fn sync(&mut self) -> Result<(), DbError> {
+        self.btree.sync()
+    }
+
+    pub fn set_columns(&mut self, columns: Vec<ColType>) -> Result<(), DbError> {
+        self.btree.set_structure(RowType {
+            columns,
+            constraints: vec![],
+        })
+    }
+
+    pub fn get_columns(&self) -> Result<Vec<ColType>, DbError> {
+        let columns = self.btree.get_structure()?.columns;
+        if columns.is_empty() {
+            return Err(DbError::invalid_input("index column is not set"));
+        }
+        Ok(columns)
+    }
+
+    pub fn insert(&mut self, mut key: Vec<Col>, primary_key: Col) -> Result<(), DbError> {
+        let entry = entry_key(&key, &primary_key);
+        key.push(primary_key);
+        self.btree.insert(entry, Row { columns: key })
+    }
+
+    pub fn remove(&mut self, key: &[Col], primary_key: &Col) -> Result<bool, DbError> {
+        let entry = entry_key(key, primary_key);
+        Ok(self.btree.delete(entry)?.is_some())
+    }
+
+    pub fn get(&self, key: &[Col]) -> Result<Vec<Col>, DbError> {
+        let entries = self.scan(Bound::Included(key.to_vec()), Bound::Included(key.to_vec()))?;
+        Ok(entries
+            .into_iter()
+            .map(|(_, primary_key)| primary_key)
+            .collect())
+    }
+
+    pub fn scan(
+        &self,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+    ) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+        let (from, to) = entry_bounds(from, to);
+        entries(self.btree.scan(from, to)?)
+    }
+
+    pub fn snapshot(&self) -> Result<IndexSnapshot, DbError> {
+        Ok(IndexSnapshot {
+            snapshot: self.btree.snapshot()?,
+        })
+    }
+
+    pub fn clear(&mut self) -> Result<(), DbError> {
+        self.btree.delete_all()?;
+        Ok(())
+    }
+
+    pub fn compact(&mut self) -> Result<(), DbError> {
+        self.btree.compact()
+    }
+
+    pub fn allocated_size(&self) -> u64 {
+        self.btree.allocated_size()
+    }
+
+    pub fn stats(&self) -> Result<Stats, DbError> {
+        self.btree.stats()
+    }
+
+    pub fn verify(&self) -> Result<Report, DbError> {
+        self.btree.verify()
+    }
+}
+
+impl IndexSnapshot {
+    pub fn scan(
+        &self,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+    ) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+        self.scan_limit(from, to, usize::MAX)
+    }
+
+    pub fn scan_limit(
+        &self,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+        let (from, to) = entry_bounds(from, to);
+        entries(self.snapshot.scan(from, to)?.take(limit))
+    }
+
+    pub fn scan_rev_limit(
+        &self,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+        limit: usize,
+    ) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+        let (from, to) = entry_bounds(from, to);
+        entries(self.snapshot.scan_rev(from, to)?.take(limit))
+    }
+}
+
+const SEPARATOR: char = '\0';
+const UPPER: char = '\u{1}';
+
+fn entry_bounds(from: Bound<Vec<Col>>, to: Bound<Vec<Col>>) -> (Bound<Col>, Bound<Col>) {
+    let from = match from {
+        Bound::Included(key) => Bound::Included(prefix(&key, SEPARATOR)),
+        Bound::Excluded(key) => Bound::Included(prefix(&key, UPPER)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let to = match to {
+        Bound::Included(key) => Bound::Excluded(prefix(&key, UPPER)),
+        Bound::Excluded(key) => Bound::Excluded(prefix(&key, SEPARATOR)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    (from, to)
+}
+
+fn entries(
+    scan: impl Iterator<Item = Result<(Col, Row), DbError>>,
+) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+    let mut entries = Vec::new();
+    for kv in scan {
+        let (_, row) = kv?;
+        let mut key = row.columns;
+        let Some(primary_key) = key.pop().filter(|_| !key.is_empty()) else {
+            return Err(DbError::Encoding);
+        };
+        entries.push((key, primary_key));
+    }
+    Ok(entries)
+}
+
+fn entry_key(key: &[Col], primary_key: &Col) -> Col {
+    let mut entry = encode_key(key);
+    entry.push(SEPARATOR);
+    entry.push_str(&encode(primary_key));
+    varchar(entry)
+}
+
+fn prefix(key: &[Col], suffix: char) -> Col {
+    let mut entry = encode_key(key);
+    entry.push(suffix);
+    varchar(entry)
+}
+
+fn encode_key(key: &[Col]) -> String {
+    let columns: Vec<String> = key.iter().map(encode).collect();
+    columns.join(&SEPARATOR.to_string())
+}
+
+fn encode(col: &Col) -> String {
+    match col {
+        Col::Int(value) => format!("{:08x}", (*value as u32) ^ (1 << 31)),
+        Col::BigInt(value) => format!("{:016x}", (*value as u64) ^ (1 << 63)),
+        Col::Varchar(value, _) => value.clone(),
+    }
+}
+
+fn varchar(value: String) -> Col {
+    let size = value.len() as u16;
+    Col::Varchar(value, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn insert_remove() {
+        let mut index = Index::new_in_memory().unwrap();
+        index
+            .set_columns(vec![ColType::varchar("name", 16)])
+            .unwrap();
+        index
+            .insert(vec![Col::varchar("a", 16)], Col::int(2))
+            .unwrap();
+        index
+            .insert(vec![Col::varchar("a", 16)], Col::int(1))
+            .unwrap();
+        index
+            .insert(vec![Col::varchar("a", 16)], Col::int(1))
+            .unwrap();
+        index
+            .insert(vec![Col::varchar("b", 16)], Col::int(3))
+            .unwrap();
+        assert_eq!(
+            vec![Col::int(1), Col::int(2)],
+            index.get(&[Col::varchar("a", 16)]).unwrap()
+        );
+        assert!(
+            index
+                .remove(&[Col::varchar("a", 16)], &Col::int(1))
+                .unwrap()
+        );
+        assert!(
+            !index
+                .remove(&[Col::varchar("a", 16)], &Col::int(1))
+                .unwrap()
+        );
+        assert!(
+            index
+                .remove(&[Col::varchar("b", 16)], &Col::int(3))
+                .unwrap()
+        );
+        assert!(index.get(&[Col::varchar("b", 16)]).unwrap().is_empty());
+        assert_eq!(
+            vec![(vec![Col::varchar("a", 16)], Col::int(2))],
+            index.scan(Bound::Unbounded, Bound::Unbounded).unwrap()
+        );
+    }
+
+    #[test]
+    fn reopen() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let mut index = Index::new(tempfile.path()).unwrap();
+        index.set_columns(vec![ColType::int("age")]).unwrap();
+        for i in 0..1000 {
+            index.insert(vec![Col::int(i % 10)], Col::int(i)).unwrap();
+        }
+        drop(index);
+
+        let index = Index::new(tempfile.path()).unwrap();
+        assert_eq!(vec![ColType::int("age")], index.get_columns().unwrap());
+        assert_eq!(100, index.get(&[Col::int(7)]).unwrap().len());
+        let range = index
+            .scan(
+                Bound::Included(vec![Col::int(2)]),
+                Bound::Excluded(vec![Col::int(4)]),
+            )
+            .unwrap();
+        assert_eq!(200, range.len());
+        assert!(index.verify().unwrap().is_ok());
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut index = Index::new_in_memory().unwrap();
+        index.set_columns(vec![ColType::int("age")]).unwrap();
+        for i in 0..100 {
+            index.insert(vec![Col::int(i % 10)], Col::int(i)).unwrap();
+        }
+        let snapshot = index.snapshot().unwrap();
+        for i in 0..50 {
+            index.remove(&[Col::int(i % 10)], &Col::int(i)).unwrap();
+        }
+        index.insert(vec![Col::int(3)], Col::int(100)).unwrap();
+        assert_eq!(6, index.get(&[Col::int(3)]).unwrap().len());
+        let entries = snapshot
+            .scan(
+                Bound::Included(vec![Col::int(3)]),
+                Bound::Included(vec![Col::int(3)]),
+            )
+            .unwrap();
+        assert_eq!(10, entries.len());
+        assert_eq!((vec![Col::int(3)], Col::int(3)), entries[0]);
+        let head = snapshot
+            .scan_limit(Bound::Included(vec![Col::int(3)]), Bound::Unbounded, 2)
+            .unwrap();
+        assert_eq!(
+            vec![
+                (vec![Col::int(3)], Col::int(3)),
+                (vec![Col::int(3)], Col::int(13))
+            ],
+            head
+        );
+        let tail = snapshot
+            .scan_rev_limit(Bound::Unbounded, Bound::Included(vec![Col::int(3)]), 2)
+            .unwrap();
+        assert_eq!(
+            vec![
+                (vec![Col::int(3)], Col::int(93)),
+                (vec![Col::int(3)], Col::int(83))
+            ],
+            tail
+        );
+    }
+
+    #[test]
+    fn composite() {
+        let mut index = Index::new_in_memory().unwrap();
+        let columns = vec![ColType::varchar("name", 16), ColType::int("age")];
+        index.set_columns(columns.clone()).unwrap();
+        assert_eq!(columns, index.get_columns().unwrap());
+        for i in 0..30 {
+            let name = ["a", "ab", "b"][i as usize % 3];
+            index
+                .insert(vec![Col::varchar(name, 16), Col::int(i % 5)], Col::int(i))
+                .unwrap();
+        }
+        assert_eq!(10, index.get(&[Col::varchar("a", 16)]).unwrap().len());
+        assert_eq!(
+            vec![Col::int(3), Col::int(18)],
+            index.get(&[Col::varchar("a", 16), Col::int(3)]).unwrap()
+        );
+        let range = index
+            .scan(
+                Bound::Excluded(vec![Col::varchar("ab", 16), Col::int(1)]),
+                Bound::Included(vec![Col::varchar("ab", 16)]),
+            )
+            .unwrap();
+        let keys: Vec<Col> = range.iter().map(|(key, _)| key[1].clone()).collect();
+        assert_eq!(
+            vec![2, 2, 3, 3, 4, 4]
+                .into_iter()
+                .map(Col::int)
+                .collect::<Vec<_>>(),
+            keys
+        );
+        assert!(
+            range
+                .iter()
+                .all(|(key, _)| key[0] == Col::varchar("ab", 16))
+        );
+        assert!(
+            index
+                .remove(&[Col::varchar("b", 16), Col::int(0)], &Col::int(5))
+                .unwrap()
+        );
+        assert_eq!(
+            vec![Col::int(20)],
+            index.get(&[Col::varchar("b", 16), Col::int(0)]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Default)]
+pub struct IoCounters {
+    page_reads: AtomicU64,
+    page_writes: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub page_reads: u64,
+    pub page_writes: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+impl IoCounters {
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            page_reads: self.page_reads.load(Ordering::Relaxed),
+            page_writes: self.page_writes.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn read(&self, pages: usize) {
+        self.page_reads.fetch_add(pages as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn write(&self, pages: usize) {
+        self.page_writes.fetch_add(pages as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl IoStats {
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.cache_hits as f64 / lookups as f64
+    }
+}
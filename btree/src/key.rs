@@ -0,0 +1,91 @@
+use std::fmt::Debug;
+
+use common::{Pageable, error::DbError};
+use row::Col;
+
+pub trait Key: Pageable + Clone + Debug + Ord {
+    fn compact_size(&self) -> usize {
+        self.size()
+    }
+
+    fn write_compact(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+        self.write(buffer)
+    }
+
+    fn read_compact(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+        Self::read(buffer)
+    }
+
+    fn separator(_left: &Self, right: &Self) -> Self {
+        right.clone()
+    }
+}
+
+pub trait Value: Pageable + Clone + Debug + Default {}
+
+impl<T: Pageable + Clone + Debug + Default> Value for T {}
+
+impl Key for Col {
+    fn compact_size(&self) -> usize {
+        Col::compact_size(self)
+    }
+
+    fn write_compact(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+        Col::write_compact(self, buffer)
+    }
+
+    fn read_compact(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+        Col::read_compact(buffer)
+    }
+
+    fn separator(left: &Self, right: &Self) -> Self {
+        match (left, right) {
+            (Col::Varchar(left, _), Col::Varchar(value, size)) => {
+                Col::Varchar(shortest_prefix(left, value), *size)
+            }
+            _ => right.clone(),
+        }
+    }
+}
+
+impl Key for String {
+    fn separator(left: &Self, right: &Self) -> Self {
+        shortest_prefix(left, right)
+    }
+}
+
+impl Key for i32 {}
+
+impl Key for i64 {}
+
+impl Key for u32 {}
+
+impl Key for u64 {}
+
+fn shortest_prefix(left: &str, right: &str) -> String {
+    let boundaries = right.char_indices().skip(1).map(|(i, _)| i);
+    for end in boundaries.chain([right.len()]) {
+        if right[..end] > *left {
+            return right[..end].to_string();
+        }
+    }
+    right.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_separator() {
+        let left = Col::varchar("apple", 100);
+        let right = Col::varchar("apricot", 100);
+        assert_eq!(Col::varchar("apr", 100), Col::separator(&left, &right));
+        let right = Col::varchar("applesauce", 100);
+        assert_eq!(Col::varchar("apples", 100), Col::separator(&left, &right));
+        assert_eq!(Col::int(5), Col::separator(&Col::int(1), &Col::int(5)));
+        let separator = String::separator(&"table_a".to_string(), &"table_b".to_string());
+        assert_eq!("table_b", separator);
+        assert_eq!("b", String::separator(&"a".to_string(), &"bcd".to_string()));
+    }
+}
@@ -1,5 +1,32 @@
+#[cfg(feature = "async")]
+mod async_btree;
+mod backend;
 mod btree;
+mod dump;
+mod index;
+mod io;
+mod key;
 mod page;
 mod pager;
+mod scan;
+mod segments;
+mod snapshot;
+mod stats;
+#[cfg(feature = "uring")]
+mod uring;
+mod verify;
+mod wal;
 
+#[cfg(feature = "async")]
+pub use async_btree::AsyncBTree;
 pub use btree::BTree;
+pub use dump::dump;
+pub use index::{Index, IndexSnapshot};
+pub use io::{IoCounters, IoStats};
+pub use key::{Key, Value};
+pub use pager::{Durability, Growth};
+pub use scan::{Keys, Scan};
+pub use snapshot::Snapshot;
+pub use stats::Stats;
+pub use verify::Report;
+pub use wal::checksum;
@@ -1,55 +1,93 @@
-use common::{Pageable, error::DbError, read_num};
+use common::{error::DbError, read_num};
 use row::{Col, Row};
 
+use crate::key::{Key, Value};
+
 pub(crate) const PAGE_SIZE: usize = 4 * 1024;
+pub(crate) const MAX_PAGE_SIZE: usize = 64 * 1024;
 pub(crate) const LEN_SIZE: usize = 2;
 pub(crate) const PTR_SIZE: usize = 4;
-pub(crate) const MAX_KEY_VALUE_SIZE: usize = PAGE_SIZE - TYPE_SIZE - PTR_SIZE - LEN_SIZE;
 
-const TYPE_SIZE: usize = 1;
+pub(crate) const TYPE_SIZE: usize = 1;
+pub(crate) const SLOT_SIZE: usize = LEN_SIZE;
+pub(crate) const LEAF_HEADER_SIZE: usize = TYPE_SIZE + 3 * PTR_SIZE + 2 * LEN_SIZE;
+pub(crate) const CHECKSUM_SIZE: usize = 4;
+pub(crate) const NODE_PAGE_TYPE: u8 = 1;
+pub(crate) const LEAF_PAGE_TYPE: u8 = 2;
+pub(crate) const FREE_PAGE_TYPE: u8 = 3;
+pub(crate) const SCHEMA_PAGE_TYPE: u8 = 4;
+pub(crate) const COMPRESSED_PAGE_FLAG: u8 = 0x80;
+pub(crate) const TAIL_FILL: usize = 90;
+
+pub(crate) fn max_key_value_size(page_size: usize) -> usize {
+    page_size - LEAF_HEADER_SIZE - SLOT_SIZE
+}
+
+pub(crate) fn cell_size<K: Key, V: Value>(key: &K, value: &V) -> usize {
+    key.size() + value.size() + SLOT_SIZE
+}
+
+pub(crate) fn min_page_size(page_size: usize) -> usize {
+    page_size / 4
+}
 
 pub type Offset = u32;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Page {
+pub enum Page<K = Col, V = Row> {
     Node {
         parent: u32,
-        children: Vec<(Col, Offset)>,
+        children: Vec<(K, Offset)>,
     },
     Leaf {
         parent: u32,
-        values: Vec<(Col, Row)>,
+        prev: Offset,
+        next: Offset,
+        values: Vec<(K, V)>,
     },
 }
 
-impl Page {
+impl<K: Key, V: Value> Page<K, V> {
     pub fn page_type(&self) -> u8 {
         match self {
-            Self::Node { .. } => 1,
-            Self::Leaf { .. } => 2,
+            Self::Node { .. } => NODE_PAGE_TYPE,
+            Self::Leaf { .. } => LEAF_PAGE_TYPE,
         }
     }
 
-    pub fn leaf_size(values: &Vec<(Col, Row)>) -> usize {
-        let mut size = TYPE_SIZE + PTR_SIZE + LEN_SIZE;
+    pub fn parent(&self) -> Offset {
+        match self {
+            Self::Node { parent, .. } => *parent,
+            Self::Leaf { parent, .. } => *parent,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Node { children, .. } => Self::node_size(children),
+            Self::Leaf { values, .. } => Self::leaf_size(values),
+        }
+    }
+
+    pub fn leaf_size(values: &Vec<(K, V)>) -> usize {
+        let mut size = LEAF_HEADER_SIZE;
         for (k, v) in values {
-            size += k.size();
-            size += v.size();
+            size += cell_size(k, v);
         }
         size
     }
 
-    pub fn node_size(values: &Vec<(Col, Offset)>) -> usize {
+    pub fn node_size(values: &Vec<(K, Offset)>) -> usize {
         let mut size = TYPE_SIZE + PTR_SIZE + LEN_SIZE;
         for (key, _) in values {
-            size += key.size();
+            size += key.compact_size();
             size += PTR_SIZE;
         }
         size
     }
 }
 
-impl TryFrom<Vec<u8>> for Page {
+impl<K: Key, V: Value> TryFrom<Vec<u8>> for Page<K, V> {
     type Error = DbError;
 
     fn try_from(buffer: Vec<u8>) -> Result<Self, Self::Error> {
@@ -60,14 +98,14 @@ impl TryFrom<Vec<u8>> for Page {
         let parent = read_num!(buffer, u32, offset);
         offset += PTR_SIZE;
 
-        let elements = read_num!(buffer, u16, offset);
-        offset += LEN_SIZE;
-
         match page_type {
-            1 => {
+            NODE_PAGE_TYPE => {
+                let elements = read_num!(buffer, u16, offset);
+                offset += LEN_SIZE;
+
                 let mut children = Vec::new();
                 for _ in 0..elements {
-                    let (key, read) = Col::read(&buffer[offset..])?;
+                    let (key, read) = K::read_compact(&buffer[offset..])?;
                     offset += read;
 
                     let pointer = read_num!(buffer, u32, offset);
@@ -77,29 +115,44 @@ impl TryFrom<Vec<u8>> for Page {
                 }
                 Ok(Self::Node { parent, children })
             }
-            2 => {
-                let mut values = Vec::new();
-                for _ in 0..elements {
-                    let (key, read) = Col::read(&buffer[offset..])?;
-                    offset += read;
+            LEAF_PAGE_TYPE => {
+                let prev = read_num!(buffer, u32, offset);
+                offset += PTR_SIZE;
 
-                    let (value, read) = Row::read(&buffer[offset..])?;
-                    offset += read;
+                let next = read_num!(buffer, u32, offset);
 
-                    values.push((key, value));
+                let slots = Slotted::new(&buffer[..]);
+                let mut values = Vec::with_capacity(slots.len());
+                for idx in 0..slots.len() {
+                    values.push(slots.get(idx)?);
                 }
-                Ok(Self::Leaf { parent, values })
+                Ok(Self::Leaf {
+                    parent,
+                    prev,
+                    next,
+                    values,
+                })
             }
             _ => Err(DbError::Encoding),
         }
     }
 }
 
-impl TryInto<Vec<u8>> for Page {
+impl<K: Key, V: Value> TryInto<Vec<u8>> for Page<K, V> {
     type Error = DbError;
 
     fn try_into(self) -> Result<Vec<u8>, Self::Error> {
-        let mut buffer = vec![0u8; PAGE_SIZE];
+        let len = self.size();
+        self.encode(len)
+    }
+}
+
+impl<K: Key, V: Value> Page<K, V> {
+    pub fn encode(self, len: usize) -> Result<Vec<u8>, DbError> {
+        if self.size() > len {
+            return Err(DbError::Encoding);
+        }
+        let mut buffer = vec![0u8; len];
         let mut offset = 0;
 
         let page_type = self.page_type();
@@ -108,9 +161,6 @@ impl TryInto<Vec<u8>> for Page {
 
         match self {
             Self::Node { parent, children } => {
-                if Self::node_size(&children) > PAGE_SIZE {
-                    return Err(DbError::Encoding);
-                }
                 buffer[offset..offset + PTR_SIZE].copy_from_slice(&parent.to_be_bytes());
                 offset += PTR_SIZE;
 
@@ -119,27 +169,33 @@ impl TryInto<Vec<u8>> for Page {
                 offset += LEN_SIZE;
 
                 for (key, pointer) in children {
-                    offset += key.write(&mut buffer[offset..])?;
+                    offset += key.write_compact(&mut buffer[offset..])?;
 
                     buffer[offset..offset + PTR_SIZE].copy_from_slice(&pointer.to_be_bytes());
                     offset += PTR_SIZE;
                 }
             }
-            Self::Leaf { parent, values } => {
-                if Self::leaf_size(&values) > PAGE_SIZE {
-                    return Err(DbError::Encoding);
-                }
+            Self::Leaf {
+                parent,
+                prev,
+                next,
+                values,
+            } => {
                 buffer[offset..offset + PTR_SIZE].copy_from_slice(&parent.to_be_bytes());
                 offset += PTR_SIZE;
 
-                buffer[offset..offset + LEN_SIZE]
-                    .copy_from_slice(&(values.len() as u16).to_be_bytes());
-                offset += LEN_SIZE;
+                buffer[offset..offset + PTR_SIZE].copy_from_slice(&prev.to_be_bytes());
+                offset += PTR_SIZE;
 
-                for (key, value) in values {
-                    offset += key.write(&mut buffer[offset..])?;
+                buffer[offset..offset + PTR_SIZE].copy_from_slice(&next.to_be_bytes());
 
-                    offset += value.write(&mut buffer[offset..])?;
+                let mut slots = Slotted::new(&mut buffer[..]);
+                slots.set_len(0);
+                slots.set_cells_start(len);
+                for (idx, (key, value)) in values.iter().enumerate() {
+                    if !slots.insert(idx, key, value)? {
+                        return Err(DbError::Encoding);
+                    }
                 }
             }
         }
@@ -147,7 +203,164 @@ impl TryInto<Vec<u8>> for Page {
     }
 }
 
-pub fn insert_key_value<T>(values: &mut Vec<(Col, T)>, value: (Col, T)) {
+pub struct Slotted<B> {
+    buffer: B,
+}
+
+const LEN_OFFSET: usize = TYPE_SIZE + 3 * PTR_SIZE;
+const CELLS_START_OFFSET: usize = LEN_OFFSET + LEN_SIZE;
+
+impl<B: AsRef<[u8]>> Slotted<B> {
+    pub fn new(buffer: B) -> Self {
+        Self { buffer }
+    }
+
+    pub fn len(&self) -> usize {
+        read_num!(self.buffer.as_ref(), u16, LEN_OFFSET) as usize
+    }
+
+    fn cells_start(&self) -> usize {
+        read_num!(self.buffer.as_ref(), u16, CELLS_START_OFFSET) as usize
+    }
+
+    fn slot(&self, idx: usize) -> usize {
+        read_num!(
+            self.buffer.as_ref(),
+            u16,
+            LEAF_HEADER_SIZE + idx * SLOT_SIZE
+        ) as usize
+    }
+
+    fn cell(&self, idx: usize) -> Result<&[u8], DbError> {
+        if idx >= self.len() {
+            return Err(DbError::Encoding);
+        }
+        let buffer = self.buffer.as_ref();
+        let start = self.slot(idx);
+        if start < LEAF_HEADER_SIZE + self.len() * SLOT_SIZE || start >= buffer.len() {
+            return Err(DbError::Encoding);
+        }
+        Ok(&buffer[start..])
+    }
+
+    fn cell_len<K: Key, V: Value>(&self, idx: usize) -> Result<usize, DbError> {
+        let cell = self.cell(idx)?;
+        let (_, key_len) = K::read(cell)?;
+        let (_, value_len) = V::read(&cell[key_len..])?;
+        Ok(key_len + value_len)
+    }
+
+    pub fn key<K: Key>(&self, idx: usize) -> Result<K, DbError> {
+        let (key, _) = K::read(self.cell(idx)?)?;
+        Ok(key)
+    }
+
+    pub fn get<K: Key, V: Value>(&self, idx: usize) -> Result<(K, V), DbError> {
+        let cell = self.cell(idx)?;
+        let (key, read) = K::read(cell)?;
+        let (value, _) = V::read(&cell[read..])?;
+        Ok((key, value))
+    }
+
+    pub fn free_space(&self) -> usize {
+        self.cells_start()
+            .saturating_sub(LEAF_HEADER_SIZE + self.len() * SLOT_SIZE)
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Slotted<B> {
+    fn set_len(&mut self, len: usize) {
+        let buffer = self.buffer.as_mut();
+        buffer[LEN_OFFSET..LEN_OFFSET + LEN_SIZE].copy_from_slice(&(len as u16).to_be_bytes());
+    }
+
+    fn set_cells_start(&mut self, start: usize) {
+        let buffer = self.buffer.as_mut();
+        buffer[CELLS_START_OFFSET..CELLS_START_OFFSET + LEN_SIZE]
+            .copy_from_slice(&(start as u16).to_be_bytes());
+    }
+
+    fn set_slot(&mut self, idx: usize, cell: usize) {
+        let offset = LEAF_HEADER_SIZE + idx * SLOT_SIZE;
+        let buffer = self.buffer.as_mut();
+        buffer[offset..offset + SLOT_SIZE].copy_from_slice(&(cell as u16).to_be_bytes());
+    }
+
+    pub fn insert<K: Key, V: Value>(
+        &mut self,
+        idx: usize,
+        key: &K,
+        value: &V,
+    ) -> Result<bool, DbError> {
+        let len = self.len();
+        if idx > len {
+            return Err(DbError::Encoding);
+        }
+        let size = key.size() + value.size();
+        if self.free_space() < size + SLOT_SIZE {
+            self.defragment::<K, V>()?;
+            if self.free_space() < size + SLOT_SIZE {
+                return Ok(false);
+            }
+        }
+        let start = self.cells_start() - size;
+        let buffer = self.buffer.as_mut();
+        let written = key.write(&mut buffer[start..])?;
+        value.write(&mut buffer[start + written..])?;
+        let slots = LEAF_HEADER_SIZE + idx * SLOT_SIZE;
+        let end = LEAF_HEADER_SIZE + len * SLOT_SIZE;
+        buffer.copy_within(slots..end, slots + SLOT_SIZE);
+        self.set_slot(idx, start);
+        self.set_len(len + 1);
+        self.set_cells_start(start);
+        Ok(true)
+    }
+
+    pub fn remove(&mut self, idx: usize) -> Result<(), DbError> {
+        let len = self.len();
+        if idx >= len {
+            return Err(DbError::Encoding);
+        }
+        let slots = LEAF_HEADER_SIZE + (idx + 1) * SLOT_SIZE;
+        let end = LEAF_HEADER_SIZE + len * SLOT_SIZE;
+        self.buffer
+            .as_mut()
+            .copy_within(slots..end, slots - SLOT_SIZE);
+        self.set_len(len - 1);
+        Ok(())
+    }
+
+    pub fn update<K: Key, V: Value>(&mut self, idx: usize, value: &V) -> Result<bool, DbError> {
+        let cell = self.cell(idx)?;
+        let (_, key_len) = K::read(cell)?;
+        let (_, value_len) = V::read(&cell[key_len..])?;
+        if value.size() != value_len {
+            return Ok(false);
+        }
+        let start = self.slot(idx) + key_len;
+        value.write(&mut self.buffer.as_mut()[start..start + value_len])?;
+        Ok(true)
+    }
+
+    fn defragment<K: Key, V: Value>(&mut self) -> Result<(), DbError> {
+        let len = self.len();
+        let mut cells = Vec::with_capacity(len);
+        for idx in 0..len {
+            let size = self.cell_len::<K, V>(idx)?;
+            cells.push(self.cell(idx)?[..size].to_vec());
+        }
+        let mut start = self.buffer.as_ref().len();
+        for (idx, cell) in cells.iter().enumerate().rev() {
+            start -= cell.len();
+            self.buffer.as_mut()[start..start + cell.len()].copy_from_slice(cell);
+            self.set_slot(idx, start);
+        }
+        self.set_cells_start(start);
+        Ok(())
+    }
+}
+
+pub fn insert_key_value<K: Ord, T>(values: &mut Vec<(K, T)>, value: (K, T)) {
     let idx = values
         .binary_search_by(|kv| kv.0.cmp(&value.0))
         .unwrap_or_else(|x| x);
@@ -160,41 +373,107 @@ pub fn insert_key_value<T>(values: &mut Vec<(Col, T)>, value: (Col, T)) {
     }
 }
 
-pub fn get_index<T>(values: &[(Col, T)], value: &Col) -> usize {
+pub fn get_index<K: Ord, T>(values: &[(K, T)], value: &K) -> usize {
     values
         .binary_search_by(|kv| kv.0.cmp(value))
         .unwrap_or_else(|x| if x == 0 { 0 } else { x - 1 })
 }
 
-pub type Splitted<T> = (Vec<(Col, T)>, Vec<(Col, T)>);
+pub type Splitted<K, T> = (Vec<(K, T)>, Vec<(K, T)>);
 
-pub fn split_leaf(mut values: Vec<(Col, Row)>) -> Splitted<Row> {
+pub fn split_leaf<K: Key, V: Value>(mut values: Vec<(K, V)>, page_size: usize) -> Splitted<K, V> {
     let mid = values.len() / 2;
     let mut right = values.split_off(mid);
-    let mut size = Page::leaf_size(&right);
-    while size > MAX_KEY_VALUE_SIZE {
+    let mut size = Page::<K, V>::leaf_size(&right);
+    while size > max_key_value_size(page_size) {
         let value = right.remove(0);
-        size -= value.0.size() + value.1.size();
+        size -= cell_size(&value.0, &value.1);
         values.push(value);
     }
     (values, right)
 }
 
-pub fn split_node(mut values: Vec<(Col, Offset)>) -> Splitted<Offset> {
+pub fn split_node<K: Key>(mut values: Vec<(K, Offset)>, page_size: usize) -> Splitted<K, Offset> {
     let mid = values.len() / 2;
     let mut right = values.split_off(mid);
-    let mut size = Page::node_size(&right);
-    while size > MAX_KEY_VALUE_SIZE {
+    let mut size = Page::<K>::node_size(&right);
+    while size > max_key_value_size(page_size) {
         let value = right.remove(0);
-        size -= value.0.size() + PTR_SIZE;
+        size -= value.0.compact_size() + PTR_SIZE;
         values.push(value);
     }
     (values, right)
 }
 
+pub fn split_tail<K, T>(
+    mut values: Vec<(K, T)>,
+    header_size: usize,
+    page_size: usize,
+    entry_size: impl Fn(&(K, T)) -> usize,
+) -> Result<Splitted<K, T>, Vec<(K, T)>> {
+    let mut size: usize = header_size + values.iter().map(&entry_size).sum::<usize>();
+    let mut mid = values.len();
+    while mid > 1 && size > page_size * TAIL_FILL / 100 {
+        mid -= 1;
+        size -= entry_size(&values[mid]);
+    }
+    let right_size: usize = header_size + values[mid..].iter().map(&entry_size).sum::<usize>();
+    if mid == values.len() || right_size > page_size {
+        return Err(values);
+    }
+    let right = values.split_off(mid);
+    Ok((values, right))
+}
+
+pub fn balance<K, T>(
+    mut values: Vec<(K, T)>,
+    entry_size: impl Fn(&(K, T)) -> usize,
+) -> Splitted<K, T> {
+    let total: usize = values.iter().map(&entry_size).sum();
+    let mut size = 0;
+    let mut mid = 0;
+    while mid < values.len() - 1 && (size + entry_size(&values[mid])) * 2 <= total {
+        size += entry_size(&values[mid]);
+        mid += 1;
+    }
+    let right = values.split_off(mid.max(1));
+    (values, right)
+}
+
+pub fn pack<K, T>(
+    values: Vec<(K, T)>,
+    header_size: usize,
+    page_size: usize,
+    entry_size: impl Fn(&(K, T)) -> usize,
+) -> Vec<Vec<(K, T)>> {
+    let mut pages = Vec::new();
+    let mut page = Vec::new();
+    let mut size = header_size;
+    for value in values {
+        let value_size = entry_size(&value);
+        if !page.is_empty() && size + value_size > page_size {
+            pages.push(std::mem::take(&mut page));
+            size = header_size;
+        }
+        size += value_size;
+        page.push(value);
+    }
+    if size < min_page_size(page_size)
+        && let Some(mut last) = pages.pop()
+    {
+        last.append(&mut page);
+        let (left, right) = balance(last, &entry_size);
+        pages.push(left);
+        page = right;
+    }
+    pages.push(page);
+    pages
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::Pageable;
     use row::row;
 
     #[test]
@@ -217,6 +496,8 @@ mod tests {
     fn leaf_node_convert() {
         let leaf = Page::Leaf {
             parent: 1338,
+            prev: 4096,
+            next: 8192,
             values: vec![
                 (Col::Int(1), row![Col::int(1)]),
                 (Col::Int(2), row![Col::int(2)]),
@@ -234,13 +515,13 @@ mod tests {
     #[test]
     fn leaf_size() {
         let leaf_values = vec![(Col::Int(1), row![Col::Int(10)])];
-        assert_eq!(18, Page::leaf_size(&leaf_values));
+        assert_eq!(30, Page::leaf_size(&leaf_values));
     }
 
     #[test]
     fn node_size() {
         let node_values = vec![(Col::Int(1), 10)];
-        assert_eq!(16, Page::node_size(&node_values));
+        assert_eq!(16, Page::<Col>::node_size(&node_values));
     }
 
     #[test]
@@ -267,8 +548,8 @@ mod tests {
 
     #[test]
     fn check_key_value_size() {
-        let mut key_size = MAX_KEY_VALUE_SIZE / 2;
-        let mut value_size = MAX_KEY_VALUE_SIZE - key_size;
+        let mut key_size = max_key_value_size(PAGE_SIZE) / 2;
+        let mut value_size = max_key_value_size(PAGE_SIZE) - key_size;
         key_size -= 1 + 2 + 2;
         value_size -= 1 + 2 + 2 + 1;
         let key = Col::varchar("", key_size as u16);
@@ -287,21 +568,141 @@ mod tests {
         }
         assert!(Page::leaf_size(&values) < PAGE_SIZE);
         values.push((Col::varchar("", 3000), row![Col::int(0)]));
-        let (left, right) = split_leaf(values);
+        let (left, right) = split_leaf(values, PAGE_SIZE);
         assert!(Page::leaf_size(&left) < PAGE_SIZE);
         assert!(Page::leaf_size(&right) < PAGE_SIZE);
     }
 
+    #[test]
+    fn balance_by_size() {
+        let mut values = vec![(Col::varchar("", 1000), 0)];
+        for i in 1..11 {
+            values.push((Col::int(i), i as Offset));
+        }
+        let (left, right) = balance(values, |(k, _)| k.size() + PTR_SIZE);
+        assert_eq!(1, left.len());
+        assert_eq!(10, right.len());
+    }
+
+    #[test]
+    fn split_at_tail() {
+        let values: Vec<_> = (0..10).map(|i| (Col::int(i), 0)).collect();
+        let (left, right) = split_tail(values, 10, 100, |_| 10).unwrap();
+        assert_eq!(8, left.len());
+        assert_eq!(vec![(Col::int(8), 0), (Col::int(9), 0)], right);
+        let values: Vec<_> = (0..2).map(|i| (Col::int(i), 0)).collect();
+        assert!(split_tail(values, 10, 15, |_| 10).is_err());
+    }
+
+    #[test]
+    fn pack_pages() {
+        let values: Vec<_> = (0..1000)
+            .map(|i| (Col::int(i), row![Col::varchar("", 100)]))
+            .collect();
+        let pages = pack(
+            values,
+            Page::<Col>::leaf_size(&vec![]),
+            PAGE_SIZE,
+            |(k, v)| k.size() + v.size(),
+        );
+        assert!(pages.len() > 1);
+        for page in pages.iter() {
+            assert!(Page::leaf_size(page) <= PAGE_SIZE);
+            assert!(Page::leaf_size(page) >= min_page_size(PAGE_SIZE));
+        }
+        assert_eq!(1000, pages.iter().map(Vec::len).sum::<usize>());
+    }
+
+    #[test]
+    fn compact_node_keys() {
+        let node = Page::Node {
+            parent: 0,
+            children: vec![(Col::varchar("a", 1000), 10), (Col::varchar("b", 1000), 11)],
+        };
+        assert_eq!(
+            TYPE_SIZE + PTR_SIZE + LEN_SIZE + 2 * (6 + PTR_SIZE),
+            node.size()
+        );
+        let buffer: Vec<u8> = node.clone().try_into().unwrap();
+        let restored: Page = buffer.try_into().unwrap();
+        assert_eq!(node, restored);
+    }
+
     #[test]
     fn split_huge_node() {
         let mut values = Vec::<(Col, Offset)>::new();
         for i in 0..100 {
             values.push((Col::varchar("", 12), i));
         }
-        assert!(Page::node_size(&values) < PAGE_SIZE);
+        assert!(Page::<Col>::node_size(&values) < PAGE_SIZE);
         values.push((Col::varchar("", 3000), 0));
-        let (left, right) = split_node(values);
-        assert!(Page::node_size(&left) < PAGE_SIZE);
-        assert!(Page::node_size(&right) < PAGE_SIZE);
+        let (left, right) = split_node(values, PAGE_SIZE);
+        assert!(Page::<Col>::node_size(&left) < PAGE_SIZE);
+        assert!(Page::<Col>::node_size(&right) < PAGE_SIZE);
+    }
+
+    #[test]
+    fn slotted_insert_remove() {
+        let leaf = Page::Leaf {
+            parent: 0,
+            prev: 0,
+            next: 0,
+            values: vec![
+                (Col::int(1), row![Col::int(1)]),
+                (Col::int(3), row![Col::int(3)]),
+            ],
+        };
+        let mut buffer = leaf.encode(256).unwrap();
+        let mut slots = Slotted::new(&mut buffer[..]);
+        assert!(slots.insert(1, &Col::int(2), &row![Col::int(2)]).unwrap());
+        slots.remove(0).unwrap();
+        assert!(slots.update::<Col, Row>(0, &row![Col::int(20)]).unwrap());
+        assert!(
+            !slots
+                .update::<Col, Row>(0, &row![Col::varchar("20", 10)])
+                .unwrap()
+        );
+        let restored: Page = buffer.try_into().unwrap();
+        let Page::Leaf { values, .. } = restored else {
+            panic!("Unexpected node page");
+        };
+        assert_eq!(
+            vec![
+                (Col::int(2), row![Col::int(20)]),
+                (Col::int(3), row![Col::int(3)])
+            ],
+            values
+        );
+    }
+
+    #[test]
+    fn slotted_defragment() {
+        let leaf: Page = Page::Leaf {
+            parent: 0,
+            prev: 0,
+            next: 0,
+            values: vec![],
+        };
+        let len = Page::leaf_size(&vec![(Col::int(0), row![Col::int(0)]); 4]);
+        let mut buffer = leaf.encode(len).unwrap();
+        let mut slots = Slotted::new(&mut buffer[..]);
+        for i in 0..4 {
+            assert!(
+                slots
+                    .insert(i, &Col::int(i as i32), &row![Col::int(0)])
+                    .unwrap()
+            );
+        }
+        assert!(!slots.insert(4, &Col::int(4), &row![Col::int(0)]).unwrap());
+        slots.remove(1).unwrap();
+        assert_eq!(SLOT_SIZE, slots.free_space());
+        assert!(slots.insert(3, &Col::int(4), &row![Col::int(0)]).unwrap());
+        let keys: Vec<Col> = (0..slots.len())
+            .map(|idx| slots.get::<Col, Row>(idx).unwrap().0)
+            .collect();
+        assert_eq!(
+            vec![Col::int(0), Col::int(2), Col::int(3), Col::int(4)],
+            keys
+        );
     }
 }
@@ -1,161 +1,1287 @@
-use common::{Pageable, error::DbError};
-use row::RowType;
+use common::{Pageable, error::DbError, read_num};
+use row::{Col, Row, RowType};
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
-    path::Path,
+    collections::HashMap,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, Weak},
 };
+use tracing::debug;
 
-use crate::page::{Offset, PAGE_SIZE, PTR_SIZE, Page};
+use crate::backend::Backend;
+use crate::io::IoCounters;
+use crate::key::{Key, Value};
+use crate::page::{
+    CHECKSUM_SIZE, COMPRESSED_PAGE_FLAG, FREE_PAGE_TYPE, LEAF_PAGE_TYPE, LEN_SIZE, MAX_PAGE_SIZE,
+    NODE_PAGE_TYPE, Offset, PAGE_SIZE, PTR_SIZE, Page, SCHEMA_PAGE_TYPE, Slotted, TYPE_SIZE,
+};
+use crate::segments::{SEGMENT_SIZE_OFFSET, Segments};
+use crate::snapshot::Preimages;
+use crate::stats::{STATS_SIZE, Stats};
+use crate::wal::{Record, Wal, checksum};
+
+pub const HEADER_SIZE: usize = 4 * 1024;
+
+const MAGIC: &[u8; 4] = b"SQLB";
+const VERSION: u32 = 1;
+const VERSION_OFFSET: u64 = MAGIC.len() as u64;
+const PAGE_SIZE_OFFSET: u64 = VERSION_OFFSET + PTR_SIZE as u64;
+const ROOT_OFFSET: u64 = PAGE_SIZE_OFFSET + PTR_SIZE as u64;
+const FREELIST_OFFSET: u64 = ROOT_OFFSET + PTR_SIZE as u64;
+const CURSOR_OFFSET: u64 = FREELIST_OFFSET + PTR_SIZE as u64;
+const SCHEMA_OFFSET: u64 = CURSOR_OFFSET + PTR_SIZE as u64;
+const STATS_OFFSET: u64 = (HEADER_SIZE - STATS_SIZE) as u64;
+const SCHEMA_HEADER_SIZE: usize = TYPE_SIZE + PTR_SIZE + LEN_SIZE;
+const CACHE_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Growth {
+    pub extent: u32,
+    pub fallocate: bool,
+}
+
+impl Growth {
+    pub fn extent(pages: u32) -> Self {
+        Self {
+            extent: pages.max(1),
+            fallocate: false,
+        }
+    }
+
+    pub fn fallocate(mut self, fallocate: bool) -> Self {
+        self.fallocate = fallocate;
+        self
+    }
+}
 
-pub const HEADER_SIZE: usize = 16 * 1024;
+impl Default for Growth {
+    fn default() -> Self {
+        Self::extent(1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    #[default]
+    Always,
+    EveryN(usize),
+    OnCommit,
+    Off,
+}
 
-pub struct Pager {
-    fd: File,
+pub struct Pager<K: Key = Col, V: Value = Row> {
+    fd: Backend,
     cursor: Offset,
+    page_size: usize,
+    wal: Option<Wal>,
+    durability: Durability,
+    growth: Growth,
+    pending: Option<Vec<Record>>,
+    dirty: HashMap<Offset, Page<K, V>>,
+    committed: Vec<Record>,
+    unsynced: usize,
+    saved_cursor: Offset,
+    cache: RwLock<HashMap<Offset, Page<K, V>>>,
+    snapshots: Mutex<Vec<Weak<Preimages>>>,
+    preimages: Option<Arc<Preimages>>,
+    read_only: Option<PathBuf>,
+    io: Arc<IoCounters>,
 }
 
-impl Pager {
+impl<K: Key, V: Value> Pager<K, V> {
     pub fn new(path: &Path) -> Result<Self, DbError> {
+        Self::with_page_size(path, PAGE_SIZE)
+    }
+
+    pub fn with_page_size(path: &Path, page_size: usize) -> Result<Self, DbError> {
+        check_page_size(page_size)?;
+        if path.is_dir() {
+            return Self::segmented(path, page_size, 0);
+        }
         let fd = OpenOptions::new()
             .create(true)
             .truncate(false)
             .write(true)
             .read(true)
             .open(path)?;
+        let fd = Backend::file(fd);
+        fd.lock(path, false)?;
+        Self::open(fd, Some(Wal::new(path)), page_size, None)
+    }
+
+    pub fn segmented(path: &Path, page_size: usize, segment_size: u64) -> Result<Self, DbError> {
+        check_page_size(page_size)?;
+        let fd = Backend::Segments(Segments::open(path, segment_size)?);
+        fd.lock(path, false)?;
+        Self::open(fd, Some(Wal::new(path)), page_size, None)
+    }
+
+    pub fn read_only(path: &Path) -> Result<Self, DbError> {
+        let fd = match path.is_dir() {
+            true => Backend::Segments(Segments::open(path, 0)?),
+            false => Backend::file(OpenOptions::new().read(true).open(path)?),
+        };
+        fd.lock(path, true)?;
+        let read_only = Some(PathBuf::from(path));
+        Self::open(fd, Some(Wal::new(path)), PAGE_SIZE, read_only)
+    }
+
+    pub fn in_memory(page_size: usize) -> Result<Self, DbError> {
+        check_page_size(page_size)?;
+        Self::open(Backend::memory(), None, page_size, None)
+    }
+
+    fn open(
+        fd: Backend,
+        wal: Option<Wal>,
+        page_size: usize,
+        read_only: Option<PathBuf>,
+    ) -> Result<Self, DbError> {
         let mut pager = Self {
             fd,
             cursor: HEADER_SIZE as u32,
+            page_size,
+            wal,
+            durability: Durability::default(),
+            growth: Growth::default(),
+            pending: None,
+            dirty: HashMap::new(),
+            committed: Vec::new(),
+            unsynced: 0,
+            saved_cursor: HEADER_SIZE as u32,
+            cache: RwLock::new(HashMap::new()),
+            snapshots: Mutex::new(Vec::new()),
+            preimages: None,
+            read_only,
+            io: Arc::default(),
         };
+        pager.recover()?;
         pager.init()?;
         Ok(pager)
     }
 
+    fn recover(&mut self) -> Result<(), DbError> {
+        let Some(wal) = self.wal.clone() else {
+            return Ok(());
+        };
+        let records = wal.read()?;
+        if self.read_only.is_some() {
+            self.committed = records;
+            return Ok(());
+        }
+        if !records.is_empty() {
+            debug!(records = records.len(), "recovering wal");
+            self.apply(&records)?;
+            self.fd.sync_data()?;
+        }
+        wal.clear()
+    }
+
     fn init(&mut self) -> Result<(), DbError> {
-        let file_size = self.fd.seek(SeekFrom::End(0))?;
+        let file_size = self.fd.len()?;
         self.cursor = file_size as u32;
         self.init_header(file_size)?;
         Ok(())
     }
 
     fn init_header(&mut self, file_size: u64) -> Result<(), DbError> {
+        if let Some(path) = &self.read_only
+            && file_size < HEADER_SIZE as u64
+        {
+            return Err(DbError::InvalidInput(format!(
+                "'{}' is not a table file",
+                path.display()
+            )));
+        }
         if file_size >= HEADER_SIZE as u64 {
+            let mut buffer = [0u8; PTR_SIZE];
+            self.read_at(0, &mut buffer)?;
+            let magic = buffer;
+            self.read_at(VERSION_OFFSET, &mut buffer)?;
+            if magic != *MAGIC || u32::from_be_bytes(buffer) != VERSION {
+                return Err(DbError::invalid_input("unsupported table file format"));
+            }
+            self.read_at(PAGE_SIZE_OFFSET, &mut buffer)?;
+            self.page_size = match u32::from_be_bytes(buffer) {
+                0 => PAGE_SIZE,
+                page_size => page_size as usize,
+            };
+            self.read_at(CURSOR_OFFSET, &mut buffer)?;
+            if let cursor @ 1.. = u32::from_be_bytes(buffer) {
+                self.cursor = cursor;
+            }
             return Ok(());
         }
-        let buffer = vec![0u8; HEADER_SIZE];
-        self.fd.seek(SeekFrom::Start(0))?;
-        self.fd.write_all(&buffer)?;
+        let mut buffer = vec![0u8; HEADER_SIZE];
+        buffer[..MAGIC.len()].copy_from_slice(MAGIC);
+        let offset = VERSION_OFFSET as usize;
+        buffer[offset..offset + PTR_SIZE].copy_from_slice(&VERSION.to_be_bytes());
+        let offset = PAGE_SIZE_OFFSET as usize;
+        buffer[offset..offset + PTR_SIZE].copy_from_slice(&(self.page_size as u32).to_be_bytes());
+        let offset = CURSOR_OFFSET as usize;
+        buffer[offset..offset + PTR_SIZE].copy_from_slice(&(HEADER_SIZE as u32).to_be_bytes());
+        if let Some(segment_size) = self.fd.segment_size() {
+            let offset = SEGMENT_SIZE_OFFSET as usize;
+            buffer[offset..offset + 8].copy_from_slice(&segment_size.to_be_bytes());
+        }
+        self.fd.write_all_at(&buffer, 0)?;
         self.cursor = HEADER_SIZE as u32;
         Ok(())
     }
 
-    pub fn set_root(&mut self, offset: Offset) -> Result<(), DbError> {
-        self.fd.seek(SeekFrom::Start(0))?;
-        self.fd.write_all(&offset.to_be_bytes())?;
-        self.fd.flush()?;
+    pub fn begin(&mut self) {
+        self.pending = Some(Vec::new());
+        self.saved_cursor = self.cursor;
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    pub fn set_growth(&mut self, growth: Growth) {
+        self.growth = growth;
+    }
+
+    pub fn set_io(&mut self, io: Arc<IoCounters>) {
+        self.io = io;
+    }
+
+    pub fn io(&self) -> Arc<IoCounters> {
+        self.io.clone()
+    }
+
+    pub fn growth(&self) -> Growth {
+        self.growth
+    }
+
+    pub fn segment_size(&self) -> Option<u64> {
+        self.fd.segment_size()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.page_size - CHECKSUM_SIZE
+    }
+
+    pub fn commit(&mut self) -> Result<(), DbError> {
+        let Some(mut records) = self.pending.take() else {
+            return Ok(());
+        };
+        let mut dirty: Vec<_> = std::mem::take(&mut self.dirty).into_iter().collect();
+        dirty.sort_by_key(|(offset, _)| *offset);
+        for (offset, page) in dirty {
+            records.push((offset as u64, encode_page(page, self.page_size)?));
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+        for (offset, data) in records {
+            merge_record(&mut self.committed, offset, data);
+        }
+        self.unsynced += 1;
+        match self.durability {
+            Durability::Always => self.sync(),
+            Durability::EveryN(n) if self.unsynced >= n => self.sync(),
+            Durability::EveryN(_) | Durability::OnCommit => Ok(()),
+            Durability::Off => {
+                let records = std::mem::take(&mut self.committed);
+                self.unsynced = 0;
+                self.apply(&records)
+            }
+        }
+    }
+
+    pub fn rollback(&mut self) {
+        self.pending = None;
+        self.dirty.clear();
+        self.cursor = self.saved_cursor;
+    }
+
+    pub fn snapshot(&self) -> Result<Self, DbError> {
+        let preimages = Arc::new(Preimages::new(self.cursor as u64));
+        let mut snapshots = self.snapshots.lock().unwrap_or_else(|err| err.into_inner());
+        snapshots.retain(|snapshot| snapshot.strong_count() > 0);
+        snapshots.push(Arc::downgrade(&preimages));
+        Ok(Self {
+            fd: self.fd.try_clone()?,
+            cursor: self.cursor,
+            page_size: self.page_size,
+            wal: self.wal.clone(),
+            durability: Durability::Off,
+            growth: self.growth,
+            pending: None,
+            dirty: HashMap::new(),
+            committed: self.committed.clone(),
+            unsynced: 0,
+            saved_cursor: self.cursor,
+            cache: RwLock::new(HashMap::new()),
+            snapshots: Mutex::new(Vec::new()),
+            preimages: Some(preimages),
+            read_only: self.read_only.clone(),
+            io: self.io.clone(),
+        })
+    }
+
+    fn preserve(&mut self, offset: u64) -> Result<(), DbError> {
+        let snapshots = self
+            .snapshots
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner());
+        snapshots.retain(|snapshot| snapshot.strong_count() > 0);
+        let snapshots: Vec<_> = snapshots.iter().filter_map(Weak::upgrade).collect();
+        let (offset, len) = match offset < HEADER_SIZE as u64 {
+            true => (0, HEADER_SIZE),
+            false => (offset, self.page_size),
+        };
+        for snapshot in snapshots {
+            if offset >= snapshot.cursor {
+                continue;
+            }
+            let mut records = snapshot
+                .records
+                .write()
+                .unwrap_or_else(|err| err.into_inner());
+            if records.contains_key(&offset) {
+                continue;
+            }
+            let end = (offset + len as u64).min(snapshot.cursor);
+            let mut buffer = vec![0u8; (end - offset) as usize];
+            self.read_overlay(offset, &mut buffer)?;
+            records.insert(offset, buffer);
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<(), DbError> {
+        if self.committed.is_empty() || self.preimages.is_some() || self.read_only.is_some() {
+            return Ok(());
+        }
+        let records = std::mem::take(&mut self.committed);
+        self.unsynced = 0;
+        debug!(records = records.len(), "syncing pages");
+        if let Some(wal) = &self.wal {
+            wal.write(&records)?;
+        }
+        self.apply(&records)?;
+        self.fd.sync_data()?;
+        match &self.wal {
+            Some(wal) => wal.clear(),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(&mut self, records: &[Record]) -> Result<(), DbError> {
+        let end = records
+            .iter()
+            .filter(|(offset, _)| *offset >= HEADER_SIZE as u64)
+            .map(|(offset, _)| offset + self.page_size as u64)
+            .max();
+        if let Some(end) = end {
+            self.reserve(end)?;
+        }
+        self.fd.write_batch(records)?;
+        let pages = records
+            .iter()
+            .filter(|(offset, _)| *offset >= HEADER_SIZE as u64)
+            .count();
+        debug!(pages, "pages written");
+        self.io.write(pages);
+        Ok(())
+    }
+
+    fn write_record(&mut self, offset: u64, data: &[u8]) -> Result<(), DbError> {
+        if offset >= HEADER_SIZE as u64 {
+            self.reserve(offset + self.page_size as u64)?;
+        }
+        self.fd.write_all_at(data, offset)?;
+        if offset >= HEADER_SIZE as u64 {
+            debug!(offset, "page written");
+            self.io.write(1);
+        }
+        Ok(())
+    }
+
+    fn reserve(&mut self, end: u64) -> Result<(), DbError> {
+        if self.fd.len()? < end {
+            let extent = self.growth.extent as u64 * self.page_size as u64;
+            let pages = (end - HEADER_SIZE as u64).div_ceil(extent) * extent;
+            self.fd
+                .grow(HEADER_SIZE as u64 + pages, self.growth.fallocate)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_writable(&self) -> Result<(), DbError> {
+        match &self.read_only {
+            Some(path) => Err(DbError::ReadOnly(path.display().to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, data: Vec<u8>) -> Result<(), DbError> {
+        self.check_writable()?;
+        self.preserve(offset)?;
+        if offset >= HEADER_SIZE as u64 {
+            self.cache_mut().remove(&(offset as Offset));
+            let page_offset = self.page_offset(offset);
+            if let Some(page) = self.dirty.remove(&page_offset)
+                && let Some(records) = self.pending.as_mut()
+            {
+                records.push((page_offset as u64, encode_page(page, self.page_size)?));
+            }
+        }
+        let Some(records) = self.pending.as_mut() else {
+            self.write_record(offset, &data)?;
+            return Ok(());
+        };
+        merge_record(records, offset, data);
         Ok(())
     }
 
-    pub fn get_root(&mut self) -> Result<Offset, DbError> {
-        if self.fd.seek(SeekFrom::End(0))? == 0 {
+    fn page_offset(&self, offset: u64) -> Offset {
+        let page = (offset - HEADER_SIZE as u64) / self.page_size as u64;
+        (HEADER_SIZE as u64 + page * self.page_size as u64) as Offset
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<(), DbError> {
+        if self.pending.is_none() && self.committed.is_empty() && self.preimages.is_none() {
+            self.fd.read_exact_at(buffer, offset)?;
+            return Ok(());
+        }
+        self.read_overlay(offset, buffer)
+    }
+
+    fn read_overlay(&self, offset: u64, buffer: &mut [u8]) -> Result<(), DbError> {
+        let file_size = self.fd.len()?;
+        buffer.fill(0);
+        if offset < file_size {
+            let len = buffer.len().min((file_size - offset) as usize);
+            self.fd.read_exact_at(&mut buffer[..len], offset)?;
+        }
+        let pending = self.pending.iter().flatten();
+        for (at, data) in self.committed.iter().chain(pending) {
+            overlay(buffer, offset, *at, data);
+        }
+        if offset >= HEADER_SIZE as u64 {
+            let page_offset = self.page_offset(offset);
+            if let Some(page) = self.dirty.get(&page_offset) {
+                let data = encode_page(page.clone(), self.page_size)?;
+                overlay(buffer, offset, page_offset as u64, &data);
+            }
+        }
+        if let Some(preimages) = &self.preimages {
+            let records = preimages
+                .records
+                .read()
+                .unwrap_or_else(|err| err.into_inner());
+            let from = offset.saturating_sub(HEADER_SIZE.max(self.page_size) as u64);
+            for (at, data) in records.range(from..offset + buffer.len() as u64) {
+                overlay(buffer, offset, *at, data);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_root(&mut self, offset: Offset) -> Result<(), DbError> {
+        self.write_at(ROOT_OFFSET, offset.to_be_bytes().to_vec())
+    }
+
+    pub fn get_root(&self) -> Result<Offset, DbError> {
+        if self.fd.len()? == 0 {
             return Ok(0);
         }
         let mut buf = [0u8; PTR_SIZE];
-        self.fd.seek(SeekFrom::Start(0))?;
-        self.fd.read_exact(&mut buf)?;
+        self.read_at(ROOT_OFFSET, &mut buf)?;
         let offset = u32::from_be_bytes(buf);
         Ok(offset)
     }
 
-    pub fn get_page(&mut self, offset: Offset) -> Result<Page, DbError> {
-        let mut buffer = vec![0u8; PAGE_SIZE];
-        self.fd.seek(SeekFrom::Start(offset as u64))?;
-        self.fd.read_exact(&mut buffer)?;
-        buffer.try_into()
+    pub fn get_page(&self, offset: Offset) -> Result<Page<K, V>, DbError> {
+        if let Some(page) = self.dirty.get(&offset) {
+            self.io.hit();
+            return Ok(page.clone());
+        }
+        if let Some(page) = self.cache().get(&offset) {
+            self.io.hit();
+            return Ok(page.clone());
+        }
+        self.io.miss();
+        let mut buffer = vec![0u8; self.page_size];
+        self.read_at(offset as u64, &mut buffer)?;
+        debug!(offset, "page read");
+        self.io.read(1);
+        let page = decode_page(buffer, self.page_size)?;
+        if self.pending.is_none() {
+            let mut cache = self.cache.write().unwrap_or_else(|err| err.into_inner());
+            if cache.len() >= CACHE_CAPACITY {
+                cache.clear();
+            }
+            cache.insert(offset, page.clone());
+        }
+        Ok(page)
     }
 
-    pub fn write_page(&mut self, page: Page) -> Result<Offset, DbError> {
-        let offset = self.cursor;
-        self.fd.seek(SeekFrom::Start(self.cursor as u64))?;
-        let buffer: Vec<u8> = page.try_into()?;
-        self.fd.write_all(&buffer)?;
-        self.fd.flush()?;
-        self.cursor += PAGE_SIZE as u32;
-        Ok(offset)
+    pub fn get_leaf_keys(&self, offset: Offset) -> Result<(Offset, Offset, Vec<K>), DbError> {
+        let cache = self.cache();
+        if let Some(Page::Leaf {
+            prev, next, values, ..
+        }) = self.dirty.get(&offset).or_else(|| cache.get(&offset))
+        {
+            let keys = values.iter().map(|(key, _)| key.clone()).collect();
+            self.io.hit();
+            return Ok((*prev, *next, keys));
+        }
+        drop(cache);
+        self.io.miss();
+        let mut buffer = vec![0u8; self.page_size];
+        self.read_at(offset as u64, &mut buffer)?;
+        debug!(offset, "page read");
+        self.io.read(1);
+        let buffer = decode_buffer(buffer, self.page_size)?;
+        if buffer[0] != LEAF_PAGE_TYPE {
+            return Err(DbError::Encoding);
+        }
+        let prev = read_num!(buffer, u32, TYPE_SIZE + PTR_SIZE);
+        let next = read_num!(buffer, u32, TYPE_SIZE + 2 * PTR_SIZE);
+        let slots = Slotted::new(&buffer[..]);
+        let keys = (0..slots.len())
+            .map(|idx| slots.key(idx))
+            .collect::<Result<_, DbError>>()?;
+        Ok((prev, next, keys))
     }
 
-    pub fn write_page_at_offset(&mut self, page: Page, offset: Offset) -> Result<(), DbError> {
-        self.fd.seek(SeekFrom::Start(offset as u64))?;
-        let buffer: Vec<u8> = page.try_into()?;
-        self.fd.write_all(&buffer)?;
-        self.fd.flush()?;
+    pub fn read_ahead(&self, offset: Offset, pages: usize) -> Result<(), DbError> {
+        if self.pending.is_some() || !self.is_page_offset(offset) {
+            return Ok(());
+        }
+        let pages = pages.min(((self.cursor - offset) as usize) / self.page_size);
+        let offsets = (0..pages).map(|i| offset + (i * self.page_size) as Offset);
+        if offsets
+            .clone()
+            .all(|offset| self.cache().contains_key(&offset))
+        {
+            return Ok(());
+        }
+        let mut buffer = vec![0u8; pages * self.page_size];
+        self.read_overlay(offset as u64, &mut buffer)?;
+        debug!(offset, pages, "pages read ahead");
+        self.io.read(pages);
+        let mut cache = self.cache.write().unwrap_or_else(|err| err.into_inner());
+        if cache.len() + pages > CACHE_CAPACITY {
+            cache.clear();
+        }
+        for (offset, chunk) in offsets.zip(buffer.chunks(self.page_size)) {
+            if !matches!(
+                chunk[0],
+                LEAF_PAGE_TYPE | NODE_PAGE_TYPE | COMPRESSED_PAGE_FLAG
+            ) {
+                continue;
+            }
+            if let Ok(page) = decode_page(chunk.to_vec(), self.page_size) {
+                cache.entry(offset).or_insert(page);
+            }
+        }
         Ok(())
     }
 
-    pub fn get_offset(&self) -> Offset {
-        self.cursor
+    fn cache(&self) -> std::sync::RwLockReadGuard<'_, HashMap<Offset, Page<K, V>>> {
+        self.cache.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn cache_mut(&mut self) -> &mut HashMap<Offset, Page<K, V>> {
+        self.cache.get_mut().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn write_page(&mut self, page: Page<K, V>) -> Result<Offset, DbError> {
+        let offset = self.allocate()?;
+        self.write_page_at_offset(page, offset)?;
+        Ok(offset)
+    }
+
+    pub fn allocate(&mut self) -> Result<Offset, DbError> {
+        let head = self.get_freelist()?;
+        if head == 0 {
+            let offset = self.cursor;
+            self.set_cursor(offset + self.page_size as u32)?;
+            return Ok(offset);
+        }
+        let mut buffer = [0u8; TYPE_SIZE + PTR_SIZE];
+        self.read_at(head as u64, &mut buffer)?;
+        if buffer[0] != FREE_PAGE_TYPE {
+            return Err(DbError::Encoding);
+        }
+        self.set_freelist(read_num!(buffer, u32, TYPE_SIZE))?;
+        let mut stats = self.get_stats()?;
+        stats.free_pages = stats.free_pages.saturating_sub(1);
+        self.set_stats(&stats)?;
+        Ok(head)
+    }
+
+    pub fn free_page(&mut self, offset: Offset) -> Result<(), DbError> {
+        let head = self.get_freelist()?;
+        let mut buffer = vec![0u8; self.page_size];
+        buffer[0] = FREE_PAGE_TYPE;
+        buffer[TYPE_SIZE..TYPE_SIZE + PTR_SIZE].copy_from_slice(&head.to_be_bytes());
+        self.write_at(offset as u64, buffer)?;
+        let mut stats = self.get_stats()?;
+        stats.free_pages += 1;
+        self.set_stats(&stats)?;
+        self.set_freelist(offset)
+    }
+
+    fn get_freelist(&self) -> Result<Offset, DbError> {
+        let mut buf = [0u8; PTR_SIZE];
+        self.read_at(FREELIST_OFFSET, &mut buf)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn set_freelist(&mut self, offset: Offset) -> Result<(), DbError> {
+        self.write_at(FREELIST_OFFSET, offset.to_be_bytes().to_vec())
+    }
+
+    pub fn freelist(&self) -> Result<Vec<Offset>, DbError> {
+        let mut pages = Vec::new();
+        let mut offset = self.get_freelist()?;
+        while offset != 0 {
+            if !self.is_page_offset(offset) || pages.len() > self.allocated_pages() as usize {
+                return Err(DbError::Encoding);
+            }
+            let mut buffer = [0u8; TYPE_SIZE + PTR_SIZE];
+            self.read_at(offset as u64, &mut buffer)?;
+            if buffer[0] != FREE_PAGE_TYPE {
+                return Err(DbError::Encoding);
+            }
+            pages.push(offset);
+            offset = read_num!(buffer, u32, TYPE_SIZE);
+        }
+        Ok(pages)
+    }
+
+    pub fn allocated_pages(&self) -> u32 {
+        (self.cursor - HEADER_SIZE as u32) / self.page_size as u32
+    }
+
+    pub fn is_page_offset(&self, offset: Offset) -> bool {
+        offset >= HEADER_SIZE as u32
+            && offset < self.cursor
+            && (offset - HEADER_SIZE as u32).is_multiple_of(self.page_size as u32)
+    }
+
+    pub fn write_page_at_offset(
+        &mut self,
+        page: Page<K, V>,
+        offset: Offset,
+    ) -> Result<(), DbError> {
+        self.check_writable()?;
+        let Some(records) = self.pending.as_mut() else {
+            let buffer = encode_page(page, self.page_size)?;
+            return self.write_at(offset as u64, buffer);
+        };
+        if page.size() > self.page_size - CHECKSUM_SIZE {
+            return Err(DbError::Encoding);
+        }
+        records.retain(|(at, _)| *at != offset as u64);
+        self.preserve(offset as u64)?;
+        self.cache_mut().remove(&offset);
+        self.dirty.insert(offset, page);
+        Ok(())
     }
 
-    pub fn get_next_offset(&self) -> Offset {
-        self.cursor + (PAGE_SIZE as u32)
+    pub fn update_leaf(
+        &mut self,
+        offset: Offset,
+        edit: impl FnOnce(&mut Slotted<&mut [u8]>) -> Result<bool, DbError>,
+    ) -> Result<bool, DbError> {
+        let capacity = self.capacity();
+        let mut buffer = vec![0u8; self.page_size];
+        self.read_at(offset as u64, &mut buffer)?;
+        debug!(offset, "page read");
+        self.io.read(1);
+        if buffer[0] != LEAF_PAGE_TYPE {
+            return Ok(false);
+        }
+        if read_num!(buffer, u32, capacity) != checksum(&buffer[..capacity]) {
+            return Err(DbError::Checksum);
+        }
+        if !edit(&mut Slotted::new(&mut buffer[..capacity]))? {
+            return Ok(false);
+        }
+        let checksum = checksum(&buffer[..capacity]);
+        buffer[capacity..].copy_from_slice(&checksum.to_be_bytes());
+        self.write_at(offset as u64, buffer)?;
+        Ok(true)
     }
 
     pub fn set_structure(&mut self, row_type: RowType) -> Result<(), DbError> {
-        let len = row_type.size();
-        self.fd.seek(SeekFrom::Start(PTR_SIZE as u64))?;
-        let mut buffer = vec![0u8; len];
+        let mut buffer = vec![0u8; row_type.size()];
         row_type.write(&mut buffer)?;
-        self.fd.write_all(&buffer)?;
-        Ok(())
+        self.write_schema(&buffer)
     }
 
-    pub fn get_structure(&mut self) -> Result<RowType, DbError> {
-        self.fd.seek(SeekFrom::Start(PTR_SIZE as u64))?;
-        let mut buffer = vec![0u8; HEADER_SIZE - PTR_SIZE];
-        self.fd.read_exact(&mut buffer)?;
+    pub fn get_structure(&self) -> Result<RowType, DbError> {
+        let buffer = self.read_schema()?;
+        if buffer.is_empty() {
+            return Ok(RowType {
+                columns: vec![],
+                constraints: vec![],
+            });
+        }
         let (row_type, _) = RowType::read(&buffer)?;
         Ok(row_type)
     }
 
+    pub fn schema_pages(&self) -> Result<Vec<Offset>, DbError> {
+        Ok(self
+            .schema_chain()?
+            .into_iter()
+            .map(|(offset, _)| offset)
+            .collect())
+    }
+
+    fn schema_chain(&self) -> Result<Vec<(Offset, Vec<u8>)>, DbError> {
+        let mut chain = Vec::new();
+        let mut buffer = [0u8; PTR_SIZE];
+        self.read_at(SCHEMA_OFFSET, &mut buffer)?;
+        let mut offset = u32::from_be_bytes(buffer);
+        while offset != 0 {
+            if !self.is_page_offset(offset) || chain.len() > self.allocated_pages() as usize {
+                return Err(DbError::Encoding);
+            }
+            let mut page = vec![0u8; self.page_size];
+            self.read_at(offset as u64, &mut page)?;
+            let len = read_num!(page, u16, TYPE_SIZE + PTR_SIZE) as usize;
+            if page[0] != SCHEMA_PAGE_TYPE || SCHEMA_HEADER_SIZE + len > self.page_size {
+                return Err(DbError::Encoding);
+            }
+            let data = page[SCHEMA_HEADER_SIZE..SCHEMA_HEADER_SIZE + len].to_vec();
+            chain.push((offset, data));
+            offset = read_num!(page, u32, TYPE_SIZE);
+        }
+        Ok(chain)
+    }
+
+    fn read_schema(&self) -> Result<Vec<u8>, DbError> {
+        Ok(self
+            .schema_chain()?
+            .into_iter()
+            .flat_map(|(_, data)| data)
+            .collect())
+    }
+
+    fn write_schema(&mut self, data: &[u8]) -> Result<(), DbError> {
+        for offset in self.schema_pages()? {
+            self.free_page(offset)?;
+        }
+        let chunks: Vec<&[u8]> = data.chunks(self.page_size - SCHEMA_HEADER_SIZE).collect();
+        let mut offsets = Vec::with_capacity(chunks.len());
+        for _ in 0..chunks.len() {
+            offsets.push(self.allocate()?);
+        }
+        for (idx, chunk) in chunks.into_iter().enumerate() {
+            let next = offsets.get(idx + 1).copied().unwrap_or(0);
+            let mut page = vec![0u8; self.page_size];
+            page[0] = SCHEMA_PAGE_TYPE;
+            page[TYPE_SIZE..TYPE_SIZE + PTR_SIZE].copy_from_slice(&next.to_be_bytes());
+            page[TYPE_SIZE + PTR_SIZE..SCHEMA_HEADER_SIZE]
+                .copy_from_slice(&(chunk.len() as u16).to_be_bytes());
+            page[SCHEMA_HEADER_SIZE..SCHEMA_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+            self.write_at(offsets[idx] as u64, page)?;
+        }
+        let head = offsets.first().copied().unwrap_or(0);
+        self.write_at(SCHEMA_OFFSET, head.to_be_bytes().to_vec())
+    }
+
+    pub fn get_stats(&self) -> Result<Stats, DbError> {
+        let mut buffer = vec![0u8; STATS_SIZE];
+        self.read_at(STATS_OFFSET, &mut buffer)?;
+        Stats::read(&buffer, self.page_size)
+    }
+
+    pub fn set_stats(&mut self, stats: &Stats) -> Result<(), DbError> {
+        self.write_at(STATS_OFFSET, stats.write())
+    }
+
+    fn set_cursor(&mut self, cursor: Offset) -> Result<(), DbError> {
+        self.cursor = cursor;
+        self.write_at(CURSOR_OFFSET, cursor.to_be_bytes().to_vec())
+    }
+
     pub fn clear(&mut self) -> Result<(), DbError> {
-        self.cursor = HEADER_SIZE as u32;
-        self.fd.set_len(self.cursor as u64)?;
+        let schema = self.read_schema()?;
+        self.set_cursor(HEADER_SIZE as u32)?;
+        self.set_freelist(0)?;
+        self.write_at(SCHEMA_OFFSET, 0u32.to_be_bytes().to_vec())?;
+        self.set_stats(&Stats::root_leaf(self.page_size))?;
         let offset = self.write_page(Page::Leaf {
             parent: 0,
+            prev: 0,
+            next: 0,
             values: vec![],
         })?;
         self.set_root(offset)?;
+        self.write_schema(&schema)
+    }
+
+    pub fn truncate(&mut self) -> Result<(), DbError> {
+        self.check_writable()?;
+        self.sync()?;
+        self.cache_mut().clear();
+        let snapshots = self
+            .snapshots
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner());
+        if snapshots.iter().any(|snapshot| snapshot.strong_count() > 0) {
+            return Ok(());
+        }
+        self.fd.set_len(self.cursor as u64)?;
         Ok(())
     }
 }
 
+impl<K: Key, V: Value> Drop for Pager<K, V> {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+fn check_page_size(page_size: usize) -> Result<(), DbError> {
+    if !page_size.is_power_of_two() || !(PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(DbError::InvalidInput(format!(
+            "page size must be a power of two between {} and {}",
+            PAGE_SIZE, MAX_PAGE_SIZE
+        )));
+    }
+    Ok(())
+}
+
+fn encode_page<K: Key, V: Value>(page: Page<K, V>, page_size: usize) -> Result<Vec<u8>, DbError> {
+    let capacity = page_size - CHECKSUM_SIZE;
+    if page.size() > capacity {
+        return Err(DbError::Encoding);
+    }
+    let mut buffer = page.encode(capacity)?;
+    #[cfg(feature = "compression")]
+    {
+        let compressed = lz4_flex::block::compress(&buffer);
+        let size = TYPE_SIZE + crate::page::LEN_SIZE + compressed.len();
+        if size < capacity {
+            let mut page = Vec::with_capacity(size + CHECKSUM_SIZE);
+            page.push(COMPRESSED_PAGE_FLAG);
+            page.extend_from_slice(&(compressed.len() as u16).to_be_bytes());
+            page.extend_from_slice(&compressed);
+            page.extend_from_slice(&checksum(&page).to_be_bytes());
+            return Ok(page);
+        }
+    }
+    buffer.extend_from_slice(&checksum(&buffer).to_be_bytes());
+    Ok(buffer)
+}
+
+fn decode_page<K: Key, V: Value>(buffer: Vec<u8>, page_size: usize) -> Result<Page<K, V>, DbError> {
+    decode_buffer(buffer, page_size)?.try_into()
+}
+
+#[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+fn decode_buffer(mut buffer: Vec<u8>, page_size: usize) -> Result<Vec<u8>, DbError> {
+    if buffer[0] != COMPRESSED_PAGE_FLAG {
+        let capacity = page_size - CHECKSUM_SIZE;
+        if read_num!(buffer, u32, capacity) != checksum(&buffer[..capacity]) {
+            return Err(DbError::Checksum);
+        }
+        buffer.truncate(capacity);
+        return Ok(buffer);
+    }
+    #[cfg(feature = "compression")]
+    {
+        let len = read_num!(buffer, u16, TYPE_SIZE) as usize;
+        let offset = TYPE_SIZE + crate::page::LEN_SIZE;
+        if offset + len + CHECKSUM_SIZE > page_size
+            || read_num!(buffer, u32, offset + len) != checksum(&buffer[..offset + len])
+        {
+            return Err(DbError::Checksum);
+        }
+        lz4_flex::block::decompress(&buffer[offset..offset + len], page_size)
+            .map_err(|_| DbError::Encoding)
+    }
+    #[cfg(not(feature = "compression"))]
+    Err(DbError::unexpected(
+        "page is compressed, enable the 'compression' feature",
+    ))
+}
+
+fn merge_record(records: &mut Vec<Record>, offset: u64, data: Vec<u8>) {
+    match records.iter_mut().find(|(at, _)| *at == offset) {
+        Some(record) => record.1 = data,
+        None => records.push((offset, data)),
+    }
+}
+
+fn overlay(buffer: &mut [u8], offset: u64, at: u64, data: &[u8]) {
+    let end = offset + buffer.len() as u64;
+    let from = offset.max(at);
+    let to = end.min(at + data.len() as u64);
+    if from < to {
+        buffer[(from - offset) as usize..(to - offset) as usize]
+            .copy_from_slice(&data[(from - at) as usize..(to - at) as usize]);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::os::unix::fs::FileExt;
+
+    use row::ColType;
     use tempfile::NamedTempFile;
 
     use super::*;
+    use crate::io::IoStats;
 
     #[test]
     fn cursor() {
         let tmpfile = NamedTempFile::new().unwrap();
-        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
         pager
             .write_page(Page::Leaf {
                 parent: 0,
+                prev: 0,
+                next: 0,
                 values: vec![],
             })
             .unwrap();
         let cursor1 = pager.cursor;
-        let pager = Pager::new(tmpfile.path()).unwrap();
+        drop(pager);
+        let pager: Pager = Pager::new(tmpfile.path()).unwrap();
         let cursor2 = pager.cursor;
         assert_eq!(cursor1, cursor2);
     }
 
     #[test]
-    fn clear() {
+    fn reuse_free_page() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        let first = pager.allocate().unwrap();
+        let second = pager.allocate().unwrap();
+        pager.free_page(first).unwrap();
+        pager.free_page(second).unwrap();
+        assert_eq!(second, pager.allocate().unwrap());
+        assert_eq!(first, pager.allocate().unwrap());
+        assert_eq!(second + PAGE_SIZE as u32, pager.allocate().unwrap());
+    }
+
+    #[test]
+    fn rollback() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.begin();
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        pager.set_root(offset).unwrap();
+        assert_eq!(offset, pager.get_root().unwrap());
+        pager.rollback();
+        assert_eq!(0, pager.get_root().unwrap());
+        assert_eq!(HEADER_SIZE as u32, pager.cursor);
+    }
+
+    #[test]
+    fn redo_on_open() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        let page = encode_page(empty_leaf(), PAGE_SIZE).unwrap();
+        let offset = HEADER_SIZE as u32;
+        pager
+            .wal
+            .as_ref()
+            .unwrap()
+            .write(&[
+                (ROOT_OFFSET, offset.to_be_bytes().to_vec()),
+                (offset as u64, page),
+            ])
+            .unwrap();
+        drop(pager);
+
+        let pager = Pager::new(tmpfile.path()).unwrap();
+        assert_eq!(offset, pager.get_root().unwrap());
+        assert_eq!(empty_leaf(), pager.get_page(offset).unwrap());
+        assert!(pager.wal.as_ref().unwrap().read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn corrupted_page() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        pager.fd.write_all_at(&[0xff], offset as u64 + 5).unwrap();
+        drop(pager);
+        let pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        assert_eq!(Err(DbError::Checksum), pager.get_page(offset));
+    }
+
+    #[test]
+    fn deferred_sync() {
         let tmpfile = NamedTempFile::new().unwrap();
         let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_durability(Durability::OnCommit);
+        pager.begin();
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        pager.set_root(offset).unwrap();
+        pager.commit().unwrap();
+        assert_eq!(offset, pager.get_root().unwrap());
+        assert_eq!(
+            HEADER_SIZE as u64,
+            tmpfile.as_file().metadata().unwrap().len()
+        );
+
+        pager.sync().unwrap();
+        assert!(pager.committed.is_empty());
+        drop(pager);
+        let reopened: Pager = Pager::new(tmpfile.path()).unwrap();
+        assert_eq!(offset, reopened.get_root().unwrap());
+    }
+
+    #[test]
+    fn sync_every_n() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_durability(Durability::EveryN(2));
+        for i in 0..3 {
+            pager.begin();
+            pager.set_root(i).unwrap();
+            pager.commit().unwrap();
+        }
+        assert_eq!(1, pager.unsynced);
+        let mut root = [0u8; PTR_SIZE];
+        tmpfile
+            .as_file()
+            .read_exact_at(&mut root, ROOT_OFFSET)
+            .unwrap();
+        assert_eq!(1, u32::from_be_bytes(root));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_page() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        let page = Page::Leaf {
+            parent: 0,
+            prev: 0,
+            next: 0,
+            values: vec![(
+                row::Col::varchar("key", 1024),
+                row::Row {
+                    columns: vec![row::Col::varchar("value", 1024)],
+                },
+            )],
+        };
+        let encoded = encode_page(page.clone(), PAGE_SIZE).unwrap();
+        assert_eq!(COMPRESSED_PAGE_FLAG, encoded[0]);
+        assert!(encoded.len() < PAGE_SIZE / 8);
+
+        pager.begin();
+        let offset = pager.write_page(page.clone()).unwrap();
+        pager.commit().unwrap();
+        assert_eq!(page, pager.get_page(offset).unwrap());
+    }
+
+    fn empty_leaf() -> Page {
+        Page::Leaf {
+            parent: 0,
+            prev: 0,
+            next: 0,
+            values: vec![],
+        }
+    }
+
+    #[test]
+    fn grow_in_extents() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_growth(Growth::extent(8).fallocate(true));
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        pager.set_root(offset).unwrap();
+        let len = tmpfile.as_file().metadata().unwrap().len();
+        assert_eq!((HEADER_SIZE + 8 * PAGE_SIZE) as u64, len);
+        for _ in 0..8 {
+            pager.write_page(empty_leaf()).unwrap();
+        }
+        let len = tmpfile.as_file().metadata().unwrap().len();
+        assert_eq!((HEADER_SIZE + 16 * PAGE_SIZE) as u64, len);
+        drop(pager);
+
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        assert_eq!(9, pager.allocated_pages());
+        assert_eq!(empty_leaf(), pager.get_page(offset).unwrap());
+        pager.truncate().unwrap();
+        let len = tmpfile.as_file().metadata().unwrap().len();
+        assert_eq!((HEADER_SIZE + 9 * PAGE_SIZE) as u64, len);
+    }
+
+    #[test]
+    fn large_structure() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        let columns = (0..200)
+            .map(|i| ColType::varchar(&format!("{}_{}", "column".repeat(8), i), 16))
+            .collect();
+        let row_type = RowType {
+            columns,
+            constraints: vec![],
+        };
+        assert!(row_type.size() > 2 * PAGE_SIZE);
+        pager.set_structure(row_type.clone()).unwrap();
+        assert_eq!(3, pager.schema_pages().unwrap().len());
+        pager.set_structure(row_type.clone()).unwrap();
+        assert_eq!(3, pager.allocated_pages());
+        pager.clear().unwrap();
+        drop(pager);
+
+        let pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        assert_eq!(row_type, pager.get_structure().unwrap());
+        assert_eq!(4, pager.allocated_pages());
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        tmpfile
+            .as_file()
+            .write_all_at(&[0u8; HEADER_SIZE], 0)
+            .unwrap();
+        let Err(err) = Pager::<Col, Row>::new(tmpfile.path()) else {
+            panic!("unknown header accepted");
+        };
+        assert_eq!(DbError::invalid_input("unsupported table file format"), err);
+    }
+
+    #[test]
+    fn clear() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
         pager.clear().unwrap();
         assert_eq!(pager.cursor as usize, HEADER_SIZE + PAGE_SIZE);
     }
+
+    #[test]
+    fn dirty_pages_written_once() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_durability(Durability::OnCommit);
+        pager.begin();
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        for i in 0..10 {
+            let page = Page::Leaf {
+                parent: 0,
+                prev: 0,
+                next: i,
+                values: vec![],
+            };
+            pager.write_page_at_offset(page, offset).unwrap();
+        }
+        assert_eq!(1, pager.dirty.len());
+        let Page::Leaf { next, .. } = pager.get_page(offset).unwrap() else {
+            panic!("expected leaf");
+        };
+        assert_eq!(9, next);
+        pager.commit().unwrap();
+        assert!(pager.dirty.is_empty());
+        let writes = pager
+            .committed
+            .iter()
+            .filter(|(at, _)| *at == offset as u64)
+            .count();
+        assert_eq!(1, writes);
+        drop(pager);
+
+        let pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        let Page::Leaf { next, .. } = pager.get_page(offset).unwrap() else {
+            panic!("expected leaf");
+        };
+        assert_eq!(9, next);
+    }
+
+    #[test]
+    fn raw_write_over_dirty_page() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.begin();
+        let offset = pager.write_page(empty_leaf()).unwrap();
+        pager.free_page(offset).unwrap();
+        assert!(pager.dirty.is_empty());
+        pager.commit().unwrap();
+        assert_eq!(offset, pager.allocate().unwrap());
+    }
+
+    #[test]
+    fn read_ahead() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        let first = pager.write_page(empty_leaf()).unwrap();
+        for _ in 0..3 {
+            pager.write_page(empty_leaf()).unwrap();
+        }
+        let free = pager.allocate().unwrap();
+        pager.free_page(free).unwrap();
+        drop(pager);
+
+        let pager = Pager::new(tmpfile.path()).unwrap();
+        pager.read_ahead(first, 16).unwrap();
+        assert_eq!(4, pager.cache().len());
+        assert_eq!(empty_leaf(), pager.get_page(first).unwrap());
+        pager.read_ahead(0, 16).unwrap();
+        assert_eq!(4, pager.cache().len());
+    }
+
+    #[test]
+    fn io_counters() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let io = Arc::new(IoCounters::default());
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_io(io.clone());
+        let first = pager.write_page(empty_leaf()).unwrap();
+        pager.write_page(empty_leaf()).unwrap();
+        assert_eq!(2, io.stats().page_writes);
+        drop(pager);
+
+        let mut pager: Pager = Pager::new(tmpfile.path()).unwrap();
+        pager.set_io(io.clone());
+        pager.get_page(first).unwrap();
+        pager.get_page(first).unwrap();
+        pager.read_ahead(first, 16).unwrap();
+        let stats = io.stats();
+        assert_eq!(
+            IoStats {
+                page_reads: 3,
+                page_writes: 2,
+                cache_hits: 1,
+                cache_misses: 1,
+            },
+            stats
+        );
+        assert_eq!(0.5, stats.hit_ratio());
+        assert_eq!(0.0, IoStats::default().hit_ratio());
+    }
+
+    #[test]
+    fn exclusive_lock() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let mut pager = Pager::new(tmpfile.path()).unwrap();
+        pager.write_page(empty_leaf()).unwrap();
+        let locked = || Some(DbError::Locked(tmpfile.path().display().to_string()));
+        assert_eq!(locked(), Pager::<Col, Row>::new(tmpfile.path()).err());
+        assert_eq!(locked(), Pager::<Col, Row>::read_only(tmpfile.path()).err());
+        drop(pager);
+
+        let mut first: Pager = Pager::read_only(tmpfile.path()).unwrap();
+        let second: Pager = Pager::read_only(tmpfile.path()).unwrap();
+        assert_eq!(first.cursor, second.cursor);
+        assert_eq!(locked(), Pager::<Col, Row>::new(tmpfile.path()).err());
+        let read_only = DbError::ReadOnly(tmpfile.path().display().to_string());
+        assert_eq!(Err(read_only), first.set_root(0));
+    }
 }
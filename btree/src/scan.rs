@@ -0,0 +1,198 @@
+use std::ops::Bound;
+use std::vec::IntoIter;
+
+use common::error::DbError;
+use row::{Col, Row};
+
+use crate::key::{Key, Value};
+use crate::page::{Offset, Page, get_index};
+use crate::pager::Pager;
+
+const READ_AHEAD_PAGES: usize = 8;
+
+pub struct Scan<'a, K: Key = Col, V: Value = Row> {
+    pager: &'a Pager<K, V>,
+    next: Offset,
+    values: IntoIter<(K, V)>,
+    from: Bound<K>,
+    to: Bound<K>,
+    reverse: bool,
+    keys_only: bool,
+    done: bool,
+}
+
+impl<'a, K: Key, V: Value> Scan<'a, K, V> {
+    pub(crate) fn new(
+        pager: &'a Pager<K, V>,
+        from: Bound<K>,
+        to: Bound<K>,
+        reverse: bool,
+    ) -> Result<Self, DbError> {
+        Self::with_keys_only(pager, from, to, reverse, false)
+    }
+
+    fn with_keys_only(
+        pager: &'a Pager<K, V>,
+        from: Bound<K>,
+        to: Bound<K>,
+        reverse: bool,
+        keys_only: bool,
+    ) -> Result<Self, DbError> {
+        let mut scan = Self {
+            pager,
+            next: 0,
+            values: Vec::new().into_iter(),
+            from,
+            to,
+            reverse,
+            keys_only,
+            done: false,
+        };
+        let root = scan.pager.get_root()?;
+        scan.descend(root)?;
+        Ok(scan)
+    }
+
+    fn descend(&mut self, mut offset: Offset) -> Result<(), DbError> {
+        loop {
+            match self.pager.get_page(offset)? {
+                Page::Node { children, .. } => {
+                    let bound = if self.reverse { &self.to } else { &self.from };
+                    let idx = match bound {
+                        Bound::Included(key) | Bound::Excluded(key) => get_index(&children, key),
+                        Bound::Unbounded if self.reverse => children.len() - 1,
+                        Bound::Unbounded => 0,
+                    };
+                    offset = children[idx].1;
+                }
+                Page::Leaf {
+                    prev, next, values, ..
+                } => {
+                    self.next = if self.reverse { prev } else { next };
+                    self.values = if self.keys_only {
+                        without_rows(values.into_iter().map(|(key, _)| key))
+                    } else {
+                        values.into_iter()
+                    };
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn next_leaf(&mut self) -> Result<bool, DbError> {
+        if self.next == 0 {
+            return Ok(false);
+        }
+        if self.keys_only {
+            let (prev, next, keys) = self.pager.get_leaf_keys(self.next)?;
+            self.next = if self.reverse { prev } else { next };
+            self.values = without_rows(keys);
+            return Ok(true);
+        }
+        let start = if self.reverse {
+            let span = (READ_AHEAD_PAGES - 1) * self.pager.page_size();
+            self.next.saturating_sub(span as Offset)
+        } else {
+            self.next
+        };
+        self.pager.read_ahead(start, READ_AHEAD_PAGES)?;
+        let Page::Leaf {
+            prev, next, values, ..
+        } = self.pager.get_page(self.next)?
+        else {
+            return Err(DbError::Encoding);
+        };
+        self.next = if self.reverse { prev } else { next };
+        self.values = values.into_iter();
+        Ok(true)
+    }
+
+    fn after_start(&self, key: &K) -> bool {
+        match &self.from {
+            Bound::Included(from) => key >= from,
+            Bound::Excluded(from) => key > from,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn before_end(&self, key: &K) -> bool {
+        match &self.to {
+            Bound::Included(to) => key <= to,
+            Bound::Excluded(to) => key < to,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+fn without_rows<K, V: Default>(keys: impl IntoIterator<Item = K>) -> IntoIter<(K, V)> {
+    keys.into_iter()
+        .map(|key| (key, V::default()))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+pub struct Keys<'a, K: Key = Col, V: Value = Row> {
+    scan: Scan<'a, K, V>,
+}
+
+impl<'a, K: Key, V: Value> Keys<'a, K, V> {
+    pub(crate) fn new(
+        pager: &'a Pager<K, V>,
+        from: Bound<K>,
+        to: Bound<K>,
+        reverse: bool,
+    ) -> Result<Self, DbError> {
+        let scan = Scan::with_keys_only(pager, from, to, reverse, true)?;
+        Ok(Self { scan })
+    }
+}
+
+impl<K: Key, V: Value> Iterator for Keys<'_, K, V> {
+    type Item = Result<K, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scan.next().map(|kv| kv.map(|(key, _)| key))
+    }
+}
+
+impl<K: Key, V: Value> Iterator for Scan<'_, K, V> {
+    type Item = Result<(K, V), DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let value = if self.reverse {
+                self.values.next_back()
+            } else {
+                self.values.next()
+            };
+            let Some((key, row)) = value else {
+                match self.next_leaf() {
+                    Ok(true) => continue,
+                    Ok(false) => {
+                        self.done = true;
+                        return None;
+                    }
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                }
+            };
+            let (skip, stop) = if self.reverse {
+                (!self.before_end(&key), !self.after_start(&key))
+            } else {
+                (!self.after_start(&key), !self.before_end(&key))
+            };
+            if skip {
+                continue;
+            }
+            if stop {
+                self.done = true;
+                return None;
+            }
+            return Some(Ok((key, row)));
+        }
+        None
+    }
+}
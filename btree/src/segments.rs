@@ -0,0 +1,206 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use common::error::DbError;
+
+pub(crate) const SEGMENT_SIZE_OFFSET: u64 = 28;
+
+const SEGMENT_EXTENSION: &str = "seg";
+
+pub(crate) struct Segments {
+    dir: PathBuf,
+    segment_size: u64,
+    files: Arc<RwLock<Vec<File>>>,
+}
+
+impl Segments {
+    pub(crate) fn open(dir: &Path, segment_size: u64) -> Result<Self, DbError> {
+        fs::create_dir_all(dir)?;
+        let mut files = Vec::new();
+        while let Ok(fd) = open_segment(dir, files.len(), false) {
+            files.push(fd);
+        }
+        if files.is_empty() {
+            files.push(open_segment(dir, 0, true)?);
+        }
+        let mut buffer = [0u8; 8];
+        let segment_size = match files[0].read_exact_at(&mut buffer, SEGMENT_SIZE_OFFSET) {
+            Ok(()) if u64::from_be_bytes(buffer) > 0 => u64::from_be_bytes(buffer),
+            _ => segment_size,
+        };
+        if segment_size == 0 {
+            return Err(DbError::InvalidInput(format!(
+                "'{}' has no segment size",
+                dir.display()
+            )));
+        }
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            segment_size,
+            files: Arc::new(RwLock::new(files)),
+        })
+    }
+
+    pub(crate) fn segment_size(&self) -> u64 {
+        self.segment_size
+    }
+
+    pub(crate) fn first(&self) -> Result<File, DbError> {
+        Ok(self.files()[0].try_clone()?)
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self, DbError> {
+        Ok(Self {
+            dir: self.dir.clone(),
+            segment_size: self.segment_size,
+            files: self.files.clone(),
+        })
+    }
+
+    pub(crate) fn len(&self) -> Result<u64, DbError> {
+        let files = self.files();
+        let last = files.len() as u64 - 1;
+        Ok(last * self.segment_size + files[last as usize].metadata()?.len())
+    }
+
+    pub(crate) fn set_len(&self, len: u64) -> Result<(), DbError> {
+        self.resize(len, |fd, len| Ok(fd.set_len(len)?))
+    }
+
+    pub(crate) fn resize(
+        &self,
+        len: u64,
+        resize: impl Fn(&File, u64) -> Result<(), DbError>,
+    ) -> Result<(), DbError> {
+        let mut files = self.files_mut();
+        let count = len.div_ceil(self.segment_size).max(1) as usize;
+        while files.len() > count {
+            files.pop();
+            fs::remove_file(segment_path(&self.dir, files.len()))?;
+        }
+        while files.len() < count {
+            let fd = open_segment(&self.dir, files.len(), true)?;
+            files.push(fd);
+        }
+        for (idx, fd) in files.iter().enumerate() {
+            let start = idx as u64 * self.segment_size;
+            let target = (len - start.min(len)).min(self.segment_size);
+            if fd.metadata()?.len() != target {
+                resize(fd, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_exact_at(&self, buffer: &mut [u8], offset: u64) -> Result<(), DbError> {
+        let files = self.files();
+        for (idx, local, range) in self.split(offset, buffer.len()) {
+            let Some(fd) = files.get(idx) else {
+                return Err(Error::from(ErrorKind::UnexpectedEof).into());
+            };
+            fd.read_exact_at(&mut buffer[range], local)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_all_at(&self, buffer: &[u8], offset: u64) -> Result<(), DbError> {
+        let end = offset + buffer.len() as u64;
+        if end > self.len()? {
+            let mut files = self.files_mut();
+            while (files.len() as u64) * self.segment_size < end {
+                if let Some(last) = files.last() {
+                    last.set_len(self.segment_size)?;
+                }
+                let fd = open_segment(&self.dir, files.len(), true)?;
+                files.push(fd);
+            }
+        }
+        let files = self.files();
+        for (idx, local, range) in self.split(offset, buffer.len()) {
+            files[idx].write_all_at(&buffer[range], local)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sync_data(&self) -> Result<(), DbError> {
+        for fd in self.files().iter() {
+            fd.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn split(
+        &self,
+        offset: u64,
+        len: usize,
+    ) -> impl Iterator<Item = (usize, u64, std::ops::Range<usize>)> {
+        let segment_size = self.segment_size;
+        let mut done = 0;
+        std::iter::from_fn(move || {
+            if done >= len {
+                return None;
+            }
+            let at = offset + done as u64;
+            let local = at % segment_size;
+            let chunk = ((segment_size - local) as usize).min(len - done);
+            let range = done..done + chunk;
+            done += chunk;
+            Some(((at / segment_size) as usize, local, range))
+        })
+    }
+
+    fn files(&self) -> RwLockReadGuard<'_, Vec<File>> {
+        self.files.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn files_mut(&self) -> RwLockWriteGuard<'_, Vec<File>> {
+        self.files.write().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn segment_path(dir: &Path, idx: usize) -> PathBuf {
+    dir.join(format!("{:08}.{}", idx, SEGMENT_EXTENSION))
+}
+
+fn open_segment(dir: &Path, idx: usize, create: bool) -> Result<File, DbError> {
+    Ok(OpenOptions::new()
+        .create(create)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(segment_path(dir, idx))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_across_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let segments = Segments::open(dir.path(), 16).unwrap();
+        let data: Vec<u8> = (0..40).collect();
+        segments.write_all_at(&data, 4).unwrap();
+        assert_eq!(44, segments.len().unwrap());
+        assert_eq!(3, segments.files().len());
+        let mut buffer = [0u8; 20];
+        segments.read_exact_at(&mut buffer, 10).unwrap();
+        assert_eq!(&data[6..26], &buffer);
+        assert!(segments.read_exact_at(&mut buffer, 30).is_err());
+
+        segments.set_len(20).unwrap();
+        assert_eq!(20, segments.len().unwrap());
+        assert!(!segment_path(dir.path(), 2).exists());
+        segments.set_len(64).unwrap();
+        assert_eq!(4, segments.files().len());
+        drop(segments);
+
+        let segments = Segments::open(dir.path(), 16).unwrap();
+        assert_eq!(64, segments.len().unwrap());
+    }
+}
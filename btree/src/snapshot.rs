@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::RwLock;
+
+use common::error::DbError;
+use row::{Col, Row, RowType};
+
+use crate::key::{Key, Value};
+use crate::pager::Pager;
+use crate::scan::{Keys, Scan};
+use crate::stats::Stats;
+
+pub(crate) struct Preimages {
+    pub(crate) cursor: u64,
+    pub(crate) records: RwLock<BTreeMap<u64, Vec<u8>>>,
+}
+
+impl Preimages {
+    pub(crate) fn new(cursor: u64) -> Self {
+        Self {
+            cursor,
+            records: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+pub struct Snapshot<K: Key = Col, V: Value = Row> {
+    pager: Pager<K, V>,
+}
+
+impl<K: Key, V: Value> Snapshot<K, V> {
+    pub(crate) fn new(pager: Pager<K, V>) -> Self {
+        Self { pager }
+    }
+
+    pub fn get_structure(&self) -> Result<RowType, DbError> {
+        self.pager.get_structure()
+    }
+
+    pub fn stats(&self) -> Result<Stats, DbError> {
+        self.pager.get_stats()
+    }
+
+    pub fn search(&self, key: K) -> Result<Option<V>, DbError> {
+        let bound = Bound::Included(key);
+        self.scan(bound.clone(), bound)?
+            .next()
+            .transpose()
+            .map(|kv| kv.map(|(_, row)| row))
+    }
+
+    pub fn scan(&self, from: Bound<K>, to: Bound<K>) -> Result<Scan<'_, K, V>, DbError> {
+        Scan::new(&self.pager, from, to, false)
+    }
+
+    pub fn scan_rev(&self, from: Bound<K>, to: Bound<K>) -> Result<Scan<'_, K, V>, DbError> {
+        Scan::new(&self.pager, from, to, true)
+    }
+
+    pub fn keys(&self) -> Result<Keys<'_, K, V>, DbError> {
+        Keys::new(&self.pager, Bound::Unbounded, Bound::Unbounded, false)
+    }
+
+    pub fn select_all(&self) -> Result<Vec<V>, DbError> {
+        self.scan(Bound::Unbounded, Bound::Unbounded)?
+            .map(|kv| kv.map(|(_, row)| row))
+            .collect()
+    }
+}
@@ -0,0 +1,126 @@
+use common::{error::DbError, read_num};
+
+use crate::page::LEAF_HEADER_SIZE;
+
+pub(crate) const STATS_SIZE: usize = 8 + 8 + 4 * 4 + 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub entries: u64,
+    pub depth: u32,
+    pub leaf_pages: u32,
+    pub node_pages: u32,
+    pub free_pages: u32,
+    pub page_size: usize,
+    pub dead_size: u64,
+    pub(crate) data_size: u64,
+}
+
+impl Stats {
+    pub(crate) fn root_leaf(page_size: usize) -> Self {
+        Self {
+            depth: 1,
+            leaf_pages: 1,
+            page_size,
+            ..Self::default()
+        }
+    }
+
+    pub fn fill_factor(&self) -> f64 {
+        if self.leaf_pages == 0 {
+            return 0.0;
+        }
+        let header_size = LEAF_HEADER_SIZE;
+        let used = self.data_size + (header_size as u64) * self.leaf_pages as u64;
+        used as f64 / (self.page_size as u64 * self.leaf_pages as u64) as f64
+    }
+
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+
+    pub fn free_size(&self) -> u64 {
+        self.free_pages as u64 * self.page_size as u64
+    }
+
+    pub fn reclaimable_size(&self) -> u64 {
+        let capacity = (self.page_size - LEAF_HEADER_SIZE) as u64 * self.leaf_pages as u64;
+        self.free_size() + capacity.saturating_sub(self.data_size)
+    }
+
+    pub(crate) fn read(buffer: &[u8], page_size: usize) -> Result<Self, DbError> {
+        if buffer.len() < STATS_SIZE {
+            return Err(DbError::Encoding);
+        }
+        Ok(Self {
+            dead_size: read_num!(buffer, u64, 0),
+            entries: read_num!(buffer, u64, 8),
+            depth: read_num!(buffer, u32, 16),
+            leaf_pages: read_num!(buffer, u32, 20),
+            node_pages: read_num!(buffer, u32, 24),
+            free_pages: read_num!(buffer, u32, 28),
+            page_size,
+            data_size: read_num!(buffer, u64, 32),
+        })
+    }
+
+    pub(crate) fn write(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(STATS_SIZE);
+        buffer.extend_from_slice(&self.dead_size.to_be_bytes());
+        buffer.extend_from_slice(&self.entries.to_be_bytes());
+        buffer.extend_from_slice(&self.depth.to_be_bytes());
+        buffer.extend_from_slice(&self.leaf_pages.to_be_bytes());
+        buffer.extend_from_slice(&self.node_pages.to_be_bytes());
+        buffer.extend_from_slice(&self.free_pages.to_be_bytes());
+        buffer.extend_from_slice(&self.data_size.to_be_bytes());
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::PAGE_SIZE;
+
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let stats = Stats {
+            entries: 1337,
+            depth: 3,
+            leaf_pages: 42,
+            node_pages: 2,
+            free_pages: 7,
+            page_size: PAGE_SIZE,
+            dead_size: 512,
+            data_size: 100_000,
+        };
+        let buffer = stats.write();
+        assert_eq!(STATS_SIZE, buffer.len());
+        assert_eq!(stats, Stats::read(&buffer, PAGE_SIZE).unwrap());
+    }
+
+    #[test]
+    fn fill_factor() {
+        let stats = Stats {
+            leaf_pages: 2,
+            data_size: (PAGE_SIZE - 2 * LEAF_HEADER_SIZE) as u64,
+            ..Stats::root_leaf(PAGE_SIZE)
+        };
+        assert_eq!(0.5, stats.fill_factor());
+        assert_eq!(0.0, Stats::default().fill_factor());
+    }
+
+    #[test]
+    fn reclaimable_size() {
+        let stats = Stats {
+            leaf_pages: 2,
+            free_pages: 3,
+            data_size: (PAGE_SIZE - LEAF_HEADER_SIZE) as u64,
+            ..Stats::root_leaf(PAGE_SIZE)
+        };
+        assert_eq!(3 * PAGE_SIZE as u64, stats.free_size());
+        let reclaimable = 3 * PAGE_SIZE + PAGE_SIZE - LEAF_HEADER_SIZE;
+        assert_eq!(reclaimable as u64, stats.reclaimable_size());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    fs::File,
+    io::{Error, ErrorKind},
+    mem,
+    os::{fd::AsRawFd, unix::fs::FileExt},
+    sync::{Arc, Mutex},
+};
+
+use common::error::DbError;
+use io_uring::{EnterFlags, IoUring, opcode, squeue, types};
+use tracing::{error, warn};
+
+const QUEUE_DEPTH: u32 = 64;
+
+pub(crate) struct Uring {
+    fd: File,
+    ring: Arc<Mutex<Option<IoUring>>>,
+}
+
+impl Uring {
+    pub(crate) fn new(fd: File) -> Result<Self, (File, Error)> {
+        match IoUring::new(QUEUE_DEPTH) {
+            Ok(ring) => Ok(Self {
+                fd,
+                ring: Arc::new(Mutex::new(Some(ring))),
+            }),
+            Err(err) => Err((fd, err)),
+        }
+    }
+
+    pub(crate) fn file(&self) -> &File {
+        &self.fd
+    }
+
+    pub(crate) fn try_clone(&self) -> Result<Self, DbError> {
+        Ok(Self {
+            fd: self.fd.try_clone()?,
+            ring: self.ring.clone(),
+        })
+    }
+
+    pub(crate) fn read_exact_at(&self, buffer: &mut [u8], offset: u64) -> Result<(), DbError> {
+        let mut data = vec![vec![0u8; buffer.len()]];
+        let entry = opcode::Read::new(
+            types::Fd(self.fd.as_raw_fd()),
+            data[0].as_mut_ptr(),
+            buffer.len() as u32,
+        )
+        .offset(offset)
+        .build();
+        let read = match self.submit(vec![entry], &mut data)? {
+            Some(read) => {
+                let read = read[0].min(buffer.len());
+                buffer[..read].copy_from_slice(&data[0][..read]);
+                read
+            }
+            None => 0,
+        };
+        if read < buffer.len() {
+            self.fd
+                .read_exact_at(&mut buffer[read..], offset + read as u64)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_batch(&self, records: &[(u64, Vec<u8>)]) -> Result<(), DbError> {
+        for records in records.chunks(QUEUE_DEPTH as usize) {
+            let mut data: Vec<Vec<u8>> = records.iter().map(|(_, data)| data.clone()).collect();
+            let entries = records
+                .iter()
+                .zip(&data)
+                .map(|((offset, _), data)| {
+                    opcode::Write::new(
+                        types::Fd(self.fd.as_raw_fd()),
+                        data.as_ptr(),
+                        data.len() as u32,
+                    )
+                    .offset(*offset)
+                    .build()
+                })
+                .collect();
+            let written = self
+                .submit(entries, &mut data)?
+                .unwrap_or_else(|| vec![0; records.len()]);
+            for ((offset, data), written) in records.iter().zip(written) {
+                if written < data.len() {
+                    self.fd
+                        .write_all_at(&data[written..], offset + written as u64)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn submit(
+        &self,
+        entries: Vec<squeue::Entry>,
+        buffers: &mut Vec<Vec<u8>>,
+    ) -> Result<Option<Vec<usize>>, DbError> {
+        let mut ring = self
+            .ring
+            .lock()
+            .map_err(|_| DbError::unexpected("io_uring lock is poisoned"))?;
+        let Some(uring) = ring.as_mut() else {
+            return Ok(None);
+        };
+        let result = complete(uring, entries);
+        if let Err(Failure::InFlight(err)) = &result {
+            error!(error = %err, "cannot wait for in-flight io_uring operations, disabling io_uring");
+            mem::forget(mem::take(buffers));
+            *ring = None;
+            return Err(DbError::IO(format!(
+                "io_uring operations left in flight: {}",
+                err
+            )));
+        }
+        if uring.unsubmitted() > 0 {
+            warn!("io_uring rejected a submission, falling back to positional IO");
+            *ring = None;
+        }
+        match result {
+            Ok(results) => Ok(Some(results)),
+            Err(Failure::Io(err) | Failure::InFlight(err)) => Err(err.into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Failure {
+    Io(Error),
+    InFlight(Error),
+}
+
+trait Ring {
+    fn free(&mut self) -> usize;
+    fn push(&mut self, entry: squeue::Entry);
+    fn unsubmitted(&mut self) -> usize;
+    fn submit_and_wait(&mut self, want: usize) -> Result<usize, Error>;
+    fn wait(&mut self, want: usize) -> Result<usize, Error>;
+    fn completion(&mut self) -> Option<(u64, i32)>;
+}
+
+impl Ring for IoUring {
+    fn free(&mut self) -> usize {
+        let submission = self.submission();
+        submission.capacity() - submission.len()
+    }
+
+    fn push(&mut self, entry: squeue::Entry) {
+        unsafe { self.submission().push(&entry) }.expect("io_uring submission queue is full");
+    }
+
+    fn unsubmitted(&mut self) -> usize {
+        self.submission().len()
+    }
+
+    fn submit_and_wait(&mut self, want: usize) -> Result<usize, Error> {
+        IoUring::submit_and_wait(self, want)
+    }
+
+    fn wait(&mut self, want: usize) -> Result<usize, Error> {
+        let flags = EnterFlags::GETEVENTS.bits();
+        unsafe {
+            self.submitter()
+                .enter::<libc::sigset_t>(0, want as u32, flags, None)
+        }
+    }
+
+    fn completion(&mut self) -> Option<(u64, i32)> {
+        self.completion()
+            .next()
+            .map(|cqe| (cqe.user_data(), cqe.result()))
+    }
+}
+
+fn complete(ring: &mut impl Ring, entries: Vec<squeue::Entry>) -> Result<Vec<usize>, Failure> {
+    let len = entries.len();
+    if ring.free() < len {
+        return Err(Failure::Io(Error::other(
+            "io_uring submission queue is full",
+        )));
+    }
+    for (idx, entry) in entries.into_iter().enumerate() {
+        ring.push(entry.user_data(idx as u64));
+    }
+    let mut results = vec![0; len];
+    let mut error = None;
+    let mut submitting = true;
+    let mut expected = len;
+    let mut completed = 0;
+    while completed < expected {
+        if let Some((user_data, result)) = ring.completion() {
+            completed += 1;
+            match results.get_mut(user_data as usize) {
+                _ if result < 0 => {
+                    error.get_or_insert(Error::from_raw_os_error(-result));
+                }
+                Some(slot) => *slot = result as usize,
+                None => {
+                    error.get_or_insert(Error::from(ErrorKind::InvalidData));
+                }
+            }
+            continue;
+        }
+        let waited = match submitting {
+            true => ring.submit_and_wait(expected - completed),
+            false => ring.wait(expected - completed),
+        };
+        match waited {
+            Ok(_) => {}
+            Err(err) if retryable(&err) => {}
+            Err(err) if submitting => {
+                submitting = false;
+                expected = len - ring.unsubmitted();
+                error.get_or_insert(err);
+            }
+            Err(err) => return Err(Failure::InFlight(err)),
+        }
+    }
+    match error {
+        Some(err) => Err(Failure::Io(err)),
+        None => Ok(results),
+    }
+}
+
+fn retryable(err: &Error) -> bool {
+    matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock)
+        || err.raw_os_error() == Some(libc::EBUSY)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn batch_write_read() {
+        let tempfile = NamedTempFile::new().unwrap();
+        let Ok(uring) = Uring::new(tempfile.reopen().unwrap()) else {
+            return;
+        };
+        let records: Vec<_> = (0..100u64).map(|i| (i * 16, vec![i as u8; 16])).collect();
+        uring.write_batch(&records).unwrap();
+        let mut buffer = [0u8; 16];
+        uring.read_exact_at(&mut buffer, 42 * 16).unwrap();
+        assert_eq!([42u8; 16], buffer);
+        assert!(uring.read_exact_at(&mut buffer, 100 * 16).is_err());
+    }
+
+    #[derive(Default)]
+    struct FakeRing {
+        queued: VecDeque<u64>,
+        in_flight: VecDeque<u64>,
+        completed: VecDeque<(u64, i32)>,
+        failures: VecDeque<(usize, Error)>,
+        waits: usize,
+    }
+
+    impl FakeRing {
+        fn progress(&mut self) {
+            if let Some(user_data) = self.in_flight.pop_front() {
+                self.completed.push_back((user_data, 16));
+            }
+        }
+    }
+
+    impl Ring for FakeRing {
+        fn free(&mut self) -> usize {
+            4 - self.queued.len()
+        }
+
+        fn push(&mut self, entry: squeue::Entry) {
+            self.queued.push_back(entry.get_user_data());
+        }
+
+        fn unsubmitted(&mut self) -> usize {
+            self.queued.len()
+        }
+
+        fn submit_and_wait(&mut self, _: usize) -> Result<usize, Error> {
+            let (consumed, result) = match self.failures.pop_front() {
+                Some((consumed, err)) => (consumed, Err(err)),
+                None => (self.queued.len(), Ok(self.queued.len())),
+            };
+            self.in_flight.extend(self.queued.drain(..consumed));
+            if result.is_ok() {
+                self.progress();
+            }
+            result
+        }
+
+        fn wait(&mut self, _: usize) -> Result<usize, Error> {
+            self.waits += 1;
+            if let Some((_, err)) = self.failures.pop_front() {
+                return Err(err);
+            }
+            self.progress();
+            Ok(0)
+        }
+
+        fn completion(&mut self) -> Option<(u64, i32)> {
+            self.completed.pop_front()
+        }
+    }
+
+    fn entries(len: usize) -> Vec<squeue::Entry> {
+        (0..len).map(|_| opcode::Nop::new().build()).collect()
+    }
+
+    #[test]
+    fn interrupted_submission() {
+        let mut ring = FakeRing::default();
+        ring.failures
+            .push_back((2, Error::from(ErrorKind::Interrupted)));
+        ring.failures
+            .push_back((0, Error::from_raw_os_error(libc::EBUSY)));
+        assert_eq!(vec![16; 3], complete(&mut ring, entries(3)).unwrap());
+        assert!(ring.in_flight.is_empty() && ring.completed.is_empty());
+        assert!(complete(&mut ring, entries(5)).is_err());
+        assert!(ring.queued.is_empty());
+    }
+
+    #[test]
+    fn failed_submission() {
+        let mut ring = FakeRing::default();
+        ring.failures
+            .push_back((2, Error::from_raw_os_error(libc::EINVAL)));
+        let Err(Failure::Io(err)) = complete(&mut ring, entries(3)) else {
+            panic!("expected a submission failure");
+        };
+        assert_eq!(Some(libc::EINVAL), err.raw_os_error());
+        assert!(ring.in_flight.is_empty() && ring.completed.is_empty());
+        assert_eq!(2, ring.waits);
+        assert_eq!(1, ring.unsubmitted());
+    }
+
+    #[test]
+    fn failed_wait() {
+        let mut ring = FakeRing::default();
+        ring.failures
+            .push_back((2, Error::from_raw_os_error(libc::EINVAL)));
+        ring.failures
+            .push_back((0, Error::from_raw_os_error(libc::EFAULT)));
+        let Err(Failure::InFlight(err)) = complete(&mut ring, entries(3)) else {
+            panic!("expected operations left in flight");
+        };
+        assert_eq!(Some(libc::EFAULT), err.raw_os_error());
+        assert_eq!(1, ring.waits);
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+
+use common::error::DbError;
+
+use crate::key::{Key, Value};
+use crate::page::{Offset, Page};
+use crate::pager::{HEADER_SIZE, Pager};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub depth: u32,
+    pub entries: u64,
+    pub leaf_pages: u32,
+    pub node_pages: u32,
+    pub free_pages: u32,
+    pub schema_pages: u32,
+    pub allocated_pages: u32,
+    pub errors: Vec<(Offset, String)>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, offset: Offset, message: impl Into<String>) {
+        self.errors.push((offset, message.into()));
+    }
+}
+
+struct Visit<K> {
+    offset: Offset,
+    parent: Offset,
+    depth: u32,
+    lower: Option<K>,
+    upper: Option<K>,
+}
+
+pub(crate) fn verify<K: Key, V: Value>(pager: &Pager<K, V>) -> Result<Report, DbError> {
+    let mut report = Report {
+        allocated_pages: pager.allocated_pages(),
+        ..Report::default()
+    };
+    let root = pager.get_root()?;
+    if root == 0 {
+        return Ok(report);
+    }
+    let mut visited = HashSet::new();
+    let mut leaves: Vec<(Offset, Offset, Offset)> = Vec::new();
+    let mut data_size = 0;
+    let mut stack = vec![Visit {
+        offset: root,
+        parent: 0,
+        depth: 1,
+        lower: None,
+        upper: None,
+    }];
+    while let Some(visit) = stack.pop() {
+        let offset = visit.offset;
+        if !pager.is_page_offset(offset) {
+            report.error(offset, "page offset out of range");
+            continue;
+        }
+        if !visited.insert(offset) {
+            report.error(offset, "page is referenced more than once");
+            continue;
+        }
+        let page = match pager.get_page(offset) {
+            Ok(page) => page,
+            Err(err) => {
+                report.error(offset, err.to_string());
+                continue;
+            }
+        };
+        if page.parent() != visit.parent {
+            report.error(
+                offset,
+                format!("parent is {}, expected {}", page.parent(), visit.parent),
+            );
+        }
+        match page {
+            Page::Node { children, .. } => {
+                report.node_pages += 1;
+                if children.is_empty() {
+                    report.error(offset, "node has no children");
+                    continue;
+                }
+                let keys: Vec<&K> = children.iter().skip(1).map(|(key, _)| key).collect();
+                check_keys(&mut report, offset, &keys, &visit);
+                for (i, (key, child)) in children.iter().enumerate().rev() {
+                    let lower = match i {
+                        0 => visit.lower.clone(),
+                        _ => Some(key.clone()),
+                    };
+                    let upper = match children.get(i + 1) {
+                        Some((key, _)) => Some(key.clone()),
+                        None => visit.upper.clone(),
+                    };
+                    stack.push(Visit {
+                        offset: *child,
+                        parent: offset,
+                        depth: visit.depth + 1,
+                        lower,
+                        upper,
+                    });
+                }
+            }
+            Page::Leaf {
+                prev, next, values, ..
+            } => {
+                report.leaf_pages += 1;
+                report.entries += values.len() as u64;
+                data_size += values
+                    .iter()
+                    .map(|(key, value)| (key.size() + value.size()) as u64)
+                    .sum::<u64>();
+                let keys: Vec<&K> = values.iter().map(|(key, _)| key).collect();
+                check_keys(&mut report, offset, &keys, &visit);
+                match report.depth {
+                    0 => report.depth = visit.depth,
+                    depth if depth != visit.depth => report.error(
+                        offset,
+                        format!("leaf at depth {}, expected {}", visit.depth, depth),
+                    ),
+                    _ => {}
+                }
+                leaves.push((offset, prev, next));
+            }
+        }
+    }
+    check_chain(&mut report, &leaves);
+    match pager.schema_pages() {
+        Ok(pages) => {
+            report.schema_pages = pages.len() as u32;
+            for offset in pages {
+                if !visited.insert(offset) {
+                    report.error(offset, "schema page is referenced more than once");
+                }
+            }
+        }
+        Err(DbError::Encoding) => report.error(0, "schema chain is corrupted"),
+        Err(err) => return Err(err),
+    }
+    check_free_pages(&mut report, pager, &visited)?;
+    check_stats(&mut report, pager, data_size)?;
+    Ok(report)
+}
+
+fn check_keys<K: Key>(report: &mut Report, offset: Offset, keys: &[&K], visit: &Visit<K>) {
+    for pair in keys.windows(2) {
+        if pair[0] >= pair[1] {
+            report.error(
+                offset,
+                format!("keys out of order: {:?} >= {:?}", pair[0], pair[1]),
+            );
+        }
+    }
+    if let (Some(lower), Some(first)) = (&visit.lower, keys.first())
+        && *first < lower
+    {
+        report.error(
+            offset,
+            format!("key {:?} below lower bound {:?}", first, lower),
+        );
+    }
+    if let (Some(upper), Some(last)) = (&visit.upper, keys.last())
+        && *last >= upper
+    {
+        report.error(
+            offset,
+            format!("key {:?} above upper bound {:?}", last, upper),
+        );
+    }
+}
+
+fn check_chain(report: &mut Report, leaves: &[(Offset, Offset, Offset)]) {
+    for (i, (offset, prev, next)) in leaves.iter().enumerate() {
+        let expected_prev = match i {
+            0 => 0,
+            _ => leaves[i - 1].0,
+        };
+        let expected_next = leaves.get(i + 1).map_or(0, |leaf| leaf.0);
+        if *prev != expected_prev {
+            report.error(
+                *offset,
+                format!("prev is {}, expected {}", prev, expected_prev),
+            );
+        }
+        if *next != expected_next {
+            report.error(
+                *offset,
+                format!("next is {}, expected {}", next, expected_next),
+            );
+        }
+    }
+}
+
+fn check_free_pages<K: Key, V: Value>(
+    report: &mut Report,
+    pager: &Pager<K, V>,
+    visited: &HashSet<Offset>,
+) -> Result<(), DbError> {
+    let freelist = match pager.freelist() {
+        Ok(freelist) => freelist,
+        Err(DbError::Encoding) => {
+            report.error(0, "freelist is corrupted");
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    report.free_pages = freelist.len() as u32;
+    let free: HashSet<Offset> = freelist.into_iter().collect();
+    for offset in free.intersection(visited) {
+        report.error(*offset, "page is both reachable and free");
+    }
+    let page_size = pager.page_size() as u32;
+    let pages = (0..pager.allocated_pages()).map(|i| HEADER_SIZE as u32 + i * page_size);
+    for offset in pages {
+        if !visited.contains(&offset) && !free.contains(&offset) {
+            report.error(offset, "page is neither reachable nor free");
+        }
+    }
+    Ok(())
+}
+
+fn check_stats<K: Key, V: Value>(
+    report: &mut Report,
+    pager: &Pager<K, V>,
+    data_size: u64,
+) -> Result<(), DbError> {
+    let stats = pager.get_stats()?;
+    let fields = [
+        ("entries", stats.entries, report.entries),
+        ("depth", stats.depth as u64, report.depth as u64),
+        (
+            "leaf_pages",
+            stats.leaf_pages as u64,
+            report.leaf_pages as u64,
+        ),
+        (
+            "node_pages",
+            stats.node_pages as u64,
+            report.node_pages as u64,
+        ),
+        (
+            "free_pages",
+            stats.free_pages as u64,
+            report.free_pages as u64,
+        ),
+        ("data_size", stats.data_size, data_size),
+    ];
+    for (name, recorded, found) in fields {
+        if recorded != found {
+            report.error(
+                0,
+                format!("stats {} is {}, found {}", name, recorded, found),
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use common::{error::DbError, read_num};
+
+const OFFSET_SIZE: usize = 8;
+const LEN_SIZE: usize = 4;
+const CHECKSUM_SIZE: usize = 4;
+
+pub(crate) type Record = (u64, Vec<u8>);
+
+#[derive(Clone)]
+pub(crate) struct Wal {
+    path: PathBuf,
+}
+
+impl Wal {
+    pub(crate) fn new(path: &Path) -> Self {
+        let mut wal_path = path.as_os_str().to_owned();
+        wal_path.push("-wal");
+        Self {
+            path: PathBuf::from(wal_path),
+        }
+    }
+
+    pub(crate) fn write(&self, records: &[Record]) -> Result<(), DbError> {
+        let mut buffer = Vec::new();
+        for (offset, data) in records {
+            buffer.extend_from_slice(&offset.to_be_bytes());
+            buffer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(data);
+        }
+        buffer.extend_from_slice(&checksum(&buffer).to_be_bytes());
+        let mut fd = File::create(&self.path)?;
+        fd.write_all(&buffer)?;
+        fd.sync_data()?;
+        Ok(())
+    }
+
+    pub(crate) fn read(&self) -> Result<Vec<Record>, DbError> {
+        let mut buffer = Vec::new();
+        match File::open(&self.path) {
+            Ok(mut fd) => {
+                fd.read_to_end(&mut buffer)?;
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        }
+        if buffer.len() < CHECKSUM_SIZE {
+            return Ok(vec![]);
+        }
+        let end = buffer.len() - CHECKSUM_SIZE;
+        if read_num!(buffer, u32, end) != checksum(&buffer[..end]) {
+            return Ok(vec![]);
+        }
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset + OFFSET_SIZE + LEN_SIZE <= end {
+            let page_offset = read_num!(buffer, u64, offset);
+            offset += OFFSET_SIZE;
+            let len = read_num!(buffer, u32, offset) as usize;
+            offset += LEN_SIZE;
+            if offset + len > end {
+                return Err(DbError::Encoding);
+            }
+            records.push((page_offset, buffer[offset..offset + len].to_vec()));
+            offset += len;
+        }
+        Ok(records)
+    }
+
+    pub(crate) fn clear(&self) -> Result<(), DbError> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn checksum(data: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in data {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let wal = Wal::new(tmpfile.path());
+        let records = vec![(0, vec![1, 2, 3, 4]), (16384, vec![5u8; 4096])];
+        wal.write(&records).unwrap();
+        assert_eq!(records, wal.read().unwrap());
+        wal.clear().unwrap();
+        assert!(wal.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn torn_write() {
+        let tmpfile = NamedTempFile::new().unwrap();
+        let wal = Wal::new(tmpfile.path());
+        wal.write(&[(0, vec![1, 2, 3, 4])]).unwrap();
+        let mut buffer = fs::read(&wal.path).unwrap();
+        buffer.truncate(buffer.len() - 1);
+        fs::write(&wal.path, buffer).unwrap();
+        assert!(wal.read().unwrap().is_empty());
+        wal.clear().unwrap();
+    }
+}
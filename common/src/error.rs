@@ -10,6 +10,8 @@ pub enum DbError {
     Unexpected(String),
     #[error("encoding exception")]
     Encoding,
+    #[error("page checksum mismatch")]
+    Checksum,
     #[error("max size error, received: {0}, limit: {1}")]
     MaxSize(usize, usize),
     #[error("unexpected EOF: {0}")]
@@ -18,8 +20,30 @@ pub enum DbError {
     InvalidInput(String),
     #[error("field '{0}' of relation '{1}' doesn't exist")]
     FieldNotFound(String, String),
+    #[error("relation '{0}' doesn't exist")]
+    TableNotFound(String),
     #[error("PRIMARY_KEY constraint is not set")]
     PrimaryKeyNotSet,
+    #[error("NOT_NULL constraint failed for field '{0}' of relation '{1}'")]
+    NotNull(String, String),
+    #[error("invalid value '{2}' for field '{0}' of relation '{1}'")]
+    InvalidValue(String, String, String),
+    #[error("value of field '{0}' of relation '{1}' is too long, received: {2}, limit: {3}")]
+    TooLong(String, String, usize, usize),
+    #[error("duplicate key '{1}' in relation '{0}'")]
+    DuplicateKey(String, String),
+    #[error("lock wait timeout exceeded for '{0}'")]
+    LockTimeout(String),
+    #[error("'{0}' is locked by another process")]
+    Locked(String),
+    #[error("'{0}' is opened read-only")]
+    ReadOnly(String),
+    #[error("query was cancelled")]
+    Cancelled,
+    #[error("query is out of memory budget, used: {0}, limit: {1}")]
+    OutOfMemoryBudget(usize, usize),
+    #[error("access denied for user '{0}'")]
+    AccessDenied(String),
 }
 
 impl DbError {
@@ -38,6 +62,64 @@ impl DbError {
     pub fn field_not_found(field: &str, relation: &str) -> Self {
         Self::FieldNotFound(field.to_string(), relation.to_string())
     }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::IO(_) => "58030",
+            Self::Unexpected(_) => "XX000",
+            Self::Encoding | Self::Checksum => "XX001",
+            Self::MaxSize(..) => "54000",
+            Self::EOF(_) => "42601",
+            Self::InvalidInput(_) => "22023",
+            Self::FieldNotFound(..) => "42703",
+            Self::TableNotFound(_) => "42P01",
+            Self::PrimaryKeyNotSet => "42P16",
+            Self::NotNull(..) => "23502",
+            Self::InvalidValue(..) => "22P02",
+            Self::TooLong(..) => "22001",
+            Self::DuplicateKey(..) => "23505",
+            Self::LockTimeout(_) => "55P03",
+            Self::Locked(_) => "55006",
+            Self::ReadOnly(_) => "25006",
+            Self::Cancelled => "57014",
+            Self::OutOfMemoryBudget(..) => "53200",
+            Self::AccessDenied(_) => "28000",
+        }
+    }
+
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            Self::FieldNotFound(_, table)
+            | Self::NotNull(_, table)
+            | Self::InvalidValue(_, table, _)
+            | Self::TooLong(_, table, ..)
+            | Self::TableNotFound(table)
+            | Self::DuplicateKey(table, _) => Some(table),
+            _ => None,
+        }
+    }
+
+    pub fn column(&self) -> Option<&str> {
+        match self {
+            Self::FieldNotFound(column, _)
+            | Self::NotNull(column, _)
+            | Self::InvalidValue(column, ..)
+            | Self::TooLong(column, ..) => Some(column),
+            _ => None,
+        }
+    }
+
+    pub fn constraint(&self) -> Option<&'static str> {
+        match self {
+            Self::NotNull(..) => Some("not_null"),
+            Self::DuplicateKey(..) | Self::PrimaryKeyNotSet => Some("primary_key"),
+            _ => None,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::LockTimeout(_) | Self::Locked(_))
+    }
 }
 
 impl From<std::io::Error> for DbError {
@@ -98,6 +180,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn structured() {
+        let duplicate = DbError::DuplicateKey("users".to_string(), "1".to_string());
+        assert_eq!("23505", duplicate.code());
+        assert_eq!(Some("users"), duplicate.table());
+        assert_eq!(None, duplicate.column());
+        assert_eq!(Some("primary_key"), duplicate.constraint());
+        assert!(!duplicate.is_retryable());
+
+        let not_null = DbError::NotNull("name".to_string(), "users".to_string());
+        assert_eq!("23502", not_null.code());
+        assert_eq!(Some("users"), not_null.table());
+        assert_eq!(Some("name"), not_null.column());
+        assert_eq!(Some("not_null"), not_null.constraint());
+
+        let timeout = DbError::LockTimeout("users".to_string());
+        assert_eq!("55P03", timeout.code());
+        assert_eq!(None, timeout.table());
+        assert!(timeout.is_retryable());
+        assert_eq!("42601", DbError::eof("expected ')'").code());
+        assert_eq!("53200", DbError::OutOfMemoryBudget(2, 1).code());
+        assert_eq!("28000", DbError::AccessDenied("root".to_string()).code());
+    }
+
     #[test]
     #[should_panic]
     fn from_parse_int_error() {
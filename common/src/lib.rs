@@ -26,12 +26,89 @@ macro_rules! read_num {
     }};
 }
 
+macro_rules! pageable_num {
+    ($($ty:ty),*) => {$(
+        impl Pageable for $ty {
+            fn write(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+                buffer[..SIZE].copy_from_slice(&self.to_be_bytes());
+                Ok(SIZE)
+            }
+
+            fn read(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+                if buffer.len() < std::mem::size_of::<$ty>() {
+                    return Err(DbError::Encoding);
+                }
+                Ok((read_num!(buffer, $ty), std::mem::size_of::<$ty>()))
+            }
+
+            fn size(&self) -> usize {
+                std::mem::size_of::<$ty>()
+            }
+        }
+    )*};
+}
+
+pageable_num!(i32, i64, u32, u64);
+
+const STRING_LEN_SIZE: usize = 2;
+
+impl Pageable for String {
+    fn write(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+        let len = self.len();
+        if len > u16::MAX as usize {
+            return Err(DbError::MaxSize(len, u16::MAX as usize));
+        }
+        buffer[..STRING_LEN_SIZE].copy_from_slice(&(len as u16).to_be_bytes());
+        buffer[STRING_LEN_SIZE..STRING_LEN_SIZE + len].copy_from_slice(self.as_bytes());
+        Ok(STRING_LEN_SIZE + len)
+    }
+
+    fn read(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+        if buffer.len() < STRING_LEN_SIZE {
+            return Err(DbError::Encoding);
+        }
+        let len = read_num!(buffer, u16) as usize;
+        let Some(value) = buffer.get(STRING_LEN_SIZE..STRING_LEN_SIZE + len) else {
+            return Err(DbError::Encoding);
+        };
+        let value = String::from_utf8(value.to_vec()).map_err(|_| DbError::Encoding)?;
+        Ok((value, STRING_LEN_SIZE + len))
+    }
+
+    fn size(&self) -> usize {
+        STRING_LEN_SIZE + self.len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn read_i32_num() {
         let num: i32 = 16;
         let bytes = num.to_be_bytes();
         assert_eq!(num, read_num!(bytes, i32));
     }
+
+    #[test]
+    fn pageable_num() {
+        let mut buffer = [0u8; 8];
+        assert_eq!(8, (-42i64).write(&mut buffer).unwrap());
+        assert_eq!((-42i64, 8), i64::read(&buffer).unwrap());
+        assert_eq!(Err(DbError::Encoding), u32::read(&buffer[..2]));
+    }
+
+    #[test]
+    fn pageable_string() {
+        let value = "ключ".to_string();
+        let mut buffer = vec![0u8; value.size()];
+        assert_eq!(value.size(), value.write(&mut buffer).unwrap());
+        assert_eq!(
+            (value.clone(), value.size()),
+            String::read(&buffer).unwrap()
+        );
+        assert_eq!(Err(DbError::Encoding), String::read(&buffer[..4]));
+    }
 }
@@ -0,0 +1,331 @@
+use std::{collections::BTreeMap, iter, mem, sync::Arc};
+
+use common::error::DbError;
+use row::{Col, ColType};
+
+use crate::{
+    batch::{self, Batches},
+    executor::Rows,
+    plan::{AggregateRef, ColumnRef, Function, SortKey, compare},
+    sort,
+    spill::{Budget, MAX_DEPTH, Partitions, footprint},
+};
+
+enum Accumulator {
+    Count(i64),
+    Sum(i64),
+    Avg(i64, i64),
+    Min(Option<Col>),
+    Max(Option<Col>),
+}
+
+impl Accumulator {
+    fn new(function: Function) -> Self {
+        match function {
+            Function::Count => Self::Count(0),
+            Function::Sum => Self::Sum(0),
+            Function::Avg => Self::Avg(0, 0),
+            Function::Min => Self::Min(None),
+            Function::Max => Self::Max(None),
+        }
+    }
+
+    fn update(&mut self, value: Option<&Col>) -> Result<(), DbError> {
+        match (self, value) {
+            (Self::Count(count), _) => *count += 1,
+            (Self::Sum(sum), Some(value)) => *sum = add(*sum, value)?,
+            (Self::Avg(sum, count), Some(value)) => {
+                *sum = add(*sum, value)?;
+                *count += 1;
+            }
+            (Self::Min(min), Some(value)) => {
+                if min.as_ref().is_none_or(|min| compare(value, min).is_lt()) {
+                    *min = Some(value.clone());
+                }
+            }
+            (Self::Max(max), Some(value)) => {
+                if max.as_ref().is_none_or(|max| compare(value, max).is_gt()) {
+                    *max = Some(value.clone());
+                }
+            }
+            _ => return Err(DbError::unexpected("aggregate without a column")),
+        }
+        Ok(())
+    }
+
+    fn update_all(&mut self, values: Option<&[Col]>, len: usize) -> Result<(), DbError> {
+        match (self, values) {
+            (Self::Count(count), _) => *count += len as i64,
+            (accumulator, Some(values)) => {
+                for value in values {
+                    accumulator.update(Some(value))?;
+                }
+            }
+            _ => return Err(DbError::unexpected("aggregate without a column")),
+        }
+        Ok(())
+    }
+
+    fn finish(self, col_type: &ColType) -> Col {
+        match self {
+            Self::Count(value) | Self::Sum(value) => Col::BigInt(value),
+            Self::Avg(_, 0) => Col::BigInt(0),
+            Self::Avg(sum, count) => Col::BigInt(sum / count),
+            Self::Min(value) | Self::Max(value) => value.unwrap_or_else(|| zero(col_type)),
+        }
+    }
+}
+
+pub(crate) fn aggregate(
+    batches: Batches,
+    group_by: &[ColumnRef],
+    aggregates: &[AggregateRef],
+    budget: &Budget,
+) -> Result<Rows, DbError> {
+    let aggregation = Aggregation {
+        group_by: group_by.into(),
+        aggregates: aggregates.into(),
+        budget: budget.clone(),
+    };
+    aggregation.run(batches, 0)
+}
+
+#[derive(Clone)]
+struct Aggregation {
+    group_by: Arc<[ColumnRef]>,
+    aggregates: Arc<[AggregateRef]>,
+    budget: Budget,
+}
+
+impl Aggregation {
+    fn start(&self) -> Vec<Accumulator> {
+        self.aggregates
+            .iter()
+            .map(|aggregate| Accumulator::new(aggregate.function))
+            .collect()
+    }
+
+    fn run(&self, batches: Batches, depth: u32) -> Result<Rows, DbError> {
+        let (group_by, aggregates) = (&self.group_by, &self.aggregates);
+        let mut groups: BTreeMap<Vec<Col>, Vec<Accumulator>> = BTreeMap::new();
+        let mut reservation = self.budget.reserve();
+        let mut spilled: Option<Partitions> = None;
+        if group_by.is_empty() {
+            groups.insert(vec![], self.start());
+        }
+        for batch in batches {
+            let batch = batch?;
+            if group_by.is_empty() {
+                let accumulators = groups.entry(vec![]).or_insert_with(|| self.start());
+                for (accumulator, aggregate) in accumulators.iter_mut().zip(aggregates.iter()) {
+                    let column = aggregate.column.as_ref().map(|c| batch.column(c.index));
+                    accumulator.update_all(column, batch.len())?;
+                }
+                continue;
+            }
+            for position in 0..batch.len() {
+                let key: Vec<Col> = group_by
+                    .iter()
+                    .map(|c| batch.column(c.index)[position].clone())
+                    .collect();
+                let accumulators = match groups.get_mut(&key) {
+                    Some(accumulators) => accumulators,
+                    None if reservation.bytes() < self.budget.limit() || depth >= MAX_DEPTH => {
+                        let size = aggregates.len() * mem::size_of::<Accumulator>();
+                        reservation.grow(footprint(&key) + size)?;
+                        groups.entry(key).or_insert_with(|| self.start())
+                    }
+                    None => {
+                        let partitions = match &mut spilled {
+                            Some(partitions) => partitions,
+                            None => spilled.insert(Partitions::new(&self.budget, depth)?),
+                        };
+                        partitions.write(&key, &batch.row(position))?;
+                        continue;
+                    }
+                };
+                for (accumulator, aggregate) in accumulators.iter_mut().zip(aggregates.iter()) {
+                    let value = aggregate
+                        .column
+                        .as_ref()
+                        .map(|c| &batch.column(c.index)[position]);
+                    accumulator.update(value)?;
+                }
+            }
+        }
+        let rows: Vec<Vec<Col>> = groups
+            .into_iter()
+            .map(|(mut key, accumulators)| {
+                for (accumulator, aggregate) in accumulators.into_iter().zip(aggregates.iter()) {
+                    key.push(accumulator.finish(&aggregate.col_type));
+                }
+                key
+            })
+            .collect();
+        let rows = reservation.hold(Box::new(rows.into_iter().map(Ok)));
+        let Some(partitions) = spilled else {
+            return Ok(rows);
+        };
+        let aggregation = self.clone();
+        let spilled = partitions.finish()?.into_iter().flat_map(move |run| {
+            match aggregation.run(batch::batched(run), depth + 1) {
+                Ok(rows) => rows,
+                Err(err) => Box::new(iter::once(Err(err))),
+            }
+        });
+        let keys = group_by
+            .iter()
+            .enumerate()
+            .map(|(index, column)| SortKey {
+                column: ColumnRef {
+                    index,
+                    name: column.name.clone(),
+                },
+                descending: false,
+            })
+            .collect();
+        sort::sort(Box::new(rows.chain(spilled)), keys, &self.budget)
+    }
+}
+
+fn add(sum: i64, value: &Col) -> Result<i64, DbError> {
+    let value = match value {
+        Col::Int(value) => *value as i64,
+        Col::BigInt(value) => *value,
+        Col::Varchar(_, _) => return Err(DbError::invalid_input("cannot add varchar")),
+    };
+    sum.checked_add(value)
+        .ok_or_else(|| DbError::invalid_input("integer overflow"))
+}
+
+fn zero(col_type: &ColType) -> Col {
+    match col_type {
+        ColType::Int(_) => Col::Int(0),
+        ColType::BigInt(_) => Col::BigInt(0),
+        ColType::Varchar(_, size) => Col::Varchar(String::new(), *size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch;
+
+    fn column(index: usize, name: &str) -> ColumnRef {
+        ColumnRef {
+            index,
+            name: name.to_string(),
+        }
+    }
+
+    fn aggregates() -> Vec<AggregateRef> {
+        let age = Some(column(1, "age"));
+        vec![
+            AggregateRef {
+                function: Function::Count,
+                column: None,
+                col_type: ColType::bigint(""),
+            },
+            AggregateRef {
+                function: Function::Sum,
+                column: age.clone(),
+                col_type: ColType::bigint(""),
+            },
+            AggregateRef {
+                function: Function::Avg,
+                column: age.clone(),
+                col_type: ColType::bigint(""),
+            },
+            AggregateRef {
+                function: Function::Min,
+                column: age.clone(),
+                col_type: ColType::int(""),
+            },
+            AggregateRef {
+                function: Function::Max,
+                column: age,
+                col_type: ColType::int(""),
+            },
+        ]
+    }
+
+    fn rows(rows: Vec<(&'static str, i32)>) -> Batches {
+        batch::batched(
+            rows.into_iter()
+                .map(|(name, age)| Ok(vec![Col::varchar(name, 8), Col::int(age)])),
+        )
+    }
+
+    fn run(input: Batches, group_by: &[ColumnRef], budget: usize) -> Vec<Vec<Col>> {
+        aggregate(input, group_by, &aggregates(), &Budget::new(budget, None))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn groups() {
+        let input = rows(vec![("bob", 30), ("ann", 20), ("bob", 41), ("ann", 25)]);
+        let result = run(input, &[column(0, "name")], usize::MAX);
+        let big = Col::big_int;
+        assert_eq!(
+            vec![
+                vec![
+                    Col::varchar("ann", 8),
+                    big(2),
+                    big(45),
+                    big(22),
+                    Col::int(20),
+                    Col::int(25),
+                ],
+                vec![
+                    Col::varchar("bob", 8),
+                    big(2),
+                    big(71),
+                    big(35),
+                    Col::int(30),
+                    Col::int(41),
+                ],
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        let result = run(rows(vec![]), &[], 0);
+        let zero = Col::big_int(0);
+        assert_eq!(
+            vec![vec![
+                zero.clone(),
+                zero.clone(),
+                zero,
+                Col::int(0),
+                Col::int(0)
+            ]],
+            result
+        );
+        let grouped = run(rows(vec![]), &[column(0, "name")], 0);
+        assert!(grouped.is_empty());
+    }
+
+    #[test]
+    fn spill() {
+        let names: Vec<String> = (0..200).map(|i| format!("n{:03}", (i * 7) % 200)).collect();
+        let input = |names: &[String]| {
+            let rows: Vec<Vec<Col>> = names
+                .iter()
+                .enumerate()
+                .flat_map(|(i, name)| {
+                    [10, 20].map(|age| vec![Col::varchar(name, 8), Col::int(age + i as i32 % 3)])
+                })
+                .collect();
+            batch::batched(rows.into_iter().map(Ok))
+        };
+        let group_by = [column(0, "name")];
+        let in_memory = run(input(&names), &group_by, usize::MAX);
+        assert_eq!(200, in_memory.len());
+        assert!(in_memory.is_sorted());
+        assert_eq!(in_memory, run(input(&names), &group_by, 512));
+    }
+}
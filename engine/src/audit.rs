@@ -0,0 +1,166 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use common::error::DbError;
+use parser::Command;
+
+use crate::{csv, exec_result::ExecResult};
+
+const HEADER: [&str; 5] = ["timestamp", "session", "statement", "count", "error"];
+
+pub(crate) struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub(crate) fn open(path: &Path) -> Result<Self, DbError> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(&line(&HEADER)?)?;
+        }
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub(crate) fn record(
+        &self,
+        session: u64,
+        command: &Command,
+        result: Result<Option<&ExecResult>, &DbError>,
+    ) -> Result<(), DbError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| DbError::unexpected("system clock is before the epoch"))?;
+        let (count, error) = match result {
+            Ok(Some(ExecResult::Rows { rows, .. })) => (rows.len().to_string(), String::new()),
+            Ok(Some(ExecResult::Affected { count, .. })) => (count.to_string(), String::new()),
+            Ok(Some(ExecResult::Ack { .. })) | Ok(None) => (String::new(), String::new()),
+            Err(err) => (String::new(), err.to_string()),
+        };
+        let line = line(&[
+            timestamp.as_millis().to_string(),
+            session.to_string(),
+            command.to_string(),
+            count,
+            error,
+        ])?;
+        self.lock()?.write_all(&line)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, File>, DbError> {
+        self.file
+            .lock()
+            .map_err(|_| DbError::unexpected("audit log lock is poisoned"))
+    }
+}
+
+fn line<T: AsRef<str>>(fields: &[T]) -> Result<Vec<u8>, DbError> {
+    let mut writer = csv::Writer::new(Vec::new(), ',');
+    writer.record(fields)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::BufReader, sync::Arc};
+
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn statements() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.csv");
+        let mut engine = Engine::in_memory();
+        engine.set_audit_log(&path).unwrap();
+        let engine = Arc::new(engine);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users (id INT, name VARCHAR(8))").unwrap();
+        execute("INSERT INTO users (id, name) VALUES (1, 'ann'), (2, 'bob')").unwrap();
+        assert!(execute("INSERT INTO users (id, name) VALUES (1, 'eve')").is_err());
+        let mut session = engine.session();
+        let id = session.id().to_string();
+        session
+            .query(parser::parse("SELECT id, name FROM users").unwrap())
+            .unwrap();
+        drop(session);
+        drop(engine);
+
+        let mut engine = Engine::in_memory();
+        engine.set_audit_log(&path).unwrap();
+        engine
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap_err();
+
+        let mut reader = csv::Reader::new(BufReader::new(File::open(&path).unwrap()), ',');
+        let mut records = Vec::new();
+        while let Some(record) = reader.record().unwrap() {
+            records.push(record);
+        }
+        assert_eq!(HEADER.to_vec(), records[0]);
+        assert!(records[1][0].parse::<u128>().unwrap() > 0);
+        let records: Vec<Vec<&str>> = records[1..]
+            .iter()
+            .map(|record| record[1..].iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            vec![
+                vec!["0", "CREATE TABLE users(id INT, name VARCHAR(8))", "", ""],
+                vec![
+                    "0",
+                    "INSERT INTO users(id, name) VALUES('1', 'ann'), ('2', 'bob')",
+                    "2",
+                    ""
+                ],
+                vec![
+                    "0",
+                    "INSERT INTO users(id, name) VALUES('1', 'eve')",
+                    "",
+                    "duplicate key '1' in relation 'users'"
+                ],
+                vec![&id, "SELECT id, name FROM users", "", ""],
+                vec![
+                    "0",
+                    "SELECT id FROM users",
+                    "",
+                    "relation 'users' doesn't exist"
+                ],
+            ],
+            records
+        );
+    }
+
+    #[test]
+    fn failed_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.csv");
+        File::create(&path).unwrap();
+        let mut engine = Engine::in_memory();
+        engine.audit = Some(AuditLog {
+            file: Mutex::new(File::open(&path).unwrap()),
+        });
+        let engine = Arc::new(engine);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(
+            Ok(ExecResult::ack("created")),
+            execute("CREATE TABLE users (id INT)")
+        );
+        assert_eq!(
+            Err(DbError::TableNotFound("missing".to_string())),
+            execute("SELECT id FROM missing")
+        );
+        let mut session = engine.session();
+        let mut cursor = session
+            .query(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert!(cursor.fetch(1).unwrap().is_empty());
+        assert_eq!(0, std::fs::metadata(&path).unwrap().len());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{iter, mem};
+
+use common::error::DbError;
+use row::Col;
+
+use crate::{
+    executor::Rows,
+    plan::{Condition, compare, holds},
+};
+
+pub(crate) const BATCH_SIZE: usize = 1024;
+
+pub(crate) type Batches = Box<dyn Iterator<Item = Result<Batch, DbError>> + Send>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Batch {
+    columns: Vec<Vec<Col>>,
+    len: usize,
+}
+
+impl Batch {
+    pub(crate) fn from_rows(rows: Vec<Vec<Col>>) -> Self {
+        let len = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+        let mut columns: Vec<Vec<Col>> = (0..width).map(|_| Vec::with_capacity(len)).collect();
+        for row in rows {
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.push(value);
+            }
+        }
+        Self { columns, len }
+    }
+
+    pub(crate) fn from_columns(columns: Vec<Vec<Col>>, len: usize) -> Self {
+        Self { columns, len }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn column(&self, index: usize) -> &[Col] {
+        &self.columns[index]
+    }
+
+    pub(crate) fn row(&self, index: usize) -> Vec<Col> {
+        self.columns
+            .iter()
+            .map(|column| column[index].clone())
+            .collect()
+    }
+
+    pub(crate) fn into_rows(self) -> Vec<Vec<Col>> {
+        let mut rows: Vec<Vec<Col>> = (0..self.len)
+            .map(|_| Vec::with_capacity(self.columns.len()))
+            .collect();
+        for column in self.columns {
+            for (row, value) in rows.iter_mut().zip(column) {
+                row.push(value);
+            }
+        }
+        rows
+    }
+
+    pub(crate) fn project(mut self, indexes: &[usize]) -> Self {
+        let columns = indexes
+            .iter()
+            .enumerate()
+            .map(
+                |(position, &index)| match indexes[position + 1..].contains(&index) {
+                    true => self.columns[index].clone(),
+                    false => mem::take(&mut self.columns[index]),
+                },
+            )
+            .collect();
+        Self {
+            columns,
+            len: self.len,
+        }
+    }
+
+    pub(crate) fn select(self, mask: &[bool]) -> Self {
+        let len = mask.iter().filter(|&&keep| keep).count();
+        if len == self.len {
+            return self;
+        }
+        let columns = self
+            .columns
+            .into_iter()
+            .map(|column| {
+                column
+                    .into_iter()
+                    .zip(mask)
+                    .filter_map(|(value, &keep)| keep.then_some(value))
+                    .collect()
+            })
+            .collect();
+        Self { columns, len }
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        for column in self.columns.iter_mut() {
+            column.truncate(len);
+        }
+        self.len = self.len.min(len);
+    }
+}
+
+pub(crate) fn mask(condition: &Condition, batch: &Batch) -> Vec<bool> {
+    match condition {
+        Condition::Compare { column, op, value } => batch
+            .column(column.index)
+            .iter()
+            .map(|col| holds(*op, compare(col, value)))
+            .collect(),
+        Condition::Call {
+            function,
+            column,
+            op,
+            value,
+        } => batch
+            .column(column.index)
+            .iter()
+            .map(|col| holds(*op, compare(&function.apply(col), value)))
+            .collect(),
+        Condition::And(left, right) => {
+            let mut mask = mask(left, batch);
+            for (keep, other) in mask.iter_mut().zip(self::mask(right, batch)) {
+                *keep &= other;
+            }
+            mask
+        }
+    }
+}
+
+pub(crate) fn batched(
+    rows: impl Iterator<Item = Result<Vec<Col>, DbError>> + Send + 'static,
+) -> Batches {
+    let mut rows = rows.fuse();
+    let mut failed = None;
+    Box::new(iter::from_fn(move || {
+        if let Some(err) = failed.take() {
+            return Some(Err(err));
+        }
+        let mut batch = Vec::new();
+        for row in rows.by_ref() {
+            match row {
+                Ok(row) => batch.push(row),
+                Err(err) if batch.is_empty() => return Some(Err(err)),
+                Err(err) => {
+                    failed = Some(err);
+                    break;
+                }
+            }
+            if batch.len() == BATCH_SIZE {
+                break;
+            }
+        }
+        (!batch.is_empty()).then(|| Ok(Batch::from_rows(batch)))
+    }))
+}
+
+pub(crate) fn rows(batches: Batches) -> Rows {
+    Box::new(batches.flat_map(|batch| match batch {
+        Ok(batch) => batch.into_rows().into_iter().map(Ok).collect::<Vec<_>>(),
+        Err(err) => vec![Err(err)],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plan::{ColumnRef, Operator};
+
+    use super::*;
+
+    fn rows(count: i32) -> Vec<Vec<Col>> {
+        (0..count)
+            .map(|i| vec![Col::int(i), Col::varchar(&format!("n{}", i % 3), 8)])
+            .collect()
+    }
+
+    #[test]
+    fn columns() {
+        let batch = Batch::from_rows(rows(4));
+        assert_eq!(4, batch.len());
+        assert_eq!(
+            [Col::int(0), Col::int(1), Col::int(2), Col::int(3)],
+            batch.column(0)
+        );
+        assert_eq!(vec![Col::int(2), Col::varchar("n2", 8)], batch.row(2));
+        assert_eq!(rows(4), batch.clone().into_rows());
+
+        let projected = batch.clone().project(&[1, 0, 1]);
+        assert_eq!(
+            vec![Col::varchar("n1", 8), Col::int(1), Col::varchar("n1", 8)],
+            projected.row(1)
+        );
+        let mut empty = batch.clone().project(&[]);
+        assert_eq!(4, empty.len());
+        assert_eq!(vec![Vec::<Col>::new(); 4], empty.clone().into_rows());
+        empty.truncate(1);
+        assert_eq!(1, empty.len());
+
+        let condition = Condition::And(
+            Box::new(Condition::Compare {
+                column: ColumnRef {
+                    index: 1,
+                    name: "name".to_string(),
+                },
+                op: Operator::Eq,
+                value: Col::varchar("n0", 2),
+            }),
+            Box::new(Condition::Compare {
+                column: ColumnRef {
+                    index: 0,
+                    name: "id".to_string(),
+                },
+                op: Operator::Gt,
+                value: Col::big_int(0),
+            }),
+        );
+        let mask = mask(&condition, &batch);
+        assert_eq!(vec![false, false, false, true], mask);
+        assert_eq!(
+            vec![vec![Col::int(3), Col::varchar("n0", 8)]],
+            batch.select(&mask).into_rows()
+        );
+    }
+
+    #[test]
+    fn batches() {
+        let total = BATCH_SIZE as i32 * 2 + 5;
+        let sizes: Vec<usize> = batched(rows(total).into_iter().map(Ok))
+            .map(|batch| batch.unwrap().len())
+            .collect();
+        assert_eq!(vec![BATCH_SIZE, BATCH_SIZE, 5], sizes);
+        let flattened = super::rows(batched(rows(total).into_iter().map(Ok)));
+        assert_eq!(
+            rows(total),
+            flattened.collect::<Result<Vec<_>, _>>().unwrap()
+        );
+
+        let input = vec![Ok(vec![Col::int(1)]), Err(DbError::Cancelled)];
+        let mut batches = batched(input.into_iter());
+        assert_eq!(1, batches.next().unwrap().unwrap().len());
+        assert_eq!(Some(Err(DbError::Cancelled)), batches.next());
+        assert!(batches.next().is_none());
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, MutexGuard,
+        mpsc::{self, Receiver, Sender},
+    },
+    time::Duration,
+};
+
+use btree::checksum;
+use common::{error::DbError, read_num};
+use row::{Col, Row};
+
+use crate::hooks::RowHooks;
+
+const START_SIZE: usize = 8;
+const LEN_SIZE: usize = 4;
+const CHECKSUM_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub commit: u64,
+    pub table: String,
+    pub op: ChangeOp,
+    pub key: Col,
+    pub before: Option<Vec<Col>>,
+    pub after: Option<Vec<Col>>,
+}
+
+pub struct Changes {
+    receiver: Receiver<Change>,
+}
+
+impl Changes {
+    pub fn try_next(&self) -> Option<Change> {
+        self.receiver.try_recv().ok()
+    }
+
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Change> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for Changes {
+    type Item = Change;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+pub(crate) struct Feed {
+    path: Option<PathBuf>,
+    read_only: bool,
+    retention: Option<u64>,
+    state: Mutex<State>,
+    hooks: RowHooks,
+}
+
+#[derive(Default)]
+struct State {
+    subscribers: Vec<Sender<Change>>,
+    log: Option<Log>,
+}
+
+#[derive(Default)]
+struct Log {
+    commit: u64,
+    first: u64,
+    retention: Option<u64>,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    retained: VecDeque<Change>,
+}
+
+pub(crate) struct ChangeSet {
+    table: String,
+    changes: Vec<(Col, Option<Row>, Option<Row>)>,
+}
+
+impl ChangeSet {
+    pub(crate) fn new(table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, key: Col, before: Option<Row>, after: Option<Row>) {
+        match (&before, &after) {
+            (Some(before), Some(after)) if before.columns[0] != after.columns[0] => {
+                self.changes.push((key, Some(before.clone()), None));
+                self.changes
+                    .push((after.columns[0].clone(), None, Some(after.clone())));
+            }
+            (None, None) => {}
+            _ => self.changes.push((key, before, after)),
+        }
+    }
+}
+
+impl Feed {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            read_only: false,
+            retention: None,
+            state: Mutex::default(),
+            hooks: RowHooks::default(),
+        }
+    }
+
+    pub(crate) fn read_only(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            read_only: true,
+            retention: None,
+            state: Mutex::default(),
+            hooks: RowHooks::default(),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> Result<Changes, DbError> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock()?.subscribers.push(sender);
+        Ok(Changes { receiver })
+    }
+
+    pub(crate) fn set_retention(&mut self, commits: u64) {
+        self.retention = Some(commits.max(1));
+        if let Ok(state) = self.state.get_mut() {
+            state.log = None;
+        }
+    }
+
+    pub(crate) fn logging(&self) -> bool {
+        self.retention.is_some()
+    }
+
+    pub(crate) fn active(&self) -> Result<bool, DbError> {
+        Ok(!self.lock()?.subscribers.is_empty())
+    }
+
+    pub(crate) fn watches(&self, table: &str) -> bool {
+        self.hooks.watches(table)
+    }
+
+    pub(crate) fn subscribe_from(&self, commit: u64) -> Result<Changes, DbError> {
+        let (sender, receiver) = mpsc::channel();
+        let mut state = self.lock()?;
+        for change in self.log(&mut state)?.replay(commit)? {
+            sender
+                .send(change)
+                .map_err(|_| DbError::unexpected("change receiver is closed"))?;
+        }
+        state.subscribers.push(sender);
+        Ok(Changes { receiver })
+    }
+
+    pub(crate) fn truncate(&self, commit: u64) -> Result<(), DbError> {
+        let mut state = self.lock()?;
+        let log = self.log(&mut state)?;
+        match log.retention {
+            Some(_) => log.truncate(commit),
+            None => Err(disabled()),
+        }
+    }
+
+    pub(crate) fn hooks_mut(&mut self) -> &mut RowHooks {
+        &mut self.hooks
+    }
+
+    pub(crate) fn notify(&self, sets: Vec<ChangeSet>) {
+        for set in sets {
+            for (_, before, after) in set.changes.iter() {
+                self.hooks
+                    .notify(&set.table, before.as_ref(), after.as_ref());
+            }
+        }
+    }
+
+    pub(crate) fn publish(&self, sets: &[ChangeSet]) -> Result<(), DbError> {
+        if sets.iter().all(|set| set.changes.is_empty()) {
+            return Ok(());
+        }
+        let mut state = self.lock()?;
+        if state.subscribers.is_empty() && !self.logging() {
+            return Ok(());
+        }
+        let log = self.log(&mut state)?;
+        let commit = log.commit + 1;
+        let mut changes = Vec::new();
+        for set in sets {
+            for (key, before, after) in set.changes.iter() {
+                changes.push(Change {
+                    commit,
+                    table: set.table.clone(),
+                    op: op(before.is_some(), after.is_some()),
+                    key: key.clone(),
+                    before: before.as_ref().map(|row| row.columns.clone()),
+                    after: after.as_ref().map(|row| row.columns.clone()),
+                });
+            }
+        }
+        log.append(commit, changes.clone())?;
+        for change in changes {
+            state
+                .subscribers
+                .retain(|subscriber| subscriber.send(change.clone()).is_ok());
+        }
+        Ok(())
+    }
+
+    fn log<'a>(&self, state: &'a mut State) -> Result<&'a mut Log, DbError> {
+        if state.log.is_none() {
+            state.log = Some(match self.retention {
+                Some(retention) => Log::open(self.path.as_deref(), self.read_only, retention)?,
+                None => Log::default(),
+            });
+        }
+        state
+            .log
+            .as_mut()
+            .ok_or_else(|| DbError::unexpected("change log is not open"))
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, State>, DbError> {
+        self.state
+            .lock()
+            .map_err(|_| DbError::unexpected("feed lock is poisoned"))
+    }
+}
+
+impl Log {
+    fn open(path: Option<&Path>, read_only: bool, retention: u64) -> Result<Self, DbError> {
+        let Some(path) = path else {
+            return Ok(Self {
+                first: 1,
+                retention: Some(retention),
+                ..Self::default()
+            });
+        };
+        let (start, changes, valid) = read_log(path)?;
+        let file = match read_only {
+            true => None,
+            false => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.set_len(valid)?;
+                if valid == 0 {
+                    file.write_all(&start.to_be_bytes())?;
+                }
+                file.sync_all()?;
+                Some(file)
+            }
+        };
+        let commit = changes
+            .last()
+            .map_or(start, |change| change.commit.max(start));
+        Ok(Self {
+            commit,
+            first: changes.first().map_or(commit + 1, |change| change.commit),
+            retention: Some(retention),
+            path: Some(path.to_path_buf()),
+            file,
+            retained: VecDeque::new(),
+        })
+    }
+
+    fn append(&mut self, commit: u64, changes: Vec<Change>) -> Result<(), DbError> {
+        self.commit = commit;
+        let Some(retention) = self.retention else {
+            return Ok(());
+        };
+        let oldest = commit.saturating_sub(retention) + 1;
+        match (&self.path, &mut self.file) {
+            (None, _) => {
+                self.retained.extend(changes);
+                while self
+                    .retained
+                    .front()
+                    .is_some_and(|change| change.commit < oldest)
+                {
+                    self.retained.pop_front();
+                }
+            }
+            (Some(_), Some(file)) => {
+                file.write_all(&record(commit, &changes)?)?;
+                file.sync_data()?;
+                if commit.saturating_sub(self.first) >= retention * 2 {
+                    self.truncate(oldest)?;
+                }
+            }
+            (Some(_), None) => return Err(DbError::unexpected("change log is read-only")),
+        }
+        Ok(())
+    }
+
+    fn replay(&self, commit: u64) -> Result<Vec<Change>, DbError> {
+        if self.retention.is_none() {
+            return Err(disabled());
+        }
+        let changes = match &self.path {
+            Some(path) => read_log(path)?.1,
+            None => self.retained.iter().cloned().collect(),
+        };
+        Ok(changes
+            .into_iter()
+            .filter(|change| change.commit >= commit)
+            .collect())
+    }
+
+    fn truncate(&mut self, commit: u64) -> Result<(), DbError> {
+        self.first = self.first.max(commit);
+        let Some(path) = &self.path else {
+            self.retained.retain(|change| change.commit >= commit);
+            return Ok(());
+        };
+        if self.file.is_none() {
+            return Err(DbError::unexpected("change log is read-only"));
+        }
+        let mut buffer = self.commit.to_be_bytes().to_vec();
+        let (_, changes, _) = read_log(path)?;
+        let kept: Vec<Change> = changes
+            .into_iter()
+            .filter(|change| change.commit >= commit)
+            .collect();
+        for group in kept.chunk_by(|a, b| a.commit == b.commit) {
+            buffer.extend(record(group[0].commit, group)?);
+        }
+        let mut pending = path.as_os_str().to_owned();
+        pending.push(".tmp");
+        let mut file = File::create(&pending)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        fs::rename(&pending, path)?;
+        self.file = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(())
+    }
+}
+
+fn disabled() -> DbError {
+    DbError::invalid_input("change log is not enabled")
+}
+
+fn op(before: bool, after: bool) -> ChangeOp {
+    match (before, after) {
+        (false, _) => ChangeOp::Insert,
+        (true, true) => ChangeOp::Update,
+        (true, false) => ChangeOp::Delete,
+    }
+}
+
+fn read_log(path: &Path) -> Result<(u64, Vec<Change>, u64), DbError> {
+    let buffer = match fs::read(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, Vec::new(), 0)),
+        buffer => buffer?,
+    };
+    if buffer.len() < START_SIZE {
+        return Ok((0, Vec::new(), 0));
+    }
+    let start = read_num!(buffer, u64);
+    let mut changes = Vec::new();
+    let mut offset = START_SIZE;
+    while offset + LEN_SIZE <= buffer.len() {
+        let len = read_num!(buffer, u32, offset) as usize;
+        let end = offset + LEN_SIZE + len;
+        if end + CHECKSUM_SIZE > buffer.len() {
+            break;
+        }
+        let payload = &buffer[offset + LEN_SIZE..end];
+        if read_num!(buffer, u32, end) != checksum(payload) {
+            break;
+        }
+        changes.extend(decode(payload)?);
+        offset = end + CHECKSUM_SIZE;
+    }
+    Ok((start, changes, offset as u64))
+}
+
+fn record(commit: u64, changes: &[Change]) -> Result<Vec<u8>, DbError> {
+    let mut payload = commit.to_be_bytes().to_vec();
+    payload.extend_from_slice(&(changes.len() as u32).to_be_bytes());
+    for change in changes {
+        payload.extend_from_slice(&(change.table.len() as u16).to_be_bytes());
+        payload.extend_from_slice(change.table.as_bytes());
+        put_col(&mut payload, &change.key)?;
+        for image in [&change.before, &change.after] {
+            match image {
+                Some(columns) => {
+                    payload.push(1);
+                    payload.extend_from_slice(&(columns.len() as u16).to_be_bytes());
+                    for col in columns {
+                        put_col(&mut payload, col)?;
+                    }
+                }
+                None => payload.push(0),
+            }
+        }
+    }
+    let mut record = (payload.len() as u32).to_be_bytes().to_vec();
+    record.extend_from_slice(&payload);
+    record.extend_from_slice(&checksum(&payload).to_be_bytes());
+    Ok(record)
+}
+
+fn decode(payload: &[u8]) -> Result<Vec<Change>, DbError> {
+    let commit = read_num!(payload, u64);
+    let mut offset = 8;
+    let count = read_num!(payload, u32, offset);
+    offset += 4;
+    let mut changes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let len = read_num!(payload, u16, offset) as usize;
+        offset += 2;
+        let table = String::from_utf8_lossy(&payload[offset..offset + len]).to_string();
+        offset += len;
+        let (key, size) = Col::read_compact(&payload[offset..])?;
+        offset += size;
+        let mut images = [None, None];
+        for image in images.iter_mut() {
+            offset += 1;
+            if payload[offset - 1] == 0 {
+                continue;
+            }
+            let len = read_num!(payload, u16, offset);
+            offset += 2;
+            let mut columns = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let (col, size) = Col::read_compact(&payload[offset..])?;
+                offset += size;
+                columns.push(col);
+            }
+            *image = Some(columns);
+        }
+        let [before, after] = images;
+        changes.push(Change {
+            commit,
+            table,
+            op: op(before.is_some(), after.is_some()),
+            key,
+            before,
+            after,
+        });
+    }
+    Ok(changes)
+}
+
+fn put_col(buffer: &mut Vec<u8>, col: &Col) -> Result<(), DbError> {
+    let start = buffer.len();
+    buffer.resize(start + col.compact_size(), 0);
+    col.write_compact(&mut buffer[start..])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i32, name: &str) -> Row {
+        Row {
+            columns: vec![Col::int(id), Col::varchar(name, 8)],
+        }
+    }
+
+    fn set(table: &str, key: i32, before: Option<Row>, after: Option<Row>) -> ChangeSet {
+        let mut set = ChangeSet::new(table);
+        set.push(Col::int(key), before, after);
+        set
+    }
+
+    fn summary(changes: &Changes) -> Vec<(u64, ChangeOp, Col)> {
+        std::iter::from_fn(|| changes.try_next())
+            .map(|change| (change.commit, change.op, change.key))
+            .collect()
+    }
+
+    fn logged(path: Option<PathBuf>) -> Feed {
+        let mut feed = Feed::new(path);
+        feed.set_retention(10);
+        feed
+    }
+
+    #[test]
+    fn disabled_log() {
+        let feed = Feed::new(None);
+        feed.publish(&[set("users", 1, None, Some(row(1, "ann")))])
+            .unwrap();
+        assert!(feed.subscribe_from(0).is_err());
+        let changes = feed.subscribe().unwrap();
+        feed.publish(&[set("users", 2, None, Some(row(2, "bob")))])
+            .unwrap();
+        assert_eq!(vec![(1, ChangeOp::Insert, Col::int(2))], summary(&changes));
+        assert!(feed.truncate(1).is_err());
+    }
+
+    #[test]
+    fn publish() {
+        let feed = logged(None);
+        feed.publish(&[set("users", 1, None, Some(row(1, "ann")))])
+            .unwrap();
+
+        let changes = feed.subscribe().unwrap();
+        let mut users = ChangeSet::new("users");
+        users.push(Col::int(1), Some(row(1, "ann")), Some(row(1, "bob")));
+        users.push(Col::int(2), Some(row(2, "eve")), Some(row(3, "eve")));
+        let orders = set("orders", 7, Some(row(7, "x")), None);
+        feed.publish(&[users, orders]).unwrap();
+        feed.publish(&[ChangeSet::new("users")]).unwrap();
+        assert_eq!(
+            vec![
+                (2, ChangeOp::Update, Col::int(1)),
+                (2, ChangeOp::Delete, Col::int(2)),
+                (2, ChangeOp::Insert, Col::int(3)),
+                (2, ChangeOp::Delete, Col::int(7)),
+            ],
+            summary(&changes)
+        );
+        drop(changes);
+        feed.publish(&[set("users", 4, None, Some(row(4, "cid")))])
+            .unwrap();
+        assert!(feed.lock().unwrap().subscribers.is_empty());
+
+        let replayed = feed.subscribe_from(0).unwrap();
+        assert_eq!(
+            vec![
+                (1, ChangeOp::Insert, Col::int(1)),
+                (2, ChangeOp::Update, Col::int(1)),
+                (2, ChangeOp::Delete, Col::int(2)),
+                (2, ChangeOp::Insert, Col::int(3)),
+                (2, ChangeOp::Delete, Col::int(7)),
+                (3, ChangeOp::Insert, Col::int(4)),
+            ],
+            summary(&replayed)
+        );
+        feed.truncate(3).unwrap();
+        feed.publish(&[set("users", 4, Some(row(4, "cid")), None)])
+            .unwrap();
+        assert_eq!(vec![(4, ChangeOp::Delete, Col::int(4))], summary(&replayed));
+        assert_eq!(
+            vec![
+                (3, ChangeOp::Insert, Col::int(4)),
+                (4, ChangeOp::Delete, Col::int(4)),
+            ],
+            summary(&feed.subscribe_from(0).unwrap())
+        );
+    }
+
+    #[test]
+    fn persisted_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("changes");
+        let feed = logged(Some(path.clone()));
+        feed.publish(&[set("users", 1, None, Some(row(1, "ann")))])
+            .unwrap();
+        let mut users = ChangeSet::new("users");
+        users.push(Col::int(1), Some(row(1, "ann")), Some(row(1, "bob")));
+        users.push(Col::int(2), None, Some(row(2, "eve")));
+        feed.publish(&[users]).unwrap();
+        drop(feed);
+
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(&[0, 0, 0, 9, 1, 2]).unwrap();
+        drop(log);
+        let feed = logged(Some(path.clone()));
+        let changes = feed.subscribe_from(2).unwrap();
+        let received: Vec<Change> = std::iter::from_fn(|| changes.try_next()).collect();
+        assert_eq!(
+            vec![
+                Change {
+                    commit: 2,
+                    table: "users".to_string(),
+                    op: ChangeOp::Update,
+                    key: Col::int(1),
+                    before: Some(row(1, "ann").columns),
+                    after: Some(row(1, "bob").columns),
+                },
+                Change {
+                    commit: 2,
+                    table: "users".to_string(),
+                    op: ChangeOp::Insert,
+                    key: Col::int(2),
+                    before: None,
+                    after: Some(row(2, "eve").columns),
+                },
+            ],
+            received
+        );
+        feed.publish(&[set("users", 2, Some(row(2, "eve")), None)])
+            .unwrap();
+        assert_eq!(vec![(3, ChangeOp::Delete, Col::int(2))], summary(&changes));
+
+        feed.truncate(4).unwrap();
+        drop(feed);
+        let feed = logged(Some(path.clone()));
+        assert!(summary(&feed.subscribe_from(0).unwrap()).is_empty());
+        feed.publish(&[set("users", 3, None, Some(row(3, "cid")))])
+            .unwrap();
+        drop(feed);
+
+        let mut feed = Feed::read_only(path);
+        feed.set_retention(10);
+        assert_eq!(
+            vec![(4, ChangeOp::Insert, Col::int(3))],
+            summary(&feed.subscribe_from(0).unwrap())
+        );
+        assert!(feed.truncate(0).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use common::error::DbError;
+use row::{Col, ColType};
+
+pub(crate) fn parse(table: &str, col_type: &ColType, value: String) -> Result<Col, DbError> {
+    let invalid = || invalid(table, col_type, &value);
+    Ok(match col_type {
+        ColType::Int(_) => Col::Int(
+            integer(&value)
+                .and_then(|value| i32::try_from(value).ok())
+                .ok_or_else(invalid)?,
+        ),
+        ColType::BigInt(_) => Col::BigInt(integer(&value).ok_or_else(invalid)?),
+        ColType::Varchar(_, size) => Col::Varchar(value, *size),
+    })
+}
+
+pub(crate) fn convert(value: Col, table: &str, target: &ColType) -> Result<Col, DbError> {
+    Ok(match (value, target) {
+        (Col::Int(value), ColType::Int(_)) => Col::Int(value),
+        (Col::BigInt(value), ColType::Int(_)) => match i32::try_from(value) {
+            Ok(value) => Col::Int(value),
+            Err(_) => return Err(invalid(table, target, &value.to_string())),
+        },
+        (Col::Int(value), ColType::BigInt(_)) => Col::BigInt(value as i64),
+        (Col::BigInt(value), ColType::BigInt(_)) => Col::BigInt(value),
+        (Col::Varchar(value, _), ColType::Varchar(_, size)) => Col::Varchar(value, *size),
+        (Col::Int(value), ColType::Varchar(_, size)) => Col::Varchar(value.to_string(), *size),
+        (Col::BigInt(value), ColType::Varchar(_, size)) => Col::Varchar(value.to_string(), *size),
+        (Col::Varchar(value, _), target) => parse(table, target, value)?,
+    })
+}
+
+fn integer(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(value) = value.parse() {
+        return Some(value);
+    }
+    let (integral, fraction) = value.split_once('.')?;
+    if !fraction.bytes().all(|b| b == b'0') || integral.trim_start_matches(['+', '-']).is_empty() {
+        return None;
+    }
+    integral.parse().ok()
+}
+
+fn invalid(table: &str, col_type: &ColType, value: &str) -> DbError {
+    DbError::InvalidValue(
+        col_type.get_name().to_string(),
+        table.to_string(),
+        value.to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let int = ColType::int("age");
+        let bigint = ColType::bigint("age");
+        let parse = |col_type: &ColType, value: &str| parse("t", col_type, value.to_string());
+        let invalid = |value: &str| {
+            Err(DbError::InvalidValue(
+                "age".to_string(),
+                "t".to_string(),
+                value.to_string(),
+            ))
+        };
+        assert_eq!(Ok(Col::int(42)), parse(&int, " 42 "));
+        assert_eq!(Ok(Col::int(-42)), parse(&int, "-42.00"));
+        assert_eq!(Ok(Col::int(42)), parse(&int, "+42."));
+        assert_eq!(invalid("42.5"), parse(&int, "42.5"));
+        assert_eq!(invalid(".0"), parse(&int, ".0"));
+        assert_eq!(invalid("3000000000"), parse(&int, "3000000000"));
+        assert_eq!(Ok(Col::big_int(3000000000)), parse(&bigint, "3000000000"));
+        assert_eq!(invalid(""), parse(&bigint, ""));
+        assert_eq!(
+            Ok(Col::varchar(" 42 ", 8)),
+            parse(&ColType::varchar("age", 8), " 42 ")
+        );
+        assert_eq!(
+            "invalid value 'abc' for field 'age' of relation 't'",
+            parse(&int, "abc").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn convert_values() {
+        let int = ColType::int("age");
+        let bigint = ColType::bigint("age");
+        assert_eq!(Ok(Col::big_int(7)), convert(Col::int(7), "t", &bigint));
+        assert_eq!(Ok(Col::int(7)), convert(Col::big_int(7), "t", &int));
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "age".to_string(),
+                "t".to_string(),
+                "4294967296".to_string()
+            )),
+            convert(Col::big_int(1 << 32), "t", &int)
+        );
+        assert_eq!(Ok(Col::int(7)), convert(Col::varchar("7", 8), "t", &int));
+        assert_eq!(
+            Ok(Col::varchar("7", 8)),
+            convert(Col::big_int(7), "t", &ColType::varchar("age", 8))
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use common::error::DbError;
+use row::{Col, ColType, RowType};
+
+use crate::exec_result::Warnings;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VarcharMode {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+pub(crate) fn check(
+    table: &str,
+    row_type: &RowType,
+    row: &mut [Col],
+    mode: VarcharMode,
+    warnings: &mut Warnings,
+) -> Result<(), DbError> {
+    if mode == VarcharMode::Lenient {
+        truncate(table, row_type, row, warnings);
+    }
+    validate(table, row_type, row)
+}
+
+fn truncate(table: &str, row_type: &RowType, row: &mut [Col], warnings: &mut Warnings) {
+    for (col_type, col) in row_type.columns.iter().zip(row) {
+        let (ColType::Varchar(name, size), Col::Varchar(value, _)) = (col_type, col) else {
+            continue;
+        };
+        let len = value.len();
+        let mut end = (*size as usize).min(len);
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < len {
+            value.truncate(end);
+            warnings.push(format!(
+                "value of field '{}' of relation '{}' truncated from {} to {} bytes",
+                name, table, len, end
+            ));
+        }
+    }
+}
+
+pub(crate) fn validate(table: &str, row_type: &RowType, row: &[Col]) -> Result<(), DbError> {
+    for (col_type, col) in row_type.columns.iter().zip(row) {
+        match (col_type, col) {
+            (ColType::Int(_), Col::Int(_)) | (ColType::BigInt(_), Col::BigInt(_)) => {}
+            (ColType::Varchar(name, size), Col::Varchar(value, _)) => {
+                if value.len() > *size as usize {
+                    return Err(DbError::TooLong(
+                        name.clone(),
+                        table.to_string(),
+                        value.len(),
+                        *size as usize,
+                    ));
+                }
+            }
+            (col_type, col) => {
+                return Err(DbError::InvalidValue(
+                    col_type.get_name().to_string(),
+                    table.to_string(),
+                    format!("{:?}", col),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_row() {
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 4)],
+            constraints: vec![],
+        };
+        assert!(validate("t", &row_type, &[Col::int(1), Col::varchar("ann", 4)]).is_ok());
+        assert_eq!(
+            Err(DbError::TooLong("name".to_string(), "t".to_string(), 5, 4)),
+            validate("t", &row_type, &[Col::int(1), Col::varchar("alice", 4)])
+        );
+        assert!(validate("t", &row_type, &[Col::big_int(1), Col::varchar("a", 4)]).is_err());
+    }
+
+    #[test]
+    fn truncate_varchar() {
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 4)],
+            constraints: vec![],
+        };
+        let mut warnings = Warnings::default();
+        let mut row = vec![Col::int(1), Col::varchar("añnie", 4)];
+        assert_eq!(
+            Err(DbError::TooLong("name".to_string(), "t".to_string(), 6, 4)),
+            check(
+                "t",
+                &row_type,
+                &mut row.clone(),
+                VarcharMode::Strict,
+                &mut warnings
+            )
+        );
+        check(
+            "t",
+            &row_type,
+            &mut row,
+            VarcharMode::Lenient,
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(vec![Col::int(1), Col::varchar("añn", 4)], row);
+        assert_eq!(
+            vec!["value of field 'name' of relation 't' truncated from 6 to 4 bytes"],
+            warnings.into_vec()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::{BufRead, Write},
+    mem,
+};
+
+use common::error::DbError;
+
+const QUOTE: char = '"';
+
+pub(crate) struct Reader<R> {
+    input: R,
+    delimiter: char,
+    line: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub(crate) fn new(input: R, delimiter: char) -> Self {
+        Self {
+            input,
+            delimiter,
+            line: 0,
+        }
+    }
+
+    pub(crate) fn line(&self) -> usize {
+        self.line
+    }
+
+    pub(crate) fn record(&mut self) -> Result<Option<Vec<String>>, DbError> {
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                if quoted {
+                    return Err(DbError::EOF(format!(
+                        "unterminated quoted field on line {}",
+                        self.line
+                    )));
+                }
+                return Ok(None);
+            }
+            self.line += 1;
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() && !quoted {
+                continue;
+            }
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    QUOTE if quoted && chars.peek() == Some(&QUOTE) => {
+                        chars.next();
+                        field.push(QUOTE);
+                    }
+                    QUOTE if quoted => quoted = false,
+                    QUOTE if field.is_empty() => quoted = true,
+                    c if c == self.delimiter && !quoted => record.push(mem::take(&mut field)),
+                    c => field.push(c),
+                }
+            }
+            if !quoted {
+                record.push(field);
+                return Ok(Some(record));
+            }
+            field.push('\n');
+        }
+    }
+}
+
+pub(crate) struct Writer<W> {
+    output: W,
+    delimiter: char,
+}
+
+impl<W: Write> Writer<W> {
+    pub(crate) fn new(output: W, delimiter: char) -> Self {
+        Self { output, delimiter }
+    }
+
+    pub(crate) fn record<T: AsRef<str>>(&mut self, fields: &[T]) -> Result<(), DbError> {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(self.output, "{}", self.delimiter)?;
+            }
+            let field = field.as_ref();
+            if field.contains([self.delimiter, QUOTE, '\n', '\r']) {
+                write!(self.output, "\"{}\"", field.replace(QUOTE, "\"\""))?;
+            } else {
+                self.output.write_all(field.as_bytes())?;
+            }
+        }
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W, DbError> {
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &str, delimiter: char) -> Result<Vec<Vec<String>>, DbError> {
+        let mut reader = Reader::new(input.as_bytes(), delimiter);
+        let mut records = Vec::new();
+        while let Some(record) = reader.record()? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn read() {
+        let input = "id,name\r\n1,ann\n\n2,\"bob, \"\"jr\"\"\"\n3,\"multi\nline\"\n4,\n";
+        assert_eq!(
+            vec![
+                vec!["id", "name"],
+                vec!["1", "ann"],
+                vec!["2", "bob, \"jr\""],
+                vec!["3", "multi\nline"],
+                vec!["4", ""],
+            ],
+            records(input, ',').unwrap()
+        );
+        assert_eq!(vec![vec!["1", "a,b"]], records("1;a,b", ';').unwrap());
+        assert_eq!(
+            Err(DbError::eof("unterminated quoted field on line 1")),
+            records("1,\"ann\n", ',').map(|_| ())
+        );
+    }
+    #[test]
+    fn write() {
+        let mut writer = Writer::new(Vec::new(), ',');
+        writer.record(&["id", "name"]).unwrap();
+        writer.record(&["1", "bob, \"jr\""]).unwrap();
+        writer.record(&["2", "multi\nline"]).unwrap();
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            "id,name\n1,\"bob, \"\"jr\"\"\"\n2,\"multi\nline\"\n",
+            output
+        );
+        assert_eq!(
+            vec![
+                vec!["id", "name"],
+                vec!["1", "bob, \"jr\""],
+                vec!["2", "multi\nline"],
+            ],
+            records(&output, ',').unwrap()
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use common::error::DbError;
+use row::{Col, ColType};
+
+use crate::{exec_result::ExecResult, executor::Rows};
+
+pub struct Cursor {
+    columns: Vec<String>,
+    types: Vec<ColType>,
+    rows: Rows,
+}
+
+impl Cursor {
+    pub(crate) fn new(columns: Vec<String>, types: Vec<ColType>, rows: Rows) -> Self {
+        Self {
+            columns,
+            types,
+            rows,
+        }
+    }
+
+    pub fn field_names(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn types(&self) -> &[ColType] {
+        &self.types
+    }
+
+    pub fn fetch(&mut self, count: usize) -> Result<Vec<Vec<Col>>, DbError> {
+        self.rows.by_ref().take(count).collect()
+    }
+
+    pub fn into_result(self) -> Result<ExecResult, DbError> {
+        Ok(ExecResult::Rows {
+            rows: self.rows.collect::<Result<_, _>>()?,
+            columns: self.columns,
+            types: self.types,
+        })
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = Result<Vec<Col>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+impl From<ExecResult> for Cursor {
+    fn from(result: ExecResult) -> Self {
+        let (columns, types, rows) = match result {
+            ExecResult::Rows {
+                columns,
+                types,
+                rows,
+            } => (columns, types, rows),
+            ExecResult::Affected { op, count, .. } => (
+                vec![op.clone()],
+                vec![ColType::bigint(&op)],
+                vec![vec![Col::BigInt(count as i64)]],
+            ),
+            ExecResult::Ack { .. } => (vec![], vec![], vec![]),
+        };
+        Self::new(columns, types, Box::new(rows.into_iter().map(Ok)))
+    }
+}
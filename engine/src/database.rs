@@ -0,0 +1,300 @@
+use std::collections::BTreeSet;
+
+use parser::Command;
+
+pub(crate) const MAIN: &str = "main";
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Namespace {
+    database: Option<String>,
+    schema: Option<String>,
+    temporary: BTreeSet<String>,
+}
+
+impl Namespace {
+    pub(crate) fn new(database: Option<&str>) -> Self {
+        Self {
+            database: database.map(str::to_string),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+
+    pub(crate) fn set_database(&mut self, database: String) {
+        self.database = Some(database).filter(|database| database != MAIN);
+    }
+
+    pub(crate) fn schema(&self) -> Option<&str> {
+        self.schema.as_deref()
+    }
+
+    pub(crate) fn set_schema(&mut self, schema: String) {
+        self.schema = Some(schema);
+    }
+
+    pub(crate) fn temporary(&self) -> Vec<String> {
+        let Some(schema) = &self.schema else {
+            return vec![];
+        };
+        self.temporary
+            .iter()
+            .map(|name| format!("{}.{}", schema, name))
+            .collect()
+    }
+
+    pub(crate) fn created(&mut self, relation: &str) {
+        if let Some(name) = self.local(relation) {
+            self.temporary.insert(name);
+        }
+    }
+
+    pub(crate) fn dropped(&mut self, relation: &str) {
+        if let Some(name) = self.local(relation) {
+            self.temporary.remove(&name);
+        }
+    }
+
+    fn local(&self, relation: &str) -> Option<String> {
+        self.schema
+            .as_deref()
+            .and_then(|schema| local(relation, Some(schema)))
+    }
+
+    fn relation(&self, name: String) -> String {
+        match &self.schema {
+            Some(schema) if self.temporary.contains(&name) => format!("{}.{}", schema, name),
+            _ => relation(name, self.database()),
+        }
+    }
+}
+
+pub(crate) fn scoped(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Use { .. }
+            | Command::Create {
+                temporary: true,
+                ..
+            }
+            | Command::Drop { .. }
+            | Command::RenameTable { .. }
+    )
+}
+
+pub(crate) fn qualify(command: Command, namespace: &Namespace) -> Command {
+    let name = |name: String| namespace.relation(name);
+    match command {
+        Command::Create {
+            name: table,
+            fields,
+            constraints,
+            temporary: true,
+        } => Command::Create {
+            name: match namespace.schema() {
+                Some(schema) if !table.contains('.') => format!("{}.{}", schema, table),
+                _ => table,
+            },
+            fields,
+            constraints,
+            temporary: true,
+        },
+        Command::Create {
+            name: table,
+            fields,
+            constraints,
+            temporary,
+        } => Command::Create {
+            name: name(table),
+            fields,
+            constraints,
+            temporary,
+        },
+        Command::CreateIndex {
+            name: index,
+            table,
+            columns,
+        } => Command::CreateIndex {
+            name: index,
+            table: name(table),
+            columns,
+        },
+        Command::CreateSequence {
+            name: sequence,
+            start,
+        } => Command::CreateSequence {
+            name: name(sequence),
+            start,
+        },
+        Command::CreateView {
+            name: view,
+            columns,
+            query,
+        } => Command::CreateView {
+            name: name(view),
+            columns,
+            query,
+        },
+        Command::Insert {
+            table,
+            fields,
+            values,
+            replace,
+        } => Command::Insert {
+            table: name(table),
+            fields,
+            values,
+            replace,
+        },
+        Command::Select {
+            distinct,
+            fields,
+            table,
+            conditions,
+            group_by,
+            having,
+            order_by,
+            limit,
+        } => Command::Select {
+            distinct,
+            fields,
+            table: name(table),
+            conditions,
+            group_by,
+            having,
+            order_by,
+            limit,
+        },
+        Command::Update {
+            table,
+            assignments,
+            conditions,
+        } => Command::Update {
+            table: name(table),
+            assignments,
+            conditions,
+        },
+        Command::Delete { table } => Command::Delete { table: name(table) },
+        Command::Drop { table } => Command::Drop { table: name(table) },
+        Command::DropSequence { name: sequence } => Command::DropSequence {
+            name: name(sequence),
+        },
+        Command::DropView { name: view } => Command::DropView { name: name(view) },
+        Command::RenameTable { table, name: to } => {
+            let table = name(table);
+            let to = match split(&table) {
+                (Some(database), _) if !to.contains('.') => format!("{}.{}", database, to),
+                _ => relation(to, None),
+            };
+            Command::RenameTable { table, name: to }
+        }
+        Command::NextVal { sequence } => Command::NextVal {
+            sequence: name(sequence),
+        },
+        Command::Vacuum { table } => Command::Vacuum {
+            table: table.map(name),
+        },
+        Command::Analyze { table } => Command::Analyze {
+            table: table.map(name),
+        },
+        Command::ShowTableStatus { table } => Command::ShowTableStatus { table: name(table) },
+        Command::CheckTable { table } => Command::CheckTable { table: name(table) },
+        Command::Explain { command, analyze } => Command::Explain {
+            command: Box::new(qualify(*command, namespace)),
+            analyze,
+        },
+        Command::CopyFrom {
+            table,
+            path,
+            options,
+        } => Command::CopyFrom {
+            table: name(table),
+            path,
+            options,
+        },
+        Command::CopyTo {
+            query,
+            path,
+            options,
+        } => Command::CopyTo {
+            query: Box::new(qualify(*query, namespace)),
+            path,
+            options,
+        },
+        command => command,
+    }
+}
+
+pub(crate) fn relation(name: String, database: Option<&str>) -> String {
+    if let Some(name) = name.strip_prefix(&format!("{}.", MAIN)) {
+        return name.to_string();
+    }
+    match database {
+        Some(database) if !name.contains('.') => format!("{}.{}", database, name),
+        _ => name,
+    }
+}
+
+pub(crate) fn split(name: &str) -> (Option<&str>, &str) {
+    match name.split_once('.') {
+        Some((database, name)) => (Some(database), name),
+        None => (None, name),
+    }
+}
+
+pub(crate) fn local(name: &str, database: Option<&str>) -> Option<String> {
+    match split(name) {
+        (found, name) if found == database => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualify_names() {
+        let select = parser::parse("SELECT users.id FROM users").unwrap();
+        assert_eq!(
+            "SELECT users.id FROM app.users",
+            qualify(select.clone(), &Namespace::new(Some("app"))).to_string()
+        );
+        assert_eq!(select, qualify(select.clone(), &Namespace::default()));
+        assert_eq!(
+            "EXPLAIN SELECT id FROM users",
+            qualify(
+                parser::parse("EXPLAIN SELECT id FROM main.users").unwrap(),
+                &Namespace::new(Some("app"))
+            )
+            .to_string()
+        );
+        assert_eq!(
+            "DROP TABLE logs.events",
+            qualify(
+                parser::parse("DROP TABLE logs.events").unwrap(),
+                &Namespace::new(Some("app"))
+            )
+            .to_string()
+        );
+        let mut namespace = Namespace::new(Some("app"));
+        namespace.set_schema("temp-1".to_string());
+        namespace.created("temp-1.users");
+        assert_eq!(
+            "SELECT id FROM temp-1.users",
+            qualify(parser::parse("SELECT id FROM users").unwrap(), &namespace).to_string()
+        );
+        assert_eq!(
+            "SELECT id FROM app.orders",
+            qualify(parser::parse("SELECT id FROM orders").unwrap(), &namespace).to_string()
+        );
+        namespace.dropped("temp-1.users");
+        assert!(namespace.temporary().is_empty());
+        assert_eq!((Some("app"), "users"), split("app.users"));
+        assert_eq!(Some("users".to_string()), local("app.users", Some("app")));
+        assert_eq!(None, local("users", Some("app")));
+        assert_eq!(Some("users".to_string()), local("users", None));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use common::error::DbError;
+use row::{Col, ColType, Constraint, Row, RowType};
+
+use crate::{
+    coerce,
+    constraints::{self, VarcharMode},
+    exec_result::Warnings,
+    sequence::Sequences,
+};
+
+pub(crate) struct Defaults<'a> {
+    table: &'a str,
+    row_type: &'a RowType,
+    sequences: &'a Sequences,
+    mode: VarcharMode,
+    warnings: Warnings,
+    now: i64,
+}
+
+impl<'a> Defaults<'a> {
+    pub(crate) fn new(
+        table: &'a str,
+        row_type: &'a RowType,
+        sequences: &'a Sequences,
+        mode: VarcharMode,
+    ) -> Result<Self, DbError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| DbError::unexpected("system clock is before the epoch"))?;
+        Ok(Self {
+            table,
+            row_type,
+            sequences,
+            mode,
+            warnings: Warnings::default(),
+            now: now.as_secs() as i64,
+        })
+    }
+
+    pub(crate) fn check_fields(&self, fields: &[String]) -> Result<(), DbError> {
+        let pk = self.row_type.get_primary_key()?;
+        let name = pk.get_name();
+        if fields.iter().any(|field| field == name) || self.generated(name).is_some() {
+            return Ok(());
+        }
+        Err(DbError::PrimaryKeyNotSet)
+    }
+
+    pub(crate) fn row(
+        &mut self,
+        fields: &[String],
+        values: Vec<String>,
+    ) -> Result<Vec<Col>, DbError> {
+        if fields.len() != values.len() {
+            return Err(DbError::invalid_input("wrong amount of insert values"));
+        }
+        let mut values: HashMap<String, String> = fields.iter().cloned().zip(values).collect();
+        let mut cols = Vec::with_capacity(self.row_type.columns.len());
+        for col_type in self.row_type.columns.iter() {
+            let value = match values.remove(col_type.get_name()) {
+                Some(value) => value,
+                None => self.fill(col_type)?,
+            };
+            cols.push(coerce::parse(self.table, col_type, value)?);
+        }
+        if let Some(key) = values.into_keys().next() {
+            return Err(DbError::field_not_found(&key, self.table));
+        }
+        constraints::check(
+            self.table,
+            self.row_type,
+            &mut cols,
+            self.mode,
+            &mut self.warnings,
+        )?;
+        Ok(cols)
+    }
+
+    pub(crate) fn warnings(self) -> Vec<String> {
+        self.warnings.into_vec()
+    }
+
+    pub(crate) fn advance(&self, rows: &[(Col, Row)]) -> Result<(), DbError> {
+        for column in self.row_type.auto_increment() {
+            let Some(position) = self.position(column) else {
+                continue;
+            };
+            let max = rows
+                .iter()
+                .filter_map(|(_, row)| match row.columns[position] {
+                    Col::Int(value) => Some(value as i64),
+                    Col::BigInt(value) => Some(value),
+                    Col::Varchar(_, _) => None,
+                })
+                .max();
+            if let Some(max) = max {
+                self.sequences
+                    .advance(&sequence_name(self.table, column), max)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fill(&self, col_type: &ColType) -> Result<String, DbError> {
+        let name = col_type.get_name();
+        match self.generated(name) {
+            Some(Constraint::AutoIncrement(_)) => {
+                let value = self.sequences.next(&sequence_name(self.table, name))?;
+                return Ok(value.to_string());
+            }
+            Some(Constraint::Default(_, value)) => return Ok(value.clone()),
+            Some(Constraint::Now(_)) => return Ok(self.now.to_string()),
+            _ => {}
+        }
+        if self.row_type.is_not_null(name) {
+            return Err(DbError::NotNull(name.to_string(), self.table.to_string()));
+        }
+        Ok(match col_type {
+            ColType::Int(_) | ColType::BigInt(_) => String::from("0"),
+            ColType::Varchar(_, _) => String::new(),
+        })
+    }
+
+    fn generated(&self, column: &str) -> Option<&Constraint> {
+        self.row_type.constraints.iter().find(|constraint| {
+            !matches!(constraint, Constraint::NotNull(_)) && constraint.get_column() == column
+        })
+    }
+
+    fn position(&self, column: &str) -> Option<usize> {
+        self.row_type
+            .columns
+            .iter()
+            .position(|col_type| col_type.get_name() == column)
+    }
+}
+
+pub(crate) fn validate(table: &str, row_type: &RowType) -> Result<(), DbError> {
+    for constraint in row_type.constraints.iter() {
+        let column = constraint.get_column();
+        let Some(col_type) = row_type
+            .columns
+            .iter()
+            .find(|col_type| col_type.get_name() == column)
+        else {
+            return Err(DbError::field_not_found(column, table));
+        };
+        let generated = row_type
+            .constraints
+            .iter()
+            .filter(|other| {
+                !matches!(other, Constraint::NotNull(_)) && other.get_column() == column
+            })
+            .count();
+        if generated > 1 {
+            return Err(DbError::InvalidInput(format!(
+                "column '{}' has more than one default",
+                column
+            )));
+        }
+        match (constraint, col_type) {
+            (Constraint::AutoIncrement(_), ColType::Int(_) | ColType::BigInt(_)) => {}
+            (Constraint::AutoIncrement(_), _) => {
+                return Err(DbError::InvalidInput(format!(
+                    "AUTO_INCREMENT column '{}' must be an integer",
+                    column
+                )));
+            }
+            (Constraint::Now(_), ColType::BigInt(_)) => {}
+            (Constraint::Now(_), _) => {
+                return Err(DbError::InvalidInput(format!(
+                    "NOW() default of column '{}' requires BIGINT",
+                    column
+                )));
+            }
+            (Constraint::Default(_, value), col_type) => {
+                let col = coerce::parse(table, col_type, value.clone())?;
+                let row_type = RowType {
+                    columns: vec![col_type.clone()],
+                    constraints: vec![],
+                };
+                constraints::validate(table, &row_type, &[col])?;
+            }
+            (Constraint::NotNull(_), _) => {}
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn sequence_name(table: &str, column: &str) -> String {
+    format!("{}_{}_seq", table, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row_type(constraints: Vec<Constraint>) -> RowType {
+        RowType {
+            columns: vec![
+                ColType::int("id"),
+                ColType::varchar("kind", 8),
+                ColType::bigint("created"),
+                ColType::int("retries"),
+            ],
+            constraints,
+        }
+    }
+
+    #[test]
+    fn fill() {
+        let row_type = row_type(vec![
+            Constraint::auto_increment("id"),
+            Constraint::not_null("kind"),
+            Constraint::default("kind", "info"),
+            Constraint::now("created"),
+        ]);
+        let sequences = Sequences::new(None);
+        sequences.create("events_id_seq", 7).unwrap();
+        let mut defaults =
+            Defaults::new("events", &row_type, &sequences, VarcharMode::Strict).unwrap();
+        let fields = ["retries".to_string()];
+        defaults.check_fields(&fields).unwrap();
+        let row = defaults.row(&fields, vec!["2".to_string()]).unwrap();
+        assert_eq!(
+            vec![
+                Col::int(7),
+                Col::varchar("info", 8),
+                Col::big_int(defaults.now),
+                Col::int(2),
+            ],
+            row
+        );
+        let row = defaults.row(&[], vec![]).unwrap();
+        assert_eq!((Col::int(8), Col::int(0)), (row[0].clone(), row[3].clone()));
+
+        let row_type = self::row_type(vec![Constraint::not_null("kind")]);
+        let mut defaults =
+            Defaults::new("events", &row_type, &sequences, VarcharMode::Strict).unwrap();
+        assert_eq!(
+            Err(DbError::PrimaryKeyNotSet),
+            defaults.check_fields(&["kind".to_string()])
+        );
+        assert_eq!(
+            Err(DbError::NotNull("kind".to_string(), "events".to_string())),
+            defaults.row(&["id".to_string()], vec!["1".to_string()])
+        );
+    }
+
+    #[test]
+    fn validate_defaults() {
+        let invalid = |constraint: Constraint| validate("events", &row_type(vec![constraint]));
+        assert!(invalid(Constraint::default("retries", "3")).is_ok());
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "retries".to_string(),
+                "events".to_string(),
+                "many".to_string()
+            )),
+            invalid(Constraint::default("retries", "many"))
+        );
+        assert_eq!(
+            Err(DbError::TooLong(
+                "kind".to_string(),
+                "events".to_string(),
+                11,
+                8
+            )),
+            invalid(Constraint::default("kind", "informative"))
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "NOW() default of column 'retries' requires BIGINT"
+            )),
+            invalid(Constraint::now("retries"))
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "column 'id' has more than one default"
+            )),
+            validate(
+                "events",
+                &row_type(vec![
+                    Constraint::auto_increment("id"),
+                    Constraint::default("id", "1")
+                ])
+            )
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use common::error::DbError;
+use row::Col;
+
+use crate::{
+    executor::Rows,
+    spill::{Budget, MAX_DEPTH, Partitions, Reservation, footprint},
+};
+
+pub(crate) fn distinct(rows: Rows, budget: &Budget) -> Rows {
+    Box::new(Distinct::new(rows, budget.clone(), 0))
+}
+
+struct Distinct {
+    input: Rows,
+    seen: HashSet<Vec<Col>>,
+    reservation: Reservation,
+    budget: Budget,
+    depth: u32,
+    partitions: Option<Partitions>,
+    spilled: Option<Rows>,
+}
+
+impl Distinct {
+    fn new(input: Rows, budget: Budget, depth: u32) -> Self {
+        Self {
+            input,
+            seen: HashSet::new(),
+            reservation: budget.reserve(),
+            budget,
+            depth,
+            partitions: None,
+            spilled: None,
+        }
+    }
+
+    fn spill(&mut self, row: Vec<Col>) -> Result<(), DbError> {
+        let partitions = match &mut self.partitions {
+            Some(partitions) => partitions,
+            None => self
+                .partitions
+                .insert(Partitions::new(&self.budget, self.depth)?),
+        };
+        partitions.write(&row, &row)
+    }
+
+    fn drain(&mut self, partitions: Partitions) -> Result<Rows, DbError> {
+        self.seen = HashSet::new();
+        self.reservation.release();
+        let runs = partitions.finish()?;
+        let (budget, depth) = (self.budget.clone(), self.depth + 1);
+        Ok(Box::new(runs.into_iter().flat_map(move |run| {
+            Distinct::new(run, budget.clone(), depth)
+        })))
+    }
+}
+
+impl Iterator for Distinct {
+    type Item = Result<Vec<Col>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(spilled) = &mut self.spilled {
+            return spilled.next();
+        }
+        loop {
+            let row = match self.input.next() {
+                Some(Ok(row)) => row,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    let partitions = self.partitions.take()?;
+                    let spilled = match self.drain(partitions) {
+                        Ok(spilled) => spilled,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    return self.spilled.insert(spilled).next();
+                }
+            };
+            if self.seen.contains(&row) {
+                continue;
+            }
+            if self.reservation.bytes() < self.budget.limit() || self.depth >= MAX_DEPTH {
+                if let Err(err) = self.reservation.grow(footprint(&row)) {
+                    return Some(Err(err));
+                }
+                self.seen.insert(row.clone());
+                return Some(Ok(row));
+            }
+            if let Err(err) = self.spill(row) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill() {
+        let rows: Vec<Vec<Col>> = (0..2000)
+            .map(|i| vec![Col::int((i * 7919) % 300), Col::varchar("x", 4)])
+            .collect();
+        let budget = Budget::new(20 * footprint(&rows[0]), None);
+        let input: Rows = Box::new(rows.clone().into_iter().map(Ok));
+        let mut unique = distinct(input, &budget)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(300, unique.len());
+        unique.sort();
+        let mut expected = rows;
+        expected.sort();
+        expected.dedup();
+        assert_eq!(expected, unique);
+
+        let input: Rows = Box::new((0..5).map(|i| Ok(vec![Col::int(i % 2)])));
+        let in_memory =
+            distinct(input, &Budget::new(usize::MAX, None)).collect::<Result<Vec<_>, _>>();
+        assert_eq!(Ok(vec![vec![Col::int(0)], vec![Col::int(1)]]), in_memory);
+    }
+}
@@ -0,0 +1,111 @@
+use std::io::BufRead;
+
+use common::error::DbError;
+
+const QUOTES: [u8; 2] = [b'\'', b'"'];
+
+pub(crate) struct Script<R> {
+    input: R,
+    line: usize,
+}
+
+impl<R: BufRead> Script<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self { input, line: 1 }
+    }
+
+    pub(crate) fn statement(&mut self) -> Result<Option<(usize, String)>, DbError> {
+        loop {
+            let mut buffer = Vec::new();
+            let mut eof = false;
+            loop {
+                if self.input.read_until(b';', &mut buffer)? == 0 || !buffer.ends_with(b";") {
+                    if quoted(&buffer) {
+                        let blank = buffer.iter().take_while(|c| c.is_ascii_whitespace());
+                        let line = self.line + blank.filter(|c| **c == b'\n').count();
+                        return Err(DbError::EOF(format!(
+                            "unterminated string in statement on line {}",
+                            line
+                        )));
+                    }
+                    eof = true;
+                    break;
+                }
+                if !quoted(&buffer) {
+                    buffer.pop();
+                    break;
+                }
+            }
+            let statement = String::from_utf8(buffer)
+                .map_err(|_| DbError::invalid_input("script is not valid UTF-8"))?;
+            let start = statement.len() - statement.trim_start().len();
+            let line = self.line + statement[..start].matches('\n').count();
+            self.line += statement.matches('\n').count();
+            let statement = statement.trim();
+            if !statement.is_empty() {
+                return Ok(Some((line, statement.to_string())));
+            }
+            if eof {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+pub(crate) fn literal(value: &str) -> bool {
+    let quote = match value.contains('\'') {
+        true => '"',
+        false => '\'',
+    };
+    !value.contains(quote) && !value.ends_with('\\')
+}
+
+fn quoted(buffer: &[u8]) -> bool {
+    let mut quote = None;
+    let mut prev = 0;
+    for &c in buffer {
+        match quote {
+            Some(q) if c == q && prev != b'\\' => quote = None,
+            None if QUOTES.contains(&c) => quote = Some(c),
+            _ => {}
+        }
+        prev = c;
+    }
+    quote.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(input: &str) -> Result<Vec<(usize, String)>, DbError> {
+        let mut script = Script::new(input.as_bytes());
+        let mut statements = Vec::new();
+        while let Some(statement) = script.statement()? {
+            statements.push(statement);
+        }
+        Ok(statements)
+    }
+
+    #[test]
+    fn split() {
+        let input = "CREATE TABLE t(id INT);\n;\nINSERT INTO t(id) VALUES('a;b'), (\"it's;\");\n";
+        assert_eq!(
+            vec![
+                (1, "CREATE TABLE t(id INT)".to_string()),
+                (
+                    3,
+                    "INSERT INTO t(id) VALUES('a;b'), (\"it's;\")".to_string()
+                ),
+            ],
+            statements(input).unwrap()
+        );
+        assert_eq!(
+            Err(DbError::eof("unterminated string in statement on line 2")),
+            statements("DUMP TO 'a';\nINSERT INTO t(id) VALUES('a;").map(|_| ())
+        );
+        assert!(literal("O'Brien"));
+        assert!(!literal("both ' and \""));
+        assert!(!literal("trailing \\"));
+    }
+}
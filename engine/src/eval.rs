@@ -0,0 +1,186 @@
+use common::error::DbError;
+use parser::Expr;
+use row::{Col, ColType, RowType};
+
+use crate::{
+    coerce::{self, convert},
+    plan::Scalar,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Term {
+    pub(crate) column: usize,
+    pub(crate) function: Option<Scalar>,
+}
+
+impl Term {
+    pub(crate) fn column(column: usize) -> Self {
+        Self {
+            column,
+            function: None,
+        }
+    }
+
+    pub(crate) fn evaluate(&self, row: &[Col]) -> Col {
+        match self.function {
+            Some(function) => function.apply(&row[self.column]),
+            None => row[self.column].clone(),
+        }
+    }
+
+    pub(crate) fn name(&self, column: &str) -> String {
+        match self.function {
+            Some(function) => format!("{}({})", function, column),
+            None => column.to_string(),
+        }
+    }
+}
+
+pub(crate) fn evaluate(
+    expr: &Expr,
+    table: &str,
+    row_type: &RowType,
+    row: &[Col],
+    target: &ColType,
+) -> Result<Col, DbError> {
+    Ok(match expr {
+        Expr::Value(value) => coerce::parse(table, target, value.clone())?,
+        Expr::Column(name) => convert(column(name, table, row_type, row)?.clone(), table, target)?,
+        Expr::Binary(left, op, right) => {
+            let left = number(left, table, row_type, row, target)?;
+            let right = number(right, table, row_type, row, target)?;
+            let value = match op {
+                '+' => left.checked_add(right),
+                '-' => left.checked_sub(right),
+                '*' => left.checked_mul(right),
+                '/' if right == 0 => return Err(DbError::invalid_input("division by zero")),
+                '/' => left.checked_div(right),
+                _ => return Err(DbError::InvalidInput(format!("unknown operator: {}", op))),
+            };
+            let Some(value) = value else {
+                return Err(DbError::invalid_input("integer overflow"));
+            };
+            convert(Col::BigInt(value), table, target)?
+        }
+    })
+}
+
+fn column<'a>(
+    name: &str,
+    table: &str,
+    row_type: &RowType,
+    row: &'a [Col],
+) -> Result<&'a Col, DbError> {
+    match row_type.columns.iter().position(|c| c.get_name() == name) {
+        Some(position) => Ok(&row[position]),
+        None => Err(DbError::field_not_found(name, table)),
+    }
+}
+
+fn number(
+    expr: &Expr,
+    table: &str,
+    row_type: &RowType,
+    row: &[Col],
+    target: &ColType,
+) -> Result<i64, DbError> {
+    match expr {
+        Expr::Value(value) => {
+            let col_type = ColType::bigint(target.get_name());
+            match coerce::parse(table, &col_type, value.clone())? {
+                Col::BigInt(value) => Ok(value),
+                _ => Err(DbError::unexpected("expected bigint")),
+            }
+        }
+        Expr::Column(name) => match column(name, table, row_type, row)? {
+            Col::Int(value) => Ok(*value as i64),
+            Col::BigInt(value) => Ok(*value),
+            Col::Varchar(_, _) => Err(DbError::InvalidInput(format!(
+                "column '{}' is not numeric",
+                name
+            ))),
+        },
+        Expr::Binary(_, _, _) => Err(DbError::invalid_input(
+            "nested expressions are not supported",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluate_expressions() {
+        let row_type = RowType {
+            columns: vec![
+                ColType::int("id"),
+                ColType::bigint("money"),
+                ColType::varchar("name", 4),
+            ],
+            constraints: vec![],
+        };
+        let row = vec![Col::int(1), Col::big_int(100), Col::varchar("ann", 4)];
+        let eval = |expr: Expr, target: &ColType| evaluate(&expr, "t", &row_type, &row, target);
+        let column = |name: &str| Box::new(Expr::Column(name.to_string()));
+        let value = |value: &str| Box::new(Expr::Value(value.to_string()));
+        assert_eq!(
+            Ok(Col::int(7)),
+            eval(Expr::Value("7".to_string()), &ColType::int("id"))
+        );
+        assert_eq!(
+            Ok(Col::big_int(200)),
+            eval(
+                Expr::Binary(column("money"), '*', value("2")),
+                &ColType::bigint("money")
+            )
+        );
+        assert_eq!(
+            Ok(Col::varchar("101", 4)),
+            eval(
+                Expr::Binary(column("id"), '+', column("money")),
+                &ColType::varchar("name", 4)
+            )
+        );
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "id".to_string(),
+                "t".to_string(),
+                "x".to_string()
+            )),
+            eval(Expr::Value("x".to_string()), &ColType::int("id"))
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("division by zero")),
+            eval(
+                Expr::Binary(column("id"), '/', value("0")),
+                &ColType::int("id")
+            )
+        );
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "id".to_string(),
+                "t".to_string(),
+                "4294967296".to_string()
+            )),
+            eval(
+                Expr::Binary(value("65536"), '*', value("65536")),
+                &ColType::int("id")
+            )
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("column 'name' is not numeric")),
+            eval(
+                Expr::Binary(column("name"), '+', value("1")),
+                &ColType::int("id")
+            )
+        );
+        assert_eq!(
+            Err(DbError::field_not_found("age", "t")),
+            eval(
+                Expr::Binary(column("age"), '+', value("1")),
+                &ColType::int("id")
+            )
+        );
+    }
+}
@@ -1,31 +1,129 @@
-use row::Col;
+use row::{Col, ColType};
+
+const MAX_WARNINGS: usize = 100;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ExecResult {
-    pub field_names: Vec<String>,
-    pub fields: Vec<Vec<Col>>,
+pub enum ExecResult {
+    Rows {
+        columns: Vec<String>,
+        types: Vec<ColType>,
+        rows: Vec<Vec<Col>>,
+    },
+    Affected {
+        op: String,
+        count: u64,
+        warnings: Vec<String>,
+    },
+    Ack {
+        op: String,
+    },
+}
+
+#[derive(Default)]
+pub(crate) struct Warnings {
+    messages: Vec<String>,
+    skipped: usize,
 }
 
 impl ExecResult {
-    pub fn ok(header: &str, count: i32) -> Self {
-        Self {
-            field_names: vec![header.to_string()],
-            fields: vec![vec![Col::int(count)]],
+    pub fn affected(op: &str, count: usize) -> Self {
+        Self::Affected {
+            op: op.to_string(),
+            count: count as u64,
+            warnings: vec![],
+        }
+    }
+
+    pub fn ack(op: &str) -> Self {
+        Self::Ack { op: op.to_string() }
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        if let Self::Affected {
+            warnings: current, ..
+        } = &mut self
+        {
+            *current = warnings;
+        }
+        self
+    }
+
+    pub fn columns(&self) -> &[String] {
+        match self {
+            Self::Rows { columns, .. } => columns,
+            _ => &[],
+        }
+    }
+
+    pub fn rows(&self) -> &[Vec<Col>] {
+        match self {
+            Self::Rows { rows, .. } => rows,
+            _ => &[],
+        }
+    }
+
+    pub fn into_rows(self) -> Vec<Vec<Col>> {
+        match self {
+            Self::Rows { rows, .. } => rows,
+            _ => vec![],
+        }
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        match self {
+            Self::Affected { warnings, .. } => warnings,
+            _ => &[],
         }
     }
 }
 
+impl Warnings {
+    pub(crate) fn push(&mut self, message: String) {
+        match self.messages.len() < MAX_WARNINGS {
+            true => self.messages.push(message),
+            false => self.skipped += 1,
+        }
+    }
+
+    pub(crate) fn into_vec(mut self) -> Vec<String> {
+        if self.skipped > 0 {
+            self.messages
+                .push(format!("{} more warnings omitted", self.skipped));
+        }
+        self.messages
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn ok() {
-        let exec_result = ExecResult::ok("created", 1);
-        assert_eq!("created", exec_result.field_names.first().unwrap());
+    fn affected() {
+        let exec_result =
+            ExecResult::affected("inserted", 2).with_warnings(vec!["truncated".to_string()]);
         assert_eq!(
-            Col::int(1),
-            *exec_result.fields.first().unwrap().first().unwrap()
+            ExecResult::Affected {
+                op: "inserted".to_string(),
+                count: 2,
+                warnings: vec!["truncated".to_string()],
+            },
+            exec_result
         );
+        assert!(exec_result.columns().is_empty());
+        assert!(exec_result.rows().is_empty());
+        assert!(ExecResult::ack("created").warnings().is_empty());
+    }
+
+    #[test]
+    fn warnings() {
+        let mut warnings = Warnings::default();
+        for i in 0..MAX_WARNINGS + 2 {
+            warnings.push(format!("warning {}", i));
+        }
+        let warnings = warnings.into_vec();
+        assert_eq!(MAX_WARNINGS + 1, warnings.len());
+        assert_eq!("warning 0", warnings[0]);
+        assert_eq!("2 more warnings omitted", warnings[MAX_WARNINGS]);
     }
 }
@@ -0,0 +1,464 @@
+use std::{
+    collections::HashMap,
+    iter,
+    ops::Bound,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use common::error::DbError;
+use row::{Col, ColType, Row};
+
+use crate::{
+    aggregate,
+    batch::{self, BATCH_SIZE, Batch, Batches},
+    cancel::CancelToken,
+    distinct, join,
+    plan::{Condition, PhysicalPlan, coerce, compare},
+    sort::{self, compare_rows},
+    spill::{Budget, footprint},
+    storage::Snapshot,
+    transaction::{self, Transaction, WriteSet},
+};
+
+pub(crate) type Rows = Box<dyn Iterator<Item = Result<Vec<Col>, DbError>> + Send>;
+
+pub(crate) struct Executor<'a> {
+    snapshot: Arc<Snapshot>,
+    transaction: Option<&'a Transaction>,
+    budget: Budget,
+    profile: Option<&'a Profile>,
+    token: CancelToken,
+}
+
+#[derive(Default)]
+pub(crate) struct Profile {
+    rows: Mutex<HashMap<usize, Arc<AtomicU64>>>,
+}
+
+impl Profile {
+    pub(crate) fn rows(&self, plan: &PhysicalPlan) -> u64 {
+        self.counter(plan).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, plan: &PhysicalPlan) -> Arc<AtomicU64> {
+        let mut rows = self.rows.lock().unwrap_or_else(|err| err.into_inner());
+        let node = plan as *const PhysicalPlan as usize;
+        rows.entry(node).or_default().clone()
+    }
+}
+
+impl<'a> Executor<'a> {
+    pub(crate) fn new(
+        snapshot: Snapshot,
+        transaction: Option<&'a Transaction>,
+        budget: Budget,
+    ) -> Self {
+        Self {
+            snapshot: Arc::new(snapshot),
+            transaction,
+            budget,
+            profile: None,
+            token: CancelToken::default(),
+        }
+    }
+
+    pub(crate) fn with_token(mut self, token: &CancelToken) -> Self {
+        self.token = token.clone();
+        self
+    }
+
+    pub(crate) fn with_profile(mut self, profile: &'a Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    pub(crate) fn execute(&self, plan: &PhysicalPlan) -> Result<Rows, DbError> {
+        let token = self.token.clone();
+        Ok(Box::new(batch::rows(self.batches(plan)?).map(move |row| {
+            token.check()?;
+            row
+        })))
+    }
+
+    fn batches(&self, plan: &PhysicalPlan) -> Result<Batches, DbError> {
+        let batches = self.operator(plan)?;
+        let Some(profile) = self.profile else {
+            return Ok(batches);
+        };
+        let counter = profile.counter(plan);
+        Ok(Box::new(batches.inspect(move |batch| {
+            if let Ok(batch) = batch {
+                counter.fetch_add(batch.len() as u64, Ordering::Relaxed);
+            }
+        })))
+    }
+
+    fn operator(&self, plan: &PhysicalPlan) -> Result<Batches, DbError> {
+        let (snapshot, transaction) = (&self.snapshot, self.transaction);
+        Ok(match plan {
+            PhysicalPlan::SeqScan {
+                table,
+                limit,
+                reverse,
+                ..
+            } => Box::new(TableScan {
+                snapshot: snapshot.clone(),
+                table: table.clone(),
+                writes: transaction.and_then(|transaction| transaction.writes(table).cloned()),
+                token: self.token.clone(),
+                from: Bound::Unbounded,
+                reverse: *reverse,
+                remaining: limit.unwrap_or(usize::MAX),
+                done: false,
+            }),
+            PhysicalPlan::KeyLookup { table, key, .. } => {
+                let row = match transaction.and_then(|transaction| transaction.get(table, key)) {
+                    Some(row) => row.cloned(),
+                    None => snapshot.search(table, key.clone())?,
+                };
+                materialized(row.into_iter().map(|row| row.columns).collect())
+            }
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                columns,
+                prefix,
+                from,
+                to,
+                limit,
+                covering,
+                reverse,
+                ..
+            } => {
+                let writes = transaction.filter(|transaction| transaction.writes(table).is_some());
+                let limit = match writes {
+                    Some(_) => usize::MAX,
+                    None => limit.unwrap_or(usize::MAX),
+                };
+                let values: Vec<Col> = prefix.iter().map(|(_, value)| value.clone()).collect();
+                let (lower, upper) = (index_bound(&values, from), index_bound(&values, to));
+                let terms = snapshot.index_terms(table, index)?.to_vec();
+                let entries =
+                    snapshot.index_entries(table, index, lower, upper, limit, *reverse)?;
+                if *covering && writes.is_none() {
+                    let width = columns.len();
+                    let mut entries = entries.into_iter().peekable();
+                    return Ok(Box::new(iter::from_fn(move || {
+                        entries.peek()?;
+                        let mut batch = vec![Vec::new(); width];
+                        let mut len = 0;
+                        for (key, primary_key) in entries.by_ref().take(BATCH_SIZE) {
+                            batch[0].push(primary_key);
+                            for (term, value) in terms.iter().zip(key) {
+                                if term.function.is_none() && term.column != 0 {
+                                    batch[term.column].push(value);
+                                }
+                            }
+                            len += 1;
+                        }
+                        Some(Ok(Batch::from_columns(batch, len)))
+                    })));
+                }
+                let keys: Vec<Col> = entries.into_iter().map(|(_, key)| key).collect();
+                if let Some(transaction) = writes {
+                    let mut rows = Vec::with_capacity(keys.len());
+                    for key in keys {
+                        rows.extend(snapshot.search(table, key)?);
+                    }
+                    let mut rows = transaction.index_scan(table, &terms, &values, from, to, rows);
+                    if *reverse {
+                        rows.reverse();
+                    }
+                    return Ok(materialized(
+                        rows.into_iter().map(|row| row.columns).collect(),
+                    ));
+                }
+                let (snapshot, table) = (snapshot.clone(), table.clone());
+                let mut keys = keys.into_iter().peekable();
+                Box::new(iter::from_fn(move || {
+                    keys.peek()?;
+                    let mut rows = Vec::with_capacity(BATCH_SIZE);
+                    for key in keys.by_ref().take(BATCH_SIZE) {
+                        match snapshot.search(&table, key) {
+                            Ok(row) => rows.extend(row.map(|row| row.columns)),
+                            Err(err) => return Some(Err(err)),
+                        }
+                    }
+                    Some(Ok(Batch::from_rows(rows)))
+                }))
+            }
+            PhysicalPlan::Filter { input, condition } => {
+                let condition = condition.clone();
+                Box::new(self.batches(input)?.filter_map(move |batch| match batch {
+                    Ok(batch) => {
+                        let mask = batch::mask(&condition, &batch);
+                        let batch = batch.select(&mask);
+                        (!batch.is_empty()).then_some(Ok(batch))
+                    }
+                    Err(err) => Some(Err(err)),
+                }))
+            }
+            PhysicalPlan::Project { input, columns } => {
+                let indexes: Vec<usize> = columns.iter().map(|c| c.index).collect();
+                Box::new(
+                    self.batches(input)?
+                        .map(move |batch| batch.map(|batch| batch.project(&indexes))),
+                )
+            }
+            PhysicalPlan::Sort { input, keys } => {
+                batch::batched(sort::sort(self.rows(input)?, keys.clone(), &self.budget)?)
+            }
+            PhysicalPlan::Distinct { input } => {
+                batch::batched(distinct::distinct(self.rows(input)?, &self.budget))
+            }
+            PhysicalPlan::TopN { input, keys, limit } => {
+                let mut reservation = self.budget.reserve();
+                let mut rows = Vec::new();
+                for row in self.rows(input)? {
+                    let row = row?;
+                    reservation.grow(footprint(&row))?;
+                    rows.push(row);
+                }
+                if *limit < rows.len() {
+                    rows.select_nth_unstable_by(*limit, |a, b| compare_rows(keys, a, b));
+                    rows.truncate(*limit);
+                }
+                rows.sort_by(|a, b| compare_rows(keys, a, b));
+                materialized(rows)
+            }
+            PhysicalPlan::Limit { input, limit } => {
+                let mut remaining = *limit;
+                Box::new(self.batches(input)?.map_while(move |batch| {
+                    if remaining == 0 {
+                        return None;
+                    }
+                    Some(batch.map(|mut batch| {
+                        batch.truncate(remaining);
+                        remaining -= batch.len();
+                        batch
+                    }))
+                }))
+            }
+            PhysicalPlan::HashAggregate {
+                input,
+                group_by,
+                aggregates,
+            } => batch::batched(aggregate::aggregate(
+                self.checked(input)?,
+                group_by,
+                aggregates,
+                &self.budget,
+            )?),
+            PhysicalPlan::RowCount { table, .. } => {
+                let mut count = snapshot.count(table)? as i64;
+                let writes = transaction.and_then(|transaction| transaction.writes(table));
+                for (key, row) in writes.into_iter().flatten() {
+                    let exists = snapshot.search(table, key.clone())?.is_some();
+                    count += row.is_some() as i64 - exists as i64;
+                }
+                materialized(vec![vec![Col::BigInt(count)]])
+            }
+            PhysicalPlan::HashJoin { left, right, on } => join::hash_join(
+                self.checked(left)?,
+                self.rows(right)?,
+                (on.0.index, on.1.index),
+                &self.budget,
+            )?,
+            PhysicalPlan::IndexJoin {
+                left,
+                table,
+                index,
+                key_type,
+                on,
+                condition,
+                ..
+            } => {
+                let probe = Probe {
+                    snapshot: snapshot.clone(),
+                    writes: transaction.and_then(|transaction| transaction.writes(table).cloned()),
+                    table: table.clone(),
+                    index: index.clone(),
+                    key_type: key_type.clone(),
+                    column: on.1.index,
+                    condition: condition.clone(),
+                };
+                let column = on.0.index;
+                Box::new(self.checked(left)?.map(move |batch| {
+                    let batch = batch?;
+                    let mut joined = Vec::new();
+                    for (position, value) in batch.column(column).iter().enumerate() {
+                        let matches = probe.rows(value)?;
+                        if matches.is_empty() {
+                            continue;
+                        }
+                        let row = batch.row(position);
+                        for other in matches {
+                            let mut row = row.clone();
+                            row.extend(other.columns);
+                            joined.push(row);
+                        }
+                    }
+                    Ok(Batch::from_rows(joined))
+                }))
+            }
+        })
+    }
+
+    fn checked(&self, plan: &PhysicalPlan) -> Result<Batches, DbError> {
+        let token = self.token.clone();
+        Ok(Box::new(self.batches(plan)?.map(move |batch| {
+            token.check()?;
+            batch
+        })))
+    }
+
+    fn rows(&self, plan: &PhysicalPlan) -> Result<Rows, DbError> {
+        Ok(batch::rows(self.checked(plan)?))
+    }
+}
+
+struct Probe {
+    snapshot: Arc<Snapshot>,
+    writes: Option<WriteSet>,
+    table: String,
+    index: Option<String>,
+    key_type: ColType,
+    column: usize,
+    condition: Option<Condition>,
+}
+
+impl Probe {
+    fn rows(&self, value: &Col) -> Result<Vec<Row>, DbError> {
+        let Some(value) = coerce(&self.key_type, value) else {
+            return Ok(vec![]);
+        };
+        let mut rows = match &self.index {
+            None => match self.writes.as_ref().and_then(|writes| writes.get(&value)) {
+                Some(row) => row.iter().cloned().collect(),
+                None => self
+                    .snapshot
+                    .search(&self.table, value)?
+                    .into_iter()
+                    .collect(),
+            },
+            Some(index) => {
+                let bound = Bound::Included(vec![value.clone()]);
+                let keys = self.snapshot.index_keys(
+                    &self.table,
+                    index,
+                    bound.clone(),
+                    bound,
+                    usize::MAX,
+                )?;
+                let mut rows = Vec::with_capacity(keys.len());
+                for key in keys {
+                    if self.writes.as_ref().is_some_and(|w| w.contains_key(&key)) {
+                        continue;
+                    }
+                    rows.extend(self.snapshot.search(&self.table, key)?);
+                }
+                if let Some(writes) = &self.writes {
+                    let staged = writes.values().flatten();
+                    rows.extend(
+                        staged
+                            .filter(|row| compare(&row.columns[self.column], &value).is_eq())
+                            .cloned(),
+                    );
+                }
+                rows
+            }
+        };
+        if let Some(condition) = &self.condition {
+            rows.retain(|row| condition.matches(&row.columns));
+        }
+        Ok(rows)
+    }
+}
+
+fn materialized(rows: Vec<Vec<Col>>) -> Batches {
+    batch::batched(rows.into_iter().map(Ok))
+}
+
+fn index_bound(prefix: &[Col], bound: &Bound<Col>) -> Bound<Vec<Col>> {
+    let key = |value: &Col| prefix.iter().chain([value]).cloned().collect();
+    match bound {
+        Bound::Included(value) => Bound::Included(key(value)),
+        Bound::Excluded(value) => Bound::Excluded(key(value)),
+        Bound::Unbounded if prefix.is_empty() => Bound::Unbounded,
+        Bound::Unbounded => Bound::Included(prefix.to_vec()),
+    }
+}
+
+struct TableScan {
+    snapshot: Arc<Snapshot>,
+    table: String,
+    writes: Option<WriteSet>,
+    token: CancelToken,
+    from: Bound<Col>,
+    reverse: bool,
+    remaining: usize,
+    done: bool,
+}
+
+impl TableScan {
+    fn fill(&mut self) -> Result<Vec<Row>, DbError> {
+        self.token.check()?;
+        let batch = BATCH_SIZE.min(self.remaining);
+        let rows = match self.reverse {
+            true => self
+                .snapshot
+                .scan_rev(&self.table, self.from.clone(), batch)?,
+            false => self.snapshot.scan(&self.table, self.from.clone(), batch)?,
+        };
+        let to = match rows.last() {
+            Some(row) if rows.len() == batch => Bound::Included(row.columns[0].clone()),
+            _ => {
+                self.done = true;
+                Bound::Unbounded
+            }
+        };
+        let rows = match (&self.writes, self.reverse) {
+            (Some(writes), false) => {
+                transaction::overlay(writes, (self.from.clone(), to.clone()), rows)
+            }
+            (Some(writes), true) => {
+                let mut rows = transaction::overlay(writes, (to.clone(), self.from.clone()), rows);
+                rows.reverse();
+                rows
+            }
+            (None, _) => rows,
+        };
+        if let Bound::Included(key) = to {
+            self.from = Bound::Excluded(key);
+        }
+        Ok(rows)
+    }
+}
+
+impl Iterator for TableScan {
+    type Item = Result<Batch, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 && !self.done {
+            let mut rows = match self.fill() {
+                Ok(rows) => rows,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            rows.truncate(self.remaining);
+            if rows.is_empty() {
+                continue;
+            }
+            self.remaining -= rows.len();
+            return Some(Ok(Batch::from_rows(
+                rows.into_iter().map(|row| row.columns).collect(),
+            )));
+        }
+        None
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use common::error::DbError;
+use parser::Command;
+use row::{Col, Row};
+
+use crate::exec_result::ExecResult;
+
+type StatementHook = Box<dyn Fn(&Command) + Send + Sync>;
+type ResultHook = Box<dyn Fn(&Command, Result<&ExecResult, &DbError>) + Send + Sync>;
+type RowHook = Box<dyn Fn(&[Col]) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Hooks {
+    before: Vec<StatementHook>,
+    after: Vec<ResultHook>,
+}
+
+impl Hooks {
+    pub(crate) fn add_before(&mut self, hook: StatementHook) {
+        self.before.push(hook);
+    }
+
+    pub(crate) fn add_after(&mut self, hook: ResultHook) {
+        self.after.push(hook);
+    }
+
+    pub(crate) fn before(&self, command: &Command) {
+        for hook in self.before.iter() {
+            hook(command);
+        }
+    }
+
+    pub(crate) fn has_after(&self) -> bool {
+        !self.after.is_empty()
+    }
+
+    pub(crate) fn after(&self, command: &Command, result: Result<&ExecResult, &DbError>) {
+        for hook in self.after.iter() {
+            hook(command, result);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RowHooks {
+    inserts: HashMap<String, Vec<RowHook>>,
+    deletes: HashMap<String, Vec<RowHook>>,
+}
+
+impl RowHooks {
+    pub(crate) fn add_insert(&mut self, table: &str, hook: RowHook) {
+        self.inserts
+            .entry(table.to_string())
+            .or_default()
+            .push(hook);
+    }
+
+    pub(crate) fn add_delete(&mut self, table: &str, hook: RowHook) {
+        self.deletes
+            .entry(table.to_string())
+            .or_default()
+            .push(hook);
+    }
+
+    pub(crate) fn watches(&self, table: &str) -> bool {
+        self.inserts.contains_key(table) || self.deletes.contains_key(table)
+    }
+
+    pub(crate) fn notify(&self, table: &str, before: Option<&Row>, after: Option<&Row>) {
+        if let Some(row) = before {
+            for hook in self.deletes.get(table).into_iter().flatten() {
+                hook(&row.columns);
+            }
+        }
+        if let Some(row) = after {
+            for hook in self.inserts.get(table).into_iter().flatten() {
+                hook(&row.columns);
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, iter, mem};
+
+use common::error::DbError;
+use row::Col;
+
+use crate::{
+    batch::{self, Batch, Batches},
+    executor::Rows,
+    spill::{Budget, MAX_DEPTH, Partitions, Reservation, footprint},
+};
+
+pub(crate) fn hash_join(
+    left: Batches,
+    right: Rows,
+    on: (usize, usize),
+    budget: &Budget,
+) -> Result<Batches, DbError> {
+    join(left, right, on, budget.clone(), 0)
+}
+
+fn join(
+    left: Batches,
+    right: Rows,
+    on: (usize, usize),
+    budget: Budget,
+    depth: u32,
+) -> Result<Batches, DbError> {
+    let mut table = Table {
+        rows: BTreeMap::new(),
+        reservation: budget.reserve(),
+    };
+    let mut spilled: Option<Partitions> = None;
+    for row in right {
+        let row = row?;
+        let key = join_key(&row[on.1]);
+        if let Some(partitions) = &mut spilled {
+            partitions.write(&key, &row)?;
+            continue;
+        }
+        table.reservation.grow(footprint(&row))?;
+        table.rows.entry(key).or_default().push(row);
+        if table.reservation.bytes() >= budget.limit() && depth < MAX_DEPTH {
+            let mut partitions = Partitions::new(&budget, depth)?;
+            for (key, rows) in mem::take(&mut table.rows) {
+                for row in rows {
+                    partitions.write(&key, &row)?;
+                }
+            }
+            table.reservation.release();
+            spilled = Some(partitions);
+        }
+    }
+    let Some(right) = spilled else {
+        return Ok(Box::new(left.map(move |batch| table.probe(on.0, batch?))));
+    };
+    let mut partitions = Partitions::new(&budget, depth)?;
+    for batch in left {
+        let batch = batch?;
+        for (position, value) in batch.column(on.0).iter().enumerate() {
+            partitions.write(&join_key(value), &batch.row(position))?;
+        }
+    }
+    let pairs = partitions.finish()?.into_iter().zip(right.finish()?);
+    Ok(Box::new(pairs.flat_map(move |(left, right)| {
+        match join(batch::batched(left), right, on, budget.clone(), depth + 1) {
+            Ok(batches) => batches,
+            Err(err) => Box::new(iter::once(Err(err))),
+        }
+    })))
+}
+
+struct Table {
+    rows: BTreeMap<Col, Vec<Vec<Col>>>,
+    reservation: Reservation,
+}
+
+impl Table {
+    fn probe(&self, column: usize, batch: Batch) -> Result<Batch, DbError> {
+        let mut joined = Vec::new();
+        for (position, value) in batch.column(column).iter().enumerate() {
+            let Some(matches) = self.rows.get(&join_key(value)) else {
+                continue;
+            };
+            let row = batch.row(position);
+            for other in matches {
+                let mut row = row.clone();
+                row.extend(other.iter().cloned());
+                joined.push(row);
+            }
+        }
+        Ok(Batch::from_rows(joined))
+    }
+}
+
+fn join_key(col: &Col) -> Col {
+    match col {
+        Col::Int(value) => Col::BigInt(*value as i64),
+        Col::BigInt(value) => Col::BigInt(*value),
+        Col::Varchar(value, _) => Col::Varchar(value.clone(), 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(count: i32, modulo: i32) -> Vec<Vec<Col>> {
+        (0..count)
+            .map(|i| vec![Col::int(i), Col::big_int((i % modulo) as i64)])
+            .collect()
+    }
+
+    fn joined(budget: usize) -> Vec<Vec<Col>> {
+        let left = batch::batched(rows(300, 50).into_iter().map(Ok));
+        let right: Rows = Box::new(rows(100, 100).into_iter().map(Ok));
+        let batches = hash_join(left, right, (1, 0), &Budget::new(budget, None)).unwrap();
+        let mut rows = batch::rows(batches).collect::<Result<Vec<_>, _>>().unwrap();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn spill() {
+        let in_memory = joined(usize::MAX);
+        assert_eq!(300, in_memory.len());
+        assert!(
+            in_memory
+                .iter()
+                .all(|row| join_key(&row[1]) == join_key(&row[2]))
+        );
+        let budget = 10 * footprint(&rows(1, 1)[0]);
+        assert_eq!(in_memory, joined(budget));
+    }
+}
@@ -1,294 +1,4825 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    mem,
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use common::error::DbError;
-use parser::Command;
-use row::{Col, ColType, Row, RowType};
+use parser::{Assignment, Command, Comparison, CopyOptions};
+use row::{Col, ColType, Constraint, Row, RowType};
+use tracing::{debug, info_span, warn};
 
-use crate::{exec_result::ExecResult, storage::Storage};
+use crate::{
+    audit::AuditLog,
+    database::Namespace,
+    defaults::{Defaults, sequence_name},
+    exec_result::{ExecResult, Warnings},
+    executor::{Executor, Profile, Rows},
+    hooks::Hooks,
+    lock::{LockManager, LockMode, Resource},
+    metrics::Counters,
+    plan::{LogicalPlan, PhysicalPlan, Planner},
+    spill::{Budget, footprint},
+    storage::Storage,
+    transaction::Transaction,
+};
 
+mod aggregate;
+mod audit;
+mod batch;
+mod cancel;
+mod changes;
+mod coerce;
+mod constraints;
+mod csv;
+mod cursor;
+mod database;
+mod defaults;
+mod distinct;
+mod dump;
+mod eval;
 pub mod exec_result;
+mod executor;
+mod hooks;
+mod join;
+mod lock;
+mod metrics;
+mod parquet;
+pub mod plan;
+mod prepared;
+mod sequence;
+mod session;
+mod sort;
+mod spill;
+mod statistics;
 mod storage;
+mod transaction;
+mod view;
+
+pub use cancel::CancelToken;
+pub use changes::{Change, ChangeOp, Changes};
+pub use constraints::VarcharMode;
+pub use cursor::Cursor;
+pub use metrics::Metrics;
+pub use prepared::PreparedStatement;
+pub use session::Session;
+
+pub const MEMORY: &str = ":memory:";
+
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+const MEMORY_CAP: usize = 1024 * 1024 * 1024;
+const COPY_BATCH: usize = 10_000;
+const DUMP_BATCH: usize = 1_000;
 
 pub struct Engine {
     storage: Storage,
+    locks: LockManager,
+    next_owner: AtomicU64,
+    transaction: Mutex<Option<Transaction>>,
+    namespace: Mutex<Namespace>,
+    queries: Mutex<HashMap<u64, CancelToken>>,
+    memory_budget: usize,
+    memory_cap: usize,
+    varchar_mode: VarcharMode,
+    read_only: Option<PathBuf>,
+    hooks: Hooks,
+    audit: Option<AuditLog>,
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Ok(namespace) = self.namespace.get_mut().map(mem::take) {
+            let _ = self.drop_temporary(&namespace);
+        }
+    }
 }
 
 impl Engine {
     pub fn new(dir: &Path) -> Result<Self, DbError> {
+        if dir.as_os_str() == MEMORY {
+            return Ok(Self::in_memory());
+        }
         fs::create_dir_all(dir)?;
-        let storage = Storage::new(dir)?;
-        Ok(Self { storage })
+        Ok(Self::with_storage(Storage::new(dir)?))
+    }
+
+    pub fn read_only(dir: &Path) -> Result<Self, DbError> {
+        if !dir.is_dir() {
+            return Err(DbError::InvalidInput(format!(
+                "directory '{}' doesn't exist",
+                dir.display()
+            )));
+        }
+        let mut engine = Self::with_storage(Storage::read_only(dir));
+        engine.read_only = Some(PathBuf::from(dir));
+        Ok(engine)
+    }
+
+    pub fn in_memory() -> Self {
+        Self::with_storage(Storage::in_memory())
+    }
+
+    fn with_storage(storage: Storage) -> Self {
+        Self {
+            storage,
+            locks: LockManager::new(LOCK_TIMEOUT),
+            next_owner: AtomicU64::new(1),
+            transaction: Mutex::new(None),
+            namespace: Mutex::new(Namespace::default()),
+            queries: Mutex::new(HashMap::new()),
+            memory_budget: MEMORY_BUDGET,
+            memory_cap: MEMORY_CAP,
+            varchar_mode: VarcharMode::default(),
+            read_only: None,
+            hooks: Hooks::default(),
+            audit: None,
+        }
+    }
+
+    pub fn set_memory_budget(&mut self, budget: usize) {
+        self.memory_budget = budget;
+    }
+
+    pub fn set_memory_cap(&mut self, cap: usize) {
+        self.memory_cap = cap;
+    }
+
+    fn budget(&self) -> Budget {
+        Budget::new(self.memory_budget, self.storage.spill_dir()).with_cap(self.memory_cap)
+    }
+
+    pub fn subscribe(&self) -> Result<Changes, DbError> {
+        self.storage.feed().subscribe()
+    }
+
+    pub fn set_change_retention(&mut self, commits: u64) {
+        self.storage.feed_mut().set_retention(commits);
+    }
+
+    pub fn subscribe_from(&self, commit: u64) -> Result<Changes, DbError> {
+        self.storage.feed().subscribe_from(commit)
+    }
+
+    pub fn truncate_changes(&self, commit: u64) -> Result<(), DbError> {
+        if let Some(path) = &self.read_only {
+            return Err(DbError::ReadOnly(path.display().to_string()));
+        }
+        self.storage.feed().truncate(commit)
+    }
+
+    pub fn before_statement(&mut self, hook: impl Fn(&Command) + Send + Sync + 'static) {
+        self.hooks.add_before(Box::new(hook));
+    }
+
+    pub fn after_statement(
+        &mut self,
+        hook: impl Fn(&Command, Result<&ExecResult, &DbError>) + Send + Sync + 'static,
+    ) {
+        self.hooks.add_after(Box::new(hook));
+    }
+
+    pub fn on_insert(&mut self, table: &str, hook: impl Fn(&[Col]) + Send + Sync + 'static) {
+        self.storage
+            .feed_mut()
+            .hooks_mut()
+            .add_insert(table, Box::new(hook));
+    }
+
+    pub fn on_delete(&mut self, table: &str, hook: impl Fn(&[Col]) + Send + Sync + 'static) {
+        self.storage
+            .feed_mut()
+            .hooks_mut()
+            .add_delete(table, Box::new(hook));
+    }
+
+    pub fn set_varchar_mode(&mut self, mode: VarcharMode) {
+        self.varchar_mode = mode;
+    }
+
+    pub fn set_audit_log(&mut self, path: &Path) -> Result<(), DbError> {
+        self.audit = Some(AuditLog::open(path)?);
+        Ok(())
     }
 
     pub fn execute(&self, command: Command) -> Result<ExecResult, DbError> {
+        self.execute_with(command, &CancelToken::new())
+    }
+
+    pub fn execute_with(
+        &self,
+        command: Command,
+        token: &CancelToken,
+    ) -> Result<ExecResult, DbError> {
+        let mut guard = self.lock_namespace()?;
+        let mut local;
+        let namespace = match database::scoped(&command) {
+            true => &mut *guard,
+            false => {
+                local = guard.clone();
+                drop(guard);
+                &mut local
+            }
+        };
+        let mut transaction = self.lock_transaction()?;
+        if transaction.is_none() && command != Command::Begin {
+            drop(transaction);
+            return self.execute_in(&mut None, namespace, token, 0, command);
+        }
+        self.execute_in(&mut transaction, namespace, token, 0, command)
+    }
+
+    pub fn query(&self, command: Command) -> Result<Cursor, DbError> {
+        self.query_with(command, &CancelToken::new())
+    }
+
+    pub fn query_with(&self, command: Command, token: &CancelToken) -> Result<Cursor, DbError> {
+        let mut guard = self.lock_namespace()?;
+        let mut local;
+        let namespace = match database::scoped(&command) {
+            true => &mut *guard,
+            false => {
+                local = guard.clone();
+                drop(guard);
+                &mut local
+            }
+        };
+        let mut transaction = self.lock_transaction()?;
+        if transaction.is_none() {
+            drop(transaction);
+            return self.query_in(&mut None, namespace, token, 0, command);
+        }
+        self.query_in(&mut transaction, namespace, token, 0, command)
+    }
+
+    pub fn execute_batch(&self, commands: &[Command]) -> Vec<Result<ExecResult, DbError>> {
+        self.batch(commands, false)
+            .unwrap_or_else(|err| vec![Err(err)])
+    }
+
+    pub fn execute_batch_atomic(&self, commands: &[Command]) -> Vec<Result<ExecResult, DbError>> {
+        self.batch(commands, true)
+            .unwrap_or_else(|err| vec![Err(err)])
+    }
+
+    fn batch(
+        &self,
+        commands: &[Command],
+        atomic: bool,
+    ) -> Result<Vec<Result<ExecResult, DbError>>, DbError> {
+        let mut namespace = self.lock_namespace()?;
+        let mut transaction = self.lock_transaction()?;
+        let token = CancelToken::new();
+        let mut results = Vec::with_capacity(commands.len());
+        let (mut owned, mut start) = (false, 0);
+        for command in commands {
+            let grouped = atomic || batched(command);
+            if owned && !grouped {
+                self.commit_batch(&mut transaction, &mut results[start..]);
+                owned = false;
+            }
+            if grouped && transaction.is_none() {
+                *transaction = Some(Transaction::new(self.next_owner()));
+                (owned, start) = (true, results.len());
+            }
+            let result =
+                self.execute_in(&mut transaction, &mut namespace, &token, 0, command.clone());
+            let failed = result.is_err();
+            results.push(result);
+            if atomic && failed {
+                if let Some(transaction) = transaction.take_if(|_| owned) {
+                    self.locks.release(transaction.id());
+                }
+                return Ok(results);
+            }
+        }
+        if owned {
+            self.commit_batch(&mut transaction, &mut results[start..]);
+        }
+        Ok(results)
+    }
+
+    fn commit_batch(
+        &self,
+        transaction: &mut Option<Transaction>,
+        results: &mut [Result<ExecResult, DbError>],
+    ) {
+        let Some(transaction) = transaction.take() else {
+            return;
+        };
+        let owner = transaction.id();
+        let committed = self.storage.commit(transaction.into_writes());
+        self.locks.release(owner);
+        if let Err(err) = committed {
+            let message = format!("batch commit failed: {}", err);
+            for result in results.iter_mut().filter(|result| result.is_ok()) {
+                *result = Err(DbError::InvalidInput(message.clone()));
+            }
+        }
+    }
+
+    pub fn prepare(&self, sql: &str) -> Result<PreparedStatement<'_>, DbError> {
+        PreparedStatement::new(self, sql)
+    }
+
+    pub fn tables(&self) -> Result<Vec<(String, RowType)>, DbError> {
+        self.storage
+            .tables(None)?
+            .into_iter()
+            .map(|table| {
+                let row_type = self.storage.get_row_type(&table)?;
+                Ok((table, row_type))
+            })
+            .collect()
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        self.storage.metrics().snapshot()
+    }
+
+    pub fn flush(&self) -> Result<(), DbError> {
+        self.storage.flush()
+    }
+
+    pub fn session(self: &Arc<Self>) -> Session {
+        Session::new(self.clone())
+    }
+
+    pub fn cancel(&self, id: u64) -> Result<(), DbError> {
+        match self.lock_queries()?.get(&id) {
+            Some(token) => {
+                token.cancel();
+                Ok(())
+            }
+            None => Err(DbError::InvalidInput(format!(
+                "query {} is not running",
+                id
+            ))),
+        }
+    }
+
+    pub(crate) fn execute_in(
+        &self,
+        transaction: &mut Option<Transaction>,
+        namespace: &mut Namespace,
+        token: &CancelToken,
+        session: u64,
+        command: Command,
+    ) -> Result<ExecResult, DbError> {
+        let span = info_span!("statement", session, kind = metrics::kind(&command));
+        let _entered = span.enter();
+        debug!(statement = %command, "executing");
+        let started = Instant::now();
+        let metrics = self.storage.metrics();
+        metrics.statement(&command);
+        self.hooks.before(&command);
+        let observed = (self.hooks.has_after() || self.audit.is_some()).then(|| command.clone());
+        let result = self
+            .execute_statement(transaction, namespace, token, command)
+            .inspect_err(|err| failed(metrics, err));
+        debug!(elapsed = ?started.elapsed(), "executed");
+        if let Some(command) = observed {
+            self.hooks.after(&command, result.as_ref());
+            if let Some(audit) = &self.audit
+                && let Err(err) = audit.record(session, &command, result.as_ref().map(Some))
+            {
+                warn!(error = %err, "audit record failed");
+            }
+        }
+        result
+    }
+
+    fn execute_statement(
+        &self,
+        transaction: &mut Option<Transaction>,
+        namespace: &mut Namespace,
+        token: &CancelToken,
+        command: Command,
+    ) -> Result<ExecResult, DbError> {
+        if let Some(path) = &self.read_only
+            && writes(&command)
+        {
+            return Err(DbError::ReadOnly(path.display().to_string()));
+        }
+        if let Command::Create {
+            name,
+            temporary: true,
+            ..
+        } = &command
+        {
+            if name.contains('.') {
+                return Err(DbError::InvalidInput(format!(
+                    "temporary table '{}' cannot be qualified",
+                    name
+                )));
+            }
+            if namespace.schema().is_none() {
+                let schema = format!("temp-{}", self.next_owner());
+                self.storage.create_temporary(&schema)?;
+                namespace.set_schema(schema);
+            }
+        }
+        let command = database::qualify(command, namespace);
+        let scoped = match &command {
+            Command::Create {
+                name,
+                temporary: true,
+                ..
+            } => vec![(name.clone(), true)],
+            Command::Drop { table } => vec![(table.clone(), false)],
+            Command::RenameTable { table, name } => {
+                vec![(table.clone(), false), (name.clone(), true)]
+            }
+            _ => vec![],
+        };
+        let result = match command {
+            Command::Use { database: name } => {
+                self.storage.check_database(&name)?;
+                namespace.set_database(name);
+                Ok(ExecResult::ack("changed"))
+            }
+            Command::Begin => {
+                if transaction.is_some() {
+                    return Err(DbError::invalid_input("transaction is already in progress"));
+                }
+                *transaction = Some(Transaction::new(self.next_owner()));
+                Ok(ExecResult::ack("started"))
+            }
+            Command::Commit => {
+                let Some(transaction) = transaction.take() else {
+                    return Err(DbError::invalid_input("no transaction in progress"));
+                };
+                let owner = transaction.id();
+                let committed = self.storage.commit(transaction.into_writes());
+                self.locks.release(owner);
+                Ok(ExecResult::affected("committed", committed?))
+            }
+            Command::Rollback => {
+                let Some(transaction) = transaction.take() else {
+                    return Err(DbError::invalid_input("no transaction in progress"));
+                };
+                self.locks.release(transaction.id());
+                let discarded = transaction
+                    .into_writes()
+                    .values()
+                    .map(|w| w.len())
+                    .sum::<usize>();
+                Ok(ExecResult::affected("rolled_back", discarded))
+            }
+            command => match transaction.as_mut() {
+                Some(transaction) => self.execute_command(
+                    command,
+                    transaction.id(),
+                    Some(transaction),
+                    namespace.database(),
+                    token,
+                ),
+                None => {
+                    let owner = self.next_owner();
+                    let result =
+                        self.execute_command(command, owner, None, namespace.database(), token);
+                    self.locks.release(owner);
+                    result
+                }
+            },
+        }?;
+        for (name, created) in scoped {
+            match created {
+                true => namespace.created(&name),
+                false => namespace.dropped(&name),
+            }
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn query_in(
+        &self,
+        transaction: &mut Option<Transaction>,
+        namespace: &mut Namespace,
+        token: &CancelToken,
+        session: u64,
+        command: Command,
+    ) -> Result<Cursor, DbError> {
+        match command {
+            Command::Select { ref fields, .. } if !fields.is_empty() => {
+                let span = info_span!("statement", session, kind = metrics::kind(&command));
+                let _entered = span.enter();
+                debug!(statement = %command, "opening cursor");
+                let started = Instant::now();
+                let metrics = self.storage.metrics();
+                metrics.statement(&command);
+                let audited = self.audit.as_ref().map(|audit| (audit, command.clone()));
+                let command = database::qualify(command, namespace);
+                let result = self
+                    .select_plan(command)
+                    .and_then(|plan| {
+                        let types = Planner::new(&self.storage).types(&plan)?;
+                        let plan = self.plan(plan)?;
+                        debug!(elapsed = ?started.elapsed(), "planned");
+                        Ok((plan, types))
+                    })
+                    .and_then(|(plan, types)| {
+                        let rows = self.stream(&plan, transaction.as_ref(), token)?;
+                        Ok(Cursor::new(plan.columns(), types, rows))
+                    })
+                    .inspect_err(|err| failed(metrics, err));
+                if let Some((audit, command)) = audited
+                    && let Err(err) = audit.record(session, &command, result.as_ref().map(|_| None))
+                {
+                    warn!(error = %err, "audit record failed");
+                }
+                result
+            }
+            command => Ok(self
+                .execute_in(transaction, namespace, token, session, command)?
+                .into()),
+        }
+    }
+
+    fn execute_command(
+        &self,
+        command: Command,
+        owner: u64,
+        transaction: Option<&mut Transaction>,
+        database: Option<&str>,
+        token: &CancelToken,
+    ) -> Result<ExecResult, DbError> {
+        if transaction.is_some()
+            && matches!(
+                command,
+                Command::Create { .. }
+                    | Command::CreateDatabase { .. }
+                    | Command::CreateIndex { .. }
+                    | Command::Drop { .. }
+                    | Command::Vacuum { .. }
+                    | Command::Analyze { .. }
+                    | Command::CreateSequence { .. }
+                    | Command::DropSequence { .. }
+                    | Command::CreateView { .. }
+                    | Command::DropView { .. }
+                    | Command::RenameTable { .. }
+                    | Command::Dump { .. }
+                    | Command::Restore { .. }
+            )
+        {
+            return Err(DbError::InvalidInput(format!(
+                "'{}' cannot run inside a transaction",
+                command
+            )));
+        }
+        let target = match &command {
+            Command::Create { name: table, .. }
+            | Command::CreateIndex { table, .. }
+            | Command::Insert { table, .. }
+            | Command::Update { table, .. }
+            | Command::Delete { table }
+            | Command::Drop { table }
+            | Command::RenameTable { table, .. }
+            | Command::Vacuum { table: Some(table) }
+            | Command::Analyze { table: Some(table) }
+            | Command::CopyFrom { table, .. }
+            | Command::ShowTableStatus { table }
+            | Command::CheckTable { table } => Some(table),
+            _ => None,
+        };
+        if let Some(table) = target
+            && self.storage.views().get(table)?.is_some()
+        {
+            return Err(DbError::InvalidInput(format!("'{}' is a view", table)));
+        }
         match command {
-            Command::Create { name, fields } => {
-                let created = self.execute_create(&name, fields)?;
-                Ok(ExecResult::ok("created", created as i32))
+            Command::Create {
+                name,
+                fields,
+                constraints,
+                ..
+            } => {
+                self.locks
+                    .lock(owner, Resource::table(&name), LockMode::Exclusive)?;
+                self.execute_create(&name, fields, constraints)?;
+                Ok(ExecResult::ack("created"))
+            }
+            Command::CreateIndex {
+                name,
+                table,
+                columns,
+            } => {
+                self.locks
+                    .lock(owner, Resource::table(&table), LockMode::Exclusive)?;
+                self.storage.create_index(&table, &name, &columns)?;
+                Ok(ExecResult::ack("created"))
+            }
+            Command::CreateSequence { name, start } => {
+                self.storage.sequences().create(&name, start)?;
+                Ok(ExecResult::ack("created"))
             }
+            Command::DropSequence { name } => {
+                self.storage.sequences().drop(&name)?;
+                Ok(ExecResult::ack("dropped"))
+            }
+            Command::CreateView {
+                name,
+                columns,
+                query,
+            } => {
+                let definition = Command::CreateView {
+                    name: database::split(&name).1.to_string(),
+                    columns,
+                    query,
+                };
+                self.execute_create_view(&name, &definition.to_string())?;
+                Ok(ExecResult::ack("created"))
+            }
+            Command::CreateDatabase { name } => {
+                self.storage.create_database(&name)?;
+                Ok(ExecResult::ack("created"))
+            }
+            Command::DropView { name } => {
+                self.storage.views().drop(&name)?;
+                Ok(ExecResult::ack("dropped"))
+            }
+            Command::NextVal { sequence } => Ok(ExecResult::Rows {
+                columns: vec!["nextval".to_string()],
+                types: vec![ColType::bigint("nextval")],
+                rows: vec![vec![Col::BigInt(self.storage.sequences().next(&sequence)?)]],
+            }),
             Command::Insert {
                 table,
                 fields,
                 values,
+                replace,
             } => {
-                let inserted = self.execute_insert(&table, fields, values)?;
-                Ok(ExecResult::ok("inserted", inserted as i32))
-            }
-            Command::Select { table, fields } => {
-                let rows = self.execute_select(&table, fields.clone())?;
-                Ok(ExecResult {
-                    field_names: fields,
-                    fields: rows,
-                })
+                let (inserted, warnings) =
+                    self.execute_insert(&table, fields, values, replace, owner, transaction)?;
+                Ok(ExecResult::affected("inserted", inserted).with_warnings(warnings))
+            }
+            Command::Select { ref fields, .. } => {
+                if fields.is_empty() {
+                    return Ok(ExecResult::Rows {
+                        columns: vec![],
+                        types: vec![],
+                        rows: vec![],
+                    });
+                }
+                let plan = self.select_plan(command)?;
+                self.run(plan, transaction.as_deref(), token)
+            }
+            Command::Update {
+                table,
+                assignments,
+                conditions,
+            } => {
+                let (updated, warnings) = self.execute_update(
+                    &table,
+                    assignments,
+                    conditions,
+                    owner,
+                    transaction,
+                    token,
+                )?;
+                Ok(ExecResult::affected("updated", updated).with_warnings(warnings))
+            }
+            Command::CopyFrom {
+                table,
+                path,
+                options,
+            } => {
+                let (copied, warnings) =
+                    self.execute_copy_from(&table, &path, options, owner, transaction)?;
+                Ok(ExecResult::affected("copied", copied).with_warnings(warnings))
+            }
+            Command::CopyTo {
+                query,
+                path,
+                options,
+            } => {
+                let copied =
+                    self.execute_copy_to(*query, &path, options, transaction.as_deref(), token)?;
+                Ok(ExecResult::affected("copied", copied))
+            }
+            Command::Dump { path } => {
+                let dumped = self.execute_dump(&path, database)?;
+                Ok(ExecResult::affected("dumped", dumped))
+            }
+            Command::Restore { path } => {
+                let restored = self.execute_restore(&path, owner, database, token)?;
+                Ok(ExecResult::affected("restored", restored))
             }
             Command::Delete { table } => {
-                let deleted = self.execute_delete(&table)?;
-                Ok(ExecResult {
-                    field_names: vec!["deleted".to_string()],
-                    fields: vec![vec![Col::int(deleted)]],
-                })
+                self.locks
+                    .lock(owner, Resource::table(&table), LockMode::Exclusive)?;
+                let deleted = self.execute_delete(&table, transaction)?;
+                Ok(ExecResult::affected("deleted", deleted as usize))
+            }
+            Command::Drop { table } => {
+                self.locks
+                    .lock(owner, Resource::table(&table), LockMode::Exclusive)?;
+                let row_type = self.storage.get_row_type(&table)?;
+                self.check_dependents("drop", &table)?;
+                self.storage.drop_table(&table)?;
+                self.storage.statistics().drop(&table)?;
+                for column in row_type.auto_increment() {
+                    self.storage
+                        .sequences()
+                        .drop(&sequence_name(&table, column))?;
+                }
+                Ok(ExecResult::ack("dropped"))
+            }
+            Command::RenameTable { table, name } => {
+                self.locks
+                    .lock(owner, Resource::table(&table), LockMode::Exclusive)?;
+                self.locks
+                    .lock(owner, Resource::table(&name), LockMode::Exclusive)?;
+                self.execute_rename_table(&table, &name)?;
+                Ok(ExecResult::ack("renamed"))
+            }
+            Command::Vacuum { table } => self.execute_vacuum(table, owner, database, token),
+            Command::Analyze { table } => {
+                let analyzed = self.execute_analyze(table, database, token)?;
+                Ok(ExecResult::affected("analyzed", analyzed))
+            }
+            Command::ShowTableStatus { table } => self.execute_show_table_status(&table),
+            Command::CheckTable { table } => self.execute_check_table(&table),
+            Command::Explain { command, analyze } => {
+                self.execute_explain(*command, analyze, transaction.as_deref(), token)
+            }
+            Command::Use { .. } | Command::Begin | Command::Commit | Command::Rollback => Err(
+                DbError::InvalidInput(format!("'{}' cannot run inside a statement", command)),
+            ),
+        }
+    }
+
+    pub fn plan(&self, plan: LogicalPlan) -> Result<PhysicalPlan, DbError> {
+        Planner::new(&self.storage).plan(plan)
+    }
+
+    pub fn execute_plan(&self, plan: LogicalPlan) -> Result<ExecResult, DbError> {
+        let transaction = self.lock_transaction()?;
+        match transaction.as_ref() {
+            Some(transaction) => self.run(plan, Some(transaction), &CancelToken::new()),
+            None => {
+                drop(transaction);
+                self.run(plan, None, &CancelToken::new())
+            }
+        }
+    }
+
+    fn lock_transaction(&self) -> Result<MutexGuard<'_, Option<Transaction>>, DbError> {
+        self.transaction
+            .lock()
+            .map_err(|_| DbError::unexpected("transaction lock is poisoned"))
+    }
+
+    fn lock_queries(&self) -> Result<MutexGuard<'_, HashMap<u64, CancelToken>>, DbError> {
+        self.queries
+            .lock()
+            .map_err(|_| DbError::unexpected("queries lock is poisoned"))
+    }
+
+    pub(crate) fn start_query(&self, id: u64) -> Result<CancelToken, DbError> {
+        let token = CancelToken::new();
+        self.lock_queries()?.insert(id, token.clone());
+        Ok(token)
+    }
+
+    pub(crate) fn finish_query(&self, id: u64) -> Result<(), DbError> {
+        self.lock_queries()?.remove(&id);
+        Ok(())
+    }
+
+    fn lock_namespace(&self) -> Result<MutexGuard<'_, Namespace>, DbError> {
+        self.namespace
+            .lock()
+            .map_err(|_| DbError::unexpected("namespace lock is poisoned"))
+    }
+
+    pub(crate) fn drop_temporary(&self, namespace: &Namespace) -> Result<(), DbError> {
+        let Some(schema) = namespace.schema() else {
+            return Ok(());
+        };
+        let owner = self.next_owner();
+        let dropped = namespace.temporary().into_iter().try_for_each(|table| {
+            self.execute_command(
+                Command::Drop { table },
+                owner,
+                None,
+                None,
+                &CancelToken::new(),
+            )
+            .map(|_| ())
+        });
+        self.locks.release(owner);
+        self.storage.drop_temporary(schema)?;
+        dropped
+    }
+
+    fn next_owner(&self) -> u64 {
+        self.next_owner.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn run(
+        &self,
+        plan: LogicalPlan,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+    ) -> Result<ExecResult, DbError> {
+        let started = Instant::now();
+        let types = Planner::new(&self.storage).types(&plan)?;
+        let plan = self.plan(plan)?;
+        debug!(elapsed = ?started.elapsed(), "planned");
+        let started = Instant::now();
+        let rows = self.read(&plan, transaction, token)?;
+        debug!(elapsed = ?started.elapsed(), rows = rows.len(), "read");
+        Ok(ExecResult::Rows {
+            columns: plan.columns(),
+            types,
+            rows,
+        })
+    }
+
+    fn read(
+        &self,
+        plan: &PhysicalPlan,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+    ) -> Result<Vec<Vec<Col>>, DbError> {
+        let budget = self.budget();
+        let mut reservation = budget.reserve();
+        let mut rows = Vec::new();
+        for row in self.stream_with(plan, transaction, token, budget)? {
+            let row = row?;
+            reservation.grow(footprint(&row))?;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    fn stream(
+        &self,
+        plan: &PhysicalPlan,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+    ) -> Result<Rows, DbError> {
+        self.stream_with(plan, transaction, token, self.budget())
+    }
+
+    fn stream_with(
+        &self,
+        plan: &PhysicalPlan,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+        budget: Budget,
+    ) -> Result<Rows, DbError> {
+        let snapshot = self.storage.snapshot(&plan.tables())?;
+        Executor::new(snapshot, transaction, budget)
+            .with_token(token)
+            .execute(plan)
+    }
+
+    fn execute_explain(
+        &self,
+        command: Command,
+        analyze: bool,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+    ) -> Result<ExecResult, DbError> {
+        if !matches!(command, Command::Select { .. }) {
+            return Err(DbError::invalid_input("only SELECT can be explained"));
+        }
+        let plan = self.plan(self.select_plan(command)?)?;
+        let mut columns = vec!["plan".to_string(), "rows".to_string(), "cost".to_string()];
+        let profile = Profile::default();
+        if analyze {
+            let snapshot = self.storage.snapshot(&plan.tables())?;
+            let executor = Executor::new(snapshot, transaction, self.budget())
+                .with_token(token)
+                .with_profile(&profile);
+            for row in executor.execute(&plan)? {
+                row?;
+            }
+            columns.push("actual".to_string());
+        }
+        let mut rows = Vec::new();
+        let mut width = 0;
+        let planner = Planner::new(&self.storage);
+        let mut nodes = vec![(&plan, 0)];
+        while let Some((node, depth)) = nodes.pop() {
+            let line = format!("{}{}", "  ".repeat(depth), node.label());
+            width = width.max(line.len() as u16);
+            let mut row = vec![
+                Col::Varchar(line.clone(), line.len() as u16),
+                Col::BigInt(planner.estimate(node)? as i64),
+                Col::BigInt(planner.cost(node)? as i64),
+            ];
+            if analyze {
+                row.push(Col::BigInt(profile.rows(node) as i64));
+            }
+            rows.push(row);
+            nodes.extend(
+                node.children()
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, depth + 1)),
+            );
+        }
+        let mut types = vec![
+            ColType::varchar("plan", width),
+            ColType::bigint("rows"),
+            ColType::bigint("cost"),
+        ];
+        if analyze {
+            types.push(ColType::bigint("actual"));
+        }
+        Ok(ExecResult::Rows {
+            columns,
+            types,
+            rows,
+        })
+    }
+
+    fn execute_check_table(&self, table: &str) -> Result<ExecResult, DbError> {
+        let mut problems = self.storage.check(table)?;
+        if problems.is_empty() {
+            problems.push(("status", "ok".to_string()));
+        }
+        let varchar = |value: &str| Col::Varchar(value.to_string(), value.len() as u16);
+        let width = problems
+            .iter()
+            .map(|(_, message)| message.len())
+            .max()
+            .unwrap_or_default();
+        Ok(ExecResult::Rows {
+            columns: vec![
+                "table".to_string(),
+                "kind".to_string(),
+                "message".to_string(),
+            ],
+            types: vec![
+                ColType::varchar("table", table.len() as u16),
+                ColType::varchar("kind", 6),
+                ColType::varchar("message", width as u16),
+            ],
+            rows: problems
+                .into_iter()
+                .map(|(kind, message)| vec![varchar(table), varchar(kind), varchar(&message)])
+                .collect(),
+        })
+    }
+
+    fn select_plan(&self, command: Command) -> Result<LogicalPlan, DbError> {
+        Planner::new(&self.storage).select(command)
+    }
+
+    fn execute_show_table_status(&self, table: &str) -> Result<ExecResult, DbError> {
+        let stats = self.storage.stats(table)?;
+        let types = vec![
+            ColType::varchar("table", table.len() as u16),
+            ColType::bigint("entries"),
+            ColType::int("depth"),
+            ColType::int("leaf_pages"),
+            ColType::int("node_pages"),
+            ColType::int("free_pages"),
+            ColType::bigint("data_size"),
+            ColType::bigint("dead_size"),
+            ColType::bigint("reclaimable_size"),
+        ];
+        Ok(ExecResult::Rows {
+            columns: types
+                .iter()
+                .map(|col_type| col_type.get_name().to_string())
+                .collect(),
+            types,
+            rows: vec![vec![
+                Col::Varchar(table.to_string(), table.len() as u16),
+                Col::big_int(stats.entries as i64),
+                Col::int(stats.depth as i32),
+                Col::int(stats.leaf_pages as i32),
+                Col::int(stats.node_pages as i32),
+                Col::int(stats.free_pages as i32),
+                Col::big_int(stats.data_size() as i64),
+                Col::big_int(stats.dead_size as i64),
+                Col::big_int(stats.reclaimable_size() as i64),
+            ]],
+        })
+    }
+
+    fn execute_create(
+        &self,
+        name: &str,
+        columns: Vec<ColType>,
+        constraints: Vec<Constraint>,
+    ) -> Result<usize, DbError> {
+        let row_type = RowType {
+            columns,
+            constraints,
+        };
+        defaults::validate(name, &row_type)?;
+        for column in row_type.auto_increment() {
+            self.storage
+                .sequences()
+                .create(&sequence_name(name, column), 1)?;
+        }
+        self.storage.create(name, row_type)
+    }
+
+    fn execute_create_view(&self, name: &str, definition: &str) -> Result<(), DbError> {
+        let (database, table) = database::split(name);
+        if self
+            .storage
+            .tables(database)?
+            .iter()
+            .any(|found| found == table)
+        {
+            return Err(DbError::InvalidInput(format!(
+                "relation '{}' already exists",
+                name
+            )));
+        }
+        Planner::new(&self.storage).define(name, definition)?;
+        self.storage.views().create(name, definition)
+    }
+
+    fn execute_rename_table(&self, name: &str, to: &str) -> Result<(), DbError> {
+        if self.storage.views().get(to)?.is_some() {
+            return Err(DbError::InvalidInput(format!(
+                "relation '{}' already exists",
+                to
+            )));
+        }
+        self.check_dependents("rename", name)?;
+        self.storage.rename_table(name, to)
+    }
+
+    fn check_dependents(&self, action: &str, table: &str) -> Result<(), DbError> {
+        let mut sources = BTreeMap::new();
+        for (view, definition) in self.storage.views().list()? {
+            let Command::CreateView { query, .. } = parser::parse(&definition)? else {
+                return Err(DbError::unexpected("expected CREATE VIEW"));
+            };
+            let namespace = Namespace::new(database::split(&view).0);
+            let Command::Select { table, .. } = database::qualify(*query, &namespace) else {
+                return Err(DbError::unexpected("expected SELECT"));
+            };
+            sources.insert(view, table);
+        }
+        for (view, mut source) in sources.iter() {
+            let mut visited = BTreeSet::new();
+            while source != table && visited.insert(source) {
+                match sources.get(source) {
+                    Some(next) => source = next,
+                    None => break,
+                }
+            }
+            if source == table {
+                return Err(DbError::InvalidInput(format!(
+                    "cannot {} '{}': view '{}' depends on it",
+                    action, table, view
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_insert(
+        &self,
+        name: &str,
+        fields: Vec<String>,
+        values: Vec<Vec<String>>,
+        replace: bool,
+        owner: u64,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<(usize, Vec<String>), DbError> {
+        let row_type = self.storage.get_row_type(name)?;
+        let mut defaults =
+            Defaults::new(name, &row_type, self.storage.sequences(), self.varchar_mode)?;
+        defaults.check_fields(&fields)?;
+        let rows = values
+            .into_iter()
+            .map(|group| defaults.row(&fields, group).map(keyed))
+            .collect::<Result<Vec<_>, _>>()?;
+        defaults.advance(&rows)?;
+        self.locks
+            .lock(owner, Resource::table(name), LockMode::Intention)?;
+        for (key, _) in rows.iter() {
+            self.locks
+                .lock(owner, Resource::key(name, key), LockMode::Exclusive)?;
+        }
+        let inserted = self.write_rows(name, rows, replace, transaction)?;
+        Ok((inserted, defaults.warnings()))
+    }
+
+    fn execute_copy_from(
+        &self,
+        name: &str,
+        path: &str,
+        options: CopyOptions,
+        owner: u64,
+        mut transaction: Option<&mut Transaction>,
+    ) -> Result<(usize, Vec<String>), DbError> {
+        let row_type = self.storage.get_row_type(name)?;
+        let mut reader = csv::Reader::new(BufReader::new(File::open(path)?), options.delimiter);
+        let fields: Vec<String> = match options.header {
+            true => reader
+                .record()?
+                .ok_or_else(|| DbError::eof("expected CSV header"))?,
+            false => row_type
+                .columns
+                .iter()
+                .map(|col_type| col_type.get_name().to_string())
+                .collect(),
+        };
+        let mut defaults =
+            Defaults::new(name, &row_type, self.storage.sequences(), self.varchar_mode)?;
+        defaults.check_fields(&fields)?;
+        self.locks
+            .lock(owner, Resource::table(name), LockMode::Exclusive)?;
+        let mut copied = 0;
+        let mut batch = Vec::with_capacity(COPY_BATCH);
+        while let Some(record) = reader.record()? {
+            let row = defaults
+                .row(&fields, record)
+                .map_err(|err| DbError::InvalidInput(format!("line {}: {}", reader.line(), err)))?;
+            batch.push(keyed(row));
+            if batch.len() == COPY_BATCH {
+                let rows = mem::take(&mut batch);
+                defaults.advance(&rows)?;
+                copied += self.write_rows(name, rows, false, transaction.as_deref_mut())?;
+            }
+        }
+        defaults.advance(&batch)?;
+        copied += self.write_rows(name, batch, false, transaction)?;
+        Ok((copied, defaults.warnings()))
+    }
+
+    fn execute_copy_to(
+        &self,
+        query: Command,
+        path: &str,
+        options: CopyOptions,
+        transaction: Option<&Transaction>,
+        token: &CancelToken,
+    ) -> Result<usize, DbError> {
+        let logical = self.select_plan(query)?;
+        if path.to_lowercase().ends_with(".parquet") {
+            if options != CopyOptions::default() {
+                return Err(DbError::invalid_input(
+                    "COPY options are not supported for parquet output",
+                ));
             }
+            let types = Planner::new(&self.storage).types(&logical)?;
+            let plan = self.plan(logical)?;
+            let rows = self.stream(&plan, transaction, token)?;
+            let output = BufWriter::new(File::create(path)?);
+            let mut writer = parquet::Writer::new(output, plan.columns(), types)?;
+            let mut copied = 0;
+            for row in rows {
+                writer.row(row?)?;
+                copied += 1;
+            }
+            writer.finish()?;
+            return Ok(copied);
+        }
+        let plan = self.plan(logical)?;
+        let rows = self.stream(&plan, transaction, token)?;
+        let mut writer = csv::Writer::new(BufWriter::new(File::create(path)?), options.delimiter);
+        if options.header {
+            writer.record(&plan.columns())?;
         }
+        let mut copied = 0;
+        for row in rows {
+            let record: Vec<String> = row?.iter().map(Col::to_string).collect();
+            writer.record(&record)?;
+            copied += 1;
+        }
+        writer.finish()?;
+        Ok(copied)
+    }
+
+    fn execute_analyze(
+        &self,
+        table: Option<String>,
+        database: Option<&str>,
+        token: &CancelToken,
+    ) -> Result<usize, DbError> {
+        let tables = self.relations(table, database)?;
+        for table in tables.iter() {
+            self.analyze_table(table, token)?;
+        }
+        Ok(tables.len())
+    }
+
+    fn execute_vacuum(
+        &self,
+        table: Option<String>,
+        owner: u64,
+        database: Option<&str>,
+        token: &CancelToken,
+    ) -> Result<ExecResult, DbError> {
+        let mut rows = Vec::new();
+        let mut width = 0;
+        for table in self.relations(table, database)? {
+            self.locks
+                .lock(owner, Resource::table(&table), LockMode::Exclusive)?;
+            let reclaimed = self.storage.vacuum(&table)?;
+            self.analyze_table(&table, token)?;
+            width = width.max(table.len() as u16);
+            rows.push(vec![
+                Col::Varchar(table.clone(), table.len() as u16),
+                Col::BigInt(reclaimed as i64),
+            ]);
+        }
+        Ok(ExecResult::Rows {
+            columns: vec!["table".to_string(), "reclaimed".to_string()],
+            types: vec![
+                ColType::varchar("table", width),
+                ColType::bigint("reclaimed"),
+            ],
+            rows,
+        })
+    }
+
+    fn relations(
+        &self,
+        table: Option<String>,
+        database: Option<&str>,
+    ) -> Result<Vec<String>, DbError> {
+        let tables: Vec<String> = match table {
+            Some(table) => vec![table],
+            None => self
+                .storage
+                .tables(database)?
+                .into_iter()
+                .map(|table| database::relation(table, database))
+                .collect(),
+        };
+        for table in tables.iter() {
+            if self.storage.get_row_type(table)?.columns.is_empty() {
+                return Err(DbError::TableNotFound(table.clone()));
+            }
+        }
+        Ok(tables)
+    }
+
+    fn analyze_table(&self, table: &str, token: &CancelToken) -> Result<(), DbError> {
+        let row_type = self.storage.get_row_type(table)?;
+        let rows = self.stream(&self.plan(LogicalPlan::scan(table))?, None, token)?;
+        let stats = statistics::analyze(&row_type, rows)?;
+        self.storage.statistics().store(table, stats)
+    }
+
+    fn execute_dump(&self, path: &str, database: Option<&str>) -> Result<usize, DbError> {
+        let tables = self.storage.tables(database)?;
+        let relation = |table: &str| database::relation(table.to_string(), database);
+        let snapshot = self
+            .storage
+            .snapshot(&tables.iter().map(|table| relation(table)).collect())?;
+        let mut output = BufWriter::new(File::create(path)?);
+        let mut dumped = 0;
+        let mut write = |command: Command| -> Result<(), DbError> {
+            writeln!(output, "{};", command)?;
+            dumped += 1;
+            Ok(())
+        };
+        let mut owned = BTreeSet::new();
+        for table in tables.iter() {
+            let row_type = self.storage.get_row_type(&relation(table))?;
+            owned.extend(
+                row_type
+                    .auto_increment()
+                    .map(|column| sequence_name(table, column)),
+            );
+        }
+        for (name, start) in self.storage.sequences().list()? {
+            let Some(name) = database::local(&name, database) else {
+                continue;
+            };
+            if !owned.contains(&name) {
+                write(Command::CreateSequence { name, start })?;
+            }
+        }
+        for table in tables {
+            let row_type = self.storage.get_row_type(&relation(&table))?;
+            let fields: Vec<String> = row_type
+                .columns
+                .iter()
+                .map(|col_type| col_type.get_name().to_string())
+                .collect();
+            write(Command::Create {
+                name: table.clone(),
+                fields: row_type.columns.clone(),
+                constraints: row_type.constraints.clone(),
+                temporary: false,
+            })?;
+            let mut from = Bound::Unbounded;
+            loop {
+                let rows = snapshot.scan(&relation(&table), from, DUMP_BATCH)?;
+                let Some(last) = rows.last() else {
+                    break;
+                };
+                from = Bound::Excluded(last.columns[0].clone());
+                let done = rows.len() < DUMP_BATCH;
+                let values = rows
+                    .iter()
+                    .map(|row| {
+                        row.columns
+                            .iter()
+                            .map(|col| dump_value(&table, col))
+                            .collect()
+                    })
+                    .collect::<Result<_, _>>()?;
+                write(Command::Insert {
+                    table: table.clone(),
+                    fields: fields.clone(),
+                    values,
+                    replace: false,
+                })?;
+                if done {
+                    break;
+                }
+            }
+            for (index, columns) in self.storage.indexes(&relation(&table))? {
+                write(Command::CreateIndex {
+                    name: index,
+                    table: table.clone(),
+                    columns: columns
+                        .iter()
+                        .map(|term| term.name(&fields[term.column]))
+                        .collect(),
+                })?;
+            }
+        }
+        let mut views = Vec::new();
+        for (name, definition) in self.storage.views().list()? {
+            let Some(name) = database::local(&name, database) else {
+                continue;
+            };
+            let Command::CreateView { query, .. } = parser::parse(&definition)? else {
+                return Err(DbError::unexpected("expected CREATE VIEW"));
+            };
+            let Command::Select { table, .. } = *query else {
+                return Err(DbError::unexpected("expected SELECT"));
+            };
+            views.push((name, table, definition));
+        }
+        while !views.is_empty() {
+            let pending: BTreeSet<String> = views.iter().map(|(name, ..)| name.clone()).collect();
+            let (ready, blocked): (Vec<_>, Vec<_>) = views
+                .into_iter()
+                .partition(|(_, table, _)| !pending.contains(table));
+            if ready.is_empty() {
+                return Err(DbError::invalid_input("views reference each other"));
+            }
+            for (_, _, definition) in ready {
+                writeln!(output, "{};", definition)?;
+                dumped += 1;
+            }
+            views = blocked;
+        }
+        output.flush()?;
+        Ok(dumped)
+    }
+
+    fn execute_restore(
+        &self,
+        path: &str,
+        owner: u64,
+        database: Option<&str>,
+        token: &CancelToken,
+    ) -> Result<usize, DbError> {
+        let mut script = dump::Script::new(BufReader::new(File::open(path)?));
+        let mut restored = 0;
+        while let Some((line, statement)) = script.statement()? {
+            token.check()?;
+            parser::parse(&statement)
+                .and_then(|command| match command {
+                    Command::Create {
+                        temporary: true, ..
+                    } => Err(DbError::invalid_input(
+                        "temporary tables cannot be restored",
+                    )),
+                    command => Ok(database::qualify(command, &Namespace::new(database))),
+                })
+                .and_then(|command| self.execute_command(command, owner, None, database, token))
+                .map_err(|err| DbError::InvalidInput(format!("line {}: {}", line, err)))?;
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    fn write_rows(
+        &self,
+        name: &str,
+        rows: Vec<(Col, Row)>,
+        replace: bool,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<usize, DbError> {
+        let Some(transaction) = transaction else {
+            return match replace {
+                true => self.storage.upsert(name, rows),
+                false => self.storage.insert(name, rows),
+            };
+        };
+        if !replace {
+            let mut keys = BTreeSet::new();
+            for (key, _) in rows.iter() {
+                if !keys.insert(key) || self.exists(transaction, name, key)? {
+                    return Err(DbError::DuplicateKey(name.to_string(), key.to_string()));
+                }
+            }
+        }
+        let len = rows.len();
+        for (key, row) in rows {
+            transaction.put(name, key, row);
+        }
+        Ok(len)
+    }
+
+    fn execute_update(
+        &self,
+        table: &str,
+        assignments: Vec<Assignment>,
+        conditions: Vec<Comparison>,
+        owner: u64,
+        transaction: Option<&mut Transaction>,
+        token: &CancelToken,
+    ) -> Result<(usize, Vec<String>), DbError> {
+        let row_type = self.storage.get_row_type(table)?;
+        let mut targets = Vec::with_capacity(assignments.len());
+        for assignment in assignments {
+            let Some(position) = row_type
+                .columns
+                .iter()
+                .position(|col_type| col_type.get_name() == assignment.column)
+            else {
+                return Err(DbError::field_not_found(&assignment.column, table));
+            };
+            targets.push((position, assignment.value));
+        }
+        let plan = self.plan(Planner::new(&self.storage).filter_plan(table, conditions)?)?;
+        self.locks
+            .lock(owner, Resource::table(table), LockMode::Intention)?;
+        let mut locked = BTreeSet::new();
+        let rows = loop {
+            let rows = self.read(&plan, transaction.as_deref(), token)?;
+            let unlocked: Vec<&Col> = rows
+                .iter()
+                .map(|row| &row[0])
+                .filter(|key| !locked.contains(*key))
+                .collect();
+            if unlocked.is_empty() {
+                break rows;
+            }
+            for key in unlocked {
+                self.locks
+                    .lock(owner, Resource::key(table, key), LockMode::Exclusive)?;
+                locked.insert(key.clone());
+            }
+        };
+        let mut updates = Vec::with_capacity(rows.len());
+        let mut warnings = Warnings::default();
+        for row in rows {
+            let mut columns = row.clone();
+            for (position, expr) in targets.iter() {
+                let target = &row_type.columns[*position];
+                columns[*position] = eval::evaluate(expr, table, &row_type, &row, target)?;
+            }
+            constraints::check(
+                table,
+                &row_type,
+                &mut columns,
+                self.varchar_mode,
+                &mut warnings,
+            )?;
+            self.locks.lock(
+                owner,
+                Resource::key(table, &columns[0]),
+                LockMode::Exclusive,
+            )?;
+            updates.push((row[0].clone(), Row { columns }));
+        }
+        let warnings = warnings.into_vec();
+        let Some(transaction) = transaction else {
+            return Ok((self.storage.update(table, updates)?, warnings));
+        };
+        let old_keys: BTreeSet<&Col> = updates.iter().map(|(key, _)| key).collect();
+        let mut new_keys = BTreeSet::new();
+        for (_, row) in updates.iter() {
+            let key = &row.columns[0];
+            let exists = !old_keys.contains(key) && self.exists(transaction, table, key)?;
+            if !new_keys.insert(key) || exists {
+                return Err(DbError::DuplicateKey(table.to_string(), key.to_string()));
+            }
+        }
+        for (key, row) in updates.iter() {
+            if row.columns[0] != *key {
+                transaction.delete(table, key.clone());
+            }
+        }
+        let len = updates.len();
+        for (_, row) in updates {
+            transaction.put(table, row.columns[0].clone(), row);
+        }
+        Ok((len, warnings))
+    }
+
+    fn execute_delete(
+        &self,
+        from: &str,
+        transaction: Option<&mut Transaction>,
+    ) -> Result<i32, DbError> {
+        let Some(transaction) = transaction else {
+            return self.storage.delete_all(from);
+        };
+        let rows = transaction.scan(from, self.storage.select_all(from)?);
+        let len = rows.len();
+        for row in rows {
+            transaction.delete(from, row.columns[0].clone());
+        }
+        Ok(len as i32)
+    }
+
+    fn exists(&self, transaction: &Transaction, table: &str, key: &Col) -> Result<bool, DbError> {
+        Ok(match transaction.get(table, key) {
+            Some(row) => row.is_some(),
+            None => self.storage.search(table, key.clone())?.is_some(),
+        })
+    }
+}
+
+fn dump_value(table: &str, col: &Col) -> Result<String, DbError> {
+    let value = col.to_string();
+    if !dump::literal(&value) {
+        return Err(DbError::InvalidInput(format!(
+            "cannot dump value {:?} of relation '{}'",
+            value, table
+        )));
+    }
+    Ok(value)
+}
+
+fn failed(metrics: &Counters, err: &DbError) {
+    metrics.error();
+    warn!(error = %err, code = err.code(), "statement failed");
+}
+
+fn writes(command: &Command) -> bool {
+    !matches!(
+        command,
+        Command::Select { .. }
+            | Command::Use { .. }
+            | Command::ShowTableStatus { .. }
+            | Command::CheckTable { .. }
+            | Command::Explain { .. }
+            | Command::CopyTo { .. }
+            | Command::Dump { .. }
+            | Command::Begin
+            | Command::Commit
+            | Command::Rollback
+    )
+}
+
+fn batched(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Insert { .. }
+            | Command::Update { .. }
+            | Command::Delete { .. }
+            | Command::Select { .. }
+    )
+}
+
+fn keyed(columns: Vec<Col>) -> (Col, Row) {
+    (columns[0].clone(), Row { columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        engine
+            .execute(Command::Create {
+                name: "test".to_string(),
+                fields: vec![ColType::int("id"), ColType::bigint("money")],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        engine
+            .execute(Command::Insert {
+                table: "test".to_string(),
+                fields: vec!["id".to_string()],
+                values: vec![vec![1.to_string()], vec![2.to_string()]],
+                replace: false,
+            })
+            .unwrap();
+        let rows = engine
+            .execute(Command::Select {
+                fields: vec!["id".to_string()],
+                table: "test".to_string(),
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
+            })
+            .unwrap();
+        assert_eq!(
+            rows,
+            ExecResult::Rows {
+                columns: vec!["id".to_string()],
+                types: vec![ColType::int("id")],
+                rows: vec![vec![Col::int(1)], vec![Col::int(2)]],
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        engine
+            .execute(Command::Create {
+                name: "test".to_string(),
+                fields: vec![ColType::int("id")],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        let Err(err) = engine.execute(Command::Select {
+            table: "test".to_string(),
+            fields: vec!["name".to_string()],
+            conditions: vec![],
+            distinct: false,
+            group_by: vec![],
+            having: vec![],
+            order_by: vec![],
+            limit: None,
+        }) else {
+            panic!("wrong field not validated");
+        };
+        assert_eq!(
+            "field 'name' of relation 'test' doesn't exist",
+            err.to_string()
+        );
+        let Err(err) = engine.execute(Command::Insert {
+            table: "test".to_string(),
+            fields: vec!["id".to_string()],
+            values: vec![vec!["1".to_string(), "name".to_string()]],
+            replace: false,
+        }) else {
+            panic!("invalid amount of field is not validated");
+        };
+        assert_eq!(
+            "invalid input: wrong amount of insert values",
+            err.to_string()
+        );
+        let Err(err) = engine.execute(Command::Insert {
+            table: "test".to_string(),
+            fields: vec!["name".to_string()],
+            values: vec![vec!["name".to_string()]],
+            replace: false,
+        }) else {
+            panic!("invalid amount of field is not validated");
+        };
+        assert_eq!("PRIMARY_KEY constraint is not set", err.to_string());
+        let Err(err) = engine.execute(Command::Insert {
+            table: "test".to_string(),
+            fields: vec!["id".to_string(), "name".to_string()],
+            values: vec![vec!["1".to_string(), "name".to_string()]],
+            replace: false,
+        }) else {
+            panic!("invalid amount of field is not validated");
+        };
+        assert_eq!(
+            "field 'name' of relation 'test' doesn't exist",
+            err.to_string()
+        );
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("INSERT INTO test(id) VALUES('7.0')").unwrap();
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "id".to_string(),
+                "test".to_string(),
+                "abc".to_string()
+            )),
+            execute("INSERT INTO test(id) VALUES('abc')")
+        );
+        assert_eq!(
+            "invalid value '7.5' for field 'id' of relation 'test'",
+            execute("SELECT id FROM test WHERE id = 7.5")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "invalid value 'x' for field 'count(*)' of relation 'test'",
+            execute("SELECT count(*) FROM test HAVING count(*) > x")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            vec![vec![Col::int(7)]],
+            execute("SELECT id FROM test WHERE id = '7'")
+                .unwrap()
+                .rows()
+        );
+    }
+
+    #[test]
+    fn insert_oversized_varchar() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        engine
+            .execute(Command::Create {
+                name: "test".to_string(),
+                fields: vec![ColType::int("id"), ColType::varchar("name", 4)],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        let result = engine.execute(Command::Insert {
+            table: "test".to_string(),
+            fields: vec!["id".to_string(), "name".to_string()],
+            values: vec![vec!["1".to_string(), "ёжик".to_string()]],
+            replace: false,
+        });
+        assert_eq!(
+            Err(DbError::TooLong(
+                "name".to_string(),
+                "test".to_string(),
+                8,
+                4
+            )),
+            result
+        );
+        let rows = engine
+            .execute(Command::Select {
+                fields: vec!["id".to_string()],
+                table: "test".to_string(),
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
+            })
+            .unwrap();
+        assert!(rows.rows().is_empty());
+    }
+
+    #[test]
+    fn lenient_varchar() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        engine.set_varchar_mode(VarcharMode::Lenient);
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        execute(&engine, "CREATE TABLE test(id int, name varchar(4))").unwrap();
+        let result = execute(
+            &engine,
+            "INSERT INTO test(id, name) VALUES(1, 'ёжик'), (2, 'ann')",
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["value of field 'name' of relation 'test' truncated from 8 to 4 bytes"],
+            result.warnings()
+        );
+        let result = execute(&engine, "UPDATE test SET name = 'annie' WHERE id = 2").unwrap();
+        assert_eq!(
+            vec!["value of field 'name' of relation 'test' truncated from 5 to 4 bytes"],
+            result.warnings()
+        );
+        let path = temp_dir.path().join("test.csv");
+        fs::write(&path, "3,bob\n4,kimberly\n").unwrap();
+        let result = execute(&engine, &format!("COPY test FROM '{}'", path.display())).unwrap();
+        assert_eq!(1, result.warnings().len());
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::varchar("ёж", 4)],
+                vec![Col::int(2), Col::varchar("anni", 4)],
+                vec![Col::int(3), Col::varchar("bob", 4)],
+                vec![Col::int(4), Col::varchar("kimb", 4)],
+            ],
+            execute(&engine, "SELECT id, name FROM test")
+                .unwrap()
+                .rows()
+        );
+        assert!(
+            execute(&engine, "INSERT INTO test(id, name) VALUES(5, 'eve')")
+                .unwrap()
+                .warnings()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn insert_constraints() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        engine
+            .execute(Command::Create {
+                name: "test".to_string(),
+                fields: vec![
+                    ColType::int("id"),
+                    ColType::int("age"),
+                    ColType::varchar("name", 8),
+                ],
+                constraints: vec![Constraint::not_null("name")],
+                temporary: false,
+            })
+            .unwrap();
+        let insert = |fields: &[&str], values: &[&str]| {
+            engine.execute(Command::Insert {
+                table: "test".to_string(),
+                fields: fields.iter().map(|f| f.to_string()).collect(),
+                values: vec![values.iter().map(|v| v.to_string()).collect()],
+                replace: false,
+            })
+        };
+        assert_eq!(
+            Err(DbError::NotNull("name".to_string(), "test".to_string())),
+            insert(&["id", "age"], &["1", "30"])
+        );
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "age".to_string(),
+                "test".to_string(),
+                "3000000000".to_string()
+            )),
+            insert(&["id", "age", "name"], &["1", "3000000000", "bob"])
+        );
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "id".to_string(),
+                "test".to_string(),
+                "one".to_string()
+            )),
+            insert(&["id", "name"], &["one", "bob"])
+        );
+        assert!(insert(&["id", "name"], &["1", "bob"]).is_ok());
+        assert_eq!(
+            Err(DbError::field_not_found("email", "other")),
+            engine.execute(Command::Create {
+                name: "other".to_string(),
+                fields: vec![ColType::int("id")],
+                constraints: vec![Constraint::not_null("email")],
+                temporary: false,
+            })
+        );
+    }
+
+    #[test]
+    fn select_no_fields() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let result = engine
+            .execute(Command::Select {
+                table: "test".to_string(),
+                fields: vec![],
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
+            })
+            .unwrap();
+        assert!(result.columns().is_empty());
+        assert!(result.rows().is_empty());
+    }
+
+    #[test]
+    fn delete_all() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let result = engine
+            .execute(Command::Delete {
+                table: "test".to_string(),
+            })
+            .unwrap();
+        assert_eq!(ExecResult::affected("deleted", 0), result);
+    }
+
+    #[test]
+    fn vacuum() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(
+            Err(DbError::TableNotFound("test".to_string())),
+            execute("VACUUM test")
+        );
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        execute("CREATE INDEX test_age ON test(age)").unwrap();
+        execute("CREATE TABLE other(id int)").unwrap();
+        let values: Vec<String> = (0..2000).map(|i| format!("({}, {})", i, i % 40)).collect();
+        execute(&format!(
+            "INSERT INTO test(id, age) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("UPDATE test SET age = age + 1 WHERE id < 1000").unwrap();
+
+        let result = execute("VACUUM test").unwrap();
+        assert_eq!(["table", "reclaimed"], result.columns());
+        assert_eq!(Col::varchar("test", 4), result.rows()[0][0]);
+        let Col::BigInt(reclaimed) = result.rows()[0][1] else {
+            panic!("unexpected reclaimed value");
+        };
+        assert!(reclaimed > 0);
+        assert_eq!(
+            2000,
+            engine
+                .storage
+                .statistics()
+                .get("test")
+                .unwrap()
+                .unwrap()
+                .rows
+        );
+        assert_eq!(
+            Col::varchar("ok", 2),
+            execute("CHECK TABLE test").unwrap().rows()[0][2]
+        );
+        assert_eq!(
+            50,
+            execute("SELECT id FROM test WHERE age = 1")
+                .unwrap()
+                .rows()
+                .len()
+        );
+
+        let result = execute("VACUUM").unwrap();
+        assert_eq!(
+            vec![Col::varchar("other", 5), Col::varchar("test", 4)],
+            result
+                .rows()
+                .iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn drop_table() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, name varchar(8))").unwrap();
+        execute("INSERT INTO test(id, name) VALUES(1, 'ann')").unwrap();
+        assert_eq!(Ok(ExecResult::ack("dropped")), execute("DROP TABLE test"));
+        assert_eq!(
+            Err(DbError::TableNotFound("test".to_string())),
+            execute("DROP TABLE test")
+        );
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        execute("INSERT INTO test(id, age) VALUES(1, 30)").unwrap();
+        let result = execute("SELECT age FROM test").unwrap();
+        assert_eq!(vec![vec![Col::int(30)]], result.rows());
+
+        execute("BEGIN").unwrap();
+        assert!(execute("DROP TABLE test").is_err());
+        execute("ROLLBACK").unwrap();
+    }
+
+    #[test]
+    fn rename_table() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int AUTO_INCREMENT, name varchar(8))").unwrap();
+        execute("CREATE INDEX test_name ON test(name)").unwrap();
+        execute("INSERT INTO test(name) VALUES('ann') ('bob')").unwrap();
+        execute("ANALYZE test").unwrap();
+        execute("CREATE TABLE other(id int)").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input("relation 'other' already exists")),
+            execute("ALTER TABLE test RENAME TO other")
+        );
+        assert_eq!(
+            Ok(ExecResult::ack("renamed")),
+            execute("ALTER TABLE test RENAME TO users")
+        );
+        assert_eq!(
+            Err(DbError::TableNotFound("test".to_string())),
+            execute("ALTER TABLE test RENAME TO users")
+        );
+        assert!(execute("SELECT id FROM test").is_err());
+        execute("INSERT INTO users(name) VALUES('eve')").unwrap();
+        assert!(engine.storage.statistics().get("test").unwrap().is_none());
+        assert!(engine.storage.statistics().get("users").unwrap().is_some());
+        drop(engine);
+
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(
+            vec![vec![Col::int(3)]],
+            execute("SELECT id FROM users WHERE name = 'eve'")
+                .unwrap()
+                .rows()
+        );
+        let check = execute("CHECK TABLE users").unwrap();
+        assert_eq!("ok", check.rows()[0][2].to_string());
+
+        execute("CREATE TEMP TABLE scratch(id int)").unwrap();
+        execute("INSERT INTO scratch(id) VALUES(1)").unwrap();
+        execute("ALTER TABLE scratch RENAME TO notes").unwrap();
+        assert_eq!(
+            vec![vec![Col::int(1)]],
+            execute("SELECT id FROM notes").unwrap().rows()
+        );
+
+        execute("BEGIN").unwrap();
+        assert!(execute("ALTER TABLE users RENAME TO test").is_err());
+        execute("ROLLBACK").unwrap();
+    }
+
+    #[test]
+    fn rename_table_with_views() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, age int)").unwrap();
+        execute("CREATE TABLE orders(id int)").unwrap();
+        execute("INSERT INTO users(id, age) VALUES(1, 20)").unwrap();
+        execute("CREATE VIEW adults AS SELECT id FROM users WHERE age > 18").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot rename 'users': view 'adults' depends on it"
+            )),
+            execute("ALTER TABLE users RENAME TO people")
+        );
+        assert_eq!(
+            vec![vec![Col::int(1)]],
+            execute("SELECT id FROM adults").unwrap().rows()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot drop 'users': view 'adults' depends on it"
+            )),
+            execute("DROP TABLE users")
+        );
+        execute("ALTER TABLE orders RENAME TO purchases").unwrap();
+        execute("DROP TABLE purchases").unwrap();
+        execute("DROP VIEW adults").unwrap();
+
+        execute("CREATE VIEW recent AS SELECT id FROM users").unwrap();
+        execute("CREATE VIEW latest AS SELECT id FROM recent WHERE id > 0").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot drop 'users': view 'latest' depends on it"
+            )),
+            execute("DROP TABLE users")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot rename 'users': view 'latest' depends on it"
+            )),
+            execute("ALTER TABLE users RENAME TO people")
+        );
+        execute("DROP VIEW latest").unwrap();
+        execute("DROP VIEW recent").unwrap();
+        execute("ALTER TABLE users RENAME TO people").unwrap();
+        assert_eq!(
+            vec![vec![Col::int(1)]],
+            execute("SELECT id FROM people").unwrap().rows()
+        );
+    }
+
+    #[test]
+    fn query_cursor() {
+        let engine = Arc::new(Engine::in_memory());
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        let values: Vec<String> = (0..600).map(|i| format!("({}, {})", i, i % 3)).collect();
+        let query = format!("INSERT INTO test(id, age) VALUES{}", values.join(" "));
+        execute(&query).unwrap();
+
+        let query = |session: &mut Session, query: &str| {
+            session.query(parser::parse(query).unwrap()).unwrap()
+        };
+        let mut session = engine.session();
+        let mut cursor = query(&mut session, "SELECT id FROM test WHERE age = 0");
+        assert_eq!(["id"], cursor.field_names());
+        let page = cursor.fetch(3).unwrap();
+        assert_eq!(
+            vec![vec![Col::int(0)], vec![Col::int(3)], vec![Col::int(6)]],
+            page
+        );
+        execute("DELETE FROM test").unwrap();
+        assert_eq!(197, cursor.fetch(1000).unwrap().len());
+        assert!(cursor.next().is_none());
+
+        execute(&format!(
+            "INSERT INTO test(id, age) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        session.execute(Command::Begin).unwrap();
+        query(&mut session, "DELETE FROM test");
+        query(
+            &mut session,
+            "INSERT INTO test(id, age) VALUES(256, 1) (1000, 1)",
+        );
+        let cursor = query(&mut session, "SELECT id FROM test");
+        let ids: Vec<Vec<Col>> = cursor.collect::<Result<_, _>>().unwrap();
+        assert_eq!(vec![vec![Col::int(256)], vec![Col::int(1000)]], ids);
+        session.execute(Command::Rollback).unwrap();
+
+        let result = query(&mut session, "SELECT id FROM test")
+            .into_result()
+            .unwrap();
+        assert_eq!(600, result.rows().len());
+        let created = query(&mut session, "CREATE TABLE other(id int)");
+        assert!(created.into_result().unwrap().rows().is_empty());
+    }
+
+    #[test]
+    fn order_by() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        engine.set_memory_budget(1024);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(8))").unwrap();
+        let values: Vec<String> = (0..500)
+            .map(|i| format!("({}, {}, 'n{}')", i, (i * 37) % 50, i % 7))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, age, name) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        let result = execute("SELECT age, id FROM test WHERE id >= 100 ORDER BY age, id").unwrap();
+        assert_eq!(400, result.rows().len());
+        let mut expected: Vec<(i32, i32)> = (100..500).map(|i| ((i * 37) % 50, i)).collect();
+        expected.sort();
+        let sorted: Vec<(i32, i32)> = result
+            .rows()
+            .iter()
+            .map(|row| match (&row[0], &row[1]) {
+                (Col::Int(age), Col::Int(id)) => (*age, *id),
+                _ => panic!("expected ints"),
+            })
+            .collect();
+        assert_eq!(expected, sorted);
+        assert_eq!(
+            Err(DbError::field_not_found("email", "test")),
+            execute("SELECT id FROM test ORDER BY email")
+        );
+    }
+
+    #[test]
+    fn distinct() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        engine.set_memory_budget(256);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(8))").unwrap();
+        let values: Vec<String> = (0..300)
+            .map(|i| format!("({}, {}, 'n{}')", i, (i * 37) % 50, i % 3))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, age, name) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        let result = execute("SELECT DISTINCT age FROM test ORDER BY age").unwrap();
+        let expected: Vec<Vec<Col>> = (0..50).map(|age| vec![Col::int(age)]).collect();
+        assert_eq!(expected, result.rows());
+        let mut pairs = execute("SELECT DISTINCT name, age FROM test WHERE id < 200")
+            .unwrap()
+            .into_rows();
+        pairs.sort();
+        assert_eq!(150, pairs.len());
+        pairs.dedup();
+        assert_eq!(150, pairs.len());
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "for SELECT DISTINCT, ORDER BY column 'id' must appear in select list"
+            )),
+            execute("SELECT DISTINCT age FROM test ORDER BY id")
+        );
+    }
+
+    #[test]
+    fn spill_to_disk() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        engine.set_memory_budget(512);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, uid int, name varchar(8))").unwrap();
+        execute("CREATE TABLE orders(id int, user_id int, total int)").unwrap();
+        let users: Vec<String> = (0..100)
+            .map(|i| format!("({}, {}, 'u{}')", i, i, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO users(id, uid, name) VALUES{}",
+            users.join(" ")
+        ))
+        .unwrap();
+        let orders: Vec<String> = (0..400)
+            .map(|i| format!("({}, {}, {})", i, (i * 7) % 100, i % 10))
+            .collect();
+        execute(&format!(
+            "INSERT INTO orders(id, user_id, total) VALUES{}",
+            orders.join(" ")
+        ))
+        .unwrap();
+        let files = || {
+            let mut files: Vec<_> = fs::read_dir(temp_dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            files.sort();
+            files
+        };
+        let before = files();
+
+        let plan = LogicalPlan::scan("orders")
+            .join(LogicalPlan::scan("users"), "user_id", "uid")
+            .aggregate(
+                vec!["user_id".to_string()],
+                vec![
+                    plan::Aggregate::new(plan::Function::Count, None),
+                    plan::Aggregate::new(plan::Function::Sum, Some("total")),
+                ],
+            );
+        assert!(
+            engine
+                .plan(plan.clone())
+                .unwrap()
+                .to_string()
+                .contains("HashJoin")
+        );
+        let expected: Vec<Vec<Col>> = (0..100)
+            .map(|user: i32| {
+                let totals = (0..400).filter(|i| (i * 7) % 100 == user).map(|i| i % 10);
+                vec![
+                    Col::int(user),
+                    Col::big_int(4),
+                    Col::big_int(totals.sum::<i32>() as i64),
+                ]
+            })
+            .collect();
+        assert_eq!(expected, engine.execute_plan(plan).unwrap().into_rows());
+        assert_eq!(before, files());
+
+        let token = CancelToken::new();
+        let mut cursor = engine
+            .query_with(
+                parser::parse("SELECT id, total FROM orders ORDER BY total, id").unwrap(),
+                &token,
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                vec![Col::int(0), Col::int(0)],
+                vec![Col::int(10), Col::int(0)]
+            ],
+            cursor.fetch(2).unwrap()
+        );
+        token.cancel();
+        assert_eq!(Err(DbError::Cancelled), cursor.fetch(10));
+        drop(cursor);
+        assert_eq!(before, files());
+    }
+
+    #[test]
+    fn memory_cap() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        engine.set_memory_budget(1024);
+        engine.set_memory_cap(16 * 1024);
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, name varchar(32))").unwrap();
+        let values: Vec<String> = (0..1000)
+            .map(|i| format!("({}, 'name {:024}')", i, 1000 - i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, name) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+
+        let result = execute("SELECT id, name FROM test");
+        assert!(matches!(
+            result,
+            Err(DbError::OutOfMemoryBudget(_, limit)) if limit == 16 * 1024
+        ));
+        assert_eq!(
+            "53200",
+            execute("SELECT DISTINCT name FROM test")
+                .unwrap_err()
+                .code()
+        );
+
+        let mut cursor = engine
+            .query(parser::parse("SELECT id FROM test ORDER BY name").unwrap())
+            .unwrap();
+        let mut ids = Vec::new();
+        loop {
+            let rows = cursor.fetch(100).unwrap();
+            if rows.is_empty() {
+                break;
+            }
+            ids.extend(rows.into_iter().map(|row| row[0].clone()));
+        }
+        let expected: Vec<Col> = (0..1000).rev().map(Col::int).collect();
+        assert_eq!(expected, ids);
+        assert_eq!(
+            vec![vec![Col::big_int(1000)]],
+            execute("SELECT count(*) FROM test").unwrap().into_rows()
+        );
+    }
+
+    #[test]
+    fn group_by() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(8))").unwrap();
+        let select = |query: &str| execute(query).map(|result| result.into_rows());
+        assert_eq!(
+            vec![vec![Col::big_int(0), Col::big_int(0), Col::int(0)]],
+            select("SELECT count(*), sum(age), min(age) FROM test").unwrap()
+        );
+        assert!(
+            select("SELECT name, count(*) FROM test GROUP BY name")
+                .unwrap()
+                .is_empty()
+        );
+        execute(
+            "INSERT INTO test(id, age, name) VALUES(1, 20, 'ann') (2, 30, 'bob') (3, 25, 'ann') (4, 40, 'cid') (5, 31, 'bob')",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                vec![Col::varchar("ann", 8), Col::big_int(2), Col::big_int(22)],
+                vec![Col::varchar("bob", 8), Col::big_int(2), Col::big_int(30)],
+                vec![Col::varchar("cid", 8), Col::big_int(1), Col::big_int(40)],
+            ],
+            select("SELECT name, count(*), avg(age) FROM test GROUP BY name").unwrap()
+        );
+        assert_eq!(
+            vec![
+                vec![Col::varchar("bob", 8), Col::int(31)],
+                vec![Col::varchar("ann", 8), Col::int(25)],
+            ],
+            select(
+                "SELECT name, max(age) FROM test WHERE id > 1 GROUP BY name HAVING count(*) >= 1 AND min(age) < 40 ORDER BY max(age)"
+            )
+            .unwrap()
+            .into_iter()
+            .rev()
+            .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(5), Col::big_int(146), Col::int(40)]],
+            select("SELECT count(*), sum(age), max(age) FROM test").unwrap()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "column 'age' must appear in the GROUP BY clause or be used in an aggregate"
+            )),
+            select("SELECT name, age FROM test GROUP BY name")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot apply sum to name VARCHAR(8)"
+            )),
+            select("SELECT sum(name) FROM test")
+        );
+    }
+
+    #[test]
+    fn execute_batch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let commands = |queries: &[&str]| -> Vec<Command> {
+            queries
+                .iter()
+                .map(|query| parser::parse(query).unwrap())
+                .collect()
+        };
+        let results = engine.execute_batch(&commands(&[
+            "CREATE TABLE test(id int, age int)",
+            "INSERT INTO test(id, age) VALUES(1, 20) (2, 30)",
+            "INSERT INTO test(id, age) VALUES(2, 40)",
+            "INSERT INTO test(id, age) VALUES(3, 25)",
+            "SELECT count(*) FROM test",
+            "CREATE INDEX test_age ON test(age)",
+            "UPDATE test SET age = 26 WHERE id = 3",
+        ]));
+        assert_eq!(7, results.len());
+        assert_eq!(Ok(ExecResult::affected("inserted", 2)), results[1]);
+        assert_eq!(
+            Err(DbError::DuplicateKey("test".to_string(), "2".to_string())),
+            results[2]
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(3)]],
+            results[4].as_ref().unwrap().rows()
+        );
+        assert!(results.iter().enumerate().all(|(i, r)| i == 2 || r.is_ok()));
+        let select = |query: &str| {
+            engine
+                .execute(parser::parse(query).unwrap())
+                .unwrap()
+                .into_rows()
+        };
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::int(20)],
+                vec![Col::int(2), Col::int(30)],
+                vec![Col::int(3), Col::int(26)],
+            ],
+            select("SELECT id, age FROM test")
+        );
+
+        let results = engine.execute_batch_atomic(&commands(&[
+            "INSERT INTO test(id, age) VALUES(4, 50)",
+            "UPDATE test SET age = 21 WHERE id = 1",
+            "INSERT INTO test(id, age) VALUES(1, 60)",
+            "INSERT INTO test(id, age) VALUES(5, 70)",
+        ]));
+        assert_eq!(3, results.len());
+        assert!(results[2].is_err());
+        assert_eq!(
+            vec![vec![Col::big_int(3)]],
+            select("SELECT count(*) FROM test")
+        );
+        assert_eq!(
+            vec![vec![Col::int(20)]],
+            select("SELECT age FROM test WHERE id = 1")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "'DROP TABLE test' cannot run inside a transaction"
+            )),
+            engine.execute_batch_atomic(&commands(&["DROP TABLE test"]))[0]
+        );
+
+        engine.execute(Command::Begin).unwrap();
+        let results = engine.execute_batch(&commands(&["INSERT INTO test(id, age) VALUES(4, 50)"]));
+        assert!(results[0].is_ok());
+        engine.execute(Command::Rollback).unwrap();
+        assert_eq!(
+            vec![vec![Col::big_int(3)]],
+            select("SELECT count(*) FROM test")
+        );
+    }
+
+    #[test]
+    fn prepared_statement() {
+        let engine = Engine::in_memory();
+        engine
+            .execute(parser::parse("CREATE TABLE test(id int, name varchar(16))").unwrap())
+            .unwrap();
+        let insert = engine
+            .prepare("INSERT INTO test(id, name) VALUES(?, ?)")
+            .unwrap();
+        assert_eq!(2, insert.parameters());
+        for (id, name) in [(1, "one"), (2, "it's ?")] {
+            assert_eq!(
+                ExecResult::affected("inserted", 1),
+                insert
+                    .execute(&[Col::int(id), Col::varchar(name, 16)])
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            ExecResult::affected("inserted", 1),
+            insert
+                .execute(&[Col::varchar("3", 16), Col::varchar("three", 16)])
+                .unwrap()
+        );
+        assert!(matches!(
+            insert.execute(&[Col::varchar("four", 16), Col::varchar("four", 16)]),
+            Err(DbError::InvalidValue(..))
+        ));
+        assert_eq!(
+            Err(DbError::invalid_input("expected 2 parameters, got 1")),
+            insert.execute(&[Col::int(4)])
+        );
+
+        let select = engine
+            .prepare("SELECT name FROM test WHERE id = ?")
+            .unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("it's ?", 16)]],
+            select.execute(&[Col::int(2)]).unwrap().rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("three", 16)]],
+            select.execute(&[Col::big_int(3)]).unwrap().rows()
+        );
+        let update = engine
+            .prepare("UPDATE test SET id = id + ? WHERE name = ?")
+            .unwrap();
+        update
+            .execute(&[Col::int(10), Col::varchar("one", 16)])
+            .unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("one", 16)]],
+            select.execute(&[Col::int(11)]).unwrap().rows()
+        );
+        assert!(engine.prepare("SELECT ? FROM test").is_err());
+    }
+
+    #[test]
+    fn session_prepared_statements() {
+        let engine = Arc::new(Engine::in_memory());
+        let mut first = engine.session();
+        let mut second = engine.session();
+        let parse = |query: &str| parser::parse(query).unwrap();
+        first.execute(parse("CREATE DATABASE shop")).unwrap();
+        first.execute(parse("USE shop")).unwrap();
+        first
+            .execute(parse("CREATE TABLE items(id int, name varchar(8))"))
+            .unwrap();
+        assert_eq!(
+            2,
+            first
+                .prepare("insert", "INSERT INTO items(id, name) VALUES(?, ?)")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "prepared statement 'insert' already exists"
+            )),
+            first.prepare("insert", "SELECT id FROM items")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "prepared statement 'insert' doesn't exist"
+            )),
+            second.execute_prepared("insert", &[Col::int(1), Col::varchar("ann", 8)])
+        );
+
+        first.execute(parse("BEGIN")).unwrap();
+        first
+            .execute_prepared("insert", &[Col::varchar("1", 8), Col::varchar("ann", 8)])
+            .unwrap();
+        assert!(
+            second
+                .execute(parse("SELECT id FROM shop.items"))
+                .unwrap()
+                .rows()
+                .is_empty()
+        );
+        first.execute(parse("COMMIT")).unwrap();
+        first
+            .prepare("select", "SELECT name FROM items WHERE id = ?")
+            .unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)]],
+            first
+                .query_prepared("select", &[Col::big_int(1)])
+                .unwrap()
+                .into_result()
+                .unwrap()
+                .rows()
+        );
+        first.deallocate("select").unwrap();
+        assert!(first.deallocate("select").is_err());
+        assert!(first.execute_prepared("select", &[Col::int(1)]).is_err());
+    }
+
+    #[test]
+    fn count_rows() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap()).unwrap();
+        let count = |query: &str| execute(query).into_rows();
+        execute("CREATE TABLE test(id int, age int)");
+        assert_eq!(
+            vec![vec![Col::big_int(0)]],
+            count("SELECT count(*) FROM test")
+        );
+        execute("INSERT INTO test(id, age) VALUES(1, 20) (2, 30) (3, 25) (4, 40) (5, 31)");
+        let plan = |query: &str| -> Vec<Col> {
+            execute(&format!("EXPLAIN {}", query))
+                .rows()
+                .iter()
+                .map(|row| row[0].clone())
+                .collect()
+        };
+        let line = |line: &str| Col::Varchar(line.to_string(), line.len() as u16);
+        assert_eq!(
+            vec![
+                line("Project [count(*)]"),
+                line("  RowCount test [count(*)]")
+            ],
+            plan("SELECT count(*) FROM test")
+        );
+        assert!(
+            plan("SELECT count(*) FROM test WHERE age > 25")[1]
+                .to_string()
+                .contains("HashAggregate")
+        );
+        assert!(
+            plan("SELECT count(age) FROM test")[1]
+                .to_string()
+                .contains("HashAggregate")
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(5)]],
+            count("SELECT count(*) FROM test")
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(2)]],
+            count("SELECT count(*) FROM test WHERE age > 30")
+        );
+
+        execute("BEGIN");
+        execute("INSERT INTO test(id, age) VALUES(6, 50) (7, 60)");
+        execute("INSERT OR REPLACE INTO test(id, age) VALUES(1, 21)");
+        assert_eq!(
+            vec![vec![Col::big_int(7)]],
+            count("SELECT count(*) FROM test")
+        );
+        execute("DELETE FROM test");
+        assert_eq!(
+            vec![vec![Col::big_int(0)]],
+            count("SELECT count(*) FROM test")
+        );
+        execute("ROLLBACK");
+        assert_eq!(
+            vec![vec![Col::big_int(5)]],
+            count("SELECT count(*) FROM test")
+        );
+        execute("DELETE FROM test");
+        assert_eq!(
+            vec![vec![Col::big_int(0)]],
+            count("SELECT count(*) FROM test")
+        );
+    }
+
+    #[test]
+    fn select_where() {
+        let engine = Engine::in_memory();
+        let queries = [
+            "CREATE TABLE test(id int, name varchar(8))",
+            "INSERT INTO test(id, name) VALUES(1, 'ann')",
+            "INSERT INTO test(id, name) VALUES(2, 'bob')",
+            "INSERT INTO test(id, name) VALUES(3, 'ann')",
+        ];
+        for query in queries {
+            engine.execute(parser::parse(query).unwrap()).unwrap();
+        }
+        let select = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            select("SELECT name FROM test WHERE id = 2").unwrap().rows()
+        );
+        assert!(
+            select("SELECT name FROM test WHERE id = 2 AND name = 'ann'")
+                .unwrap()
+                .rows()
+                .is_empty()
+        );
+        assert_eq!(
+            vec![vec![Col::int(3)]],
+            select("SELECT id FROM test WHERE name = ann AND test.id > 1")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            Err(DbError::field_not_found("age", "test")),
+            select("SELECT id FROM test WHERE age = 1")
+        );
+        assert!(select("SELECT id FROM test WHERE id = abc").is_err());
+    }
+
+    #[test]
+    fn composite_index() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE orders(id int, customer int, total int)").unwrap();
+        let values: Vec<String> = (1..=5000)
+            .map(|i| format!("({}, {}, {})", i, i % 100, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO orders(id, customer, total) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("CREATE INDEX orders_customer_total ON orders(customer, total)").unwrap();
+        execute("ANALYZE orders").unwrap();
+        let explain = |query: &str| -> Vec<String> {
+            let plan = execute(&format!("EXPLAIN {}", query)).unwrap();
+            plan.into_rows()
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect()
+        };
+        let ids = |query: &str| -> Vec<Col> {
+            let rows = execute(query).unwrap().into_rows();
+            rows.into_iter().map(|row| row[0].clone()).collect()
+        };
+        let range = "SELECT id FROM orders WHERE customer = 7 AND total > 4500";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan orders using orders_customer_total customer = 7 AND total > 4500 index only",
+            ],
+            explain(range)
+        );
+        let expected: Vec<Col> = (4507..5000).step_by(100).map(Col::int).collect();
+        assert_eq!(expected, ids(range));
+        let point = "SELECT id FROM orders WHERE total = 507 AND customer = 7";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan orders using orders_customer_total customer = 7 AND total = 507 index only",
+            ],
+            explain(point)
+        );
+        assert_eq!(vec![Col::int(507)], ids(point));
+        assert_eq!(50, ids("SELECT id FROM orders WHERE customer = 7").len());
+        assert_eq!(
+            vec!["Project [id]", "  Filter total = 507", "    SeqScan orders"],
+            explain("SELECT id FROM orders WHERE total = 507")
+        );
+
+        execute("BEGIN").unwrap();
+        execute("INSERT INTO orders(id, customer, total) VALUES(6000, 7, 4600) (6001, 8, 4600)")
+            .unwrap();
+        let mut expected = expected;
+        expected.insert(1, Col::int(6000));
+        assert_eq!(expected, ids(range));
+        execute("ROLLBACK").unwrap();
+        assert!(matches!(
+            execute("CREATE INDEX orders_twice ON orders(customer, customer)"),
+            Err(DbError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn index_order() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(8))").unwrap();
+        let values: Vec<String> = (0..2000)
+            .map(|i| format!("({}, {}, 'n{}')", i, (i * 37) % 50, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, age, name) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("CREATE INDEX test_age ON test(age)").unwrap();
+        execute("ANALYZE test").unwrap();
+        let explain = |query: &str| -> Vec<String> {
+            let plan = execute(&format!("EXPLAIN {}", query)).unwrap();
+            plan.into_rows()
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect()
+        };
+        let rows = |query: &str| execute(query).unwrap().into_rows();
+
+        let latest = "SELECT id FROM test ORDER BY id DESC LIMIT 3";
+        assert_eq!(
+            vec![
+                "Limit 3",
+                "  Project [id]",
+                "    SeqScan test reverse limit 3"
+            ],
+            explain(latest)
+        );
+        let ids: Vec<Vec<Col>> = [1999, 1998, 1997].map(|i| vec![Col::int(i)]).into();
+        assert_eq!(ids, rows(latest));
+
+        let youngest = "SELECT name, age FROM test ORDER BY age, id LIMIT 3";
+        assert_eq!(
+            vec![
+                "Limit 3",
+                "  Project [name, age]",
+                "    IndexScan test using test_age limit 3",
+            ],
+            explain(youngest)
+        );
+        let expected: Vec<Vec<Col>> = ["n0", "n50", "n100"]
+            .map(|name| vec![Col::varchar(name, 8), Col::int(0)])
+            .into();
+        assert_eq!(expected, rows(youngest));
+        let oldest = "SELECT age, id FROM test ORDER BY age DESC LIMIT 2";
+        assert_eq!(
+            vec![
+                "Limit 2",
+                "  Project [age, id]",
+                "    IndexScan test using test_age reverse index only limit 2",
+            ],
+            explain(oldest)
+        );
+        assert_eq!(
+            vec![
+                vec![Col::int(49), Col::int(1977)],
+                vec![Col::int(49), Col::int(1927)],
+            ],
+            rows(oldest)
+        );
+        let point = "SELECT id FROM test WHERE age = 30 ORDER BY age, id DESC";
+        assert_eq!(
+            vec!["Project [id]", "  Sort [age, id DESC]"],
+            explain(point)[..2]
+        );
+        let point = "SELECT id FROM test WHERE age = 30 ORDER BY id DESC";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan test using test_age age = 30 reverse index only"
+            ],
+            explain(point)
+        );
+        let ids: Vec<Col> = rows(point).into_iter().map(|row| row[0].clone()).collect();
+        let mut expected: Vec<Col> = (0..2000)
+            .filter(|i| (i * 37) % 50 == 30)
+            .map(Col::int)
+            .collect();
+        expected.reverse();
+        assert_eq!(expected, ids);
+        assert_eq!(
+            vec!["Project [name]", "  Sort [age]", "    SeqScan test"],
+            explain("SELECT name FROM test ORDER BY age")
+        );
+
+        execute("BEGIN").unwrap();
+        execute("INSERT INTO test(id, age, name) VALUES(5000, 49, 'new')").unwrap();
+        execute("UPDATE test SET age = 0 WHERE id = 1977").unwrap();
+        assert_eq!(
+            vec![vec![Col::int(5000)], vec![Col::int(1999)]],
+            rows("SELECT id FROM test ORDER BY id DESC LIMIT 2")
+        );
+        assert_eq!(
+            vec![
+                vec![Col::int(49), Col::int(5000)],
+                vec![Col::int(49), Col::int(1927)],
+            ],
+            rows(oldest)
+        );
+        execute("ROLLBACK").unwrap();
+    }
+
+    #[test]
+    fn covering_index() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE orders(id int, customer int, total int, note varchar(8))").unwrap();
+        let values: Vec<String> = (1..=5000)
+            .map(|i| format!("({}, {}, {}, 'n{}')", i, i % 100, i, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO orders(id, customer, total, note) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("CREATE INDEX orders_customer_total ON orders(customer, total)").unwrap();
+        execute("ANALYZE orders").unwrap();
+        let explain = |query: &str| -> Vec<String> {
+            let plan = execute(&format!("EXPLAIN {}", query)).unwrap();
+            plan.into_rows()
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect()
+        };
+        let rows = |query: &str| execute(query).unwrap().into_rows();
+
+        let covered = "SELECT total, id FROM orders WHERE customer = 7 AND total > 4500";
+        assert_eq!(
+            vec![
+                "Project [total, id]",
+                "  IndexScan orders using orders_customer_total customer = 7 AND total > 4500 index only",
+            ],
+            explain(covered)
+        );
+        let expected: Vec<Vec<Col>> = (4507..5000)
+            .step_by(100)
+            .map(|i| vec![Col::int(i), Col::int(i)])
+            .collect();
+        assert_eq!(expected, rows(covered));
+        let count = "SELECT count(*) FROM orders WHERE customer = 7 AND total > 4500";
+        assert_eq!(
+            vec![
+                "Project [count(*)]",
+                "  HashAggregate [] [count(*)]",
+                "    IndexScan orders using orders_customer_total customer = 7 AND total > 4500 index only",
+            ],
+            explain(count)
+        );
+        assert_eq!(vec![vec![Col::BigInt(5)]], rows(count));
+        let ordered =
+            "SELECT id FROM orders WHERE customer = 7 AND total > 4500 ORDER BY total DESC";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan orders using orders_customer_total customer = 7 AND total > 4500 reverse index only",
+            ],
+            explain(ordered)
+        );
+        let ids: Vec<Vec<Col>> = expected
+            .iter()
+            .rev()
+            .map(|row| vec![row[1].clone()])
+            .collect();
+        assert_eq!(ids, rows(ordered));
+
+        let uncovered = "SELECT note FROM orders WHERE customer = 7 AND total > 4500";
+        assert_eq!(
+            vec![
+                "Project [note]",
+                "  IndexScan orders using orders_customer_total customer = 7 AND total > 4500",
+            ],
+            explain(uncovered)
+        );
+        assert_eq!(vec![Col::varchar("n4507", 8)], rows(uncovered)[0]);
+
+        execute("BEGIN").unwrap();
+        execute("INSERT INTO orders(id, customer, total, note) VALUES(6000, 7, 4600, 'x')")
+            .unwrap();
+        let mut expected = expected;
+        expected.insert(1, vec![Col::int(4600), Col::int(6000)]);
+        assert_eq!(expected, rows(covered));
+        execute("ROLLBACK").unwrap();
+    }
+
+    #[test]
+    fn index_only_scan() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE t(id int, a int, b varchar(16))").unwrap();
+        let values: Vec<String> = (1..=20000)
+            .map(|i| format!("({}, {}, 'row-{}')", i, i % 10, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO t(id, a, b) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("CREATE INDEX ia ON t(a)").unwrap();
+        execute("ANALYZE t").unwrap();
+        let explain = |query: &str| -> Vec<String> {
+            let plan = execute(&format!("EXPLAIN {}", query)).unwrap();
+            plan.into_rows()
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["Project [a]", "  IndexScan t using ia a = 7 index only"],
+            explain("SELECT a FROM t WHERE a = 7")
+        );
+        assert_eq!(
+            vec!["Project [b]", "  Filter a = 7", "    SeqScan t"],
+            explain("SELECT b FROM t WHERE a = 7")
+        );
+        assert_eq!(
+            vec!["Project [id, a]", "  IndexScan t using ia a > 8 index only"],
+            explain("SELECT id, a FROM t WHERE a > 8")
+        );
+        let rows = execute("SELECT a FROM t WHERE a = 7").unwrap().into_rows();
+        assert_eq!(2000, rows.len());
+        assert!(rows.iter().all(|row| row == &vec![Col::int(7)]));
+    }
+
+    #[test]
+    fn expression_index() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        execute(&engine, "CREATE TABLE users(id int, email varchar(20))").unwrap();
+        let values: Vec<String> = (1..=2000)
+            .map(|i| format!("({}, 'User{}@Mail.com')", i, i))
+            .collect();
+        execute(
+            &engine,
+            &format!("INSERT INTO users(id, email) VALUES{}", values.join(" ")),
+        )
+        .unwrap();
+        execute(&engine, "CREATE INDEX users_email ON users(LOWER(email))").unwrap();
+        execute(&engine, "ANALYZE users").unwrap();
+        let explain = |engine: &Engine, query: &str| -> Vec<String> {
+            let plan = execute(engine, &format!("EXPLAIN {}", query)).unwrap();
+            plan.into_rows()
+                .into_iter()
+                .map(|row| row[0].to_string())
+                .collect()
+        };
+        let ids = |engine: &Engine, query: &str| -> Vec<Col> {
+            let rows = execute(engine, query).unwrap().into_rows();
+            rows.into_iter().map(|row| row[0].clone()).collect()
+        };
+        let lookup = "SELECT id FROM users WHERE lower(email) = 'user7@mail.com'";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan users using users_email lower(email) = 'user7@mail.com' index only",
+            ],
+            explain(&engine, lookup)
+        );
+        assert_eq!(vec![Col::int(7)], ids(&engine, lookup));
+        assert!(
+            ids(
+                &engine,
+                "SELECT id FROM users WHERE lower(email) = 'User7@Mail.com'"
+            )
+            .is_empty()
+        );
+        let upper = "SELECT id FROM users WHERE UPPER(email) = 'USER7@MAIL.COM'";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  Filter upper(email) = 'USER7@MAIL.COM'",
+                "    SeqScan users",
+            ],
+            explain(&engine, upper)
+        );
+        assert_eq!(vec![Col::int(7)], ids(&engine, upper));
+        assert!(execute(&engine, "SELECT id FROM users WHERE lower(id) = 'x'").is_err());
+        assert!(execute(&engine, "CREATE INDEX users_id ON users(upper(id))").is_err());
+
+        execute(&engine, "BEGIN").unwrap();
+        execute(
+            &engine,
+            "UPDATE users SET email = 'New@Mail.com' WHERE id = 7",
+        )
+        .unwrap();
+        assert!(ids(&engine, lookup).is_empty());
+        assert_eq!(
+            vec![Col::int(7)],
+            ids(
+                &engine,
+                "SELECT id FROM users WHERE lower(email) = 'new@mail.com'"
+            )
+        );
+        execute(&engine, "COMMIT").unwrap();
+        assert_eq!(
+            Col::varchar("ok", 2),
+            execute(&engine, "CHECK TABLE users").unwrap().rows()[0][2]
+        );
+        drop(engine);
+
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let lookup = "SELECT id FROM users WHERE lower(email) = 'new@mail.com'";
+        assert_eq!(
+            vec![
+                "Project [id]",
+                "  IndexScan users using users_email lower(email) = 'new@mail.com' index only",
+            ],
+            explain(&engine, lookup)
+        );
+        assert_eq!(vec![Col::int(7)], ids(&engine, lookup));
+        let path = temp_dir.path().join("backup.sql");
+        execute(&engine, &format!("DUMP TO '{}'", path.display())).unwrap();
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .contains("CREATE INDEX users_email ON users(lower(email))")
+        );
+    }
+
+    #[test]
+    fn explain_index_selection() {
+        let engine = Engine::in_memory();
+        let queries = [
+            "CREATE TABLE test(id int, age int, name varchar(8))",
+            "INSERT INTO test(id, age, name) VALUES(1, 30, 'ann')",
+            "INSERT INTO test(id, age, name) VALUES(2, 20, 'bob')",
+            "INSERT INTO test(id, age, name) VALUES(3, 30, 'cid')",
+            "CREATE INDEX test_age ON test(age)",
+        ];
+        for query in queries {
+            engine.execute(parser::parse(query).unwrap()).unwrap();
+        }
+        let values: Vec<String> = (100..1100)
+            .map(|i| format!("({}, {}, 'n{}')", i, i * 10, i))
+            .collect();
+        let query = format!("INSERT INTO test(id, age, name) VALUES{}", values.join(" "));
+        engine.execute(parser::parse(&query).unwrap()).unwrap();
+        engine
+            .execute(parser::parse("ANALYZE test").unwrap())
+            .unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap()).unwrap();
+        let explain = |query: &str| -> Vec<String> {
+            let plan = execute(query).into_rows().into_iter();
+            plan.map(|row| match &row[0] {
+                Col::Varchar(line, _) => line.clone(),
+                other => panic!("unexpected plan line: {:?}", other),
+            })
+            .collect()
+        };
+        assert_eq!(
+            vec!["Project [name]", "  IndexScan test using test_age age = 30"],
+            explain("EXPLAIN SELECT name FROM test WHERE age = 30")
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)], vec![Col::varchar("cid", 8)]],
+            execute("SELECT name FROM test WHERE age = 30").rows()
+        );
+        assert_eq!(
+            vec![
+                "Project [name]",
+                "  Filter name <> 'cid'",
+                "    IndexScan test using test_age age > 20 AND age <= 30",
+            ],
+            explain("EXPLAIN SELECT name FROM test WHERE age > 20 AND name != cid AND age <= 30")
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)]],
+            execute("SELECT name FROM test WHERE age > 20 AND name != cid AND age <= 30").rows()
+        );
+        assert_eq!(
+            vec![
+                "Project [name]",
+                "  Filter age = 30",
+                "    KeyLookup test id = 2",
+            ],
+            explain("EXPLAIN SELECT name FROM test WHERE age = 30 AND id = 2")
+        );
+        assert_eq!(
+            vec!["Project [age]", "  Filter name = 'bob'", "    SeqScan test"],
+            explain("EXPLAIN SELECT age FROM test WHERE name = bob")
+        );
+        assert!(
+            engine
+                .execute(parser::parse("EXPLAIN DELETE FROM test").unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn explain_rows() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap()).unwrap();
+        execute("CREATE TABLE test(id int, age int, name varchar(8))");
+        let values: Vec<String> = (0..30)
+            .map(|i| format!("({}, {}, 'n{}')", i, i % 10, i % 3))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, age, name) VALUES{}",
+            values.join(" ")
+        ));
+        execute("CREATE INDEX test_age ON test(age)");
+        let line = |line: &str| Col::Varchar(line.to_string(), line.len() as u16);
+        let explain = execute("EXPLAIN SELECT name FROM test WHERE age = 3");
+        assert_eq!(vec!["plan", "rows", "cost"], explain.columns());
+        assert_eq!(
+            vec![
+                vec![line("Project [name]"), Col::big_int(3), Col::big_int(1)],
+                vec![line("  Filter age = 3"), Col::big_int(3), Col::big_int(1)],
+                vec![line("    SeqScan test"), Col::big_int(30), Col::big_int(1)],
+            ],
+            explain.rows()
+        );
+        let analyze = execute("EXPLAIN ANALYZE SELECT id FROM test WHERE name = n1");
+        assert_eq!(vec!["plan", "rows", "cost", "actual"], analyze.columns());
+        let row = |plan: &str, rows: i64, actual: i64| {
+            vec![
+                line(plan),
+                Col::big_int(rows),
+                Col::big_int(1),
+                Col::big_int(actual),
+            ]
+        };
+        assert_eq!(
+            vec![
+                row("Project [id]", 3, 10),
+                row("  Filter name = 'n1'", 3, 10),
+                row("    SeqScan test", 30, 30),
+            ],
+            analyze.rows()
+        );
+    }
+
+    #[test]
+    fn analyze() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(8))").unwrap();
+        let values: Vec<String> = (0..30)
+            .map(|i| format!("({}, {}, 'n{}')", i, i % 10, i % 3))
+            .collect();
+        execute(&format!(
+            "INSERT INTO test(id, age, name) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+        execute("CREATE INDEX test_age ON test(age)").unwrap();
+        let estimate = |query: &str| execute(query).unwrap().rows()[1][1].clone();
+        assert_eq!(
+            Col::big_int(3),
+            estimate("EXPLAIN SELECT id FROM test WHERE name = n1")
+        );
+        assert_eq!(
+            Col::big_int(10),
+            estimate("EXPLAIN SELECT id FROM test WHERE id < 12")
+        );
+        assert_eq!(
+            Col::big_int(10),
+            estimate("EXPLAIN SELECT id FROM test WHERE age >= 5")
+        );
+
+        assert_eq!(
+            ExecResult::affected("analyzed", 1),
+            execute("ANALYZE").unwrap()
+        );
+        let stats = engine.storage.statistics().get("test").unwrap().unwrap();
+        assert_eq!(30, stats.rows);
+        assert_eq!(3, stats.columns["name"].distinct);
+        assert_eq!(Some((0, 9)), stats.columns["age"].range());
+        assert_eq!(
+            Col::big_int(10),
+            estimate("EXPLAIN SELECT id FROM test WHERE name = n1")
+        );
+        assert_eq!(
+            Col::big_int(12),
+            estimate("EXPLAIN SELECT id FROM test WHERE id < 12")
+        );
+        assert_eq!(
+            Col::big_int(15),
+            estimate("EXPLAIN SELECT id FROM test WHERE age >= 5")
+        );
+
+        assert_eq!(
+            Err(DbError::TableNotFound("missing".to_string())),
+            execute("ANALYZE missing")
+        );
+        execute("DROP TABLE test").unwrap();
+        assert_eq!(None, engine.storage.statistics().get("test").unwrap());
+    }
+
+    #[test]
+    fn cost_based_plans() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap()).unwrap();
+        execute("CREATE TABLE big(id int, flag int, code int)");
+        execute("CREATE TABLE kinds(id int, name varchar(8))");
+        execute("CREATE INDEX big_flag ON big(flag)");
+        execute("CREATE INDEX big_code ON big(code)");
+        let values: Vec<String> = (0..1000)
+            .map(|i| format!("({}, {}, {})", i, i % 2, i))
+            .collect();
+        execute(&format!(
+            "INSERT INTO big(id, flag, code) VALUES{}",
+            values.join(" ")
+        ));
+        execute("INSERT INTO kinds(id, name) VALUES(1, 'a') (2, 'b') (3, 'c')");
+        execute("ANALYZE");
+        let explain = |query: &str| -> Vec<(String, i64)> {
+            execute(&format!("EXPLAIN {}", query))
+                .into_rows()
+                .into_iter()
+                .map(|row| match (&row[0], &row[2]) {
+                    (Col::Varchar(line, _), Col::BigInt(cost)) => (line.clone(), *cost),
+                    other => panic!("unexpected plan row: {:?}", other),
+                })
+                .collect()
+        };
+        let pages = engine.storage.stats("big").unwrap().leaf_pages as i64;
+        assert_eq!(
+            vec![
+                ("Project [id]".to_string(), pages),
+                ("  Filter flag = 1".to_string(), pages),
+                ("    SeqScan big".to_string(), pages),
+            ],
+            explain("SELECT id FROM big WHERE flag = 1")
+        );
+        let plan = explain("SELECT id FROM big WHERE flag = 1 AND code = 7");
+        assert_eq!("    IndexScan big using big_code code = 7", plan[2].0);
+        assert!(plan[0].1 < pages);
+        assert_eq!(
+            vec![vec![Col::int(7)]],
+            execute("SELECT id FROM big WHERE flag = 1 AND code = 7").rows()
+        );
+
+        let join = LogicalPlan::scan("big").join(LogicalPlan::scan("kinds"), "big.id", "kinds.id");
+        let plan = engine.plan(join.clone()).unwrap();
+        assert_eq!(
+            "Project [id, flag, code, id, name]\n\
+             \x20 IndexJoin kinds.id = big.id on big using primary key\n\
+             \x20   SeqScan kinds\n",
+            plan.to_string()
+        );
+        let planner = Planner::new(&engine.storage);
+        assert_eq!(4, planner.cost(&plan).unwrap());
+        assert_eq!(
+            vec![
+                vec![
+                    Col::int(1),
+                    Col::int(1),
+                    Col::int(1),
+                    Col::int(1),
+                    Col::varchar("a", 8)
+                ],
+                vec![
+                    Col::int(2),
+                    Col::int(0),
+                    Col::int(2),
+                    Col::int(2),
+                    Col::varchar("b", 8)
+                ],
+                vec![
+                    Col::int(3),
+                    Col::int(1),
+                    Col::int(3),
+                    Col::int(3),
+                    Col::varchar("c", 8)
+                ],
+            ],
+            engine.execute_plan(join).unwrap().rows()
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, name varchar(8))").unwrap();
+        execute("CREATE TABLE orders(id bigint)").unwrap();
+        execute("CREATE DATABASE shop").unwrap();
+        execute("CREATE TABLE shop.items(id int)").unwrap();
+        let tables: Vec<(String, Vec<String>)> = engine
+            .tables()
+            .unwrap()
+            .into_iter()
+            .map(|(table, row_type)| {
+                let columns = row_type
+                    .columns
+                    .iter()
+                    .map(|col| col.get_name().to_string());
+                (table, columns.collect())
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("orders".to_string(), vec!["id".to_string()]),
+                (
+                    "users".to_string(),
+                    vec!["id".to_string(), "name".to_string()]
+                ),
+            ],
+            tables
+        );
+    }
+
+    #[test]
+    fn tracing_events() {
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Output {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let engine = Engine::in_memory();
+            let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+            execute("CREATE TABLE users(id int)").unwrap();
+            execute("SELECT id FROM users").unwrap();
+            execute("INSERT INTO users(id) VALUES(1), (1)").unwrap_err();
+        });
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("statement{session=0 kind=\"select\"}"));
+        assert!(output.contains("planned elapsed="));
+        assert!(output.contains("read elapsed="));
+        assert!(output.contains("executed elapsed="));
+        assert!(output.contains("WARN statement{session=0 kind=\"insert\"}"));
+        assert!(output.contains("statement failed"));
+        assert!(output.contains("code=\"23505\""));
+    }
+
+    #[test]
+    fn flush() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int)").unwrap();
+        execute("CREATE INDEX users_id ON users(id)").unwrap();
+        execute("INSERT INTO users(id) VALUES(1), (2)").unwrap();
+        engine.flush().unwrap();
+        drop(engine);
+        let wal = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with("-wal"))
+            .count();
+        assert_eq!(0, wal);
+        let engine = Engine::read_only(temp_dir.path()).unwrap();
+        let users = engine
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert_eq!(2, users.rows().len());
+        Engine::in_memory().flush().unwrap();
+    }
+
+    #[test]
+    fn metrics() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(Metrics::default(), engine.metrics());
+        execute("CREATE TABLE test(id int, name varchar(8))").unwrap();
+        execute("INSERT INTO test(id, name) VALUES(1, 'a') (2, 'b') (3, 'c')").unwrap();
+        execute("UPDATE test SET name = 'd' WHERE id = 2").unwrap();
+        execute("SELECT id FROM test").unwrap();
+        let cursor = engine
+            .query(parser::parse("SELECT id FROM test WHERE id = 3").unwrap())
+            .unwrap();
+        assert_eq!(1, cursor.count());
+        assert!(execute("SELECT id FROM missing").is_err());
+        assert!(execute("INSERT INTO test(id, name) VALUES(1, 'a')").is_err());
+
+        let metrics = engine.metrics();
+        let statements: Vec<(&str, u64)> = metrics
+            .statements
+            .iter()
+            .map(|(kind, count)| (kind.as_str(), *count))
+            .collect();
+        assert_eq!(
+            vec![("create", 1), ("insert", 2), ("select", 3), ("update", 1)],
+            statements
+        );
+        assert_eq!(4, metrics.rows_written);
+        assert!(metrics.rows_read >= 5, "{:?}", metrics);
+        assert_eq!(2, metrics.errors);
+        assert!(metrics.page_writes > 0, "{:?}", metrics);
+        assert!(metrics.page_reads + metrics.cache_hits > 0, "{:?}", metrics);
+        assert!((0.0..=1.0).contains(&metrics.cache_hit_ratio()));
+    }
+
+    #[test]
+    fn limit() {
+        let engine = Arc::new(Engine::in_memory());
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap()).unwrap();
+        execute("CREATE TABLE test(id int, age int)");
+        let values: Vec<String> = (0..1000).map(|i| format!("({}, {})", i, i % 10)).collect();
+        execute(&format!(
+            "INSERT INTO test(id, age) VALUES{}",
+            values.join(" ")
+        ));
+        execute("CREATE INDEX test_age ON test(age)");
+        let ids = |range: std::ops::Range<i32>| -> Vec<Vec<Col>> {
+            range.map(|id| vec![Col::int(id)]).collect()
+        };
+        let explain = execute("EXPLAIN ANALYZE SELECT id FROM test LIMIT 5");
+        let plan: Vec<String> = explain
+            .rows()
+            .iter()
+            .map(|row| row[0].to_string())
+            .collect();
+        assert_eq!(
+            vec!["Limit 5", "  Project [id]", "    SeqScan test limit 5"],
+            plan
+        );
+        assert_eq!(Col::big_int(5), explain.rows()[2][3]);
+        assert_eq!(ids(0..5), execute("SELECT id FROM test LIMIT 5").rows());
+        assert_eq!(
+            vec![vec![Col::int(997)], vec![Col::int(998)]],
+            execute("SELECT id FROM test WHERE id > 996 LIMIT 2").rows()
+        );
+        assert_eq!(
+            vec![vec![Col::int(9)], vec![Col::int(19)]],
+            execute("SELECT id FROM test WHERE age = 9 LIMIT 2").rows()
+        );
+        assert!(execute("SELECT id FROM test LIMIT 0").rows().is_empty());
+
+        let mut session = engine.session();
+        let mut execute = |query: &str| session.execute(parser::parse(query).unwrap()).unwrap();
+        execute("BEGIN");
+        execute("INSERT INTO test(id, age) VALUES(-2, 0) (-1, 0)");
+        execute("UPDATE test SET age = 100 WHERE id = 3");
+        assert_eq!(ids(-2..3), execute("SELECT id FROM test LIMIT 5").rows());
+        assert_eq!(
+            vec![vec![Col::int(13)], vec![Col::int(23)]],
+            execute("SELECT id FROM test WHERE age = 3 LIMIT 2").rows()
+        );
+        execute("ROLLBACK");
+    }
+
+    #[test]
+    fn copy_from() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int, name varchar(16))").unwrap();
+        let path = temp_dir.path().join("test.csv");
+        let mut csv = String::from("name,id,age\n");
+        for i in 0..25_000 {
+            csv.push_str(&format!("\"n, {}\",{},{}\n", i, i, i % 50));
+        }
+        fs::write(&path, csv).unwrap();
+        let copy =
+            |options: &str| execute(&format!("COPY test FROM '{}' {}", path.display(), options));
+        assert_eq!(
+            ExecResult::affected("copied", 25_000),
+            copy("(HEADER true)").unwrap()
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(25_000), Col::int(49)]],
+            execute("SELECT count(*), max(age) FROM test")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("n, 7", 16)]],
+            execute("SELECT name FROM test WHERE id = 7")
+                .unwrap()
+                .rows()
+        );
+
+        fs::write(&path, "30000;1;ann\n30001;x;bob\n").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "line 2: invalid value 'x' for field 'age' of relation 'test'"
+            )),
+            copy("(DELIMITER ';')")
+        );
+        fs::write(&path, "name\nann\n").unwrap();
+        assert_eq!(Err(DbError::PrimaryKeyNotSet), copy("(HEADER true)"));
+        fs::write(&path, "0,1,dup\n").unwrap();
+        assert_eq!(
+            Err(DbError::DuplicateKey("test".to_string(), "0".to_string())),
+            copy("")
+        );
+    }
+
+    #[test]
+    fn copy_to() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, name varchar(16))").unwrap();
+        execute("CREATE TABLE backup(id int, name varchar(16))").unwrap();
+        execute("INSERT INTO test(id, name) VALUES(1, 'ann') (2, 'b;\"o\"b') (3, 'eve')").unwrap();
+        let path = temp_dir.path().join("out.csv");
+        let copied = execute(&format!(
+            "COPY (SELECT name, id FROM test WHERE id < 3 ORDER BY name) TO '{}' (HEADER true, DELIMITER ';')",
+            path.display()
+        ))
+        .unwrap();
+        assert_eq!(ExecResult::affected("copied", 2), copied);
+        assert_eq!(
+            "name;id\nann;1\n\"b;\"\"o\"\"b\";2\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        execute(&format!(
+            "COPY backup FROM '{}' (HEADER true, DELIMITER ';')",
+            path.display()
+        ))
+        .unwrap();
+        assert_eq!(
+            execute("SELECT id, name FROM test WHERE id < 3")
+                .unwrap()
+                .rows(),
+            execute("SELECT id, name FROM backup").unwrap().rows()
+        );
+    }
+
+    #[test]
+    fn copy_to_parquet() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, total bigint, name varchar(16))").unwrap();
+        execute("INSERT INTO test(id, total, name) VALUES(1, 10, 'ann') (2, 20, 'bob')").unwrap();
+        let path = temp_dir.path().join("out.parquet");
+        let copied = execute(&format!(
+            "COPY (SELECT name, total FROM test ORDER BY id) TO '{}'",
+            path.display()
+        ))
+        .unwrap();
+        assert_eq!(ExecResult::affected("copied", 2), copied);
+        let file = fs::read(&path).unwrap();
+        assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+        assert!(file.windows(5).any(|window| window == b"total"));
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "COPY options are not supported for parquet output"
+            )),
+            execute(&format!(
+                "COPY (SELECT id FROM test) TO '{}' (HEADER true)",
+                path.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn dump_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = Engine::new(&temp_dir.path().join("source")).unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        execute(
+            &source,
+            "CREATE TABLE users(id int, name varchar(16) NOT NULL, age bigint)",
+        )
+        .unwrap();
+        execute(&source, "CREATE INDEX users_age ON users(age)").unwrap();
+        execute(&source, "CREATE TABLE notes(id int, text varchar(32))").unwrap();
+        for i in 0..2_500 {
+            let query = format!(
+                "INSERT INTO users(id, name, age) VALUES({}, 'user {}', {})",
+                i,
+                i,
+                i % 70
+            );
+            execute(&source, &query).unwrap();
+        }
+        execute(
+            &source,
+            "INSERT INTO notes(id, text) VALUES(1, \"it's; fine\") (2, 'say \"hi\"')",
+        )
+        .unwrap();
+        let path = temp_dir.path().join("backup.sql");
+        let dump = format!("DUMP TO '{}'", path.display());
+        assert_eq!(
+            ExecResult::affected("dumped", 7),
+            execute(&source, &dump).unwrap()
+        );
+
+        let target = Engine::new(&temp_dir.path().join("target")).unwrap();
+        let restore = format!("RESTORE FROM '{}'", path.display());
+        assert_eq!(
+            ExecResult::affected("restored", 7),
+            execute(&target, &restore).unwrap()
+        );
+        for query in [
+            "SELECT id, name, age FROM users",
+            "SELECT id, text FROM notes",
+        ] {
+            assert_eq!(
+                execute(&source, query).unwrap(),
+                execute(&target, query).unwrap()
+            );
+        }
+        assert_eq!(
+            vec![("users_age".to_string(), vec![eval::Term::column(2)])],
+            target.storage.indexes("users").unwrap()
+        );
+        assert_eq!(
+            Err(DbError::NotNull("name".to_string(), "users".to_string())),
+            execute(&target, "INSERT INTO users(id, age) VALUES(5000, 1)")
+        );
+        assert!(matches!(
+            execute(&target, &restore),
+            Err(DbError::InvalidInput(err)) if err.starts_with("line 2: duplicate")
+        ));
+
+        execute(
+            &source,
+            "INSERT INTO notes(id, text) VALUES(3, 'both \" and \\'')",
+        )
+        .unwrap();
+        assert!(matches!(
+            execute(&source, &dump),
+            Err(DbError::InvalidInput(err)) if err.starts_with("cannot dump value")
+        ));
+    }
+
+    #[test]
+    fn sequences() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        let nextval = |engine: &Engine, sequence: &str| {
+            execute(engine, &format!("SELECT nextval('{}')", sequence))
+                .map(|result| result.into_rows())
+        };
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        execute(&engine, "CREATE SEQUENCE ids START 5").unwrap();
+        assert_eq!(
+            vec![vec![Col::big_int(5)]],
+            nextval(&engine, "ids").unwrap()
+        );
+        assert_eq!(
+            vec![vec![Col::big_int(6)]],
+            nextval(&engine, "ids").unwrap()
+        );
+        execute(
+            &engine,
+            "CREATE TABLE users(id int AUTO_INCREMENT, name varchar(8))",
+        )
+        .unwrap();
+        execute(&engine, "INSERT INTO users(name) VALUES('ann') ('bob')").unwrap();
+        execute(&engine, "INSERT INTO users(id, name) VALUES(10, 'eve')").unwrap();
+        execute(&engine, "INSERT INTO users(name) VALUES('joe')").unwrap();
+        let path = temp_dir.path().join("users.csv");
+        fs::write(&path, "name\nkim\n").unwrap();
+        execute(
+            &engine,
+            &format!("COPY users FROM '{}' (HEADER true)", path.display()),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::varchar("ann", 8)],
+                vec![Col::int(2), Col::varchar("bob", 8)],
+                vec![Col::int(10), Col::varchar("eve", 8)],
+                vec![Col::int(11), Col::varchar("joe", 8)],
+                vec![Col::int(12), Col::varchar("kim", 8)],
+            ],
+            execute(&engine, "SELECT id, name FROM users")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "AUTO_INCREMENT column 'name' must be an integer"
+            )),
+            execute(
+                &engine,
+                "CREATE TABLE bad(id int, name varchar(8) AUTO_INCREMENT)"
+            )
+        );
+        drop(engine);
+
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        let Col::BigInt(next) = nextval(&engine, "ids").unwrap()[0][0] else {
+            panic!("expected bigint");
+        };
+        assert!(next > 6);
+        execute(&engine, "INSERT INTO users(name) VALUES('sam')").unwrap();
+        let count = execute(&engine, "SELECT count(*) FROM users WHERE id > 12").unwrap();
+        assert_eq!(vec![vec![Col::big_int(1)]], count.rows());
+
+        let dump = temp_dir.path().join("backup.sql");
+        execute(&engine, &format!("DUMP TO '{}'", dump.display())).unwrap();
+        let script = fs::read_to_string(&dump).unwrap();
+        assert!(script.starts_with(&format!("CREATE SEQUENCE ids START {};\n", next + 1)));
+        assert!(script.contains("CREATE TABLE users(id INT AUTO_INCREMENT, name VARCHAR(8));"));
+
+        execute(&engine, "DROP TABLE users").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "sequence 'users_id_seq' doesn't exist"
+            )),
+            nextval(&engine, "users_id_seq")
+        );
+        execute(&engine, "DROP SEQUENCE ids").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input("sequence 'ids' doesn't exist")),
+            nextval(&engine, "ids")
+        );
+    }
+
+    #[test]
+    fn views() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        execute(
+            &engine,
+            "CREATE TABLE users(id int, name varchar(8), age int)",
+        )
+        .unwrap();
+        execute(
+            &engine,
+            "INSERT INTO users(id, name, age) VALUES(1, 'ann', 17), (2, 'bob', 30), (3, 'eve', 30)",
+        )
+        .unwrap();
+        execute(
+            &engine,
+            "CREATE VIEW adults(user_id, user_name) AS SELECT id, name FROM users WHERE age >= 18",
+        )
+        .unwrap();
+        let result = execute(
+            &engine,
+            "SELECT user_name, adults.user_id FROM adults WHERE user_id > 2",
+        )
+        .unwrap();
+        assert_eq!(vec!["user_name", "adults.user_id"], result.columns());
+        assert_eq!(
+            vec![vec![Col::varchar("eve", 8), Col::int(3)]],
+            result.rows()
+        );
+        let explain = execute(
+            &engine,
+            "EXPLAIN SELECT user_name FROM adults WHERE user_id = 2",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "Project [user_name]",
+                "  Project [user_id, user_name]",
+                "    Project [id, name]",
+                "      Filter age >= 18",
+                "        KeyLookup users id = 2",
+            ],
+            explain
+                .rows()
+                .iter()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        );
+
+        execute(
+            &engine,
+            "CREATE VIEW ages(age, total) AS SELECT age, count(*) FROM users GROUP BY age",
+        )
+        .unwrap();
+        execute(
+            &engine,
+            "CREATE VIEW names AS SELECT user_name FROM adults ORDER BY user_name LIMIT 1",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![vec![Col::big_int(2)]],
+            execute(&engine, "SELECT total FROM ages WHERE age = 30")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            execute(&engine, "SELECT user_name FROM names")
+                .unwrap()
+                .rows()
+        );
+
+        assert_eq!(
+            Err(DbError::invalid_input("'adults' is a view")),
+            execute(&engine, "INSERT INTO adults(user_id) VALUES(4)")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("relation 'users' already exists")),
+            execute(&engine, "CREATE VIEW users AS SELECT id FROM adults")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("view 'adults' already exists")),
+            execute(&engine, "CREATE VIEW adults AS SELECT id FROM users")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "view 'bad' has 1 columns, but its query returns 2"
+            )),
+            execute(&engine, "CREATE VIEW bad(id) AS SELECT id, name FROM users")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "column 'id' specified more than once in view 'bad'"
+            )),
+            execute(
+                &engine,
+                "CREATE VIEW bad(id, id) AS SELECT id, name FROM users"
+            )
+        );
+        assert!(execute(&engine, "CREATE VIEW bad AS SELECT missing FROM users").is_err());
+
+        let dump = temp_dir.path().join("backup.sql");
+        execute(&engine, &format!("DUMP TO '{}'", dump.display())).unwrap();
+        let script = fs::read_to_string(&dump).unwrap();
+        assert!(script.ends_with(
+            "CREATE VIEW adults(user_id, user_name) AS \
+             SELECT id, name FROM users WHERE age >= '18';\n\
+             CREATE VIEW ages(age, total) AS SELECT age, count(*) FROM users GROUP BY age;\n\
+             CREATE VIEW names AS \
+             SELECT user_name FROM adults ORDER BY user_name LIMIT 1;\n"
+        ));
+        drop(engine);
+
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            execute(&engine, "SELECT user_name FROM names")
+                .unwrap()
+                .rows()
+        );
+        execute(&engine, "DROP VIEW adults").unwrap();
+        assert!(execute(&engine, "SELECT user_name FROM names").is_err());
+        assert_eq!(
+            Err(DbError::invalid_input("view 'adults' doesn't exist")),
+            execute(&engine, "DROP VIEW adults")
+        );
+
+        let restored = tempfile::tempdir().unwrap();
+        let engine = Engine::new(restored.path()).unwrap();
+        execute(&engine, &format!("RESTORE FROM '{}'", dump.display())).unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            execute(&engine, "SELECT user_name FROM names")
+                .unwrap()
+                .rows()
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        execute(
+            &engine,
+            "CREATE TABLE events(id int AUTO_INCREMENT, kind varchar(8) NOT NULL DEFAULT 'info', \
+             created bigint DEFAULT NOW(), retries int DEFAULT 3)",
+        )
+        .unwrap();
+        drop(engine);
+
+        let engine = Engine::new(temp_dir.path()).unwrap();
+        execute(&engine, "INSERT INTO events(retries) VALUES(0)").unwrap();
+        let path = temp_dir.path().join("events.csv");
+        fs::write(&path, "kind\nwarn\n").unwrap();
+        execute(
+            &engine,
+            &format!("COPY events FROM '{}' (HEADER true)", path.display()),
+        )
+        .unwrap();
+        let rows = execute(&engine, "SELECT id, kind, retries FROM events")
+            .unwrap()
+            .into_rows();
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::varchar("info", 8), Col::int(0)],
+                vec![Col::int(2), Col::varchar("warn", 8), Col::int(3)],
+            ],
+            rows
+        );
+        let count = execute(
+            &engine,
+            "SELECT count(*) FROM events WHERE created > 1700000000",
+        )
+        .unwrap()
+        .into_rows();
+        assert_eq!(vec![vec![Col::big_int(2)]], count);
+        assert_eq!(
+            Err(DbError::InvalidValue(
+                "retries".to_string(),
+                "bad".to_string(),
+                "many".to_string()
+            )),
+            execute(
+                &engine,
+                "CREATE TABLE bad(id int, retries int DEFAULT 'many')"
+            )
+        );
+    }
+
+    #[test]
+    fn insert_duplicate_key() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, name varchar(4))").unwrap();
+        execute("CREATE INDEX test_name ON test(name)").unwrap();
+        execute("INSERT INTO test(id, name) VALUES(1, 'ann')").unwrap();
+        assert_eq!(
+            Err(DbError::DuplicateKey("test".to_string(), "1".to_string())),
+            execute("INSERT INTO test(id, name) VALUES(1, 'bob')")
+        );
+        assert_eq!(
+            Ok(ExecResult::affected("inserted", 1)),
+            execute("INSERT OR REPLACE INTO test(id, name) VALUES(1, 'bob')")
+        );
+        assert_eq!(
+            vec![vec![Col::int(1)]],
+            execute("SELECT id FROM test WHERE name = bob")
+                .unwrap()
+                .rows()
+        );
+        assert!(
+            execute("SELECT id FROM test WHERE name = ann")
+                .unwrap()
+                .rows()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn transaction() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        let select = |query: &str| execute(query).unwrap().into_rows();
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        execute("CREATE INDEX test_age ON test(age)").unwrap();
+        execute("INSERT INTO test(id, age) VALUES(1, 30)").unwrap();
+        execute("INSERT INTO test(id, age) VALUES(2, 20)").unwrap();
+
+        assert_eq!(Ok(ExecResult::ack("started")), execute("BEGIN"));
+        assert_eq!(
+            Err(DbError::invalid_input("transaction is already in progress")),
+            execute("BEGIN")
+        );
+        assert!(execute("CREATE INDEX test_id ON test(id)").is_err());
+        execute("INSERT INTO test(id, age) VALUES(3, 30)").unwrap();
+        assert_eq!(
+            Err(DbError::DuplicateKey("test".to_string(), "3".to_string())),
+            execute("INSERT INTO test(id, age) VALUES(3, 40)")
+        );
+        execute("UPDATE test SET id = 4 WHERE id = 2").unwrap();
+        execute("UPDATE test SET age = 20 WHERE id = 1").unwrap();
+        assert_eq!(
+            vec![vec![Col::int(1)], vec![Col::int(4)]],
+            select("SELECT id FROM test WHERE age = 20")
+        );
+        assert_eq!(
+            vec![vec![Col::int(3)]],
+            select("SELECT id FROM test WHERE age = 30")
+        );
+        assert!(select("SELECT id FROM test WHERE id = 2").is_empty());
+        assert_eq!(2, engine.storage.select_all("test").unwrap().len());
+        assert_eq!(Ok(ExecResult::affected("committed", 4)), execute("COMMIT"));
+        assert_eq!(
+            vec![vec![Col::int(1)], vec![Col::int(3)], vec![Col::int(4)]],
+            select("SELECT id FROM test")
+        );
+        assert_eq!(
+            vec![vec![Col::int(1)], vec![Col::int(4)]],
+            select("SELECT id FROM test WHERE age = 20")
+        );
+
+        execute("BEGIN").unwrap();
+        assert_eq!(
+            Ok(ExecResult::affected("deleted", 3)),
+            execute("DELETE FROM test")
+        );
+        assert!(select("SELECT id FROM test").is_empty());
+        assert_eq!(
+            Ok(ExecResult::affected("rolled_back", 3)),
+            execute("ROLLBACK")
+        );
+        assert_eq!(3, select("SELECT id FROM test").len());
+        assert_eq!(
+            Err(DbError::invalid_input("no transaction in progress")),
+            execute("COMMIT")
+        );
     }
 
-    fn execute_create(&self, name: &str, columns: Vec<ColType>) -> Result<usize, DbError> {
-        let row_type = RowType { columns };
-        self.storage.create(name, row_type)
+    #[test]
+    fn concurrent_updates() {
+        let engine = Arc::new(Engine::in_memory());
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        execute("INSERT INTO test(id, age) VALUES(1, 0)").unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let mut session = engine.session();
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        let query = "UPDATE test SET age = age + 1 WHERE id = 1";
+                        session.execute(parser::parse(query).unwrap()).unwrap();
+                    }
+                });
+            }
+        });
+        let mut session = engine.session();
+        session.execute(Command::Begin).unwrap();
+        let query = "UPDATE test SET age = age + 1 WHERE id = 1";
+        session.execute(parser::parse(query).unwrap()).unwrap();
+        drop(session);
+        assert_eq!(
+            Ok(ExecResult::affected("updated", 1)),
+            execute("UPDATE test SET age = age - 40 WHERE id = 1")
+        );
+        assert_eq!(
+            vec![vec![Col::int(0)]],
+            execute("SELECT age FROM test WHERE id = 1").unwrap().rows()
+        );
     }
 
-    fn execute_insert(
-        &self,
-        name: &str,
-        fields: Vec<String>,
-        values: Vec<Vec<String>>,
-    ) -> Result<usize, DbError> {
-        let row_type = self.storage.get_row_type(name)?;
-        let rows = build_rows(name, row_type, fields, values)?;
-        let rows: Vec<(Col, Row)> = rows
-            .into_iter()
-            .map(|columns| (columns.first().cloned().unwrap(), Row { columns }))
-            .collect();
-        self.storage.insert(name, rows)
-    }
+    #[test]
+    fn shared_engine() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Engine>();
 
-    fn execute_select(&self, name: &str, fields: Vec<String>) -> Result<Vec<Vec<Col>>, DbError> {
-        if fields.is_empty() {
-            return Ok(vec![]);
-        }
-        let fields_len = fields.len();
-        let row_type = self.storage.get_row_type(name)?;
-        let indexes = get_indexes(name, row_type, fields)?;
-        let raw_rows = self.storage.select_all(name)?;
-        let mut rows = Vec::with_capacity(raw_rows.len());
-        for raw_row in raw_rows {
-            let mut row = Vec::with_capacity(fields_len);
-            for i in indexes.iter() {
-                let col = raw_row.columns.get(*i).cloned().unwrap();
-                row.push(col);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Arc::new(Engine::new(temp_dir.path()).unwrap());
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE shared(id int, age int)").unwrap();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let engine = engine.clone();
+                scope.spawn(move || {
+                    let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+                    execute(&format!("CREATE TABLE t{}(id int)", thread)).unwrap();
+                    for i in 0..25 {
+                        let id = thread * 25 + i;
+                        execute(&format!("INSERT INTO shared(id, age) VALUES({}, 1)", id)).unwrap();
+                        execute(&format!("INSERT INTO t{}(id) VALUES({})", thread, i)).unwrap();
+                        execute("SELECT id FROM shared").unwrap();
+                    }
+                });
             }
-            rows.push(row);
+        });
+        assert_eq!(100, execute("SELECT id FROM shared").unwrap().rows().len());
+        for thread in 0..4 {
+            let query = format!("SELECT id FROM t{}", thread);
+            assert_eq!(25, execute(&query).unwrap().rows().len());
         }
-        Ok(rows)
     }
 
-    fn execute_delete(&self, from: &str) -> Result<i32, DbError> {
-        self.storage.delete_all(from)
+    #[test]
+    fn update() {
+        let engine = Engine::in_memory();
+        let queries = [
+            "CREATE TABLE test(id int, age int, name varchar(4))",
+            "INSERT INTO test(id, age, name) VALUES(1, 30, 'ann')",
+            "INSERT INTO test(id, age, name) VALUES(2, 20, 'bob')",
+            "INSERT INTO test(id, age, name) VALUES(3, 30, 'cid')",
+            "CREATE INDEX test_age ON test(age)",
+        ];
+        for query in queries {
+            engine.execute(parser::parse(query).unwrap()).unwrap();
+        }
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        assert_eq!(
+            Ok(ExecResult::affected("updated", 2)),
+            execute("UPDATE test SET age = age + 1, name = joe WHERE age = 30")
+        );
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::int(31), Col::varchar("joe", 4)],
+                vec![Col::int(3), Col::int(31), Col::varchar("joe", 4)],
+            ],
+            execute("SELECT id, age, name FROM test WHERE age = 31")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            Ok(ExecResult::affected("updated", 3)),
+            execute("UPDATE test SET id = id * 10")
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 4)]],
+            execute("SELECT name FROM test WHERE id = 20")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            Err(DbError::TooLong(
+                "name".to_string(),
+                "test".to_string(),
+                5,
+                4
+            )),
+            execute("UPDATE test SET name = alice WHERE id = 10")
+        );
+        assert!(execute("UPDATE test SET id = 30 WHERE id = 10").is_err());
+        assert_eq!(
+            Err(DbError::field_not_found("email", "test")),
+            execute("UPDATE test SET email = x")
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("joe", 4)], vec![Col::varchar("joe", 4)]],
+            execute("SELECT name FROM test WHERE age > 30")
+                .unwrap()
+                .rows()
+        );
     }
-}
 
-fn get_indexes(table: &str, row_type: RowType, fields: Vec<String>) -> Result<Vec<usize>, DbError> {
-    let mut indexes_by_names: HashMap<&str, usize> = HashMap::new();
-    for (i, col_type) in row_type.columns.iter().enumerate() {
-        indexes_by_names.insert(col_type.get_name(), i);
-    }
-    let mut indexes = Vec::with_capacity(fields.len());
-    for field in fields {
-        let Some(i) = indexes_by_names.get(field.as_str()) else {
-            return Err(DbError::field_not_found(&field, table));
-        };
-        indexes.push(*i);
+    #[test]
+    fn execute_plan() {
+        let engine = Engine::in_memory();
+        engine
+            .execute(Command::Create {
+                name: "users".to_string(),
+                fields: vec![ColType::int("id"), ColType::varchar("name", 8)],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        engine
+            .execute(Command::Create {
+                name: "orders".to_string(),
+                fields: vec![ColType::int("id"), ColType::int("user_id")],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        engine
+            .execute(Command::Insert {
+                table: "users".to_string(),
+                fields: vec!["id".to_string(), "name".to_string()],
+                values: vec![
+                    vec!["1".to_string(), "ann".to_string()],
+                    vec!["2".to_string(), "bob".to_string()],
+                ],
+                replace: false,
+            })
+            .unwrap();
+        engine
+            .execute(Command::Insert {
+                table: "orders".to_string(),
+                fields: vec!["id".to_string(), "user_id".to_string()],
+                values: (0..10)
+                    .map(|i| vec![i.to_string(), (i % 3).to_string()])
+                    .collect(),
+                replace: false,
+            })
+            .unwrap();
+        let plan = LogicalPlan::scan("orders")
+            .join(LogicalPlan::scan("users"), "user_id", "users.id")
+            .filter(plan::Predicate::compare(
+                "orders.id",
+                plan::Operator::Lt,
+                Col::int(8),
+            ))
+            .sort(vec![
+                parser::OrderBy::asc("name"),
+                parser::OrderBy::asc("orders.id"),
+            ])
+            .limit(3)
+            .project(vec!["orders.id".to_string(), "name".to_string()]);
+        let result = engine.execute_plan(plan).unwrap();
+        assert_eq!(
+            vec!["orders.id".to_string(), "name".to_string()],
+            result.columns()
+        );
+        assert_eq!(
+            vec![
+                vec![Col::int(1), Col::varchar("ann", 8)],
+                vec![Col::int(4), Col::varchar("ann", 8)],
+                vec![Col::int(7), Col::varchar("ann", 8)],
+            ],
+            result.rows()
+        );
     }
-    Ok(indexes)
-}
 
-fn check_primary_key(row_type: &RowType, fields: &[String]) -> Result<(), DbError> {
-    let pk = row_type.get_primary_key()?;
-    let name = pk.get_name();
-    for field in fields.iter() {
-        if field == name {
-            return Ok(());
+    #[test]
+    fn index_join() {
+        let engine = Arc::new(Engine::in_memory());
+        let queries = [
+            "CREATE TABLE users(id int, name varchar(8))",
+            "CREATE TABLE orders(id int, user_id bigint)",
+            "CREATE INDEX orders_user ON orders(user_id)",
+            "INSERT INTO users(id, name) VALUES(1, 'ann') (2, 'bob') (3, 'cid')",
+            "INSERT INTO orders(id, user_id) VALUES(10, 1) (11, 2) (12, 1) (13, 9)",
+        ];
+        for query in queries {
+            engine.execute(parser::parse(query).unwrap()).unwrap();
         }
-    }
-    Err(DbError::PrimaryKeyNotSet)
-}
-
-fn build_rows(
-    table: &str,
-    row_type: RowType,
-    fields: Vec<String>,
-    values: Vec<Vec<String>>,
-) -> Result<Vec<Vec<Col>>, DbError> {
-    let fields_len = fields.len();
-    let mut rows = Vec::new();
-    check_primary_key(&row_type, &fields)?;
-    for group in values {
-        if fields_len != group.len() {
-            return Err(DbError::invalid_input("wrong amount of insert values"));
-        }
-        let mut row = HashMap::new();
-        for (i, value) in group.into_iter().enumerate() {
-            let name = fields.get(i).cloned().unwrap();
-            row.insert(name, value);
-        }
-        let row = build_row(table, &row_type, row)?;
-        rows.push(row);
-    }
-    Ok(rows)
-}
+        let values: Vec<String> = (100..1100).map(|i| format!("({}, {})", i, i)).collect();
+        let query = format!("INSERT INTO orders(id, user_id) VALUES{}", values.join(" "));
+        engine.execute(parser::parse(&query).unwrap()).unwrap();
+        let plan = || {
+            LogicalPlan::scan("users")
+                .join(LogicalPlan::scan("orders"), "users.id", "user_id")
+                .filter(plan::Predicate::compare(
+                    "orders.id",
+                    plan::Operator::Ne,
+                    Col::int(11),
+                ))
+                .project(vec!["name".to_string(), "orders.id".to_string()])
+        };
+        assert_eq!(
+            "Project [name, orders.id]\n\
+             \x20 IndexJoin users.id = user_id on orders using orders_user where orders.id <> 11\n\
+             \x20   SeqScan users\n",
+            engine.plan(plan()).unwrap().to_string()
+        );
+        let row = |name: &str, id: i32| vec![Col::varchar(name, 8), Col::int(id)];
+        assert_eq!(
+            vec![row("ann", 10), row("ann", 12)],
+            engine.execute_plan(plan()).unwrap().rows()
+        );
 
-fn build_row(
-    table: &str,
-    row_type: &RowType,
-    mut values: HashMap<String, String>,
-) -> Result<Vec<Col>, DbError> {
-    let mut cols = Vec::new();
-    for col_type in row_type.columns.iter() {
-        let name = col_type.get_name();
-        match col_type {
-            ColType::Int(_) => {
-                let value = values.remove(name).unwrap_or(String::from("0"));
-                let value: i32 = value.parse()?;
-                cols.push(Col::Int(value));
-            }
-            ColType::BigInt(_) => {
-                let value = values.remove(name).unwrap_or(String::from("0"));
-                let value: i64 = value.parse()?;
-                cols.push(Col::BigInt(value));
-            }
-            ColType::Varchar(_, size) => {
-                let value = values.remove(name).unwrap_or_default();
-                cols.push(Col::Varchar(value, *size));
-            }
-        }
-    }
-    if let Some(key) = values.into_keys().next() {
-        return Err(DbError::field_not_found(&key, table));
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("BEGIN").unwrap();
+        execute("INSERT INTO orders(id, user_id) VALUES(14, 3)").unwrap();
+        execute("UPDATE orders SET user_id = 2 WHERE id = 12").unwrap();
+        assert_eq!(
+            vec![row("ann", 10), row("bob", 12), row("cid", 14)],
+            engine.execute_plan(plan()).unwrap().rows()
+        );
+        execute("ROLLBACK").unwrap();
+        assert_eq!(2, engine.execute_plan(plan()).unwrap().rows().len());
     }
-    Ok(cols)
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn show_table_status() {
+        let engine = Engine::in_memory();
+        engine
+            .execute(Command::Create {
+                name: "test".to_string(),
+                fields: vec![ColType::int("id")],
+                constraints: vec![],
+                temporary: false,
+            })
+            .unwrap();
+        engine
+            .execute(Command::Insert {
+                table: "test".to_string(),
+                fields: vec!["id".to_string()],
+                values: (0..10).map(|i| vec![i.to_string()]).collect(),
+                replace: false,
+            })
+            .unwrap();
+        let status = engine
+            .execute(Command::ShowTableStatus {
+                table: "test".to_string(),
+            })
+            .unwrap();
+        assert_eq!(9, status.columns().len());
+        assert_eq!(Col::varchar("test", 4), status.rows()[0][0]);
+        assert_eq!(Col::big_int(10), status.rows()[0][1]);
+        assert_eq!(Col::big_int(0), status.rows()[0][7]);
+    }
 
     #[test]
-    fn create() {
-        let temp_dir = tempfile::tempdir().unwrap();
-        let engine = Engine::new(temp_dir.path()).unwrap();
+    fn in_memory() {
+        let engine = Engine::new(Path::new(MEMORY)).unwrap();
         engine
             .execute(Command::Create {
                 name: "test".to_string(),
-                fields: vec![ColType::int("id"), ColType::bigint("money")],
+                fields: vec![ColType::int("id")],
+                constraints: vec![],
+                temporary: false,
             })
             .unwrap();
         engine
             .execute(Command::Insert {
                 table: "test".to_string(),
                 fields: vec!["id".to_string()],
-                values: vec![vec![1.to_string()], vec![2.to_string()]],
+                values: vec![vec![1.to_string()]],
+                replace: false,
             })
             .unwrap();
         let rows = engine
             .execute(Command::Select {
                 fields: vec!["id".to_string()],
                 table: "test".to_string(),
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
             })
             .unwrap();
+        assert_eq!(vec![vec![Col::int(1)]], rows.rows());
+        assert!(!Path::new(MEMORY).exists());
+    }
+
+    #[test]
+    fn databases() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        let engine = Arc::new(Engine::new(temp_dir.path()).unwrap());
+        execute(&engine, "CREATE TABLE users(id int, name varchar(8))").unwrap();
+        execute(&engine, "INSERT INTO users(id, name) VALUES(1, 'root')").unwrap();
+        execute(&engine, "CREATE DATABASE app").unwrap();
         assert_eq!(
-            rows,
-            ExecResult {
-                field_names: vec!["id".to_string()],
-                fields: vec![vec![Col::int(1)], vec![Col::int(2)]]
-            }
+            Err(DbError::invalid_input("database 'app' already exists")),
+            execute(&engine, "CREATE DATABASE app")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("database 'shop' doesn't exist")),
+            execute(&engine, "USE shop")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("database 'shop' doesn't exist")),
+            execute(&engine, "SELECT id FROM shop.users")
+        );
+
+        let mut session = engine.session();
+        let mut run = |query: &str| session.execute(parser::parse(query).unwrap());
+        run("USE app").unwrap();
+        run("CREATE TABLE users(id int auto_increment, name varchar(8))").unwrap();
+        run("CREATE INDEX users_name ON users(name)").unwrap();
+        run("INSERT INTO users(name) VALUES('ann'), ('bob')").unwrap();
+        run("CREATE VIEW names AS SELECT name FROM users WHERE id > 1").unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            run("SELECT name FROM names").unwrap().rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)]],
+            run("SELECT users.name FROM users WHERE users.name = 'ann'")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("root", 8)]],
+            run("SELECT name FROM main.users").unwrap().rows()
+        );
+        assert!(temp_dir.path().join("app.db").join("users").exists());
+        assert!(
+            temp_dir
+                .path()
+                .join("app.db")
+                .join("users.users_name.idx")
+                .exists()
+        );
+
+        assert_eq!(
+            vec![vec![Col::varchar("root", 8)]],
+            execute(&engine, "SELECT name FROM users").unwrap().rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            execute(&engine, "SELECT name FROM app.names")
+                .unwrap()
+                .rows()
+        );
+        execute(&engine, "USE app").unwrap();
+        let dump = temp_dir.path().join("app.sql");
+        execute(&engine, &format!("DUMP TO '{}'", dump.display())).unwrap();
+        assert_eq!(
+            "CREATE TABLE users(id INT AUTO_INCREMENT, name VARCHAR(8));\n\
+             INSERT INTO users(id, name) VALUES('1', 'ann'), ('2', 'bob');\n\
+             CREATE INDEX users_name ON users(name);\n\
+             CREATE VIEW names AS SELECT name FROM users WHERE id > '1';\n",
+            fs::read_to_string(&dump).unwrap()
+        );
+        execute(&engine, "CREATE DATABASE archive").unwrap();
+        execute(&engine, "USE archive").unwrap();
+        execute(&engine, &format!("RESTORE FROM '{}'", dump.display())).unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)]],
+            execute(&engine, "SELECT name FROM names").unwrap().rows()
+        );
+        execute(&engine, "DROP VIEW names").unwrap();
+        execute(&engine, "DROP TABLE users").unwrap();
+        assert!(!temp_dir.path().join("archive.db").join("users").exists());
+        execute(&engine, "USE main").unwrap();
+        assert_eq!(
+            vec![vec![Col::int(1)]],
+            execute(&engine, "SELECT id FROM users").unwrap().rows()
         );
     }
 
     #[test]
-    fn invalid_inputs() {
+    fn read_only() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let engine = Engine::new(temp_dir.path()).unwrap();
-        engine
-            .execute(Command::Create {
-                name: "test".to_string(),
-                fields: vec![ColType::int("id")],
-            })
-            .unwrap();
-        let Err(err) = engine.execute(Command::Select {
-            table: "test".to_string(),
-            fields: vec!["name".to_string()],
-        }) else {
-            panic!("wrong field not validated");
+        let execute = |engine: &Engine, query: &str| engine.execute(parser::parse(query).unwrap());
+        {
+            let engine = Engine::new(temp_dir.path()).unwrap();
+            for query in [
+                "CREATE TABLE users(id int, age int)",
+                "CREATE INDEX users_age ON users(age)",
+                "INSERT INTO users(id, age) VALUES(1, 20) (2, 30)",
+                "CREATE VIEW adults AS SELECT id FROM users WHERE age > 25",
+            ] {
+                execute(&engine, query).unwrap();
+            }
+        }
+        let files = || {
+            let mut files: Vec<_> = fs::read_dir(temp_dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            files.sort();
+            files
         };
+        let before = files();
+        let engine = Engine::read_only(temp_dir.path()).unwrap();
         assert_eq!(
-            "field 'name' of relation 'test' doesn't exist",
-            err.to_string()
+            vec![vec![Col::int(2)]],
+            execute(&engine, "SELECT id FROM users WHERE age = 30")
+                .unwrap()
+                .into_rows()
         );
-        let Err(err) = engine.execute(Command::Insert {
-            table: "test".to_string(),
-            fields: vec!["id".to_string()],
-            values: vec![vec!["1".to_string(), "name".to_string()]],
-        }) else {
-            panic!("invalid amount of field is not validated");
-        };
         assert_eq!(
-            "invalid input: wrong amount of insert values",
-            err.to_string()
+            vec![vec![Col::int(2)]],
+            execute(&engine, "SELECT id FROM adults")
+                .unwrap()
+                .into_rows()
         );
-        let Err(err) = engine.execute(Command::Insert {
-            table: "test".to_string(),
-            fields: vec!["name".to_string()],
-            values: vec![vec!["name".to_string()]],
-        }) else {
-            panic!("invalid amount of field is not validated");
-        };
-        assert_eq!("PRIMARY_KEY constraint is not set", err.to_string());
-        let Err(err) = engine.execute(Command::Insert {
-            table: "test".to_string(),
-            fields: vec!["id".to_string(), "name".to_string()],
-            values: vec![vec!["1".to_string(), "name".to_string()]],
-        }) else {
-            panic!("invalid amount of field is not validated");
-        };
+        let read_only = Err(DbError::ReadOnly(temp_dir.path().display().to_string()));
+        for query in [
+            "INSERT INTO users(id, age) VALUES(3, 40)",
+            "UPDATE users SET age = 21 WHERE id = 1",
+            "DELETE FROM users",
+            "CREATE TABLE other(id int)",
+            "DROP TABLE users",
+        ] {
+            assert_eq!(read_only, execute(&engine, query));
+        }
         assert_eq!(
-            "field 'name' of relation 'test' doesn't exist",
-            err.to_string()
+            Err(DbError::TableNotFound("missing".to_string())),
+            execute(&engine, "SELECT id FROM missing")
         );
+        assert_eq!(before, files());
+        assert!(Engine::read_only(&temp_dir.path().join("missing")).is_err());
     }
 
     #[test]
-    fn select_no_fields() {
+    fn check_table() {
+        let engine = Engine::in_memory();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        execute("CREATE INDEX test_age ON test(age)").unwrap();
+        execute("INSERT INTO test(id, age) VALUES(1, 20) (2, 30)").unwrap();
+        assert_eq!(
+            Ok(ExecResult::Rows {
+                columns: vec![
+                    "table".to_string(),
+                    "kind".to_string(),
+                    "message".to_string()
+                ],
+                types: vec![
+                    ColType::varchar("table", 4),
+                    ColType::varchar("kind", 6),
+                    ColType::varchar("message", 2),
+                ],
+                rows: vec![vec![
+                    Col::varchar("test", 4),
+                    Col::varchar("status", 6),
+                    Col::varchar("ok", 2),
+                ]],
+            }),
+            execute("CHECK TABLE test")
+        );
+        assert_eq!(
+            Err(DbError::TableNotFound("missing".to_string())),
+            execute("CHECK TABLE missing")
+        );
+    }
+
+    #[test]
+    fn temporary_tables() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = Arc::new(Engine::new(temp_dir.path()).unwrap());
+        let execute =
+            |session: &mut Session, query: &str| session.execute(parser::parse(query).unwrap());
+        let mut first = engine.session();
+        let mut second = engine.session();
+        execute(&mut first, "CREATE TABLE users(id int, name varchar(8))").unwrap();
+        execute(&mut first, "INSERT INTO users(id, name) VALUES(1, 'ann')").unwrap();
+        execute(
+            &mut first,
+            "CREATE TEMP TABLE users(id int auto_increment, name varchar(8))",
+        )
+        .unwrap();
+        execute(&mut first, "INSERT INTO users(name) VALUES('bob'), ('eve')").unwrap();
+        assert_eq!(
+            vec![vec![Col::varchar("bob", 8)], vec![Col::varchar("eve", 8)]],
+            execute(&mut first, "SELECT users.name FROM users")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)]],
+            execute(&mut second, "SELECT name FROM users")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            vec![vec![Col::varchar("ann", 8)]],
+            execute(&mut first, "SELECT name FROM main.users")
+                .unwrap()
+                .rows()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "temporary table 'main.staging' cannot be qualified"
+            )),
+            execute(&mut first, "CREATE TEMP TABLE main.staging(id int)")
+        );
+        execute(&mut second, "CREATE TEMP TABLE staging(id int)").unwrap();
+        assert_eq!(vec!["users"], engine.storage.tables(None).unwrap());
+
+        drop(first);
+        assert!(engine.storage.sequences().list().unwrap().is_empty());
+        execute(&mut second, "DROP TABLE staging").unwrap();
+        execute(&mut second, "CREATE TEMP TABLE staging(id int)").unwrap();
+        execute(&mut second, "DROP TABLE users").unwrap();
+        let files: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(vec!["catalog.seq"], files);
+    }
+
+    #[test]
+    fn change_feed() {
         let temp_dir = tempfile::tempdir().unwrap();
         let engine = Engine::new(temp_dir.path()).unwrap();
-        let result = engine
-            .execute(Command::Select {
-                table: "test".to_string(),
-                fields: vec![],
-            })
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, name varchar(8))").unwrap();
+        execute("CREATE INDEX users_name ON users(name)").unwrap();
+        execute("INSERT INTO users(id, name) VALUES(1, 'ann')").unwrap();
+
+        let changes = engine.subscribe().unwrap();
+        let row = |id: i32, name: &str| Some(vec![Col::int(id), Col::varchar(name, 8)]);
+        execute("INSERT INTO users(id, name) VALUES(2, 'bob') (3, 'cid')").unwrap();
+        execute("UPDATE users SET name = 'amy' WHERE id = 1").unwrap();
+        execute("CREATE TEMP TABLE scratch(id int)").unwrap();
+        execute("INSERT INTO scratch(id) VALUES(1)").unwrap();
+        execute("BEGIN").unwrap();
+        execute("UPDATE users SET id = 4 WHERE id = 2").unwrap();
+        execute("COMMIT").unwrap();
+        execute("BEGIN").unwrap();
+        execute("DELETE FROM users").unwrap();
+        execute("ROLLBACK").unwrap();
+        execute("DELETE FROM users").unwrap();
+
+        let received: Vec<Change> = std::iter::from_fn(|| changes.try_next()).collect();
+        let summary: Vec<(u64, ChangeOp, Col)> = received
+            .iter()
+            .map(|change| (change.commit, change.op, change.key.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, ChangeOp::Insert, Col::int(2)),
+                (1, ChangeOp::Insert, Col::int(3)),
+                (2, ChangeOp::Update, Col::int(1)),
+                (3, ChangeOp::Delete, Col::int(2)),
+                (3, ChangeOp::Insert, Col::int(4)),
+                (4, ChangeOp::Delete, Col::int(1)),
+                (4, ChangeOp::Delete, Col::int(3)),
+                (4, ChangeOp::Delete, Col::int(4)),
+            ],
+            summary
+        );
+        assert!(received.iter().all(|change| change.table == "users"));
+        let images = |change: &Change| (change.before.clone(), change.after.clone());
+        assert_eq!((row(1, "ann"), row(1, "amy")), images(&received[2]));
+        assert_eq!((None, row(4, "bob")), images(&received[4]));
+        assert_eq!((row(3, "cid"), None), images(&received[6]));
+
+        drop(changes);
+        execute("INSERT INTO users(id, name) VALUES(5, 'eve')").unwrap();
+        let changes = engine.subscribe().unwrap();
+        assert!(changes.try_next().is_none());
+        assert!(engine.subscribe_from(0).is_err());
+        assert!(!temp_dir.path().join("catalog.changes").exists());
+    }
+
+    #[test]
+    fn change_log() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let open = || {
+            let mut engine = Engine::new(temp_dir.path()).unwrap();
+            engine.set_change_retention(3);
+            engine
+        };
+        let engine = open();
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, name varchar(8))").unwrap();
+        for id in 1..=3 {
+            execute(&format!(
+                "INSERT INTO users(id, name) VALUES({}, 'ann')",
+                id
+            ))
+            .unwrap();
+        }
+        drop(engine);
+
+        let engine = open();
+        let changes = engine.subscribe_from(2).unwrap();
+        engine
+            .execute(parser::parse("DELETE FROM users").unwrap())
             .unwrap();
-        assert!(result.field_names.is_empty());
-        assert!(result.fields.is_empty());
+        let summary: Vec<(u64, ChangeOp, Col)> = std::iter::from_fn(|| changes.try_next())
+            .map(|change| (change.commit, change.op, change.key))
+            .collect();
+        assert_eq!(
+            vec![
+                (2, ChangeOp::Insert, Col::int(2)),
+                (3, ChangeOp::Insert, Col::int(3)),
+                (4, ChangeOp::Delete, Col::int(1)),
+                (4, ChangeOp::Delete, Col::int(2)),
+                (4, ChangeOp::Delete, Col::int(3)),
+            ],
+            summary
+        );
+        engine.truncate_changes(4).unwrap();
+        assert_eq!(
+            4,
+            engine.subscribe_from(0).unwrap().try_next().unwrap().commit
+        );
+        for id in 1..=8 {
+            engine
+                .execute(
+                    parser::parse(&format!(
+                        "INSERT INTO users(id, name) VALUES({}, 'bob')",
+                        id
+                    ))
+                    .unwrap(),
+                )
+                .unwrap();
+        }
+        let replayed = engine.subscribe_from(0).unwrap();
+        let commits: Vec<u64> = std::iter::from_fn(|| replayed.try_next())
+            .map(|change| change.commit)
+            .collect();
+        assert_eq!((8..=12).collect::<Vec<_>>(), commits);
     }
 
     #[test]
-    fn delete_all() {
+    fn execution_hooks() {
         let temp_dir = tempfile::tempdir().unwrap();
-        let engine = Engine::new(temp_dir.path()).unwrap();
-        let result = engine
-            .execute(Command::Delete {
-                table: "test".to_string(),
-            })
+        let mut engine = Engine::new(temp_dir.path()).unwrap();
+        let statements = Arc::new(Mutex::new(Vec::new()));
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let inserted = Arc::new(Mutex::new(Vec::new()));
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let seen = statements.clone();
+        engine.before_statement(move |command| {
+            seen.lock()
+                .unwrap()
+                .push(matches!(command, Command::Insert { .. }))
+        });
+        let seen = results.clone();
+        engine.after_statement(move |_, result| {
+            let affected = result.ok().map(|result| match result {
+                ExecResult::Affected { count, .. } => *count,
+                _ => 0,
+            });
+            seen.lock().unwrap().push(affected)
+        });
+        let seen = inserted.clone();
+        engine.on_insert("users", move |row| seen.lock().unwrap().push(row.to_vec()));
+        let seen = deleted.clone();
+        engine.on_delete("users", move |row| seen.lock().unwrap().push(row.to_vec()));
+
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE users(id int, name varchar(8))").unwrap();
+        execute("CREATE TABLE other(id int)").unwrap();
+        execute("INSERT INTO users(id, name) VALUES(1, 'ann') (2, 'bob')").unwrap();
+        execute("INSERT INTO other(id) VALUES(1)").unwrap();
+        execute("UPDATE users SET name = 'amy' WHERE id = 1").unwrap();
+        assert!(execute("INSERT INTO missing(id) VALUES(1)").is_err());
+        execute("BEGIN").unwrap();
+        execute("DELETE FROM users").unwrap();
+        execute("COMMIT").unwrap();
+
+        assert_eq!(
+            vec![false, false, true, true, false, true, false, false, false],
+            *statements.lock().unwrap()
+        );
+        assert_eq!(
+            vec![
+                Some(0),
+                Some(0),
+                Some(2),
+                Some(1),
+                Some(1),
+                None,
+                Some(0),
+                Some(2),
+                Some(2)
+            ],
+            *results.lock().unwrap()
+        );
+        let row = |id: i32, name: &str| vec![Col::int(id), Col::varchar(name, 8)];
+        assert_eq!(
+            vec![row(1, "ann"), row(2, "bob"), row(1, "amy")],
+            *inserted.lock().unwrap()
+        );
+        assert_eq!(
+            vec![row(1, "ann"), row(1, "amy"), row(2, "bob")],
+            *deleted.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn cancellation() {
+        let engine = Arc::new(Engine::in_memory());
+        let execute = |query: &str| engine.execute(parser::parse(query).unwrap());
+        execute("CREATE TABLE test(id int, age int)").unwrap();
+        let values: Vec<String> = (0..600).map(|i| format!("({}, {})", i, i % 3)).collect();
+        execute(&format!(
+            "INSERT INTO test(id, age) VALUES{}",
+            values.join(" ")
+        ))
+        .unwrap();
+
+        let token = CancelToken::new();
+        let mut cursor = engine
+            .query_with(parser::parse("SELECT id FROM test").unwrap(), &token)
             .unwrap();
-        assert!(!result.field_names.is_empty());
-        assert!(!result.fields.is_empty());
+        assert_eq!(3, cursor.fetch(3).unwrap().len());
+        token.cancel();
+        assert_eq!(Err(DbError::Cancelled), cursor.fetch(300));
+        for query in [
+            "SELECT id FROM test ORDER BY age",
+            "SELECT age, count(*) FROM test GROUP BY age",
+            "UPDATE test SET age = 1",
+        ] {
+            assert_eq!(
+                Err(DbError::Cancelled),
+                engine.execute_with(parser::parse(query).unwrap(), &token)
+            );
+        }
+
+        let session = engine.session();
+        assert_eq!(
+            Err(DbError::InvalidInput(format!(
+                "query {} is not running",
+                session.id()
+            ))),
+            engine.cancel(session.id())
+        );
     }
 }
@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use common::error::DbError;
+use row::Col;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Resource {
+    Table(String),
+    Key(String, Col),
+}
+
+impl Resource {
+    pub(crate) fn table(name: &str) -> Self {
+        Self::Table(name.to_string())
+    }
+
+    pub(crate) fn key(table: &str, key: &Col) -> Self {
+        Self::Key(table.to_string(), key.clone())
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table(table) => write!(f, "{}", table),
+            Self::Key(table, key) => write!(f, "{}({})", table, key),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum LockMode {
+    Intention,
+    Exclusive,
+}
+
+impl LockMode {
+    fn compatible(self, other: LockMode) -> bool {
+        self == LockMode::Intention && other == LockMode::Intention
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    granted: HashMap<u64, LockMode>,
+    waiting: VecDeque<u64>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: BTreeMap<Resource, Entry>,
+    held: HashMap<u64, Vec<Resource>>,
+}
+
+pub(crate) struct LockManager {
+    state: Mutex<State>,
+    released: Condvar,
+    timeout: Duration,
+}
+
+impl LockManager {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            released: Condvar::new(),
+            timeout,
+        }
+    }
+
+    pub(crate) fn lock(
+        &self,
+        owner: u64,
+        resource: Resource,
+        mode: LockMode,
+    ) -> Result<(), DbError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut state = self.state();
+        let entry = state.entries.entry(resource.clone()).or_default();
+        if entry.granted.get(&owner).is_some_and(|held| *held >= mode) {
+            return Ok(());
+        }
+        entry.waiting.push_back(owner);
+        loop {
+            let State { entries, held } = &mut *state;
+            let Some(entry) = entries.get_mut(&resource) else {
+                return Err(DbError::unexpected("lock entry is missing"));
+            };
+            let grantable = entry.waiting.front() == Some(&owner)
+                && entry
+                    .granted
+                    .iter()
+                    .all(|(holder, held)| *holder == owner || held.compatible(mode));
+            if grantable {
+                entry.waiting.pop_front();
+                match entry.granted.insert(owner, mode) {
+                    Some(previous) => {
+                        entry.granted.insert(owner, previous.max(mode));
+                    }
+                    None => held.entry(owner).or_default().push(resource),
+                }
+                self.released.notify_all();
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                entry.waiting.retain(|waiter| *waiter != owner);
+                if entry.granted.is_empty() && entry.waiting.is_empty() {
+                    entries.remove(&resource);
+                }
+                self.released.notify_all();
+                return Err(DbError::LockTimeout(resource.to_string()));
+            }
+            state = self
+                .released
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+    }
+
+    pub(crate) fn release(&self, owner: u64) {
+        let mut state = self.state();
+        let State { entries, held } = &mut *state;
+        for resource in held.remove(&owner).unwrap_or_default() {
+            let Some(entry) = entries.get_mut(&resource) else {
+                continue;
+            };
+            entry.granted.remove(&owner);
+            if entry.granted.is_empty() && entry.waiting.is_empty() {
+                entries.remove(&resource);
+            }
+        }
+        self.released.notify_all();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+
+    #[test]
+    fn compatibility() {
+        let locks = LockManager::new(Duration::from_millis(50));
+        let table = Resource::table("t");
+        locks.lock(1, table.clone(), LockMode::Intention).unwrap();
+        locks.lock(2, table.clone(), LockMode::Intention).unwrap();
+        locks
+            .lock(1, Resource::key("t", &Col::int(1)), LockMode::Exclusive)
+            .unwrap();
+        assert_eq!(
+            Err(DbError::LockTimeout("t(1)".to_string())),
+            locks.lock(2, Resource::key("t", &Col::int(1)), LockMode::Exclusive)
+        );
+        assert_eq!(
+            Err(DbError::LockTimeout("t".to_string())),
+            locks.lock(3, table.clone(), LockMode::Exclusive)
+        );
+        locks.release(1);
+        locks.release(2);
+        locks.lock(3, table.clone(), LockMode::Exclusive).unwrap();
+        locks.lock(3, table.clone(), LockMode::Intention).unwrap();
+        locks.release(3);
+        assert!(locks.state().entries.is_empty());
+        assert!(locks.state().held.is_empty());
+    }
+
+    #[test]
+    fn wait_queue() {
+        let locks = LockManager::new(Duration::from_secs(10));
+        let key = Resource::key("t", &Col::int(1));
+        locks.lock(1, key.clone(), LockMode::Exclusive).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for owner in [2, 3] {
+                let tx = tx.clone();
+                let (locks, key) = (&locks, key.clone());
+                scope.spawn(move || {
+                    locks.lock(owner, key, LockMode::Exclusive).unwrap();
+                    tx.send(owner).unwrap();
+                    thread::sleep(Duration::from_millis(10));
+                    locks.release(owner);
+                });
+            }
+            thread::sleep(Duration::from_millis(50));
+            assert!(rx.try_recv().is_err());
+            locks.release(1);
+            let mut granted = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+            granted.sort();
+            assert_eq!(vec![2, 3], granted);
+        });
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use btree::IoCounters;
+use parser::Command;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub statements: BTreeMap<String, u64>,
+    pub rows_read: u64,
+    pub rows_written: u64,
+    pub page_reads: u64,
+    pub page_writes: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub errors: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    statements: Mutex<BTreeMap<&'static str, u64>>,
+    rows_read: AtomicU64,
+    rows_written: AtomicU64,
+    errors: AtomicU64,
+    io: Arc<IoCounters>,
+}
+
+impl Metrics {
+    pub fn cache_hit_ratio(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.cache_hits as f64 / lookups as f64
+    }
+}
+
+impl Counters {
+    pub(crate) fn statement(&self, command: &Command) {
+        let mut statements = self
+            .statements
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        *statements.entry(kind(command)).or_default() += 1;
+    }
+
+    pub(crate) fn read(&self, rows: usize) {
+        self.rows_read.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn written(&self, rows: usize) {
+        self.rows_written.fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn io(&self) -> Arc<IoCounters> {
+        self.io.clone()
+    }
+
+    pub(crate) fn snapshot(&self) -> Metrics {
+        let statements = self
+            .statements
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|(kind, count)| (kind.to_string(), *count))
+            .collect();
+        let io = self.io.stats();
+        Metrics {
+            statements,
+            rows_read: self.rows_read.load(Ordering::Relaxed),
+            rows_written: self.rows_written.load(Ordering::Relaxed),
+            page_reads: io.page_reads,
+            page_writes: io.page_writes,
+            cache_hits: io.cache_hits,
+            cache_misses: io.cache_misses,
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) fn kind(command: &Command) -> &'static str {
+    match command {
+        Command::Create { .. } => "create",
+        Command::CreateIndex { .. } => "create_index",
+        Command::CreateSequence { .. } => "create_sequence",
+        Command::CreateView { .. } => "create_view",
+        Command::CreateDatabase { .. } => "create_database",
+        Command::Use { .. } => "use",
+        Command::Insert { .. } => "insert",
+        Command::Select { .. } => "select",
+        Command::Update { .. } => "update",
+        Command::Delete { .. } => "delete",
+        Command::Drop { .. } => "drop",
+        Command::DropSequence { .. } => "drop_sequence",
+        Command::DropView { .. } => "drop_view",
+        Command::RenameTable { .. } => "rename_table",
+        Command::NextVal { .. } => "nextval",
+        Command::Vacuum { .. } => "vacuum",
+        Command::Analyze { .. } => "analyze",
+        Command::ShowTableStatus { .. } => "show_table_status",
+        Command::CheckTable { .. } => "check_table",
+        Command::Explain { .. } => "explain",
+        Command::CopyFrom { .. } => "copy_from",
+        Command::CopyTo { .. } => "copy_to",
+        Command::Dump { .. } => "dump",
+        Command::Restore { .. } => "restore",
+        Command::Begin => "begin",
+        Command::Commit => "commit",
+        Command::Rollback => "rollback",
+    }
+}
@@ -0,0 +1,386 @@
+use std::io::Write;
+
+use common::error::DbError;
+use row::{Col, ColType};
+
+use crate::coerce;
+
+const MAGIC: &[u8] = b"PAR1";
+const ROW_GROUP_SIZE: usize = 65536;
+const CREATED_BY: &str = "sql engine";
+
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const CONVERTED_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const UNCOMPRESSED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+
+const COMPACT_I32: u8 = 5;
+const COMPACT_I64: u8 = 6;
+const COMPACT_BINARY: u8 = 8;
+const COMPACT_LIST: u8 = 9;
+const COMPACT_STRUCT: u8 = 12;
+
+pub(crate) struct Writer<W> {
+    output: W,
+    offset: u64,
+    names: Vec<String>,
+    columns: Vec<ColType>,
+    rows: Vec<Vec<Col>>,
+    row_groups: Vec<RowGroup>,
+}
+
+struct RowGroup {
+    chunks: Vec<Chunk>,
+    rows: usize,
+}
+
+struct Chunk {
+    offset: u64,
+    size: u64,
+}
+
+#[derive(Default)]
+struct Compact {
+    buffer: Vec<u8>,
+    last: Vec<i16>,
+    field: i16,
+}
+
+impl<W: Write> Writer<W> {
+    pub(crate) fn new(
+        mut output: W,
+        names: Vec<String>,
+        columns: Vec<ColType>,
+    ) -> Result<Self, DbError> {
+        output.write_all(MAGIC)?;
+        Ok(Self {
+            output,
+            offset: MAGIC.len() as u64,
+            names,
+            columns,
+            rows: Vec::new(),
+            row_groups: Vec::new(),
+        })
+    }
+
+    pub(crate) fn row(&mut self, row: Vec<Col>) -> Result<(), DbError> {
+        if row.len() != self.columns.len() {
+            return Err(DbError::unexpected("row does not match parquet schema"));
+        }
+        let row = row
+            .into_iter()
+            .zip(self.columns.iter())
+            .map(|(value, col_type)| coerce::convert(value, "parquet", col_type))
+            .collect::<Result<_, _>>()?;
+        self.rows.push(row);
+        if self.rows.len() == ROW_GROUP_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<W, DbError> {
+        self.flush()?;
+        let footer = self.footer();
+        self.output.write_all(&footer)?;
+        self.output
+            .write_all(&(footer.len() as u32).to_le_bytes())?;
+        self.output.write_all(MAGIC)?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn flush(&mut self) -> Result<(), DbError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut chunks = Vec::with_capacity(self.columns.len());
+        for index in 0..self.columns.len() {
+            let mut values = Vec::new();
+            for row in self.rows.iter() {
+                encode(&row[index], &mut values);
+            }
+            let header = page_header(self.rows.len(), values.len());
+            self.output.write_all(&header)?;
+            self.output.write_all(&values)?;
+            let size = (header.len() + values.len()) as u64;
+            chunks.push(Chunk {
+                offset: self.offset,
+                size,
+            });
+            self.offset += size;
+        }
+        self.row_groups.push(RowGroup {
+            chunks,
+            rows: self.rows.len(),
+        });
+        self.rows.clear();
+        Ok(())
+    }
+
+    fn footer(&self) -> Vec<u8> {
+        let mut footer = Compact::default();
+        footer.i32(1, 1);
+        footer.list(2, COMPACT_STRUCT, self.columns.len() + 1);
+        footer.begin();
+        footer.binary(4, b"schema");
+        footer.i32(5, self.columns.len() as i32);
+        footer.end();
+        for (name, col_type) in self.names.iter().zip(self.columns.iter()) {
+            footer.begin();
+            footer.i32(1, physical_type(col_type));
+            footer.i32(3, REQUIRED);
+            footer.binary(4, name.as_bytes());
+            if let ColType::Varchar(..) = col_type {
+                footer.i32(6, CONVERTED_UTF8);
+                footer.field(10, COMPACT_STRUCT);
+                footer.begin();
+                footer.field(1, COMPACT_STRUCT);
+                footer.begin();
+                footer.end();
+                footer.end();
+            }
+            footer.end();
+        }
+        let rows: usize = self.row_groups.iter().map(|group| group.rows).sum();
+        footer.i64(3, rows as i64);
+        footer.list(4, COMPACT_STRUCT, self.row_groups.len());
+        for group in self.row_groups.iter() {
+            footer.begin();
+            footer.list(1, COMPACT_STRUCT, group.chunks.len());
+            let columns = self.names.iter().zip(self.columns.iter());
+            for (chunk, (name, col_type)) in group.chunks.iter().zip(columns) {
+                footer.begin();
+                footer.i64(2, chunk.offset as i64);
+                footer.field(3, COMPACT_STRUCT);
+                footer.begin();
+                footer.i32(1, physical_type(col_type));
+                footer.list(2, COMPACT_I32, 2);
+                footer.varint(zigzag(ENCODING_PLAIN as i64));
+                footer.varint(zigzag(ENCODING_RLE as i64));
+                footer.list(3, COMPACT_BINARY, 1);
+                footer.bytes(name.as_bytes());
+                footer.i32(4, UNCOMPRESSED);
+                footer.i64(5, group.rows as i64);
+                footer.i64(6, chunk.size as i64);
+                footer.i64(7, chunk.size as i64);
+                footer.i64(9, chunk.offset as i64);
+                footer.end();
+                footer.end();
+            }
+            let size: u64 = group.chunks.iter().map(|chunk| chunk.size).sum();
+            footer.i64(2, size as i64);
+            footer.i64(3, group.rows as i64);
+            footer.end();
+        }
+        footer.binary(6, CREATED_BY.as_bytes());
+        footer.end();
+        footer.buffer
+    }
+}
+
+impl Compact {
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.field;
+        if (1..=15).contains(&delta) {
+            self.buffer.push(((delta as u8) << 4) | kind);
+        } else {
+            self.buffer.push(kind);
+            self.varint(zigzag(id as i64));
+        }
+        self.field = id;
+    }
+
+    fn begin(&mut self) {
+        self.last.push(self.field);
+        self.field = 0;
+    }
+
+    fn end(&mut self) {
+        self.buffer.push(0);
+        self.field = self.last.pop().unwrap_or_default();
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, COMPACT_I32);
+        self.varint(zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, COMPACT_I64);
+        self.varint(zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, COMPACT_BINARY);
+        self.bytes(value);
+    }
+
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, COMPACT_LIST);
+        if len < 15 {
+            self.buffer.push(((len as u8) << 4) | kind);
+        } else {
+            self.buffer.push(0xF0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.buffer.extend_from_slice(value);
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buffer.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buffer.push(value as u8);
+    }
+}
+
+fn page_header(rows: usize, size: usize) -> Vec<u8> {
+    let mut header = Compact::default();
+    header.i32(1, DATA_PAGE);
+    header.i32(2, size as i32);
+    header.i32(3, size as i32);
+    header.field(5, COMPACT_STRUCT);
+    header.begin();
+    header.i32(1, rows as i32);
+    header.i32(2, ENCODING_PLAIN);
+    header.i32(3, ENCODING_RLE);
+    header.i32(4, ENCODING_RLE);
+    header.end();
+    header.end();
+    header.buffer
+}
+
+fn encode(value: &Col, output: &mut Vec<u8>) {
+    match value {
+        Col::Int(value) => output.extend_from_slice(&value.to_le_bytes()),
+        Col::BigInt(value) => output.extend_from_slice(&value.to_le_bytes()),
+        Col::Varchar(value, _) => {
+            output.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            output.extend_from_slice(value.as_bytes());
+        }
+    }
+}
+
+fn physical_type(col_type: &ColType) -> i32 {
+    match col_type {
+        ColType::Int(_) => TYPE_INT32,
+        ColType::BigInt(_) => TYPE_INT64,
+        ColType::Varchar(..) => TYPE_BYTE_ARRAY,
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::{
+        basic::{LogicalType, Type},
+        file::reader::{FileReader, SerializedFileReader},
+        record::RowAccessor,
+    };
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let names = vec!["id".to_string(), "total".to_string(), "name".to_string()];
+        let types = vec![
+            ColType::int("id"),
+            ColType::bigint("total"),
+            ColType::varchar("name", 8),
+        ];
+        let mut writer = Writer::new(tempfile::tempfile().unwrap(), names, types).unwrap();
+        writer
+            .row(vec![Col::int(1), Col::int(10), Col::varchar("ann", 8)])
+            .unwrap();
+        writer
+            .row(vec![Col::int(2), Col::big_int(20), Col::varchar("bob", 8)])
+            .unwrap();
+        assert!(writer.row(vec![Col::int(3)]).is_err());
+        let reader = SerializedFileReader::new(writer.finish().unwrap()).unwrap();
+
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(2, metadata.num_rows());
+        assert_eq!(Some(CREATED_BY), metadata.created_by());
+        let schema: Vec<(&str, Type, Option<LogicalType>)> = metadata
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| (column.name(), column.physical_type(), column.logical_type()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("id", Type::INT32, None),
+                ("total", Type::INT64, None),
+                ("name", Type::BYTE_ARRAY, Some(LogicalType::String)),
+            ],
+            schema
+        );
+        let rows: Vec<(i32, i64, String)> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (
+                    row.get_int(0).unwrap(),
+                    row.get_long(1).unwrap(),
+                    row.get_string(2).unwrap().clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![(1, 10, "ann".to_string()), (2, 20, "bob".to_string())],
+            rows
+        );
+    }
+
+    #[test]
+    fn row_groups() {
+        let mut writer = Writer::new(
+            tempfile::tempfile().unwrap(),
+            vec!["id".to_string()],
+            vec![ColType::bigint("id")],
+        )
+        .unwrap();
+        let count = ROW_GROUP_SIZE + 1;
+        for id in 0..count {
+            writer.row(vec![Col::big_int(id as i64)]).unwrap();
+        }
+        let reader = SerializedFileReader::new(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(2, reader.num_row_groups());
+        assert_eq!(count as i64, reader.metadata().file_metadata().num_rows());
+        let ids: Vec<i64> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_long(0).unwrap())
+            .collect();
+        assert_eq!((0..count as i64).collect::<Vec<_>>(), ids);
+    }
+
+    #[test]
+    fn compact_fields() {
+        let mut compact = Compact::default();
+        compact.i32(1, -1);
+        compact.i64(20, 300);
+        compact.list(21, COMPACT_I32, 20);
+        compact.end();
+        assert_eq!(
+            vec![0x15, 0x01, 0x06, 0x28, 0xD8, 0x04, 0x19, 0xF5, 0x14, 0x00],
+            compact.buffer
+        );
+    }
+}
@@ -0,0 +1,2334 @@
+use std::{cell::RefCell, collections::BTreeSet, fmt, ops::Bound};
+
+use btree::Stats;
+use common::error::DbError;
+pub use parser::Operator;
+use parser::{Command, Comparison, OrderBy};
+use row::{Col, ColType};
+
+use crate::{
+    coerce,
+    database::{self, Namespace},
+    eval::Term,
+    statistics::ColumnStats,
+    storage::Storage,
+};
+
+const EQ_SELECTIVITY: u64 = 10;
+const RANGE_SELECTIVITY: u64 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Predicate {
+    Compare {
+        column: String,
+        op: Operator,
+        value: Col,
+    },
+    And(Box<Predicate>, Box<Predicate>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scalar {
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Aggregate {
+    pub function: Function,
+    pub column: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogicalPlan {
+    Scan {
+        table: String,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: Predicate,
+    },
+    Project {
+        input: Box<LogicalPlan>,
+        columns: Vec<String>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<OrderBy>,
+    },
+    Distinct {
+        input: Box<LogicalPlan>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        limit: usize,
+    },
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<String>,
+        aggregates: Vec<Aggregate>,
+    },
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        on: (String, String),
+    },
+    View {
+        input: Box<LogicalPlan>,
+        name: String,
+        columns: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnRef {
+    pub index: usize,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: ColumnRef,
+    pub descending: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregateRef {
+    pub function: Function,
+    pub column: Option<ColumnRef>,
+    pub col_type: ColType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Compare {
+        column: ColumnRef,
+        op: Operator,
+        value: Col,
+    },
+    Call {
+        function: Scalar,
+        column: ColumnRef,
+        op: Operator,
+        value: Col,
+    },
+    And(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhysicalPlan {
+    SeqScan {
+        table: String,
+        columns: Vec<String>,
+        limit: Option<usize>,
+        reverse: bool,
+    },
+    KeyLookup {
+        table: String,
+        columns: Vec<String>,
+        key: Col,
+    },
+    IndexScan {
+        table: String,
+        index: String,
+        columns: Vec<String>,
+        prefix: Vec<(String, Col)>,
+        column: String,
+        from: Bound<Col>,
+        to: Bound<Col>,
+        limit: Option<usize>,
+        covering: bool,
+        reverse: bool,
+    },
+    Filter {
+        input: Box<PhysicalPlan>,
+        condition: Condition,
+    },
+    Project {
+        input: Box<PhysicalPlan>,
+        columns: Vec<ColumnRef>,
+    },
+    Sort {
+        input: Box<PhysicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Distinct {
+        input: Box<PhysicalPlan>,
+    },
+    TopN {
+        input: Box<PhysicalPlan>,
+        keys: Vec<SortKey>,
+        limit: usize,
+    },
+    Limit {
+        input: Box<PhysicalPlan>,
+        limit: usize,
+    },
+    HashAggregate {
+        input: Box<PhysicalPlan>,
+        group_by: Vec<ColumnRef>,
+        aggregates: Vec<AggregateRef>,
+    },
+    RowCount {
+        table: String,
+        aggregate: AggregateRef,
+    },
+    HashJoin {
+        left: Box<PhysicalPlan>,
+        right: Box<PhysicalPlan>,
+        on: (ColumnRef, ColumnRef),
+    },
+    IndexJoin {
+        left: Box<PhysicalPlan>,
+        table: String,
+        columns: Vec<String>,
+        index: Option<String>,
+        key_type: ColType,
+        on: (ColumnRef, ColumnRef),
+        condition: Option<Condition>,
+    },
+}
+
+struct Literal<'a>(&'a Col);
+struct Label<'a>(&'a PhysicalPlan);
+
+struct InnerLookup {
+    table: String,
+    index: Option<String>,
+    condition: Option<Condition>,
+}
+
+struct Field {
+    table: String,
+    col_type: ColType,
+}
+
+pub(crate) struct Planner<'a> {
+    storage: &'a Storage,
+    expanding: RefCell<Vec<String>>,
+}
+
+impl Predicate {
+    pub fn compare(column: &str, op: Operator, value: Col) -> Self {
+        Self::Compare {
+            column: column.to_string(),
+            op,
+            value,
+        }
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    fn conjuncts(self) -> Vec<Predicate> {
+        match self {
+            Self::And(left, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            other => vec![other],
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            Self::Compare { column, .. } => match Scalar::parse(column) {
+                Some((_, column)) => vec![column],
+                None => vec![column.as_str()],
+            },
+            Self::And(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+        }
+    }
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Self::Count),
+            "sum" => Some(Self::Sum),
+            "avg" => Some(Self::Avg),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count => write!(f, "count"),
+            Self::Sum => write!(f, "sum"),
+            Self::Avg => write!(f, "avg"),
+            Self::Min => write!(f, "min"),
+            Self::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl Scalar {
+    pub fn parse(term: &str) -> Option<(Self, &str)> {
+        let (function, column) = term.strip_suffix(')')?.split_once('(')?;
+        let function = match function {
+            "lower" => Self::Lower,
+            "upper" => Self::Upper,
+            _ => return None,
+        };
+        Some((function, column))
+    }
+
+    pub fn apply(self, value: &Col) -> Col {
+        match (self, value) {
+            (Self::Lower, Col::Varchar(value, size)) => Col::Varchar(value.to_lowercase(), *size),
+            (Self::Upper, Col::Varchar(value, size)) => Col::Varchar(value.to_uppercase(), *size),
+            (_, value) => value.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lower => write!(f, "lower"),
+            Self::Upper => write!(f, "upper"),
+        }
+    }
+}
+
+impl Aggregate {
+    pub fn new(function: Function, column: Option<&str>) -> Self {
+        Self {
+            function,
+            column: column.map(str::to_string),
+        }
+    }
+
+    pub fn parse(field: &str) -> Option<Self> {
+        let (function, column) = field.strip_suffix(')')?.split_once('(')?;
+        let function = Function::parse(function)?;
+        match column {
+            "*" if function == Function::Count => Some(Self::new(function, None)),
+            "*" => None,
+            column => Some(Self::new(function, Some(column))),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!(
+            "{}({})",
+            self.function,
+            self.column.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+impl AggregateRef {
+    pub fn name(&self) -> String {
+        match &self.column {
+            Some(column) => format!("{}({})", self.function, column.name),
+            None => format!("{}(*)", self.function),
+        }
+    }
+}
+
+impl LogicalPlan {
+    pub fn scan(table: &str) -> Self {
+        Self::Scan {
+            table: table.to_string(),
+        }
+    }
+
+    pub fn filter(self, predicate: Predicate) -> Self {
+        Self::Filter {
+            input: Box::new(self),
+            predicate,
+        }
+    }
+
+    pub fn project(self, columns: Vec<String>) -> Self {
+        Self::Project {
+            input: Box::new(self),
+            columns,
+        }
+    }
+
+    pub fn sort(self, keys: Vec<OrderBy>) -> Self {
+        Self::Sort {
+            input: Box::new(self),
+            keys,
+        }
+    }
+
+    pub fn distinct(self) -> Self {
+        Self::Distinct {
+            input: Box::new(self),
+        }
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        Self::Limit {
+            input: Box::new(self),
+            limit,
+        }
+    }
+
+    pub fn aggregate(self, group_by: Vec<String>, aggregates: Vec<Aggregate>) -> Self {
+        Self::Aggregate {
+            input: Box::new(self),
+            group_by,
+            aggregates,
+        }
+    }
+
+    pub fn join(self, right: LogicalPlan, left_column: &str, right_column: &str) -> Self {
+        Self::Join {
+            left: Box::new(self),
+            right: Box::new(right),
+            on: (left_column.to_string(), right_column.to_string()),
+        }
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, row: &[Col]) -> bool {
+        match self {
+            Self::Compare { column, op, value } => holds(*op, compare(&row[column.index], value)),
+            Self::Call {
+                function,
+                column,
+                op,
+                value,
+            } => holds(*op, compare(&function.apply(&row[column.index]), value)),
+            Self::And(left, right) => left.matches(row) && right.matches(row),
+        }
+    }
+
+    fn references(&self, columns: &mut BTreeSet<usize>) {
+        match self {
+            Self::Compare { column, .. } | Self::Call { column, .. } => {
+                columns.insert(column.index);
+            }
+            Self::And(left, right) => {
+                left.references(columns);
+                right.references(columns);
+            }
+        }
+    }
+}
+
+impl PhysicalPlan {
+    pub fn columns(&self) -> Vec<String> {
+        match self {
+            Self::SeqScan { columns, .. }
+            | Self::KeyLookup { columns, .. }
+            | Self::IndexScan { columns, .. } => columns.clone(),
+            Self::Filter { input, .. }
+            | Self::Sort { input, .. }
+            | Self::Distinct { input }
+            | Self::TopN { input, .. }
+            | Self::Limit { input, .. } => input.columns(),
+            Self::Project { columns, .. } => columns.iter().map(|c| c.name.clone()).collect(),
+            Self::HashAggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by
+                .iter()
+                .map(|c| c.name.clone())
+                .chain(aggregates.iter().map(AggregateRef::name))
+                .collect(),
+            Self::RowCount { aggregate, .. } => vec![aggregate.name()],
+            Self::HashJoin { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Self::IndexJoin { left, columns, .. } => {
+                let mut left = left.columns();
+                left.extend(columns.iter().cloned());
+                left
+            }
+        }
+    }
+
+    pub(crate) fn tables(&self) -> BTreeSet<String> {
+        match self {
+            Self::SeqScan { table, .. }
+            | Self::KeyLookup { table, .. }
+            | Self::IndexScan { table, .. }
+            | Self::RowCount { table, .. } => BTreeSet::from([table.clone()]),
+            Self::Filter { input, .. }
+            | Self::Project { input, .. }
+            | Self::Sort { input, .. }
+            | Self::Distinct { input }
+            | Self::TopN { input, .. }
+            | Self::Limit { input, .. }
+            | Self::HashAggregate { input, .. } => input.tables(),
+            Self::HashJoin { left, right, .. } => {
+                let mut tables = left.tables();
+                tables.extend(right.tables());
+                tables
+            }
+            Self::IndexJoin { left, table, .. } => {
+                let mut tables = left.tables();
+                tables.insert(table.clone());
+                tables
+            }
+        }
+    }
+
+    pub(crate) fn children(&self) -> Vec<&PhysicalPlan> {
+        match self {
+            Self::SeqScan { .. }
+            | Self::KeyLookup { .. }
+            | Self::IndexScan { .. }
+            | Self::RowCount { .. } => vec![],
+            Self::Filter { input, .. }
+            | Self::Project { input, .. }
+            | Self::Distinct { input }
+            | Self::Sort { input, .. }
+            | Self::TopN { input, .. }
+            | Self::Limit { input, .. }
+            | Self::HashAggregate { input, .. } => vec![input],
+            Self::HashJoin { left, right, .. } => vec![left, right],
+            Self::IndexJoin { left, .. } => vec![left],
+        }
+    }
+
+    pub(crate) fn label(&self) -> String {
+        Label(self).to_string()
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{}{}", "  ".repeat(depth), Label(self))?;
+        for child in self.children() {
+            child.fmt_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            PhysicalPlan::SeqScan {
+                table,
+                limit,
+                reverse,
+                ..
+            } => {
+                write!(f, "SeqScan {}", table)?;
+                write_reverse(f, *reverse)?;
+                write_limit(f, *limit)
+            }
+            PhysicalPlan::KeyLookup {
+                table,
+                columns,
+                key,
+            } => write!(f, "KeyLookup {} {} = {}", table, columns[0], Literal(key)),
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                prefix,
+                column,
+                from,
+                to,
+                limit,
+                covering,
+                reverse,
+                ..
+            } => {
+                write!(f, "IndexScan {} using {}", table, index)?;
+                for (name, value) in prefix {
+                    write!(f, " {} = {} AND", name, Literal(value))?;
+                }
+                match (from, to) {
+                    (Bound::Included(from), Bound::Included(to)) if from == to => {
+                        write!(f, " {} = {}", column, Literal(from))?;
+                    }
+                    _ => {
+                        let from = match from {
+                            Bound::Included(from) => Some((">=", from)),
+                            Bound::Excluded(from) => Some((">", from)),
+                            Bound::Unbounded => None,
+                        };
+                        let to = match to {
+                            Bound::Included(to) => Some(("<=", to)),
+                            Bound::Excluded(to) => Some(("<", to)),
+                            Bound::Unbounded => None,
+                        };
+                        for (i, (op, value)) in from.into_iter().chain(to).enumerate() {
+                            match i {
+                                0 => write!(f, " {} {} {}", column, op, Literal(value))?,
+                                _ => write!(f, " AND {} {} {}", column, op, Literal(value))?,
+                            }
+                        }
+                    }
+                }
+                write_reverse(f, *reverse)?;
+                if *covering {
+                    write!(f, " index only")?;
+                }
+                write_limit(f, *limit)
+            }
+            PhysicalPlan::Filter { condition, .. } => write!(f, "Filter {}", condition),
+            PhysicalPlan::Project { columns, .. } => write!(f, "Project [{}]", names(columns)),
+            PhysicalPlan::Distinct { .. } => write!(f, "Distinct"),
+            PhysicalPlan::Sort { keys, .. } => write!(f, "Sort [{}]", sort_keys(keys)),
+            PhysicalPlan::TopN { keys, limit, .. } => {
+                write!(f, "TopN {} [{}]", limit, sort_keys(keys))
+            }
+            PhysicalPlan::Limit { limit, .. } => write!(f, "Limit {}", limit),
+            PhysicalPlan::HashAggregate {
+                group_by,
+                aggregates,
+                ..
+            } => {
+                let aggregates: Vec<String> = aggregates.iter().map(AggregateRef::name).collect();
+                write!(
+                    f,
+                    "HashAggregate [{}] [{}]",
+                    names(group_by),
+                    aggregates.join(", ")
+                )
+            }
+            PhysicalPlan::RowCount { table, aggregate } => {
+                write!(f, "RowCount {} [{}]", table, aggregate.name())
+            }
+            PhysicalPlan::HashJoin { on, .. } => {
+                write!(f, "HashJoin {} = {}", on.0.name, on.1.name)
+            }
+            PhysicalPlan::IndexJoin {
+                table,
+                index,
+                on,
+                condition,
+                ..
+            } => {
+                write!(f, "IndexJoin {} = {} on {}", on.0.name, on.1.name, table)?;
+                match index {
+                    Some(index) => write!(f, " using {}", index)?,
+                    None => write!(f, " using primary key")?,
+                }
+                match condition {
+                    Some(condition) => write!(f, " where {}", condition),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compare { column, op, value } => {
+                write!(f, "{} {} {}", column.name, op, Literal(value))
+            }
+            Self::Call {
+                function,
+                column,
+                op,
+                value,
+            } => write!(f, "{}({}) {} {}", function, column.name, op, Literal(value)),
+            Self::And(left, right) => write!(f, "{} AND {}", left, right),
+        }
+    }
+}
+
+impl fmt::Display for Literal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Col::Int(value) => write!(f, "{}", value),
+            Col::BigInt(value) => write!(f, "{}", value),
+            Col::Varchar(value, _) => write!(f, "'{}'", value),
+        }
+    }
+}
+
+impl fmt::Display for PhysicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, 0)
+    }
+}
+
+impl Field {
+    fn name(&self) -> &str {
+        self.col_type.get_name()
+    }
+}
+
+impl<'a> Planner<'a> {
+    pub(crate) fn new(storage: &'a Storage) -> Self {
+        Self {
+            storage,
+            expanding: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn plan(&self, plan: LogicalPlan) -> Result<PhysicalPlan, DbError> {
+        let plan = self.expand(plan)?;
+        let plan = self.rewrite(plan)?;
+        self.physical(plan, None)
+    }
+
+    pub(crate) fn select(&self, command: Command) -> Result<LogicalPlan, DbError> {
+        let Command::Select {
+            distinct,
+            fields,
+            table,
+            conditions,
+            group_by,
+            having,
+            order_by,
+            limit,
+        } = command
+        else {
+            return Err(DbError::unexpected("expected SELECT"));
+        };
+        let mut plan = self.filter_plan(&table, conditions)?;
+        let aggregates: Vec<Aggregate> = fields
+            .iter()
+            .chain(having.iter().map(|condition| &condition.column))
+            .chain(order_by.iter().map(|key| &key.column))
+            .filter_map(|field| Aggregate::parse(field))
+            .fold(Vec::new(), |mut aggregates, aggregate| {
+                if !aggregates.contains(&aggregate) {
+                    aggregates.push(aggregate);
+                }
+                aggregates
+            });
+        if !aggregates.is_empty() || !group_by.is_empty() {
+            let grouped = fields
+                .iter()
+                .filter(|field| Aggregate::parse(field).is_none())
+                .find(|field| !group_by.contains(field));
+            if let Some(field) = grouped {
+                return Err(DbError::InvalidInput(format!(
+                    "column '{}' must appear in the GROUP BY clause or be used in an aggregate",
+                    field
+                )));
+            }
+            plan = plan.aggregate(group_by, aggregates);
+            if let Some(predicate) = self.predicate(&table, having)? {
+                plan = plan.filter(predicate);
+            }
+        } else if !having.is_empty() {
+            return Err(DbError::invalid_input(
+                "HAVING requires GROUP BY or an aggregate",
+            ));
+        }
+        if distinct {
+            if let Some(key) = order_by.iter().find(|key| !fields.contains(&key.column)) {
+                return Err(DbError::InvalidInput(format!(
+                    "for SELECT DISTINCT, ORDER BY column '{}' must appear in select list",
+                    key.column
+                )));
+            }
+            plan = plan.project(fields).distinct();
+            if !order_by.is_empty() {
+                plan = plan.sort(order_by);
+            }
+        } else {
+            if !order_by.is_empty() {
+                plan = plan.sort(order_by);
+            }
+            plan = plan.project(fields);
+        }
+        Ok(match limit {
+            Some(limit) => plan.limit(limit),
+            None => plan,
+        })
+    }
+
+    pub(crate) fn filter_plan(
+        &self,
+        table: &str,
+        conditions: Vec<Comparison>,
+    ) -> Result<LogicalPlan, DbError> {
+        let plan = LogicalPlan::scan(table);
+        Ok(match self.predicate(table, conditions)? {
+            Some(predicate) => plan.filter(predicate),
+            None => plan,
+        })
+    }
+
+    fn predicate(
+        &self,
+        table: &str,
+        conditions: Vec<Comparison>,
+    ) -> Result<Option<Predicate>, DbError> {
+        if conditions.is_empty() {
+            return Ok(None);
+        }
+        let fields = self.fields(&LogicalPlan::scan(table))?;
+        let mut predicates = Vec::with_capacity(conditions.len());
+        for condition in conditions {
+            let name = unqualify(&condition.column, table);
+            let aggregate = Aggregate::parse(name);
+            let name = match &aggregate {
+                Some(Aggregate {
+                    function: Function::Min | Function::Max,
+                    column: Some(column),
+                }) => unqualify(column, table),
+                Some(_) => {
+                    let col_type = ColType::bigint(&condition.column);
+                    let value = coerce::parse(table, &col_type, condition.value)?;
+                    predicates.push(Predicate::compare(&condition.column, condition.op, value));
+                    continue;
+                }
+                None => Scalar::parse(name).map_or(name, |(_, column)| unqualify(column, table)),
+            };
+            let Some(field) = fields.iter().find(|field| field.name() == name) else {
+                return Err(DbError::field_not_found(&condition.column, table));
+            };
+            let value = coerce::parse(table, &field.col_type, condition.value)?;
+            predicates.push(Predicate::compare(&condition.column, condition.op, value));
+        }
+        Ok(predicates.into_iter().reduce(Predicate::and))
+    }
+
+    pub(crate) fn define(&self, name: &str, definition: &str) -> Result<LogicalPlan, DbError> {
+        let Command::CreateView { columns, query, .. } = parser::parse(definition)? else {
+            return Err(DbError::unexpected("expected CREATE VIEW"));
+        };
+        let namespace = Namespace::new(database::split(name).0);
+        let query = database::qualify(*query, &namespace);
+        let input = self.expand(self.select(query)?)?;
+        let fields = self.fields(&input)?;
+        let columns = match columns.is_empty() {
+            true => fields
+                .iter()
+                .map(|field| field.name().to_string())
+                .collect(),
+            false if columns.len() == fields.len() => columns,
+            false => {
+                return Err(DbError::InvalidInput(format!(
+                    "view '{}' has {} columns, but its query returns {}",
+                    name,
+                    columns.len(),
+                    fields.len()
+                )));
+            }
+        };
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].contains(column) {
+                return Err(DbError::InvalidInput(format!(
+                    "column '{}' specified more than once in view '{}'",
+                    column, name
+                )));
+            }
+        }
+        Ok(LogicalPlan::View {
+            input: Box::new(input),
+            name: name.to_string(),
+            columns,
+        })
+    }
+
+    pub(crate) fn estimate(&self, plan: &PhysicalPlan) -> Result<u64, DbError> {
+        Ok(match plan {
+            PhysicalPlan::SeqScan { table, limit, .. } => {
+                let rows = self.storage.stats(table)?.entries;
+                limit.map_or(rows, |limit| rows.min(limit as u64))
+            }
+            PhysicalPlan::KeyLookup { .. } | PhysicalPlan::RowCount { .. } => 1,
+            PhysicalPlan::IndexScan {
+                table,
+                prefix,
+                column,
+                from,
+                to,
+                limit,
+                ..
+            } => {
+                let mut rows = self.storage.stats(table)?.entries;
+                for (name, _) in prefix {
+                    let stats = self.column_stats(table, name)?;
+                    rows =
+                        rows.div_ceil(stats.map_or(EQ_SELECTIVITY, |stats| stats.distinct.max(1)));
+                }
+                let stats = self.column_stats(table, column)?;
+                let rows = match (from, to, stats) {
+                    (Bound::Unbounded, Bound::Unbounded, _) => rows,
+                    (Bound::Included(from), Bound::Included(to), stats) if from == to => {
+                        rows.div_ceil(stats.map_or(EQ_SELECTIVITY, |stats| stats.distinct.max(1)))
+                    }
+                    (from, to, Some(stats)) => match stats.range() {
+                        Some(range) => {
+                            let lower = match from {
+                                Bound::Included(from) => fraction(Operator::Ge, from, range),
+                                Bound::Excluded(from) => fraction(Operator::Gt, from, range),
+                                Bound::Unbounded => Some(1.0),
+                            };
+                            let upper = match to {
+                                Bound::Included(to) => fraction(Operator::Le, to, range),
+                                Bound::Excluded(to) => fraction(Operator::Lt, to, range),
+                                Bound::Unbounded => Some(1.0),
+                            };
+                            match lower.zip(upper) {
+                                Some((lower, upper)) => scale(rows, lower + upper - 1.0),
+                                None => rows.div_ceil(RANGE_SELECTIVITY),
+                            }
+                        }
+                        None => rows.div_ceil(RANGE_SELECTIVITY),
+                    },
+                    _ => rows.div_ceil(RANGE_SELECTIVITY),
+                };
+                limit.map_or(rows, |limit| rows.min(limit as u64))
+            }
+            PhysicalPlan::Filter { input, condition } => {
+                self.selectivity(condition, source(input), self.estimate(input)?)?
+            }
+            PhysicalPlan::Project { input, .. }
+            | PhysicalPlan::Distinct { input }
+            | PhysicalPlan::Sort { input, .. } => self.estimate(input)?,
+            PhysicalPlan::TopN { input, limit, .. } | PhysicalPlan::Limit { input, limit } => {
+                self.estimate(input)?.min(*limit as u64)
+            }
+            PhysicalPlan::HashAggregate { group_by, .. } if group_by.is_empty() => 1,
+            PhysicalPlan::HashAggregate { input, .. } => {
+                self.estimate(input)?.div_ceil(EQ_SELECTIVITY)
+            }
+            PhysicalPlan::HashJoin { left, right, .. } => {
+                self.estimate(left)?.max(self.estimate(right)?)
+            }
+            PhysicalPlan::IndexJoin { left, .. } => self.estimate(left)?,
+        })
+    }
+
+    pub(crate) fn cost(&self, plan: &PhysicalPlan) -> Result<u64, DbError> {
+        Ok(match plan {
+            PhysicalPlan::SeqScan { table, limit, .. } => {
+                let stats = self.storage.stats(table)?;
+                let pages = leaf_pages(&stats);
+                match limit {
+                    Some(limit) if stats.entries > 0 => (pages
+                        * (*limit as u64).min(stats.entries))
+                    .div_ceil(stats.entries)
+                    .max(1),
+                    _ => pages,
+                }
+            }
+            PhysicalPlan::KeyLookup { table, .. } => depth(&self.storage.stats(table)?),
+            PhysicalPlan::RowCount { .. } => 1,
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                covering,
+                ..
+            } => {
+                let rows = self.estimate(plan)?;
+                let stats = self.storage.stats(table)?;
+                let index = self.storage.index_stats(table, index)?;
+                match covering {
+                    true => index_pages(&index, rows),
+                    false => index_pages(&index, rows) + rows.min(leaf_pages(&stats)),
+                }
+            }
+            PhysicalPlan::Filter { input, .. }
+            | PhysicalPlan::Project { input, .. }
+            | PhysicalPlan::Distinct { input }
+            | PhysicalPlan::Sort { input, .. }
+            | PhysicalPlan::TopN { input, .. }
+            | PhysicalPlan::Limit { input, .. }
+            | PhysicalPlan::HashAggregate { input, .. } => self.cost(input)?,
+            PhysicalPlan::HashJoin { left, right, .. } => self.cost(left)? + self.cost(right)?,
+            PhysicalPlan::IndexJoin {
+                left, table, index, ..
+            } => {
+                let probes = self.estimate(left)?;
+                let pages = match index {
+                    Some(index) => index_pages(&self.storage.index_stats(table, index)?, probes),
+                    None => 0,
+                };
+                let stats = self.storage.stats(table)?;
+                self.cost(left)? + pages + probes.min(leaf_pages(&stats))
+            }
+        })
+    }
+
+    fn selectivity(
+        &self,
+        condition: &Condition,
+        table: Option<&str>,
+        rows: u64,
+    ) -> Result<u64, DbError> {
+        Ok(match condition {
+            Condition::Compare { column, op, value } => {
+                let stats = match table {
+                    Some(table) => self.column_stats(table, &column.name)?,
+                    None => None,
+                };
+                match (op, stats) {
+                    (Operator::Eq, Some(stats)) => rows.div_ceil(stats.distinct.max(1)),
+                    (Operator::Eq, None) => rows.div_ceil(EQ_SELECTIVITY),
+                    (Operator::Ne, _) => rows,
+                    (op, stats) => match stats
+                        .and_then(|stats| stats.range())
+                        .and_then(|range| fraction(*op, value, range))
+                    {
+                        Some(fraction) => scale(rows, fraction),
+                        None => rows.div_ceil(RANGE_SELECTIVITY),
+                    },
+                }
+            }
+            Condition::Call { column, op, .. } => {
+                let stats = match table {
+                    Some(table) => self.column_stats(table, &column.name)?,
+                    None => None,
+                };
+                match (op, stats) {
+                    (Operator::Eq, Some(stats)) => rows.div_ceil(stats.distinct.max(1)),
+                    (Operator::Eq, None) => rows.div_ceil(EQ_SELECTIVITY),
+                    (Operator::Ne, _) => rows,
+                    _ => rows.div_ceil(RANGE_SELECTIVITY),
+                }
+            }
+            Condition::And(left, right) => {
+                let rows = self.selectivity(left, table, rows)?;
+                self.selectivity(right, table, rows)?
+            }
+        })
+    }
+
+    fn column_stats(&self, table: &str, column: &str) -> Result<Option<ColumnStats>, DbError> {
+        let column = Scalar::parse(column).map_or(column, |(_, column)| column);
+        let column = column.rsplit_once('.').map_or(column, |(_, column)| column);
+        Ok(self
+            .storage
+            .statistics()
+            .get(table)?
+            .and_then(|mut stats| stats.columns.remove(column)))
+    }
+
+    fn rewrite(&self, plan: LogicalPlan) -> Result<LogicalPlan, DbError> {
+        Ok(match plan {
+            LogicalPlan::Scan { table } => LogicalPlan::Scan { table },
+            LogicalPlan::Filter { input, predicate } => {
+                let input = self.rewrite(*input)?;
+                self.push_filter(input, predicate)?
+            }
+            LogicalPlan::Project { input, columns } => self.rewrite(*input)?.project(columns),
+            LogicalPlan::Sort { input, keys } => self.rewrite(*input)?.sort(keys),
+            LogicalPlan::Distinct { input } => self.rewrite(*input)?.distinct(),
+            LogicalPlan::Limit { input, limit } => match self.rewrite(*input)? {
+                LogicalPlan::Limit {
+                    input,
+                    limit: inner,
+                } => input.limit(limit.min(inner)),
+                input => input.limit(limit),
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => self.rewrite(*input)?.aggregate(group_by, aggregates),
+            LogicalPlan::Join { left, right, on } => LogicalPlan::Join {
+                left: Box::new(self.rewrite(*left)?),
+                right: Box::new(self.rewrite(*right)?),
+                on,
+            },
+            LogicalPlan::View {
+                input,
+                name,
+                columns,
+            } => LogicalPlan::View {
+                input: Box::new(self.rewrite(*input)?),
+                name,
+                columns,
+            },
+        })
+    }
+
+    fn expand(&self, plan: LogicalPlan) -> Result<LogicalPlan, DbError> {
+        Ok(match plan {
+            LogicalPlan::Scan { table } => match self.view(&table)? {
+                Some(view) => view,
+                None => LogicalPlan::Scan { table },
+            },
+            LogicalPlan::Filter { input, predicate } => self.expand(*input)?.filter(predicate),
+            LogicalPlan::Project { input, columns } => self.expand(*input)?.project(columns),
+            LogicalPlan::Sort { input, keys } => self.expand(*input)?.sort(keys),
+            LogicalPlan::Distinct { input } => self.expand(*input)?.distinct(),
+            LogicalPlan::Limit { input, limit } => self.expand(*input)?.limit(limit),
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => self.expand(*input)?.aggregate(group_by, aggregates),
+            LogicalPlan::Join { left, right, on } => LogicalPlan::Join {
+                left: Box::new(self.expand(*left)?),
+                right: Box::new(self.expand(*right)?),
+                on,
+            },
+            LogicalPlan::View {
+                input,
+                name,
+                columns,
+            } => LogicalPlan::View {
+                input: Box::new(self.expand(*input)?),
+                name,
+                columns,
+            },
+        })
+    }
+
+    fn view(&self, name: &str) -> Result<Option<LogicalPlan>, DbError> {
+        let Some(definition) = self.storage.views().get(name)? else {
+            return Ok(None);
+        };
+        if self.expanding.borrow().iter().any(|view| view == name) {
+            return Err(DbError::InvalidInput(format!(
+                "view '{}' references itself",
+                name
+            )));
+        }
+        self.expanding.borrow_mut().push(name.to_string());
+        let view = self.define(name, &definition);
+        self.expanding.borrow_mut().pop();
+        view.map(Some)
+    }
+
+    fn push_filter(
+        &self,
+        input: LogicalPlan,
+        predicate: Predicate,
+    ) -> Result<LogicalPlan, DbError> {
+        Ok(match input {
+            LogicalPlan::Filter {
+                input,
+                predicate: inner,
+            } => self.push_filter(*input, inner.and(predicate))?,
+            LogicalPlan::Project { input, columns } => {
+                self.push_filter(*input, predicate)?.project(columns)
+            }
+            LogicalPlan::Sort { input, keys } => self.push_filter(*input, predicate)?.sort(keys),
+            LogicalPlan::Join { left, right, on } => {
+                let left_fields = self.fields(&left)?;
+                let right_fields = self.fields(&right)?;
+                let (mut to_left, mut to_right, mut rest) = (None, None, None);
+                for conjunct in predicate.conjuncts() {
+                    let in_left = resolves(&left_fields, &conjunct);
+                    let in_right = resolves(&right_fields, &conjunct);
+                    let target = match (in_left, in_right) {
+                        (true, false) => &mut to_left,
+                        (false, true) => &mut to_right,
+                        _ => &mut rest,
+                    };
+                    *target = Some(match target.take() {
+                        Some(predicate) => Predicate::and(predicate, conjunct),
+                        None => conjunct,
+                    });
+                }
+                let left = match to_left {
+                    Some(predicate) => self.push_filter(*left, predicate)?,
+                    None => *left,
+                };
+                let right = match to_right {
+                    Some(predicate) => self.push_filter(*right, predicate)?,
+                    None => *right,
+                };
+                let join = left.join(right, &on.0, &on.1);
+                match rest {
+                    Some(predicate) => join.filter(predicate),
+                    None => join,
+                }
+            }
+            LogicalPlan::View {
+                input,
+                name,
+                columns,
+            } => {
+                let fields = self.fields(&input)?;
+                match unalias(&view_fields(&name, &columns, &fields), &fields, &predicate) {
+                    Some(renamed) => LogicalPlan::View {
+                        input: Box::new(self.push_filter(*input, renamed)?),
+                        name,
+                        columns,
+                    },
+                    None => LogicalPlan::View {
+                        input,
+                        name,
+                        columns,
+                    }
+                    .filter(predicate),
+                }
+            }
+            input => input.filter(predicate),
+        })
+    }
+
+    fn physical(
+        &self,
+        plan: LogicalPlan,
+        used: Option<&BTreeSet<usize>>,
+    ) -> Result<PhysicalPlan, DbError> {
+        Ok(match plan {
+            LogicalPlan::Scan { table } => PhysicalPlan::SeqScan {
+                columns: self
+                    .fields(&LogicalPlan::scan(&table))?
+                    .iter()
+                    .map(|field| field.name().to_string())
+                    .collect(),
+                table,
+                limit: None,
+                reverse: false,
+            },
+            LogicalPlan::Filter { input, predicate } => {
+                let fields = self.fields(&input)?;
+                if let LogicalPlan::Scan { table } = input.as_ref()
+                    && let Some(plan) = self.access_path(table, &fields, predicate.clone(), used)?
+                {
+                    return Ok(plan);
+                }
+                let condition = bind(&fields, predicate)?;
+                let used = referenced(used, &condition);
+                PhysicalPlan::Filter {
+                    input: Box::new(self.physical(*input, used.as_ref())?),
+                    condition,
+                }
+            }
+            LogicalPlan::Project { input, columns } => {
+                let fields = self.fields(&input)?;
+                let columns = resolve_all(&fields, &columns)?;
+                let used = columns.iter().map(|c| c.index).collect();
+                let input = self.physical(*input, Some(&used))?;
+                PhysicalPlan::Project {
+                    columns,
+                    input: Box::new(input),
+                }
+            }
+            LogicalPlan::Sort { input, keys } => {
+                let fields = self.fields(&input)?;
+                let columns: Vec<String> = keys.iter().map(|key| key.column.clone()).collect();
+                let keys: Vec<SortKey> = resolve_all(&fields, &columns)?
+                    .into_iter()
+                    .zip(keys)
+                    .map(|(column, key)| SortKey {
+                        column,
+                        descending: key.descending,
+                    })
+                    .collect();
+                let used = sorted(used, &keys);
+                let input = self.physical(*input, used.as_ref())?;
+                match self.order(&input, &keys, None, used.as_ref())? {
+                    Some(plan) => plan,
+                    None => PhysicalPlan::Sort {
+                        keys,
+                        input: Box::new(input),
+                    },
+                }
+            }
+            LogicalPlan::Distinct { input } => PhysicalPlan::Distinct {
+                input: Box::new(self.physical(*input, None)?),
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                let fields = self.fields(&input)?;
+                if let (LogicalPlan::Scan { table }, [], [aggregate]) =
+                    (input.as_ref(), group_by.as_slice(), aggregates.as_slice())
+                    && aggregate.function == Function::Count
+                    && aggregate.column.is_none()
+                {
+                    return Ok(PhysicalPlan::RowCount {
+                        table: table.clone(),
+                        aggregate: bind_aggregate(&fields, aggregate)?,
+                    });
+                }
+                let group_by = resolve_all(&fields, &group_by)?;
+                let aggregates = aggregates
+                    .iter()
+                    .map(|aggregate| bind_aggregate(&fields, aggregate))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let used = group_by
+                    .iter()
+                    .chain(aggregates.iter().filter_map(|a| a.column.as_ref()))
+                    .map(|c| c.index)
+                    .collect();
+                let input = self.physical(*input, Some(&used))?;
+                PhysicalPlan::HashAggregate {
+                    group_by,
+                    aggregates,
+                    input: Box::new(input),
+                }
+            }
+            LogicalPlan::Limit { input, limit } => match self.ordered(*input, limit, used)? {
+                PhysicalPlan::Sort { input, keys } => PhysicalPlan::TopN { input, keys, limit },
+                mut input => {
+                    let mut node = &mut input;
+                    while let PhysicalPlan::Project { input, .. } = node {
+                        node = input;
+                    }
+                    if let PhysicalPlan::SeqScan { limit: scan, .. }
+                    | PhysicalPlan::IndexScan { limit: scan, .. } = node
+                    {
+                        *scan = Some(scan.map_or(limit, |scan| scan.min(limit)));
+                    }
+                    PhysicalPlan::Limit {
+                        input: Box::new(input),
+                        limit,
+                    }
+                }
+            },
+            LogicalPlan::Join { left, right, on } => {
+                let left_fields = self.fields(&left)?;
+                let right_fields = self.fields(&right)?;
+                let left_column = resolve(&left_fields, &on.0)?;
+                let right_column = resolve(&right_fields, &on.1)?;
+                let left_type = &left_fields[left_column.index].col_type;
+                let right_type = &right_fields[right_column.index].col_type;
+                if !comparable(left_type, right_type) {
+                    return Err(DbError::InvalidInput(format!(
+                        "cannot join {} on {}",
+                        left_type, right_type
+                    )));
+                }
+                let left_plan = self.physical((*left).clone(), None)?;
+                let right_plan = self.physical((*right).clone(), None)?;
+                let mut candidates = Vec::new();
+                if let Some(inner) = self.inner_lookup(&right, &right_fields, &right_column)? {
+                    candidates.push(PhysicalPlan::IndexJoin {
+                        left: Box::new(left_plan.clone()),
+                        columns: right_fields.iter().map(|f| f.name().to_string()).collect(),
+                        key_type: right_type.clone(),
+                        table: inner.table,
+                        index: inner.index,
+                        on: (left_column.clone(), right_column.clone()),
+                        condition: inner.condition,
+                    });
+                }
+                candidates.push(PhysicalPlan::HashJoin {
+                    left: Box::new(left_plan),
+                    right: Box::new(right_plan.clone()),
+                    on: (left_column.clone(), right_column.clone()),
+                });
+                if let Some(inner) = self.inner_lookup(&left, &left_fields, &left_column)? {
+                    let join = PhysicalPlan::IndexJoin {
+                        left: Box::new(right_plan),
+                        columns: left_fields.iter().map(|f| f.name().to_string()).collect(),
+                        key_type: left_type.clone(),
+                        table: inner.table,
+                        index: inner.index,
+                        on: (right_column, left_column),
+                        condition: inner.condition,
+                    };
+                    let columns = join.columns();
+                    let (right_names, left_names) = columns.split_at(right_fields.len());
+                    candidates.push(PhysicalPlan::Project {
+                        columns: left_names
+                            .iter()
+                            .enumerate()
+                            .map(|(index, name)| (right_names.len() + index, name))
+                            .chain(right_names.iter().enumerate())
+                            .map(|(index, name)| ColumnRef {
+                                index,
+                                name: name.clone(),
+                            })
+                            .collect(),
+                        input: Box::new(join),
+                    });
+                }
+                let costs = candidates
+                    .iter()
+                    .map(|plan| self.cost(plan))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let best = (0..costs.len())
+                    .min_by_key(|&idx| costs[idx])
+                    .unwrap_or_default();
+                candidates.swap_remove(best)
+            }
+            LogicalPlan::View { input, columns, .. } => {
+                let input = self.physical(*input, None)?;
+                if input.columns() == columns {
+                    return Ok(input);
+                }
+                PhysicalPlan::Project {
+                    columns: columns
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, name)| ColumnRef { index, name })
+                        .collect(),
+                    input: Box::new(input),
+                }
+            }
+        })
+    }
+
+    fn ordered(
+        &self,
+        plan: LogicalPlan,
+        limit: usize,
+        used: Option<&BTreeSet<usize>>,
+    ) -> Result<PhysicalPlan, DbError> {
+        let mut plan = self.physical(plan, used)?;
+        let mut used = used.cloned();
+        let mut node = &mut plan;
+        while let PhysicalPlan::Project { input, columns } = node {
+            used = Some(columns.iter().map(|c| c.index).collect());
+            node = input;
+        }
+        if let PhysicalPlan::Sort { input, keys } = node
+            && let Some(ordered) = self.order(
+                input,
+                keys,
+                Some(limit),
+                sorted(used.as_ref(), keys).as_ref(),
+            )?
+        {
+            *node = ordered;
+        }
+        Ok(plan)
+    }
+
+    fn order(
+        &self,
+        plan: &PhysicalPlan,
+        keys: &[SortKey],
+        limit: Option<usize>,
+        used: Option<&BTreeSet<usize>>,
+    ) -> Result<Option<PhysicalPlan>, DbError> {
+        let Some(descending) = keys.first().map(|key| key.descending) else {
+            return Ok(None);
+        };
+        if keys.iter().any(|key| key.descending != descending) {
+            return Ok(None);
+        }
+        Ok(match plan {
+            PhysicalPlan::KeyLookup { .. } => Some(plan.clone()),
+            PhysicalPlan::Filter { input, condition } => {
+                let used = referenced(used, condition);
+                self.order(input, keys, None, used.as_ref())?
+                    .map(|input| PhysicalPlan::Filter {
+                        input: Box::new(input),
+                        condition: condition.clone(),
+                    })
+            }
+            PhysicalPlan::SeqScan {
+                table,
+                columns,
+                limit: scan,
+                ..
+            } => {
+                if keys[0].column.index == 0 {
+                    return Ok(Some(PhysicalPlan::SeqScan {
+                        table: table.clone(),
+                        columns: columns.clone(),
+                        limit: *scan,
+                        reverse: descending,
+                    }));
+                }
+                let cost = self.cost(plan)?;
+                let mut best: Option<(u64, PhysicalPlan)> = None;
+                for (index, terms) in self.storage.indexes(table)? {
+                    if !ordered_by(&terms, 0, keys) {
+                        continue;
+                    }
+                    let plan = PhysicalPlan::IndexScan {
+                        table: table.clone(),
+                        index,
+                        columns: columns.clone(),
+                        prefix: Vec::new(),
+                        column: terms[0].name(&columns[terms[0].column]),
+                        from: Bound::Unbounded,
+                        to: Bound::Unbounded,
+                        limit: limit.or(*scan),
+                        covering: covers(&terms, used),
+                        reverse: descending,
+                    };
+                    let index_cost = self.cost(&plan)?;
+                    if index_cost <= cost
+                        && best.as_ref().is_none_or(|(best, _)| index_cost < *best)
+                    {
+                        best = Some((index_cost, plan));
+                    }
+                }
+                best.map(|(_, plan)| plan)
+            }
+            PhysicalPlan::IndexScan {
+                table,
+                index,
+                prefix,
+                from,
+                to,
+                ..
+            } => {
+                let Some((_, terms)) = self
+                    .storage
+                    .indexes(table)?
+                    .into_iter()
+                    .find(|(name, _)| name == index)
+                else {
+                    return Ok(None);
+                };
+                let point = matches!((from, to), (Bound::Included(from), Bound::Included(to)) if from == to);
+                if !ordered_by(&terms, prefix.len() + point as usize, keys) {
+                    return Ok(None);
+                }
+                let mut plan = plan.clone();
+                if let PhysicalPlan::IndexScan { reverse, .. } = &mut plan {
+                    *reverse = descending;
+                }
+                Some(plan)
+            }
+            _ => None,
+        })
+    }
+
+    fn inner_lookup(
+        &self,
+        plan: &LogicalPlan,
+        fields: &[Field],
+        column: &ColumnRef,
+    ) -> Result<Option<InnerLookup>, DbError> {
+        let (table, predicate) = match plan {
+            LogicalPlan::Scan { table } => (table, None),
+            LogicalPlan::Filter { input, predicate } => match input.as_ref() {
+                LogicalPlan::Scan { table }
+                    if self
+                        .access_path(table, fields, predicate.clone(), None)?
+                        .is_none() =>
+                {
+                    (table, Some(predicate.clone()))
+                }
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        let index = match column.index {
+            0 => None,
+            position => {
+                let indexes = self.storage.indexes(table)?;
+                let Some((index, _)) = indexes
+                    .into_iter()
+                    .find(|(_, terms)| terms.first() == Some(&Term::column(position)))
+                else {
+                    return Ok(None);
+                };
+                Some(index)
+            }
+        };
+        let condition = match predicate {
+            Some(predicate) => Some(bind(fields, predicate)?),
+            None => None,
+        };
+        Ok(Some(InnerLookup {
+            table: table.clone(),
+            index,
+            condition,
+        }))
+    }
+
+    fn access_path(
+        &self,
+        table: &str,
+        fields: &[Field],
+        predicate: Predicate,
+        used: Option<&BTreeSet<usize>>,
+    ) -> Result<Option<PhysicalPlan>, DbError> {
+        let mut conjuncts = predicate.conjuncts();
+        let keys: Vec<Option<(Term, Operator, Col)>> = conjuncts
+            .iter()
+            .map(|conjunct| {
+                let Predicate::Compare { column, op, value } = conjunct else {
+                    return None;
+                };
+                let term = match Scalar::parse(column) {
+                    Some((function, column)) => Term {
+                        column: resolve(fields, column).ok()?.index,
+                        function: Some(function),
+                    },
+                    None => Term::column(resolve(fields, column).ok()?.index),
+                };
+                let value = coerce(&fields[term.column].col_type, value)?;
+                Some((term, *op, value))
+            })
+            .collect();
+        let find = |term: Term, ops: &[Operator]| {
+            keys.iter().position(
+                |key| matches!(key, Some((key, op, _)) if *key == term && ops.contains(op)),
+            )
+        };
+        let name = |term: &Term| term.name(fields[term.column].name());
+        let key = |position: usize| keys[position].clone().map(|(_, op, value)| (op, value));
+        let columns: Vec<String> = fields.iter().map(|f| f.name().to_string()).collect();
+        let indexes = self.storage.indexes(table)?;
+        let lower = [Operator::Gt, Operator::Ge];
+        let upper = [Operator::Lt, Operator::Le];
+        let mut candidates = Vec::new();
+        if let Some(position) = find(Term::column(0), &[Operator::Eq])
+            && let Some((_, key)) = key(position)
+        {
+            let plan = PhysicalPlan::KeyLookup {
+                table: table.to_string(),
+                columns: columns.clone(),
+                key,
+            };
+            candidates.push((plan, vec![position]));
+        }
+        for (index, terms) in indexes.iter() {
+            let mut prefix = Vec::new();
+            let mut used = Vec::new();
+            for term in terms.iter() {
+                let Some(position) = find(*term, &[Operator::Eq]) else {
+                    break;
+                };
+                let Some((_, value)) = key(position) else {
+                    break;
+                };
+                prefix.push((name(term), value));
+                used.push(position);
+            }
+            if let Some((column, value)) = prefix.last().cloned() {
+                let plan = PhysicalPlan::IndexScan {
+                    table: table.to_string(),
+                    index: index.clone(),
+                    columns: columns.clone(),
+                    prefix: prefix[..prefix.len() - 1].to_vec(),
+                    column,
+                    from: Bound::Included(value.clone()),
+                    to: Bound::Included(value),
+                    limit: None,
+                    covering: false,
+                    reverse: false,
+                };
+                candidates.push((plan, used.clone()));
+            }
+            let Some(term) = terms.get(prefix.len()) else {
+                continue;
+            };
+            let (from, to) = (find(*term, &lower), find(*term, &upper));
+            if from.or(to).is_none() {
+                continue;
+            }
+            let bound = |position: Option<usize>| match position.and_then(key) {
+                Some((op, value)) => bound(op, value),
+                None => Bound::Unbounded,
+            };
+            let plan = PhysicalPlan::IndexScan {
+                table: table.to_string(),
+                index: index.clone(),
+                columns: columns.clone(),
+                prefix,
+                column: name(term),
+                from: bound(from),
+                to: bound(to),
+                limit: None,
+                covering: false,
+                reverse: false,
+            };
+            used.extend(from.into_iter().chain(to));
+            candidates.push((plan, used));
+        }
+        if let Some(used) = used {
+            let conditions = conjuncts
+                .iter()
+                .map(|conjunct| bind(fields, conjunct.clone()))
+                .collect::<Result<Vec<_>, _>>()?;
+            for (plan, consumed) in candidates.iter_mut() {
+                let PhysicalPlan::IndexScan {
+                    index, covering, ..
+                } = plan
+                else {
+                    continue;
+                };
+                let Some((_, terms)) = indexes.iter().find(|(name, _)| name == index) else {
+                    continue;
+                };
+                let mut needed = used.clone();
+                for (position, condition) in conditions.iter().enumerate() {
+                    if !consumed.contains(&position) {
+                        condition.references(&mut needed);
+                    }
+                }
+                *covering = covers(terms, Some(&needed));
+            }
+        }
+        let scan = self.cost(&PhysicalPlan::SeqScan {
+            table: table.to_string(),
+            columns,
+            limit: None,
+            reverse: false,
+        })?;
+        let mut best: Option<(u64, PhysicalPlan, Vec<usize>)> = None;
+        for (plan, used) in candidates {
+            let cost = self.cost(&plan)?;
+            if cost <= scan
+                && best.as_ref().is_none_or(|(best, _, consumed)| {
+                    cost < *best || cost == *best && used.len() > consumed.len()
+                })
+            {
+                best = Some((cost, plan, used));
+            }
+        }
+        let Some((_, plan, mut used)) = best else {
+            return Ok(None);
+        };
+        used.sort_unstable();
+        for position in used.into_iter().rev() {
+            conjuncts.remove(position);
+        }
+        let Some(predicate) = conjuncts.into_iter().reduce(Predicate::and) else {
+            return Ok(Some(plan));
+        };
+        Ok(Some(PhysicalPlan::Filter {
+            condition: bind(fields, predicate)?,
+            input: Box::new(plan),
+        }))
+    }
+
+    pub(crate) fn types(&self, plan: &LogicalPlan) -> Result<Vec<ColType>, DbError> {
+        Ok(self
+            .fields(plan)?
+            .into_iter()
+            .map(|field| field.col_type)
+            .collect())
+    }
+
+    fn fields(&self, plan: &LogicalPlan) -> Result<Vec<Field>, DbError> {
+        Ok(match plan {
+            LogicalPlan::Scan { table } => match self.view(table)? {
+                Some(view) => self.fields(&view)?,
+                None => {
+                    let columns = self.storage.get_row_type(table)?.columns;
+                    if columns.is_empty() {
+                        return Err(DbError::TableNotFound(table.clone()));
+                    }
+                    columns
+                        .into_iter()
+                        .map(|col_type| Field {
+                            table: table.clone(),
+                            col_type,
+                        })
+                        .collect()
+                }
+            },
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Distinct { input }
+            | LogicalPlan::Limit { input, .. } => self.fields(input)?,
+            LogicalPlan::Project { input, columns } => {
+                let fields = self.fields(input)?;
+                let mut projected = Vec::with_capacity(columns.len());
+                for column in resolve_all(&fields, columns)? {
+                    let field = &fields[column.index];
+                    projected.push(Field {
+                        table: field.table.clone(),
+                        col_type: field.col_type.clone(),
+                    });
+                }
+                projected
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => {
+                let fields = self.fields(input)?;
+                let mut output = Vec::with_capacity(group_by.len() + aggregates.len());
+                for column in resolve_all(&fields, group_by)? {
+                    let field = &fields[column.index];
+                    output.push(Field {
+                        table: field.table.clone(),
+                        col_type: field.col_type.clone(),
+                    });
+                }
+                for aggregate in aggregates {
+                    let aggregate = bind_aggregate(&fields, aggregate)?;
+                    let name = aggregate.name();
+                    let col_type = match aggregate.col_type {
+                        ColType::Int(_) => ColType::int(&name),
+                        ColType::BigInt(_) => ColType::bigint(&name),
+                        ColType::Varchar(_, size) => ColType::varchar(&name, size),
+                    };
+                    output.push(Field {
+                        table: String::new(),
+                        col_type,
+                    });
+                }
+                output
+            }
+            LogicalPlan::Join { left, right, .. } => {
+                let mut fields = self.fields(left)?;
+                fields.extend(self.fields(right)?);
+                fields
+            }
+            LogicalPlan::View {
+                input,
+                name,
+                columns,
+            } => view_fields(name, columns, &self.fields(input)?),
+        })
+    }
+}
+
+pub(crate) fn compare(left: &Col, right: &Col) -> std::cmp::Ordering {
+    match (left, right) {
+        (Col::Int(left), Col::BigInt(right)) => (*left as i64).cmp(right),
+        (Col::BigInt(left), Col::Int(right)) => left.cmp(&(*right as i64)),
+        (Col::Varchar(left, _), Col::Varchar(right, _)) => left.cmp(right),
+        _ => left.cmp(right),
+    }
+}
+
+pub(crate) fn holds(op: Operator, ordering: std::cmp::Ordering) -> bool {
+    match op {
+        Operator::Eq => ordering.is_eq(),
+        Operator::Ne => ordering.is_ne(),
+        Operator::Lt => ordering.is_lt(),
+        Operator::Le => ordering.is_le(),
+        Operator::Gt => ordering.is_gt(),
+        Operator::Ge => ordering.is_ge(),
+    }
+}
+
+fn bound(op: Operator, value: Col) -> Bound<Col> {
+    match op {
+        Operator::Ge | Operator::Le => Bound::Included(value),
+        _ => Bound::Excluded(value),
+    }
+}
+
+pub(crate) fn coerce(col_type: &ColType, value: &Col) -> Option<Col> {
+    match (col_type, value) {
+        (ColType::Int(_), Col::Int(value)) => Some(Col::Int(*value)),
+        (ColType::Int(_), Col::BigInt(value)) => i32::try_from(*value).ok().map(Col::Int),
+        (ColType::BigInt(_), Col::Int(value)) => Some(Col::BigInt(*value as i64)),
+        (ColType::BigInt(_), Col::BigInt(value)) => Some(Col::BigInt(*value)),
+        (ColType::Varchar(_, size), Col::Varchar(value, _)) => {
+            Some(Col::Varchar(value.clone(), *size))
+        }
+        _ => None,
+    }
+}
+
+fn comparable(left: &ColType, right: &ColType) -> bool {
+    matches!(
+        (left, right),
+        (
+            ColType::Int(_) | ColType::BigInt(_),
+            ColType::Int(_) | ColType::BigInt(_)
+        ) | (ColType::Varchar(_, _), ColType::Varchar(_, _))
+    )
+}
+
+fn write_reverse(f: &mut fmt::Formatter<'_>, reverse: bool) -> fmt::Result {
+    match reverse {
+        true => write!(f, " reverse"),
+        false => Ok(()),
+    }
+}
+
+fn write_limit(f: &mut fmt::Formatter<'_>, limit: Option<usize>) -> fmt::Result {
+    match limit {
+        Some(limit) => write!(f, " limit {}", limit),
+        None => Ok(()),
+    }
+}
+
+fn source(plan: &PhysicalPlan) -> Option<&str> {
+    match plan {
+        PhysicalPlan::SeqScan { table, .. } | PhysicalPlan::IndexScan { table, .. } => Some(table),
+        PhysicalPlan::Filter { input, .. } => source(input),
+        _ => None,
+    }
+}
+
+fn fraction(op: Operator, value: &Col, (min, max): (i64, i64)) -> Option<f64> {
+    let value = match value {
+        Col::Int(value) => *value as f64,
+        Col::BigInt(value) => *value as f64,
+        Col::Varchar(..) => return None,
+    };
+    let width = max as f64 - min as f64 + 1.0;
+    let below = (value - min as f64).clamp(0.0, width);
+    let above = (max as f64 - value).clamp(0.0, width);
+    let covered = match op {
+        Operator::Lt => below,
+        Operator::Le => below + 1.0,
+        Operator::Gt => above,
+        Operator::Ge => above + 1.0,
+        Operator::Eq | Operator::Ne => return None,
+    };
+    Some((covered / width).clamp(0.0, 1.0))
+}
+
+fn referenced(used: Option<&BTreeSet<usize>>, condition: &Condition) -> Option<BTreeSet<usize>> {
+    let mut used = used?.clone();
+    condition.references(&mut used);
+    Some(used)
+}
+
+fn sorted(used: Option<&BTreeSet<usize>>, keys: &[SortKey]) -> Option<BTreeSet<usize>> {
+    let mut used = used?.clone();
+    used.extend(keys.iter().map(|key| key.column.index));
+    Some(used)
+}
+
+fn covers(terms: &[Term], used: Option<&BTreeSet<usize>>) -> bool {
+    used.is_some_and(|used| {
+        used.iter()
+            .all(|&column| column == 0 || terms.contains(&Term::column(column)))
+    })
+}
+
+fn leaf_pages(stats: &Stats) -> u64 {
+    (stats.leaf_pages as u64).max(1)
+}
+
+fn depth(stats: &Stats) -> u64 {
+    (stats.depth as u64).max(1)
+}
+
+fn index_pages(stats: &Stats, rows: u64) -> u64 {
+    let leaves = (rows * leaf_pages(stats)).div_ceil(stats.entries.max(1));
+    leaves.max(1) + depth(stats) - 1
+}
+
+fn scale(rows: u64, fraction: f64) -> u64 {
+    (rows as f64 * fraction.max(0.0)).ceil() as u64
+}
+
+fn names(columns: &[ColumnRef]) -> String {
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    names.join(", ")
+}
+
+fn ordered_by(terms: &[Term], fixed: usize, keys: &[SortKey]) -> bool {
+    let mut order = terms[fixed..].iter().copied().chain([Term::column(0)]);
+    for key in keys {
+        let term = Term::column(key.column.index);
+        if terms[..fixed].contains(&term) {
+            continue;
+        }
+        match order.next() {
+            Some(next) if next == term && term.column == 0 => return true,
+            Some(next) if next == term => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn sort_keys(keys: &[SortKey]) -> String {
+    let keys: Vec<String> = keys
+        .iter()
+        .map(|key| match key.descending {
+            true => format!("{} DESC", key.column.name),
+            false => key.column.name.clone(),
+        })
+        .collect();
+    keys.join(", ")
+}
+
+fn bind(fields: &[Field], predicate: Predicate) -> Result<Condition, DbError> {
+    Ok(match predicate {
+        Predicate::Compare { column, op, value } => match Scalar::parse(&column) {
+            Some((function, name)) => {
+                let column = resolve(fields, name)?;
+                let col_type = &fields[column.index].col_type;
+                if !matches!((col_type, &value), (ColType::Varchar(..), Col::Varchar(..))) {
+                    return Err(DbError::InvalidInput(format!(
+                        "cannot apply {} to {}",
+                        function, col_type
+                    )));
+                }
+                Condition::Call {
+                    function,
+                    column,
+                    op,
+                    value,
+                }
+            }
+            None => {
+                let column = resolve(fields, &column)?;
+                let col_type = &fields[column.index].col_type;
+                let literal = match &value {
+                    Col::Int(_) => ColType::int(""),
+                    Col::BigInt(_) => ColType::bigint(""),
+                    Col::Varchar(_, size) => ColType::varchar("", *size),
+                };
+                if !comparable(col_type, &literal) {
+                    return Err(DbError::InvalidInput(format!(
+                        "cannot compare {} with {:?}",
+                        col_type, value
+                    )));
+                }
+                Condition::Compare { column, op, value }
+            }
+        },
+        Predicate::And(left, right) => Condition::And(
+            Box::new(bind(fields, *left)?),
+            Box::new(bind(fields, *right)?),
+        ),
+    })
+}
+
+fn bind_aggregate(fields: &[Field], aggregate: &Aggregate) -> Result<AggregateRef, DbError> {
+    let Some(name) = &aggregate.column else {
+        return Ok(AggregateRef {
+            function: aggregate.function,
+            column: None,
+            col_type: ColType::bigint(""),
+        });
+    };
+    let column = resolve(fields, name)?;
+    let input = &fields[column.index].col_type;
+    let col_type = match (aggregate.function, input) {
+        (Function::Count, _) => ColType::bigint(""),
+        (Function::Min | Function::Max, _) => input.clone(),
+        (Function::Sum | Function::Avg, ColType::Int(_) | ColType::BigInt(_)) => {
+            ColType::bigint("")
+        }
+        (function, _) => {
+            return Err(DbError::InvalidInput(format!(
+                "cannot apply {} to {}",
+                function, input
+            )));
+        }
+    };
+    Ok(AggregateRef {
+        function: aggregate.function,
+        column: Some(column),
+        col_type,
+    })
+}
+
+fn view_fields(name: &str, columns: &[String], fields: &[Field]) -> Vec<Field> {
+    columns
+        .iter()
+        .zip(fields)
+        .map(|(column, field)| Field {
+            table: name.to_string(),
+            col_type: match &field.col_type {
+                ColType::Int(_) => ColType::int(column),
+                ColType::BigInt(_) => ColType::bigint(column),
+                ColType::Varchar(_, size) => ColType::varchar(column, *size),
+            },
+        })
+        .collect()
+}
+
+fn unalias(view: &[Field], fields: &[Field], predicate: &Predicate) -> Option<Predicate> {
+    Some(match predicate {
+        Predicate::Compare { column, op, value } => {
+            let index = resolve(view, column).ok()?.index;
+            let field = &fields[index];
+            let column = match field.table.is_empty() {
+                true => field.name().to_string(),
+                false => format!("{}.{}", field.table, field.name()),
+            };
+            if resolve(fields, &column).ok()?.index != index {
+                return None;
+            }
+            Predicate::compare(&column, *op, value.clone())
+        }
+        Predicate::And(left, right) => {
+            Predicate::and(unalias(view, fields, left)?, unalias(view, fields, right)?)
+        }
+    })
+}
+
+fn resolves(fields: &[Field], predicate: &Predicate) -> bool {
+    predicate
+        .columns()
+        .iter()
+        .all(|column| resolve(fields, column).is_ok())
+}
+
+fn resolve_all(fields: &[Field], columns: &[String]) -> Result<Vec<ColumnRef>, DbError> {
+    columns
+        .iter()
+        .map(|column| resolve(fields, column))
+        .collect()
+}
+
+fn unqualify<'a>(column: &'a str, table: &str) -> &'a str {
+    [table, database::split(table).1]
+        .into_iter()
+        .find_map(|table| column.strip_prefix(table)?.strip_prefix('.'))
+        .unwrap_or(column)
+}
+
+fn resolve(fields: &[Field], column: &str) -> Result<ColumnRef, DbError> {
+    let (table, name) = match column.rsplit_once('.') {
+        Some((table, name)) if Aggregate::parse(column).is_none() => (Some(table), name),
+        _ => (None, column),
+    };
+    let mut found = fields.iter().enumerate().filter(|(_, field)| {
+        field.name() == name
+            && table.is_none_or(|table| {
+                field.table == table || database::split(&field.table).1 == table
+            })
+    });
+    match (found.next(), found.next()) {
+        (Some((index, _)), None) => Ok(ColumnRef {
+            index,
+            name: column.to_string(),
+        }),
+        (Some(_), Some(_)) => Err(DbError::InvalidInput(format!(
+            "column '{}' is ambiguous",
+            column
+        ))),
+        (None, _) => {
+            let relation = match table {
+                Some(table) => table.to_string(),
+                None => {
+                    let mut tables: Vec<&str> = fields.iter().map(|f| f.table.as_str()).collect();
+                    tables.dedup();
+                    tables.join(", ")
+                }
+            };
+            Err(DbError::field_not_found(column, &relation))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use row::{Row, RowType};
+
+    use super::*;
+
+    fn storage() -> Storage {
+        let storage = Storage::in_memory();
+        let users = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 16)],
+            constraints: vec![],
+        };
+        storage.create("users", users).unwrap();
+        let orders = RowType {
+            columns: vec![ColType::int("id"), ColType::int("user_id")],
+            constraints: vec![],
+        };
+        storage.create("orders", orders).unwrap();
+        storage
+    }
+
+    fn columns(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_filters_and_limits() {
+        let storage = storage();
+        let planner = Planner::new(&storage);
+        let plan = LogicalPlan::scan("users")
+            .project(columns(&["name"]))
+            .filter(Predicate::compare("id", Operator::Gt, Col::int(1)))
+            .filter(Predicate::compare("id", Operator::Lt, Col::int(9)))
+            .limit(10)
+            .limit(5);
+        let expected = LogicalPlan::scan("users")
+            .filter(
+                Predicate::compare("id", Operator::Gt, Col::int(1)).and(Predicate::compare(
+                    "id",
+                    Operator::Lt,
+                    Col::int(9),
+                )),
+            )
+            .project(columns(&["name"]))
+            .limit(5);
+        assert_eq!(expected, planner.rewrite(plan).unwrap());
+    }
+
+    #[test]
+    fn push_filter_into_join() {
+        let storage = storage();
+        let planner = Planner::new(&storage);
+        let plan = LogicalPlan::scan("users")
+            .join(LogicalPlan::scan("orders"), "users.id", "user_id")
+            .filter(
+                Predicate::compare("name", Operator::Eq, Col::varchar("bob", 3))
+                    .and(Predicate::compare("orders.id", Operator::Ge, Col::int(2)))
+                    .and(Predicate::compare(
+                        "users.id",
+                        Operator::Ne,
+                        Col::big_int(4),
+                    )),
+            )
+            .sort(vec![OrderBy::asc("orders.id")])
+            .limit(3);
+        let plan = planner.plan(plan).unwrap();
+        assert_eq!(
+            "TopN 3 [orders.id]\n\
+             \x20 Project [id, name, id, user_id]\n\
+             \x20   IndexJoin user_id = users.id on users using primary key \
+             where name = 'bob' AND users.id <> 4\n\
+             \x20     Filter orders.id >= 2\n\
+             \x20       SeqScan orders\n",
+            plan.to_string()
+        );
+        assert_eq!(columns(&["id", "name", "id", "user_id"]), plan.columns());
+    }
+
+    #[test]
+    fn index_join_on_primary_key() {
+        let storage = storage();
+        let planner = Planner::new(&storage);
+        let plan =
+            LogicalPlan::scan("orders").join(LogicalPlan::scan("users"), "user_id", "users.id");
+        let plan = planner.plan(plan).unwrap();
+        assert_eq!(
+            "IndexJoin user_id = users.id on users using primary key\n\
+             \x20 SeqScan orders\n",
+            plan.to_string()
+        );
+        assert_eq!(columns(&["id", "user_id", "id", "name"]), plan.columns());
+        assert_eq!(
+            BTreeSet::from(["orders".to_string(), "users".to_string()]),
+            plan.tables()
+        );
+
+        let plan = LogicalPlan::scan("orders")
+            .join(LogicalPlan::scan("users"), "user_id", "users.id")
+            .filter(Predicate::compare("users.id", Operator::Eq, Col::int(1)));
+        assert_eq!(
+            "HashJoin user_id = users.id\n\
+             \x20 SeqScan orders\n\
+             \x20 KeyLookup users id = 1\n",
+            planner.plan(plan).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn primary_key_lookup() {
+        let storage = storage();
+        let planner = Planner::new(&storage);
+        let plan = LogicalPlan::scan("users")
+            .filter(Predicate::compare(
+                "name",
+                Operator::Ne,
+                Col::varchar("bob", 3),
+            ))
+            .filter(Predicate::compare("id", Operator::Eq, Col::big_int(5)))
+            .project(columns(&["name"]));
+        let plan = planner.plan(plan).unwrap();
+        assert_eq!(
+            "Project [name]\n\
+             \x20 Filter name <> 'bob'\n\
+             \x20   KeyLookup users id = 5\n",
+            plan.to_string()
+        );
+        let PhysicalPlan::Project { input, .. } = plan else {
+            panic!("expected projection");
+        };
+        let PhysicalPlan::Filter { input, .. } = *input else {
+            panic!("expected filter");
+        };
+        assert_eq!(
+            PhysicalPlan::KeyLookup {
+                table: "users".to_string(),
+                columns: columns(&["id", "name"]),
+                key: Col::int(5),
+            },
+            *input
+        );
+
+        let plan = LogicalPlan::scan("users").filter(Predicate::compare(
+            "id",
+            Operator::Eq,
+            Col::big_int(i64::MAX),
+        ));
+        assert_eq!(
+            "Filter id = 9223372036854775807\n\
+             \x20 SeqScan users\n",
+            planner.plan(plan).unwrap().to_string()
+        );
+        let plan = LogicalPlan::scan("orders").filter(Predicate::compare(
+            "user_id",
+            Operator::Eq,
+            Col::int(1),
+        ));
+        assert_eq!(
+            "Filter user_id = 1\n\
+             \x20 SeqScan orders\n",
+            planner.plan(plan).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn invalid_plans() {
+        let storage = storage();
+        let planner = Planner::new(&storage);
+        let plan = LogicalPlan::scan("users").join(LogicalPlan::scan("orders"), "id", "id");
+        assert_eq!(
+            Err(DbError::invalid_input("column 'id' is ambiguous")),
+            planner.plan(plan.project(columns(&["id"])))
+        );
+        let plan = LogicalPlan::scan("users").project(columns(&["age"]));
+        assert_eq!(
+            Err(DbError::field_not_found("age", "users")),
+            planner.plan(plan)
+        );
+        let err = planner
+            .plan(LogicalPlan::scan("missing").project(columns(&["id"])))
+            .unwrap_err();
+        assert_eq!(DbError::TableNotFound("missing".to_string()), err);
+        assert_eq!("relation 'missing' doesn't exist", err.to_string());
+        let plan = LogicalPlan::scan("users").filter(Predicate::compare(
+            "name",
+            Operator::Eq,
+            Col::int(1),
+        ));
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "cannot compare name VARCHAR(16) with Int(1)"
+            )),
+            planner.plan(plan)
+        );
+    }
+
+    #[test]
+    fn condition_matches() {
+        let row = Row {
+            columns: vec![Col::int(3), Col::varchar("bob", 16)],
+        };
+        let id = ColumnRef {
+            index: 0,
+            name: "id".to_string(),
+        };
+        let condition = Condition::Compare {
+            column: id.clone(),
+            op: Operator::Le,
+            value: Col::big_int(3),
+        };
+        assert!(condition.matches(&row.columns));
+        let condition = Condition::And(
+            Box::new(condition),
+            Box::new(Condition::Compare {
+                column: ColumnRef {
+                    index: 1,
+                    name: "name".to_string(),
+                },
+                op: Operator::Eq,
+                value: Col::varchar("bob", 3),
+            }),
+        );
+        assert!(condition.matches(&row.columns));
+        let condition = Condition::Compare {
+            column: id,
+            op: Operator::Gt,
+            value: Col::int(3),
+        };
+        assert!(!condition.matches(&row.columns));
+    }
+}
@@ -0,0 +1,105 @@
+use common::error::DbError;
+use parser::{Command, Prepared};
+use row::{Col, ColType};
+
+use crate::{
+    Cursor, Engine, coerce,
+    database::{self, Namespace},
+    exec_result::ExecResult,
+};
+
+pub struct PreparedStatement<'a> {
+    engine: &'a Engine,
+    statement: Statement,
+}
+
+pub(crate) struct Statement {
+    prepared: Prepared,
+    targets: Vec<Option<(String, ColType)>>,
+}
+
+impl<'a> PreparedStatement<'a> {
+    pub(crate) fn new(engine: &'a Engine, sql: &str) -> Result<Self, DbError> {
+        let namespace = engine.lock_namespace()?.clone();
+        Ok(Self {
+            engine,
+            statement: Statement::new(engine, &namespace, sql)?,
+        })
+    }
+
+    pub fn parameters(&self) -> usize {
+        self.statement.parameters()
+    }
+
+    pub fn execute(&self, params: &[Col]) -> Result<ExecResult, DbError> {
+        self.engine.execute(self.statement.bind(params)?)
+    }
+
+    pub fn query(&self, params: &[Col]) -> Result<Cursor, DbError> {
+        self.engine.query(self.statement.bind(params)?)
+    }
+}
+
+impl Statement {
+    pub(crate) fn new(engine: &Engine, namespace: &Namespace, sql: &str) -> Result<Self, DbError> {
+        let prepared = parser::prepare(sql)?;
+        let command = database::qualify(prepared.command().clone(), namespace);
+        let columns = prepared.columns();
+        let targets = match target(&command) {
+            Some(table) if engine.storage.views().get(table)?.is_none() => {
+                let row_type = engine.storage.get_row_type(table)?;
+                columns
+                    .into_iter()
+                    .map(|column| {
+                        let column = column?;
+                        let name = column.rsplit('.').next().unwrap_or(&column);
+                        let col_type = row_type
+                            .columns
+                            .iter()
+                            .find(|col_type| col_type.get_name() == name)?;
+                        Some((table.to_string(), col_type.clone()))
+                    })
+                    .collect()
+            }
+            _ => vec![None; columns.len()],
+        };
+        Ok(Self { prepared, targets })
+    }
+
+    pub(crate) fn parameters(&self) -> usize {
+        self.prepared.parameters()
+    }
+
+    pub(crate) fn bind(&self, params: &[Col]) -> Result<Command, DbError> {
+        if params.len() != self.targets.len() {
+            return Err(DbError::InvalidInput(format!(
+                "expected {} parameters, got {}",
+                self.targets.len(),
+                params.len()
+            )));
+        }
+        let values = params
+            .iter()
+            .zip(self.targets.iter())
+            .map(|(param, target)| match target {
+                Some((table, col_type)) => {
+                    coerce::convert(param.clone(), table, col_type).map(|value| value.to_string())
+                }
+                None => Ok(param.to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.prepared.bind(&values)
+    }
+}
+
+fn target(command: &Command) -> Option<&str> {
+    match command {
+        Command::Insert { table, .. }
+        | Command::Select { table, .. }
+        | Command::Update { table, .. } => Some(table),
+        Command::Explain { command, .. } | Command::CopyTo { query: command, .. } => {
+            target(command)
+        }
+        _ => None,
+    }
+}
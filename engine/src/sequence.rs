@@ -0,0 +1,251 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use btree::BTree;
+use common::error::DbError;
+use row::{Col, ColType, Row, RowType};
+
+use crate::storage;
+
+const NAME_SIZE: u16 = 255;
+const RESERVE: i64 = 32;
+
+pub(crate) struct Sequences {
+    path: Option<PathBuf>,
+    read_only: bool,
+    catalog: Mutex<Option<Catalog>>,
+}
+
+struct Catalog {
+    btree: BTree,
+    counters: HashMap<String, Counter>,
+}
+
+struct Counter {
+    next: i64,
+    reserved: i64,
+}
+
+impl Sequences {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            read_only: false,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn read_only(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            read_only: true,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn create(&self, name: &str, start: i64) -> Result<(), DbError> {
+        self.with(|catalog| {
+            if catalog.btree.search(key(name)?)?.is_some() {
+                return Err(DbError::InvalidInput(format!(
+                    "sequence '{}' already exists",
+                    name
+                )));
+            }
+            catalog.store(name, start)
+        })
+    }
+
+    pub(crate) fn drop(&self, name: &str) -> Result<(), DbError> {
+        self.with(|catalog| {
+            catalog.counters.remove(name);
+            if catalog.btree.delete(key(name)?)?.is_none() {
+                return Err(not_found(name));
+            }
+            Ok(())
+        })
+    }
+
+    pub(crate) fn rename(&self, name: &str, to: &str) -> Result<(), DbError> {
+        self.with(|catalog| {
+            if catalog.btree.search(key(to)?)?.is_some() {
+                return Err(DbError::InvalidInput(format!(
+                    "sequence '{}' already exists",
+                    to
+                )));
+            }
+            let next = catalog.counter(name)?.next;
+            catalog.counters.remove(name);
+            catalog.btree.delete(key(name)?)?;
+            catalog.store(to, next)
+        })
+    }
+
+    pub(crate) fn exists(&self, name: &str) -> Result<bool, DbError> {
+        self.with(|catalog| Ok(catalog.btree.search(key(name)?)?.is_some()))
+    }
+
+    pub(crate) fn next(&self, name: &str) -> Result<i64, DbError> {
+        self.with(|catalog| {
+            let counter = catalog.counter(name)?;
+            let value = counter.next;
+            if value < counter.reserved {
+                counter.next += 1;
+                return Ok(value);
+            }
+            let reserved = value.checked_add(RESERVE).ok_or_else(|| {
+                DbError::InvalidInput(format!("sequence '{}' is exhausted", name))
+            })?;
+            catalog.store(name, reserved)?;
+            let counter = catalog.counter(name)?;
+            counter.next = value + 1;
+            counter.reserved = reserved;
+            Ok(value)
+        })
+    }
+
+    pub(crate) fn advance(&self, name: &str, value: i64) -> Result<(), DbError> {
+        self.with(|catalog| {
+            let counter = catalog.counter(name)?;
+            if value < counter.next {
+                return Ok(());
+            }
+            counter.next = value.saturating_add(1);
+            if counter.next <= counter.reserved {
+                return Ok(());
+            }
+            let next = counter.next;
+            catalog.store(name, next)?;
+            catalog.counter(name)?.reserved = next;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn list(&self) -> Result<Vec<(String, i64)>, DbError> {
+        self.with(|catalog| {
+            let names: Vec<String> = catalog
+                .btree
+                .select_all()?
+                .into_iter()
+                .map(|row| row.columns[0].to_string())
+                .collect();
+            let mut sequences = Vec::with_capacity(names.len());
+            for name in names {
+                let next = catalog.counter(&name)?.next;
+                sequences.push((name, next));
+            }
+            Ok(sequences)
+        })
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Catalog) -> Result<T, DbError>) -> Result<T, DbError> {
+        let mut catalog = self
+            .catalog
+            .lock()
+            .map_err(|_| DbError::unexpected("sequences lock is poisoned"))?;
+        if catalog.is_none() {
+            *catalog = Some(Catalog::open(self.path.as_deref(), self.read_only)?);
+        }
+        match catalog.as_mut() {
+            Some(catalog) => f(catalog),
+            None => Err(DbError::unexpected("sequence catalog is not open")),
+        }
+    }
+}
+
+impl Catalog {
+    fn open(path: Option<&Path>, read_only: bool) -> Result<Self, DbError> {
+        let mut btree = storage::open_catalog(path, read_only)?;
+        if btree.get_structure()?.columns.is_empty() {
+            btree.set_structure(RowType {
+                columns: vec![
+                    ColType::varchar("name", NAME_SIZE),
+                    ColType::bigint("value"),
+                ],
+                constraints: vec![],
+            })?;
+        }
+        Ok(Self {
+            btree,
+            counters: HashMap::new(),
+        })
+    }
+
+    fn counter(&mut self, name: &str) -> Result<&mut Counter, DbError> {
+        match self.counters.entry(name.to_string()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let Some(row) = self.btree.search(key(name)?)? else {
+                    return Err(not_found(name));
+                };
+                let Col::BigInt(next) = row.columns[1] else {
+                    return Err(DbError::Encoding);
+                };
+                Ok(entry.insert(Counter {
+                    next,
+                    reserved: next,
+                }))
+            }
+        }
+    }
+
+    fn store(&mut self, name: &str, value: i64) -> Result<(), DbError> {
+        let key = key(name)?;
+        let row = Row {
+            columns: vec![key.clone(), Col::BigInt(value)],
+        };
+        self.btree.insert(key, row)
+    }
+}
+
+fn key(name: &str) -> Result<Col, DbError> {
+    if name.len() > NAME_SIZE as usize {
+        return Err(DbError::TooLong(
+            "name".to_string(),
+            "sequences".to_string(),
+            name.len(),
+            NAME_SIZE as usize,
+        ));
+    }
+    Ok(Col::varchar(name, NAME_SIZE))
+}
+
+fn not_found(name: &str) -> DbError {
+    DbError::InvalidInput(format!("sequence '{}' doesn't exist", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("catalog.seq");
+        let sequences = Sequences::new(Some(path.clone()));
+        sequences.create("ids", 1).unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input("sequence 'ids' already exists")),
+            sequences.create("ids", 1)
+        );
+        for expected in 1..=40 {
+            assert_eq!(expected, sequences.next("ids").unwrap());
+        }
+        sequences.advance("ids", 100).unwrap();
+        assert_eq!(101, sequences.next("ids").unwrap());
+        sequences.advance("ids", 5).unwrap();
+        assert_eq!(vec![("ids".to_string(), 102)], sequences.list().unwrap());
+        drop(sequences);
+
+        let sequences = Sequences::new(Some(path.clone()));
+        let next = sequences.next("ids").unwrap();
+        assert!((102..102 + RESERVE).contains(&next));
+        sequences.drop("ids").unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input("sequence 'ids' doesn't exist")),
+            sequences.next("ids")
+        );
+    }
+}
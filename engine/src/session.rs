@@ -0,0 +1,107 @@
+use std::{collections::HashMap, sync::Arc};
+
+use common::error::DbError;
+use parser::Command;
+use row::Col;
+
+use crate::{
+    Cursor, Engine, database::Namespace, exec_result::ExecResult, prepared::Statement,
+    transaction::Transaction,
+};
+
+pub struct Session {
+    id: u64,
+    engine: Arc<Engine>,
+    transaction: Option<Transaction>,
+    namespace: Namespace,
+    prepared: HashMap<String, Statement>,
+}
+
+impl Session {
+    pub(crate) fn new(engine: Arc<Engine>) -> Self {
+        Self {
+            id: engine.next_owner(),
+            engine,
+            transaction: None,
+            namespace: Namespace::default(),
+            prepared: HashMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn execute(&mut self, command: Command) -> Result<ExecResult, DbError> {
+        let token = self.engine.start_query(self.id)?;
+        let result = self.engine.execute_in(
+            &mut self.transaction,
+            &mut self.namespace,
+            &token,
+            self.id,
+            command,
+        );
+        self.engine.finish_query(self.id)?;
+        result
+    }
+
+    pub fn query(&mut self, command: Command) -> Result<Cursor, DbError> {
+        let token = self.engine.start_query(self.id)?;
+        let result = self.engine.query_in(
+            &mut self.transaction,
+            &mut self.namespace,
+            &token,
+            self.id,
+            command,
+        );
+        self.engine.finish_query(self.id)?;
+        result
+    }
+
+    pub fn prepare(&mut self, name: &str, sql: &str) -> Result<usize, DbError> {
+        if self.prepared.contains_key(name) {
+            return Err(DbError::InvalidInput(format!(
+                "prepared statement '{}' already exists",
+                name
+            )));
+        }
+        let statement = Statement::new(&self.engine, &self.namespace, sql)?;
+        let parameters = statement.parameters();
+        self.prepared.insert(name.to_string(), statement);
+        Ok(parameters)
+    }
+
+    pub fn execute_prepared(&mut self, name: &str, params: &[Col]) -> Result<ExecResult, DbError> {
+        let command = self.statement(name)?.bind(params)?;
+        self.execute(command)
+    }
+
+    pub fn query_prepared(&mut self, name: &str, params: &[Col]) -> Result<Cursor, DbError> {
+        let command = self.statement(name)?.bind(params)?;
+        self.query(command)
+    }
+
+    pub fn deallocate(&mut self, name: &str) -> Result<(), DbError> {
+        self.prepared
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| not_prepared(name))
+    }
+
+    fn statement(&self, name: &str) -> Result<&Statement, DbError> {
+        self.prepared.get(name).ok_or_else(|| not_prepared(name))
+    }
+}
+
+fn not_prepared(name: &str) -> DbError {
+    DbError::InvalidInput(format!("prepared statement '{}' doesn't exist", name))
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(transaction) = self.transaction.take() {
+            self.engine.locks.release(transaction.id());
+        }
+        let _ = self.engine.drop_temporary(&self.namespace);
+    }
+}
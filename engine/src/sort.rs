@@ -0,0 +1,171 @@
+use std::{cmp::Ordering, collections::BinaryHeap, mem, sync::Arc};
+
+use common::error::DbError;
+use row::Col;
+
+use crate::{
+    executor::Rows,
+    plan::{SortKey, compare},
+    spill::{Budget, footprint},
+};
+
+pub(crate) fn sort(rows: Rows, keys: Vec<SortKey>, budget: &Budget) -> Result<Rows, DbError> {
+    let keys: Arc<[SortKey]> = keys.into();
+    let mut runs: Vec<Rows> = Vec::new();
+    let mut buffer = Vec::new();
+    let mut reservation = budget.reserve();
+    for row in rows {
+        let row = row?;
+        reservation.grow(footprint(&row))?;
+        buffer.push(row);
+        if reservation.bytes() >= budget.limit() {
+            buffer.sort_by(|a, b| compare_rows(&keys, a, b));
+            let mut spill = budget.spill()?;
+            for row in mem::take(&mut buffer) {
+                spill.write(&row)?;
+            }
+            runs.push(spill.finish()?);
+            reservation.release();
+        }
+    }
+    buffer.sort_by(|a, b| compare_rows(&keys, a, b));
+    let buffer = reservation.hold(Box::new(buffer.into_iter().map(Ok)));
+    if runs.is_empty() {
+        return Ok(buffer);
+    }
+    runs.push(buffer);
+    Ok(Box::new(Merge::new(runs, keys)?))
+}
+
+pub(crate) fn compare_rows(keys: &[SortKey], a: &[Col], b: &[Col]) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = compare(&a[key.column.index], &b[key.column.index]);
+            match key.descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+struct Head {
+    row: Vec<Col>,
+    run: usize,
+    keys: Arc<[SortKey]>,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_rows(&self.keys, &other.row, &self.row).then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+struct Merge {
+    runs: Vec<Rows>,
+    heads: BinaryHeap<Head>,
+    keys: Arc<[SortKey]>,
+}
+
+impl Merge {
+    fn new(runs: Vec<Rows>, keys: Arc<[SortKey]>) -> Result<Self, DbError> {
+        let mut merge = Self {
+            heads: BinaryHeap::with_capacity(runs.len()),
+            runs,
+            keys,
+        };
+        for run in 0..merge.runs.len() {
+            merge.advance(run)?;
+        }
+        Ok(merge)
+    }
+
+    fn advance(&mut self, run: usize) -> Result<(), DbError> {
+        if let Some(row) = self.runs[run].next().transpose()? {
+            self.heads.push(Head {
+                row,
+                run,
+                keys: self.keys.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<Vec<Col>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.heads.pop()?;
+        if let Err(err) = self.advance(head.run) {
+            self.heads.clear();
+            return Some(Err(err));
+        }
+        Some(Ok(head.row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::ColumnRef;
+
+    #[test]
+    fn external() {
+        let keys = vec![
+            SortKey {
+                column: ColumnRef {
+                    index: 1,
+                    name: "age".to_string(),
+                },
+                descending: true,
+            },
+            SortKey {
+                column: ColumnRef {
+                    index: 0,
+                    name: "id".to_string(),
+                },
+                descending: false,
+            },
+        ];
+        let rows: Vec<Vec<Col>> = (0..1000)
+            .map(|i| {
+                vec![
+                    Col::int(i),
+                    Col::int((i * 7919) % 100),
+                    Col::varchar("x", 4),
+                ]
+            })
+            .collect();
+        let budget = Budget::new(100 * footprint(&rows[0]), None);
+        let input: Rows = Box::new(rows.clone().into_iter().map(Ok));
+        let sorted = sort(input, keys.clone(), &budget)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut expected = rows.clone();
+        expected.sort_by(|a, b| compare_rows(&keys, a, b));
+        assert_eq!(expected, sorted);
+        assert_eq!(vec![Col::int(21), Col::int(99)], sorted[0][..2]);
+
+        let input: Rows = Box::new(rows.into_iter().map(Ok));
+        let in_memory = sort(input, keys, &Budget::new(usize::MAX, None)).unwrap();
+        assert_eq!(expected, in_memory.collect::<Result<Vec<_>, _>>().unwrap());
+    }
+}
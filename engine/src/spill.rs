@@ -0,0 +1,270 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    mem,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use common::error::DbError;
+use row::Col;
+
+use crate::executor::Rows;
+
+const COUNT_SIZE: usize = 2;
+const LEN_SIZE: usize = 4;
+const PARTITIONS: usize = 16;
+pub(crate) const MAX_DEPTH: u32 = 4;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Budget {
+    limit: usize,
+    cap: usize,
+    used: Arc<AtomicUsize>,
+    dir: Option<Arc<Path>>,
+}
+
+impl Budget {
+    pub(crate) fn new(limit: usize, dir: Option<&Path>) -> Self {
+        Self {
+            limit,
+            cap: usize::MAX,
+            used: Arc::default(),
+            dir: dir.map(Arc::from),
+        }
+    }
+
+    pub(crate) fn with_cap(mut self, cap: usize) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub(crate) fn reserve(&self) -> Reservation {
+        Reservation {
+            used: self.used.clone(),
+            cap: self.cap,
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn spill(&self) -> Result<Spill, DbError> {
+        let file = match &self.dir {
+            Some(dir) => tempfile::tempfile_in(dir)?,
+            None => tempfile::tempfile()?,
+        };
+        Ok(Spill {
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+pub(crate) struct Reservation {
+    used: Arc<AtomicUsize>,
+    cap: usize,
+    bytes: usize,
+}
+
+impl Reservation {
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn grow(&mut self, bytes: usize) -> Result<(), DbError> {
+        self.bytes += bytes;
+        let used = self.used.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if used > self.cap {
+            return Err(DbError::OutOfMemoryBudget(used, self.cap));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn release(&mut self) {
+        self.used
+            .fetch_sub(mem::take(&mut self.bytes), Ordering::Relaxed);
+    }
+
+    pub(crate) fn hold(self, rows: Rows) -> Rows {
+        Box::new(Held {
+            rows,
+            _reservation: self,
+        })
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+struct Held {
+    rows: Rows,
+    _reservation: Reservation,
+}
+
+impl Iterator for Held {
+    type Item = Result<Vec<Col>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+pub(crate) fn footprint(row: &[Col]) -> usize {
+    row.iter()
+        .map(|col| match col {
+            Col::Varchar(value, _) => mem::size_of::<Col>() + value.len(),
+            _ => mem::size_of::<Col>(),
+        })
+        .sum()
+}
+
+pub(crate) struct Spill {
+    writer: BufWriter<File>,
+}
+
+impl Spill {
+    pub(crate) fn write(&mut self, row: &[Col]) -> Result<(), DbError> {
+        let len = row.iter().map(Col::compact_size).sum::<usize>();
+        let mut buffer = vec![0u8; COUNT_SIZE + LEN_SIZE + len];
+        buffer[..COUNT_SIZE].copy_from_slice(&(row.len() as u16).to_be_bytes());
+        buffer[COUNT_SIZE..COUNT_SIZE + LEN_SIZE].copy_from_slice(&(len as u32).to_be_bytes());
+        let mut offset = COUNT_SIZE + LEN_SIZE;
+        for col in row {
+            offset += col.write_compact(&mut buffer[offset..])?;
+        }
+        self.writer.write_all(&buffer)?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<Rows, DbError> {
+        let mut file = self
+            .writer
+            .into_inner()
+            .map_err(|err| DbError::IO(err.to_string()))?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Box::new(Run {
+            reader: BufReader::new(file),
+        }))
+    }
+}
+
+pub(crate) struct Partitions {
+    spills: Vec<Spill>,
+    depth: u32,
+}
+
+impl Partitions {
+    pub(crate) fn new(budget: &Budget, depth: u32) -> Result<Self, DbError> {
+        let mut spills = Vec::with_capacity(PARTITIONS);
+        for _ in 0..PARTITIONS {
+            spills.push(budget.spill()?);
+        }
+        Ok(Self { spills, depth })
+    }
+
+    pub(crate) fn write(&mut self, key: &impl Hash, row: &[Col]) -> Result<(), DbError> {
+        let mut hasher = DefaultHasher::new();
+        self.depth.hash(&mut hasher);
+        key.hash(&mut hasher);
+        let partition = hasher.finish() as usize % PARTITIONS;
+        self.spills[partition].write(row)
+    }
+
+    pub(crate) fn finish(self) -> Result<Vec<Rows>, DbError> {
+        self.spills.into_iter().map(Spill::finish).collect()
+    }
+}
+
+struct Run {
+    reader: BufReader<File>,
+}
+
+impl Run {
+    fn read_row(&mut self) -> Result<Option<Vec<Col>>, DbError> {
+        let mut header = [0u8; COUNT_SIZE + LEN_SIZE];
+        match self.reader.read_exact(&mut header) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let count = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let mut len = [0u8; LEN_SIZE];
+        len.copy_from_slice(&header[COUNT_SIZE..]);
+        let mut buffer = vec![0u8; u32::from_be_bytes(len) as usize];
+        self.reader.read_exact(&mut buffer)?;
+        let mut row = Vec::with_capacity(count);
+        let mut offset = 0;
+        for _ in 0..count {
+            let (col, read) = Col::read_compact(&buffer[offset..])?;
+            offset += read;
+            row.push(col);
+        }
+        Ok(Some(row))
+    }
+}
+
+impl Iterator for Run {
+    type Item = Result<Vec<Col>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_row().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, iter};
+
+    use super::*;
+
+    #[test]
+    fn partitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let budget = Budget::new(0, Some(dir.path()));
+        let mut partitions = Partitions::new(&budget, 0).unwrap();
+        for i in 0..100 {
+            partitions
+                .write(&(i % 10), &[Col::int(i % 10), Col::varchar("x", 4)])
+                .unwrap();
+        }
+        let runs = partitions.finish().unwrap();
+        assert_eq!(PARTITIONS, runs.len());
+        let mut keys = Vec::new();
+        for run in runs {
+            let mut rows = run.collect::<Result<Vec<_>, _>>().unwrap();
+            rows.sort();
+            rows.dedup();
+            keys.extend(rows.into_iter().map(|row| row[0].clone()));
+        }
+        keys.sort();
+        assert_eq!((0..10).map(Col::int).collect::<Vec<_>>(), keys);
+        assert_eq!(0, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn reservations() {
+        let budget = Budget::new(0, None).with_cap(100);
+        let mut first = budget.reserve();
+        first.grow(60).unwrap();
+        let mut second = budget.reserve();
+        assert_eq!(Err(DbError::OutOfMemoryBudget(110, 100)), second.grow(50));
+        drop(second);
+        first.release();
+        assert_eq!(0, first.bytes());
+        let mut third = budget.reserve();
+        third.grow(100).unwrap();
+        let rows = third.hold(Box::new(iter::once(Ok(vec![Col::int(1)]))));
+        assert!(budget.reserve().grow(1).is_err());
+        drop(rows);
+        budget.reserve().grow(100).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use btree::BTree;
+use common::error::DbError;
+use row::{Col, ColType, Row, RowType};
+
+use crate::{executor::Rows, storage};
+
+const KEY_SIZE: u16 = 511;
+const VALUE_SIZE: usize = 255;
+const SKETCH_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TableStats {
+    pub(crate) rows: u64,
+    pub(crate) columns: BTreeMap<String, ColumnStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ColumnStats {
+    pub(crate) distinct: u64,
+    pub(crate) min: Option<String>,
+    pub(crate) max: Option<String>,
+}
+
+pub(crate) struct Statistics {
+    path: Option<PathBuf>,
+    read_only: bool,
+    catalog: Mutex<Option<Catalog>>,
+}
+
+struct Catalog {
+    btree: BTree,
+    tables: BTreeMap<String, TableStats>,
+}
+
+#[derive(Default)]
+struct Sketch {
+    hashes: BTreeSet<u64>,
+}
+
+impl ColumnStats {
+    pub(crate) fn range(&self) -> Option<(i64, i64)> {
+        let min = self.min.as_ref()?.parse().ok()?;
+        let max = self.max.as_ref()?.parse().ok()?;
+        Some((min, max))
+    }
+}
+
+impl Statistics {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            read_only: false,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn read_only(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            read_only: true,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn store(&self, table: &str, stats: TableStats) -> Result<(), DbError> {
+        self.with(|catalog| {
+            catalog.remove(table)?;
+            for (column, column_stats) in stats.columns.iter() {
+                let row = Row {
+                    columns: vec![
+                        key(table, column)?,
+                        Col::BigInt(stats.rows as i64),
+                        Col::BigInt(column_stats.distinct as i64),
+                        value(column_stats.min.as_deref()),
+                        value(column_stats.max.as_deref()),
+                    ],
+                };
+                catalog.btree.insert(row.columns[0].clone(), row)?;
+            }
+            catalog.tables.insert(table.to_string(), stats);
+            Ok(())
+        })
+    }
+
+    pub(crate) fn get(&self, table: &str) -> Result<Option<TableStats>, DbError> {
+        if !self.exists()? {
+            return Ok(None);
+        }
+        self.with(|catalog| Ok(catalog.tables.get(table).cloned()))
+    }
+
+    pub(crate) fn drop(&self, table: &str) -> Result<(), DbError> {
+        if !self.exists()? {
+            return Ok(());
+        }
+        self.with(|catalog| catalog.remove(table))
+    }
+
+    fn exists(&self) -> Result<bool, DbError> {
+        let catalog = self
+            .catalog
+            .lock()
+            .map_err(|_| DbError::unexpected("statistics lock is poisoned"))?;
+        Ok(catalog.is_some() || self.path.as_deref().is_none_or(Path::is_file))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Catalog) -> Result<T, DbError>) -> Result<T, DbError> {
+        let mut catalog = self
+            .catalog
+            .lock()
+            .map_err(|_| DbError::unexpected("statistics lock is poisoned"))?;
+        if catalog.is_none() {
+            *catalog = Some(Catalog::open(self.path.as_deref(), self.read_only)?);
+        }
+        match catalog.as_mut() {
+            Some(catalog) => f(catalog),
+            None => Err(DbError::unexpected("statistics catalog is not open")),
+        }
+    }
+}
+
+impl Catalog {
+    fn open(path: Option<&Path>, read_only: bool) -> Result<Self, DbError> {
+        let mut btree = storage::open_catalog(path, read_only)?;
+        if btree.get_structure()?.columns.is_empty() {
+            btree.set_structure(RowType {
+                columns: vec![
+                    ColType::varchar("name", KEY_SIZE),
+                    ColType::bigint("rows"),
+                    ColType::bigint("distinct"),
+                    ColType::varchar("min", VALUE_SIZE as u16),
+                    ColType::varchar("max", VALUE_SIZE as u16),
+                ],
+                constraints: vec![],
+            })?;
+        }
+        let mut tables: BTreeMap<String, TableStats> = BTreeMap::new();
+        let rows: Vec<Row> = btree.select_all()?;
+        for row in rows {
+            let name = row.columns[0].to_string();
+            let Some((table, column)) = name.rsplit_once('.') else {
+                continue;
+            };
+            let (Col::BigInt(rows), Col::BigInt(distinct)) = (&row.columns[1], &row.columns[2])
+            else {
+                return Err(DbError::unexpected("invalid statistics row"));
+            };
+            let bound = |col: &Col| (*rows > 0).then(|| col.to_string());
+            let stats = tables.entry(table.to_string()).or_default();
+            stats.rows = *rows as u64;
+            stats.columns.insert(
+                column.to_string(),
+                ColumnStats {
+                    distinct: *distinct as u64,
+                    min: bound(&row.columns[3]),
+                    max: bound(&row.columns[4]),
+                },
+            );
+        }
+        Ok(Self { btree, tables })
+    }
+
+    fn remove(&mut self, table: &str) -> Result<(), DbError> {
+        let Some(stats) = self.tables.remove(table) else {
+            return Ok(());
+        };
+        for column in stats.columns.keys() {
+            self.btree.delete(key(table, column)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl Sketch {
+    fn insert(&mut self, col: &Col) {
+        let mut hasher = DefaultHasher::new();
+        col.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.hashes.len() < SKETCH_SIZE {
+            self.hashes.insert(hash);
+        } else if self.hashes.last().is_some_and(|last| hash < *last) && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        match self.hashes.last() {
+            Some(last) if self.hashes.len() == SKETCH_SIZE => {
+                let fraction = *last as f64 / u64::MAX as f64;
+                ((SKETCH_SIZE - 1) as f64 / fraction) as u64
+            }
+            _ => self.hashes.len() as u64,
+        }
+    }
+}
+
+pub(crate) fn analyze(row_type: &RowType, rows: Rows) -> Result<TableStats, DbError> {
+    let mut count = 0;
+    let mut columns: Vec<(Option<Col>, Option<Col>, Sketch)> = row_type
+        .columns
+        .iter()
+        .map(|_| (None, None, Sketch::default()))
+        .collect();
+    for row in rows {
+        count += 1;
+        for (col, (min, max, sketch)) in row?.into_iter().zip(columns.iter_mut()) {
+            sketch.insert(&col);
+            if min.as_ref().is_none_or(|min| col < *min) {
+                *min = Some(col.clone());
+            }
+            if max.as_ref().is_none_or(|max| col > *max) {
+                *max = Some(col);
+            }
+        }
+    }
+    Ok(TableStats {
+        rows: count,
+        columns: row_type
+            .columns
+            .iter()
+            .zip(columns)
+            .map(|(col_type, (min, max, sketch))| {
+                let stats = ColumnStats {
+                    distinct: sketch.estimate().min(count),
+                    min: min.map(|col| bounded(col.to_string())),
+                    max: max.map(|col| bounded(col.to_string())),
+                };
+                (col_type.get_name().to_string(), stats)
+            })
+            .collect(),
+    })
+}
+
+fn bounded(mut value: String) -> String {
+    let mut end = value.len().min(VALUE_SIZE);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    value.truncate(end);
+    value
+}
+
+fn key(table: &str, column: &str) -> Result<Col, DbError> {
+    let name = format!("{}.{}", table, column);
+    if name.len() > KEY_SIZE as usize {
+        return Err(DbError::TooLong(
+            "name".to_string(),
+            "statistics".to_string(),
+            name.len(),
+            KEY_SIZE as usize,
+        ));
+    }
+    Ok(Col::Varchar(name, KEY_SIZE))
+}
+
+fn value(value: Option<&str>) -> Col {
+    Col::varchar(value.unwrap_or_default(), VALUE_SIZE as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(values: impl Iterator<Item = i64> + Send + 'static) -> Rows {
+        Box::new(values.map(|value| Ok(vec![Col::BigInt(value), Col::varchar("a", 4)])))
+    }
+
+    #[test]
+    fn analyze_rows() {
+        let row_type = RowType {
+            columns: vec![ColType::bigint("id"), ColType::varchar("name", 4)],
+            constraints: vec![],
+        };
+        let stats = analyze(&row_type, rows((0..100).map(|i| i % 40 - 10))).unwrap();
+        assert_eq!(100, stats.rows);
+        let id = &stats.columns["id"];
+        assert_eq!(40, id.distinct);
+        assert_eq!(Some((-10, 29)), id.range());
+        assert_eq!(1, stats.columns["name"].distinct);
+        assert_eq!(None, stats.columns["name"].range());
+
+        let stats = analyze(&row_type, rows(0..100_000)).unwrap();
+        let distinct = stats.columns["id"].distinct;
+        assert!((90_000..=100_000).contains(&distinct), "{}", distinct);
+
+        let empty = analyze(&row_type, rows(0..0)).unwrap();
+        assert_eq!(0, empty.rows);
+        assert_eq!(None, empty.columns["id"].min);
+    }
+
+    #[test]
+    fn catalog() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("catalog.stat");
+        let statistics = Statistics::new(Some(path.clone()));
+        assert_eq!(None, statistics.get("users").unwrap());
+        statistics.drop("users").unwrap();
+        assert!(!path.exists());
+        let row_type = RowType {
+            columns: vec![ColType::bigint("id"), ColType::varchar("name", 4)],
+            constraints: vec![],
+        };
+        let stats = analyze(&row_type, rows(0..10)).unwrap();
+        statistics.store("app.users", stats.clone()).unwrap();
+        statistics
+            .store("empty", analyze(&row_type, rows(0..0)).unwrap())
+            .unwrap();
+        drop(statistics);
+
+        let statistics = Statistics::new(Some(path));
+        assert_eq!(Some(stats), statistics.get("app.users").unwrap());
+        assert_eq!(0, statistics.get("empty").unwrap().unwrap().rows);
+        statistics.drop("app.users").unwrap();
+        assert_eq!(None, statistics.get("app.users").unwrap());
+    }
+}
@@ -1,62 +1,1221 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
-use btree::BTree;
+use btree::{BTree, Durability, Growth, Index, IndexSnapshot, IoCounters, Stats};
 use common::error::DbError;
-use row::{Col, Row, RowType};
+use row::{Col, ColType, Row, RowType};
+use tracing::warn;
+
+use crate::{
+    changes::{ChangeSet, Feed},
+    database::{self, MAIN},
+    defaults::sequence_name,
+    eval::Term,
+    metrics::Counters,
+    plan::Scalar,
+    sequence::Sequences,
+    statistics::Statistics,
+    transaction::WriteSet,
+    view::Views,
+};
+
+type Handle = Arc<RwLock<Table>>;
+
+const GROWTH_EXTENT: u32 = 16;
+const SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+const INDEX_EXTENSION: &str = "idx";
+const DATABASE_EXTENSION: &str = "db";
+const SEQUENCES_FILE: &str = "catalog.seq";
+const VIEWS_FILE: &str = "catalog.view";
+const STATISTICS_FILE: &str = "catalog.stat";
+const RENAME_FILE: &str = "catalog.rename";
+const CHANGES_FILE: &str = "catalog.changes";
+
+struct Table {
+    btree: BTree,
+    indexes: Vec<TableIndex>,
+}
+
+struct TableIndex {
+    name: String,
+    terms: Vec<Term>,
+    index: Index,
+}
+
+impl TableIndex {
+    fn key(&self, row: &Row) -> Vec<Col> {
+        self.terms
+            .iter()
+            .map(|term| term.evaluate(&row.columns))
+            .collect()
+    }
+}
 
 pub(crate) struct Storage {
-    path: PathBuf,
+    path: Option<PathBuf>,
+    read_only: bool,
+    tables: Mutex<HashMap<String, Handle>>,
+    databases: Mutex<BTreeSet<String>>,
+    temporary: Mutex<BTreeSet<String>>,
+    sequences: Sequences,
+    views: Views,
+    statistics: Statistics,
+    metrics: Arc<Counters>,
+    feed: Feed,
+}
+
+pub(crate) struct Snapshot {
+    tables: HashMap<String, TableSnapshot>,
+    metrics: Arc<Counters>,
+}
+
+struct TableSnapshot {
+    btree: btree::Snapshot,
+    indexes: Vec<(String, Vec<Term>, IndexSnapshot)>,
 }
 
 impl Storage {
     pub(crate) fn new(path: &Path) -> Result<Self, DbError> {
-        Ok(Self {
-            path: PathBuf::from(path),
-        })
+        let storage = Self {
+            path: Some(PathBuf::from(path)),
+            read_only: false,
+            tables: Mutex::new(HashMap::new()),
+            databases: Mutex::new(BTreeSet::new()),
+            temporary: Mutex::new(BTreeSet::new()),
+            sequences: Sequences::new(Some(path.join(SEQUENCES_FILE))),
+            views: Views::new(Some(path.join(VIEWS_FILE))),
+            statistics: Statistics::new(Some(path.join(STATISTICS_FILE))),
+            metrics: Arc::default(),
+            feed: Feed::new(Some(path.join(CHANGES_FILE))),
+        };
+        storage.recover_rename()?;
+        Ok(storage)
+    }
+
+    pub(crate) fn read_only(path: &Path) -> Self {
+        Self {
+            path: Some(PathBuf::from(path)),
+            read_only: true,
+            tables: Mutex::new(HashMap::new()),
+            databases: Mutex::new(BTreeSet::new()),
+            temporary: Mutex::new(BTreeSet::new()),
+            sequences: Sequences::read_only(path.join(SEQUENCES_FILE)),
+            views: Views::read_only(path.join(VIEWS_FILE)),
+            statistics: Statistics::read_only(path.join(STATISTICS_FILE)),
+            metrics: Arc::default(),
+            feed: Feed::read_only(path.join(CHANGES_FILE)),
+        }
+    }
+
+    pub(crate) fn in_memory() -> Self {
+        Self {
+            path: None,
+            read_only: false,
+            tables: Mutex::new(HashMap::new()),
+            databases: Mutex::new(BTreeSet::new()),
+            temporary: Mutex::new(BTreeSet::new()),
+            sequences: Sequences::new(None),
+            views: Views::new(None),
+            statistics: Statistics::new(None),
+            metrics: Arc::default(),
+            feed: Feed::new(None),
+        }
+    }
+
+    pub(crate) fn spill_dir(&self) -> Option<&Path> {
+        self.path.as_deref().filter(|_| !self.read_only)
+    }
+
+    pub(crate) fn sequences(&self) -> &Sequences {
+        &self.sequences
+    }
+
+    pub(crate) fn views(&self) -> &Views {
+        &self.views
+    }
+
+    pub(crate) fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    pub(crate) fn metrics(&self) -> &Counters {
+        &self.metrics
+    }
+
+    pub(crate) fn feed(&self) -> &Feed {
+        &self.feed
+    }
+
+    pub(crate) fn feed_mut(&mut self) -> &mut Feed {
+        &mut self.feed
+    }
+
+    pub(crate) fn create_database(&self, name: &str) -> Result<usize, DbError> {
+        if name.contains(['.', '-']) {
+            return Err(DbError::InvalidInput(format!(
+                "invalid database name '{}'",
+                name
+            )));
+        }
+        let exists = || DbError::InvalidInput(format!("database '{}' already exists", name));
+        if name == MAIN {
+            return Err(exists());
+        }
+        match &self.path {
+            Some(path) => match fs::create_dir(path.join(database_dir(name))) {
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(exists()),
+                result => result?,
+            },
+            None => {
+                if !self.lock_databases()?.insert(name.to_string()) {
+                    return Err(exists());
+                }
+            }
+        }
+        Ok(1)
+    }
+
+    fn has_database(&self, name: &str) -> Result<bool, DbError> {
+        Ok(name == MAIN
+            || self.lock_temporary()?.contains(name)
+            || match &self.path {
+                Some(path) => path.join(database_dir(name)).is_dir(),
+                None => self.lock_databases()?.contains(name),
+            })
+    }
+
+    pub(crate) fn create_temporary(&self, schema: &str) -> Result<(), DbError> {
+        self.lock_temporary()?.insert(schema.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn drop_temporary(&self, schema: &str) -> Result<(), DbError> {
+        self.lock_temporary()?.remove(schema);
+        Ok(())
+    }
+
+    pub(crate) fn check_database(&self, name: &str) -> Result<(), DbError> {
+        match self.has_database(name)? {
+            true => Ok(()),
+            false => Err(DbError::InvalidInput(format!(
+                "database '{}' doesn't exist",
+                name
+            ))),
+        }
+    }
+
+    pub(crate) fn table_path(&self, name: &str) -> Result<Option<PathBuf>, DbError> {
+        let (database, table) = database::split(name);
+        if table.contains('.') {
+            return Err(DbError::InvalidInput(format!(
+                "invalid relation name '{}'",
+                name
+            )));
+        }
+        if let Some(database) = database {
+            self.check_database(database)?;
+            if self.lock_temporary()?.contains(database) {
+                return Ok(None);
+            }
+        }
+        Ok(self.path.as_ref().map(|path| match database {
+            Some(database) => path.join(database_dir(database)).join(table),
+            None => path.join(table),
+        }))
+    }
+
+    pub(crate) fn tables(&self, database: Option<&str>) -> Result<Vec<String>, DbError> {
+        if let Some(database) = database {
+            self.check_database(database)?;
+        }
+        let mut names: BTreeSet<String> = self
+            .tables
+            .lock()
+            .map_err(|_| DbError::unexpected("tables lock is poisoned"))?
+            .keys()
+            .filter_map(|name| database::local(name, database))
+            .collect();
+        if let Some(path) = &self.path {
+            let path = match database {
+                Some(database) => path.join(database_dir(database)),
+                None => path.clone(),
+            };
+            for entry in fs::read_dir(path)? {
+                if let Some(name) = entry?.file_name().to_str()
+                    && !name.contains(['.', '-'])
+                {
+                    names.insert(name.to_string());
+                }
+            }
+        }
+        let mut tables = Vec::with_capacity(names.len());
+        for name in names {
+            let relation = database::relation(name.clone(), database);
+            if !self.get_row_type(&relation)?.columns.is_empty() {
+                tables.push(name);
+            }
+        }
+        Ok(tables)
     }
 
     pub(crate) fn get_row_type(&self, name: &str) -> Result<RowType, DbError> {
-        let path = self.table_path(name);
-        let mut btree = BTree::new(&path)?;
-        btree.get_structure()
+        let table = self.table(name)?;
+        let table = read(&table)?;
+        table.btree.get_structure()
     }
 
     pub(crate) fn create(&self, name: &str, row_type: RowType) -> Result<usize, DbError> {
-        let path = self.table_path(name);
-        let mut btree = BTree::new(&path)?;
-        btree.set_structure(row_type)?;
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        table.btree.set_structure(row_type)?;
+        Ok(1)
+    }
+
+    pub(crate) fn create_index(
+        &self,
+        name: &str,
+        index_name: &str,
+        columns: &[String],
+    ) -> Result<usize, DbError> {
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        if table.indexes.iter().any(|index| index.name == index_name) {
+            return Err(DbError::InvalidInput(format!(
+                "index '{}' already exists",
+                index_name
+            )));
+        }
+        let row_type = table.btree.get_structure()?;
+        let mut terms = Vec::with_capacity(columns.len());
+        let mut index_columns = Vec::with_capacity(columns.len());
+        for column in columns {
+            let (function, field) = match Scalar::parse(column) {
+                Some((function, field)) => (Some(function), field),
+                None => (None, column.as_str()),
+            };
+            let Some(position) = row_type
+                .columns
+                .iter()
+                .position(|col_type| col_type.get_name() == field)
+            else {
+                return Err(DbError::field_not_found(field, name));
+            };
+            let term = Term {
+                column: position,
+                function,
+            };
+            if terms.contains(&term) {
+                return Err(DbError::InvalidInput(format!(
+                    "column '{}' appears more than once in index '{}'",
+                    column, index_name
+                )));
+            }
+            let col_type = match (function, &row_type.columns[position]) {
+                (None, col_type) => col_type.clone(),
+                (Some(_), ColType::Varchar(_, size)) => ColType::varchar(column, *size),
+                (Some(function), col_type) => {
+                    return Err(DbError::InvalidInput(format!(
+                        "cannot apply {} to {}",
+                        function, col_type
+                    )));
+                }
+            };
+            terms.push(term);
+            index_columns.push(col_type);
+        }
+        let mut index = match self.table_path(name)? {
+            Some(path) => Index::new(&index_path(&path, index_name))?,
+            None => Index::new_in_memory()?,
+        };
+        index.set_durability(Durability::OnCommit);
+        index.set_io(self.metrics.io());
+        index.set_columns(index_columns)?;
+        let mut index = TableIndex {
+            name: index_name.to_string(),
+            terms,
+            index,
+        };
+        for kv in table.btree.scan(Bound::Unbounded, Bound::Unbounded)? {
+            let (key, row) = kv?;
+            index.index.insert(index.key(&row), key)?;
+        }
+        index.index.sync()?;
+        table.indexes.push(index);
         Ok(1)
     }
 
     pub(crate) fn insert(&self, name: &str, values: Vec<(Col, Row)>) -> Result<usize, DbError> {
-        let path = self.table_path(name);
-        let mut btree = BTree::new(&path)?;
+        self.write_rows(name, values, false)
+    }
+
+    pub(crate) fn upsert(&self, name: &str, values: Vec<(Col, Row)>) -> Result<usize, DbError> {
+        self.write_rows(name, values, true)
+    }
+
+    fn write_rows(
+        &self,
+        name: &str,
+        mut values: Vec<(Col, Row)>,
+        replace: bool,
+    ) -> Result<usize, DbError> {
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        let Table { btree, indexes } = &mut *table;
+        btree.set_durability(Durability::OnCommit);
         let len = values.len();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut sorted: Vec<(Col, Row)> = Vec::with_capacity(len);
         for (key, value) in values {
-            btree.insert(key, value)?;
+            match sorted.last_mut() {
+                Some(last) if last.0 == key && replace => last.1 = value,
+                Some(last) if last.0 == key => {
+                    return Err(DbError::DuplicateKey(name.to_string(), key.to_string()));
+                }
+                _ => sorted.push((key, value)),
+            }
+        }
+        if !replace {
+            for (key, _) in sorted.iter() {
+                if btree.search(key.clone())?.is_some() {
+                    return Err(DbError::DuplicateKey(name.to_string(), key.to_string()));
+                }
+            }
+        }
+        let mut changes = self.observed(name)?.then(|| ChangeSet::new(name));
+        if !indexes.is_empty() || changes.is_some() {
+            for (key, value) in sorted.iter() {
+                let old = match replace {
+                    true => btree.search(key.clone())?,
+                    false => None,
+                };
+                for index in indexes.iter_mut() {
+                    if let Some(old) = &old {
+                        index.index.remove(&index.key(old), key)?;
+                    }
+                    index.index.insert(index.key(value), key.clone())?;
+                }
+                if let Some(changes) = &mut changes {
+                    changes.push(key.clone(), old, Some(value.clone()));
+                }
+            }
         }
+        if !replace && btree.stats()?.entries == 0 {
+            btree.bulk_load(sorted)?;
+        } else {
+            btree.insert_many(sorted)?;
+        }
+        btree.sync()?;
+        for index in indexes.iter_mut() {
+            index.index.sync()?;
+        }
+        let changes: Vec<ChangeSet> = changes.into_iter().collect();
+        self.publish(&changes);
+        drop(table);
+        self.feed.notify(changes);
+        self.metrics.written(len);
+        Ok(len)
+    }
+
+    pub(crate) fn update(
+        &self,
+        name: &str,
+        mut updates: Vec<(Col, Row)>,
+    ) -> Result<usize, DbError> {
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        let Table { btree, indexes } = &mut *table;
+        btree.set_durability(Durability::OnCommit);
+        let old_keys: BTreeSet<&Col> = updates.iter().map(|(key, _)| key).collect();
+        let mut new_keys = BTreeSet::new();
+        for (_, row) in updates.iter() {
+            let key = &row.columns[0];
+            let exists = !old_keys.contains(key) && btree.search(key.clone())?.is_some();
+            if !new_keys.insert(key) || exists {
+                return Err(DbError::DuplicateKey(name.to_string(), key.to_string()));
+            }
+        }
+        let mut olds = Vec::with_capacity(updates.len());
+        for (key, row) in updates.iter() {
+            let old = match row.columns[0] == *key {
+                true => btree.search(key.clone())?,
+                false => btree.delete(key.clone())?,
+            };
+            olds.push(old);
+        }
+        let mut changes = self.observed(name)?.then(|| ChangeSet::new(name));
+        if let Some(changes) = &mut changes {
+            for ((key, row), old) in updates.iter().zip(olds.iter()) {
+                changes.push(key.clone(), old.clone(), Some(row.clone()));
+            }
+        }
+        for index in indexes.iter_mut() {
+            for ((key, _), old) in updates.iter().zip(olds.iter()) {
+                if let Some(old) = old {
+                    index.index.remove(&index.key(old), key)?;
+                }
+            }
+            for (_, row) in updates.iter() {
+                index.index.insert(index.key(row), row.columns[0].clone())?;
+            }
+        }
+        let len = updates.len();
+        updates.sort_by(|a, b| a.1.columns[0].cmp(&b.1.columns[0]));
+        let rows: Vec<(Col, Row)> = updates
+            .into_iter()
+            .map(|(_, row)| (row.columns[0].clone(), row))
+            .collect();
+        btree.insert_many(rows)?;
+        btree.sync()?;
+        for index in indexes.iter_mut() {
+            index.index.sync()?;
+        }
+        let changes: Vec<ChangeSet> = changes.into_iter().collect();
+        self.publish(&changes);
+        drop(table);
+        self.feed.notify(changes);
+        self.metrics.written(len);
+        Ok(len)
+    }
+
+    pub(crate) fn snapshot(&self, names: &BTreeSet<String>) -> Result<Snapshot, DbError> {
+        let handles = names
+            .iter()
+            .map(|name| self.table(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut guards = Vec::with_capacity(handles.len());
+        for handle in handles.iter() {
+            guards.push(read(handle)?);
+        }
+        let mut tables = HashMap::with_capacity(guards.len());
+        for (name, table) in names.iter().zip(guards.iter()) {
+            let mut indexes = Vec::with_capacity(table.indexes.len());
+            for index in table.indexes.iter() {
+                indexes.push((
+                    index.name.clone(),
+                    index.terms.clone(),
+                    index.index.snapshot()?,
+                ));
+            }
+            let btree = table.btree.snapshot()?;
+            tables.insert(name.clone(), TableSnapshot { btree, indexes });
+        }
+        Ok(Snapshot {
+            tables,
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    pub(crate) fn commit(&self, writes: BTreeMap<String, WriteSet>) -> Result<usize, DbError> {
+        let handles = writes
+            .keys()
+            .map(|name| self.table(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut tables = Vec::with_capacity(handles.len());
+        for handle in handles.iter() {
+            tables.push(write(handle)?);
+        }
+        let mut len = 0;
+        let mut sets = Vec::new();
+        for (table, (name, writes)) in tables.iter_mut().zip(writes) {
+            let Table { btree, indexes } = &mut **table;
+            btree.set_durability(Durability::OnCommit);
+            len += writes.len();
+            let mut changes = self.observed(&name)?.then(|| ChangeSet::new(&name));
+            let mut rows = Vec::with_capacity(writes.len());
+            for (key, row) in writes {
+                let old = match row {
+                    Some(_) => btree.search(key.clone())?,
+                    None => btree.delete(key.clone())?,
+                };
+                for index in indexes.iter_mut() {
+                    if let Some(old) = &old {
+                        index.index.remove(&index.key(old), &key)?;
+                    }
+                    if let Some(row) = &row {
+                        index.index.insert(index.key(row), key.clone())?;
+                    }
+                }
+                if let Some(changes) = &mut changes {
+                    changes.push(key.clone(), old, row.clone());
+                }
+                if let Some(row) = row {
+                    rows.push((key, row));
+                }
+            }
+            if !rows.is_empty() {
+                btree.insert_many(rows)?;
+            }
+            btree.sync()?;
+            for index in indexes.iter_mut() {
+                index.index.sync()?;
+            }
+            sets.extend(changes);
+        }
+        self.publish(&sets);
+        drop(tables);
+        self.feed.notify(sets);
+        self.metrics.written(len);
         Ok(len)
     }
 
     pub(crate) fn select_all(&self, name: &str) -> Result<Vec<Row>, DbError> {
-        let path = self.table_path(name);
-        let mut btree = BTree::new(&path)?;
-        btree.select_all()
+        let table = self.table(name)?;
+        let snapshot = read(&table)?.btree.snapshot()?;
+        snapshot.select_all()
+    }
+
+    pub(crate) fn search(&self, name: &str, key: Col) -> Result<Option<Row>, DbError> {
+        let table = self.table(name)?;
+        read(&table)?.btree.search(key)
+    }
+
+    pub(crate) fn indexes(&self, name: &str) -> Result<Vec<(String, Vec<Term>)>, DbError> {
+        let table = self.table(name)?;
+        let table = read(&table)?;
+        Ok(table
+            .indexes
+            .iter()
+            .map(|index| (index.name.clone(), index.terms.clone()))
+            .collect())
     }
 
     pub(crate) fn delete_all(&self, name: &str) -> Result<i32, DbError> {
-        let path = self.table_path(name);
-        let mut btree = BTree::new(&path)?;
-        btree.delete_all()
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        let mut changes = self.observed(name)?.then(|| ChangeSet::new(name));
+        if let Some(changes) = &mut changes {
+            for entry in table.btree.scan(Bound::Unbounded, Bound::Unbounded)? {
+                let (key, row) = entry?;
+                changes.push(key, Some(row), None);
+            }
+        }
+        for index in table.indexes.iter_mut() {
+            index.index.clear()?;
+        }
+        let deleted = table.btree.delete_all()?;
+        let changes: Vec<ChangeSet> = changes.into_iter().collect();
+        self.publish(&changes);
+        drop(table);
+        self.feed.notify(changes);
+        self.metrics.written(deleted as usize);
+        Ok(deleted)
+    }
+
+    pub(crate) fn flush(&self) -> Result<(), DbError> {
+        let tables: Vec<Handle> = self
+            .tables
+            .lock()
+            .map_err(|_| DbError::unexpected("tables lock is poisoned"))?
+            .values()
+            .cloned()
+            .collect();
+        for table in tables {
+            let mut table = write(&table)?;
+            let Table { btree, indexes } = &mut *table;
+            btree.sync()?;
+            for index in indexes.iter_mut() {
+                index.index.sync()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn stats(&self, name: &str) -> Result<Stats, DbError> {
+        let table = self.table(name)?;
+        read(&table)?.btree.stats()
+    }
+
+    pub(crate) fn index_stats(&self, name: &str, index_name: &str) -> Result<Stats, DbError> {
+        let table = self.table(name)?;
+        let table = read(&table)?;
+        let Some(index) = table.indexes.iter().find(|index| index.name == index_name) else {
+            return Err(DbError::InvalidInput(format!(
+                "index '{}' doesn't exist",
+                index_name
+            )));
+        };
+        index.index.stats()
+    }
+
+    pub(crate) fn check(&self, name: &str) -> Result<Vec<(&'static str, String)>, DbError> {
+        let table = self.table(name)?;
+        let table = read(&table)?;
+        let row_type = table.btree.get_structure()?;
+        if row_type.columns.is_empty() {
+            return Err(DbError::TableNotFound(name.to_string()));
+        }
+        let mut problems = Vec::new();
+        for (offset, message) in table.btree.verify()?.errors {
+            problems.push(("btree", format!("page {}: {}", offset, message)));
+        }
+        let mut entries = vec![HashSet::new(); table.indexes.len()];
+        for entry in table.btree.scan(Bound::Unbounded, Bound::Unbounded)? {
+            let (key, row) = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    problems.push(("row", err.to_string()));
+                    break;
+                }
+            };
+            if let Some(message) = check_row(&row_type, &key, &row) {
+                problems.push(("row", format!("key {}: {}", key, message)));
+            }
+            for (index, entries) in table.indexes.iter().zip(entries.iter_mut()) {
+                if index
+                    .terms
+                    .iter()
+                    .all(|term| term.column < row.columns.len())
+                {
+                    entries.insert((index.key(&row), key.clone()));
+                }
+            }
+        }
+        for (index, mut expected) in table.indexes.iter().zip(entries) {
+            for (offset, message) in index.index.verify()?.errors {
+                problems.push((
+                    "index",
+                    format!("index '{}' page {}: {}", index.name, offset, message),
+                ));
+            }
+            for (value, key) in index.index.scan(Bound::Unbounded, Bound::Unbounded)? {
+                if !expected.remove(&(value.clone(), key.clone())) {
+                    problems.push((
+                        "index",
+                        format!(
+                            "index '{}' has entry {} for key {} not found in the table",
+                            index.name,
+                            display_key(&value),
+                            key
+                        ),
+                    ));
+                }
+            }
+            for (value, key) in expected {
+                problems.push((
+                    "index",
+                    format!(
+                        "index '{}' is missing entry {} for key {}",
+                        index.name,
+                        display_key(&value),
+                        key
+                    ),
+                ));
+            }
+        }
+        Ok(problems)
+    }
+
+    pub(crate) fn vacuum(&self, name: &str) -> Result<u64, DbError> {
+        let table = self.table(name)?;
+        let mut table = write(&table)?;
+        let Table { btree, indexes } = &mut *table;
+        let before = allocated_size(btree, indexes);
+        btree.compact()?;
+        for index in indexes.iter_mut() {
+            index.index.clear()?;
+        }
+        for kv in btree.scan(Bound::Unbounded, Bound::Unbounded)? {
+            let (key, row) = kv?;
+            for index in indexes.iter_mut() {
+                index.index.insert(index.key(&row), key.clone())?;
+            }
+        }
+        for index in indexes.iter_mut() {
+            index.index.compact()?;
+        }
+        Ok(before.saturating_sub(allocated_size(btree, indexes)))
     }
 
-    fn table_path(&self, table_name: &str) -> PathBuf {
-        let mut path = self.path.clone();
-        path.push(table_name);
-        path
+    pub(crate) fn drop_table(&self, name: &str) -> Result<usize, DbError> {
+        let handle = self.table(name)?;
+        let mut tables = self
+            .tables
+            .lock()
+            .map_err(|_| DbError::unexpected("tables lock is poisoned"))?;
+        let table = write(&handle)?;
+        if table.btree.get_structure()?.columns.is_empty() {
+            return Err(DbError::TableNotFound(name.to_string()));
+        }
+        tables.remove(name);
+        if let Some(path) = self.table_path(name)? {
+            remove_files(&path)?;
+            for index in table.indexes.iter() {
+                remove_files(&index_path(&path, &index.name))?;
+            }
+        }
+        Ok(1)
+    }
+
+    pub(crate) fn rename_table(&self, name: &str, to: &str) -> Result<(), DbError> {
+        if database::split(name).0 != database::split(to).0 {
+            return Err(DbError::InvalidInput(format!(
+                "cannot move '{}' to another database",
+                name
+            )));
+        }
+        let handle = self.table(name)?;
+        let target = self.table(to)?;
+        let mut tables = self
+            .tables
+            .lock()
+            .map_err(|_| DbError::unexpected("tables lock is poisoned"))?;
+        let table = write(&handle)?;
+        if table.btree.get_structure()?.columns.is_empty() {
+            return Err(DbError::TableNotFound(name.to_string()));
+        }
+        if !read(&target)?.btree.get_structure()?.columns.is_empty() {
+            return Err(DbError::InvalidInput(format!(
+                "relation '{}' already exists",
+                to
+            )));
+        }
+        let row_type = table.btree.get_structure()?;
+        for column in row_type.auto_increment() {
+            let sequence = sequence_name(to, column);
+            if self.sequences.exists(&sequence)? {
+                return Err(DbError::InvalidInput(format!(
+                    "sequence '{}' already exists",
+                    sequence
+                )));
+            }
+        }
+        tables.remove(name);
+        tables.remove(to);
+        drop(target);
+        let (Some(root), Some(from), Some(path)) = (
+            self.path.as_ref(),
+            self.table_path(name)?,
+            self.table_path(to)?,
+        ) else {
+            drop(table);
+            tables.insert(to.to_string(), handle);
+            drop(tables);
+            return self.rename_catalogs(name, to);
+        };
+        let indexes: Vec<String> = table
+            .indexes
+            .iter()
+            .map(|index| index.name.clone())
+            .collect();
+        drop(table);
+        drop(handle);
+        if path.exists() {
+            remove_files(&path)?;
+        }
+        let journal = root.join(RENAME_FILE);
+        write_journal(&journal, name, to, &indexes)?;
+        move_files(&journal, &file_moves(&from, &path, &indexes))?;
+        drop(tables);
+        self.rename_catalogs(name, to)?;
+        fs::remove_file(journal)?;
+        Ok(())
+    }
+
+    fn recover_rename(&self) -> Result<(), DbError> {
+        let Some(journal) = self.path.as_ref().map(|path| path.join(RENAME_FILE)) else {
+            return Ok(());
+        };
+        let contents = match fs::read_to_string(&journal) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            contents => contents?,
+        };
+        let mut lines = contents.lines();
+        let (Some(name), Some(to)) = (lines.next(), lines.next()) else {
+            return Err(DbError::unexpected("rename journal is corrupted"));
+        };
+        let indexes: Vec<String> = lines.map(str::to_string).collect();
+        if let (Some(from), Some(path)) = (self.table_path(name)?, self.table_path(to)?) {
+            move_files(&journal, &file_moves(&from, &path, &indexes))?;
+        }
+        self.rename_catalogs(name, to)?;
+        fs::remove_file(journal)?;
+        Ok(())
+    }
+
+    fn rename_catalogs(&self, name: &str, to: &str) -> Result<(), DbError> {
+        if let Some(stats) = self.statistics.get(name)? {
+            self.statistics.store(to, stats)?;
+            self.statistics.drop(name)?;
+        }
+        let row_type = self.get_row_type(to)?;
+        for column in row_type.auto_increment() {
+            let sequence = sequence_name(name, column);
+            if self.sequences.exists(&sequence)? {
+                self.sequences
+                    .rename(&sequence, &sequence_name(to, column))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn table(&self, name: &str) -> Result<Handle, DbError> {
+        let mut tables = self
+            .tables
+            .lock()
+            .map_err(|_| DbError::unexpected("tables lock is poisoned"))?;
+        if let Some(table) = tables.get(name) {
+            return Ok(table.clone());
+        }
+        let path = self.table_path(name)?;
+        let mut btree = match &path {
+            Some(path) if self.read_only => match path.exists() {
+                true => BTree::read_only(path)?,
+                false => return Err(DbError::TableNotFound(name.to_string())),
+            },
+            Some(path) if path.is_file() => BTree::new(path)?,
+            Some(path) => BTree::segmented(path, SEGMENT_SIZE)?,
+            None => BTree::new_in_memory()?,
+        };
+        btree.set_growth(Growth::extent(GROWTH_EXTENT));
+        btree.set_io(self.metrics.io());
+        let indexes = match &path {
+            Some(path) => open_indexes(path, name, &btree, self.read_only, self.metrics.io())?,
+            None => Vec::new(),
+        };
+        let table = Arc::new(RwLock::new(Table { btree, indexes }));
+        tables.insert(name.to_string(), table.clone());
+        Ok(table)
+    }
+
+    fn publish(&self, sets: &[ChangeSet]) {
+        if let Err(err) = self.feed.publish(sets) {
+            warn!(error = %err, "change feed publish failed");
+        }
+    }
+
+    fn observed(&self, name: &str) -> Result<bool, DbError> {
+        if !self.feed.active()? && !self.feed.logging() && !self.feed.watches(name) {
+            return Ok(false);
+        }
+        Ok(match database::split(name).0 {
+            Some(database) => !self.lock_temporary()?.contains(database),
+            None => true,
+        })
+    }
+
+    fn lock_temporary(&self) -> Result<MutexGuard<'_, BTreeSet<String>>, DbError> {
+        self.temporary
+            .lock()
+            .map_err(|_| DbError::unexpected("temporary lock is poisoned"))
+    }
+
+    fn lock_databases(&self) -> Result<MutexGuard<'_, BTreeSet<String>>, DbError> {
+        self.databases
+            .lock()
+            .map_err(|_| DbError::unexpected("databases lock is poisoned"))
     }
 }
 
+impl Snapshot {
+    pub(crate) fn scan(
+        &self,
+        name: &str,
+        from: Bound<Col>,
+        limit: usize,
+    ) -> Result<Vec<Row>, DbError> {
+        let rows: Vec<Row> = self
+            .table(name)?
+            .btree
+            .scan(from, Bound::Unbounded)?
+            .take(limit)
+            .map(|kv| kv.map(|(_, row)| row))
+            .collect::<Result<_, _>>()?;
+        self.metrics.read(rows.len());
+        Ok(rows)
+    }
+
+    pub(crate) fn scan_rev(
+        &self,
+        name: &str,
+        to: Bound<Col>,
+        limit: usize,
+    ) -> Result<Vec<Row>, DbError> {
+        let rows: Vec<Row> = self
+            .table(name)?
+            .btree
+            .scan_rev(Bound::Unbounded, to)?
+            .take(limit)
+            .map(|kv| kv.map(|(_, row)| row))
+            .collect::<Result<_, _>>()?;
+        self.metrics.read(rows.len());
+        Ok(rows)
+    }
+
+    pub(crate) fn count(&self, name: &str) -> Result<u64, DbError> {
+        Ok(self.table(name)?.btree.stats()?.entries)
+    }
+
+    pub(crate) fn search(&self, name: &str, key: Col) -> Result<Option<Row>, DbError> {
+        let row = self.table(name)?.btree.search(key)?;
+        self.metrics.read(row.iter().len());
+        Ok(row)
+    }
+
+    pub(crate) fn index_keys(
+        &self,
+        name: &str,
+        index_name: &str,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+        limit: usize,
+    ) -> Result<Vec<Col>, DbError> {
+        Ok(self
+            .index_entries(name, index_name, from, to, limit, false)?
+            .into_iter()
+            .map(|(_, key)| key)
+            .collect())
+    }
+
+    pub(crate) fn index_entries(
+        &self,
+        name: &str,
+        index_name: &str,
+        from: Bound<Vec<Col>>,
+        to: Bound<Vec<Col>>,
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<(Vec<Col>, Col)>, DbError> {
+        let (_, index) = self.index(name, index_name)?;
+        match reverse {
+            true => index.scan_rev_limit(from, to, limit),
+            false => index.scan_limit(from, to, limit),
+        }
+    }
+
+    pub(crate) fn index_terms(&self, name: &str, index_name: &str) -> Result<&[Term], DbError> {
+        Ok(self.index(name, index_name)?.0)
+    }
+
+    fn index(&self, name: &str, index_name: &str) -> Result<(&[Term], &IndexSnapshot), DbError> {
+        let table = self.table(name)?;
+        match table.indexes.iter().find(|(name, ..)| name == index_name) {
+            Some((_, terms, index)) => Ok((terms, index)),
+            None => Err(DbError::InvalidInput(format!(
+                "index '{}' doesn't exist",
+                index_name
+            ))),
+        }
+    }
+
+    fn table(&self, name: &str) -> Result<&TableSnapshot, DbError> {
+        self.tables.get(name).ok_or_else(|| {
+            DbError::InvalidInput(format!("relation '{}' is not in the snapshot", name))
+        })
+    }
+}
+
+pub(crate) fn open_catalog(path: Option<&Path>, read_only: bool) -> Result<BTree, DbError> {
+    match path {
+        Some(path) if !read_only => BTree::new(path),
+        Some(path) if path.is_file() => BTree::read_only(path),
+        _ => BTree::new_in_memory(),
+    }
+}
+
+fn allocated_size(btree: &BTree, indexes: &[TableIndex]) -> u64 {
+    indexes
+        .iter()
+        .map(|index| index.index.allocated_size())
+        .sum::<u64>()
+        + btree.allocated_size()
+}
+
+fn check_row(row_type: &RowType, key: &Col, row: &Row) -> Option<String> {
+    if row.columns.len() != row_type.columns.len() {
+        return Some(format!(
+            "expected {} columns, found {}",
+            row_type.columns.len(),
+            row.columns.len()
+        ));
+    }
+    if row.columns.first() != Some(key) {
+        return Some(format!(
+            "primary key {} doesn't match the row key",
+            row.columns[0]
+        ));
+    }
+    for (col, col_type) in row.columns.iter().zip(row_type.columns.iter()) {
+        let valid = match (col, col_type) {
+            (Col::Int(_), ColType::Int(_)) | (Col::BigInt(_), ColType::BigInt(_)) => true,
+            (Col::Varchar(value, _), ColType::Varchar(_, size)) => value.len() <= *size as usize,
+            _ => false,
+        };
+        if !valid {
+            return Some(format!(
+                "invalid value '{}' for field '{}'",
+                col,
+                col_type.get_name()
+            ));
+        }
+    }
+    None
+}
+
+fn index_file(table: &str, index_name: &str) -> String {
+    format!("{}.{}.{}", table, index_name, INDEX_EXTENSION)
+}
+
+fn database_dir(name: &str) -> String {
+    format!("{}.{}", name, DATABASE_EXTENSION)
+}
+
+fn index_path(table: &Path, index_name: &str) -> PathBuf {
+    let name = table
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    table.with_file_name(index_file(name, index_name))
+}
+
+fn open_indexes(
+    table: &Path,
+    name: &str,
+    btree: &BTree,
+    read_only: bool,
+    io: Arc<IoCounters>,
+) -> Result<Vec<TableIndex>, DbError> {
+    let mut indexes = Vec::new();
+    let table_name = table
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let prefix = format!("{}.", table_name);
+    let suffix = format!(".{}", INDEX_EXTENSION);
+    let Some(path) = table.parent() else {
+        return Ok(indexes);
+    };
+    for entry in fs::read_dir(path)? {
+        let file_name = entry?.file_name();
+        let Some(index_name) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|file_name| file_name.strip_suffix(&suffix))
+        else {
+            continue;
+        };
+        let mut index = match read_only {
+            true => Index::read_only(&path.join(&file_name))?,
+            false => Index::new(&path.join(&file_name))?,
+        };
+        index.set_durability(Durability::OnCommit);
+        index.set_io(io.clone());
+        let row_type = btree.get_structure()?;
+        let mut terms = Vec::new();
+        for column in index.get_columns()? {
+            let term = match Scalar::parse(column.get_name()) {
+                Some((function, field)) => row_type
+                    .columns
+                    .iter()
+                    .position(|col| col.get_name() == field)
+                    .map(|position| Term {
+                        column: position,
+                        function: Some(function),
+                    }),
+                None => row_type
+                    .columns
+                    .iter()
+                    .position(|col| *col == column)
+                    .map(Term::column),
+            };
+            let Some(term) = term else {
+                return Err(DbError::field_not_found(column.get_name(), name));
+            };
+            terms.push(term);
+        }
+        indexes.push(TableIndex {
+            name: index_name.to_string(),
+            terms,
+            index,
+        });
+    }
+    indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(indexes)
+}
+
+fn display_key(key: &[Col]) -> String {
+    let values: Vec<String> = key.iter().map(Col::to_string).collect();
+    values.join(", ")
+}
+
+fn remove_files(path: &Path) -> Result<(), DbError> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    match fs::remove_file(wal_path(path)) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+fn wal_path(path: &Path) -> PathBuf {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    PathBuf::from(wal)
+}
+
+fn file_moves(from: &Path, to: &Path, indexes: &[String]) -> Vec<(PathBuf, PathBuf)> {
+    let mut moves = vec![
+        (from.to_path_buf(), to.to_path_buf()),
+        (wal_path(from), wal_path(to)),
+    ];
+    for index in indexes {
+        let (from, to) = (index_path(from, index), index_path(to, index));
+        moves.push((wal_path(&from), wal_path(&to)));
+        moves.push((from, to));
+    }
+    moves
+}
+
+fn write_journal(journal: &Path, name: &str, to: &str, indexes: &[String]) -> Result<(), DbError> {
+    let mut lines = vec![name, to];
+    lines.extend(indexes.iter().map(String::as_str));
+    let mut pending = journal.as_os_str().to_owned();
+    pending.push(".tmp");
+    let mut file = fs::File::create(&pending)?;
+    file.write_all(lines.join("\n").as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&pending, journal)?;
+    Ok(())
+}
+
+fn move_files(journal: &Path, moves: &[(PathBuf, PathBuf)]) -> Result<(), DbError> {
+    for (i, (from, to)) in moves.iter().enumerate() {
+        match fs::rename(from, to) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                for (from, to) in moves[..i].iter().rev() {
+                    if to.exists() {
+                        fs::rename(to, from)?;
+                    }
+                }
+                fs::remove_file(journal)?;
+                return Err(err.into());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read(table: &Handle) -> Result<RwLockReadGuard<'_, Table>, DbError> {
+    table
+        .read()
+        .map_err(|_| DbError::unexpected("table lock is poisoned"))
+}
+
+fn write(table: &Handle) -> Result<RwLockWriteGuard<'_, Table>, DbError> {
+    table
+        .write()
+        .map_err(|_| DbError::unexpected("table lock is poisoned"))
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -87,6 +1246,28 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn insert_unsorted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let name = "test";
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let row_type = row::row_type![ColType::int("id")];
+        storage.create(name, row_type).unwrap();
+        let rows = vec![
+            (Col::int(3), row::row![Col::int(3)]),
+            (Col::int(1), row::row![Col::int(1)]),
+            (Col::int(3), row::row![Col::int(4)]),
+        ];
+        assert_eq!(
+            Err(DbError::DuplicateKey(name.to_string(), "3".to_string())),
+            storage.insert(name, rows.clone())
+        );
+        assert!(storage.select_all(name).unwrap().is_empty());
+        assert_eq!(3, storage.upsert(name, rows).unwrap());
+        let rows = storage.select_all(name).unwrap();
+        assert_eq!(vec![row::row![Col::int(1)], row::row![Col::int(4)]], rows);
+    }
+
     #[test]
     fn select_all() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -100,4 +1281,378 @@ mod tests {
         let rows = storage.select_all(name).unwrap();
         assert_eq!(1, rows.len());
     }
+
+    #[test]
+    fn concurrent_select() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let name = "test";
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let row_type = row::row_type![ColType::int("id")];
+        storage.create(name, row_type).unwrap();
+        let rows = (0..100).map(|i| (Col::int(i), row::row![Col::int(i)]));
+        storage.insert(name, rows.collect()).unwrap();
+        let storage = &storage;
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(move || {
+                    assert_eq!(100, storage.select_all(name).unwrap().len());
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn in_memory() {
+        let name = "test";
+        let storage = Storage::in_memory();
+        let row_type = row::row_type![ColType::int("id")];
+        storage.create(name, row_type.clone()).unwrap();
+        let rows = (0..100).map(|i| (Col::int(i), row::row![Col::int(i)]));
+        storage.insert(name, rows.collect()).unwrap();
+        storage.vacuum(name).unwrap();
+        assert_eq!(row_type, storage.get_row_type(name).unwrap());
+        assert_eq!(100, storage.select_all(name).unwrap().len());
+        assert_eq!(100, storage.delete_all(name).unwrap());
+    }
+
+    #[test]
+    fn update() {
+        let name = "test";
+        let storage = Storage::in_memory();
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type).unwrap();
+        storage
+            .create_index(name, "test_age", &["age".to_string()])
+            .unwrap();
+        let rows = (0..3).map(|i| {
+            let row = Row {
+                columns: vec![Col::int(i), Col::int(i)],
+            };
+            (Col::int(i), row)
+        });
+        storage.insert(name, rows.collect()).unwrap();
+        let updates = (0..3).map(|i| {
+            let row = Row {
+                columns: vec![Col::int(i + 1), Col::int(i * 10)],
+            };
+            (Col::int(i), row)
+        });
+        assert_eq!(3, storage.update(name, updates.collect()).unwrap());
+        let ids: Vec<Col> = storage
+            .select_all(name)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.columns[0].clone())
+            .collect();
+        assert_eq!(vec![Col::int(1), Col::int(2), Col::int(3)], ids);
+        let snapshot = storage.snapshot(&BTreeSet::from([name.to_string()]));
+        let keys = snapshot
+            .unwrap()
+            .index_keys(
+                name,
+                "test_age",
+                Bound::Included(vec![Col::int(10)]),
+                Bound::Unbounded,
+                usize::MAX,
+            )
+            .unwrap();
+        assert_eq!(vec![Col::int(2), Col::int(3)], keys);
+        let duplicate = Row {
+            columns: vec![Col::int(2), Col::int(0)],
+        };
+        assert!(
+            storage
+                .update(name, vec![(Col::int(1), duplicate)])
+                .is_err()
+        );
+        assert_eq!(3, storage.select_all(name).unwrap().len());
+    }
+
+    #[test]
+    fn snapshot() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let name = "test";
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type).unwrap();
+        let rows = (0..10).map(|i| {
+            let row = Row {
+                columns: vec![Col::int(i), Col::int(i % 2)],
+            };
+            (Col::int(i), row)
+        });
+        storage.insert(name, rows.collect()).unwrap();
+        storage
+            .create_index(name, "test_age", &["age".to_string()])
+            .unwrap();
+        let snapshot = storage
+            .snapshot(&BTreeSet::from([name.to_string()]))
+            .unwrap();
+        let row = Row {
+            columns: vec![Col::int(0), Col::int(1)],
+        };
+        storage.update(name, vec![(Col::int(0), row)]).unwrap();
+        assert_eq!(10, storage.delete_all(name).unwrap());
+
+        assert_eq!(10, snapshot.scan(name, Bound::Unbounded, 20).unwrap().len());
+        let batch = snapshot
+            .scan(name, Bound::Excluded(Col::int(3)), 4)
+            .unwrap();
+        assert_eq!(
+            vec![Col::int(4), Col::int(5), Col::int(6), Col::int(7)],
+            batch
+                .into_iter()
+                .map(|row| row.columns[0].clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(Col::int(0)),
+            snapshot
+                .search(name, Col::int(0))
+                .unwrap()
+                .map(|row| row.columns[1].clone())
+        );
+        let evens = snapshot
+            .index_keys(
+                name,
+                "test_age",
+                Bound::Included(vec![Col::int(0)]),
+                Bound::Included(vec![Col::int(0)]),
+                usize::MAX,
+            )
+            .unwrap();
+        assert_eq!((0..10).step_by(2).map(Col::int).collect::<Vec<_>>(), evens);
+        assert!(snapshot.search("other", Col::int(0)).is_err());
+        assert!(storage.select_all(name).unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_maintenance() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let name = "test";
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type).unwrap();
+        let rows = (0..10).map(|i| {
+            let row = Row {
+                columns: vec![Col::int(i), Col::int(i % 2)],
+            };
+            (Col::int(i), row)
+        });
+        storage.insert(name, rows.collect()).unwrap();
+        storage
+            .create_index(name, "test_age", &["age".to_string()])
+            .unwrap();
+        assert!(
+            storage
+                .create_index(name, "test_age", &["age".to_string()])
+                .is_err()
+        );
+        let row = Row {
+            columns: vec![Col::int(0), Col::int(1)],
+        };
+        assert_eq!(
+            Err(DbError::DuplicateKey(name.to_string(), "0".to_string())),
+            storage.insert(name, vec![(Col::int(0), row.clone())])
+        );
+        storage.upsert(name, vec![(Col::int(0), row)]).unwrap();
+        drop(storage);
+
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let table = storage.table(name).unwrap();
+        let table = read(&table).unwrap();
+        assert_eq!(1, table.indexes.len());
+        let index = &table.indexes[0];
+        assert_eq!(vec![Term::column(1)], index.terms);
+        assert_eq!(
+            vec![Col::int(2), Col::int(4), Col::int(6), Col::int(8)],
+            index.index.get(&[Col::int(0)]).unwrap()
+        );
+        assert_eq!(6, index.index.get(&[Col::int(1)]).unwrap().len());
+        drop(table);
+        assert_eq!(10, storage.delete_all(name).unwrap());
+        let table = storage.table(name).unwrap();
+        let table = read(&table).unwrap();
+        assert!(
+            table.indexes[0]
+                .index
+                .get(&[Col::int(1)])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn check() {
+        let storage = Storage::in_memory();
+        let name = "test";
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type).unwrap();
+        let rows = (0..4).map(|i| {
+            let row = Row {
+                columns: vec![Col::int(i), Col::int(i * 10)],
+            };
+            (Col::int(i), row)
+        });
+        storage.insert(name, rows.collect()).unwrap();
+        storage
+            .create_index(name, "test_age", &["age".to_string()])
+            .unwrap();
+        assert!(storage.check(name).unwrap().is_empty());
+
+        {
+            let table = storage.table(name).unwrap();
+            let mut table = write(&table).unwrap();
+            let index = &mut table.indexes[0].index;
+            index.remove(&[Col::int(10)], &Col::int(1)).unwrap();
+            index.insert(vec![Col::int(70)], Col::int(7)).unwrap();
+            table
+                .btree
+                .insert(
+                    Col::int(9),
+                    Row {
+                        columns: vec![Col::int(9), Col::varchar("nine", 4)],
+                    },
+                )
+                .unwrap();
+        }
+        assert_eq!(
+            vec![
+                (
+                    "index",
+                    "index 'test_age' has entry 70 for key 7 not found in the table".to_string()
+                ),
+                (
+                    "index",
+                    "index 'test_age' is missing entry 10 for key 1".to_string()
+                ),
+                (
+                    "index",
+                    "index 'test_age' is missing entry nine for key 9".to_string()
+                ),
+                (
+                    "row",
+                    "key 9: invalid value 'nine' for field 'age'".to_string()
+                ),
+            ],
+            {
+                let mut problems = storage.check(name).unwrap();
+                problems.sort();
+                problems
+            }
+        );
+        assert_eq!(
+            Err(DbError::TableNotFound("missing".to_string())),
+            storage.check("missing")
+        );
+    }
+
+    #[test]
+    fn drop_table() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let name = "test";
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type.clone()).unwrap();
+        storage
+            .create_index(name, "test_age", &["age".to_string()])
+            .unwrap();
+        let row = Row {
+            columns: vec![Col::int(1), Col::int(30)],
+        };
+        storage.insert(name, vec![(Col::int(1), row)]).unwrap();
+        let handle = storage.table(name).unwrap();
+        assert!(Arc::ptr_eq(&handle, &storage.table(name).unwrap()));
+
+        assert_eq!(1, storage.drop_table(name).unwrap());
+        assert!(!temp_dir.path().join(name).exists());
+        assert!(!temp_dir.path().join(index_file(name, "test_age")).exists());
+        assert!(!Arc::ptr_eq(&handle, &storage.table(name).unwrap()));
+        assert!(storage.get_row_type(name).unwrap().columns.is_empty());
+        assert_eq!(
+            Err(DbError::TableNotFound("other".to_string())),
+            storage.drop_table("other")
+        );
+
+        storage.create(name, row_type).unwrap();
+        assert!(storage.select_all(name).unwrap().is_empty());
+        assert!(storage.indexes(name).unwrap().is_empty());
+    }
+
+    fn indexed_table(storage: &Storage, name: &str) {
+        let row_type = RowType {
+            columns: vec![ColType::int("id"), ColType::int("age")],
+            constraints: vec![],
+        };
+        storage.create(name, row_type).unwrap();
+        storage
+            .create_index(name, "age", &["age".to_string()])
+            .unwrap();
+        let row = Row {
+            columns: vec![Col::int(1), Col::int(30)],
+        };
+        storage.insert(name, vec![(Col::int(1), row)]).unwrap();
+        storage.flush().unwrap();
+    }
+
+    #[test]
+    fn rename_rollback() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        indexed_table(&storage, "test");
+        fs::write(
+            wal_path(&temp_dir.path().join(index_file("test", "age"))),
+            b"",
+        )
+        .unwrap();
+        let blocker = wal_path(&temp_dir.path().join(index_file("users", "age")));
+        fs::create_dir(&blocker).unwrap();
+        fs::write(blocker.join("file"), b"").unwrap();
+
+        assert!(storage.rename_table("test", "users").is_err());
+        assert!(!temp_dir.path().join(RENAME_FILE).exists());
+        assert!(!temp_dir.path().join("users").exists());
+        assert_eq!(1, storage.select_all("test").unwrap().len());
+        assert_eq!(1, storage.indexes("test").unwrap().len());
+    }
+
+    #[test]
+    fn rename_recovery() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        indexed_table(&storage, "test");
+        drop(storage);
+
+        let journal = temp_dir.path().join(RENAME_FILE);
+        let indexes = vec!["age".to_string()];
+        write_journal(&journal, "test", "users", &indexes).unwrap();
+        let moves = file_moves(
+            &temp_dir.path().join("test"),
+            &temp_dir.path().join("users"),
+            &indexes,
+        );
+        fs::rename(&moves[0].0, &moves[0].1).unwrap();
+
+        let storage = Storage::new(temp_dir.path()).unwrap();
+        assert!(!journal.exists());
+        assert_eq!(vec!["users".to_string()], storage.tables(None).unwrap());
+        assert_eq!(1, storage.select_all("users").unwrap().len());
+        assert_eq!("age", storage.indexes("users").unwrap()[0].0);
+        assert!(!temp_dir.path().join(index_file("test", "age")).exists());
+    }
 }
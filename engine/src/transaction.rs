@@ -0,0 +1,187 @@
+use std::{cmp::Ordering, collections::BTreeMap, ops::Bound};
+
+use row::{Col, Row};
+
+use crate::{eval::Term, plan::compare};
+
+pub(crate) type WriteSet = BTreeMap<Col, Option<Row>>;
+
+pub(crate) struct Transaction {
+    id: u64,
+    writes: BTreeMap<String, WriteSet>,
+}
+
+impl Transaction {
+    pub(crate) fn new(id: u64) -> Self {
+        Self {
+            id,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn get(&self, table: &str, key: &Col) -> Option<Option<&Row>> {
+        self.writes.get(table)?.get(key).map(Option::as_ref)
+    }
+
+    pub(crate) fn put(&mut self, table: &str, key: Col, row: Row) {
+        self.write_set(table).insert(key, Some(row));
+    }
+
+    pub(crate) fn delete(&mut self, table: &str, key: Col) {
+        self.write_set(table).insert(key, None);
+    }
+
+    pub(crate) fn into_writes(self) -> BTreeMap<String, WriteSet> {
+        self.writes
+    }
+
+    pub(crate) fn scan(&self, table: &str, rows: Vec<Row>) -> Vec<Row> {
+        match self.writes.get(table) {
+            Some(writes) => overlay(writes, (Bound::Unbounded, Bound::Unbounded), rows),
+            None => rows,
+        }
+    }
+
+    pub(crate) fn writes(&self, table: &str) -> Option<&WriteSet> {
+        self.writes.get(table)
+    }
+
+    pub(crate) fn index_scan(
+        &self,
+        table: &str,
+        terms: &[Term],
+        prefix: &[Col],
+        from: &Bound<Col>,
+        to: &Bound<Col>,
+        rows: Vec<Row>,
+    ) -> Vec<Row> {
+        let Some(writes) = self.writes.get(table) else {
+            return rows;
+        };
+        let mut rows: Vec<Row> = rows
+            .into_iter()
+            .filter(|row| !writes.contains_key(&row.columns[0]))
+            .collect();
+        let staged = writes.values().flatten();
+        rows.extend(
+            staged
+                .filter(|row| {
+                    let key: Vec<Col> = terms
+                        .iter()
+                        .map(|term| term.evaluate(&row.columns))
+                        .collect();
+                    key.iter()
+                        .zip(prefix)
+                        .all(|(key, value)| compare(key, value).is_eq())
+                        && in_range(&key[prefix.len()], from, to)
+                })
+                .cloned(),
+        );
+        let order = &terms[prefix.len()..];
+        rows.sort_by(|a, b| {
+            order
+                .iter()
+                .map(|term| compare(&term.evaluate(&a.columns), &term.evaluate(&b.columns)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.columns[0].cmp(&b.columns[0]))
+        });
+        rows
+    }
+
+    fn write_set(&mut self, table: &str) -> &mut WriteSet {
+        self.writes.entry(table.to_string()).or_default()
+    }
+}
+
+pub(crate) fn overlay(
+    writes: &WriteSet,
+    range: (Bound<Col>, Bound<Col>),
+    rows: Vec<Row>,
+) -> Vec<Row> {
+    let mut merged: BTreeMap<Col, Row> = rows
+        .into_iter()
+        .map(|row| (row.columns[0].clone(), row))
+        .collect();
+    for (key, row) in writes.range(range) {
+        match row {
+            Some(row) => merged.insert(key.clone(), row.clone()),
+            None => merged.remove(key),
+        };
+    }
+    merged.into_values().collect()
+}
+
+fn in_range(value: &Col, from: &Bound<Col>, to: &Bound<Col>) -> bool {
+    let lower = match from {
+        Bound::Included(from) => compare(value, from).is_ge(),
+        Bound::Excluded(from) => compare(value, from).is_gt(),
+        Bound::Unbounded => true,
+    };
+    let upper = match to {
+        Bound::Included(to) => compare(value, to).is_le(),
+        Bound::Excluded(to) => compare(value, to).is_lt(),
+        Bound::Unbounded => true,
+    };
+    lower && upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i32, age: i32) -> Row {
+        Row {
+            columns: vec![Col::int(id), Col::int(age)],
+        }
+    }
+
+    #[test]
+    fn overlay() {
+        let mut transaction = Transaction::new(1);
+        transaction.put("t", Col::int(2), row(2, 40));
+        transaction.put("t", Col::int(4), row(4, 20));
+        transaction.delete("t", Col::int(3));
+        assert_eq!(Some(Some(&row(2, 40))), transaction.get("t", &Col::int(2)));
+        assert_eq!(Some(None), transaction.get("t", &Col::int(3)));
+        assert_eq!(None, transaction.get("t", &Col::int(1)));
+        assert_eq!(None, transaction.get("other", &Col::int(2)));
+
+        let stored = vec![row(1, 20), row(2, 30), row(3, 20)];
+        assert_eq!(
+            vec![row(1, 20), row(2, 40), row(4, 20)],
+            transaction.scan("t", stored.clone())
+        );
+        assert_eq!(stored, transaction.scan("other", stored.clone()));
+        let writes = transaction.writes("t").unwrap();
+        let head = (Bound::Unbounded, Bound::Included(Col::int(3)));
+        assert_eq!(
+            vec![row(1, 20), row(2, 40)],
+            super::overlay(writes, head, stored.clone())
+        );
+
+        let from = Bound::Included(Col::int(20));
+        let to = Bound::Included(Col::int(20));
+        assert_eq!(
+            vec![row(1, 20), row(4, 20)],
+            transaction.index_scan(
+                "t",
+                &[Term::column(1)],
+                &[],
+                &from,
+                &to,
+                vec![row(1, 20), row(3, 20)]
+            )
+        );
+        let terms = [Term::column(0), Term::column(1)];
+        assert_eq!(
+            vec![row(4, 20)],
+            transaction.index_scan("t", &terms, &[Col::int(4)], &from, &to, vec![])
+        );
+        assert_eq!(1, transaction.into_writes().len());
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use btree::BTree;
+use common::error::DbError;
+use row::{Col, ColType, Row, RowType};
+
+use crate::storage;
+
+const NAME_SIZE: u16 = 255;
+const DEFINITION_SIZE: u16 = 2048;
+
+pub(crate) struct Views {
+    path: Option<PathBuf>,
+    read_only: bool,
+    catalog: Mutex<Option<Catalog>>,
+}
+
+struct Catalog {
+    btree: BTree,
+    definitions: BTreeMap<String, String>,
+}
+
+impl Views {
+    pub(crate) fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            read_only: false,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn read_only(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            read_only: true,
+            catalog: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn create(&self, name: &str, definition: &str) -> Result<(), DbError> {
+        self.with(|catalog| {
+            if catalog.definitions.contains_key(name) {
+                return Err(DbError::InvalidInput(format!(
+                    "view '{}' already exists",
+                    name
+                )));
+            }
+            let key = column("name", name, NAME_SIZE)?;
+            let row = Row {
+                columns: vec![
+                    key.clone(),
+                    column("definition", definition, DEFINITION_SIZE)?,
+                ],
+            };
+            catalog.btree.insert(key, row)?;
+            catalog
+                .definitions
+                .insert(name.to_string(), definition.to_string());
+            Ok(())
+        })
+    }
+
+    pub(crate) fn drop(&self, name: &str) -> Result<(), DbError> {
+        self.with(|catalog| {
+            if catalog.definitions.remove(name).is_none() {
+                return Err(DbError::InvalidInput(format!(
+                    "view '{}' doesn't exist",
+                    name
+                )));
+            }
+            catalog.btree.delete(column("name", name, NAME_SIZE)?)?;
+            Ok(())
+        })
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<Option<String>, DbError> {
+        if !self.exists()? {
+            return Ok(None);
+        }
+        self.with(|catalog| Ok(catalog.definitions.get(name).cloned()))
+    }
+
+    pub(crate) fn list(&self) -> Result<Vec<(String, String)>, DbError> {
+        if !self.exists()? {
+            return Ok(vec![]);
+        }
+        self.with(|catalog| {
+            Ok(catalog
+                .definitions
+                .iter()
+                .map(|(name, definition)| (name.clone(), definition.clone()))
+                .collect())
+        })
+    }
+
+    fn exists(&self) -> Result<bool, DbError> {
+        let catalog = self
+            .catalog
+            .lock()
+            .map_err(|_| DbError::unexpected("views lock is poisoned"))?;
+        Ok(catalog.is_some() || self.path.as_deref().is_none_or(Path::is_file))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Catalog) -> Result<T, DbError>) -> Result<T, DbError> {
+        let mut catalog = self
+            .catalog
+            .lock()
+            .map_err(|_| DbError::unexpected("views lock is poisoned"))?;
+        if catalog.is_none() {
+            *catalog = Some(Catalog::open(self.path.as_deref(), self.read_only)?);
+        }
+        match catalog.as_mut() {
+            Some(catalog) => f(catalog),
+            None => Err(DbError::unexpected("view catalog is not open")),
+        }
+    }
+}
+
+impl Catalog {
+    fn open(path: Option<&Path>, read_only: bool) -> Result<Self, DbError> {
+        let mut btree = storage::open_catalog(path, read_only)?;
+        if btree.get_structure()?.columns.is_empty() {
+            btree.set_structure(RowType {
+                columns: vec![
+                    ColType::varchar("name", NAME_SIZE),
+                    ColType::varchar("definition", DEFINITION_SIZE),
+                ],
+                constraints: vec![],
+            })?;
+        }
+        let definitions = btree
+            .select_all()?
+            .into_iter()
+            .map(|row: Row| (row.columns[0].to_string(), row.columns[1].to_string()))
+            .collect();
+        Ok(Self { btree, definitions })
+    }
+}
+
+fn column(field: &str, value: &str, size: u16) -> Result<Col, DbError> {
+    if value.len() > size as usize {
+        return Err(DbError::TooLong(
+            field.to_string(),
+            "views".to_string(),
+            value.len(),
+            size as usize,
+        ));
+    }
+    Ok(Col::varchar(value, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("catalog.view");
+        let views = Views::new(Some(path.clone()));
+        assert_eq!(None, views.get("adults").unwrap());
+        assert!(!path.exists());
+        views
+            .create("adults", "CREATE VIEW adults AS SELECT id FROM users")
+            .unwrap();
+        assert_eq!(
+            Err(DbError::invalid_input("view 'adults' already exists")),
+            views.create("adults", "")
+        );
+        drop(views);
+
+        let views = Views::new(Some(path));
+        assert_eq!(
+            vec![(
+                "adults".to_string(),
+                "CREATE VIEW adults AS SELECT id FROM users".to_string()
+            )],
+            views.list().unwrap()
+        );
+        views.drop("adults").unwrap();
+        assert_eq!(None, views.get("adults").unwrap());
+        assert_eq!(
+            Err(DbError::invalid_input("view 'adults' doesn't exist")),
+            views.drop("adults")
+        );
+    }
+}
@@ -2,28 +2,183 @@ use core::fmt;
 use std::str::FromStr;
 
 use common::error::DbError;
-use row::ColType;
+use row::{ColType, Constraint};
 
 use crate::token::Token;
 
+const AGGREGATES: [&str; 5] = ["count", "sum", "avg", "min", "max"];
+const FUNCTIONS: [&str; 2] = ["lower", "upper"];
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Command {
     Create {
         name: String,
         fields: Vec<ColType>,
+        constraints: Vec<Constraint>,
+        temporary: bool,
+    },
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<String>,
+    },
+    CreateSequence {
+        name: String,
+        start: i64,
+    },
+    CreateView {
+        name: String,
+        columns: Vec<String>,
+        query: Box<Command>,
+    },
+    CreateDatabase {
+        name: String,
+    },
+    Use {
+        database: String,
     },
     Insert {
         table: String,
         fields: Vec<String>,
         values: Vec<Vec<String>>,
+        replace: bool,
     },
     Select {
+        distinct: bool,
         fields: Vec<String>,
         table: String,
+        conditions: Vec<Comparison>,
+        group_by: Vec<String>,
+        having: Vec<Comparison>,
+        order_by: Vec<OrderBy>,
+        limit: Option<usize>,
+    },
+    Update {
+        table: String,
+        assignments: Vec<Assignment>,
+        conditions: Vec<Comparison>,
     },
     Delete {
         table: String,
     },
+    Drop {
+        table: String,
+    },
+    DropSequence {
+        name: String,
+    },
+    DropView {
+        name: String,
+    },
+    RenameTable {
+        table: String,
+        name: String,
+    },
+    NextVal {
+        sequence: String,
+    },
+    Vacuum {
+        table: Option<String>,
+    },
+    Analyze {
+        table: Option<String>,
+    },
+    ShowTableStatus {
+        table: String,
+    },
+    CheckTable {
+        table: String,
+    },
+    Explain {
+        command: Box<Command>,
+        analyze: bool,
+    },
+    CopyFrom {
+        table: String,
+        path: String,
+        options: CopyOptions,
+    },
+    CopyTo {
+        query: Box<Command>,
+        path: String,
+        options: CopyOptions,
+    },
+    Dump {
+        path: String,
+    },
+    Restore {
+        path: String,
+    },
+    Begin,
+    Commit,
+    Rollback,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CopyOptions {
+    pub header: bool,
+    pub delimiter: char,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            header: false,
+            delimiter: ',',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Comparison {
+    pub column: String,
+    pub op: Operator,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OrderBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl OrderBy {
+    pub fn asc(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            descending: false,
+        }
+    }
+
+    pub fn desc(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            descending: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expr {
+    Value(String),
+    Column(String),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Assignment {
+    pub column: String,
+    pub value: Expr,
 }
 
 impl Command {
@@ -34,9 +189,24 @@ impl Command {
         let idx = 1;
         match tokens.first().unwrap() {
             Token::Create => Self::parse_create(tokens, idx),
+            Token::Drop => Self::parse_drop(tokens, idx),
             Token::Insert => Self::parse_insert(tokens, idx),
             Token::Select => Self::parse_select(tokens, idx),
             Token::Delete => Self::parse_delete(tokens, idx),
+            Token::Update => Self::parse_update(tokens, idx),
+            Token::Vacuum => Self::parse_vacuum(tokens, idx),
+            Token::Analyze => Self::parse_analyze(tokens, idx),
+            Token::Show => Self::parse_show(tokens, idx),
+            Token::Explain => Self::parse_explain(tokens, idx),
+            Token::Copy => Self::parse_copy(tokens, idx),
+            Token::Dump => Self::parse_path(tokens, idx, Token::To),
+            Token::Restore => Self::parse_path(tokens, idx, Token::From),
+            Token::Begin => Self::parse_transaction(tokens, idx, Command::Begin),
+            Token::Commit => Self::parse_transaction(tokens, idx, Command::Commit),
+            Token::Rollback => Self::parse_transaction(tokens, idx, Command::Rollback),
+            token if is_keyword(Some(token), "use") => Self::parse_use(tokens, idx),
+            token if is_keyword(Some(token), "check") => Self::parse_check(tokens, idx),
+            token if is_keyword(Some(token), "alter") => Self::parse_alter(tokens, idx),
             other => Err(DbError::InvalidInput(format!(
                 "unexpected symbol: {}",
                 other
@@ -45,8 +215,26 @@ impl Command {
     }
 
     fn parse_create(tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        let temporary =
+            is_keyword(tokens.get(idx), "temp") || is_keyword(tokens.get(idx), "temporary");
+        if temporary {
+            idx += 1;
+            if tokens.get(idx) != Some(&Token::Table) {
+                return Err(DbError::invalid_input("expected 'TABLE' specifier"));
+            }
+        }
         match tokens.get(idx) {
             Some(Token::Table) => {}
+            Some(Token::Index) => return Self::parse_create_index(tokens, idx + 1),
+            Some(token) if is_keyword(Some(token), "sequence") => {
+                return Self::parse_create_sequence(tokens, idx + 1);
+            }
+            Some(token) if is_keyword(Some(token), "view") => {
+                return Self::parse_create_view(tokens, idx + 1);
+            }
+            Some(token) if is_keyword(Some(token), "database") => {
+                return Self::parse_create_database(tokens, idx + 1);
+            }
             Some(token) => {
                 return Err(DbError::InvalidInput(format!(
                     "unexpected symbol: {}",
@@ -70,6 +258,7 @@ impl Command {
         check_delimeter(tokens.get(idx), '(')?;
         idx += 1;
         let mut fields = vec![];
+        let mut constraints = vec![];
         let len = tokens.len();
         let Some(Token::Delimiter(')')) = tokens.last() else {
             return Err(DbError::invalid_input("expect: ')'"));
@@ -102,13 +291,172 @@ impl Command {
                     )));
                 }
             };
+            loop {
+                if is_keyword(tokens.get(idx), "not") {
+                    if !is_keyword(tokens.get(idx + 1), "null") {
+                        return Err(DbError::invalid_input("expected 'NULL' after 'NOT'"));
+                    }
+                    constraints.push(Constraint::NotNull(field_name.clone()));
+                    idx += 2;
+                } else if is_keyword(tokens.get(idx), "auto_increment") {
+                    constraints.push(Constraint::AutoIncrement(field_name.clone()));
+                    idx += 1;
+                } else if is_keyword(tokens.get(idx), "default") {
+                    let Some(Token::Element(value)) = tokens.get(idx + 1) else {
+                        return Err(DbError::invalid_input("expected DEFAULT value"));
+                    };
+                    idx += 2;
+                    match tokens.get(idx..idx + 2) {
+                        Some([Token::Delimiter('('), Token::Delimiter(')')])
+                            if value.eq_ignore_ascii_case("now") =>
+                        {
+                            constraints.push(Constraint::Now(field_name.clone()));
+                            idx += 2;
+                        }
+                        _ => {
+                            constraints.push(Constraint::Default(field_name.clone(), value.clone()))
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
             fields.push(field);
             idx += 1;
         }
-        Ok(Self::Create { name, fields })
+        Ok(Self::Create {
+            name,
+            fields,
+            constraints,
+            temporary,
+        })
+    }
+
+    fn parse_create_sequence(tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        let Some(Token::Element(name)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'sequence_name' specifier"));
+        };
+        idx += 1;
+        let start = match tokens.get(idx) {
+            None => 1,
+            Some(token) if is_keyword(Some(token), "start") => get_num(tokens.get(idx + 1))?,
+            Some(token) => {
+                return Err(DbError::InvalidInput(format!(
+                    "unexpected token: {}",
+                    token
+                )));
+            }
+        };
+        if tokens.len() > idx + 2 {
+            return Err(DbError::invalid_input("invalid create sequence statement"));
+        }
+        Ok(Self::CreateSequence {
+            name: name.clone(),
+            start,
+        })
+    }
+
+    fn parse_create_view(mut tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        let Some(Token::Element(name)) = tokens.get(idx).cloned() else {
+            return Err(DbError::invalid_input("expected 'view_name' specifier"));
+        };
+        idx += 1;
+        let mut columns = vec![];
+        if tokens.get(idx) == Some(&Token::Delimiter('(')) {
+            idx += 1;
+            loop {
+                let Some(Token::Element(column)) = tokens.get(idx) else {
+                    return Err(DbError::invalid_input("expected column name"));
+                };
+                columns.push(column.clone());
+                idx += 1;
+                match tokens.get(idx) {
+                    Some(Token::Delimiter(',')) => idx += 1,
+                    Some(Token::Delimiter(')')) => break,
+                    _ => return Err(DbError::invalid_input("expect: ')'")),
+                }
+            }
+            idx += 1;
+        }
+        if !is_keyword(tokens.get(idx), "as") {
+            return Err(DbError::invalid_input("expected 'AS' clause"));
+        }
+        let query = match tokens.get(idx + 1) {
+            Some(Token::Select) => Self::parse(tokens.split_off(idx + 1))?,
+            _ => return Err(DbError::invalid_input("only SELECT can define a view")),
+        };
+        if !matches!(query, Command::Select { .. }) {
+            return Err(DbError::invalid_input("only SELECT can define a view"));
+        }
+        Ok(Self::CreateView {
+            name,
+            columns,
+            query: Box::new(query),
+        })
+    }
+
+    fn parse_create_database(tokens: Vec<Token>, idx: usize) -> Result<Command, DbError> {
+        if tokens.len() != 3 {
+            return Err(DbError::invalid_input("invalid create database statement"));
+        }
+        let Some(Token::Element(name)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'database_name' specifier"));
+        };
+        Ok(Self::CreateDatabase {
+            name: name.to_string(),
+        })
+    }
+
+    fn parse_create_index(tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        let tokens = fold_calls(tokens);
+        if tokens.len() < 8 {
+            return Err(DbError::invalid_input("invalid create index statement"));
+        }
+        let Some(Token::Element(name)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'index_name' specifier"));
+        };
+        idx += 1;
+        let Some(Token::On) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'ON' clause"));
+        };
+        idx += 1;
+        let Some(Token::Element(table)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'table_name' specifier"));
+        };
+        idx += 1;
+        check_delimeter(tokens.get(idx), '(')?;
+        let mut columns = Vec::new();
+        loop {
+            idx += 1;
+            let Some(Token::Element(column)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected column name"));
+            };
+            columns.push(column.clone());
+            idx += 1;
+            if check_delimeter(tokens.get(idx), ',').is_err() {
+                break;
+            }
+        }
+        check_delimeter(tokens.get(idx), ')')?;
+        if idx + 1 != tokens.len() {
+            return Err(DbError::invalid_input("invalid create index statement"));
+        }
+        Ok(Self::CreateIndex {
+            name: name.clone(),
+            table: table.clone(),
+            columns,
+        })
     }
 
     fn parse_insert(tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        let replace = is_keyword(tokens.get(idx), "or");
+        if replace {
+            idx += 1;
+            if !is_keyword(tokens.get(idx), "replace") {
+                return Err(DbError::invalid_input("expected 'REPLACE' after 'OR'"));
+            }
+            idx += 1;
+        }
         let Some(Token::Into) = tokens.get(idx) else {
             return Err(DbError::invalid_input("expected INTO"));
         };
@@ -180,15 +528,53 @@ impl Command {
             idx += 1;
             values.push(sub_values);
             sub_values = Vec::with_capacity(fields_len);
+            if idx + 1 < len && tokens.get(idx) == Some(&Token::Delimiter(',')) {
+                idx += 1;
+            }
         }
         Ok(Self::Insert {
             table: table_name.clone(),
             fields,
             values,
+            replace,
         })
     }
 
     fn parse_select(tokens: Vec<Token>, mut idx: usize) -> Result<Command, DbError> {
+        if let [
+            _,
+            name,
+            Token::Delimiter('('),
+            Token::Element(sequence),
+            Token::Delimiter(')'),
+        ] = tokens.as_slice()
+            && is_keyword(Some(name), "nextval")
+        {
+            return Ok(Command::NextVal {
+                sequence: sequence.clone(),
+            });
+        }
+        let mut tokens = fold_calls(tokens);
+        let limit = match split_clause(&mut tokens, Token::Limit) {
+            Some(clause) => Some(Self::parse_limit(&clause)?),
+            None => None,
+        };
+        let order_by = match split_clause(&mut tokens, Token::Order) {
+            Some(clause) => Self::parse_order_by(&clause)?,
+            None => vec![],
+        };
+        let having = match split_clause(&mut tokens, Token::Having) {
+            Some(clause) => Self::parse_where(&clause, 0)?,
+            None => vec![],
+        };
+        let group_by = match split_clause(&mut tokens, Token::Group) {
+            Some(clause) => Self::parse_by(&clause, "GROUP")?,
+            None => vec![],
+        };
+        let distinct = tokens.get(idx) == Some(&Token::Distinct);
+        if distinct {
+            idx += 1;
+        }
         let mut fields = Vec::new();
         let len = tokens.len();
         let mut token = None::<Token>;
@@ -240,12 +626,142 @@ impl Command {
         let Some(Token::Element(table)) = tokens.get(idx) else {
             return Err(DbError::invalid_input("missing FROM specifier"));
         };
+        idx += 1;
+        let conditions = match tokens.get(idx) {
+            Some(Token::Where) => Self::parse_where(&tokens, idx + 1)?,
+            Some(token) => {
+                return Err(DbError::InvalidInput(format!(
+                    "unexpected token: {}",
+                    token
+                )));
+            }
+            None => vec![],
+        };
         Ok(Self::Select {
+            distinct,
             fields,
             table: table.to_string(),
+            conditions,
+            group_by,
+            having,
+            order_by,
+            limit,
         })
     }
 
+    fn parse_limit(tokens: &[Token]) -> Result<usize, DbError> {
+        match tokens {
+            [Token::Element(limit)] => limit
+                .parse()
+                .map_err(|_| DbError::InvalidInput(format!("invalid LIMIT: {}", limit))),
+            [] => Err(DbError::eof("expected LIMIT value")),
+            [_, token, ..] | [token] => Err(DbError::InvalidInput(format!(
+                "unexpected token: {}",
+                token
+            ))),
+        }
+    }
+
+    fn parse_by(tokens: &[Token], clause: &str) -> Result<Vec<String>, DbError> {
+        let Some(Token::By) = tokens.first() else {
+            return Err(DbError::InvalidInput(format!(
+                "expected 'BY' after '{}'",
+                clause
+            )));
+        };
+        let mut columns = Vec::new();
+        let mut idx = 1;
+        loop {
+            let Some(Token::Element(column)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected column name"));
+            };
+            columns.push(column.clone());
+            idx += 1;
+            match tokens.get(idx) {
+                Some(Token::Delimiter(',')) => idx += 1,
+                Some(token) => {
+                    return Err(DbError::InvalidInput(format!(
+                        "unexpected token: {}",
+                        token
+                    )));
+                }
+                None => return Ok(columns),
+            }
+        }
+    }
+
+    fn parse_order_by(tokens: &[Token]) -> Result<Vec<OrderBy>, DbError> {
+        let Some(Token::By) = tokens.first() else {
+            return Err(DbError::invalid_input("expected 'BY' after 'ORDER'"));
+        };
+        let mut keys = Vec::new();
+        let mut idx = 1;
+        loop {
+            let Some(Token::Element(column)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected column name"));
+            };
+            idx += 1;
+            let descending = is_keyword(tokens.get(idx), "DESC");
+            if descending || is_keyword(tokens.get(idx), "ASC") {
+                idx += 1;
+            }
+            keys.push(OrderBy {
+                column: column.clone(),
+                descending,
+            });
+            match tokens.get(idx) {
+                Some(Token::Delimiter(',')) => idx += 1,
+                Some(token) => {
+                    return Err(DbError::InvalidInput(format!(
+                        "unexpected token: {}",
+                        token
+                    )));
+                }
+                None => return Ok(keys),
+            }
+        }
+    }
+
+    fn parse_where(tokens: &[Token], mut idx: usize) -> Result<Vec<Comparison>, DbError> {
+        let mut conditions = Vec::new();
+        loop {
+            let Some(Token::Element(column)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected column name"));
+            };
+            idx += 1;
+            let op = match tokens.get(idx) {
+                Some(Token::Operator(op)) => Operator::parse(op)?,
+                Some(token) => {
+                    return Err(DbError::InvalidInput(format!(
+                        "expected operator, found: {}",
+                        token
+                    )));
+                }
+                None => return Err(DbError::eof("expected operator")),
+            };
+            idx += 1;
+            let Some(Token::Element(value)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected value"));
+            };
+            idx += 1;
+            conditions.push(Comparison {
+                column: column.clone(),
+                op,
+                value: value.clone(),
+            });
+            match tokens.get(idx) {
+                Some(Token::And) => idx += 1,
+                Some(token) => {
+                    return Err(DbError::InvalidInput(format!(
+                        "unexpected token: {}",
+                        token
+                    )));
+                }
+                None => return Ok(conditions),
+            }
+        }
+    }
+
     fn parse_delete(tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
         if tokens.len() != 3 {
             return Err(DbError::invalid_input("invalid delete statement"));
@@ -261,28 +777,434 @@ impl Command {
             table: table.to_string(),
         })
     }
+
+    fn parse_update(tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
+        let tokens = fold_calls(tokens);
+        let Some(Token::Element(table)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        idx += 1;
+        let Some(Token::Set) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'SET' clause"));
+        };
+        idx += 1;
+        let mut assignments = Vec::new();
+        loop {
+            let Some(Token::Element(column)) = tokens.get(idx) else {
+                return Err(DbError::invalid_input("expected column name"));
+            };
+            idx += 1;
+            match tokens.get(idx) {
+                Some(Token::Operator(op)) if op == "=" => {}
+                _ => return Err(DbError::invalid_input("expected '='")),
+            }
+            idx += 1;
+            let value = match (tokens.get(idx), tokens.get(idx + 1), tokens.get(idx + 2)) {
+                (
+                    Some(Token::Element(left)),
+                    Some(Token::Element(op)),
+                    Some(Token::Element(right)),
+                ) if is_arithmetic(op) => {
+                    idx += 3;
+                    Expr::Binary(
+                        Box::new(Expr::operand(left)),
+                        op.chars().next().unwrap(),
+                        Box::new(Expr::operand(right)),
+                    )
+                }
+                (Some(Token::Element(value)), _, _) => {
+                    idx += 1;
+                    Expr::Value(value.clone())
+                }
+                _ => return Err(DbError::invalid_input("expected value")),
+            };
+            assignments.push(Assignment {
+                column: column.clone(),
+                value,
+            });
+            match tokens.get(idx) {
+                Some(Token::Delimiter(',')) => idx += 1,
+                _ => break,
+            }
+        }
+        let conditions = match tokens.get(idx) {
+            Some(Token::Where) => Self::parse_where(&tokens, idx + 1)?,
+            Some(token) => {
+                return Err(DbError::InvalidInput(format!(
+                    "unexpected token: {}",
+                    token
+                )));
+            }
+            None => vec![],
+        };
+        Ok(Command::Update {
+            table: table.to_string(),
+            assignments,
+            conditions,
+        })
+    }
+
+    fn parse_drop(tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
+        if tokens.len() != 3 {
+            return Err(DbError::invalid_input("invalid drop statement"));
+        }
+        let specifier = tokens.get(idx);
+        idx += 1;
+        let Some(Token::Element(name)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        let name = name.to_string();
+        Ok(match specifier {
+            Some(Token::Table) => Command::Drop { table: name },
+            token if is_keyword(token, "sequence") => Command::DropSequence { name },
+            token if is_keyword(token, "view") => Command::DropView { name },
+            _ => return Err(DbError::invalid_input("expected 'TABLE' specifier")),
+        })
+    }
+
+    fn parse_copy(mut tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
+        if tokens.get(idx) == Some(&Token::Delimiter('(')) {
+            let mut depth = 0;
+            let end = tokens[idx..]
+                .iter()
+                .position(|token| {
+                    match token {
+                        Token::Delimiter('(') => depth += 1,
+                        Token::Delimiter(')') => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .ok_or_else(|| DbError::eof("expected ')'"))?
+                + idx;
+            let rest = tokens.split_off(end + 1);
+            tokens.truncate(end);
+            let query = Self::parse(tokens.split_off(idx + 1))?;
+            if !matches!(query, Command::Select { .. }) {
+                return Err(DbError::invalid_input("only SELECT can be copied"));
+            }
+            let [Token::To, Token::Element(path), options @ ..] = rest.as_slice() else {
+                return Err(DbError::invalid_input("expected 'TO' file path"));
+            };
+            return Ok(Command::CopyTo {
+                query: Box::new(query),
+                path: path.to_string(),
+                options: Self::parse_copy_options(options)?,
+            });
+        }
+        let Some(Token::Element(table)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        idx += 1;
+        let Some(Token::From) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'FROM' clause"));
+        };
+        idx += 1;
+        let Some(Token::Element(path)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected file path"));
+        };
+        idx += 1;
+        Ok(Command::CopyFrom {
+            table: table.to_string(),
+            path: path.to_string(),
+            options: Self::parse_copy_options(&tokens[idx..])?,
+        })
+    }
+
+    fn parse_copy_options(tokens: &[Token]) -> Result<CopyOptions, DbError> {
+        let mut options = CopyOptions::default();
+        let Some((first, mut tokens)) = tokens.split_first() else {
+            return Ok(options);
+        };
+        if *first != Token::Delimiter('(') {
+            return Err(DbError::InvalidInput(format!(
+                "unexpected token: {}",
+                first
+            )));
+        }
+        loop {
+            let [Token::Element(name), Token::Element(value), rest @ ..] = tokens else {
+                return Err(DbError::invalid_input("expected COPY option"));
+            };
+            match name.to_lowercase().as_str() {
+                "header" => {
+                    options.header = value
+                        .to_lowercase()
+                        .parse()
+                        .map_err(|_| DbError::InvalidInput(format!("invalid HEADER: {}", value)))?;
+                }
+                "delimiter" => {
+                    let mut chars = value.chars();
+                    let (Some(delimiter), None) = (chars.next(), chars.next()) else {
+                        return Err(DbError::invalid_input(
+                            "DELIMITER must be a single character",
+                        ));
+                    };
+                    options.delimiter = delimiter;
+                }
+                _ => {
+                    return Err(DbError::InvalidInput(format!(
+                        "unknown COPY option: {}",
+                        name
+                    )));
+                }
+            }
+            match rest {
+                [Token::Delimiter(','), rest @ ..] => tokens = rest,
+                [Token::Delimiter(')')] => return Ok(options),
+                [token, ..] => {
+                    return Err(DbError::InvalidInput(format!(
+                        "unexpected token: {}",
+                        token
+                    )));
+                }
+                [] => return Err(DbError::eof("expected ')'")),
+            }
+        }
+    }
+
+    fn parse_path(tokens: Vec<Token>, idx: usize, clause: Token) -> Result<Self, DbError> {
+        let [keyword, Token::Element(path)] = &tokens[idx..] else {
+            return Err(DbError::InvalidInput(format!(
+                "expected '{}' file path",
+                clause
+            )));
+        };
+        if *keyword != clause {
+            return Err(DbError::InvalidInput(format!(
+                "unexpected token: {}",
+                keyword
+            )));
+        }
+        let path = path.to_string();
+        Ok(match tokens[0] {
+            Token::Dump => Command::Dump { path },
+            _ => Command::Restore { path },
+        })
+    }
+
+    fn parse_vacuum(tokens: Vec<Token>, idx: usize) -> Result<Self, DbError> {
+        let table = match tokens.get(idx) {
+            None => None,
+            Some(Token::Element(table)) if tokens.len() == 2 => Some(table.to_string()),
+            Some(_) => return Err(DbError::invalid_input("invalid vacuum statement")),
+        };
+        Ok(Command::Vacuum { table })
+    }
+
+    fn parse_analyze(tokens: Vec<Token>, idx: usize) -> Result<Self, DbError> {
+        let table = match tokens.get(idx) {
+            None => None,
+            Some(Token::Element(table)) if tokens.len() == 2 => Some(table.to_string()),
+            Some(_) => return Err(DbError::invalid_input("invalid analyze statement")),
+        };
+        Ok(Command::Analyze { table })
+    }
+
+    fn parse_use(tokens: Vec<Token>, idx: usize) -> Result<Self, DbError> {
+        if tokens.len() != 2 {
+            return Err(DbError::invalid_input("invalid use statement"));
+        }
+        let Some(Token::Element(database)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'database_name' specifier"));
+        };
+        Ok(Command::Use {
+            database: database.to_string(),
+        })
+    }
+
+    fn parse_transaction(
+        tokens: Vec<Token>,
+        mut idx: usize,
+        command: Self,
+    ) -> Result<Self, DbError> {
+        if is_keyword(tokens.get(idx), "transaction") {
+            idx += 1;
+        }
+        match tokens.get(idx) {
+            Some(token) => Err(DbError::InvalidInput(format!(
+                "unexpected symbol: {}",
+                token
+            ))),
+            None => Ok(command),
+        }
+    }
+
+    fn parse_show(tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
+        if tokens.len() != 4 {
+            return Err(DbError::invalid_input("invalid show statement"));
+        }
+        let Some(Token::Table) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'TABLE' specifier"));
+        };
+        idx += 1;
+        match tokens.get(idx) {
+            Some(Token::Element(status)) if status.eq_ignore_ascii_case("status") => {}
+            _ => return Err(DbError::invalid_input("expected 'STATUS' specifier")),
+        }
+        idx += 1;
+        let Some(Token::Element(table)) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        Ok(Command::ShowTableStatus {
+            table: table.to_string(),
+        })
+    }
+
+    fn parse_check(tokens: Vec<Token>, idx: usize) -> Result<Self, DbError> {
+        if tokens.len() != 3 {
+            return Err(DbError::invalid_input("invalid check statement"));
+        }
+        let Some(Token::Table) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'TABLE' specifier"));
+        };
+        let Some(Token::Element(table)) = tokens.get(idx + 1) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        Ok(Command::CheckTable {
+            table: table.to_string(),
+        })
+    }
+
+    fn parse_alter(tokens: Vec<Token>, idx: usize) -> Result<Self, DbError> {
+        let Some(Token::Table) = tokens.get(idx) else {
+            return Err(DbError::invalid_input("expected 'TABLE' specifier"));
+        };
+        let Some(Token::Element(table)) = tokens.get(idx + 1) else {
+            return Err(DbError::invalid_input("expected relation_name"));
+        };
+        if !is_keyword(tokens.get(idx + 2), "rename") {
+            return Err(DbError::invalid_input("expected 'RENAME'"));
+        }
+        if tokens.get(idx + 3) != Some(&Token::To) {
+            return Err(DbError::invalid_input("expected 'TO'"));
+        }
+        let Some(Token::Element(name)) = tokens.get(idx + 4) else {
+            return Err(DbError::invalid_input("expected new relation_name"));
+        };
+        if tokens.len() != idx + 5 {
+            return Err(DbError::invalid_input("invalid alter statement"));
+        }
+        Ok(Command::RenameTable {
+            table: table.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    fn parse_explain(mut tokens: Vec<Token>, mut idx: usize) -> Result<Self, DbError> {
+        let analyze = tokens.get(idx) == Some(&Token::Analyze);
+        if analyze {
+            idx += 1;
+        }
+        if tokens.len() <= idx {
+            return Err(DbError::eof("expected statement to explain"));
+        }
+        let command = Self::parse(tokens.split_off(idx))?;
+        Ok(Command::Explain {
+            command: Box::new(command),
+            analyze,
+        })
+    }
+}
+
+impl fmt::Display for CopyOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == Self::default() {
+            return Ok(());
+        }
+        write!(
+            f,
+            " (HEADER {}, DELIMITER '{}')",
+            self.header, self.delimiter
+        )
+    }
 }
 
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Create { name, fields } => {
-                write!(f, "CREATE TABLE {}(", name)?;
+            Self::Create {
+                name,
+                fields,
+                constraints,
+                temporary,
+            } => {
+                write!(f, "CREATE ")?;
+                if *temporary {
+                    write!(f, "TEMP ")?;
+                }
+                write!(f, "TABLE {}(", name)?;
                 let len = fields.len();
                 for (i, field) in fields.iter().enumerate() {
                     write!(f, "{}", field)?;
+                    if constraints.contains(&Constraint::NotNull(field.get_name().to_string())) {
+                        write!(f, " NOT NULL")?;
+                    }
+                    if constraints.contains(&Constraint::auto_increment(field.get_name())) {
+                        write!(f, " AUTO_INCREMENT")?;
+                    }
+                    for constraint in constraints.iter() {
+                        match constraint {
+                            Constraint::Default(column, value) if column == field.get_name() => {
+                                write!(f, " DEFAULT ")?;
+                                write_value(f, value)?;
+                            }
+                            Constraint::Now(column) if column == field.get_name() => {
+                                write!(f, " DEFAULT NOW()")?;
+                            }
+                            _ => {}
+                        }
+                    }
                     if i < len - 1 {
                         write!(f, ", ")?;
                     }
                 }
                 write!(f, ")")?;
             }
+            Self::CreateIndex {
+                name,
+                table,
+                columns,
+            } => {
+                write!(
+                    f,
+                    "CREATE INDEX {} ON {}({})",
+                    name,
+                    table,
+                    columns.join(", ")
+                )?;
+            }
+            Self::CreateSequence { name, start } => {
+                write!(f, "CREATE SEQUENCE {} START {}", name, start)?;
+            }
+            Self::CreateView {
+                name,
+                columns,
+                query,
+            } => {
+                write!(f, "CREATE VIEW {}", name)?;
+                if !columns.is_empty() {
+                    write!(f, "({})", columns.join(", "))?;
+                }
+                write!(f, " AS {}", query)?;
+            }
+            Self::CreateDatabase { name } => {
+                write!(f, "CREATE DATABASE {}", name)?;
+            }
+            Self::Use { database } => {
+                write!(f, "USE {}", database)?;
+            }
             Self::Insert {
                 table,
                 fields,
                 values,
+                replace,
             } => {
-                write!(f, "INSERT INTO {}(", table)?;
+                write!(f, "INSERT ")?;
+                if *replace {
+                    write!(f, "OR REPLACE ")?;
+                }
+                write!(f, "INTO {}(", table)?;
                 let len = fields.len();
                 for (i, field) in fields.iter().enumerate() {
                     write!(f, "{}", field)?;
@@ -296,7 +1218,7 @@ impl fmt::Display for Command {
                     write!(f, "(")?;
                     let group_len = group.len();
                     for (i, value) in group.iter().enumerate() {
-                        write!(f, "'{}'", value)?;
+                        write_value(f, value)?;
                         if i < group_len - 1 {
                             write!(f, ", ")?;
                         }
@@ -307,8 +1229,20 @@ impl fmt::Display for Command {
                     }
                 }
             }
-            Self::Select { table, fields } => {
+            Self::Select {
+                distinct,
+                table,
+                fields,
+                conditions,
+                group_by,
+                having,
+                order_by,
+                limit,
+            } => {
                 write!(f, "SELECT ")?;
+                if *distinct {
+                    write!(f, "DISTINCT ")?;
+                }
                 let len = fields.len();
                 for (i, field) in fields.iter().enumerate() {
                     write!(f, "{}", field)?;
@@ -317,13 +1251,225 @@ impl fmt::Display for Command {
                     }
                 }
                 write!(f, " FROM {}", table)?;
+                write_conditions(f, "WHERE", conditions)?;
+                if !group_by.is_empty() {
+                    write!(f, " GROUP BY {}", group_by.join(", "))?;
+                }
+                write_conditions(f, "HAVING", having)?;
+                for (i, key) in order_by.iter().enumerate() {
+                    match i {
+                        0 => write!(f, " ORDER BY ")?,
+                        _ => write!(f, ", ")?,
+                    }
+                    write!(f, "{}", key)?;
+                }
+                if let Some(limit) = limit {
+                    write!(f, " LIMIT {}", limit)?;
+                }
+            }
+            Self::Update {
+                table,
+                assignments,
+                conditions,
+            } => {
+                write!(f, "UPDATE {} SET ", table)?;
+                let len = assignments.len();
+                for (i, assignment) in assignments.iter().enumerate() {
+                    write!(f, "{} = {}", assignment.column, assignment.value)?;
+                    if i < len - 1 {
+                        write!(f, ", ")?;
+                    }
+                }
+                write_conditions(f, "WHERE", conditions)?;
             }
             Self::Delete { table } => {
                 write!(f, "DELETE FROM {}", table)?;
             }
+            Self::Drop { table } => {
+                write!(f, "DROP TABLE {}", table)?;
+            }
+            Self::DropSequence { name } => {
+                write!(f, "DROP SEQUENCE {}", name)?;
+            }
+            Self::DropView { name } => {
+                write!(f, "DROP VIEW {}", name)?;
+            }
+            Self::RenameTable { table, name } => {
+                write!(f, "ALTER TABLE {} RENAME TO {}", table, name)?;
+            }
+            Self::NextVal { sequence } => {
+                write!(f, "SELECT nextval('{}')", sequence)?;
+            }
+            Self::CopyFrom {
+                table,
+                path,
+                options,
+            } => {
+                write!(f, "COPY {} FROM '{}'{}", table, path, options)?;
+            }
+            Self::CopyTo {
+                query,
+                path,
+                options,
+            } => {
+                write!(f, "COPY ({}) TO '{}'{}", query, path, options)?;
+            }
+            Self::Dump { path } => {
+                write!(f, "DUMP TO '{}'", path)?;
+            }
+            Self::Restore { path } => {
+                write!(f, "RESTORE FROM '{}'", path)?;
+            }
+            Self::Vacuum { table } => {
+                write!(f, "VACUUM")?;
+                if let Some(table) = table {
+                    write!(f, " {}", table)?;
+                }
+            }
+            Self::Analyze { table } => {
+                write!(f, "ANALYZE")?;
+                if let Some(table) = table {
+                    write!(f, " {}", table)?;
+                }
+            }
+            Self::ShowTableStatus { table } => {
+                write!(f, "SHOW TABLE STATUS {}", table)?;
+            }
+            Self::CheckTable { table } => {
+                write!(f, "CHECK TABLE {}", table)?;
+            }
+            Self::Explain { command, analyze } => {
+                write!(f, "EXPLAIN ")?;
+                if *analyze {
+                    write!(f, "ANALYZE ")?;
+                }
+                write!(f, "{}", command)?;
+            }
+            Self::Begin => write!(f, "BEGIN")?,
+            Self::Commit => write!(f, "COMMIT")?,
+            Self::Rollback => write!(f, "ROLLBACK")?,
+        }
+        Ok(())
+    }
+}
+
+impl Expr {
+    fn operand(value: &str) -> Self {
+        match value.parse::<i64>() {
+            Ok(_) => Self::Value(value.to_string()),
+            Err(_) => Self::Column(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(value) => write!(f, "'{}'", value),
+            Self::Column(column) => write!(f, "{}", column),
+            Self::Binary(left, op, right) => write!(f, "{} {} {}", left, op, right),
+        }
+    }
+}
+
+impl Operator {
+    fn parse(op: &str) -> Result<Self, DbError> {
+        match op {
+            "=" => Ok(Self::Eq),
+            "!=" | "<>" => Ok(Self::Ne),
+            "<" => Ok(Self::Lt),
+            "<=" => Ok(Self::Le),
+            ">" => Ok(Self::Gt),
+            ">=" => Ok(Self::Ge),
+            _ => Err(DbError::InvalidInput(format!("unknown operator: {}", op))),
+        }
+    }
+}
+
+impl fmt::Display for OrderBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.column)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Eq => write!(f, "="),
+            Self::Ne => write!(f, "<>"),
+            Self::Lt => write!(f, "<"),
+            Self::Le => write!(f, "<="),
+            Self::Gt => write!(f, ">"),
+            Self::Ge => write!(f, ">="),
+        }
+    }
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    match value.contains('\'') {
+        true => write!(f, "\"{}\"", value),
+        false => write!(f, "'{}'", value),
+    }
+}
+
+fn write_conditions(
+    f: &mut fmt::Formatter<'_>,
+    clause: &str,
+    conditions: &[Comparison],
+) -> fmt::Result {
+    for (i, condition) in conditions.iter().enumerate() {
+        match i {
+            0 => write!(f, " {} ", clause)?,
+            _ => write!(f, " AND ")?,
+        }
+        write!(f, "{} {} ", condition.column, condition.op)?;
+        write_value(f, &condition.value)?;
+    }
+    Ok(())
+}
+
+fn fold_calls(tokens: Vec<Token>) -> Vec<Token> {
+    let mut folded = Vec::with_capacity(tokens.len());
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if let [
+            Token::Element(function),
+            Token::Delimiter('('),
+            Token::Element(argument),
+            Token::Delimiter(')'),
+            ..,
+        ] = &tokens[idx..]
+            && (AGGREGATES.contains(&function.to_lowercase().as_str())
+                || FUNCTIONS.contains(&function.to_lowercase().as_str()))
+        {
+            let field = format!("{}({})", function.to_lowercase(), argument);
+            folded.push(Token::Element(field));
+            idx += 4;
+            continue;
         }
-        Ok(())
+        folded.push(tokens[idx].clone());
+        idx += 1;
     }
+    folded
+}
+
+fn split_clause(tokens: &mut Vec<Token>, keyword: Token) -> Option<Vec<Token>> {
+    let position = tokens.iter().position(|token| *token == keyword)?;
+    let mut clause = tokens.split_off(position);
+    clause.remove(0);
+    Some(clause)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Element(element)) if element.eq_ignore_ascii_case(keyword))
+}
+
+fn is_arithmetic(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/")
 }
 
 fn get_num<T: FromStr>(token: Option<&Token>) -> Result<T, DbError> {
@@ -387,7 +1533,9 @@ mod tests {
                 fields: vec![
                     ColType::Int("id".to_string()),
                     ColType::Varchar("name".to_string(), 10)
-                ]
+                ],
+                constraints: vec![],
+                temporary: false,
             },
             command
         );
@@ -564,11 +1712,126 @@ mod tests {
             Command::Select {
                 fields: vec!["*".to_string(), "name".to_string()],
                 table: "users".to_string(),
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
             },
             command
         );
     }
 
+    #[test]
+    fn select_order_by() {
+        let query = vec![
+            Token::Select,
+            Token::element("name"),
+            Token::From,
+            Token::element("users"),
+            Token::Where,
+            Token::element("id"),
+            Token::Operator(">".to_string()),
+            Token::element("5"),
+            Token::Order,
+            Token::By,
+            Token::element("age"),
+            Token::Delimiter(','),
+            Token::element("name"),
+        ];
+        let command = Command::parse(query).unwrap();
+        assert_eq!(
+            "SELECT name FROM users WHERE id > '5' ORDER BY age, name",
+            command.to_string()
+        );
+        let Command::Select { order_by, .. } = command else {
+            panic!("expected select");
+        };
+        assert_eq!(vec![OrderBy::asc("age"), OrderBy::asc("name")], order_by);
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'BY' after 'ORDER'")),
+            Command::parse(vec![
+                Token::Select,
+                Token::element("name"),
+                Token::From,
+                Token::element("users"),
+                Token::Order,
+                Token::element("name"),
+            ])
+        );
+    }
+
+    #[test]
+    fn select_where() {
+        let query = vec![
+            Token::Select,
+            Token::element("name"),
+            Token::From,
+            Token::element("users"),
+            Token::Where,
+            Token::element("id"),
+            Token::Operator("=".to_string()),
+            Token::element("5"),
+            Token::And,
+            Token::element("name"),
+            Token::Operator("!=".to_string()),
+            Token::element("bob"),
+        ];
+        let command = Command::parse(query).unwrap();
+        assert_eq!(
+            "SELECT name FROM users WHERE id = '5' AND name <> 'bob'",
+            command.to_string()
+        );
+        let Command::Select { conditions, .. } = command else {
+            panic!("expected select");
+        };
+        assert_eq!(
+            vec![
+                Comparison {
+                    column: "id".to_string(),
+                    op: Operator::Eq,
+                    value: "5".to_string(),
+                },
+                Comparison {
+                    column: "name".to_string(),
+                    op: Operator::Ne,
+                    value: "bob".to_string(),
+                },
+            ],
+            conditions
+        );
+        let query = vec![
+            Token::Select,
+            Token::element("name"),
+            Token::From,
+            Token::element("users"),
+            Token::Where,
+            Token::element("id"),
+            Token::Operator("=>".to_string()),
+            Token::element("5"),
+        ];
+        assert_eq!(
+            Err(DbError::invalid_input("unknown operator: =>")),
+            Command::parse(query)
+        );
+        let query = vec![
+            Token::Select,
+            Token::element("name"),
+            Token::From,
+            Token::element("users"),
+            Token::Where,
+            Token::element("id"),
+            Token::Operator("=".to_string()),
+            Token::element("5"),
+            Token::element("6"),
+        ];
+        assert_eq!(
+            Err(DbError::invalid_input("unexpected token: '6'")),
+            Command::parse(query)
+        );
+    }
+
     #[test]
     fn select_unexpected_delimiter() {
         let query = vec![
@@ -588,6 +1851,12 @@ mod tests {
         let select = Command::Select {
             fields: vec!["*".to_string()],
             table: "users".to_string(),
+            conditions: vec![],
+            distinct: false,
+            group_by: vec![],
+            having: vec![],
+            order_by: vec![],
+            limit: None,
         };
         assert_eq!(select.to_string(), "SELECT * FROM users");
     }
@@ -597,6 +1866,8 @@ mod tests {
         let select = Command::Create {
             name: "users".to_string(),
             fields: vec![ColType::int("id"), ColType::varchar("name", 16)],
+            constraints: vec![],
+            temporary: false,
         };
         assert_eq!(
             select.to_string(),
@@ -604,12 +1875,57 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_not_null() {
+        let tokens = vec![
+            Token::Create,
+            Token::Table,
+            Token::element("users"),
+            Token::Delimiter('('),
+            Token::element("id"),
+            Token::element("int"),
+            Token::Delimiter(','),
+            Token::element("name"),
+            Token::element("varchar"),
+            Token::Delimiter('('),
+            Token::element("10"),
+            Token::Delimiter(')'),
+            Token::element("not"),
+            Token::element("NULL"),
+            Token::Delimiter(')'),
+        ];
+        let command = Command::parse(tokens).unwrap();
+        assert_eq!(
+            "CREATE TABLE users(id INT, name VARCHAR(10) NOT NULL)",
+            command.to_string()
+        );
+        let Command::Create { constraints, .. } = command else {
+            panic!("expected create");
+        };
+        assert_eq!(vec![Constraint::not_null("name")], constraints);
+        let tokens = vec![
+            Token::Create,
+            Token::Table,
+            Token::element("users"),
+            Token::Delimiter('('),
+            Token::element("id"),
+            Token::element("int"),
+            Token::element("not"),
+            Token::Delimiter(')'),
+        ];
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'NULL' after 'NOT'")),
+            Command::parse(tokens)
+        );
+    }
+
     #[test]
     fn display_insert() {
         let select = Command::Insert {
             table: "users".to_string(),
             fields: vec!["id".to_string(), "name".to_string()],
             values: vec![vec!["1".to_string(), "John".to_string()]],
+            replace: false,
         };
         assert_eq!(
             select.to_string(),
@@ -617,6 +1933,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_insert_or_replace() {
+        let tokens = vec![
+            Token::Insert,
+            Token::element("OR"),
+            Token::element("REPLACE"),
+            Token::Into,
+            Token::element("users"),
+            Token::Delimiter('('),
+            Token::element("id"),
+            Token::Delimiter(')'),
+            Token::Values,
+            Token::Delimiter('('),
+            Token::element("1"),
+            Token::Delimiter(')'),
+        ];
+        let command = Command::parse(tokens).unwrap();
+        assert_eq!(
+            Command::Insert {
+                table: "users".to_string(),
+                fields: vec!["id".to_string()],
+                values: vec![vec!["1".to_string()]],
+                replace: true,
+            },
+            command
+        );
+        assert_eq!(
+            "INSERT OR REPLACE INTO users(id) VALUES('1')",
+            command.to_string()
+        );
+        let tokens = vec![Token::Insert, Token::element("or"), Token::Into];
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'REPLACE' after 'OR'")),
+            Command::parse(tokens)
+        );
+    }
+
     #[test]
     fn parse_delete() {
         let table = "test".to_string();
@@ -629,6 +1982,312 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_drop() {
+        let table = "test".to_string();
+        let drop = Command::Drop {
+            table: table.clone(),
+        };
+        assert_eq!("DROP TABLE test", drop.to_string());
+        assert_eq!(
+            Ok(drop),
+            Command::parse(vec![Token::Drop, Token::Table, Token::Element(table)])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'TABLE' specifier")),
+            Command::parse(vec![Token::Drop, Token::Index, Token::element("test")])
+        );
+    }
+
+    #[test]
+    fn parse_copy_from() {
+        let copy = Command::CopyFrom {
+            table: "users".to_string(),
+            path: "users.csv".to_string(),
+            options: CopyOptions {
+                header: true,
+                delimiter: ';',
+            },
+        };
+        assert_eq!(
+            "COPY users FROM 'users.csv' (HEADER true, DELIMITER ';')",
+            copy.to_string()
+        );
+        let tokens = vec![
+            Token::Copy,
+            Token::element("users"),
+            Token::From,
+            Token::element("users.csv"),
+            Token::Delimiter('('),
+            Token::element("HEADER"),
+            Token::element("true"),
+            Token::Delimiter(','),
+            Token::element("delimiter"),
+            Token::element(";"),
+            Token::Delimiter(')'),
+        ];
+        assert_eq!(Ok(copy), Command::parse(tokens));
+        let plain = Command::parse(vec![
+            Token::Copy,
+            Token::element("users"),
+            Token::From,
+            Token::element("users.csv"),
+        ])
+        .unwrap();
+        assert_eq!("COPY users FROM 'users.csv'", plain.to_string());
+        assert_eq!(
+            Err(DbError::invalid_input("unknown COPY option: format")),
+            Command::parse(vec![
+                Token::Copy,
+                Token::element("users"),
+                Token::From,
+                Token::element("users.csv"),
+                Token::Delimiter('('),
+                Token::element("format"),
+                Token::element("csv"),
+                Token::Delimiter(')'),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_vacuum() {
+        let table = "test".to_string();
+        let vacuum = Command::Vacuum {
+            table: Some(table.clone()),
+        };
+        assert_eq!("VACUUM test", vacuum.to_string());
+        assert_eq!(
+            Ok(vacuum),
+            Command::parse(vec![Token::Vacuum, Token::Element(table)])
+        );
+        let vacuum = Command::Vacuum { table: None };
+        assert_eq!("VACUUM", vacuum.to_string());
+        assert_eq!(Ok(vacuum), Command::parse(vec![Token::Vacuum]));
+        assert_eq!(
+            Err(DbError::invalid_input("invalid vacuum statement")),
+            Command::parse(vec![Token::Vacuum, Token::Select])
+        );
+    }
+
+    #[test]
+    fn parse_transaction() {
+        assert_eq!(Ok(Command::Begin), Command::parse(vec![Token::Begin]));
+        assert_eq!(
+            Ok(Command::Begin),
+            Command::parse(vec![Token::Begin, Token::element("TRANSACTION")])
+        );
+        assert_eq!(Ok(Command::Commit), Command::parse(vec![Token::Commit]));
+        assert_eq!(Ok(Command::Rollback), Command::parse(vec![Token::Rollback]));
+        assert_eq!("BEGIN", Command::Begin.to_string());
+        assert_eq!("ROLLBACK", Command::Rollback.to_string());
+        assert_eq!(
+            Err(DbError::invalid_input("unexpected symbol: 'work'")),
+            Command::parse(vec![Token::Commit, Token::element("work")])
+        );
+    }
+
+    #[test]
+    fn parse_show_table_status() {
+        let table = "test".to_string();
+        let show = Command::ShowTableStatus {
+            table: table.clone(),
+        };
+        assert_eq!("SHOW TABLE STATUS test", show.to_string());
+        assert_eq!(
+            Ok(show),
+            Command::parse(vec![
+                Token::Show,
+                Token::Table,
+                Token::element("STATUS"),
+                Token::Element(table)
+            ])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'STATUS' specifier")),
+            Command::parse(vec![
+                Token::Show,
+                Token::Table,
+                Token::element("tables"),
+                Token::element("test")
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_check_table() {
+        let check = Command::CheckTable {
+            table: "test".to_string(),
+        };
+        assert_eq!("CHECK TABLE test", check.to_string());
+        assert_eq!(
+            Ok(check),
+            Command::parse(vec![
+                Token::element("CHECK"),
+                Token::Table,
+                Token::element("test")
+            ])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'TABLE' specifier")),
+            Command::parse(vec![
+                Token::element("check"),
+                Token::Index,
+                Token::element("test")
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_rename_table() {
+        let rename = Command::RenameTable {
+            table: "test".to_string(),
+            name: "other".to_string(),
+        };
+        assert_eq!("ALTER TABLE test RENAME TO other", rename.to_string());
+        assert_eq!(
+            Ok(rename),
+            Command::parse(vec![
+                Token::element("ALTER"),
+                Token::Table,
+                Token::element("test"),
+                Token::element("rename"),
+                Token::To,
+                Token::element("other")
+            ])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'TO'")),
+            Command::parse(vec![
+                Token::element("alter"),
+                Token::Table,
+                Token::element("test"),
+                Token::element("rename"),
+                Token::element("other")
+            ])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected new relation_name")),
+            Command::parse(vec![
+                Token::element("alter"),
+                Token::Table,
+                Token::element("test"),
+                Token::element("rename"),
+                Token::To
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_explain() {
+        let explain = Command::Explain {
+            command: Box::new(Command::Select {
+                fields: vec!["id".to_string()],
+                table: "test".to_string(),
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
+            }),
+            analyze: false,
+        };
+        assert_eq!("EXPLAIN SELECT id FROM test", explain.to_string());
+        assert_eq!(
+            Ok(explain),
+            Command::parse(vec![
+                Token::Explain,
+                Token::Select,
+                Token::element("id"),
+                Token::From,
+                Token::element("test"),
+            ])
+        );
+        assert_eq!(
+            Err(DbError::eof("expected statement to explain")),
+            Command::parse(vec![Token::Explain])
+        );
+        let analyze = Command::parse(vec![
+            Token::Explain,
+            Token::Analyze,
+            Token::Select,
+            Token::element("id"),
+            Token::From,
+            Token::element("test"),
+        ])
+        .unwrap();
+        assert_eq!("EXPLAIN ANALYZE SELECT id FROM test", analyze.to_string());
+        assert_eq!(
+            Err(DbError::eof("expected statement to explain")),
+            Command::parse(vec![Token::Explain, Token::Analyze])
+        );
+    }
+
+    #[test]
+    fn parse_update() {
+        let update = Command::Update {
+            table: "users".to_string(),
+            assignments: vec![
+                Assignment {
+                    column: "name".to_string(),
+                    value: Expr::Value("bob".to_string()),
+                },
+                Assignment {
+                    column: "age".to_string(),
+                    value: Expr::Binary(
+                        Box::new(Expr::Column("age".to_string())),
+                        '+',
+                        Box::new(Expr::Value("1".to_string())),
+                    ),
+                },
+            ],
+            conditions: vec![Comparison {
+                column: "id".to_string(),
+                op: Operator::Eq,
+                value: "5".to_string(),
+            }],
+        };
+        assert_eq!(
+            "UPDATE users SET name = 'bob', age = age + '1' WHERE id = '5'",
+            update.to_string()
+        );
+        let tokens = vec![
+            Token::Update,
+            Token::element("users"),
+            Token::Set,
+            Token::element("name"),
+            Token::Operator("=".to_string()),
+            Token::element("bob"),
+            Token::Delimiter(','),
+            Token::element("age"),
+            Token::Operator("=".to_string()),
+            Token::element("age"),
+            Token::element("+"),
+            Token::element("1"),
+            Token::Where,
+            Token::element("id"),
+            Token::Operator("=".to_string()),
+            Token::element("5"),
+        ];
+        assert_eq!(Ok(update), Command::parse(tokens));
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'SET' clause")),
+            Command::parse(vec![Token::Update, Token::element("users")])
+        );
+        let tokens = vec![
+            Token::Update,
+            Token::element("users"),
+            Token::Set,
+            Token::element("name"),
+            Token::element("bob"),
+        ];
+        assert_eq!(
+            Err(DbError::invalid_input("expected '='")),
+            Command::parse(tokens)
+        );
+    }
+
     #[test]
     fn parse_invalid_delete() {
         let query = vec![Token::Delete];
@@ -647,4 +2306,43 @@ mod tests {
             Command::parse(query)
         );
     }
+
+    #[test]
+    fn parse_create_index() {
+        let index = Command::CreateIndex {
+            name: "users_name".to_string(),
+            table: "users".to_string(),
+            columns: vec!["name".to_string()],
+        };
+        assert_eq!("CREATE INDEX users_name ON users(name)", index.to_string());
+        let tokens = vec![
+            Token::Create,
+            Token::Index,
+            Token::element("users_name"),
+            Token::On,
+            Token::element("users"),
+            Token::Delimiter('('),
+            Token::element("name"),
+            Token::Delimiter(')'),
+        ];
+        assert_eq!(Ok(index), Command::parse(tokens));
+        assert_eq!(
+            Err(DbError::invalid_input("invalid create index statement")),
+            Command::parse(vec![Token::Create, Token::Index])
+        );
+        let tokens = vec![
+            Token::Create,
+            Token::Index,
+            Token::element("users_name"),
+            Token::element("users"),
+            Token::element("users"),
+            Token::Delimiter('('),
+            Token::element("name"),
+            Token::Delimiter(')'),
+        ];
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'ON' clause")),
+            Command::parse(tokens)
+        );
+    }
 }
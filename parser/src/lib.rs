@@ -1,14 +1,36 @@
 mod command;
+mod prepared;
+mod script;
 mod token;
 
-pub use command::Command;
+pub use command::{Assignment, Command, Comparison, CopyOptions, Expr, Operator, OrderBy};
 use common::error::DbError;
+pub use prepared::Prepared;
+use token::Token;
 
 pub fn parse(query: &str) -> Result<Command, DbError> {
-    let tokens = token::tokenize(query)?;
+    let tokens = token::tokenize(query)?
+        .into_iter()
+        .map(|token| match token {
+            Token::Parameter => Token::Element("?".to_string()),
+            token => token,
+        })
+        .collect();
     Command::parse(tokens)
 }
 
+pub fn keywords() -> &'static [&'static str] {
+    token::KEYWORDS
+}
+
+pub fn prepare(query: &str) -> Result<Prepared, DbError> {
+    Prepared::parse(query)
+}
+
+pub fn split(script: &str) -> Vec<String> {
+    script::split(script)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -20,7 +42,8 @@ mod tests {
             Command::Insert {
                 table: "users".to_string(),
                 fields: vec!["id".to_string(), "name".to_string()],
-                values: vec![vec!["10".to_string(), "Daniil".to_string()]]
+                values: vec![vec!["10".to_string(), "Daniil".to_string()]],
+                replace: false,
             },
             command
         );
@@ -34,8 +57,432 @@ mod tests {
             Command::Select {
                 table: "users".to_string(),
                 fields: vec![],
+                conditions: vec![],
+                distinct: false,
+                group_by: vec![],
+                having: vec![],
+                order_by: vec![],
+                limit: None,
+            },
+            command
+        );
+    }
+
+    #[test]
+    fn parse_create_index() {
+        let command = parse("CREATE INDEX users_name ON users(name)").unwrap();
+        assert_eq!(
+            Command::CreateIndex {
+                name: "users_name".to_string(),
+                table: "users".to_string(),
+                columns: vec!["name".to_string()],
+            },
+            command
+        );
+        let index = Command::CreateIndex {
+            name: "users_name_age".to_string(),
+            table: "users".to_string(),
+            columns: vec!["name".to_string(), "age".to_string()],
+        };
+        assert_eq!(
+            "CREATE INDEX users_name_age ON users(name, age)",
+            index.to_string()
+        );
+        assert_eq!(Ok(index.clone()), parse(&index.to_string()));
+        assert_eq!(
+            Err(DbError::invalid_input("expected column name")),
+            parse("CREATE INDEX users_name ON users(name, )")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("invalid create index statement")),
+            parse("CREATE INDEX users_name ON users(name) age")
+        );
+    }
+
+    #[test]
+    fn parse_scalar_functions() {
+        let command = parse("CREATE INDEX users_email ON users(LOWER(email), age)").unwrap();
+        assert_eq!(
+            Command::CreateIndex {
+                name: "users_email".to_string(),
+                table: "users".to_string(),
+                columns: vec!["lower(email)".to_string(), "age".to_string()],
             },
             command
         );
+        let Command::Select { conditions, .. } =
+            parse("SELECT id FROM users WHERE Upper(email) = 'ANN' AND age > 1").unwrap()
+        else {
+            panic!("expected SELECT");
+        };
+        let columns: Vec<&str> = conditions.iter().map(|c| c.column.as_str()).collect();
+        assert_eq!(vec!["upper(email)", "age"], columns);
+    }
+
+    #[test]
+    fn parse_order_by_direction() {
+        let command = parse("SELECT id FROM users ORDER BY age DESC, id asc LIMIT 3").unwrap();
+        assert_eq!(
+            "SELECT id FROM users ORDER BY age DESC, id LIMIT 3",
+            command.to_string()
+        );
+        let Command::Select { order_by, .. } = command else {
+            panic!("expected SELECT");
+        };
+        assert_eq!(vec![OrderBy::desc("age"), OrderBy::asc("id")], order_by);
+        assert_eq!(
+            Err(DbError::invalid_input("unexpected token: 'DESC'")),
+            parse("SELECT id FROM users ORDER BY age DESC DESC")
+        );
+        assert!(parse("SELECT id FROM users GROUP BY age DESC").is_err());
+    }
+
+    #[test]
+    fn parse_group_by() {
+        let command = parse(
+            "SELECT name, COUNT(*), max(age) FROM users WHERE id > 1 GROUP BY name HAVING count(*) > 2 ORDER BY name",
+        )
+        .unwrap();
+        assert_eq!(
+            "SELECT name, count(*), max(age) FROM users WHERE id > '1' GROUP BY name HAVING count(*) > '2' ORDER BY name",
+            command.to_string()
+        );
+        let Command::Select {
+            group_by, having, ..
+        } = command
+        else {
+            panic!("expected select");
+        };
+        assert_eq!(vec!["name".to_string()], group_by);
+        assert_eq!("count(*)", having[0].column);
+    }
+
+    #[test]
+    fn parse_distinct() {
+        let command = parse("SELECT DISTINCT name, age FROM users ORDER BY age").unwrap();
+        assert_eq!(
+            "SELECT DISTINCT name, age FROM users ORDER BY age",
+            command.to_string()
+        );
+        let Command::Select {
+            distinct, fields, ..
+        } = command
+        else {
+            panic!("expected select");
+        };
+        assert!(distinct);
+        assert_eq!(vec!["name".to_string(), "age".to_string()], fields);
+    }
+
+    #[test]
+    fn parse_limit() {
+        let command = parse("SELECT id FROM users WHERE id > 1 ORDER BY id LIMIT 5").unwrap();
+        assert_eq!(
+            "SELECT id FROM users WHERE id > '1' ORDER BY id LIMIT 5",
+            command.to_string()
+        );
+        let Command::Select { limit, .. } = command else {
+            panic!("expected select");
+        };
+        assert_eq!(Some(5), limit);
+        assert_eq!(
+            Err(DbError::invalid_input("invalid LIMIT: ten")),
+            parse("SELECT id FROM users LIMIT ten")
+        );
+        assert_eq!(
+            Err(DbError::eof("expected LIMIT value")),
+            parse("SELECT id FROM users LIMIT")
+        );
+    }
+    #[test]
+    fn parse_copy_to() {
+        let command = parse(
+            "COPY (SELECT name, count(*) FROM users GROUP BY name) TO 'out.csv' (HEADER true)",
+        )
+        .unwrap();
+        assert_eq!(
+            "COPY (SELECT name, count(*) FROM users GROUP BY name) TO 'out.csv' (HEADER true, DELIMITER ',')",
+            command.to_string()
+        );
+        let Command::CopyTo { query, options, .. } = command else {
+            panic!("expected copy to");
+        };
+        assert!(matches!(*query, Command::Select { .. }));
+        assert!(options.header);
+        assert_eq!(
+            Err(DbError::invalid_input("only SELECT can be copied")),
+            parse("COPY (DELETE FROM users) TO 'out.csv'")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'TO' file path")),
+            parse("COPY (SELECT id FROM users) 'out.csv'")
+        );
+        assert_eq!(
+            Err(DbError::eof("expected ')'")),
+            parse("COPY (SELECT id FROM users TO 'out.csv'")
+        );
+    }
+    #[test]
+    fn parse_dump_restore() {
+        let dump = parse("DUMP TO 'backup.sql'").unwrap();
+        assert_eq!(
+            Command::Dump {
+                path: "backup.sql".to_string()
+            },
+            dump
+        );
+        assert_eq!("DUMP TO 'backup.sql'", dump.to_string());
+        let restore = parse("RESTORE FROM 'backup.sql'").unwrap();
+        assert_eq!("RESTORE FROM 'backup.sql'", restore.to_string());
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'FROM' file path")),
+            parse("RESTORE 'backup.sql'")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("unexpected token: FROM")),
+            parse("DUMP FROM 'backup.sql'")
+        );
+    }
+
+    #[test]
+    fn insert_round_trip() {
+        let command = Command::Insert {
+            table: "users".to_string(),
+            fields: vec!["id".to_string(), "name".to_string()],
+            values: vec![
+                vec!["1".to_string(), "O'Brien".to_string()],
+                vec!["2".to_string(), "say \"hi\"".to_string()],
+            ],
+            replace: false,
+        };
+        assert_eq!(
+            "INSERT INTO users(id, name) VALUES('1', \"O'Brien\"), ('2', 'say \"hi\"')",
+            command.to_string()
+        );
+        assert_eq!(Ok(command.clone()), parse(&command.to_string()));
+    }
+    #[test]
+    fn parse_sequences() {
+        let create = parse("CREATE SEQUENCE ids START 10").unwrap();
+        assert_eq!(
+            Command::CreateSequence {
+                name: "ids".to_string(),
+                start: 10
+            },
+            create
+        );
+        assert_eq!("CREATE SEQUENCE ids START 10", create.to_string());
+        assert_eq!(
+            "CREATE SEQUENCE ids START 1",
+            parse("create sequence ids").unwrap().to_string()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected int, found: 'x'")),
+            parse("CREATE SEQUENCE ids START x")
+        );
+        assert_eq!(
+            "DROP SEQUENCE ids",
+            parse("DROP SEQUENCE ids").unwrap().to_string()
+        );
+        let next = parse("SELECT nextval('ids')").unwrap();
+        assert_eq!(
+            Command::NextVal {
+                sequence: "ids".to_string()
+            },
+            next
+        );
+        assert_eq!("SELECT nextval('ids')", next.to_string());
+
+        let create =
+            parse("CREATE TABLE users(id INT AUTO_INCREMENT NOT NULL, name VARCHAR(8))").unwrap();
+        assert_eq!(
+            "CREATE TABLE users(id INT NOT NULL AUTO_INCREMENT, name VARCHAR(8))",
+            create.to_string()
+        );
+        let Command::Create { constraints, .. } = create else {
+            panic!("expected create");
+        };
+        assert_eq!(
+            vec![
+                row::Constraint::auto_increment("id"),
+                row::Constraint::not_null("id")
+            ],
+            constraints
+        );
+    }
+    #[test]
+    fn parse_defaults() {
+        let query = "CREATE TABLE events(id INT, kind VARCHAR(8) NOT NULL DEFAULT 'info', \
+                     created BIGINT DEFAULT now(), retries INT DEFAULT 3)";
+        let create = parse(query).unwrap();
+        assert_eq!(
+            "CREATE TABLE events(id INT, kind VARCHAR(8) NOT NULL DEFAULT 'info', \
+             created BIGINT DEFAULT NOW(), retries INT DEFAULT '3')",
+            create.to_string()
+        );
+        let Command::Create { constraints, .. } = &create else {
+            panic!("expected create");
+        };
+        assert_eq!(
+            &vec![
+                row::Constraint::not_null("kind"),
+                row::Constraint::default("kind", "info"),
+                row::Constraint::now("created"),
+                row::Constraint::default("retries", "3"),
+            ],
+            constraints
+        );
+        assert_eq!(Ok(create.clone()), parse(&create.to_string()));
+        assert_eq!(
+            Err(DbError::invalid_input("expected DEFAULT value")),
+            parse("CREATE TABLE events(id INT DEFAULT)")
+        );
+    }
+    #[test]
+    fn parse_views() {
+        let query = "CREATE VIEW adults(user_id, user_name) AS \
+                     SELECT id, name FROM users WHERE age >= 18 AND name != \"O'Brien\"";
+        let create = parse(query).unwrap();
+        assert_eq!(
+            "CREATE VIEW adults(user_id, user_name) AS \
+             SELECT id, name FROM users WHERE age >= '18' AND name <> \"O'Brien\"",
+            create.to_string()
+        );
+        let Command::CreateView { name, columns, .. } = &create else {
+            panic!("expected create view");
+        };
+        assert_eq!("adults", name);
+        assert_eq!(&vec!["user_id", "user_name"], columns);
+        assert_eq!(Ok(create.clone()), parse(&create.to_string()));
+        assert_eq!(
+            "CREATE VIEW names AS SELECT name FROM users",
+            parse("create view names as select name from users")
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            Command::DropView {
+                name: "adults".to_string()
+            },
+            parse("DROP VIEW adults").unwrap()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'AS' clause")),
+            parse("CREATE VIEW adults SELECT id FROM users")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("only SELECT can define a view")),
+            parse("CREATE VIEW adults AS DELETE FROM users")
+        );
+    }
+
+    #[test]
+    fn parse_databases() {
+        let create = parse("create database app").unwrap();
+        assert_eq!(
+            Command::CreateDatabase {
+                name: "app".to_string()
+            },
+            create
+        );
+        assert_eq!("CREATE DATABASE app", create.to_string());
+        let use_database = parse("USE app").unwrap();
+        assert_eq!("USE app", use_database.to_string());
+        assert_eq!(Ok(use_database.clone()), parse(&use_database.to_string()));
+        assert_eq!(
+            "SELECT users.id FROM app.users",
+            parse("SELECT users.id FROM app.users").unwrap().to_string()
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("invalid use statement")),
+            parse("USE app other")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("invalid create database statement")),
+            parse("CREATE DATABASE")
+        );
+    }
+
+    #[test]
+    fn parse_temporary_table() {
+        let create = parse("CREATE TEMPORARY TABLE staging(id INT, name VARCHAR(8))").unwrap();
+        let Command::Create { temporary, .. } = &create else {
+            panic!("expected create");
+        };
+        assert!(temporary);
+        assert_eq!(
+            "CREATE TEMP TABLE staging(id INT, name VARCHAR(8))",
+            create.to_string()
+        );
+        assert_eq!(Ok(create.clone()), parse(&create.to_string()));
+        assert_eq!(
+            Err(DbError::invalid_input("expected 'TABLE' specifier")),
+            parse("CREATE TEMP VIEW names AS SELECT name FROM users")
+        );
+    }
+
+    #[test]
+    fn parse_analyze() {
+        assert_eq!(Ok(Command::Analyze { table: None }), parse("ANALYZE"));
+        let analyze = parse("analyze users").unwrap();
+        assert_eq!(
+            Command::Analyze {
+                table: Some("users".to_string())
+            },
+            analyze
+        );
+        assert_eq!("ANALYZE users", analyze.to_string());
+        assert_eq!(
+            Err(DbError::invalid_input("invalid analyze statement")),
+            parse("ANALYZE users orders")
+        );
+    }
+
+    #[test]
+    fn prepare_statement() {
+        let select = prepare("SELECT id FROM users WHERE id = ? AND name = '?'").unwrap();
+        assert_eq!(1, select.parameters());
+        assert_eq!(vec![Some("id".to_string())], select.columns());
+        assert_eq!(
+            parse("SELECT id FROM users WHERE id = 7 AND name = '?'"),
+            select.bind(&["7".to_string()])
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("expected 1 parameters, got 0")),
+            select.bind(&[])
+        );
+
+        let insert = prepare("INSERT INTO users(id, name) VALUES(?, ?)").unwrap();
+        assert_eq!(
+            vec![Some("id".to_string()), Some("name".to_string())],
+            insert.columns()
+        );
+        assert_eq!(
+            parse("INSERT INTO users(id, name) VALUES(1, 'a b')"),
+            insert.bind(&["1".to_string(), "a b".to_string()])
+        );
+
+        let update = prepare("UPDATE users SET age = age + ? WHERE id = ?").unwrap();
+        assert_eq!(
+            vec![Some("age".to_string()), Some("id".to_string())],
+            update.columns()
+        );
+        assert_eq!(
+            parse("UPDATE users SET age = age + 2 WHERE id = 3"),
+            update.bind(&["2".to_string(), "3".to_string()])
+        );
+
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "parameters are only allowed in place of values"
+            )),
+            prepare("SELECT ? FROM users")
+        );
+        assert_eq!(
+            Command::Delete {
+                table: "users".to_string()
+            },
+            *prepare("DELETE FROM users").unwrap().command()
+        );
     }
 }
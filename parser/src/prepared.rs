@@ -0,0 +1,147 @@
+use std::mem;
+
+use common::error::DbError;
+
+use crate::{
+    command::{Command, Comparison, Expr},
+    token::{self, Token},
+};
+
+const MARKER: char = '\0';
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prepared {
+    command: Command,
+    parameters: usize,
+}
+
+impl Prepared {
+    pub(crate) fn parse(query: &str) -> Result<Self, DbError> {
+        let mut parameters = 0;
+        let tokens = token::tokenize(query)?
+            .into_iter()
+            .map(|token| match token {
+                Token::Parameter => {
+                    parameters += 1;
+                    Ok(Token::Element(marker(parameters - 1)))
+                }
+                Token::Element(element) if element.contains(MARKER) => {
+                    Err(DbError::invalid_input("unexpected NUL character in query"))
+                }
+                token => Ok(token),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut prepared = Self {
+            command: Command::parse(tokens)?,
+            parameters,
+        };
+        let mut found = 0;
+        visit(&mut prepared.command, &mut |value, _| {
+            found += parse_marker(value).is_some() as usize;
+        });
+        if found != parameters {
+            return Err(DbError::invalid_input(
+                "parameters are only allowed in place of values",
+            ));
+        }
+        Ok(prepared)
+    }
+
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+
+    pub fn columns(&self) -> Vec<Option<String>> {
+        let mut columns = vec![None; self.parameters];
+        let mut command = self.command.clone();
+        visit(&mut command, &mut |value, column| {
+            if let Some(idx) = parse_marker(value) {
+                columns[idx] = column.map(str::to_string);
+            }
+        });
+        columns
+    }
+
+    pub fn bind(&self, values: &[String]) -> Result<Command, DbError> {
+        if values.len() != self.parameters {
+            return Err(DbError::InvalidInput(format!(
+                "expected {} parameters, got {}",
+                self.parameters,
+                values.len()
+            )));
+        }
+        let mut command = self.command.clone();
+        visit(&mut command, &mut |value, _| {
+            if let Some(idx) = parse_marker(value) {
+                *value = values[idx].clone();
+            }
+        });
+        Ok(command)
+    }
+}
+
+fn marker(idx: usize) -> String {
+    format!("{}{}", MARKER, idx)
+}
+
+fn parse_marker(value: &str) -> Option<usize> {
+    value.strip_prefix(MARKER)?.parse().ok()
+}
+
+fn visit(command: &mut Command, f: &mut impl FnMut(&mut String, Option<&str>)) {
+    match command {
+        Command::Insert { fields, values, .. } => {
+            for row in values.iter_mut() {
+                for (idx, value) in row.iter_mut().enumerate() {
+                    f(value, fields.get(idx).map(String::as_str));
+                }
+            }
+        }
+        Command::Select {
+            conditions, having, ..
+        } => {
+            visit_conditions(conditions, f);
+            visit_conditions(having, f);
+        }
+        Command::Update {
+            assignments,
+            conditions,
+            ..
+        } => {
+            for assignment in assignments.iter_mut() {
+                visit_expr(&mut assignment.value, &assignment.column, f);
+            }
+            visit_conditions(conditions, f);
+        }
+        Command::Explain { command, .. } | Command::CopyTo { query: command, .. } => {
+            visit(command, f)
+        }
+        _ => {}
+    }
+}
+
+fn visit_conditions(conditions: &mut [Comparison], f: &mut impl FnMut(&mut String, Option<&str>)) {
+    for condition in conditions.iter_mut() {
+        f(&mut condition.value, Some(&condition.column));
+    }
+}
+
+fn visit_expr(expr: &mut Expr, column: &str, f: &mut impl FnMut(&mut String, Option<&str>)) {
+    match expr {
+        Expr::Value(value) => f(value, Some(column)),
+        Expr::Column(name) if parse_marker(name).is_some() => {
+            let mut value = mem::take(name);
+            f(&mut value, Some(column));
+            *expr = Expr::Value(value);
+        }
+        Expr::Column(_) => {}
+        Expr::Binary(left, _, right) => {
+            visit_expr(left, column, f);
+            visit_expr(right, column, f);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+pub(crate) fn split(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut str_char = None::<char>;
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match str_char {
+            Some(quote) => {
+                statement.push(c);
+                if c == '\\' {
+                    statement.extend(chars.next());
+                } else if c == quote {
+                    str_char = None;
+                }
+            }
+            None if c == '\'' || c == '"' => {
+                str_char = Some(c);
+                statement.push(c);
+            }
+            None if c == '-' && chars.peek() == Some(&'-') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            None if c == ';' => push(&mut statements, &mut statement),
+            None => statement.push(c),
+        }
+    }
+    push(&mut statements, &mut statement);
+    statements
+}
+
+fn push(statements: &mut Vec<String>, statement: &mut String) {
+    let trimmed = statement.trim();
+    if !trimmed.is_empty() {
+        statements.push(trimmed.to_string());
+    }
+    statement.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_statements() {
+        let script = "-- schema\nCREATE TABLE users(id int, name varchar(16));\n\n\
+            INSERT INTO users(id, name) VALUES(1, 'a;b'), (2, 'it\\'s -- fine'); -- seed\n\
+            SELECT id FROM users;;\n  DELETE FROM users";
+        assert_eq!(
+            vec![
+                "CREATE TABLE users(id int, name varchar(16))",
+                "INSERT INTO users(id, name) VALUES(1, 'a;b'), (2, 'it\\'s -- fine')",
+                "SELECT id FROM users",
+                "DELETE FROM users",
+            ],
+            split(script)
+        );
+        assert!(split(" ;\n-- only a comment").is_empty());
+    }
+}
@@ -2,18 +2,112 @@ use std::fmt::Display;
 
 use common::error::DbError;
 
+pub(crate) const KEYWORDS: &[&str] = &[
+    "analyze",
+    "and",
+    "as",
+    "asc",
+    "auto_increment",
+    "avg",
+    "begin",
+    "bigint",
+    "by",
+    "check",
+    "commit",
+    "copy",
+    "count",
+    "create",
+    "database",
+    "default",
+    "delete",
+    "delimiter",
+    "desc",
+    "distinct",
+    "drop",
+    "dump",
+    "explain",
+    "format",
+    "from",
+    "group",
+    "having",
+    "header",
+    "index",
+    "insert",
+    "int",
+    "into",
+    "limit",
+    "lower",
+    "max",
+    "min",
+    "nextval",
+    "not",
+    "now",
+    "null",
+    "on",
+    "or",
+    "order",
+    "rename",
+    "replace",
+    "restore",
+    "rollback",
+    "select",
+    "sequence",
+    "set",
+    "show",
+    "start",
+    "status",
+    "sum",
+    "table",
+    "temporary",
+    "to",
+    "transaction",
+    "update",
+    "upper",
+    "use",
+    "vacuum",
+    "values",
+    "varchar",
+    "view",
+    "where",
+];
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum Token {
     Create,
+    Drop,
     Table,
+    Index,
+    On,
     From,
     Select,
+    Distinct,
     Insert,
     Into,
     Delete,
+    Update,
+    Set,
+    Vacuum,
+    Copy,
+    To,
+    Dump,
+    Restore,
+    Show,
+    Explain,
+    Analyze,
+    Begin,
+    Commit,
+    Rollback,
     Where,
+    And,
+    Group,
+    Having,
+    Order,
+    By,
+    Limit,
     Values,
+    Parameter,
     Delimiter(char),
+    Operator(String),
     Element(String),
 }
 
@@ -26,14 +120,38 @@ impl Token {
     fn parse(token: &str) -> Option<Self> {
         match token {
             "create" => Some(Self::Create),
+            "drop" => Some(Self::Drop),
             "table" => Some(Self::Table),
+            "index" => Some(Self::Index),
+            "on" => Some(Self::On),
             "into" => Some(Self::Into),
             "insert" => Some(Self::Insert),
             "select" => Some(Self::Select),
+            "distinct" => Some(Self::Distinct),
             "delete" => Some(Self::Delete),
+            "update" => Some(Self::Update),
+            "set" => Some(Self::Set),
+            "vacuum" => Some(Self::Vacuum),
+            "copy" => Some(Self::Copy),
+            "to" => Some(Self::To),
+            "dump" => Some(Self::Dump),
+            "restore" => Some(Self::Restore),
+            "show" => Some(Self::Show),
+            "explain" => Some(Self::Explain),
+            "analyze" => Some(Self::Analyze),
+            "begin" => Some(Self::Begin),
+            "commit" => Some(Self::Commit),
+            "rollback" => Some(Self::Rollback),
             "from" => Some(Self::From),
             "where" => Some(Self::Where),
+            "and" => Some(Self::And),
+            "group" => Some(Self::Group),
+            "having" => Some(Self::Having),
+            "order" => Some(Self::Order),
+            "by" => Some(Self::By),
+            "limit" => Some(Self::Limit),
             "values" => Some(Self::Values),
+            "?" => Some(Self::Parameter),
             _ => None,
         }
     }
@@ -43,15 +161,40 @@ impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Create => write!(f, "CREATE"),
+            Self::Drop => write!(f, "DROP"),
             Self::Table => write!(f, "TABLE"),
+            Self::Index => write!(f, "INDEX"),
+            Self::On => write!(f, "ON"),
             Self::From => write!(f, "FROM"),
             Self::Select => write!(f, "SELECT"),
+            Self::Distinct => write!(f, "DISTINCT"),
             Self::Insert => write!(f, "INSERT"),
             Self::Into => write!(f, "INSERT"),
             Self::Delete => write!(f, "DELETE"),
+            Self::Update => write!(f, "UPDATE"),
+            Self::Set => write!(f, "SET"),
+            Self::Vacuum => write!(f, "VACUUM"),
+            Self::Copy => write!(f, "COPY"),
+            Self::To => write!(f, "TO"),
+            Self::Dump => write!(f, "DUMP"),
+            Self::Restore => write!(f, "RESTORE"),
+            Self::Show => write!(f, "SHOW"),
+            Self::Explain => write!(f, "EXPLAIN"),
+            Self::Analyze => write!(f, "ANALYZE"),
+            Self::Begin => write!(f, "BEGIN"),
+            Self::Commit => write!(f, "COMMIT"),
+            Self::Rollback => write!(f, "ROLLBACK"),
             Self::Where => write!(f, "WHERE"),
+            Self::And => write!(f, "AND"),
+            Self::Group => write!(f, "GROUP"),
+            Self::Having => write!(f, "HAVING"),
+            Self::Order => write!(f, "ORDER"),
+            Self::By => write!(f, "BY"),
+            Self::Limit => write!(f, "LIMIT"),
             Self::Values => write!(f, "VALUES"),
+            Self::Parameter => write!(f, "?"),
             Self::Delimiter(c) => write!(f, "{}", c),
+            Self::Operator(op) => write!(f, "{}", op),
             Self::Element(el) => write!(f, "'{}'", el),
         }
     }
@@ -82,6 +225,16 @@ pub(crate) fn tokenize(query: &str) -> Result<Vec<Token>, DbError> {
             } else {
                 token_chars.push(c);
             }
+        } else if is_operator(c) {
+            if !token_chars.is_empty() {
+                let token: String = token_chars.into_iter().collect();
+                tokens.push(Token::Element(token));
+                token_chars = Vec::new();
+            }
+            match tokens.last_mut() {
+                Some(Token::Operator(op)) if is_operator(prev_char) => op.push(c),
+                _ => tokens.push(Token::Operator(c.to_string())),
+            }
         } else if is_delimeter(c) || i == last_idx {
             if last_idx == i && !is_delimeter(c) {
                 token_chars.push(c);
@@ -110,6 +263,10 @@ fn is_str_token(c: char) -> bool {
     c == '\'' || c == '"'
 }
 
+fn is_operator(c: char) -> bool {
+    c == '=' || c == '<' || c == '>' || c == '!'
+}
+
 fn is_markable_delimeter(c: char) -> bool {
     c == '(' || c == ')' || c == ','
 }
@@ -122,6 +279,18 @@ fn is_delimeter(c: char) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn keywords() {
+        assert!(KEYWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+        let tokens = tokenize(&KEYWORDS.join(" ")).unwrap();
+        assert_eq!(KEYWORDS.len(), tokens.len());
+        let reserved = tokens
+            .iter()
+            .filter(|token| !matches!(token, Token::Element(_)))
+            .count();
+        assert_eq!(32, reserved);
+    }
+
     #[test]
     fn create() {
         let query = "CREATE TABLE(id int, name varchar(256))";
@@ -197,6 +366,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn operators() {
+        let query = "SELECT id FROM test WHERE id>=5 AND name = 'a=b' and age<>1";
+        let tokens = tokenize(query).unwrap();
+        assert_eq!(
+            vec![
+                Token::Select,
+                Token::element("id"),
+                Token::From,
+                Token::element("test"),
+                Token::Where,
+                Token::element("id"),
+                Token::Operator(">=".to_string()),
+                Token::element("5"),
+                Token::And,
+                Token::element("name"),
+                Token::Operator("=".to_string()),
+                Token::element("a=b"),
+                Token::And,
+                Token::element("age"),
+                Token::Operator("<>".to_string()),
+                Token::element("1"),
+            ],
+            tokens
+        );
+    }
+
     #[test]
     fn str_with_escaped() {
         let query = "\"\\\" \"";
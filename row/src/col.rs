@@ -1,3 +1,5 @@
+use core::fmt;
+
 use common::{Pageable, error::DbError, read_num};
 
 pub const INT_SIZE: usize = 4;
@@ -10,7 +12,8 @@ pub const INT_TYPE: u8 = 1;
 pub const BIG_INT_TYPE: u8 = 2;
 pub const VARCHAR_TYPE: u8 = 3;
 
-#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Col {
     Int(i32),
     BigInt(i64),
@@ -51,6 +54,42 @@ impl Col {
         Ok(Self::BigInt(value))
     }
 
+    pub fn compact_size(&self) -> usize {
+        match self {
+            Self::Varchar(value, _) => COL_TYPE_SIZE + VARCHAR_LEN_SIZE * 2 + value.len(),
+            _ => self.size(),
+        }
+    }
+
+    pub fn write_compact(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+        let Self::Varchar(value, size) = self else {
+            return self.write(buffer);
+        };
+        let len = value.len();
+        let mut offset = 0;
+        buffer[offset] = VARCHAR_TYPE;
+        offset += COL_TYPE_SIZE;
+        buffer[offset..offset + VARCHAR_LEN_SIZE].copy_from_slice(&size.to_be_bytes());
+        offset += VARCHAR_LEN_SIZE;
+        buffer[offset..offset + VARCHAR_LEN_SIZE].copy_from_slice(&(len as u16).to_be_bytes());
+        offset += VARCHAR_LEN_SIZE;
+        buffer[offset..offset + len].copy_from_slice(value.as_bytes());
+        Ok(offset + len)
+    }
+
+    pub fn read_compact(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+        if buffer[0] != VARCHAR_TYPE {
+            return Self::read(buffer);
+        }
+        let mut offset = COL_TYPE_SIZE;
+        let size = read_num!(buffer, u16, offset);
+        offset += VARCHAR_LEN_SIZE;
+        let len = read_num!(buffer, u16, offset) as usize;
+        offset += VARCHAR_LEN_SIZE;
+        let value = String::from_utf8_lossy(&buffer[offset..offset + len]);
+        Ok((Col::Varchar(value.to_string(), size), offset + len))
+    }
+
     pub fn parse_varchar(buffer: &[u8]) -> Result<(Self, usize), DbError> {
         let mut offset = 0;
         let max_len = read_num!(buffer, u16, offset);
@@ -66,6 +105,16 @@ impl Col {
     }
 }
 
+impl fmt::Display for Col {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::BigInt(value) => write!(f, "{}", value),
+            Self::Varchar(value, _) => write!(f, "{}", value),
+        }
+    }
+}
+
 impl Pageable for Col {
     fn write(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
         let mut offset = 1;
@@ -82,9 +131,12 @@ impl Pageable for Col {
                 Ok(offset)
             }
             Self::Varchar(value, size) => {
+                let len = value.len();
+                if len > *size as usize {
+                    return Err(DbError::MaxSize(len, *size as usize));
+                }
                 buffer[offset..offset + VARCHAR_LEN_SIZE].copy_from_slice(&(*size).to_be_bytes());
                 offset += VARCHAR_LEN_SIZE;
-                let len = value.len();
                 buffer[offset..offset + VARCHAR_LEN_SIZE]
                     .copy_from_slice(&(len as u16).to_be_bytes());
                 offset += VARCHAR_LEN_SIZE;
@@ -171,6 +223,27 @@ mod tests {
         assert_eq!(Col::Varchar(value.to_string(), max_size), col);
     }
 
+    #[test]
+    fn write_oversized_varchar() {
+        let varchar = Col::varchar("привет", 6);
+        let mut buffer = vec![0u8; varchar.size()];
+        assert_eq!(Err(DbError::MaxSize(12, 6)), varchar.write(&mut buffer));
+    }
+
+    #[test]
+    fn write_read_compact() {
+        let varchar = Col::varchar("Hello", 256);
+        assert_eq!(10, varchar.compact_size());
+        let mut buffer = vec![0u8; varchar.compact_size()];
+        assert_eq!(10, varchar.write_compact(&mut buffer).unwrap());
+        assert_eq!((varchar, 10), Col::read_compact(&buffer).unwrap());
+
+        let int = Col::int(7);
+        let mut buffer = vec![0u8; int.compact_size()];
+        int.write_compact(&mut buffer).unwrap();
+        assert_eq!((int, 5), Col::read_compact(&buffer).unwrap());
+    }
+
     #[test]
     fn row_size() {
         assert_eq!(COL_TYPE_SIZE + INT_SIZE, Col::Int(1).size());
@@ -190,4 +263,11 @@ mod tests {
             _ => panic!("expected error"),
         }
     }
+
+    #[test]
+    fn display() {
+        assert_eq!("42", Col::int(42).to_string());
+        assert_eq!("-7", Col::big_int(-7).to_string());
+        assert_eq!("ann", Col::varchar("ann", 8).to_string());
+    }
 }
@@ -8,6 +8,7 @@ const COL_TYPE_SIZE: usize = 1;
 const COL_NAME_LEN_SIZE: usize = 1;
 
 #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColType {
     Int(String),
     BigInt(String),
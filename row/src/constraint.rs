@@ -0,0 +1,155 @@
+use core::fmt;
+
+use common::{Pageable, error::DbError};
+
+const NOT_NULL_TYPE: u8 = 1;
+const AUTO_INCREMENT_TYPE: u8 = 2;
+const DEFAULT_TYPE: u8 = 3;
+const NOW_TYPE: u8 = 4;
+
+const CONSTRAINT_TYPE_SIZE: usize = 1;
+const COL_NAME_LEN_SIZE: usize = 1;
+const DEFAULT_LEN_SIZE: usize = 2;
+
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constraint {
+    NotNull(String),
+    AutoIncrement(String),
+    Default(String, String),
+    Now(String),
+}
+
+impl Constraint {
+    pub fn not_null(column: &str) -> Self {
+        Self::NotNull(column.to_string())
+    }
+
+    pub fn auto_increment(column: &str) -> Self {
+        Self::AutoIncrement(column.to_string())
+    }
+
+    pub fn default(column: &str, value: &str) -> Self {
+        Self::Default(column.to_string(), value.to_string())
+    }
+
+    pub fn now(column: &str) -> Self {
+        Self::Now(column.to_string())
+    }
+
+    pub fn get_column(&self) -> &str {
+        match self {
+            Self::NotNull(column)
+            | Self::AutoIncrement(column)
+            | Self::Default(column, _)
+            | Self::Now(column) => column,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotNull(column) => write!(f, "{} NOT NULL", column),
+            Self::AutoIncrement(column) => write!(f, "{} AUTO_INCREMENT", column),
+            Self::Default(column, value) => write!(f, "{} DEFAULT '{}'", column, value),
+            Self::Now(column) => write!(f, "{} DEFAULT NOW()", column),
+        }
+    }
+}
+
+impl Pageable for Constraint {
+    fn write(&self, buffer: &mut [u8]) -> Result<usize, DbError> {
+        let mut offset = 0;
+        buffer[offset] = match self {
+            Self::NotNull(_) => NOT_NULL_TYPE,
+            Self::AutoIncrement(_) => AUTO_INCREMENT_TYPE,
+            Self::Default(_, _) => DEFAULT_TYPE,
+            Self::Now(_) => NOW_TYPE,
+        };
+        offset += CONSTRAINT_TYPE_SIZE;
+        let column = self.get_column();
+        let len = column.len();
+        buffer[offset] = len as u8;
+        offset += COL_NAME_LEN_SIZE;
+        buffer[offset..offset + len].copy_from_slice(column.as_bytes());
+        offset += len;
+        if let Self::Default(_, value) = self {
+            let len = value.len();
+            buffer[offset..offset + DEFAULT_LEN_SIZE].copy_from_slice(&(len as u16).to_le_bytes());
+            offset += DEFAULT_LEN_SIZE;
+            buffer[offset..offset + len].copy_from_slice(value.as_bytes());
+            offset += len;
+        }
+        Ok(offset)
+    }
+
+    fn read(buffer: &[u8]) -> Result<(Self, usize), DbError> {
+        let mut offset = 0;
+        let constraint_type = *buffer.get(offset).ok_or(DbError::Encoding)?;
+        offset += CONSTRAINT_TYPE_SIZE;
+        let len = *buffer.get(offset).ok_or(DbError::Encoding)? as usize;
+        offset += COL_NAME_LEN_SIZE;
+        let Some(column) = buffer.get(offset..offset + len) else {
+            return Err(DbError::Encoding);
+        };
+        offset += len;
+        let column = String::from_utf8_lossy(column).to_string();
+        let constraint = match constraint_type {
+            NOT_NULL_TYPE => Self::NotNull(column),
+            AUTO_INCREMENT_TYPE => Self::AutoIncrement(column),
+            NOW_TYPE => Self::Now(column),
+            DEFAULT_TYPE => {
+                let Some(len) = buffer.get(offset..offset + DEFAULT_LEN_SIZE) else {
+                    return Err(DbError::Encoding);
+                };
+                let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+                offset += DEFAULT_LEN_SIZE;
+                let Some(value) = buffer.get(offset..offset + len) else {
+                    return Err(DbError::Encoding);
+                };
+                offset += len;
+                Self::Default(column, String::from_utf8_lossy(value).to_string())
+            }
+            _ => return Err(DbError::Encoding),
+        };
+        Ok((constraint, offset))
+    }
+
+    fn size(&self) -> usize {
+        let size = CONSTRAINT_TYPE_SIZE + COL_NAME_LEN_SIZE + self.get_column().len();
+        match self {
+            Self::Default(_, value) => size + DEFAULT_LEN_SIZE + value.len(),
+            _ => size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let constraint = Constraint::not_null("name");
+        let mut buffer = vec![0u8; constraint.size()];
+        assert_eq!(constraint.size(), constraint.write(&mut buffer).unwrap());
+        let (restored, read) = Constraint::read(&buffer).unwrap();
+        assert_eq!(constraint.size(), read);
+        assert_eq!(constraint, restored);
+        assert_eq!("name NOT NULL", restored.to_string());
+        for constraint in [
+            Constraint::auto_increment("id"),
+            Constraint::default("name", "n/a"),
+            Constraint::now("created"),
+        ] {
+            let mut buffer = vec![0u8; constraint.size()];
+            assert_eq!(buffer.len(), constraint.write(&mut buffer).unwrap());
+            assert_eq!(
+                (constraint, buffer.len()),
+                Constraint::read(&buffer).unwrap()
+            );
+        }
+        assert_eq!(Err(DbError::Encoding), Constraint::read(&[7]));
+    }
+}
@@ -1,10 +1,12 @@
 mod col;
 mod col_type;
+mod constraint;
 mod row;
 mod row_type;
 
 pub use col::Col;
 pub use col_type::ColType;
+pub use constraint::Constraint;
 pub use row::Row;
 pub use row_type::RowType;
 
@@ -18,6 +20,6 @@ macro_rules! row {
 #[macro_export]
 macro_rules! row_type {
     [$cols:expr] => {
-        RowType {columns: vec![$cols]}
+        RowType {columns: vec![$cols], constraints: vec![]}
     };
 }
@@ -5,6 +5,7 @@ use crate::Col;
 pub const ROW_COLS_SIZE: usize = 1;
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Row {
     pub columns: Vec<Col>,
 }
@@ -92,4 +93,15 @@ mod tests {
         let r_size = row.size();
         assert_eq!(size, r_size);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let row = Row {
+            columns: vec![Col::int(1), Col::big_int(2), Col::varchar("John", 16)],
+        };
+        let json = serde_json::to_string(&row).unwrap();
+        let restored: Row = serde_json::from_str(&json).unwrap();
+        assert_eq!(row, restored);
+    }
 }
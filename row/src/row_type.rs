@@ -1,12 +1,15 @@
 use common::{Pageable, error::DbError};
 
 const ROW_TYPE_COLS_LEN_SIZE: usize = 1;
+const ROW_TYPE_CONSTRAINTS_LEN_SIZE: usize = 1;
 
-use crate::ColType;
+use crate::{ColType, Constraint};
 
 #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RowType {
     pub columns: Vec<ColType>,
+    pub constraints: Vec<Constraint>,
 }
 
 impl RowType {
@@ -16,6 +19,19 @@ impl RowType {
             .cloned()
             .ok_or(DbError::PrimaryKeyNotSet)
     }
+
+    pub fn is_not_null(&self, column: &str) -> bool {
+        self.constraints.contains(&Constraint::not_null(column))
+    }
+
+    pub fn auto_increment(&self) -> impl Iterator<Item = &str> {
+        self.constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                Constraint::AutoIncrement(column) => Some(column.as_str()),
+                _ => None,
+            })
+    }
 }
 
 impl Pageable for RowType {
@@ -26,6 +42,13 @@ impl Pageable for RowType {
         for col in self.columns.iter() {
             offset += col.write(&mut buffer[offset..])?;
         }
+        if !self.constraints.is_empty() {
+            buffer[offset] = self.constraints.len() as u8;
+            offset += ROW_TYPE_CONSTRAINTS_LEN_SIZE;
+            for constraint in self.constraints.iter() {
+                offset += constraint.write(&mut buffer[offset..])?;
+            }
+        }
         Ok(offset)
     }
 
@@ -39,7 +62,23 @@ impl Pageable for RowType {
             offset += read;
             columns.push(col);
         }
-        Ok((Self { columns }, offset))
+        let mut constraints = Vec::new();
+        if offset < buffer.len() {
+            let len = buffer[offset] as usize;
+            offset += ROW_TYPE_CONSTRAINTS_LEN_SIZE;
+            for _ in 0..len {
+                let (constraint, read) = Constraint::read(&buffer[offset..])?;
+                offset += read;
+                constraints.push(constraint);
+            }
+        }
+        Ok((
+            Self {
+                columns,
+                constraints,
+            },
+            offset,
+        ))
     }
 
     fn size(&self) -> usize {
@@ -47,6 +86,12 @@ impl Pageable for RowType {
         for col in self.columns.iter() {
             size += col.size();
         }
+        if !self.constraints.is_empty() {
+            size += ROW_TYPE_CONSTRAINTS_LEN_SIZE;
+            for constraint in self.constraints.iter() {
+                size += constraint.size();
+            }
+        }
         size
     }
 }
@@ -60,6 +105,7 @@ mod tests {
         let key = ColType::int("id");
         let row = RowType {
             columns: vec![key.clone()],
+            constraints: vec![],
         };
         let pk = row.get_primary_key().unwrap();
         assert_eq!(pk, key);
@@ -73,6 +119,7 @@ mod tests {
                 ColType::bigint("timestamp"),
                 ColType::varchar("name", 16),
             ],
+            constraints: vec![],
         };
         let mut buffer = vec![0u8; row.size()];
         let write = row.write(&mut buffer).unwrap();
@@ -81,4 +128,19 @@ mod tests {
         assert_eq!(read, row.size());
         assert_eq!(restored, row);
     }
+
+    #[test]
+    fn write_read_constraints() {
+        let row = RowType {
+            columns: vec![ColType::int("id"), ColType::varchar("name", 16)],
+            constraints: vec![Constraint::not_null("name")],
+        };
+        let mut buffer = vec![0u8; row.size()];
+        assert_eq!(row.size(), row.write(&mut buffer).unwrap());
+        let (restored, read) = RowType::read(&buffer).unwrap();
+        assert_eq!(row.size(), read);
+        assert_eq!(row, restored);
+        assert!(restored.is_not_null("name"));
+        assert!(!restored.is_not_null("id"));
+    }
 }
@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+use common::error::DbError;
+use runner::{config::Config, mysql::Server};
+
+fn main() -> Result<(), DbError> {
+    let config = Config::builder().path(PathBuf::from("storage")).build();
+    let server = Server::new(config, "127.0.0.1:3306")?;
+    println!("listening on {}", server.local_addr()?);
+    server.run()
+}
@@ -0,0 +1,209 @@
+use std::sync::{Arc, Mutex};
+
+use common::error::DbError;
+use engine::{Engine, Session};
+use tokio::{
+    sync::mpsc::{Receiver, Sender},
+    task,
+};
+use tracing::info_span;
+
+use crate::{
+    command,
+    config::Config,
+    open_engine,
+    protocol::{Request, Response},
+};
+
+pub struct AsyncRunner {
+    engine: Arc<Engine>,
+    session: Arc<Mutex<Session>>,
+    id: u64,
+    tx: Sender<Response>,
+    rx: Receiver<Request>,
+}
+
+impl AsyncRunner {
+    pub async fn new(
+        config: Config,
+        tx: Sender<Response>,
+        rx: Receiver<Request>,
+    ) -> Result<Self, DbError> {
+        let engine = blocking(move || open_engine(&config)).await?;
+        Ok(Self::with_engine(Arc::new(engine), tx, rx))
+    }
+
+    pub fn with_engine(engine: Arc<Engine>, tx: Sender<Response>, rx: Receiver<Request>) -> Self {
+        let session = engine.session();
+        Self {
+            id: session.id(),
+            session: Arc::new(Mutex::new(session)),
+            engine,
+            tx,
+            rx,
+        }
+    }
+
+    pub fn attach(&self, tx: Sender<Response>, rx: Receiver<Request>) -> Self {
+        Self::with_engine(self.engine.clone(), tx, rx)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub async fn run(mut self) -> Result<(), DbError> {
+        while let Some(request) = self.rx.recv().await {
+            self.execute(request).await?;
+        }
+        self.shutdown().await
+    }
+
+    async fn shutdown(self) -> Result<(), DbError> {
+        let Self {
+            engine, session, ..
+        } = self;
+        blocking(move || {
+            drop(session);
+            engine.flush()
+        })
+        .await
+    }
+
+    async fn execute(&mut self, request: Request) -> Result<(), DbError> {
+        let Request { id, sql } = request;
+        let engine = self.engine.clone();
+        let session = self.session.clone();
+        let span = info_span!("request", id, session = self.id);
+        let result = blocking(move || {
+            let _entered = span.enter();
+            let mut session = session
+                .lock()
+                .map_err(|_| DbError::unexpected("session lock is poisoned"))?;
+            command::execute(&engine, &mut session, &sql)
+        })
+        .await;
+        if let Err(err) = self.tx.send(Response { id, result }).await {
+            return Err(DbError::IO(err.to_string()));
+        }
+        Ok(())
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> Result<T, DbError> + Send + 'static,
+) -> Result<T, DbError> {
+    task::spawn_blocking(operation)
+        .await
+        .map_err(|err| DbError::Unexpected(err.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use engine::exec_result::ExecResult;
+    use row::Col;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn queries() {
+        let (r_tx, mut r_rx) = mpsc::channel(1);
+        let (q_tx, q_rx) = mpsc::channel(1);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = Config::builder()
+            .path(PathBuf::from(temp_dir.path()))
+            .build();
+        let runner = AsyncRunner::new(config, r_tx, q_rx).await.unwrap();
+        let handle = tokio::spawn(runner.run());
+
+        q_tx.send(Request::new(
+            1,
+            "CREATE TABLE users(id INT, name VARCHAR(16))",
+        ))
+        .await
+        .unwrap();
+        assert_eq!(
+            Some(Response {
+                id: 1,
+                result: Ok(ExecResult::ack("created"))
+            }),
+            r_rx.recv().await
+        );
+        q_tx.send(Request::new(
+            2,
+            "INSERT INTO users(id, name) VALUES(1, 'John')",
+        ))
+        .await
+        .unwrap();
+        assert_eq!(
+            Some(Response {
+                id: 2,
+                result: Ok(ExecResult::affected("inserted", 1))
+            }),
+            r_rx.recv().await
+        );
+        q_tx.send(Request::new(3, "SELECT id, name FROM users"))
+            .await
+            .unwrap();
+        let Some(Response {
+            id: 3,
+            result: Ok(selected),
+        }) = r_rx.recv().await
+        else {
+            panic!("cannot select");
+        };
+        assert_eq!(
+            &[vec![Col::int(1), Col::varchar("John", 16)]],
+            selected.rows()
+        );
+        q_tx.send(Request::new(4, "SELEC")).await.unwrap();
+        assert!(matches!(
+            r_rx.recv().await,
+            Some(Response { result: Err(_), .. })
+        ));
+        q_tx.send(Request::new(5, "CANCEL 999")).await.unwrap();
+        assert!(matches!(
+            r_rx.recv().await,
+            Some(Response { result: Err(_), .. })
+        ));
+
+        drop(q_tx);
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    type Channels = (Sender<Request>, Receiver<Response>);
+
+    async fn query(channels: &mut Channels, query: &str) -> Result<ExecResult, DbError> {
+        channels.0.send(Request::new(0, query)).await.unwrap();
+        channels.1.recv().await.unwrap().result
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shared_engine() {
+        let engine = Arc::new(Engine::in_memory());
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let (r_tx, r_rx) = mpsc::channel(1);
+            let (q_tx, q_rx) = mpsc::channel(1);
+            let runner = AsyncRunner::with_engine(engine.clone(), r_tx, q_rx);
+            tokio::spawn(runner.run());
+            sessions.push((q_tx, r_rx));
+        }
+        let [first, second] = sessions.as_mut_slice() else {
+            unreachable!();
+        };
+        query(first, "CREATE TABLE users(id INT)").await.unwrap();
+        query(first, "BEGIN").await.unwrap();
+        query(first, "INSERT INTO users(id) VALUES(1)")
+            .await
+            .unwrap();
+        let users = query(second, "SELECT id FROM users").await.unwrap();
+        assert!(users.rows().is_empty());
+        query(first, "COMMIT").await.unwrap();
+        let users = query(second, "SELECT id FROM users").await.unwrap();
+        assert_eq!(vec![vec![Col::int(1)]], users.rows());
+    }
+}
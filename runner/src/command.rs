@@ -0,0 +1,157 @@
+use std::{iter::Peekable, time::Instant};
+
+use common::error::DbError;
+use engine::{Engine, Session, exec_result::ExecResult};
+use row::Col;
+use tracing::{debug, warn};
+
+pub(crate) fn execute(
+    engine: &Engine,
+    session: &mut Session,
+    query: &str,
+) -> Result<ExecResult, DbError> {
+    let query = query.trim();
+    let (keyword, rest) = query.split_once(char::is_whitespace).unwrap_or((query, ""));
+    match keyword.to_lowercase().as_str() {
+        "cancel" => {
+            engine.cancel(rest.trim().parse()?)?;
+            Ok(ExecResult::ack("cancelled"))
+        }
+        "prepare" => {
+            let (name, sql) = prepare(rest)?;
+            session.prepare(name, sql)?;
+            Ok(ExecResult::ack("prepared"))
+        }
+        "execute" => {
+            let (name, params) = arguments(rest)?;
+            session.execute_prepared(name, &params)
+        }
+        "deallocate" => {
+            session.deallocate(rest.trim())?;
+            Ok(ExecResult::ack("deallocated"))
+        }
+        _ => {
+            let started = Instant::now();
+            let command = parser::parse(query)
+                .inspect_err(|err| warn!(error = %err, query, "cannot parse statement"))?;
+            debug!(elapsed = ?started.elapsed(), "parsed");
+            session.execute(command)
+        }
+    }
+}
+
+fn prepare(input: &str) -> Result<(&str, &str), DbError> {
+    let expected = || DbError::invalid_input("expected PREPARE <name> AS <statement>");
+    let (name, rest) = input
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(expected)?;
+    let (keyword, sql) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .ok_or_else(expected)?;
+    if !keyword.eq_ignore_ascii_case("as") {
+        return Err(expected());
+    }
+    Ok((name, sql))
+}
+
+fn arguments(input: &str) -> Result<(&str, Vec<Col>), DbError> {
+    let input = input.trim();
+    let end = input
+        .find(|c: char| c == '(' || c.is_whitespace())
+        .unwrap_or(input.len());
+    let (name, rest) = input.split_at(end);
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Ok((name, vec![]));
+    }
+    let Some(list) = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+    else {
+        return Err(DbError::invalid_input(
+            "expected EXECUTE <name> [(<value>, ...)]",
+        ));
+    };
+    Ok((name, parameters(list)?))
+}
+
+fn parameters(list: &str) -> Result<Vec<Col>, DbError> {
+    let mut params = Vec::new();
+    if list.trim().is_empty() {
+        return Ok(params);
+    }
+    let mut chars = list.chars().peekable();
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.next_if_eq(&'\'').is_some() {
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') if chars.next_if_eq(&'\'').is_some() => value.push('\''),
+                    Some('\'') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(DbError::eof("unterminated string parameter")),
+                }
+            }
+            let size = u16::try_from(value.len())
+                .map_err(|_| DbError::MaxSize(value.len(), u16::MAX as usize))?;
+            params.push(Col::varchar(&value, size));
+        } else {
+            let mut value = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',' && !c.is_whitespace()) {
+                value.push(c);
+            }
+            params.push(Col::big_int(value.parse()?));
+        }
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => return Ok(params),
+            Some(',') => continue,
+            Some(c) => {
+                return Err(DbError::InvalidInput(format!(
+                    "unexpected character '{}' in parameters",
+                    c
+                )));
+            }
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<impl Iterator<Item = char>>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_arguments() {
+        assert_eq!(
+            Ok(("insert", "INSERT INTO users(id) VALUES(?)")),
+            prepare(" insert  as INSERT INTO users(id) VALUES(?)")
+        );
+        assert!(prepare("insert INSERT INTO users(id) VALUES(?)").is_err());
+        assert_eq!(Ok(("select", vec![])), arguments("select"));
+        assert_eq!(Ok(("select", vec![])), arguments("select ()"));
+        assert_eq!(
+            Ok(("insert", vec![Col::big_int(-1), Col::varchar("it's, x", 7)])),
+            arguments("insert(-1,  'it''s, x' )")
+        );
+        assert!(arguments("insert(1 2)").is_err());
+        assert!(arguments("insert('x)").is_err());
+        assert!(arguments("insert(x)").is_err());
+        assert!(arguments("insert 1").is_err());
+        assert_eq!(
+            Ok(("insert", vec![Col::varchar("héllo", 6)])),
+            arguments("insert('héllo')")
+        );
+        let long = "é".repeat(u16::MAX as usize / 2 + 1);
+        assert_eq!(
+            Err(DbError::MaxSize(long.len(), u16::MAX as usize)),
+            arguments(&format!("insert('{}')", long))
+        );
+    }
+}
@@ -1,19 +1,38 @@
 use std::{env::home_dir, path::PathBuf};
 
+use engine::VarcharMode;
+
 const DEFAULT_DIR: &str = ".sql";
 
 pub struct Config {
     pub(crate) path: PathBuf,
+    pub(crate) varchar_mode: VarcharMode,
+    pub(crate) read_only: bool,
+    pub(crate) audit_log: Option<PathBuf>,
+    pub(crate) tls: Option<(PathBuf, PathBuf)>,
+    pub(crate) credentials: Option<(String, String)>,
 }
 
 impl Config {
     pub fn builder() -> ConfigBuilder {
-        ConfigBuilder { path: None }
+        ConfigBuilder {
+            path: None,
+            varchar_mode: VarcharMode::default(),
+            read_only: false,
+            audit_log: None,
+            tls: None,
+            credentials: None,
+        }
     }
 }
 
 pub struct ConfigBuilder {
     path: Option<PathBuf>,
+    varchar_mode: VarcharMode,
+    read_only: bool,
+    audit_log: Option<PathBuf>,
+    tls: Option<(PathBuf, PathBuf)>,
+    credentials: Option<(String, String)>,
 }
 
 impl ConfigBuilder {
@@ -22,9 +41,39 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn varchar_mode(mut self, mode: VarcharMode) -> Self {
+        self.varchar_mode = mode;
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn audit_log(mut self, path: PathBuf) -> Self {
+        self.audit_log = Some(path);
+        self
+    }
+
+    pub fn tls(mut self, cert: PathBuf, key: PathBuf) -> Self {
+        self.tls = Some((cert, key));
+        self
+    }
+
+    pub fn credentials(mut self, user: &str, password: &str) -> Self {
+        self.credentials = Some((user.to_string(), password.to_string()));
+        self
+    }
+
     pub fn build(self) -> Config {
         Config {
             path: self.path.unwrap_or(default_path()),
+            varchar_mode: self.varchar_mode,
+            read_only: self.read_only,
+            audit_log: self.audit_log,
+            tls: self.tls,
+            credentials: self.credentials,
         }
     }
 }
@@ -43,5 +92,29 @@ mod tests {
     fn config_builder() {
         let config = Config::builder().path(PathBuf::from("test")).build();
         assert!(config.path.to_string_lossy().to_string().ends_with("test"));
+        assert_eq!(VarcharMode::Strict, config.varchar_mode);
+        assert!(!config.read_only);
+        assert_eq!(None, config.audit_log);
+        assert_eq!(None, config.tls);
+        assert_eq!(None, config.credentials);
+        let config = Config::builder()
+            .path(PathBuf::from("test"))
+            .varchar_mode(VarcharMode::Lenient)
+            .read_only(true)
+            .audit_log(PathBuf::from("audit.csv"))
+            .tls(PathBuf::from("server.crt"), PathBuf::from("server.key"))
+            .credentials("admin", "secret")
+            .build();
+        assert_eq!(VarcharMode::Lenient, config.varchar_mode);
+        assert!(config.read_only);
+        assert_eq!(Some(PathBuf::from("audit.csv")), config.audit_log);
+        assert_eq!(
+            Some((PathBuf::from("server.crt"), PathBuf::from("server.key"))),
+            config.tls
+        );
+        assert_eq!(
+            Some(("admin".to_string(), "secret".to_string())),
+            config.credentials
+        );
     }
 }
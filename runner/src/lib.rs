@@ -1,53 +1,115 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{
+    Arc,
+    mpsc::{Receiver, Sender},
+};
 
 use common::error::DbError;
-use engine::{Engine, exec_result::ExecResult};
+use engine::{Engine, Session};
+use tracing::{info_span, warn};
 
 use crate::config::Config;
+pub use crate::protocol::{Request, Response};
 
+#[cfg(feature = "async")]
+pub use async_runner::AsyncRunner;
+
+#[cfg(feature = "async")]
+mod async_runner;
+mod command;
 pub mod config;
+pub mod mysql;
+mod protocol;
+pub mod script;
+#[cfg(feature = "shell")]
+pub mod shell;
+#[cfg(feature = "tls")]
+mod tls;
 
 pub struct Runner {
-    engine: Engine,
-    tx: Sender<Result<ExecResult, DbError>>,
-    rx: Receiver<String>,
+    engine: Arc<Engine>,
+    session: Session,
+    tx: Sender<Response>,
+    rx: Receiver<Request>,
 }
 
 impl Runner {
     pub fn new(
         config: Config,
-        tx: Sender<Result<ExecResult, DbError>>,
-        rx: Receiver<String>,
+        tx: Sender<Response>,
+        rx: Receiver<Request>,
     ) -> Result<Self, DbError> {
-        let engine = Engine::new(&config.path)?;
-        Ok(Self { engine, tx, rx })
+        Ok(Self::with_engine(Arc::new(open_engine(&config)?), tx, rx))
     }
 
-    pub fn run(self) -> Result<(), DbError> {
-        loop {
-            match self.rx.recv() {
-                Ok(query) => self.execute(query)?,
-                Err(err) => return Err(DbError::IO(err.to_string())),
+    pub fn with_engine(engine: Arc<Engine>, tx: Sender<Response>, rx: Receiver<Request>) -> Self {
+        Self {
+            session: engine.session(),
+            engine,
+            tx,
+            rx,
+        }
+    }
+
+    pub fn attach(&self, tx: Sender<Response>, rx: Receiver<Request>) -> Self {
+        Self::with_engine(self.engine.clone(), tx, rx)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.session.id()
+    }
+
+    pub fn run(mut self) -> Result<(), DbError> {
+        while let Ok(request) = self.rx.recv() {
+            if let Err(err) = self.execute(request) {
+                if let Err(flush) = self.shutdown() {
+                    warn!(error = %flush, "shutdown failed");
+                }
+                return Err(err);
             }
         }
+        self.shutdown()
     }
 
-    fn execute(&self, query: String) -> Result<(), DbError> {
-        let result = match parser::parse(&query) {
-            Ok(command) => self.engine.execute(command),
-            Err(err) => Err(err),
+    fn shutdown(self) -> Result<(), DbError> {
+        let Self {
+            engine, session, ..
+        } = self;
+        drop(session);
+        engine.flush()
+    }
+
+    fn execute(&mut self, request: Request) -> Result<(), DbError> {
+        let span = info_span!("request", id = request.id, session = self.session.id());
+        let _entered = span.enter();
+        let result = command::execute(&self.engine, &mut self.session, &request.sql);
+        let response = Response {
+            id: request.id,
+            result,
         };
-        if let Err(err) = self.tx.send(result) {
+        if let Err(err) = self.tx.send(response) {
             return Err(DbError::IO(err.to_string()));
         }
         Ok(())
     }
 }
 
+pub(crate) fn open_engine(config: &Config) -> Result<Engine, DbError> {
+    let mut engine = match config.read_only {
+        true => Engine::read_only(&config.path)?,
+        false => Engine::new(&config.path)?,
+    };
+    engine.set_varchar_mode(config.varchar_mode);
+    if let Some(path) = &config.audit_log {
+        engine.set_audit_log(path)?;
+    }
+    Ok(engine)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, sync::mpsc, thread::spawn};
+    use std::{collections::HashMap, path::PathBuf, sync::mpsc, thread::spawn};
 
+    use engine::exec_result::ExecResult;
     use row::Col;
 
     use super::*;
@@ -58,28 +120,241 @@ mod tests {
         let (q_tx, q_rx) = mpsc::channel();
 
         let temp_dir = tempfile::tempdir().unwrap();
-        let config = Config {
-            path: PathBuf::from(temp_dir.path()),
-        };
+        let config = Config::builder()
+            .path(PathBuf::from(temp_dir.path()))
+            .build();
 
         let runner = Runner::new(config, r_tx, q_rx).unwrap();
         spawn(move || {
             runner.run().unwrap();
         });
 
-        q_tx.send("CREATE TABLE users(id INT, name VARCHAR(16))".to_string())
-            .unwrap();
-        let Ok(result) = r_rx.recv().unwrap() else {
+        q_tx.send(Request::new(
+            0,
+            "CREATE TABLE users(id INT, name VARCHAR(16))",
+        ))
+        .unwrap();
+        let Ok(result) = r_rx.recv().unwrap().result else {
             panic!("cannot get result");
         };
-        assert_eq!(result.field_names.first().unwrap(), "created");
-        q_tx.send("INSERT INTO users(id, name) VALUES(1, 'John')".to_string())
-            .unwrap();
+        assert_eq!(ExecResult::ack("created"), result);
+        q_tx.send(Request::new(
+            0,
+            "INSERT INTO users(id, name) VALUES(1, 'John')",
+        ))
+        .unwrap();
+        assert_eq!(
+            Ok(ExecResult::affected("inserted", 1)),
+            r_rx.recv().unwrap().result
+        );
 
-        q_tx.send("DELETE FROM users".to_string()).unwrap();
-        let Ok(deleted) = r_rx.recv().unwrap() else {
+        q_tx.send(Request::new(0, "DELETE FROM users")).unwrap();
+        let Ok(deleted) = r_rx.recv().unwrap().result else {
             panic!("cannot delete");
         };
-        assert_eq!(Col::Int(1), deleted.fields[0][0]);
+        assert_eq!(ExecResult::affected("deleted", 1), deleted);
+    }
+
+    #[test]
+    fn shared_engine() {
+        let engine = Arc::new(Engine::in_memory());
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let (r_tx, r_rx) = mpsc::channel();
+            let (q_tx, q_rx) = mpsc::channel();
+            let runner = Runner::with_engine(engine.clone(), r_tx, q_rx);
+            spawn(move || runner.run());
+            sessions.push((q_tx, r_rx));
+        }
+        let query = |session: usize, query: &str| {
+            let (q_tx, r_rx) = &sessions[session];
+            q_tx.send(Request::new(0, query)).unwrap();
+            r_rx.recv().unwrap().result
+        };
+        query(0, "CREATE TABLE users(id INT, name VARCHAR(16))").unwrap();
+        query(0, "BEGIN").unwrap();
+        query(0, "INSERT INTO users(id, name) VALUES(1, 'John')").unwrap();
+        query(1, "BEGIN").unwrap();
+        assert!(query(1, "SELECT id FROM users").unwrap().rows().is_empty());
+        sessions[1]
+            .0
+            .send(Request::new(
+                0,
+                "INSERT INTO users(id, name) VALUES(1, 'Jane')",
+            ))
+            .unwrap();
+        query(0, "COMMIT").unwrap();
+        assert_eq!(
+            Err(DbError::DuplicateKey("users".to_string(), "1".to_string())),
+            sessions[1].1.recv().unwrap().result
+        );
+        query(1, "ROLLBACK").unwrap();
+        let users = query(1, "SELECT name FROM users").unwrap();
+        assert_eq!(vec![vec![Col::varchar("John", 16)]], users.rows());
+    }
+
+    #[test]
+    fn shutdown() {
+        let (r_tx, r_rx) = mpsc::channel();
+        let (q_tx, q_rx) = mpsc::channel();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = Config::builder()
+            .path(PathBuf::from(temp_dir.path()))
+            .build();
+        let runner = Runner::new(config, r_tx, q_rx).unwrap();
+        let handle = spawn(move || runner.run());
+        q_tx.send(Request::new(1, "CREATE TABLE users(id INT)"))
+            .unwrap();
+        q_tx.send(Request::new(2, "BEGIN")).unwrap();
+        q_tx.send(Request::new(3, "INSERT INTO users(id) VALUES(1)"))
+            .unwrap();
+        q_tx.send(Request::new(4, "COMMIT")).unwrap();
+        q_tx.send(Request::new(5, "BEGIN")).unwrap();
+        q_tx.send(Request::new(6, "INSERT INTO users(id) VALUES(2)"))
+            .unwrap();
+        drop(q_tx);
+        assert!(handle.join().unwrap().is_ok());
+        let ids: Vec<u64> = r_rx.iter().map(|response| response.id).collect();
+        assert_eq!(vec![1, 2, 3, 4, 5, 6], ids);
+
+        let engine = Engine::read_only(temp_dir.path()).unwrap();
+        let users = engine
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert_eq!(&[vec![Col::int(1)]], users.rows());
+    }
+
+    #[test]
+    fn shutdown_on_send_error() {
+        let (r_tx, r_rx) = mpsc::channel();
+        let (q_tx, q_rx) = mpsc::channel();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = Config::builder()
+            .path(PathBuf::from(temp_dir.path()))
+            .build();
+        let runner = Runner::new(config, r_tx, q_rx).unwrap();
+        drop(r_rx);
+        q_tx.send(Request::new(1, "CREATE TABLE users(id INT)"))
+            .unwrap();
+        q_tx.send(Request::new(2, "INSERT INTO users(id) VALUES(1)"))
+            .unwrap();
+        assert!(matches!(runner.run(), Err(DbError::IO(_))));
+
+        let engine = Engine::read_only(temp_dir.path()).unwrap();
+        let users = engine
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert!(users.rows().is_empty());
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let (r_tx, r_rx) = mpsc::channel();
+        let engine = Arc::new(Engine::in_memory());
+        engine
+            .execute(parser::parse("CREATE TABLE users(id INT)").unwrap())
+            .unwrap();
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let (q_tx, q_rx) = mpsc::channel();
+            let runner = Runner::with_engine(engine.clone(), r_tx.clone(), q_rx);
+            spawn(move || runner.run());
+            sessions.push(q_tx);
+        }
+        for id in 1..=6 {
+            let sql = format!("INSERT INTO users(id) VALUES({})", id);
+            sessions[id as usize % 2]
+                .send(Request::new(id, &sql))
+                .unwrap();
+        }
+        sessions[0]
+            .send(Request::new(7, "SELECT id FROM orders"))
+            .unwrap();
+        let mut responses: HashMap<u64, Result<ExecResult, DbError>> = (0..7)
+            .map(|_| {
+                let response = r_rx.recv().unwrap();
+                (response.id, response.result)
+            })
+            .collect();
+        assert!(responses.remove(&7).unwrap().is_err());
+        assert_eq!(
+            (1..=6)
+                .map(|id| (id, Ok(ExecResult::affected("inserted", 1))))
+                .collect::<HashMap<_, _>>(),
+            responses
+        );
+    }
+
+    #[test]
+    fn attached_sessions() {
+        let (r_tx, r_rx) = mpsc::channel();
+        let (q_tx, q_rx) = mpsc::channel();
+        let runner = Runner::with_engine(Arc::new(Engine::in_memory()), r_tx, q_rx);
+        let mut sessions = vec![(q_tx, r_rx)];
+        let (r_tx, r_rx) = mpsc::channel();
+        let (q_tx, q_rx) = mpsc::channel();
+        let other = runner.attach(r_tx, q_rx);
+        assert_ne!(runner.id(), other.id());
+        sessions.push((q_tx, r_rx));
+        spawn(move || runner.run());
+        spawn(move || other.run());
+        let query = |session: usize, query: &str| {
+            let (q_tx, r_rx) = &sessions[session];
+            q_tx.send(Request::new(0, query)).unwrap();
+            r_rx.recv().unwrap().result
+        };
+        query(0, "CREATE DATABASE shop").unwrap();
+        query(0, "USE shop").unwrap();
+        query(0, "CREATE TABLE items(id INT, name VARCHAR(8))").unwrap();
+        assert_eq!(
+            Ok(ExecResult::ack("prepared")),
+            query(
+                0,
+                "PREPARE insert AS INSERT INTO items(id, name) VALUES(?, ?)"
+            )
+        );
+        assert_eq!(
+            Ok(ExecResult::affected("inserted", 1)),
+            query(0, "EXECUTE insert(1, 'ann')")
+        );
+        assert_eq!(
+            Err(DbError::invalid_input(
+                "prepared statement 'insert' doesn't exist"
+            )),
+            query(1, "EXECUTE insert(2, 'bob')")
+        );
+        assert!(query(1, "SELECT name FROM items").is_err());
+        let items = query(1, "SELECT name FROM shop.items").unwrap();
+        assert_eq!(vec![vec![Col::varchar("ann", 8)]], items.rows());
+        assert_eq!(
+            Ok(ExecResult::ack("deallocated")),
+            query(0, "DEALLOCATE insert")
+        );
+        assert!(query(0, "EXECUTE insert(2, 'bob')").is_err());
+    }
+
+    #[test]
+    fn cancel_query() {
+        let engine = Arc::new(Engine::in_memory());
+        let (r_tx, r_rx) = mpsc::channel();
+        let (q_tx, q_rx) = mpsc::channel();
+        let runner = Runner::with_engine(engine.clone(), r_tx, q_rx);
+        let other = engine.session();
+        spawn(move || runner.run());
+        let query = |query: String| {
+            q_tx.send(Request::new(0, &query)).unwrap();
+            r_rx.recv().unwrap().result
+        };
+        assert_eq!(
+            Err(DbError::InvalidInput(format!(
+                "query {} is not running",
+                other.id()
+            ))),
+            query(format!("CANCEL {}", other.id()))
+        );
+        assert_eq!(
+            Err(DbError::invalid_input("invalid digit found in string")),
+            query("cancel abc".to_string())
+        );
     }
 }
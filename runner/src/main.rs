@@ -0,0 +1,123 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use common::error::DbError;
+use runner::{config::Config, mysql::Server, script::OnError, shell};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(
+    name = "sql",
+    version,
+    about = "Run SQL statements against a local database"
+)]
+struct Cli {
+    #[arg(long, global = true, help = "Directory holding the database files")]
+    data_dir: Option<PathBuf>,
+    #[arg(long, global = true, help = "Reject statements that modify data")]
+    read_only: bool,
+    #[arg(long, global = true, help = "Append every statement to this CSV file")]
+    audit_log: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        conflicts_with = "script",
+        help = "Execute the given statements and exit"
+    )]
+    command: Option<String>,
+    #[arg(help = "Execute the statements of a .sql file and exit")]
+    script: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Stop at the first failing statement instead of continuing"
+    )]
+    stop_on_error: bool,
+    #[command(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(Subcommand)]
+enum Mode {
+    #[command(about = "Serve clients over the MySQL protocol")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1", help = "Address to listen on")]
+        host: String,
+        #[arg(long, default_value_t = 3306, help = "Port to listen on")]
+        port: u16,
+        #[arg(long, requires = "tls_key", help = "PEM certificate chain for TLS")]
+        tls_cert: Option<PathBuf>,
+        #[arg(long, requires = "tls_cert", help = "PEM private key for TLS")]
+        tls_key: Option<PathBuf>,
+        #[arg(long, requires = "password", help = "User clients must log in as")]
+        user: Option<String>,
+        #[arg(long, requires = "user", help = "Password clients must log in with")]
+        password: Option<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+    match run(Cli::parse()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("ERR [{}]: {}", err.code(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, DbError> {
+    let mut config = Config::builder().read_only(cli.read_only);
+    if let Some(path) = cli.data_dir {
+        config = config.path(path);
+    }
+    if let Some(path) = cli.audit_log {
+        config = config.audit_log(path);
+    }
+    match cli.mode {
+        Some(Mode::Serve {
+            host,
+            port,
+            tls_cert,
+            tls_key,
+            user,
+            password,
+        }) => {
+            if let (Some(cert), Some(key)) = (tls_cert, tls_key) {
+                config = config.tls(cert, key);
+            }
+            if let (Some(user), Some(password)) = (user, password) {
+                config = config.credentials(&user, &password);
+            }
+            let server = Server::new(config.build(), (host.as_str(), port))?;
+            eprintln!("listening on {}", server.local_addr()?);
+            server.run()?;
+        }
+        None => {
+            let on_error = match cli.stop_on_error {
+                true => OnError::Stop,
+                false => OnError::Continue,
+            };
+            let summary = match (cli.command, cli.script) {
+                (Some(command), _) => shell::execute(config.build(), &command, on_error)?,
+                (None, Some(script)) => {
+                    let summary =
+                        shell::execute(config.build(), &fs::read_to_string(script)?, on_error)?;
+                    eprintln!("{}", summary);
+                    summary
+                }
+                (None, None) => {
+                    shell::run(config.build())?;
+                    return Ok(ExitCode::SUCCESS);
+                }
+            };
+            if !summary.is_success() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
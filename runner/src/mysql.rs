@@ -0,0 +1,922 @@
+use std::{
+    io::{self, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::Arc,
+    thread::spawn,
+};
+
+use common::error::DbError;
+use engine::{Engine, Session, exec_result::ExecResult};
+use parser::Command;
+use row::ColType;
+use sha1::{Digest, Sha1};
+use tracing::{debug, info_span, warn};
+
+#[cfg(feature = "tls")]
+use crate::tls;
+use crate::{command, config::Config, open_engine};
+
+#[cfg(feature = "tls")]
+type TlsConfig = Arc<rustls::ServerConfig>;
+#[cfg(not(feature = "tls"))]
+#[derive(Clone)]
+enum TlsConfig {}
+
+const PROTOCOL_VERSION: u8 = 10;
+const SERVER_VERSION: &str = "8.0.0-sql";
+const AUTH_PLUGIN: &str = "mysql_native_password";
+const SCRAMBLE_SIZE: usize = 20;
+const MAX_PACKET: usize = 0xff_ffff;
+const MAX_PAYLOAD: usize = 64 * 1024 * 1024;
+const SSL_REQUEST_SIZE: usize = 32;
+
+const CLIENT_LONG_PASSWORD: u32 = 0x0000_0001;
+const CLIENT_LONG_FLAG: u32 = 0x0000_0004;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_TRANSACTIONS: u32 = 0x0000_2000;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+const CLIENT_PLUGIN_AUTH_LENENC_DATA: u32 = 0x0020_0000;
+const CAPABILITIES: u32 = CLIENT_LONG_PASSWORD
+    | CLIENT_LONG_FLAG
+    | CLIENT_CONNECT_WITH_DB
+    | CLIENT_PROTOCOL_41
+    | CLIENT_TRANSACTIONS
+    | CLIENT_SECURE_CONNECTION
+    | CLIENT_PLUGIN_AUTH
+    | CLIENT_PLUGIN_AUTH_LENENC_DATA;
+
+const STATUS_AUTOCOMMIT: u16 = 0x0002;
+const CHARSET_UTF8MB4: u8 = 45;
+const CHARSET_BINARY: u8 = 63;
+
+const COM_QUIT: u8 = 0x01;
+const COM_INIT_DB: u8 = 0x02;
+const COM_QUERY: u8 = 0x03;
+const COM_PING: u8 = 0x0e;
+
+const OK: u8 = 0x00;
+const EOF: u8 = 0xfe;
+const AUTH_SWITCH: u8 = 0xfe;
+const ERR: u8 = 0xff;
+
+const TYPE_LONG: u8 = 0x03;
+const TYPE_LONGLONG: u8 = 0x08;
+const TYPE_VAR_STRING: u8 = 0xfd;
+
+pub struct Server {
+    engine: Arc<Engine>,
+    listener: TcpListener,
+    tls: Option<TlsConfig>,
+    credentials: Option<Arc<Credentials>>,
+}
+
+impl Server {
+    pub fn new(config: Config, addr: impl ToSocketAddrs) -> Result<Self, DbError> {
+        let listener = bind(addr, config.credentials.is_some())?;
+        let tls = match &config.tls {
+            Some((cert, key)) => Some(tls_config(cert, key)?),
+            None => None,
+        };
+        let credentials = config
+            .credentials
+            .as_ref()
+            .map(|(user, password)| Arc::new(Credentials::new(user, password)));
+        Ok(Self {
+            engine: Arc::new(open_engine(&config)?),
+            listener,
+            tls,
+            credentials,
+        })
+    }
+
+    pub fn with_engine(engine: Arc<Engine>, addr: impl ToSocketAddrs) -> Result<Self, DbError> {
+        Ok(Self {
+            engine,
+            listener: bind(addr, false)?,
+            tls: None,
+            credentials: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, DbError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn run(self) -> Result<(), DbError> {
+        for stream in self.listener.incoming() {
+            let connection = Connection::new(
+                stream?,
+                self.engine.clone(),
+                self.tls.clone(),
+                self.credentials.clone(),
+            );
+            spawn(move || {
+                let span = info_span!("connection", session = connection.session.id());
+                let _entered = span.enter();
+                if let Err(err) = connection.serve() {
+                    warn!(error = %err, "connection failed");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn bind(addr: impl ToSocketAddrs, authenticated: bool) -> Result<TcpListener, DbError> {
+    let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    if !authenticated && addrs.iter().any(|addr| !addr.ip().is_loopback()) {
+        return Err(DbError::invalid_input(
+            "credentials are required to listen on a non-loopback address",
+        ));
+    }
+    Ok(TcpListener::bind(addrs.as_slice())?)
+}
+
+#[cfg(feature = "tls")]
+fn tls_config(cert: &Path, key: &Path) -> Result<TlsConfig, DbError> {
+    tls::server_config(cert, key)
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_config(_: &Path, _: &Path) -> Result<TlsConfig, DbError> {
+    Err(DbError::invalid_input("TLS support is not enabled"))
+}
+
+struct Credentials {
+    user: String,
+    hash: Option<[u8; 20]>,
+}
+
+impl Credentials {
+    fn new(user: &str, password: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            hash: (!password.is_empty()).then(|| Sha1::digest(Sha1::digest(password)).into()),
+        }
+    }
+
+    fn verify(&self, user: &str, scramble: &[u8], token: &[u8]) -> bool {
+        if user != self.user {
+            return false;
+        }
+        let Some(hash) = &self.hash else {
+            return token.is_empty();
+        };
+        if token.len() != hash.len() {
+            return false;
+        }
+        let mask = Sha1::new()
+            .chain_update(scramble)
+            .chain_update(hash)
+            .finalize();
+        let candidate: Vec<u8> = token.iter().zip(mask).map(|(a, b)| a ^ b).collect();
+        Sha1::digest(candidate)
+            .iter()
+            .zip(hash)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+struct Login {
+    user: String,
+    token: Vec<u8>,
+    database: Option<String>,
+    plugin: Option<String>,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ServerConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+struct Connection {
+    engine: Arc<Engine>,
+    stream: BufReader<Stream>,
+    output: Vec<u8>,
+    session: Session,
+    sequence: u8,
+    tls: Option<TlsConfig>,
+    credentials: Option<Arc<Credentials>>,
+}
+
+impl Connection {
+    fn new(
+        stream: TcpStream,
+        engine: Arc<Engine>,
+        tls: Option<TlsConfig>,
+        credentials: Option<Arc<Credentials>>,
+    ) -> Self {
+        Self {
+            session: engine.session(),
+            engine,
+            stream: BufReader::new(Stream::Plain(stream)),
+            output: Vec::new(),
+            sequence: 0,
+            tls,
+            credentials,
+        }
+    }
+
+    fn serve(mut self) -> Result<(), DbError> {
+        if !self.handshake()? {
+            return Ok(());
+        }
+        debug!("connected");
+        while let Some(packet) = self.read()? {
+            let result = match packet.split_first() {
+                None | Some((&COM_QUIT, _)) => return Ok(()),
+                Some((&COM_PING, _)) => Ok(ExecResult::ack("pong")),
+                Some((&COM_INIT_DB, database)) => self.session.execute(Command::Use {
+                    database: String::from_utf8_lossy(database).to_string(),
+                }),
+                Some((&COM_QUERY, query)) => match str::from_utf8(query) {
+                    Ok(query) => command::execute(&self.engine, &mut self.session, query),
+                    Err(_) => Err(DbError::Encoding),
+                },
+                Some((command, _)) => Err(DbError::InvalidInput(format!(
+                    "unsupported command 0x{:02x}",
+                    command
+                ))),
+            };
+            self.respond(result)?;
+        }
+        Ok(())
+    }
+
+    fn handshake(&mut self) -> Result<bool, DbError> {
+        let scramble = scramble()?;
+        let mut packet = vec![PROTOCOL_VERSION];
+        put_str_nul(&mut packet, SERVER_VERSION);
+        packet.extend_from_slice(&(self.session.id() as u32).to_le_bytes());
+        packet.extend_from_slice(&scramble[..8]);
+        packet.push(0);
+        let capabilities = match self.tls {
+            Some(_) => CAPABILITIES | CLIENT_SSL,
+            None => CAPABILITIES,
+        };
+        packet.extend_from_slice(&(capabilities as u16).to_le_bytes());
+        packet.push(CHARSET_UTF8MB4);
+        packet.extend_from_slice(&STATUS_AUTOCOMMIT.to_le_bytes());
+        packet.extend_from_slice(&((capabilities >> 16) as u16).to_le_bytes());
+        packet.push(SCRAMBLE_SIZE as u8 + 1);
+        packet.extend_from_slice(&[0; 10]);
+        packet.extend_from_slice(&scramble[8..]);
+        packet.push(0);
+        put_str_nul(&mut packet, AUTH_PLUGIN);
+        self.write(&packet)?;
+        self.flush()?;
+        let Some(mut response) = self.read()? else {
+            return Ok(false);
+        };
+        if let Some(config) = self.tls.clone() {
+            if !ssl_request(&response) {
+                self.respond(Err(DbError::invalid_input(
+                    "connections using insecure transport are prohibited",
+                )))?;
+                return Ok(false);
+            }
+            self.upgrade(&config)?;
+            let Some(secured) = self.read()? else {
+                return Ok(false);
+            };
+            response = secured;
+        }
+        let mut login = match login(&response) {
+            Ok(login) => login,
+            Err(err) => {
+                self.respond(Err(err))?;
+                return Ok(false);
+            }
+        };
+        if let Some(credentials) = self.credentials.clone() {
+            if login
+                .plugin
+                .as_deref()
+                .is_some_and(|plugin| plugin != AUTH_PLUGIN)
+            {
+                let mut packet = vec![AUTH_SWITCH];
+                put_str_nul(&mut packet, AUTH_PLUGIN);
+                packet.extend_from_slice(&scramble);
+                packet.push(0);
+                self.write(&packet)?;
+                self.flush()?;
+                let Some(token) = self.read()? else {
+                    return Ok(false);
+                };
+                login.token = token;
+            }
+            if !credentials.verify(&login.user, &scramble, &login.token) {
+                warn!(user = login.user, "access denied");
+                self.respond(Err(DbError::AccessDenied(login.user)))?;
+                return Ok(false);
+            }
+        }
+        let result = match login.database {
+            Some(database) => self.session.execute(Command::Use { database }),
+            None => Ok(ExecResult::ack("connected")),
+        };
+        let connected = result.is_ok();
+        self.respond(result)?;
+        Ok(connected)
+    }
+
+    fn respond(&mut self, result: Result<ExecResult, DbError>) -> Result<(), DbError> {
+        match result {
+            Ok(ExecResult::Rows {
+                columns,
+                types,
+                rows,
+            }) => {
+                let mut packet = Vec::new();
+                put_lenenc_int(&mut packet, columns.len() as u64);
+                self.write(&packet)?;
+                for (i, name) in columns.iter().enumerate() {
+                    self.write(&column_definition(name, types.get(i)))?;
+                }
+                self.write(&eof())?;
+                for row in rows {
+                    let mut packet = Vec::new();
+                    for col in row {
+                        put_lenenc_str(&mut packet, &col.to_string());
+                    }
+                    self.write(&packet)?;
+                }
+                self.write(&eof())?;
+            }
+            Ok(ExecResult::Affected {
+                count, warnings, ..
+            }) => self.write(&ok(count, warnings.len() as u16))?,
+            Ok(ExecResult::Ack { .. }) => self.write(&ok(0, 0))?,
+            Err(err) => self.write(&error(&err))?,
+        }
+        self.flush()
+    }
+
+    #[cfg(feature = "tls")]
+    fn upgrade(&mut self, config: &TlsConfig) -> Result<(), DbError> {
+        let Stream::Plain(stream) = self.stream.get_ref() else {
+            return Err(DbError::unexpected("connection is already secured"));
+        };
+        let stream = tls::accept(stream.try_clone()?, self.stream.buffer(), config.clone())?;
+        self.stream = BufReader::new(Stream::Tls(Box::new(stream)));
+        debug!("secured connection");
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn upgrade(&mut self, config: &TlsConfig) -> Result<(), DbError> {
+        match *config {}
+    }
+
+    fn flush(&mut self) -> Result<(), DbError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(&self.output)?;
+        stream.flush()?;
+        self.output.clear();
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<Option<Vec<u8>>, DbError> {
+        let packet = read_packet(&mut self.stream)?;
+        if let Some((sequence, _)) = &packet {
+            self.sequence = sequence.wrapping_add(1);
+        }
+        Ok(packet.map(|(_, payload)| payload))
+    }
+
+    fn write(&mut self, payload: &[u8]) -> Result<(), DbError> {
+        self.sequence = write_packet(&mut self.output, self.sequence, payload)?;
+        Ok(())
+    }
+}
+
+fn read_packet(reader: &mut impl Read) -> Result<Option<(u8, Vec<u8>)>, DbError> {
+    let mut payload = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        match reader.read_exact(&mut header) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof && payload.is_empty() => {
+                return Ok(None);
+            }
+            result => result?,
+        }
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let start = payload.len();
+        if start + len > MAX_PAYLOAD {
+            return Err(DbError::MaxSize(start + len, MAX_PAYLOAD));
+        }
+        payload.resize(start + len, 0);
+        reader.read_exact(&mut payload[start..])?;
+        if len < MAX_PACKET {
+            return Ok(Some((header[3], payload)));
+        }
+    }
+}
+
+fn write_packet(writer: &mut impl Write, mut sequence: u8, payload: &[u8]) -> Result<u8, DbError> {
+    let mut chunks: Vec<&[u8]> = payload.chunks(MAX_PACKET).collect();
+    if payload.len().is_multiple_of(MAX_PACKET) {
+        chunks.push(&[]);
+    }
+    for chunk in chunks {
+        writer.write_all(&(chunk.len() as u32).to_le_bytes()[..3])?;
+        writer.write_all(&[sequence])?;
+        writer.write_all(chunk)?;
+        sequence = sequence.wrapping_add(1);
+    }
+    Ok(sequence)
+}
+
+fn ssl_request(packet: &[u8]) -> bool {
+    packet.len() == SSL_REQUEST_SIZE
+        && u32::from_le_bytes(packet[..4].try_into().unwrap()) & CLIENT_SSL != 0
+}
+
+fn scramble() -> Result<[u8; SCRAMBLE_SIZE], DbError> {
+    let mut scramble = [0u8; SCRAMBLE_SIZE];
+    getrandom::fill(&mut scramble).map_err(|err| DbError::IO(err.to_string()))?;
+    for byte in scramble.iter_mut() {
+        *byte = (*byte & 0x7f).max(1);
+    }
+    Ok(scramble)
+}
+
+fn login(packet: &[u8]) -> Result<Login, DbError> {
+    let mut input = packet;
+    let capabilities = u32::from_le_bytes(take(&mut input, 4)?.try_into().unwrap());
+    if capabilities & CLIENT_PROTOCOL_41 == 0 {
+        return Err(DbError::invalid_input("client protocol is older than 4.1"));
+    }
+    take(&mut input, 28)?;
+    let user = String::from_utf8_lossy(take_nul(&mut input)?).to_string();
+    let auth = if capabilities & CLIENT_PLUGIN_AUTH_LENENC_DATA != 0 {
+        take_lenenc_int(&mut input)? as usize
+    } else if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        take(&mut input, 1)?[0] as usize
+    } else {
+        take_nul(&mut input)?.len()
+    };
+    let token = take(&mut input, auth)?.to_vec();
+    let mut database = None;
+    if capabilities & CLIENT_CONNECT_WITH_DB != 0 && !input.is_empty() {
+        let name = take_nul(&mut input)?;
+        database = (!name.is_empty()).then(|| String::from_utf8_lossy(name).to_string());
+    }
+    let mut plugin = None;
+    if capabilities & CLIENT_PLUGIN_AUTH != 0 && !input.is_empty() {
+        plugin = Some(String::from_utf8_lossy(take_nul(&mut input)?).to_string());
+    }
+    Ok(Login {
+        user,
+        token,
+        database,
+        plugin,
+    })
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DbError> {
+    if input.len() < len {
+        return Err(DbError::eof("truncated handshake response"));
+    }
+    let (value, rest) = input.split_at(len);
+    *input = rest;
+    Ok(value)
+}
+
+fn take_nul<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], DbError> {
+    let len = input
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| DbError::eof("unterminated string in handshake response"))?;
+    let value = take(input, len)?;
+    take(input, 1)?;
+    Ok(value)
+}
+
+fn take_lenenc_int(input: &mut &[u8]) -> Result<u64, DbError> {
+    let len = match take(input, 1)?[0] {
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        value => return Ok(value as u64),
+    };
+    let mut bytes = [0u8; 8];
+    bytes[..len].copy_from_slice(take(input, len)?);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn put_lenenc_int(packet: &mut Vec<u8>, value: u64) {
+    match value {
+        0..0xfb => packet.push(value as u8),
+        0xfb..0x1_0000 => {
+            packet.push(0xfc);
+            packet.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x1_0000..0x100_0000 => {
+            packet.push(0xfd);
+            packet.extend_from_slice(&(value as u32).to_le_bytes()[..3]);
+        }
+        _ => {
+            packet.push(0xfe);
+            packet.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn put_lenenc_str(packet: &mut Vec<u8>, value: &str) {
+    put_lenenc_int(packet, value.len() as u64);
+    packet.extend_from_slice(value.as_bytes());
+}
+
+fn put_str_nul(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    packet.push(0);
+}
+
+fn column_definition(name: &str, col_type: Option<&ColType>) -> Vec<u8> {
+    let (kind, charset, len) = match col_type {
+        Some(ColType::Int(_)) => (TYPE_LONG, CHARSET_BINARY, 11),
+        Some(ColType::BigInt(_)) => (TYPE_LONGLONG, CHARSET_BINARY, 20),
+        Some(ColType::Varchar(_, len)) => (TYPE_VAR_STRING, CHARSET_UTF8MB4, *len as u32 * 4),
+        None => (TYPE_VAR_STRING, CHARSET_UTF8MB4, u16::MAX as u32),
+    };
+    let mut packet = Vec::new();
+    for value in ["def", "", "", "", name, name] {
+        put_lenenc_str(&mut packet, value);
+    }
+    packet.push(0x0c);
+    packet.extend_from_slice(&(charset as u16).to_le_bytes());
+    packet.extend_from_slice(&len.to_le_bytes());
+    packet.push(kind);
+    packet.extend_from_slice(&[0; 5]);
+    packet
+}
+
+fn ok(count: u64, warnings: u16) -> Vec<u8> {
+    let mut packet = vec![OK];
+    put_lenenc_int(&mut packet, count);
+    put_lenenc_int(&mut packet, 0);
+    packet.extend_from_slice(&STATUS_AUTOCOMMIT.to_le_bytes());
+    packet.extend_from_slice(&warnings.to_le_bytes());
+    packet
+}
+
+fn eof() -> Vec<u8> {
+    let mut packet = vec![EOF, 0, 0];
+    packet.extend_from_slice(&STATUS_AUTOCOMMIT.to_le_bytes());
+    packet
+}
+
+fn error(err: &DbError) -> Vec<u8> {
+    let code: u16 = match err {
+        DbError::DuplicateKey(..) => 1062,
+        DbError::TableNotFound(_) => 1146,
+        DbError::FieldNotFound(..) => 1054,
+        DbError::NotNull(..) => 1048,
+        DbError::TooLong(..) => 1406,
+        DbError::EOF(_) => 1064,
+        DbError::LockTimeout(_) => 1205,
+        DbError::Cancelled => 1317,
+        DbError::AccessDenied(_) => 1045,
+        _ => 1105,
+    };
+    let mut packet = vec![ERR];
+    packet.extend_from_slice(&code.to_le_bytes());
+    packet.push(b'#');
+    packet.extend_from_slice(err.code().as_bytes());
+    packet.extend_from_slice(err.to_string().as_bytes());
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Client<S> {
+        stream: S,
+        sequence: u8,
+    }
+
+    impl Client<TcpStream> {
+        fn connect(addr: SocketAddr, database: &str) -> (Self, Vec<u8>) {
+            Self::login(addr, "root", "", database)
+        }
+
+        fn login(addr: SocketAddr, user: &str, password: &str, database: &str) -> (Self, Vec<u8>) {
+            let mut client = Self {
+                stream: TcpStream::connect(addr).unwrap(),
+                sequence: 0,
+            };
+            let handshake = client.read();
+            let capabilities =
+                CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_CONNECT_WITH_DB;
+            let token = token(password, &scramble_of(&handshake));
+            client.write(&handshake_response(capabilities, user, &token, database));
+            (client, handshake)
+        }
+    }
+
+    impl<S: Read + Write> Client<S> {
+        fn command(&mut self, command: u8, argument: &str) -> Vec<u8> {
+            self.sequence = 0;
+            let mut packet = vec![command];
+            packet.extend_from_slice(argument.as_bytes());
+            self.write(&packet);
+            self.read()
+        }
+
+        fn read(&mut self) -> Vec<u8> {
+            let (sequence, payload) = read_packet(&mut self.stream).unwrap().unwrap();
+            assert_eq!(self.sequence, sequence);
+            self.sequence = sequence.wrapping_add(1);
+            payload
+        }
+
+        fn write(&mut self, payload: &[u8]) {
+            self.sequence = write_packet(&mut self.stream, self.sequence, payload).unwrap();
+            self.stream.flush().unwrap();
+        }
+    }
+
+    fn ssl_request(capabilities: u32) -> Vec<u8> {
+        let mut request = Vec::new();
+        request.extend_from_slice(&capabilities.to_le_bytes());
+        request.extend_from_slice(&(MAX_PACKET as u32).to_le_bytes());
+        request.push(CHARSET_UTF8MB4);
+        request.extend_from_slice(&[0; 23]);
+        request
+    }
+
+    fn handshake_response(capabilities: u32, user: &str, token: &[u8], database: &str) -> Vec<u8> {
+        let mut response = ssl_request(capabilities);
+        put_str_nul(&mut response, user);
+        response.push(token.len() as u8);
+        response.extend_from_slice(token);
+        put_str_nul(&mut response, database);
+        response
+    }
+
+    fn scramble_of(handshake: &[u8]) -> Vec<u8> {
+        let offset = SERVER_VERSION.len() + 6;
+        let mut scramble = handshake[offset..offset + 8].to_vec();
+        scramble.extend_from_slice(&handshake[offset + 27..offset + 39]);
+        scramble
+    }
+
+    fn token(password: &str, scramble: &[u8]) -> Vec<u8> {
+        if password.is_empty() {
+            return Vec::new();
+        }
+        let hash = Sha1::digest(password);
+        let mask = Sha1::new()
+            .chain_update(scramble)
+            .chain_update(Sha1::digest(hash))
+            .finalize();
+        hash.iter().zip(mask).map(|(a, b)| a ^ b).collect()
+    }
+
+    fn server() -> SocketAddr {
+        let server = Server::with_engine(Arc::new(Engine::in_memory()), "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        spawn(move || server.run());
+        addr
+    }
+
+    #[test]
+    fn queries() {
+        let addr = server();
+        let (mut client, handshake) = Client::connect(addr, "");
+        assert_eq!(PROTOCOL_VERSION, handshake[0]);
+        assert!(handshake[1..].starts_with(SERVER_VERSION.as_bytes()));
+        assert_eq!(OK, client.read()[0]);
+
+        let created = client.command(COM_QUERY, "CREATE TABLE users(id INT, name VARCHAR(8))");
+        assert_eq!(ok(0, 0), created);
+        let inserted = client.command(
+            COM_QUERY,
+            "INSERT INTO users(id, name) VALUES(1, 'ann'), (2, 'bob')",
+        );
+        assert_eq!(ok(2, 0), inserted);
+
+        assert_eq!(
+            vec![2],
+            client.command(COM_QUERY, "SELECT id, name FROM users")
+        );
+        assert_eq!(
+            column_definition("id", Some(&ColType::int("id"))),
+            client.read()
+        );
+        assert_eq!(
+            column_definition("name", Some(&ColType::varchar("name", 8))),
+            client.read()
+        );
+        assert_eq!(eof(), client.read());
+        assert_eq!(b"\x011\x03ann".to_vec(), client.read());
+        assert_eq!(b"\x012\x03bob".to_vec(), client.read());
+        assert_eq!(eof(), client.read());
+
+        let duplicate = client.command(COM_QUERY, "INSERT INTO users(id, name) VALUES(1, 'eve')");
+        assert_eq!(ERR, duplicate[0]);
+        assert_eq!(1062, u16::from_le_bytes([duplicate[1], duplicate[2]]));
+        assert_eq!(b"#23505", &duplicate[3..9]);
+        assert_eq!(ok(0, 0), client.command(COM_PING, ""));
+        assert_eq!(ERR, client.command(0x7f, "")[0]);
+
+        client.command(COM_QUERY, "CREATE DATABASE shop");
+        let (mut other, _) = Client::connect(addr, "shop");
+        assert_eq!(OK, other.read()[0]);
+        other.command(COM_QUERY, "CREATE TABLE orders(id INT)");
+        assert_eq!(ERR, client.command(COM_QUERY, "SELECT id FROM orders")[0]);
+        assert_eq!(OK, client.command(COM_INIT_DB, "shop")[0]);
+        assert_eq!(vec![1], client.command(COM_QUERY, "SELECT id FROM orders"));
+
+        let (mut missing, _) = Client::connect(addr, "missing");
+        assert_eq!(ERR, missing.read()[0]);
+    }
+
+    #[test]
+    fn authentication() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = Config::builder()
+            .path(temp_dir.path().to_path_buf())
+            .credentials("admin", "secret")
+            .build();
+        let server = Server::new(config, "0.0.0.0:0").unwrap();
+        let addr = SocketAddr::from(([127, 0, 0, 1], server.local_addr().unwrap().port()));
+        spawn(move || server.run());
+
+        let public = Config::builder()
+            .path(temp_dir.path().to_path_buf())
+            .build();
+        assert!(matches!(
+            Server::new(public, "0.0.0.0:0"),
+            Err(DbError::InvalidInput(_))
+        ));
+        let engine = Arc::new(Engine::in_memory());
+        assert!(Server::with_engine(engine, "0.0.0.0:0").is_err());
+
+        let (mut client, first) = Client::login(addr, "admin", "secret", "");
+        assert_eq!(OK, client.read()[0]);
+        assert_eq!(ok(0, 0), client.command(COM_PING, ""));
+        let (mut client, second) = Client::login(addr, "admin", "secret", "");
+        assert_eq!(OK, client.read()[0]);
+        assert_ne!(scramble_of(&first), scramble_of(&second));
+        assert!(scramble_of(&first).iter().all(|&byte| byte != 0));
+
+        for (user, password) in [("admin", "wrong"), ("admin", ""), ("root", "secret")] {
+            let (mut client, _) = Client::login(addr, user, password, "");
+            let denied = client.read();
+            assert_eq!(ERR, denied[0]);
+            assert_eq!(1045, u16::from_le_bytes([denied[1], denied[2]]));
+            assert_eq!(b"#28000", &denied[3..9]);
+        }
+
+        let mut client = Client {
+            stream: TcpStream::connect(addr).unwrap(),
+            sequence: 0,
+        };
+        client.read();
+        let capabilities = CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH;
+        let mut response = handshake_response(capabilities, "admin", &[1; 32], "");
+        put_str_nul(&mut response, "caching_sha2_password");
+        client.write(&response);
+        let switch = client.read();
+        assert_eq!(AUTH_SWITCH, switch[0]);
+        let mut input = &switch[1..];
+        assert_eq!(AUTH_PLUGIN.as_bytes(), take_nul(&mut input).unwrap());
+        client.write(&token("secret", &input[..SCRAMBLE_SIZE]));
+        assert_eq!(OK, client.read()[0]);
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn tls() {
+        use rustls::{
+            ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+            crypto::ring,
+            pki_types::{CertificateDer, pem::PemObject},
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let config = Config::builder()
+            .path(temp_dir.path().to_path_buf())
+            .tls(
+                testdata.join("localhost.crt"),
+                testdata.join("localhost.key"),
+            )
+            .build();
+        let server = Server::new(config, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        spawn(move || server.run());
+
+        let (mut insecure, handshake) = Client::connect(addr, "");
+        let offset = SERVER_VERSION.len() + 15;
+        let capabilities = u16::from_le_bytes([handshake[offset], handshake[offset + 1]]);
+        assert_ne!(0, capabilities as u32 & CLIENT_SSL);
+        assert_eq!(ERR, insecure.read()[0]);
+
+        let mut client = Client {
+            stream: TcpStream::connect(addr).unwrap(),
+            sequence: 0,
+        };
+        client.read();
+        let capabilities = CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_SSL;
+        client.write(&ssl_request(capabilities));
+        let mut roots = RootCertStore::empty();
+        let cert = CertificateDer::from_pem_file(testdata.join("localhost.crt")).unwrap();
+        roots.add(cert).unwrap();
+        let tls = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connection =
+            ClientConnection::new(Arc::new(tls), "localhost".try_into().unwrap()).unwrap();
+        let mut client = Client {
+            stream: StreamOwned::new(connection, client.stream),
+            sequence: client.sequence,
+        };
+        client.write(&handshake_response(capabilities, "root", &[], ""));
+        assert_eq!(OK, client.read()[0]);
+        assert_eq!(
+            ok(0, 0),
+            client.command(COM_QUERY, "CREATE TABLE users(id INT)")
+        );
+        assert_eq!(
+            ok(1, 0),
+            client.command(COM_QUERY, "INSERT INTO users(id) VALUES(1)")
+        );
+        assert_eq!(vec![1], client.command(COM_QUERY, "SELECT id FROM users"));
+    }
+
+    #[test]
+    fn lenenc() {
+        for value in [
+            0,
+            250,
+            251,
+            0xffff,
+            0x1_0000,
+            0xff_ffff,
+            0x100_0000,
+            u64::MAX,
+        ] {
+            let mut packet = Vec::new();
+            put_lenenc_int(&mut packet, value);
+            let mut input = packet.as_slice();
+            assert_eq!(value, take_lenenc_int(&mut input).unwrap());
+            assert!(input.is_empty());
+        }
+    }
+
+    #[test]
+    fn large_packets() {
+        let payload = vec![1u8; MAX_PACKET * 2];
+        let mut buffer = Vec::new();
+        assert_eq!(3, write_packet(&mut buffer, 0, &payload).unwrap());
+        assert_eq!(payload.len() + 12, buffer.len());
+        let (sequence, read) = read_packet(&mut buffer.as_slice()).unwrap().unwrap();
+        assert_eq!(2, sequence);
+        assert_eq!(payload, read);
+
+        assert_eq!(
+            Err(DbError::MaxSize(MAX_PACKET * 5, MAX_PAYLOAD)),
+            read_packet(&mut io::repeat(0xff))
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use common::error::DbError;
+use engine::exec_result::ExecResult;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+    pub id: u64,
+    pub sql: String,
+}
+
+impl Request {
+    pub fn new(id: u64, sql: &str) -> Self {
+        Self {
+            id,
+            sql: sql.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response {
+    pub id: u64,
+    pub result: Result<ExecResult, DbError>,
+}
@@ -0,0 +1,137 @@
+use std::fmt::{self, Display, Formatter};
+
+use common::error::DbError;
+use engine::{Engine, Session, exec_result::ExecResult};
+
+use crate::command;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnError {
+    #[default]
+    Continue,
+    Stop,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub failures: Vec<(usize, DbError)>,
+    pub skipped: usize,
+}
+
+impl Summary {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed, {} skipped",
+            self.succeeded,
+            self.failures.len(),
+            self.skipped
+        )
+    }
+}
+
+pub fn run(
+    engine: &Engine,
+    session: &mut Session,
+    script: &str,
+    on_error: OnError,
+    mut report: impl FnMut(usize, &str, &Result<ExecResult, DbError>),
+) -> Summary {
+    let statements = parser::split(script);
+    let mut summary = Summary::default();
+    for (i, statement) in statements.iter().enumerate() {
+        let number = i + 1;
+        let result = command::execute(engine, session, statement);
+        report(number, statement, &result);
+        match result {
+            Ok(_) => summary.succeeded += 1,
+            Err(err) => {
+                summary.failures.push((number, err));
+                if on_error == OnError::Stop {
+                    summary.skipped = statements.len() - number;
+                    break;
+                }
+            }
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const SCRIPT: &str = "CREATE TABLE users(id INT);
+        INSERT INTO users(id) VALUES(1);
+        INSERT INTO users(id) VALUES(1);
+        SELEC id FROM users;
+        INSERT INTO users(id) VALUES(2);";
+
+    #[test]
+    fn continue_on_error() {
+        let engine = Arc::new(Engine::in_memory());
+        let mut session = engine.session();
+        let mut reported = Vec::new();
+        let summary = run(
+            &engine,
+            &mut session,
+            SCRIPT,
+            OnError::Continue,
+            |number, _, result| reported.push((number, result.is_ok())),
+        );
+        assert_eq!(
+            vec![(1, true), (2, true), (3, false), (4, false), (5, true)],
+            reported
+        );
+        assert_eq!(3, summary.succeeded);
+        assert_eq!(
+            vec![3, 4],
+            summary
+                .failures
+                .iter()
+                .map(|(number, _)| *number)
+                .collect::<Vec<_>>()
+        );
+        assert!(matches!(summary.failures[0].1, DbError::DuplicateKey(..)));
+        assert_eq!(0, summary.skipped);
+        assert!(!summary.is_success());
+        assert_eq!("3 succeeded, 2 failed, 0 skipped", summary.to_string());
+        let users = session
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert_eq!(2, users.rows().len());
+    }
+
+    #[test]
+    fn stop_on_error() {
+        let engine = Arc::new(Engine::in_memory());
+        let mut session = engine.session();
+        let mut reported = 0;
+        let summary = run(&engine, &mut session, SCRIPT, OnError::Stop, |_, _, _| {
+            reported += 1
+        });
+        assert_eq!(3, reported);
+        assert_eq!("2 succeeded, 1 failed, 2 skipped", summary.to_string());
+        let users = session
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert_eq!(1, users.rows().len());
+        let summary = run(
+            &engine,
+            &mut session,
+            "SELECT id FROM users",
+            OnError::Stop,
+            |_, _, _| {},
+        );
+        assert!(summary.is_success());
+    }
+}
@@ -0,0 +1,227 @@
+use std::{env::home_dir, iter, sync::Arc};
+
+use common::error::DbError;
+use engine::{Engine, exec_result::ExecResult};
+use row::{Col, ColType};
+use rustyline::{
+    Context, Editor, Helper, completion::Completer, error::ReadlineError, highlight::Highlighter,
+    hint::Hinter, history::FileHistory, validate::Validator,
+};
+
+use crate::{
+    command,
+    config::Config,
+    open_engine,
+    script::{self, OnError, Summary},
+};
+
+const PROMPT: &str = "sql> ";
+const HISTORY: &str = ".sql_history";
+
+pub fn run(config: Config) -> Result<(), DbError> {
+    let engine = Arc::new(open_engine(&config)?);
+    let mut session = engine.session();
+    let mut editor = Editor::<Completion, FileHistory>::new().map_err(readline_error)?;
+    editor.set_helper(Some(Completion {
+        engine: engine.clone(),
+    }));
+    let history = home_dir().map(|dir| dir.join(HISTORY));
+    if let Some(path) = &history
+        && path.exists()
+        && let Err(err) = editor.load_history(path)
+    {
+        eprintln!("WARN: cannot load history: {}", err);
+    }
+    loop {
+        match editor.readline(PROMPT) {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if line == "exit" {
+                    break;
+                }
+                editor.add_history_entry(line).map_err(readline_error)?;
+                print_result(&command::execute(&engine, &mut session, line));
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(readline_error(err)),
+        }
+    }
+    if let Some(path) = &history
+        && let Err(err) = editor.save_history(path)
+    {
+        eprintln!("WARN: cannot save history: {}", err);
+    }
+    Ok(())
+}
+
+struct Completion {
+    engine: Arc<Engine>,
+}
+
+impl Completer for Completion {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let tables = self.engine.tables().unwrap_or_default();
+        let names = tables.iter().flat_map(|(table, row_type)| {
+            iter::once(table.as_str()).chain(row_type.columns.iter().map(ColType::get_name))
+        });
+        Ok(complete(line, pos, names))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+pub fn execute(config: Config, script: &str, on_error: OnError) -> Result<Summary, DbError> {
+    let engine = Arc::new(open_engine(&config)?);
+    let mut session = engine.session();
+    Ok(script::run(
+        &engine,
+        &mut session,
+        script,
+        on_error,
+        |_, _, result| print_result(result),
+    ))
+}
+
+fn complete<'a>(
+    line: &str,
+    pos: usize,
+    names: impl Iterator<Item = &'a str>,
+) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(pos, |(i, _)| i);
+    let prefix = line[start..pos].to_lowercase();
+    if prefix.is_empty() {
+        return (pos, vec![]);
+    }
+    let upper = line[start..].starts_with(char::is_uppercase);
+    let keywords = parser::keywords().iter().map(|keyword| match upper {
+        true => keyword.to_uppercase(),
+        false => keyword.to_string(),
+    });
+    let mut candidates: Vec<String> = keywords
+        .chain(names.map(str::to_string))
+        .filter(|candidate| candidate.to_lowercase().starts_with(&prefix))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+fn readline_error(err: ReadlineError) -> DbError {
+    DbError::IO(err.to_string())
+}
+
+fn print_result(result: &Result<ExecResult, DbError>) {
+    match result {
+        Ok(ExecResult::Rows { columns, rows, .. }) => {
+            for column in columns {
+                print!("| {0: <10} ", column);
+            }
+            println!("|");
+            for row in rows {
+                for col in row {
+                    match col {
+                        Col::Int(value) => print!("| {0: <10} ", value),
+                        Col::BigInt(value) => print!("| {0: <10} ", value),
+                        Col::Varchar(value, _) => print!("| {0: <10} ", value),
+                    }
+                }
+                println!("|");
+            }
+        }
+        Ok(ExecResult::Affected {
+            op,
+            count,
+            warnings,
+        }) => {
+            println!("{} {}", op, count);
+            for warning in warnings {
+                println!("WARN: {}", warning);
+            }
+        }
+        Ok(ExecResult::Ack { op }) => println!("{}", op),
+        Err(err) => eprintln!("ERR [{}]: {}", err.code(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn execute_script() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = || {
+            Config::builder()
+                .path(temp_dir.path().to_path_buf())
+                .build()
+        };
+        let summary = execute(
+            config(),
+            "CREATE TABLE users(id INT);\nINSERT INTO users(id) VALUES(1);",
+            OnError::Stop,
+        )
+        .unwrap();
+        assert!(summary.is_success());
+        let summary = execute(
+            config(),
+            "INSERT INTO users(id) VALUES(2); INSERT INTO users(id) VALUES(1); \
+             INSERT INTO users(id) VALUES(3)",
+            OnError::Stop,
+        )
+        .unwrap();
+        assert_eq!("1 succeeded, 1 failed, 1 skipped", summary.to_string());
+        let engine = Engine::read_only(temp_dir.path()).unwrap();
+        let users = engine
+            .execute(parser::parse("SELECT id FROM users").unwrap())
+            .unwrap();
+        assert_eq!(&[vec![Col::int(1)], vec![Col::int(2)]], users.rows());
+    }
+
+    #[test]
+    fn completion() {
+        let names = ["users", "user_id", "name"];
+        let complete = |line: &str| {
+            let (start, candidates) = complete(line, line.len(), names.into_iter());
+            (start, candidates.join(" "))
+        };
+        assert_eq!((0, "SELECT".to_string()), complete("SEL"));
+        assert_eq!((0, "select".to_string()), complete("sel"));
+        assert_eq!(
+            (14, "use user_id users".to_string()),
+            complete("SELECT * FROM use")
+        );
+        assert_eq!(
+            (21, "OR ORDER".to_string()),
+            complete("SELECT id FROM users OR")
+        );
+        assert_eq!((9, String::new()), complete("SELECT * "));
+        assert_eq!(
+            (7, "name nextval not now null".to_string()),
+            complete("SELECT n")
+        );
+    }
+}
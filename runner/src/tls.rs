@@ -0,0 +1,43 @@
+use std::{fmt::Display, net::TcpStream, path::Path, sync::Arc};
+
+use common::error::DbError;
+use rustls::{
+    ServerConfig, ServerConnection, StreamOwned,
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+
+pub(crate) fn server_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>, DbError> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| pem_error(cert, err))?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|err| pem_error(key, err))?;
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(tls_error)?;
+    Ok(Arc::new(config))
+}
+
+pub(crate) fn accept(
+    stream: TcpStream,
+    mut buffered: &[u8],
+    config: Arc<ServerConfig>,
+) -> Result<StreamOwned<ServerConnection, TcpStream>, DbError> {
+    let mut connection = ServerConnection::new(config).map_err(tls_error)?;
+    while !buffered.is_empty() {
+        connection.read_tls(&mut buffered)?;
+    }
+    connection.process_new_packets().map_err(tls_error)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+fn pem_error(path: &Path, err: impl Display) -> DbError {
+    DbError::IO(format!("cannot read '{}': {}", path.display(), err))
+}
+
+fn tls_error(err: rustls::Error) -> DbError {
+    DbError::IO(err.to_string())
+}